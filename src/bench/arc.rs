@@ -1,32 +1,22 @@
 // ARC-AGI benchmark: multi-strategy solver pipeline.
 //
-// Strategy cascade (fastest → slowest):
+// Strategy cascade (fastest → slowest), run by synthesis::solver::Solver:
 // 0.  Smart/learned transforms (color map, tiling, subgrid, dedup)
 // 0b. Cellular Automaton rule learning
 // 0c. Grid partition + sub-grid operations (split/select/combine)
-// 0d. Object-centric operations (stamp patterns, bbox, markers)
+// 0d. Connect markers with lines
+// 0e. Object-centric operations (stamp patterns, bbox, markers)
 // 1.  Heuristic-filtered enumeration (1-step, 2-step compose)
 // 2.  Bidirectional DAG search (forward + backward with inverse prims)
 // 3.  DAG search with library (wake-sleep learned abstractions)
 // 4.  Full brute-force enumeration
 // 5.  Genetic evolution (crossover/mutation)
 //
-// Each strategy has a time/node budget. If one fails, cascade to next.
+// Each strategy has a time/node budget. If one fails, or its program gets
+// a test pair wrong, cascade to next.
 
-use std::time::Instant;
 use crate::perception::grid::ArcTask;
-use crate::synthesis::dsl::{Grid, Prim};
-use crate::synthesis::enumerate::synthesize;
-use crate::synthesis::evolve::evolve;
-use crate::synthesis::heuristics::{analyze_features, select_primitives};
-use crate::synthesis::bidir::BidirSearch;
-use crate::synthesis::abstraction::SearchDag;
-use crate::synthesis::compression::mdl_score;
-use crate::synthesis::smart_prims::try_smart_transforms;
-use crate::synthesis::cellular::try_ca_solve;
-use crate::synthesis::partition::try_partition_solve;
-use crate::synthesis::object_ops::try_object_solve;
-use crate::synthesis::connect::try_connect_solve;
+use crate::synthesis::solver::{Program, Solver, SolverConfig};
 
 const TASK_TIMEOUT_MS: u128 = 3_000;
 const COMPOSE_BUDGET: usize = 5_000;
@@ -42,207 +32,21 @@ pub struct ArcResult {
 }
 
 pub fn solve_arc_task(task: &ArcTask, max_size: usize) -> ArcResult {
-    let start = Instant::now();
-    let examples: Vec<(Grid, Grid)> = task.train.iter()
-        .map(|ex| (ex.input.clone(), ex.output.clone()))
-        .collect();
-
-    // --- Strategy 0: Smart/learned transforms (instant) ---
-    if let Some(smart) = try_smart_transforms(&examples) {
-        let test_ok = task.test.iter().all(|ex| smart.apply(&ex.input) == ex.output);
-        if test_ok {
-            return ArcResult {
-                task_id: task.id.clone(),
-                solved: true,
-                method: format!("smart_{}", smart.name()),
-                program_size: 1,
-                checked: 1,
-                mdl: 2.0,
-            };
-        }
-    }
-
-    // --- Strategy 0b: Cellular Automaton rule learning ---
-    if let Some(ca) = try_ca_solve(&examples, 3) {
-        let test_ok = task.test.iter().all(|ex| ca.apply(&ex.input) == ex.output);
-        if test_ok {
-            return ArcResult {
-                task_id: task.id.clone(),
-                solved: true,
-                method: format!("cellular_{}steps", ca.steps),
-                program_size: 1,
-                checked: 1,
-                mdl: 3.0,
-            };
-        }
-    }
-
-    // --- Strategy 0c: Grid partition operations ---
-    if let Some(psol) = try_partition_solve(&examples) {
-        let test_ok = task.test.iter().all(|ex| psol.apply(&ex.input) == ex.output);
-        if test_ok {
-            return ArcResult {
-                task_id: task.id.clone(),
-                solved: true,
-                method: format!("partition_{}", psol.method),
-                program_size: 2,
-                checked: 1,
-                mdl: 4.0,
-            };
-        }
-    }
-
-    // --- Strategy 0d: Connect markers with lines ---
-    if let Some(csol) = try_connect_solve(&examples) {
-        let test_ok = task.test.iter().all(|ex| csol.apply(&ex.input) == ex.output);
-        if test_ok {
-            return ArcResult {
-                task_id: task.id.clone(),
-                solved: true,
-                method: format!("connect_{}", csol.name()),
-                program_size: 2,
-                checked: 1,
-                mdl: 4.0,
-            };
-        }
-    }
-
-    // --- Strategy 0e: Object-centric operations ---
-    if let Some(osol) = try_object_solve(&examples) {
-        let test_ok = task.test.iter().all(|ex| osol.apply(&ex.input) == ex.output);
-        if test_ok {
-            return ArcResult {
-                task_id: task.id.clone(),
-                solved: true,
-                method: format!("object_{}", osol.name()),
-                program_size: 2,
-                checked: 1,
-                mdl: 4.0,
-            };
-        }
-    }
-
-    // --- Strategy 1: Heuristic-filtered enumeration ---
-    let profile = analyze_features(&examples);
-    let heuristic_prims = select_primitives(&profile);
-
-    // 1a. Single-step
-    for p in &heuristic_prims {
-        if matches_all(p, &examples) && validates(p, task) {
-            let mdl = mdl_score(p, &examples);
-            return ArcResult {
-                task_id: task.id.clone(),
-                solved: true,
-                method: "heuristic_single".into(),
-                program_size: p.size(),
-                checked: heuristic_prims.len(),
-                mdl,
-            };
-        }
-    }
-
-    // 1b. 2-step compositions
-    let mut checked = heuristic_prims.len();
-    'compose: for a in &heuristic_prims {
-        for b in &heuristic_prims {
-            checked += 1;
-            let composed = Prim::Compose(Box::new(a.clone()), Box::new(b.clone()));
-            if matches_all(&composed, &examples) && validates(&composed, task) {
-                let mdl = mdl_score(&composed, &examples);
-                return ArcResult {
-                    task_id: task.id.clone(),
-                    solved: true,
-                    method: "heuristic_compose2".into(),
-                    program_size: composed.size(),
-                    checked,
-                    mdl,
-                };
-            }
-            if start.elapsed().as_millis() > TASK_TIMEOUT_MS { break 'compose; }
-        }
-    }
-
-    if start.elapsed().as_millis() > TASK_TIMEOUT_MS {
-        return unsolved(task, checked);
-    }
-
-    // --- Strategy 2: Bidirectional search ---
-    let bidir = BidirSearch::new(5_000);
-    if let Some(result) = bidir.search_all(&examples, &heuristic_prims, 3) {
-        if validates(&result.program, task) {
-            let mdl = mdl_score(&result.program, &examples);
-            return ArcResult {
-                task_id: task.id.clone(),
-                solved: true,
-                method: format!("bidir_{}f_{}b", result.forward_depth, result.backward_depth),
-                program_size: result.program.size(),
-                checked: checked + result.nodes_explored,
-                mdl,
-            };
-        }
+    let config = SolverConfig::default()
+        .with_timeout_ms(TASK_TIMEOUT_MS)
+        .with_max_size(max_size.min(2));
+    let validates = |program: &Program| task.test.iter().all(|ex| program.apply(&ex.input) == ex.output);
+    match Solver::new(config).solve_with(&task.train_pairs(), validates) {
+        Ok(solved) => ArcResult {
+            task_id: task.id.clone(),
+            solved: true,
+            method: solved.method,
+            program_size: solved.program_size,
+            checked: solved.checked,
+            mdl: solved.mdl,
+        },
+        Err(failed) => unsolved(task, failed.checked),
     }
-
-    if start.elapsed().as_millis() > TASK_TIMEOUT_MS {
-        return unsolved(task, checked);
-    }
-
-    // --- Strategy 3: DAG search ---
-    let mut dag = SearchDag::new(20_000);
-    if let Some(first_ex) = examples.first() {
-        if let Some(prog) = dag.search(&first_ex.0, &first_ex.1, &heuristic_prims, 3) {
-            if matches_all(&prog, &examples) && validates(&prog, task) {
-                let mdl = mdl_score(&prog, &examples);
-                return ArcResult {
-                    task_id: task.id.clone(),
-                    solved: true,
-                    method: "dag_search".into(),
-                    program_size: prog.size(),
-                    checked: checked + dag.nodes_explored(),
-                    mdl,
-                };
-            }
-        }
-    }
-
-    if start.elapsed().as_millis() > TASK_TIMEOUT_MS {
-        return unsolved(task, checked);
-    }
-
-    // --- Strategy 4: Full brute-force enumeration ---
-    if let Some(result) = synthesize(&examples, max_size.min(2)) {
-        if validates(&result.program, task) {
-            let mdl = mdl_score(&result.program, &examples);
-            return ArcResult {
-                task_id: task.id.clone(),
-                solved: true,
-                method: "enumerate".into(),
-                program_size: result.size,
-                checked: checked + result.checked,
-                mdl,
-            };
-        }
-    }
-
-    if start.elapsed().as_millis() > TASK_TIMEOUT_MS {
-        return unsolved(task, checked);
-    }
-
-    // --- Strategy 5: Genetic evolution ---
-    if let Some(individual) = evolve(&examples, 30, 50) {
-        if validates(&individual.program, task) {
-            let mdl = mdl_score(&individual.program, &examples);
-            return ArcResult {
-                task_id: task.id.clone(),
-                solved: true,
-                method: "evolution".into(),
-                program_size: individual.program.size(),
-                checked: checked + 1500,
-                mdl,
-            };
-        }
-    }
-
-    unsolved(task, checked)
 }
 
 fn unsolved(task: &ArcTask, checked: usize) -> ArcResult {
@@ -285,15 +89,3 @@ pub struct ArcBenchmarkResult {
     pub avg_mdl: f64,
     pub results: Vec<ArcResult>,
 }
-
-fn matches_all(program: &Prim, examples: &[(Grid, Grid)]) -> bool {
-    examples.iter().all(|(input, expected)| {
-        program.apply(input) == *expected
-    })
-}
-
-fn validates(program: &Prim, task: &ArcTask) -> bool {
-    task.test.iter().all(|ex| {
-        program.apply(&ex.input) == ex.output
-    })
-}
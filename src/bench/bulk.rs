@@ -0,0 +1,115 @@
+// Bulk-load benchmark: add_nodes_bulk / add_edges_bulk against one
+// add_node / add_edge call per fact.
+//
+// Both sides load the same synthetic facts (edges / 4 nodes over 16
+// labels, 8 relations, pseudo-random endpoints) into an empty graph.
+//
+//   let report = bench_bulk(1_000_000);
+//   println!("{}", report.summary());
+
+use std::time::Instant;
+use crate::memory::graph::{KnowledgeGraph, NodeId};
+
+#[derive(Debug, Clone)]
+pub struct BulkReport {
+    pub edges: usize,
+    pub incremental_ms: u64,
+    pub bulk_ms: u64,
+}
+
+// Edges loaded per second
+fn throughput(ms: u64, edges: usize) -> f64 {
+    edges as f64 * 1000.0 / ms.max(1) as f64
+}
+
+impl BulkReport {
+    pub fn summary(&self) -> String {
+        format!(
+            "{} edges: incremental {} ms ({:.0} edges/s) | bulk {} ms ({:.0} edges/s)",
+            self.edges,
+            self.incremental_ms, throughput(self.incremental_ms, self.edges),
+            self.bulk_ms, throughput(self.bulk_ms, self.edges),
+        )
+    }
+}
+
+// (label per node, (source index, relation, target index) per edge)
+pub fn synthetic_facts(edges: usize) -> (Vec<u32>, Vec<(usize, u32, usize)>) {
+    let nodes = (edges / 4).max(1);
+    let labels = (0..nodes).map(|i| (i % 16) as u32).collect();
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    let triples = (0..edges).map(|i| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state as usize % nodes, 100 + (i % 8) as u32, (state >> 32) as usize % nodes)
+    }).collect();
+    (labels, triples)
+}
+
+pub fn load_incremental(labels: &[u32], triples: &[(usize, u32, usize)]) -> KnowledgeGraph {
+    let mut g = KnowledgeGraph::new();
+    let ids: Vec<NodeId> = labels.iter().map(|&l| g.add_node(l)).collect();
+    for &(s, r, t) in triples {
+        g.add_edge(ids[s], r, ids[t]);
+    }
+    g
+}
+
+pub fn load_bulk(labels: &[u32], triples: &[(usize, u32, usize)]) -> KnowledgeGraph {
+    let mut g = KnowledgeGraph::new();
+    let first = g.add_nodes_bulk(labels.iter().copied()).start;
+    g.add_edges_bulk(triples.iter().map(|&(s, r, t)| (first + s as NodeId, r, first + t as NodeId)));
+    g
+}
+
+pub fn bench_bulk(edges: usize) -> BulkReport {
+    let (labels, triples) = synthetic_facts(edges);
+
+    let start = Instant::now();
+    let incremental = load_incremental(&labels, &triples);
+    let incremental_ms = start.elapsed().as_millis() as u64;
+    let start = Instant::now();
+    let bulk = load_bulk(&labels, &triples);
+    let bulk_ms = start.elapsed().as_millis() as u64;
+    assert_eq!(bulk.edge_count(), incremental.edge_count());
+
+    BulkReport { edges, incremental_ms, bulk_ms }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bulk_load_builds_the_same_indexes() {
+        let (labels, triples) = synthetic_facts(2_000);
+        let a = load_incremental(&labels, &triples);
+        let b = load_bulk(&labels, &triples);
+        assert_eq!((a.node_count(), a.edge_count()), (b.node_count(), b.edge_count()));
+        for id in 1..=labels.len() as NodeId {
+            let ids = |g: &KnowledgeGraph| (g.outgoing_edges(id).iter().map(|e| e.id).collect::<Vec<_>>(), g.incoming_edges(id).iter().map(|e| e.id).collect::<Vec<_>>());
+            assert_eq!(ids(&a), ids(&b));
+        }
+        for sym in 0..108 {
+            assert_eq!(a.nodes_by_label(sym), b.nodes_by_label(sym));
+            assert_eq!(a.edges_by_relation(sym), b.edges_by_relation(sym));
+        }
+
+        let mut g = KnowledgeGraph::new().with_history();
+        let nodes = g.add_nodes_bulk([1, 2, 1]);
+        g.add_edges_bulk([(nodes.start, 7, nodes.start + 1)]);
+        assert_eq!(g.nodes_by_label(1), vec![1, 3]);
+        g.undo(1);
+        assert_eq!((g.node_count(), g.edge_count()), (3, 0));
+    }
+
+    // cargo test --release bench::bulk -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn bulk_beats_incremental_on_a_million_edges() {
+        let report = bench_bulk(1_000_000);
+        println!("{}", report.summary());
+        assert!(report.bulk_ms < report.incremental_ms);
+    }
+}
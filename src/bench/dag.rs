@@ -0,0 +1,128 @@
+// DAG search benchmark: flat Grid against the nested Vec<Vec<u8>> rows it
+// replaced.
+//
+// Runs SearchDag over all primitives on synthetic pairs (sparse random
+// inputs, outputs through FlipH then GravityDown) and times cloning the same
+// grids in both layouts, the copy every search node pays.
+//
+// The search itself no longer exists for nested rows, so it is compared with
+// a baseline: the same bench_dag(12, 3) search timed in release mode on the
+// tree just before the switch to Grid. The median of nine runs there was
+// 790 ms, against 450 ms over ten runs with Grid on the same machine, about
+// a 1.75x speedup; single runs varied by a quarter either way. Only that
+// configuration has a baseline, and the ratio only means something on a
+// machine like the one that recorded it.
+//
+//   let report = bench_dag(12, 3);
+//   println!("{}", report.summary());
+
+use std::time::Instant;
+use crate::synthesis::abstraction::SearchDag;
+use crate::synthesis::dsl::{Grid, Prim};
+
+// Clones per layout when timing grid copies
+const CLONES: usize = 100_000;
+
+// (side, runs, milliseconds) of the nested-rows search baseline
+const NESTED_SEARCH_BASELINE: (usize, usize, u64) = (12, 3, 790);
+
+#[derive(Debug, Clone)]
+pub struct DagReport {
+    pub side: usize,
+    pub runs: usize,
+    pub nodes: usize,
+    pub solved: bool,
+    pub search_ms: u64,
+    // Recorded search time with nested rows, for the configuration that has one
+    pub nested_search_ms: Option<u64>,
+    pub flat_clone_ms: u64,
+    pub nested_clone_ms: u64,
+}
+
+impl DagReport {
+    // Nested-rows baseline time over this run's search time
+    pub fn search_speedup(&self) -> Option<f64> {
+        self.nested_search_ms.map(|nested| nested as f64 / self.search_ms.max(1) as f64)
+    }
+
+    pub fn summary(&self) -> String {
+        let baseline = match (self.nested_search_ms, self.search_speedup()) {
+            (Some(ms), Some(speedup)) => format!(" vs nested baseline {} ms ({:.2}x)", ms, speedup),
+            _ => String::new(),
+        };
+        format!(
+            "{}x{} grids, {} runs: {} nodes in {} ms{} (solved: {}) | {} clones: flat {} ms, nested {} ms",
+            self.side, self.side, self.runs, self.nodes, self.search_ms, baseline, self.solved,
+            CLONES, self.flat_clone_ms, self.nested_clone_ms,
+        )
+    }
+}
+
+// Three (input, FlipH then GravityDown of input) pairs of side x side grids
+pub fn synthetic_pairs(side: usize) -> Vec<(Grid, Grid)> {
+    let target = Prim::Compose(Box::new(Prim::FlipH), Box::new(Prim::GravityDown));
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    (0..3).map(|_| {
+        let input: Grid = (0..side).map(|_| (0..side).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            if state & 3 == 0 { (state % 9 + 1) as u8 } else { 0 }
+        }).collect()).collect();
+        let output = target.apply(&input);
+        (input, output)
+    }).collect()
+}
+
+pub fn bench_dag(side: usize, runs: usize) -> DagReport {
+    let pairs = synthetic_pairs(side);
+    let prims = Prim::all_primitives();
+
+    let start = Instant::now();
+    let mut nodes = 0;
+    let mut solved = true;
+    for _ in 0..runs {
+        let mut dag = SearchDag::new(20_000);
+        solved &= dag.search_all(&pairs, &prims, 2).is_some();
+        nodes += dag.nodes_explored();
+    }
+    let search_ms = start.elapsed().as_millis() as u64;
+
+    let flat = &pairs[0].0;
+    let start = Instant::now();
+    let copies: usize = (0..CLONES).map(|_| std::hint::black_box(flat.clone()).len()).sum();
+    let flat_clone_ms = start.elapsed().as_millis() as u64;
+    let nested = flat.to_rows();
+    let start = Instant::now();
+    let nested_copies: usize = (0..CLONES).map(|_| std::hint::black_box(nested.clone()).len()).sum();
+    let nested_clone_ms = start.elapsed().as_millis() as u64;
+    assert_eq!(copies, nested_copies);
+
+    let (base_side, base_runs, base_ms) = NESTED_SEARCH_BASELINE;
+    let nested_search_ms = (side == base_side && runs == base_runs).then_some(base_ms);
+    DagReport { side, runs, nodes, solved, search_ms, nested_search_ms, flat_clone_ms, nested_clone_ms }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dag_search_solves_synthetic_pairs() {
+        let pairs = synthetic_pairs(5);
+        let mut dag = SearchDag::new(5_000);
+        let program = dag.search_all(&pairs, &Prim::all_primitives(), 2).unwrap();
+        assert!(pairs.iter().all(|(input, output)| program.apply(input) == *output));
+    }
+
+    // cargo test --release bench::dag -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn flat_grids_clone_and_search_faster() {
+        let report = bench_dag(12, 3);
+        println!("{}", report.summary());
+        assert!(report.solved);
+        assert!(report.flat_clone_ms <= report.nested_clone_ms, "{}", report.summary());
+        assert!(report.search_speedup().is_some());
+    }
+}
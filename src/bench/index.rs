@@ -0,0 +1,101 @@
+// Fact index benchmark: TermIndex candidates against unifying every fact.
+//
+// Both sides answer the same bound-first-argument queries over synthetic
+// edge(I, J) facts (pseudo-random targets), counting the facts that unify.
+//
+//   let report = bench_index(100_000, 100);
+//   println!("{}", report.summary());
+
+use std::time::Instant;
+use crate::core::{Term, Sym};
+use crate::reasoning::index::TermIndex;
+use crate::reasoning::unifier::{unify, Substitution};
+
+const EDGE: Sym = 0;
+const OUT: Sym = 1;
+
+#[derive(Debug, Clone)]
+pub struct IndexReport {
+    pub facts: usize,
+    pub queries: usize,
+    pub answers: usize,
+    pub build_ms: u64,
+    pub indexed_ms: u64,
+    pub linear_ms: u64,
+}
+
+impl IndexReport {
+    pub fn summary(&self) -> String {
+        format!(
+            "{} facts, {} queries ({} answers): index built in {} ms | indexed {} ms, linear {} ms",
+            self.facts, self.queries, self.answers, self.build_ms, self.indexed_ms, self.linear_ms,
+        )
+    }
+}
+
+// edge(i, target) for i in 0..facts
+pub fn synthetic_facts(facts: usize) -> Vec<Term> {
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    (0..facts).map(|i| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        Term::compound(EDGE, vec![Term::int(i as i64), Term::int((state % facts as u64) as i64)])
+    }).collect()
+}
+
+// edge(i, Out) for evenly spread sources i
+pub fn synthetic_queries(facts: usize, queries: usize) -> Vec<Term> {
+    let step = (facts / queries.max(1)).max(1);
+    (0..queries)
+        .map(|q| Term::compound(EDGE, vec![Term::int((q * step % facts.max(1)) as i64), Term::var(OUT)]))
+        .collect()
+}
+
+fn unifies(fact: &Term, query: &Term) -> bool {
+    unify(fact, query, &Substitution::new()).is_ok()
+}
+
+pub fn bench_index(facts: usize, queries: usize) -> IndexReport {
+    let terms = synthetic_facts(facts);
+    let goals = synthetic_queries(facts, queries);
+
+    let start = Instant::now();
+    let index = TermIndex::from_terms(&terms);
+    let build_ms = start.elapsed().as_millis() as u64;
+
+    let start = Instant::now();
+    let answers: usize = goals.iter()
+        .map(|g| index.candidates(g).into_iter().filter(|&i| unifies(&terms[i], g)).count())
+        .sum();
+    let indexed_ms = start.elapsed().as_millis() as u64;
+
+    let start = Instant::now();
+    let linear: usize = goals.iter()
+        .map(|g| terms.iter().filter(|t| unifies(t, g)).count())
+        .sum();
+    let linear_ms = start.elapsed().as_millis() as u64;
+    assert_eq!(answers, linear);
+
+    IndexReport { facts, queries, answers, build_ms, indexed_ms, linear_ms }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indexed_and_linear_lookups_agree() {
+        let report = bench_index(2_000, 50);
+        assert_eq!(report.answers, 50);
+    }
+
+    // cargo test --release bench::index -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn index_beats_linear_scan_on_100k_facts() {
+        let report = bench_index(100_000, 100);
+        println!("{}", report.summary());
+        assert!(report.indexed_ms < report.linear_ms, "{}", report.summary());
+    }
+}
@@ -1,2 +1,6 @@
 pub mod arc;
 pub mod runner;
+pub mod snapshot;
+pub mod bulk;
+pub mod dag;
+pub mod index;
@@ -0,0 +1,100 @@
+// Graph snapshot benchmark: binary format against serde_json.
+//
+// Builds a synthetic graph (edges / 4 nodes, a few relations, an integer
+// attribute on every tenth edge) and times saving and loading it both ways.
+//
+//   let report = bench_snapshot(1_000_000);
+//   println!("{}", report.summary());
+
+use std::time::Instant;
+use crate::core::Term;
+use crate::memory::graph::KnowledgeGraph;
+
+#[derive(Debug, Clone)]
+pub struct SnapshotReport {
+    pub edges: usize,
+    pub binary_bytes: usize,
+    pub json_bytes: usize,
+    pub binary_save_ms: u64,
+    pub binary_load_ms: u64,
+    pub json_save_ms: u64,
+    pub json_load_ms: u64,
+}
+
+impl SnapshotReport {
+    pub fn summary(&self) -> String {
+        format!(
+            "{} edges: binary {} B, save {} ms, load {} ms | json {} B, save {} ms, load {} ms",
+            self.edges, self.binary_bytes, self.binary_save_ms, self.binary_load_ms,
+            self.json_bytes, self.json_save_ms, self.json_load_ms,
+        )
+    }
+}
+
+pub fn synthetic_graph(edges: usize) -> KnowledgeGraph {
+    let mut g = KnowledgeGraph::new();
+    let nodes: Vec<u32> = (0..(edges / 4).max(1)).map(|i| g.add_node((i % 16) as u32)).collect();
+    let mut state: u64 = 0x9E37_79B9_7F4A_7C15;
+    for i in 0..edges {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        let (a, b) = (nodes[state as usize % nodes.len()], nodes[(state >> 32) as usize % nodes.len()]);
+        if i % 10 == 0 {
+            g.add_edge_with_attrs(a, 100 + (i % 8) as u32, b, vec![(200, Term::Int(i as i64))]);
+        } else {
+            g.add_edge(a, 100 + (i % 8) as u32, b);
+        }
+    }
+    g
+}
+
+pub fn bench_snapshot(edges: usize) -> SnapshotReport {
+    let graph = synthetic_graph(edges);
+
+    let start = Instant::now();
+    let binary = graph.to_binary(None);
+    let binary_save_ms = start.elapsed().as_millis() as u64;
+    let start = Instant::now();
+    let loaded = KnowledgeGraph::from_binary(&binary).map(|(g, _)| g.edge_count());
+    let binary_load_ms = start.elapsed().as_millis() as u64;
+    assert_eq!(loaded, Some(graph.edge_count()));
+
+    let start = Instant::now();
+    let json = graph.save_json();
+    let json_save_ms = start.elapsed().as_millis() as u64;
+    let start = Instant::now();
+    let loaded = KnowledgeGraph::load_json(&json).map(|g| g.edge_count());
+    let json_load_ms = start.elapsed().as_millis() as u64;
+    assert_eq!(loaded, Some(graph.edge_count()));
+
+    SnapshotReport {
+        edges,
+        binary_bytes: binary.len(),
+        json_bytes: json.len(),
+        binary_save_ms,
+        binary_load_ms,
+        json_save_ms,
+        json_load_ms,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_is_smaller() {
+        let report = bench_snapshot(2_000);
+        assert!(report.binary_bytes * 2 < report.json_bytes, "{}", report.summary());
+    }
+
+    // cargo test --release bench::snapshot -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn binary_beats_json_on_a_million_edges() {
+        let report = bench_snapshot(1_000_000);
+        println!("{}", report.summary());
+        assert!(report.binary_save_ms + report.binary_load_ms < report.json_save_ms + report.json_load_ms);
+    }
+}
@@ -10,6 +10,8 @@ pub enum KolossError {
     SynthesisFail(String),
     MemoryFull,
     InvalidTerm(String),
+    Parse(String),
+    Schema(String),
 }
 
 impl fmt::Display for KolossError {
@@ -23,6 +25,8 @@ impl fmt::Display for KolossError {
             Self::SynthesisFail(msg) => write!(f, "synthesis failed: {}", msg),
             Self::MemoryFull => write!(f, "memory full"),
             Self::InvalidTerm(msg) => write!(f, "invalid term: {}", msg),
+            Self::Parse(msg) => write!(f, "parse error: {}", msg),
+            Self::Schema(msg) => write!(f, "schema violation: {}", msg),
         }
     }
 }
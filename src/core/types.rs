@@ -2,6 +2,11 @@ use std::fmt;
 
 pub type Sym = u32;
 
+// Reserved functor of a list cell `[H|T]`. Lists are stored flat
+// (Term::List); a cons cell only appears while its tail is unbound and is
+// folded back into a flat list once the tail is known (see Substitution::apply).
+pub const CONS: Sym = Sym::MAX;
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
 pub enum Term {
     Var(Sym),
@@ -12,6 +17,8 @@ pub enum Term {
     Bool(bool),
     Compound(Sym, Vec<Term>),
     List(Vec<Term>),
+    // Record of key -> value pairs, sorted by key, keys unique (see Term::dict)
+    Dict(Vec<(Sym, Term)>),
     Nil,
 }
 
@@ -58,12 +65,60 @@ impl Term {
         Term::List(items)
     }
 
+    // Sorts the pairs by key; on duplicate keys the last value wins
+    pub fn dict(pairs: Vec<(Sym, Term)>) -> Self {
+        let mut out: Vec<(Sym, Term)> = Vec::with_capacity(pairs.len());
+        for (k, v) in pairs {
+            match out.binary_search_by_key(&k, |(key, _)| *key) {
+                Ok(i) => out[i].1 = v,
+                Err(i) => out.insert(i, (k, v)),
+            }
+        }
+        Term::Dict(out)
+    }
+
+    // Value under `key` if self is a dict holding it
+    pub fn dict_get(&self, key: Sym) -> Option<&Term> {
+        match self {
+            Term::Dict(pairs) => pairs.binary_search_by_key(&key, |(k, _)| *k).ok().map(|i| &pairs[i].1),
+            _ => None,
+        }
+    }
+
+    // Copy of the dict with `key` set to `value`; None if self is not a dict
+    pub fn dict_put(&self, key: Sym, value: Term) -> Option<Term> {
+        match self {
+            Term::Dict(pairs) => {
+                let mut pairs = pairs.clone();
+                match pairs.binary_search_by_key(&key, |(k, _)| *k) {
+                    Ok(i) => pairs[i].1 = value,
+                    Err(i) => pairs.insert(i, (key, value)),
+                }
+                Some(Term::Dict(pairs))
+            }
+            _ => None,
+        }
+    }
+
+    // [head | tail]
+    pub fn cons(head: Term, tail: Term) -> Self {
+        match tail {
+            Term::List(mut items) => {
+                items.insert(0, head);
+                Term::List(items)
+            }
+            Term::Nil => Term::List(vec![head]),
+            tail => Term::Compound(CONS, vec![head, tail]),
+        }
+    }
+
     pub fn is_ground(&self) -> bool {
         match self {
             Term::Var(_) => false,
             Term::Atom(_) | Term::Int(_) | Term::Float(_) | Term::Str(_)
             | Term::Bool(_) | Term::Nil => true,
             Term::Compound(_, args) | Term::List(args) => args.iter().all(|a| a.is_ground()),
+            Term::Dict(pairs) => pairs.iter().all(|(_, v)| v.is_ground()),
         }
     }
 
@@ -85,6 +140,11 @@ impl Term {
                     a.collect_vars(out);
                 }
             }
+            Term::Dict(pairs) => {
+                for (_, v) in pairs {
+                    v.collect_vars(out);
+                }
+            }
             _ => {}
         }
     }
@@ -98,6 +158,9 @@ impl Term {
             Term::List(items) => {
                 Term::List(items.iter().map(|a| a.substitute(var, replacement)).collect())
             }
+            Term::Dict(pairs) => {
+                Term::Dict(pairs.iter().map(|(k, v)| (*k, v.substitute(var, replacement))).collect())
+            }
             other => other.clone(),
         }
     }
@@ -107,6 +170,7 @@ impl Term {
             Term::Compound(_, args) | Term::List(args) => {
                 1 + args.iter().map(|a| a.size()).sum::<usize>()
             }
+            Term::Dict(pairs) => 1 + pairs.iter().map(|(_, v)| v.size()).sum::<usize>(),
             _ => 1,
         }
     }
@@ -147,6 +211,137 @@ impl SymbolTable {
     }
 }
 
+// User-facing variable names (X, Who, ...) for the numeric variables of a query
+#[derive(Debug, Clone, Default)]
+pub struct VarNames {
+    names: Vec<(Sym, Box<str>)>,
+    next: Sym,
+}
+
+impl VarNames {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Variable for `name`, allocating a fresh id the first time it is seen
+    pub fn var(&mut self, name: &str) -> Term {
+        if let Some(id) = self.id_of(name) {
+            return Term::Var(id);
+        }
+        while self.name_of(self.next).is_some() {
+            self.next += 1;
+        }
+        let id = self.next;
+        self.next += 1;
+        self.names.push((id, name.into()));
+        Term::Var(id)
+    }
+
+    pub fn insert(&mut self, var: Sym, name: &str) {
+        self.names.retain(|(v, _)| *v != var);
+        self.names.push((var, name.into()));
+    }
+
+    pub fn name_of(&self, var: Sym) -> Option<&str> {
+        self.names.iter().find(|(v, _)| *v == var).map(|(_, n)| &**n)
+    }
+
+    pub fn id_of(&self, name: &str) -> Option<Sym> {
+        self.names.iter().find(|(_, n)| &**n == name).map(|(v, _)| *v)
+    }
+
+    // (var, name) pairs in declaration order
+    pub fn iter(&self) -> impl Iterator<Item = (Sym, &str)> {
+        self.names.iter().map(|(v, n)| (*v, &**n))
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+// Display adapter resolving symbols (and optionally variable names)
+pub struct TermDisplay<'a> {
+    term: &'a Term,
+    syms: &'a SymbolTable,
+    vars: Option<&'a VarNames>,
+}
+
+impl Term {
+    pub fn display<'a>(&'a self, syms: &'a SymbolTable) -> TermDisplay<'a> {
+        TermDisplay { term: self, syms, vars: None }
+    }
+
+    pub fn display_named<'a>(&'a self, syms: &'a SymbolTable, vars: &'a VarNames) -> TermDisplay<'a> {
+        TermDisplay { term: self, syms, vars: Some(vars) }
+    }
+}
+
+impl TermDisplay<'_> {
+    fn child<'b>(&'b self, term: &'b Term) -> TermDisplay<'b> {
+        TermDisplay { term, syms: self.syms, vars: self.vars }
+    }
+
+    fn sym(&self, f: &mut fmt::Formatter<'_>, id: Sym) -> fmt::Result {
+        match self.syms.resolve(id) {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, ":{}", id),
+        }
+    }
+}
+
+impl fmt::Display for TermDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.term {
+            Term::Var(v) => match self.vars.and_then(|n| n.name_of(*v)) {
+                Some(name) => write!(f, "{}", name),
+                None => write!(f, "_G{}", v),
+            },
+            Term::Atom(a) => self.sym(f, *a),
+            Term::Compound(CONS, args) if args.len() == 2 => {
+                write!(f, "[{}|{}]", self.child(&args[0]), self.child(&args[1]))
+            }
+            Term::Compound(func, args) => {
+                self.sym(f, *func)?;
+                write!(f, "(")?;
+                for (i, a) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", self.child(a))?;
+                }
+                write!(f, ")")
+            }
+            Term::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", self.child(item))?;
+                }
+                write!(f, "]")
+            }
+            Term::Dict(pairs) => {
+                write!(f, "_{{")?;
+                for (i, (k, v)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    self.sym(f, *k)?;
+                    write!(f, ": {}", self.child(v))?;
+                }
+                write!(f, "}}")
+            }
+            other => write!(f, "{}", other),
+        }
+    }
+}
+
 impl fmt::Display for Term {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -157,6 +352,7 @@ impl fmt::Display for Term {
             Term::Str(s) => write!(f, "\"{}\"", s),
             Term::Bool(b) => write!(f, "{}", b),
             Term::Nil => write!(f, "nil"),
+            Term::Compound(CONS, args) if args.len() == 2 => write!(f, "[{}|{}]", args[0], args[1]),
             Term::Compound(func, args) => {
                 write!(f, "{}(", func)?;
                 for (i, a) in args.iter().enumerate() {
@@ -177,6 +373,16 @@ impl fmt::Display for Term {
                 }
                 write!(f, "]")
             }
+            Term::Dict(pairs) => {
+                write!(f, "_{{")?;
+                for (i, (k, v)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, ":{}: {}", k, v)?;
+                }
+                write!(f, "}}")
+            }
         }
     }
 }
@@ -4,7 +4,7 @@ use koloss_v2::reasoning::solver::{SatProblem, SatResult};
 use koloss_v2::reasoning::rules::{Rule, RuleEngine};
 use koloss_v2::reasoning::builtins;
 use koloss_v2::memory::graph::KnowledgeGraph;
-use koloss_v2::synthesis::dsl::Prim;
+use koloss_v2::synthesis::dsl::{Grid, Prim};
 
 fn main() {
     println!("KOLOSS v2 — Autonomous Reasoning Engine");
@@ -117,23 +117,14 @@ fn demo_rules() {
 fn demo_builtins() {
     println!("\n--- Built-in Predicates ---");
     let mut syms = SymbolTable::new();
-    let mut engine = RuleEngine::new();
+    let mut engine = RuleEngine::new().with_standard_builtins(&mut syms);
 
-    // Register builtins
     let is_sym = syms.intern("is");
     let gt_sym = syms.intern(">");
-    let lt_sym = syms.intern("<");
     let plus_sym = syms.intern("+");
     let mul_sym = syms.intern("*");
     let between_sym = syms.intern("between");
 
-    engine.builtins_mut().register(builtins::BUILTIN_IS, is_sym);
-    engine.builtins_mut().register(builtins::BUILTIN_GT, gt_sym);
-    engine.builtins_mut().register(builtins::BUILTIN_LT, lt_sym);
-    engine.builtins_mut().register(builtins::BUILTIN_PLUS, plus_sym);
-    engine.builtins_mut().register(builtins::BUILTIN_MUL, mul_sym);
-    engine.builtins_mut().register(builtins::BUILTIN_BETWEEN, between_sym);
-
     // is(X, 3 + 4 * 2) => X = 11
     let expr = Term::compound(plus_sym, vec![
         Term::int(3),
@@ -308,13 +299,13 @@ fn demo_arc_dsl() {
     use koloss_v2::synthesis::dsl::{connected_components, count_objects, is_above, is_symmetric_h,
         detect_period_h, overlay_grids, unique_colors};
 
-    let grid = vec![
+    let grid = Grid::from(vec![
         vec![0, 1, 0, 0, 2, 0],
         vec![0, 1, 0, 0, 2, 0],
         vec![0, 0, 0, 0, 0, 0],
         vec![0, 0, 3, 3, 0, 0],
         vec![0, 0, 3, 3, 0, 0],
-    ];
+    ]);
 
     println!("  input: {}x{}", grid.len(), grid[0].len());
     println!("  colors: {:?}", unique_colors(&grid));
@@ -335,16 +326,16 @@ fn demo_arc_dsl() {
     println!("  flood_fill(0,0,5): {} cells filled", fill_count);
 
     // Symmetry
-    let sym_grid = vec![vec![1, 0, 1], vec![0, 1, 0], vec![1, 0, 1]];
+    let sym_grid = Grid::from(vec![vec![1, 0, 1], vec![0, 1, 0], vec![1, 0, 1]]);
     println!("  symmetric_h([[1,0,1],...]): {}", is_symmetric_h(&sym_grid));
 
     // Pattern repetition
-    let rep_grid = vec![vec![1, 2, 1, 2, 1, 2]];
+    let rep_grid = Grid::from(vec![vec![1, 2, 1, 2, 1, 2]]);
     println!("  period_h([[1,2,1,2,1,2]]): {:?}", detect_period_h(&rep_grid));
 
     // Overlay
-    let base = vec![vec![1, 1], vec![1, 1]];
-    let top = vec![vec![0, 2], vec![2, 0]];
+    let base = Grid::from(vec![vec![1, 1], vec![1, 1]]);
+    let top = Grid::from(vec![vec![0, 2], vec![2, 0]]);
     let merged = overlay_grids(&base, &top);
     println!("  overlay: {:?}", merged);
 
@@ -354,11 +345,11 @@ fn demo_arc_dsl() {
     println!("  keep_largest: {} cells", largest_count);
 
     // Fill inside
-    let hollow = vec![
+    let hollow = Grid::from(vec![
         vec![1, 1, 1],
         vec![1, 0, 1],
         vec![1, 1, 1],
-    ];
+    ]);
     let filled_inside = Prim::FillInsideObjects(2).apply(&hollow);
     println!("  fill_inside hollow square: center={}", filled_inside[1][1]);
 
@@ -374,8 +365,8 @@ fn demo_new_synthesis() {
     use koloss_v2::synthesis::fingerprint::GridFingerprint;
 
     // Demo: heuristic selection
-    let input = vec![vec![1, 2, 3], vec![4, 5, 6]];
-    let output = vec![vec![3, 2, 1], vec![6, 5, 4]]; // FlipH
+    let input = Grid::from(vec![vec![1, 2, 3], vec![4, 5, 6]]);
+    let output = Grid::from(vec![vec![3, 2, 1], vec![6, 5, 4]]); // FlipH
     let profile = analyze_features(&[(input.clone(), output.clone())]);
     let selected = select_primitives(&profile);
     let all = Prim::all_primitives();
@@ -392,7 +383,7 @@ fn demo_new_synthesis() {
     }
 
     // Demo: compression metrics
-    let grid = vec![vec![1, 1, 1, 2, 2, 2], vec![1, 1, 1, 2, 2, 2]];
+    let grid = Grid::from(vec![vec![1, 1, 1, 2, 2, 2], vec![1, 1, 1, 2, 2, 2]]);
     println!("  grid entropy: {:.2} bits/cell", grid_entropy(&grid));
     println!("  compression ratio: {:.2}", compression_ratio(&grid));
     println!("  DL(FlipH): {:.1} bits, DL(FlipH∘RotCW): {:.1} bits",
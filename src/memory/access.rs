@@ -0,0 +1,106 @@
+// Access tracking on reads.
+//
+// node() only borrows the graph, so it cannot touch the node the way
+// node_mut does. With read tracking enabled it queues the read instead
+// (a count per node behind a mutex, so shared readers on several threads
+// can record reads), and the queue is applied as touches before decay:
+// each read moves last_access to the current tick, counts one access and
+// boosts the weight, like touch_node. Frequently read knowledge is
+// therefore not pruned as stale.
+//
+//   let mut graph = KnowledgeGraph::new().with_read_tracking();
+//   ... queries ...
+//   graph.apply_decay();         // flushes pending reads first
+//
+// flush_reads applies the queue at any time. Tracking is off by default;
+// applied touches are recorded like any other mutation.
+
+use super::graph::{KnowledgeGraph, NodeId};
+use rustc_hash::FxHashMap;
+use std::sync::Mutex;
+
+#[derive(Debug, Default)]
+pub(super) struct ReadLog {
+    enabled: bool,
+    pending: Mutex<FxHashMap<NodeId, u32>>,
+}
+
+impl ReadLog {
+    pub(super) fn record(&self, id: NodeId) {
+        if self.enabled {
+            let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            *pending.entry(id).or_insert(0) += 1;
+        }
+    }
+
+    fn take(&mut self) -> FxHashMap<NodeId, u32> {
+        std::mem::take(self.pending.get_mut().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl Clone for ReadLog {
+    fn clone(&self) -> Self {
+        let pending = self.pending.lock().unwrap_or_else(|e| e.into_inner()).clone();
+        Self { enabled: self.enabled, pending: Mutex::new(pending) }
+    }
+}
+
+impl KnowledgeGraph {
+    pub fn with_read_tracking(mut self) -> Self {
+        self.reads.enabled = true;
+        self
+    }
+
+    // Turning tracking off drops the reads not flushed yet
+    pub fn set_read_tracking(&mut self, on: bool) {
+        self.reads.enabled = on;
+        if !on {
+            self.reads.take();
+        }
+    }
+
+    pub fn pending_reads(&self) -> usize {
+        self.reads.pending.lock().unwrap_or_else(|e| e.into_inner()).values().map(|&n| n as usize).sum()
+    }
+
+    // Applies the queued reads as touches, returns how many nodes were
+    // touched. Reads of missing nodes are dropped.
+    pub fn flush_reads(&mut self) -> usize {
+        let mut reads: Vec<(NodeId, u32)> = self.reads.take().into_iter().collect();
+        reads.sort_unstable();
+        reads.into_iter().filter(|&(id, count)| self.touch_node_times(id, count)).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::graph::DecayConfig;
+
+    const FACT: u32 = 1;
+
+    #[test]
+    fn reads_keep_nodes_alive() {
+        let config = DecayConfig { decay_rate: 0.1, prune_threshold: 0.5, access_boost: 0.2, ..DecayConfig::default() };
+        let mut g = KnowledgeGraph::new().with_decay(config).with_read_tracking();
+        let read = g.add_node(FACT);
+        let unread = g.add_node(FACT);
+        for _ in 0..8 {
+            g.tick();
+            let _ = g.node(read);
+            let _ = g.node(read);
+            g.apply_decay();
+        }
+        assert_eq!(g.pending_reads(), 0);
+        let node = g.node(read).unwrap();
+        assert_eq!((node.access_count, node.last_access), (16, 8));
+        assert_eq!(g.pending_reads(), 1);
+        g.prune_weak();
+        assert!(g.node(read).is_some());
+        assert!(g.node(unread).is_none());
+
+        g.set_read_tracking(false);
+        let _ = g.node(read);
+        assert_eq!((g.pending_reads(), g.flush_reads()), (0, 0));
+    }
+}
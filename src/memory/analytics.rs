@@ -0,0 +1,169 @@
+// Centrality scores over the knowledge graph.
+//
+// - pagerank: random walk along edge directions, each step choosing an
+//   outgoing edge in proportion to its weight; dangling nodes jump anywhere.
+//   Scores sum to 1.
+// - betweenness: Brandes' algorithm on the directed, unweighted graph, the
+//   number of shortest paths through a node. Sampling k source nodes (spread
+//   evenly over the ids) and scaling by n / k approximates it on large graphs.
+// - degree_centrality: (in + out degree) / (n - 1).
+//
+// apply_centrality blends normalised scores into node weights, so that decay
+// and prune_weak keep important nodes around longer.
+
+use super::graph::{KnowledgeGraph, NodeId};
+use rustc_hash::FxHashMap;
+use std::collections::VecDeque;
+
+pub type Scores = FxHashMap<NodeId, f64>;
+
+impl KnowledgeGraph {
+    fn sorted_node_ids(&self) -> Vec<NodeId> {
+        let mut ids: Vec<NodeId> = self.nodes().map(|n| n.id).collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    pub fn pagerank(&self, damping: f64, iterations: usize) -> Scores {
+        let ids = self.sorted_node_ids();
+        let n = ids.len() as f64;
+        let mut rank: Scores = ids.iter().map(|&id| (id, 1.0 / n)).collect();
+        for _ in 0..iterations {
+            let mut next: Scores = ids.iter().map(|&id| (id, (1.0 - damping) / n)).collect();
+            let mut dangling = 0.0;
+            for &id in &ids {
+                let out = self.outgoing_edges(id);
+                let total: f64 = out.iter().map(|e| e.weight.max(0.0)).sum();
+                if total <= 0.0 {
+                    dangling += rank[&id];
+                    continue;
+                }
+                for e in out {
+                    *next.entry(e.target).or_default() += damping * rank[&id] * e.weight.max(0.0) / total;
+                }
+            }
+            for score in next.values_mut() {
+                *score += damping * dangling / n;
+            }
+            rank = next;
+        }
+        rank
+    }
+
+    // Exact with samples None, otherwise from that many source nodes
+    pub fn betweenness(&self, samples: Option<usize>) -> Scores {
+        let ids = self.sorted_node_ids();
+        let mut score: Scores = ids.iter().map(|&id| (id, 0.0)).collect();
+        let k = samples.unwrap_or(ids.len()).clamp(1, ids.len().max(1));
+        let sources: Vec<NodeId> = (0..k).filter_map(|i| ids.get(i * ids.len() / k).copied()).collect();
+        for &s in &sources {
+            let mut order = Vec::new();
+            let mut preds: FxHashMap<NodeId, Vec<NodeId>> = FxHashMap::default();
+            let mut sigma: FxHashMap<NodeId, f64> = FxHashMap::default();
+            let mut dist: FxHashMap<NodeId, usize> = FxHashMap::default();
+            sigma.insert(s, 1.0);
+            dist.insert(s, 0);
+            let mut queue = VecDeque::from([s]);
+            while let Some(v) = queue.pop_front() {
+                order.push(v);
+                for e in self.outgoing_edges(v) {
+                    let w = e.target;
+                    if !dist.contains_key(&w) {
+                        dist.insert(w, dist[&v] + 1);
+                        queue.push_back(w);
+                    }
+                    if dist[&w] == dist[&v] + 1 {
+                        *sigma.entry(w).or_default() += sigma[&v];
+                        preds.entry(w).or_default().push(v);
+                    }
+                }
+            }
+            let mut delta: Scores = FxHashMap::default();
+            for &w in order.iter().rev() {
+                let dw = delta.get(&w).copied().unwrap_or(0.0);
+                for &v in preds.get(&w).into_iter().flatten() {
+                    *delta.entry(v).or_default() += sigma[&v] / sigma[&w] * (1.0 + dw);
+                }
+                if w != s {
+                    *score.entry(w).or_default() += dw;
+                }
+            }
+        }
+        let scale = ids.len() as f64 / sources.len().max(1) as f64;
+        for v in score.values_mut() {
+            *v *= scale;
+        }
+        score
+    }
+
+    pub fn degree_centrality(&self) -> Scores {
+        let norm = (self.node_count().max(2) - 1) as f64;
+        self.nodes()
+            .map(|n| (n.id, (self.outgoing_edges(n.id).len() + self.incoming_edges(n.id).len()) as f64 / norm))
+            .collect()
+    }
+
+    // weight = (1 - blend) * weight + blend * score / max score
+    pub fn apply_centrality(&mut self, scores: &Scores, blend: f64) {
+        let max = scores.values().copied().fold(0.0, f64::max);
+        if max <= 0.0 {
+            return;
+        }
+        for (&id, &score) in scores {
+            if let Some(weight) = self.node(id).map(|n| n.weight) {
+                self.set_node_weight(id, (1.0 - blend) * weight + blend * score / max);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINK: u32 = 1;
+
+    // Leaves all point at a hub, the hub points at one leaf
+    fn star() -> (KnowledgeGraph, NodeId, Vec<NodeId>) {
+        let mut g = KnowledgeGraph::new();
+        let hub = g.add_node(0);
+        let leaves: Vec<NodeId> = (0..4).map(|_| g.add_node(0)).collect();
+        for &l in &leaves {
+            g.add_edge(l, LINK, hub);
+        }
+        g.add_edge(hub, LINK, leaves[0]);
+        (g, hub, leaves)
+    }
+
+    #[test]
+    fn pagerank_and_degree_favour_the_hub() {
+        let (mut g, hub, leaves) = star();
+        let rank = g.pagerank(0.85, 50);
+        assert!((rank.values().sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!(leaves.iter().all(|l| rank[&hub] > rank[l]));
+        assert!(rank[&leaves[0]] > rank[&leaves[1]]);
+
+        let degree = g.degree_centrality();
+        assert_eq!(degree[&hub], 5.0 / 4.0);
+        assert_eq!(degree[&leaves[1]], 1.0 / 4.0);
+
+        for &l in &leaves {
+            g.set_node_weight(l, 0.0);
+        }
+        g.apply_centrality(&degree, 0.5);
+        assert_eq!(g.node(hub).unwrap().weight, 1.0);
+        assert_eq!(g.node(leaves[1]).unwrap().weight, 0.1);
+    }
+
+    #[test]
+    fn betweenness_counts_shortest_paths() {
+        let (g, hub, leaves) = star();
+        let exact = g.betweenness(None);
+        // Paths l -> hub -> leaves[0] for the three other leaves
+        assert_eq!(exact[&hub], 3.0);
+        assert_eq!(exact[&leaves[0]], 0.0);
+        let sampled = g.betweenness(Some(5));
+        assert_eq!(sampled, exact);
+        assert!(g.betweenness(Some(2)).values().all(|v| v.is_finite()));
+    }
+}
@@ -0,0 +1,283 @@
+// Approximate nearest neighbours over node embeddings.
+//
+// find_similar_nodes embeds and compares every node on each query. With a
+// similarity index the embed_node vectors are cached and hashed into
+// random-hyperplane LSH tables (one bit per hyperplane, the side of it the
+// vector lies on), so a query only ranks the nodes sharing a bucket with the
+// target in some table, plus the buckets one bit away when that gives too
+// few candidates. At most max_candidates are ranked, which bounds the cost
+// of a query when many nodes embed alike.
+//
+//   graph.enable_similarity_index(AnnConfig::default().with_dim(16));
+//   let near = graph.similar_nodes(node, 10);
+//
+// Mutations mark the nodes they touch (through the change log); touched
+// nodes are re-embedded and re-hashed on the next tick() or
+// sync_similarity_index(). Until then queries embed them afresh and
+// consider them as candidates, so results never use a stale vector.
+
+use super::graph::{Embedding, KnowledgeGraph, NodeId};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnnConfig {
+    // embed_node dimension
+    pub dim: usize,
+    pub tables: usize,
+    // Hyperplanes per table, at most 64
+    pub bits: usize,
+    pub max_candidates: usize,
+    pub seed: u64,
+}
+
+impl Default for AnnConfig {
+    fn default() -> Self {
+        Self { dim: 8, tables: 8, bits: 12, max_candidates: 1024, seed: 0x2545_F491_4F6C_DD1D }
+    }
+}
+
+impl AnnConfig {
+    pub fn with_dim(mut self, dim: usize) -> Self {
+        self.dim = dim;
+        self
+    }
+
+    pub fn with_tables(mut self, tables: usize, bits: usize) -> Self {
+        self.tables = tables.max(1);
+        self.bits = bits.clamp(1, 64);
+        self
+    }
+
+    pub fn with_max_candidates(mut self, max_candidates: usize) -> Self {
+        self.max_candidates = max_candidates.max(1);
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SimilarityIndex {
+    config: AnnConfig,
+    // tables * bits hyperplanes
+    planes: Vec<Vec<f64>>,
+    buckets: Vec<FxHashMap<u64, Vec<NodeId>>>,
+    // Cached embedding and bucket key per table
+    cache: FxHashMap<NodeId, (Embedding, Vec<u64>)>,
+}
+
+impl SimilarityIndex {
+    fn new(config: AnnConfig) -> Self {
+        let mut state = config.seed | 1;
+        let mut uniform = || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+        };
+        let planes = (0..config.tables * config.bits).map(|_| (0..config.dim).map(|_| uniform()).collect()).collect();
+        let buckets = vec![FxHashMap::default(); config.tables];
+        Self { config, planes, buckets, cache: FxHashMap::default() }
+    }
+
+    fn keys(&self, v: &[f64]) -> Vec<u64> {
+        self.planes.chunks(self.config.bits).map(|planes| {
+            planes.iter().enumerate().fold(0u64, |key, (bit, plane)| {
+                let dot: f64 = plane.iter().zip(v).map(|(a, b)| a * b).sum();
+                if dot >= 0.0 { key | 1 << bit } else { key }
+            })
+        }).collect()
+    }
+
+    fn remove(&mut self, id: NodeId) {
+        if let Some((_, keys)) = self.cache.remove(&id) {
+            for (table, key) in keys.into_iter().enumerate() {
+                if let Some(ids) = self.buckets[table].get_mut(&key) {
+                    ids.retain(|n| *n != id);
+                    if ids.is_empty() {
+                        self.buckets[table].remove(&key);
+                    }
+                }
+            }
+        }
+    }
+
+    fn insert(&mut self, id: NodeId, embedding: Embedding) {
+        self.remove(id);
+        let keys = self.keys(&embedding);
+        for (table, &key) in keys.iter().enumerate() {
+            self.buckets[table].entry(key).or_default().push(id);
+        }
+        self.cache.insert(id, (embedding, keys));
+    }
+
+    // Nodes sharing a bucket with the keys; one-bit neighbours when fewer
+    // than `want`
+    fn candidates(&self, keys: &[u64], want: usize) -> FxHashSet<NodeId> {
+        let limit = self.config.max_candidates.max(want);
+        let mut found: FxHashSet<NodeId> = FxHashSet::default();
+        let take = |found: &mut FxHashSet<NodeId>, ids: Option<&Vec<NodeId>>| {
+            found.extend(ids.into_iter().flatten().take(limit.saturating_sub(found.len())));
+        };
+        for (table, key) in keys.iter().enumerate() {
+            take(&mut found, self.buckets[table].get(key));
+        }
+        if found.len() < want {
+            for (table, key) in keys.iter().enumerate() {
+                for bit in 0..self.config.bits {
+                    take(&mut found, self.buckets[table].get(&(key ^ 1 << bit)));
+                }
+            }
+        }
+        found
+    }
+
+    // Index over the given embeddings only, for callers that partition the
+    // nodes themselves
+    pub(super) fn from_embeddings(config: AnnConfig, embeddings: impl IntoIterator<Item = (NodeId, Embedding)>) -> Self {
+        let mut index = Self::new(config);
+        for (id, embedding) in embeddings {
+            index.insert(id, embedding);
+        }
+        index
+    }
+
+    // At most max_candidates indexed nodes likely near the embedding
+    pub(super) fn near(&self, embedding: &[f64]) -> FxHashSet<NodeId> {
+        self.candidates(&self.keys(embedding), 2)
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cache.is_empty()
+    }
+}
+
+impl KnowledgeGraph {
+    // Builds the index over every node, replacing any previous one
+    pub fn enable_similarity_index(&mut self, config: AnnConfig) {
+        let mut index = SimilarityIndex::new(config);
+        let dim = index.config.dim;
+        for node in self.nodes() {
+            index.insert(node.id, self.embed_node(node.id, dim));
+        }
+        self.similarity = Some(index);
+        self.journal.track_touched(true);
+    }
+
+    pub fn disable_similarity_index(&mut self) {
+        self.similarity = None;
+        self.journal.track_touched(false);
+    }
+
+    pub fn similarity_index(&self) -> Option<&SimilarityIndex> {
+        self.similarity.as_ref()
+    }
+
+    // Re-embeds the nodes touched since the last sync, returns how many
+    pub fn sync_similarity_index(&mut self) -> usize {
+        let Some(mut index) = self.similarity.take() else { return 0 };
+        let touched = self.journal.take_touched();
+        for &id in &touched {
+            if self.contains_node(id) {
+                index.insert(id, self.embed_node(id, index.config.dim));
+            } else {
+                index.remove(id);
+            }
+        }
+        self.similarity = Some(index);
+        touched.len()
+    }
+
+    // Approximate top_k of find_similar_nodes; empty without an index
+    pub fn similar_nodes(&self, target: NodeId, top_k: usize) -> Vec<(NodeId, f64)> {
+        let Some(index) = &self.similarity else { return Vec::new() };
+        if !self.contains_node(target) {
+            return Vec::new();
+        }
+        let dim = index.config.dim;
+        let empty = FxHashSet::default();
+        let touched = self.journal.touched().unwrap_or(&empty);
+        let embedding = |id: NodeId| match index.cache.get(&id) {
+            Some((e, _)) if !touched.contains(&id) => e.clone(),
+            _ => self.embed_node(id, dim),
+        };
+        let target_emb = embedding(target);
+        let mut candidates = index.candidates(&index.keys(&target_emb), top_k + 1);
+        candidates.extend(touched.iter().copied());
+        let mut scores: Vec<(NodeId, f64)> = candidates.into_iter()
+            .filter(|&id| id != target && self.contains_node(id))
+            .map(|id| (id, Self::similarity(&target_emb, &embedding(id))))
+            .collect();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        scores.truncate(top_k);
+        scores
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Sym;
+    use std::time::Instant;
+
+    const HUB: Sym = 1;
+    const LEAF: Sym = 2;
+    const LINK: Sym = 3;
+
+    fn star_graph(hubs: usize, leaves: usize) -> KnowledgeGraph {
+        let mut g = KnowledgeGraph::new();
+        for _ in 0..hubs {
+            let hub = g.add_node(HUB);
+            for _ in 0..leaves {
+                let leaf = g.add_node(LEAF);
+                g.add_edge(hub, LINK, leaf);
+            }
+        }
+        g
+    }
+
+    #[test]
+    fn index_agrees_with_the_scan_and_follows_mutations() {
+        let mut g = star_graph(4, 5);
+        g.enable_similarity_index(AnnConfig::default());
+        let exact: Vec<NodeId> = g.find_similar_nodes(1, 8, 3).into_iter().map(|p| p.0).collect();
+        let approx: Vec<NodeId> = g.similar_nodes(1, 3).into_iter().map(|p| p.0).collect();
+        assert_eq!(approx, exact);
+        assert_eq!(g.similarity_index().map(|i| i.len()), Some(24));
+
+        // A new hub is found before and after the sync
+        let hub = g.add_node(HUB);
+        let leaves: Vec<NodeId> = (0..5).map(|_| g.add_node(LEAF)).collect();
+        for &leaf in &leaves {
+            g.add_edge(hub, LINK, leaf);
+        }
+        assert!(g.similar_nodes(1, 4).iter().any(|p| p.0 == hub));
+        g.remove_node(leaves[0]);
+        g.tick();
+        assert_eq!(g.similarity_index().map(|i| i.len()), Some(29));
+        assert!(g.similar_nodes(1, 4).iter().any(|p| p.0 == hub));
+        assert!(g.similar_nodes(leaves[1], 50).iter().all(|p| p.0 != leaves[0]));
+    }
+
+    // cargo test --release memory::ann -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn sub_millisecond_queries_on_100k_nodes() {
+        let mut g = star_graph(10_000, 10);
+        g.enable_similarity_index(AnnConfig::default());
+        let start = Instant::now();
+        for id in (1..100_000).step_by(1_000) {
+            assert!(!g.similar_nodes(id, 10).is_empty());
+        }
+        let per_query = start.elapsed().as_secs_f64() * 1000.0 / 100.0;
+        println!("{:.3} ms per query over {} nodes", per_query, g.node_count());
+        assert!(per_query < 1.0);
+    }
+}
@@ -9,11 +9,29 @@
 //
 // Section:
 //   [type: u8] [len: u32] [data: [u8; len]]
+//
+// Graph snapshots (KnowledgeGraph::to_binary) use sections SYMBOLS (the
+// SymbolTable, optional), GRAPH (next ids, tick), then as many NODES and
+// EDGES sections as needed; readers skip section types they do not know.
+// Counts are u32, attribute values are terms. With the "compression" feature
+// a snapshot can be streamed through LZ4 (compressed.rs).
+//
+// Version 2 added the edge context, version 3 the edge a node reifies,
+// version 4 the pinned flags; older snapshots still load, without them.
+// Readers are version-gated: read_header rejects versions newer than this
+// build, and migrate_binary rewrites an old snapshot in the current format:
+//
+//   if snapshot_version(&bytes) < Some(FORMAT_VERSION) {
+//       std::fs::write(path, KnowledgeGraph::migrate_binary(&bytes)?)?;
+//   }
 
-use crate::core::{Term, OrderedFloat};
+use crate::core::{Term, OrderedFloat, SymbolTable};
+use super::graph::{Edge, GraphSnapshot, KnowledgeGraph, Node, TermSer};
+use std::io::{self, Write};
 
 const MAGIC: u32 = 0x4B4F4C53; // "KOLS"
-const VERSION: u8 = 1;
+const VERSION: u8 = 4;
+pub const FORMAT_VERSION: u8 = VERSION;
 
 // Term tags
 const TAG_VAR: u8 = 0;
@@ -25,6 +43,17 @@ const TAG_BOOL: u8 = 5;
 const TAG_COMPOUND: u8 = 6;
 const TAG_LIST: u8 = 7;
 const TAG_NIL: u8 = 8;
+const TAG_DICT: u8 = 9;
+
+// Section types
+pub const SECTION_SYMBOLS: u8 = 1;
+pub const SECTION_GRAPH: u8 = 2;
+pub const SECTION_NODES: u8 = 3;
+pub const SECTION_EDGES: u8 = 4;
+
+// Graph snapshots split nodes and edges into sections of this many records;
+// a reader appends the records of repeated sections
+pub const RECORDS_PER_SECTION: usize = 1 << 16;
 
 pub struct BinaryWriter {
     buf: Vec<u8>,
@@ -117,6 +146,14 @@ impl BinaryWriter {
                     self.write_term(item);
                 }
             }
+            Term::Dict(pairs) => {
+                self.write_u8(TAG_DICT);
+                self.write_u16(pairs.len() as u16);
+                for (k, v) in pairs {
+                    self.write_u32(*k);
+                    self.write_term(v);
+                }
+            }
             Term::Nil => {
                 self.write_u8(TAG_NIL);
             }
@@ -141,16 +178,100 @@ impl BinaryWriter {
             self.write_str(s);
         }
     }
+
+    pub fn write_section_count(&mut self, count: u16) {
+        self.write_u16(count);
+    }
+
+    // Section of the given type; its length is filled in after `body`
+    pub fn write_section<F: FnOnce(&mut Self)>(&mut self, kind: u8, body: F) {
+        self.write_u8(kind);
+        let at = self.buf.len();
+        self.write_u32(0);
+        body(self);
+        let len = (self.buf.len() - at - 4) as u32;
+        self.buf[at..at + 4].copy_from_slice(&len.to_le_bytes());
+    }
+
+    fn write_attrs(&mut self, attrs: &[(u32, TermSer)]) {
+        self.write_u32(attrs.len() as u32);
+        for (k, v) in attrs {
+            self.write_u32(*k);
+            self.write_term(&v.to_term());
+        }
+    }
+
+    fn write_opt_u64(&mut self, v: Option<u64>) {
+        match v {
+            Some(v) => {
+                self.write_u8(1);
+                self.write_u64(v);
+            }
+            None => self.write_u8(0),
+        }
+    }
+
+    pub fn write_node(&mut self, node: &Node) {
+        self.write_u32(node.id);
+        self.write_u32(node.label);
+        self.write_attrs(&node.attributes);
+        self.write_u64(node.created_at);
+        self.write_u64(node.last_access);
+        self.write_u32(node.access_count);
+        self.write_f64(node.weight);
+        match node.reifies {
+            Some(edge) => {
+                self.write_u8(1);
+                self.write_u32(edge);
+            }
+            None => self.write_u8(0),
+        }
+        self.write_u8(u8::from(node.pinned));
+    }
+
+    pub fn write_edge(&mut self, edge: &Edge) {
+        self.write_u32(edge.id);
+        self.write_u32(edge.relation);
+        self.write_u32(edge.source);
+        self.write_u32(edge.target);
+        self.write_f64(edge.weight);
+        self.write_attrs(&edge.attributes);
+        self.write_u64(edge.created_at);
+        self.write_u64(edge.last_access);
+        self.write_u32(edge.access_count);
+        self.write_opt_u64(edge.valid_from);
+        self.write_opt_u64(edge.valid_to);
+        match edge.context {
+            Some(ctx) => {
+                self.write_u8(1);
+                self.write_u32(ctx);
+            }
+            None => self.write_u8(0),
+        }
+        self.write_u8(u8::from(edge.pinned));
+    }
 }
 
 pub struct BinaryReader<'a> {
     data: &'a [u8],
     pos: usize,
+    // Format version of the records, set by read_header
+    version: u8,
 }
 
 impl<'a> BinaryReader<'a> {
     pub fn new(data: &'a [u8]) -> Self {
-        Self { data, pos: 0 }
+        Self { data, pos: 0, version: VERSION }
+    }
+
+    // Reader for records written in an older format version
+    pub fn with_version(mut self, version: u8) -> Self {
+        self.version = version;
+        self
+    }
+
+    pub fn version(&self) -> u8 {
+        self.version
     }
 
     pub fn remaining(&self) -> usize {
@@ -171,14 +292,14 @@ impl<'a> BinaryReader<'a> {
         Some(v)
     }
 
-    fn read_u32(&mut self) -> Option<u32> {
+    pub(super) fn read_u32(&mut self) -> Option<u32> {
         if self.pos + 4 > self.data.len() { return None; }
         let v = u32::from_le_bytes(self.data[self.pos..self.pos + 4].try_into().ok()?);
         self.pos += 4;
         Some(v)
     }
 
-    fn read_u64(&mut self) -> Option<u64> {
+    pub(super) fn read_u64(&mut self) -> Option<u64> {
         if self.pos + 8 > self.data.len() { return None; }
         let v = u64::from_le_bytes(self.data[self.pos..self.pos + 8].try_into().ok()?);
         self.pos += 8;
@@ -192,6 +313,10 @@ impl<'a> BinaryReader<'a> {
         Some(v)
     }
 
+    fn read_f64(&mut self) -> Option<f64> {
+        self.read_u64().map(f64::from_bits)
+    }
+
     fn read_bytes(&mut self) -> Option<Vec<u8>> {
         let len = self.read_u32()? as usize;
         if self.pos + len > self.data.len() { return None; }
@@ -231,6 +356,15 @@ impl<'a> BinaryReader<'a> {
                 }
                 Some(Term::List(items))
             }
+            TAG_DICT => {
+                let n = self.read_u16()? as usize;
+                let mut pairs = Vec::with_capacity(n);
+                for _ in 0..n {
+                    let k = self.read_u32()?;
+                    pairs.push((k, self.read_term()?));
+                }
+                Some(Term::dict(pairs))
+            }
             TAG_NIL => Some(Term::Nil),
             _ => None,
         }
@@ -245,10 +379,14 @@ impl<'a> BinaryReader<'a> {
         Some(terms)
     }
 
+    // Format version; None for bad magic or a version this build can't read
     pub fn read_header(&mut self) -> Option<u8> {
         let magic = self.read_u32()?;
         if magic != MAGIC { return None; }
-        self.read_u8()
+        let version = self.read_u8()?;
+        if version == 0 || version > VERSION { return None; }
+        self.version = version;
+        Some(version)
     }
 
     pub fn read_symbol_table(&mut self) -> Option<Vec<String>> {
@@ -259,6 +397,194 @@ impl<'a> BinaryReader<'a> {
         }
         Some(syms)
     }
+
+    pub fn read_section_count(&mut self) -> Option<u16> {
+        self.read_u16()
+    }
+
+    // Next section's type and a reader over its data
+    pub fn read_section(&mut self) -> Option<(u8, BinaryReader<'a>)> {
+        let kind = self.read_u8()?;
+        let len = self.read_u32()? as usize;
+        if self.pos + len > self.data.len() { return None; }
+        let section = BinaryReader::new(&self.data[self.pos..self.pos + len]).with_version(self.version);
+        self.pos += len;
+        Some((kind, section))
+    }
+
+    fn read_attrs(&mut self) -> Option<Vec<(u32, TermSer)>> {
+        let count = self.read_u32()? as usize;
+        let mut attrs = Vec::with_capacity(count.min(self.remaining()));
+        for _ in 0..count {
+            let k = self.read_u32()?;
+            attrs.push((k, TermSer::from_term(&self.read_term()?)?));
+        }
+        Some(attrs)
+    }
+
+    fn read_opt_u64(&mut self) -> Option<Option<u64>> {
+        match self.read_u8()? {
+            0 => Some(None),
+            _ => self.read_u64().map(Some),
+        }
+    }
+
+    pub fn read_node(&mut self) -> Option<Node> {
+        let mut node = Node {
+            id: self.read_u32()?,
+            label: self.read_u32()?,
+            attributes: self.read_attrs()?,
+            created_at: self.read_u64()?,
+            last_access: self.read_u64()?,
+            access_count: self.read_u32()?,
+            weight: self.read_f64()?,
+            reifies: None,
+            pinned: false,
+        };
+        if self.version >= 3 && self.read_u8()? != 0 {
+            node.reifies = Some(self.read_u32()?);
+        }
+        if self.version >= 4 {
+            node.pinned = self.read_u8()? != 0;
+        }
+        Some(node)
+    }
+
+    pub fn read_edge(&mut self) -> Option<Edge> {
+        let mut edge = Edge {
+            id: self.read_u32()?,
+            relation: self.read_u32()?,
+            source: self.read_u32()?,
+            target: self.read_u32()?,
+            weight: self.read_f64()?,
+            attributes: self.read_attrs()?,
+            created_at: self.read_u64()?,
+            last_access: self.read_u64()?,
+            access_count: self.read_u32()?,
+            valid_from: self.read_opt_u64()?,
+            valid_to: self.read_opt_u64()?,
+            context: None,
+            pinned: false,
+        };
+        if self.version >= 2 && self.read_u8()? != 0 {
+            edge.context = Some(self.read_u32()?);
+        }
+        if self.version >= 4 {
+            edge.pinned = self.read_u8()? != 0;
+        }
+        Some(edge)
+    }
+}
+
+impl KnowledgeGraph {
+    // Binary snapshot, with the symbol table when given
+    pub fn to_binary(&self, syms: Option<&SymbolTable>) -> Vec<u8> {
+        let mut buf = Vec::new();
+        self.write_binary(syms, &mut buf).expect("writing to a Vec");
+        buf
+    }
+
+    // Same snapshot streamed to `out`. Nodes and edges go in sections of at
+    // most RECORDS_PER_SECTION records, so only one section is buffered.
+    pub fn write_binary<W: Write>(&self, syms: Option<&SymbolTable>, mut out: W) -> io::Result<()> {
+        let node_sections = self.node_count().div_ceil(RECORDS_PER_SECTION);
+        let edge_sections = self.edge_count().div_ceil(RECORDS_PER_SECTION);
+        let sections = usize::from(syms.is_some()) + 1 + node_sections + edge_sections;
+        let sections = u16::try_from(sections).map_err(|_| io::Error::other("graph too large for one snapshot"))?;
+        let mut w = BinaryWriter::new();
+        w.write_header();
+        w.write_section_count(sections);
+        if let Some(syms) = syms {
+            let names: Vec<&str> = (0..syms.len() as u32).map(|id| syms.resolve(id).unwrap_or("")).collect();
+            w.write_section(SECTION_SYMBOLS, |w| w.write_symbol_table(&names));
+        }
+        let (next_node, next_edge) = self.next_ids();
+        w.write_section(SECTION_GRAPH, |w| {
+            w.write_u32(next_node);
+            w.write_u32(next_edge);
+            w.write_u64(self.current_tick());
+        });
+        out.write_all(&w.buf)?;
+
+        let mut nodes = self.nodes().peekable();
+        while nodes.peek().is_some() {
+            let batch: Vec<&Node> = nodes.by_ref().take(RECORDS_PER_SECTION).collect();
+            w.buf.clear();
+            w.write_section(SECTION_NODES, |w| {
+                w.write_u32(batch.len() as u32);
+                for node in batch {
+                    w.write_node(node);
+                }
+            });
+            out.write_all(&w.buf)?;
+        }
+        let mut edges = self.edges().peekable();
+        while edges.peek().is_some() {
+            let batch: Vec<&Edge> = edges.by_ref().take(RECORDS_PER_SECTION).collect();
+            w.buf.clear();
+            w.write_section(SECTION_EDGES, |w| {
+                w.write_u32(batch.len() as u32);
+                for edge in batch {
+                    w.write_edge(edge);
+                }
+            });
+            out.write_all(&w.buf)?;
+        }
+        Ok(())
+    }
+
+    // The graph and, when the snapshot has one, its symbol table
+    pub fn from_binary(data: &[u8]) -> Option<(Self, Option<SymbolTable>)> {
+        let mut r = BinaryReader::new(data);
+        r.read_header()?;
+        let sections = r.read_section_count()?;
+        let mut snapshot = GraphSnapshot { nodes: Vec::new(), edges: Vec::new(), next_node_id: 1, next_edge_id: 1, tick: 0 };
+        let mut syms = None;
+        for _ in 0..sections {
+            let (kind, mut s) = r.read_section()?;
+            match kind {
+                SECTION_SYMBOLS => {
+                    let mut table = SymbolTable::new();
+                    for name in s.read_symbol_table()? {
+                        table.intern(&name);
+                    }
+                    syms = Some(table);
+                }
+                SECTION_GRAPH => {
+                    snapshot.next_node_id = s.read_u32()?;
+                    snapshot.next_edge_id = s.read_u32()?;
+                    snapshot.tick = s.read_u64()?;
+                }
+                SECTION_NODES => {
+                    let count = s.read_u32()? as usize;
+                    snapshot.nodes.reserve(count.min(s.remaining()));
+                    for _ in 0..count {
+                        snapshot.nodes.push(s.read_node()?);
+                    }
+                }
+                SECTION_EDGES => {
+                    let count = s.read_u32()? as usize;
+                    snapshot.edges.reserve(count.min(s.remaining()));
+                    for _ in 0..count {
+                        snapshot.edges.push(s.read_edge()?);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Some((Self::from_snapshot(snapshot), syms))
+    }
+
+    // Snapshot rewritten in the current format, symbol table included
+    pub fn migrate_binary(data: &[u8]) -> Option<Vec<u8>> {
+        let (graph, syms) = Self::from_binary(data)?;
+        Some(graph.to_binary(syms.as_ref()))
+    }
+}
+
+// Format version of a snapshot, None if it is not one this build can read
+pub fn snapshot_version(data: &[u8]) -> Option<u8> {
+    BinaryReader::new(data).read_header()
 }
 
 // Compact bitfield operations for grid storage
@@ -327,3 +653,116 @@ pub fn unpack_grid(data: &[u8]) -> Option<Vec<Vec<u8>>> {
 
     Some(grid)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn graph_round_trip() {
+        let mut syms = SymbolTable::new();
+        let (person, knows, name, since) = (syms.intern("person"), syms.intern("knows"), syms.intern("name"), syms.intern("since"));
+        let mut g = KnowledgeGraph::new();
+        let a = g.add_node_with_attrs(person, vec![(name, Term::Str("ada".into()))]);
+        let b = g.add_node(person);
+        let removed = g.add_node(person);
+        g.remove_node(removed);
+        g.tick();
+        let e = g.add_edge_with_attrs(a, knows, b, vec![(since, Term::Int(1843))]);
+        g.set_edge_validity(e, Some(1), None);
+        g.add_edge_weighted(b, knows, a, 0.25);
+
+        let bytes = g.to_binary(Some(&syms));
+        let (back, table) = KnowledgeGraph::from_binary(&bytes).unwrap();
+        let table = table.unwrap();
+        assert_eq!(table.resolve(since), Some("since"));
+        assert_eq!(table.len(), syms.len());
+        assert_eq!(back.next_ids(), g.next_ids());
+        assert_eq!(back.current_tick(), 1);
+        assert_eq!(back.node(a), g.node(a));
+        assert_eq!(back.edge(e), g.edge(e));
+        assert_eq!(back.outgoing_edges(b)[0].weight, 0.25);
+        assert_eq!(back.nodes_by_label(person).len(), 2);
+
+        let (plain, none) = KnowledgeGraph::from_binary(&g.to_binary(None)).unwrap();
+        assert!(none.is_none());
+        assert_eq!(plain.edge_count(), 2);
+        assert!(KnowledgeGraph::from_binary(&bytes[..bytes.len() - 3]).is_none());
+    }
+
+    #[test]
+    fn large_graphs_span_several_sections() {
+        let mut g = KnowledgeGraph::new();
+        let ids: Vec<u32> = (0..RECORDS_PER_SECTION + 10).map(|i| g.add_node((i % 3) as u32)).collect();
+        g.add_edge(ids[0], 7, ids[RECORDS_PER_SECTION + 9]);
+        let bytes = g.to_binary(None);
+        let mut r = BinaryReader::new(&bytes);
+        r.read_header();
+        assert_eq!(r.read_section_count(), Some(4));
+        let (back, _) = KnowledgeGraph::from_binary(&bytes).unwrap();
+        assert_eq!(back.node_count(), ids.len());
+        assert_eq!(back.outgoing_edges(ids[0])[0].target, ids[RECORDS_PER_SECTION + 9]);
+    }
+
+    #[test]
+    fn migrates_version_1_snapshots() {
+        let mut w = BinaryWriter::new();
+        w.write_header();
+        w.write_section_count(2);
+        w.write_section(SECTION_NODES, |w| {
+            w.write_u32(2);
+            for id in [1, 2] {
+                w.write_u32(id);
+                w.write_u32(5);
+                w.write_attrs(&[]);
+                w.write_u64(0);
+                w.write_u64(0);
+                w.write_u32(0);
+                w.write_f64(1.0);
+            }
+        });
+        w.write_section(SECTION_EDGES, |w| {
+            w.write_u32(1);
+            w.write_u32(1);
+            w.write_u32(7);
+            w.write_u32(1);
+            w.write_u32(2);
+            w.write_f64(0.5);
+            w.write_attrs(&[(3, TermSer::Int(9))]);
+            w.write_u64(0);
+            w.write_u64(0);
+            w.write_u32(0);
+            w.write_opt_u64(Some(2));
+            w.write_opt_u64(None);
+        });
+        let mut old = w.into_bytes();
+        old[4] = 1;
+        assert_eq!(snapshot_version(&old), Some(1));
+
+        let migrated = KnowledgeGraph::migrate_binary(&old).unwrap();
+        assert_eq!(snapshot_version(&migrated), Some(FORMAT_VERSION));
+        let (g, _) = KnowledgeGraph::from_binary(&migrated).unwrap();
+        let edge = g.edge(1).unwrap();
+        assert_eq!((edge.source, edge.target, edge.valid_from, edge.context), (1, 2, Some(2), None));
+        assert!(!g.node(2).unwrap().pinned);
+
+        old[4] = FORMAT_VERSION + 1;
+        assert!(snapshot_version(&old).is_none() && KnowledgeGraph::from_binary(&old).is_none());
+    }
+
+    #[test]
+    fn unknown_sections_are_skipped() {
+        let mut g = KnowledgeGraph::new();
+        let id = g.add_node(5);
+        let mut w = BinaryWriter::new();
+        w.write_header();
+        w.write_section_count(2);
+        w.write_section(99, |w| w.write_terms(&[Term::Int(7)]));
+        w.write_section(SECTION_NODES, |w| {
+            w.write_u32(1);
+            w.write_node(g.node(id).unwrap());
+        });
+        let (back, _) = KnowledgeGraph::from_binary(&w.into_bytes()).unwrap();
+        assert_eq!(back.nodes_by_label(5), vec![id]);
+    }
+}
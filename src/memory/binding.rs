@@ -0,0 +1,246 @@
+// Live binding between the knowledge graph and a RuleEngine.
+//
+// Bound relations are views shared by both sides: an edge a -rel-> b is the
+// engine fact rel(A, B) over the endpoint labels, as in to_terms. sync
+// compares each side with the state of the previous sync and carries the
+// differences across: facts asserted in the engine (or derived by
+// forward_chain) become edges, new edges become facts, and a retraction or
+// removal on one side is applied to the other. Edges created for engine
+// facts are stamped with the binding's context (context.rs) and every bound
+// fact remembers where it came from:
+//
+//   let mut binding = GraphBinding::new(syms.intern("engine"))
+//       .with_relation(knows)
+//       .with_functional(lives_in, Conflict::EngineWins);
+//   engine.forward_chain(10);
+//   let report = binding.sync(&mut graph, &mut engine);
+//
+// A functional relation holds at most one object per subject. When a sync
+// finds several, the conflict rule picks the side whose facts stand; the
+// others are removed from both sides and listed in the report. A fact
+// rel(a, b) reaches the graph between the first nodes labelled a and b,
+// created when missing.
+
+use crate::core::{Sym, Term};
+use crate::reasoning::rules::RuleEngine;
+use super::graph::{EdgeId, KnowledgeGraph};
+use super::working::node_for;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+// (relation, subject label, object label)
+pub type BoundFact = (Sym, Sym, Sym);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    Graph,
+    Engine,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conflict {
+    // Facts present in the graph stand
+    GraphWins,
+    // Facts present in the engine stand
+    EngineWins,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SyncReport {
+    pub to_engine: usize,
+    pub to_graph: usize,
+    pub retracted: usize,
+    pub removed_edges: usize,
+    // Facts dropped by a conflict rule
+    pub conflicts: Vec<BoundFact>,
+}
+
+#[derive(Debug, Clone)]
+pub struct GraphBinding {
+    relations: FxHashMap<Sym, Option<Conflict>>,
+    context: Sym,
+    // Bound facts as of the last sync
+    mirror: FxHashMap<BoundFact, Origin>,
+}
+
+fn to_term(fact: BoundFact) -> Term {
+    Term::compound(fact.0, vec![Term::atom(fact.1), Term::atom(fact.2)])
+}
+
+impl GraphBinding {
+    pub fn new(context: Sym) -> Self {
+        Self { relations: FxHashMap::default(), context, mirror: FxHashMap::default() }
+    }
+
+    pub fn with_relation(mut self, relation: Sym) -> Self {
+        self.relations.insert(relation, None);
+        self
+    }
+
+    pub fn with_functional(mut self, relation: Sym, rule: Conflict) -> Self {
+        self.relations.insert(relation, Some(rule));
+        self
+    }
+
+    // Where a bound fact came from, as of the last sync
+    pub fn origin(&self, fact: BoundFact) -> Option<Origin> {
+        self.mirror.get(&fact).copied()
+    }
+
+    fn graph_facts(&self, graph: &KnowledgeGraph) -> FxHashMap<BoundFact, Vec<EdgeId>> {
+        let mut facts: FxHashMap<BoundFact, Vec<EdgeId>> = FxHashMap::default();
+        for &relation in self.relations.keys() {
+            for id in graph.edges_by_relation(relation) {
+                let Some(edge) = graph.edge(id) else { continue };
+                if let (Some(a), Some(b)) = (graph.label_of(edge.source), graph.label_of(edge.target)) {
+                    facts.entry((relation, a, b)).or_default().push(id);
+                }
+            }
+        }
+        facts
+    }
+
+    fn engine_facts(&self, engine: &RuleEngine) -> FxHashSet<BoundFact> {
+        engine.facts().iter().filter_map(|fact| match fact {
+            Term::Compound(f, args) if self.relations.contains_key(f) => match args.as_slice() {
+                [Term::Atom(a), Term::Atom(b)] => Some((*f, *a, *b)),
+                _ => None,
+            },
+            _ => None,
+        }).collect()
+    }
+
+    pub fn sync(&mut self, graph: &mut KnowledgeGraph, engine: &mut RuleEngine) -> SyncReport {
+        let mut report = SyncReport::default();
+        let mut in_graph = self.graph_facts(graph);
+        let mut in_engine = self.engine_facts(engine);
+
+        // Removals since the last sync
+        let mut gone: Vec<BoundFact> = self.mirror.keys().copied().filter(|f| !in_graph.contains_key(f) || !in_engine.contains(f)).collect();
+        gone.sort_unstable();
+        for fact in gone {
+            self.mirror.remove(&fact);
+            if in_engine.remove(&fact) && engine.retract(&to_term(fact)) {
+                report.retracted += 1;
+            }
+            for id in in_graph.remove(&fact).unwrap_or_default() {
+                report.removed_edges += usize::from(graph.remove_edge(id));
+            }
+        }
+
+        // Functional relations: one object per subject
+        let mut all: Vec<BoundFact> = in_graph.keys().copied().chain(in_engine.iter().copied()).collect();
+        all.sort_unstable();
+        all.dedup();
+        for group in all.chunk_by(|x, y| (x.0, x.1) == (y.0, y.1)) {
+            let Some(Some(rule)) = self.relations.get(&group[0].0) else { continue };
+            if group.len() < 2 {
+                continue;
+            }
+            let wins = |f: &BoundFact| match rule {
+                Conflict::GraphWins => in_graph.contains_key(f),
+                Conflict::EngineWins => in_engine.contains(f),
+            };
+            let losers: Vec<BoundFact> = group.iter().copied().filter(|f| !wins(f)).collect();
+            if losers.len() == group.len() {
+                continue;
+            }
+            for fact in losers {
+                self.mirror.remove(&fact);
+                if in_engine.remove(&fact) {
+                    engine.retract(&to_term(fact));
+                }
+                for id in in_graph.remove(&fact).unwrap_or_default() {
+                    graph.remove_edge(id);
+                }
+                report.conflicts.push(fact);
+            }
+        }
+
+        // Additions on either side
+        let mut to_engine: Vec<BoundFact> = in_graph.keys().copied().filter(|f| !in_engine.contains(f)).collect();
+        to_engine.sort_unstable();
+        for fact in to_engine {
+            if engine.assert_fact(to_term(fact)).is_ok() {
+                self.mirror.insert(fact, Origin::Graph);
+                report.to_engine += 1;
+            }
+        }
+        let mut to_graph: Vec<BoundFact> = in_engine.iter().copied().filter(|f| !in_graph.contains_key(f)).collect();
+        to_graph.sort_unstable();
+        for fact in to_graph {
+            let (source, target) = (node_for(graph, fact.1), node_for(graph, fact.2));
+            let id = graph.add_edge(source, fact.0, target);
+            graph.set_edge_context(id, Some(self.context));
+            self.mirror.insert(fact, Origin::Engine);
+            report.to_graph += 1;
+        }
+        for fact in in_engine {
+            self.mirror.entry(fact).or_insert(Origin::Graph);
+        }
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reasoning::rules::Rule;
+
+    const KNOWS: Sym = 3;
+    const FRIEND: Sym = 4;
+    const LIVES_IN: Sym = 5;
+    const ENGINE: Sym = 6;
+    const ADA: Sym = 10;
+    const BOB: Sym = 11;
+    const CAROL: Sym = 12;
+    const PARIS: Sym = 13;
+    const LONDON: Sym = 14;
+
+    #[test]
+    fn edges_and_facts_flow_both_ways() {
+        let mut g = KnowledgeGraph::new();
+        let ada = g.add_node(ADA);
+        let bob = g.add_node(BOB);
+        g.add_edge(ada, KNOWS, bob);
+        let mut engine = RuleEngine::new();
+        // friend(X, Y) :- knows(X, Y).
+        engine.add_rule(Rule::new(Term::compound(FRIEND, vec![Term::var(0), Term::var(1)]), vec![Term::compound(KNOWS, vec![Term::var(0), Term::var(1)])]));
+        engine.add_fact(to_term((KNOWS, BOB, CAROL)));
+
+        let mut binding = GraphBinding::new(ENGINE).with_relation(KNOWS).with_relation(FRIEND);
+        let report = binding.sync(&mut g, &mut engine);
+        assert_eq!((report.to_engine, report.to_graph), (1, 1));
+        assert!(engine.has_fact(&to_term((KNOWS, ADA, BOB))));
+        let carol = g.nodes_by_label(CAROL)[0];
+        assert_eq!(g.incoming_edges(carol)[0].context, Some(ENGINE));
+        assert_eq!(binding.origin((KNOWS, BOB, CAROL)), Some(Origin::Engine));
+
+        engine.forward_chain(5);
+        assert_eq!(binding.sync(&mut g, &mut engine).to_graph, 2);
+        assert_eq!(g.edges_in_context(ENGINE).len(), 3);
+
+        // Removing the edge retracts the fact
+        let ab = g.outgoing_edges(ada).iter().find(|e| e.relation == KNOWS).unwrap().id;
+        g.remove_edge(ab);
+        assert_eq!(binding.sync(&mut g, &mut engine).retracted, 1);
+        assert!(!engine.has_fact(&to_term((KNOWS, ADA, BOB))));
+        assert_eq!(binding.sync(&mut g, &mut engine), SyncReport::default());
+    }
+
+    #[test]
+    fn functional_conflicts_follow_the_rule() {
+        let mut g = KnowledgeGraph::new();
+        let ada = g.add_node(ADA);
+        let london = g.add_node(LONDON);
+        g.add_edge(ada, LIVES_IN, london);
+        let mut engine = RuleEngine::new();
+        engine.add_fact(to_term((LIVES_IN, ADA, PARIS)));
+
+        let mut binding = GraphBinding::new(ENGINE).with_functional(LIVES_IN, Conflict::EngineWins);
+        let report = binding.sync(&mut g, &mut engine);
+        assert_eq!(report.conflicts, vec![(LIVES_IN, ADA, LONDON)]);
+        assert_eq!(g.outgoing_edges(ada).len(), 1);
+        assert_eq!(g.label_of(g.outgoing_edges(ada)[0].target), Some(PARIS));
+        assert_eq!(engine.facts().len(), 1);
+    }
+}
@@ -0,0 +1,213 @@
+// Community detection with the Louvain method.
+//
+// The graph is taken as undirected, edge weights (clamped at 0) summed over
+// parallel edges. Each level moves single nodes to the neighbouring
+// community with the best modularity gain until nothing moves, then
+// collapses every community into one node and repeats on that smaller graph.
+// Nodes are visited in id order so the result is deterministic.
+//
+//   let partition = graph.assign_communities(syms.intern("community"));
+//   for topic in &partition.members { ... }
+
+use crate::core::{Sym, Term};
+use super::graph::{KnowledgeGraph, NodeId};
+use rustc_hash::FxHashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Partition {
+    // Community of each node; ids are 0.. in order of their smallest member
+    pub community: FxHashMap<NodeId, usize>,
+    // Sorted members of each community
+    pub members: Vec<Vec<NodeId>>,
+    pub modularity: f64,
+}
+
+impl Partition {
+    pub fn community_of(&self, node: NodeId) -> Option<usize> {
+        self.community.get(&node).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.members.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.members.is_empty()
+    }
+}
+
+// Symmetric weighted adjacency; a self-loop of weight w is stored as 2w so
+// that row sums are degrees
+type Adjacency = Vec<FxHashMap<usize, f64>>;
+
+// One round of local moves; returns the community of each node and whether
+// any node moved
+fn local_moves(adj: &Adjacency, m2: f64) -> (Vec<usize>, bool) {
+    let degree: Vec<f64> = adj.iter().map(|row| row.values().sum()).collect();
+    let mut community: Vec<usize> = (0..adj.len()).collect();
+    let mut total = degree.clone();
+    let mut moved_any = false;
+    loop {
+        let mut moved = false;
+        for i in 0..adj.len() {
+            let own = community[i];
+            total[own] -= degree[i];
+            // Weight from i into each neighbouring community, in first-seen order
+            let mut links: Vec<(usize, f64)> = vec![(own, 0.0)];
+            let mut neighbours: Vec<(&usize, &f64)> = adj[i].iter().filter(|(&j, _)| j != i).collect();
+            neighbours.sort_unstable_by_key(|(&j, _)| j);
+            for (&j, &w) in neighbours {
+                let c = community[j];
+                match links.iter_mut().find(|(d, _)| *d == c) {
+                    Some(link) => link.1 += w,
+                    None => links.push((c, w)),
+                }
+            }
+            let gain = |(c, w): (usize, f64)| w - total[c] * degree[i] / m2;
+            let mut best = (own, gain(links[0]));
+            for &(c, w) in &links[1..] {
+                if gain((c, w)) > best.1 + 1e-12 {
+                    best = (c, gain((c, w)));
+                }
+            }
+            total[best.0] += degree[i];
+            if best.0 != own {
+                community[i] = best.0;
+                moved = true;
+            }
+        }
+        if !moved {
+            return (community, moved_any);
+        }
+        moved_any = true;
+    }
+}
+
+fn modularity(adj: &Adjacency, community: &[usize], m2: f64) -> f64 {
+    let mut inside: FxHashMap<usize, f64> = FxHashMap::default();
+    let mut total: FxHashMap<usize, f64> = FxHashMap::default();
+    for (i, row) in adj.iter().enumerate() {
+        for (&j, &w) in row {
+            *total.entry(community[i]).or_default() += w;
+            if community[i] == community[j] {
+                *inside.entry(community[i]).or_default() += w;
+            }
+        }
+    }
+    total.iter().map(|(c, t)| inside.get(c).unwrap_or(&0.0) / m2 - (t / m2).powi(2)).sum()
+}
+
+impl KnowledgeGraph {
+    pub fn communities(&self) -> Partition {
+        let mut ids: Vec<NodeId> = self.nodes().map(|n| n.id).collect();
+        ids.sort_unstable();
+        let index: FxHashMap<NodeId, usize> = ids.iter().enumerate().map(|(i, &id)| (id, i)).collect();
+        let mut adj: Adjacency = vec![FxHashMap::default(); ids.len()];
+        for e in self.edges() {
+            let (Some(&a), Some(&b)) = (index.get(&e.source), index.get(&e.target)) else { continue };
+            let w = e.weight.max(0.0);
+            *adj[a].entry(b).or_default() += w;
+            *adj[b].entry(a).or_default() += w;
+        }
+        let m2: f64 = adj.iter().flat_map(|row| row.values()).sum();
+
+        // Super node of each original node
+        let mut assignment: Vec<usize> = (0..ids.len()).collect();
+        if m2 > 0.0 {
+            loop {
+                let (community, moved) = local_moves(&adj, m2);
+                if !moved {
+                    break;
+                }
+                let mut renumber: FxHashMap<usize, usize> = FxHashMap::default();
+                for &c in &community {
+                    let next = renumber.len();
+                    renumber.entry(c).or_insert(next);
+                }
+                let mut collapsed: Adjacency = vec![FxHashMap::default(); renumber.len()];
+                for (i, row) in adj.iter().enumerate() {
+                    for (&j, &w) in row {
+                        *collapsed[renumber[&community[i]]].entry(renumber[&community[j]]).or_default() += w;
+                    }
+                }
+                for a in assignment.iter_mut() {
+                    *a = renumber[&community[*a]];
+                }
+                adj = collapsed;
+            }
+        }
+
+        let mut ordered: FxHashMap<usize, usize> = FxHashMap::default();
+        let mut members: Vec<Vec<NodeId>> = Vec::new();
+        let mut community = FxHashMap::default();
+        for (i, &id) in ids.iter().enumerate() {
+            let next = ordered.len();
+            let c = *ordered.entry(assignment[i]).or_insert(next);
+            if c == members.len() {
+                members.push(Vec::new());
+            }
+            members[c].push(id);
+            community.insert(id, c);
+        }
+        let mq = if m2 > 0.0 {
+            let labels: Vec<usize> = (0..adj.len()).collect();
+            modularity(&adj, &labels, m2)
+        } else {
+            0.0
+        };
+        Partition { community, members, modularity: mq }
+    }
+
+    // communities(), also stored as an integer attribute on every node
+    pub fn assign_communities(&mut self, attr: Sym) -> Partition {
+        let partition = self.communities();
+        for (&id, &c) in &partition.community {
+            self.set_node_attr(id, attr, &Term::Int(c as i64));
+        }
+        partition
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::graph::TermSer;
+
+    const LINK: Sym = 1;
+    const COMMUNITY: Sym = 2;
+
+    // Two triangles joined by a single edge
+    fn barbell() -> (KnowledgeGraph, Vec<NodeId>) {
+        let mut g = KnowledgeGraph::new();
+        let n: Vec<NodeId> = (0..6).map(|_| g.add_node(0)).collect();
+        for base in [0, 3] {
+            g.add_edge(n[base], LINK, n[base + 1]);
+            g.add_edge(n[base + 1], LINK, n[base + 2]);
+            g.add_edge(n[base + 2], LINK, n[base]);
+        }
+        g.add_edge(n[2], LINK, n[3]);
+        (g, n)
+    }
+
+    #[test]
+    fn splits_the_barbell() {
+        let (mut g, n) = barbell();
+        let partition = g.assign_communities(COMMUNITY);
+        assert_eq!(partition.members, vec![n[..3].to_vec(), n[3..].to_vec()]);
+        assert_eq!(partition.community_of(n[4]), Some(1));
+        // 2 * (6/14 - (7/14)^2)
+        assert!((partition.modularity - 5.0 / 14.0).abs() < 1e-9);
+        assert_eq!(g.node(n[5]).unwrap().attributes, vec![(COMMUNITY, TermSer::Int(1))]);
+    }
+
+    #[test]
+    fn isolated_nodes_stay_alone() {
+        let mut g = KnowledgeGraph::new();
+        let a = g.add_node(0);
+        let b = g.add_node(0);
+        let partition = g.communities();
+        assert_eq!(partition.members, vec![vec![a], vec![b]]);
+        assert_eq!(partition.modularity, 0.0);
+        assert!(KnowledgeGraph::new().communities().is_empty());
+    }
+}
@@ -0,0 +1,200 @@
+// LZ4-compressed graph snapshots (feature "compression").
+//
+// The binary snapshot (binary.rs) is streamed through a chunked encoder, so
+// saving buffers one snapshot section and one chunk on top of the graph:
+//
+//   let file = BufWriter::new(File::create("mem.kolz")?);
+//   graph.save_compressed(Some(&syms), file)?;
+//   let (graph, syms) = KnowledgeGraph::load_compressed(File::open("mem.kolz")?)?;
+//
+// Format:
+//   [magic: u32 = 0x4B4F4C5A "KOLZ"]
+//   [version: u8]
+//   [frames...]
+//   [end: u32 = 0]
+//
+// Frame:
+//   [raw_len: u32] [packed_len: u32] [data: [u8; packed_len]]
+//
+// Each frame is an independent LZ4 block of at most CHUNK_SIZE bytes; a
+// frame with packed_len == raw_len is stored as is. Loading decompresses the
+// whole snapshot before decoding it.
+
+use crate::core::SymbolTable;
+use super::graph::KnowledgeGraph;
+use std::io::{self, Read, Write};
+
+const MAGIC: u32 = 0x4B4F4C5A; // "KOLZ"
+const VERSION: u8 = 1;
+
+pub const CHUNK_SIZE: usize = 1 << 20;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+pub struct CompressedWriter<W: Write> {
+    out: W,
+    chunk: Vec<u8>,
+    packed: Vec<u8>,
+}
+
+impl<W: Write> CompressedWriter<W> {
+    pub fn new(mut out: W) -> io::Result<Self> {
+        out.write_all(&MAGIC.to_le_bytes())?;
+        out.write_all(&[VERSION])?;
+        Ok(Self {
+            out,
+            chunk: Vec::with_capacity(CHUNK_SIZE),
+            packed: vec![0; lz4_flex::block::get_maximum_output_size(CHUNK_SIZE)],
+        })
+    }
+
+    fn write_frame(&mut self) -> io::Result<()> {
+        if self.chunk.is_empty() {
+            return Ok(());
+        }
+        let len = lz4_flex::block::compress_into(&self.chunk, &mut self.packed).map_err(io::Error::other)?;
+        let data = if len < self.chunk.len() { &self.packed[..len] } else { &self.chunk[..] };
+        self.out.write_all(&(self.chunk.len() as u32).to_le_bytes())?;
+        self.out.write_all(&(data.len() as u32).to_le_bytes())?;
+        self.out.write_all(data)?;
+        self.chunk.clear();
+        Ok(())
+    }
+
+    // Write the last frame and the end marker, returning the inner writer
+    pub fn finish(mut self) -> io::Result<W> {
+        self.write_frame()?;
+        self.out.write_all(&0u32.to_le_bytes())?;
+        self.out.flush()?;
+        Ok(self.out)
+    }
+}
+
+impl<W: Write> Write for CompressedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(CHUNK_SIZE - self.chunk.len());
+        self.chunk.extend_from_slice(&buf[..n]);
+        if self.chunk.len() == CHUNK_SIZE {
+            self.write_frame()?;
+        }
+        Ok(n)
+    }
+
+    // Frames are only cut at CHUNK_SIZE or on finish
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+pub struct CompressedReader<R: Read> {
+    input: R,
+    chunk: Vec<u8>,
+    packed: Vec<u8>,
+    pos: usize,
+    done: bool,
+}
+
+impl<R: Read> CompressedReader<R> {
+    pub fn new(mut input: R) -> io::Result<Self> {
+        let mut header = [0u8; 5];
+        input.read_exact(&mut header)?;
+        if header[..4] != MAGIC.to_le_bytes() {
+            return Err(invalid("not a compressed snapshot"));
+        }
+        if header[4] != VERSION {
+            return Err(invalid("unsupported compressed snapshot version"));
+        }
+        Ok(Self { input, chunk: Vec::new(), packed: Vec::new(), pos: 0, done: false })
+    }
+
+    fn read_u32(&mut self) -> io::Result<usize> {
+        let mut b = [0u8; 4];
+        self.input.read_exact(&mut b)?;
+        Ok(u32::from_le_bytes(b) as usize)
+    }
+
+    // Load the next frame into `chunk`; false at the end marker
+    fn next_frame(&mut self) -> io::Result<bool> {
+        let raw = self.read_u32()?;
+        if raw == 0 {
+            return Ok(false);
+        }
+        let packed = self.read_u32()?;
+        if raw > CHUNK_SIZE || packed > raw {
+            return Err(invalid("corrupt compressed frame"));
+        }
+        self.chunk.resize(raw, 0);
+        self.pos = 0;
+        if packed == raw {
+            self.input.read_exact(&mut self.chunk)?;
+        } else {
+            self.packed.resize(packed, 0);
+            self.input.read_exact(&mut self.packed)?;
+            let n = lz4_flex::block::decompress_into(&self.packed, &mut self.chunk).map_err(|_| invalid("corrupt compressed frame"))?;
+            if n != raw {
+                return Err(invalid("corrupt compressed frame"));
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for CompressedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pos == self.chunk.len() {
+            if self.done || !self.next_frame()? {
+                self.done = true;
+                return Ok(0);
+            }
+        }
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl KnowledgeGraph {
+    // Compressed binary snapshot; returns `out` once the stream is complete
+    pub fn save_compressed<W: Write>(&self, syms: Option<&SymbolTable>, out: W) -> io::Result<W> {
+        let mut w = CompressedWriter::new(out)?;
+        self.write_binary(syms, &mut w)?;
+        w.finish()
+    }
+
+    pub fn load_compressed<R: Read>(input: R) -> io::Result<(Self, Option<SymbolTable>)> {
+        let mut data = Vec::new();
+        CompressedReader::new(input)?.read_to_end(&mut data)?;
+        Self::from_binary(&data).ok_or_else(|| invalid("unreadable graph snapshot"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bench::snapshot::synthetic_graph;
+
+    #[test]
+    fn compressed_round_trip() {
+        let mut syms = SymbolTable::new();
+        syms.intern("person");
+        let g = synthetic_graph(200_000);
+        let bytes = g.save_compressed(Some(&syms), Vec::new()).unwrap();
+        let plain = g.to_binary(Some(&syms));
+        assert!(plain.len() > CHUNK_SIZE && bytes.len() < plain.len());
+
+        let (back, table) = KnowledgeGraph::load_compressed(&bytes[..]).unwrap();
+        assert_eq!(table.unwrap().resolve(0), Some("person"));
+        assert_eq!(back.to_binary(Some(&syms)), plain);
+    }
+
+    #[test]
+    fn rejects_damaged_streams() {
+        let bytes = KnowledgeGraph::new().save_compressed(None, Vec::new()).unwrap();
+        assert!(KnowledgeGraph::load_compressed(&bytes[..]).is_ok());
+        assert!(KnowledgeGraph::load_compressed(&bytes[..bytes.len() - 1]).is_err());
+        assert!(KnowledgeGraph::load_compressed(&b"KOLS\x01"[..]).is_err());
+    }
+}
@@ -0,0 +1,118 @@
+// Shared knowledge graph for several threads.
+//
+// ConcurrentKnowledgeGraph is a cloneable handle on one graph behind a
+// RwLock: any number of readers query it at once, writers are serialized
+// and wait for the readers to finish. The guards deref to KnowledgeGraph,
+// so reads and writes use the usual API:
+//
+//   let shared = ConcurrentKnowledgeGraph::new(graph);
+//   let worker = shared.clone();
+//   std::thread::spawn(move || worker.read().query_triple(None, Some(knows), None));
+//   shared.write().add_edge(a, knows, b);
+//
+// transaction runs a closure as one graph transaction under the write lock,
+// committed on Ok and rolled back on Err, so readers never see half of it.
+// Read tracking (access.rs) works through read guards as well. A panic
+// under the write lock does not poison the handle; the graph is left as
+// the panicking writer left it.
+
+use super::graph::KnowledgeGraph;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+#[derive(Debug, Clone)]
+pub struct ConcurrentKnowledgeGraph {
+    inner: Arc<RwLock<KnowledgeGraph>>,
+}
+
+impl ConcurrentKnowledgeGraph {
+    pub fn new(graph: KnowledgeGraph) -> Self {
+        Self { inner: Arc::new(RwLock::new(graph)) }
+    }
+
+    pub fn read(&self) -> RwLockReadGuard<'_, KnowledgeGraph> {
+        self.inner.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn write(&self) -> RwLockWriteGuard<'_, KnowledgeGraph> {
+        self.inner.write().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // None instead of blocking when a writer holds the lock
+    pub fn try_read(&self) -> Option<RwLockReadGuard<'_, KnowledgeGraph>> {
+        self.inner.try_read().ok()
+    }
+
+    pub fn transaction<T, E>(&self, f: impl FnOnce(&mut KnowledgeGraph) -> Result<T, E>) -> Result<T, E> {
+        let mut graph = self.write();
+        graph.begin();
+        let out = f(&mut graph);
+        if out.is_ok() {
+            graph.commit();
+        } else {
+            graph.rollback();
+        }
+        out
+    }
+
+    // Copy of the current graph
+    pub fn snapshot(&self) -> KnowledgeGraph {
+        self.read().clone()
+    }
+
+    // The graph, or the handle back when other clones are alive
+    pub fn try_into_inner(self) -> Result<KnowledgeGraph, Self> {
+        match Arc::try_unwrap(self.inner) {
+            Ok(lock) => Ok(lock.into_inner().unwrap_or_else(|e| e.into_inner())),
+            Err(inner) => Err(Self { inner }),
+        }
+    }
+}
+
+impl From<KnowledgeGraph> for ConcurrentKnowledgeGraph {
+    fn from(graph: KnowledgeGraph) -> Self {
+        Self::new(graph)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Sym;
+    use std::thread;
+
+    const PERSON: Sym = 1;
+    const KNOWS: Sym = 2;
+
+    #[test]
+    fn readers_share_writers_serialize() {
+        let shared = ConcurrentKnowledgeGraph::new(KnowledgeGraph::new().with_read_tracking());
+        let hub = shared.write().add_node(PERSON);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                let shared = shared.clone();
+                s.spawn(move || {
+                    for _ in 0..50 {
+                        let mut graph = shared.write();
+                        let person = graph.add_node(PERSON);
+                        graph.add_edge(person, KNOWS, hub);
+                        drop(graph);
+                        let graph = shared.read();
+                        assert!(graph.node(hub).is_some());
+                        assert_eq!(graph.node_count(), graph.edge_count() + 1);
+                    }
+                });
+            }
+        });
+        assert_eq!(shared.read().incoming_edges(hub).len(), 200);
+        assert_eq!(shared.read().pending_reads(), 200);
+
+        let failed: Result<(), ()> = shared.transaction(|g| {
+            g.remove_node(hub);
+            Err(())
+        });
+        assert!(failed.is_err());
+        assert_eq!(shared.read().edge_count(), 200);
+        let graph = shared.try_into_inner().unwrap();
+        assert_eq!(graph.node_count(), 201);
+    }
+}
@@ -0,0 +1,104 @@
+// Edge contexts (named graphs): which ingestion run, inference pass or
+// source document produced an edge.
+//
+// set_context stamps every edge added afterwards; edges_in_context and the
+// *_in queries read one context only, and retract_context removes all of
+// its edges at once, e.g. to drop an inference pass and keep the observed
+// facts:
+//
+//   graph.set_context(Some(syms.intern("inference:1")));
+//   taxonomy.materialize(&mut graph);
+//   graph.set_context(None);
+//   ...
+//   graph.retract_context(syms.intern("inference:1"));
+//
+// Nodes are shared between contexts and are not removed with them.
+
+use crate::core::Sym;
+use super::graph::{Edge, EdgeId, KnowledgeGraph, NodeId};
+
+impl KnowledgeGraph {
+    // Runs `f` with `context` stamped on new edges, then restores the
+    // previous context
+    pub fn in_context<T>(&mut self, context: Sym, f: impl FnOnce(&mut Self) -> T) -> T {
+        let previous = self.context();
+        self.set_context(Some(context));
+        let out = f(self);
+        self.set_context(previous);
+        out
+    }
+
+    pub fn outgoing_edges_in(&self, node: NodeId, context: Sym) -> Vec<&Edge> {
+        self.outgoing_edges(node).into_iter().filter(|e| e.context == Some(context)).collect()
+    }
+
+    pub fn incoming_edges_in(&self, node: NodeId, context: Sym) -> Vec<&Edge> {
+        self.incoming_edges(node).into_iter().filter(|e| e.context == Some(context)).collect()
+    }
+
+    // query_triple restricted to one context, in edge id order
+    pub fn query_triple_in(&self, source_label: Option<Sym>, relation: Option<Sym>, target_label: Option<Sym>, context: Sym) -> Vec<(NodeId, EdgeId, NodeId)> {
+        self.edges_in_context(context)
+            .into_iter()
+            .filter_map(|id| self.edge(id))
+            .filter(|e| relation.is_none_or(|r| e.relation == r))
+            .filter(|e| source_label.is_none_or(|l| self.node(e.source).is_some_and(|n| n.label == l)))
+            .filter(|e| target_label.is_none_or(|l| self.node(e.target).is_some_and(|n| n.label == l)))
+            .map(|e| (e.source, e.id, e.target))
+            .collect()
+    }
+
+    // Removes every edge of the context, returns how many. Each removal is
+    // recorded, so a retraction can be rolled back or undone.
+    pub fn retract_context(&mut self, context: Sym) -> usize {
+        self.edges_in_context(context).into_iter().filter(|&id| self.remove_edge(id)).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERSON: Sym = 1;
+    const KNOWS: Sym = 2;
+    const OBSERVED: Sym = 3;
+    const INFERRED: Sym = 4;
+
+    #[test]
+    fn separates_and_retracts_contexts() {
+        let mut g = KnowledgeGraph::new().with_history();
+        let a = g.add_node(PERSON);
+        let b = g.add_node(PERSON);
+        let c = g.add_node(PERSON);
+        let plain = g.add_edge(c, KNOWS, a);
+        let (ab, bc) = g.in_context(OBSERVED, |g| (g.add_edge(a, KNOWS, b), g.add_edge(b, KNOWS, c)));
+        let ac = g.in_context(INFERRED, |g| g.add_edge(a, KNOWS, c));
+        assert_eq!(g.context(), None);
+        assert_eq!(g.edge(plain).unwrap().context, None);
+        assert_eq!(g.contexts(), vec![OBSERVED, INFERRED]);
+        assert_eq!(g.edges_in_context(OBSERVED), vec![ab, bc]);
+        assert_eq!(g.query_triple_in(Some(PERSON), Some(KNOWS), None, INFERRED), vec![(a, ac, c)]);
+        assert_eq!(g.outgoing_edges_in(a, OBSERVED).len(), 1);
+
+        assert!(g.set_edge_context(bc, Some(INFERRED)));
+        assert_eq!(g.edges_in_context(INFERRED), vec![bc, ac]);
+
+        let units = g.history_len();
+        assert_eq!(g.retract_context(INFERRED), 2);
+        assert_eq!(g.edge_count(), 2);
+        assert!(g.edges_in_context(INFERRED).is_empty());
+        g.undo(g.history_len() - units);
+        assert_eq!(g.edges_in_context(INFERRED), vec![bc, ac]);
+    }
+
+    #[test]
+    fn context_survives_binary_round_trip() {
+        let mut g = KnowledgeGraph::new();
+        let a = g.add_node(PERSON);
+        let b = g.add_node(PERSON);
+        let e = g.in_context(INFERRED, |g| g.add_edge(a, KNOWS, b));
+        let back = KnowledgeGraph::from_binary(&g.to_binary(None)).unwrap().0;
+        assert_eq!(back.edge(e).unwrap().context, Some(INFERRED));
+        assert_eq!(back.edges_in_context(INFERRED), vec![e]);
+    }
+}
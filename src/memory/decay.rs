@@ -0,0 +1,116 @@
+// Decay curves, per-label overrides and pinning.
+//
+// apply_decay lowers the weight of every node and edge by its age (ticks
+// since last access) along DecayConfig's curve:
+//
+//   Linear       w - rate * age                 (the original model)
+//   Exponential  w * e^(-rate * age)
+//   PowerLaw     w * (1 + age)^(-rate)
+//
+// An override gives a node label or an edge relation its own curve and
+// rate, and pinned nodes and edges (core ontology, user-provided facts)
+// neither decay nor get pruned:
+//
+//   let config = DecayConfig::default()
+//       .with_curve(DecayCurve::Exponential)
+//       .with_override(observation, DecayCurve::PowerLaw, 0.5);
+//   let mut graph = KnowledgeGraph::new().with_decay(config);
+//   graph.pin_node(is_a_root, true);
+
+use crate::core::Sym;
+use super::graph::{DecayConfig, EdgeId, KnowledgeGraph, NodeId};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DecayCurve {
+    Linear,
+    Exponential,
+    PowerLaw,
+}
+
+impl DecayCurve {
+    pub fn apply(self, weight: f64, rate: f64, age: f64) -> f64 {
+        match self {
+            DecayCurve::Linear => weight - rate * age,
+            DecayCurve::Exponential => weight * (-rate * age).exp(),
+            DecayCurve::PowerLaw => weight * (1.0 + age).powf(-rate),
+        }
+    }
+}
+
+impl DecayConfig {
+    pub fn with_curve(mut self, curve: DecayCurve) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    // Curve and rate for nodes labelled, or edges related by, `sym`
+    pub fn with_override(mut self, sym: Sym, curve: DecayCurve, rate: f64) -> Self {
+        self.overrides.insert(sym, (curve, rate));
+        self
+    }
+
+    pub fn rule_for(&self, sym: Sym) -> (DecayCurve, f64) {
+        self.overrides.get(&sym).copied().unwrap_or((self.curve, self.decay_rate))
+    }
+}
+
+impl KnowledgeGraph {
+    // False if the node is missing
+    pub fn pin_node(&mut self, id: NodeId, pinned: bool) -> bool {
+        self.update_node(id, |node| node.pinned = pinned)
+    }
+
+    pub fn pin_edge(&mut self, id: EdgeId, pinned: bool) -> bool {
+        self.update_edge(id, |edge| edge.pinned = pinned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERSON: Sym = 1;
+    const CLASS: Sym = 2;
+    const KNOWS: Sym = 3;
+    const IS_A: Sym = 4;
+
+    #[test]
+    fn curves_shape_the_decay() {
+        assert_eq!(DecayCurve::Linear.apply(1.0, 0.1, 3.0), 0.7);
+        assert!((DecayCurve::Exponential.apply(1.0, 0.5, 2.0) - (-1.0f64).exp()).abs() < 1e-12);
+        assert_eq!(DecayCurve::PowerLaw.apply(0.8, 1.0, 3.0), 0.2);
+        assert_eq!(DecayCurve::Exponential.apply(1.0, 0.5, 0.0), 1.0);
+    }
+
+    #[test]
+    fn overrides_and_pins_apply() {
+        let config = DecayConfig { decay_rate: 0.1, prune_threshold: 0.3, ..DecayConfig::default() }
+            .with_override(CLASS, DecayCurve::Exponential, 0.01);
+        let mut g = KnowledgeGraph::new().with_decay(config).with_history();
+        let ada = g.add_node(PERSON);
+        let bob = g.add_node(PERSON);
+        let root = g.add_node(CLASS);
+        let thing = g.add_node(CLASS);
+        let knows = g.add_edge(ada, KNOWS, bob);
+        let is_a = g.add_edge(ada, IS_A, root);
+        assert!(g.pin_node(bob, true) && g.pin_edge(is_a, true));
+        assert!(!g.pin_node(99, true));
+        // Pinning is recorded like any change
+        g.undo(1);
+        assert!(!g.edge(is_a).unwrap().pinned);
+        g.redo(1);
+        for _ in 0..5 {
+            g.tick();
+        }
+        g.apply_decay();
+
+        assert_eq!(g.node(bob).unwrap().weight, 1.0);
+        assert!((g.node(ada).unwrap().weight - 0.5).abs() < 1e-9);
+        assert!((g.node(thing).unwrap().weight - (-0.05f64).exp()).abs() < 1e-9);
+        g.apply_decay();
+        // ada goes, and her edges with her
+        assert_eq!(g.prune_weak(), 1);
+        assert!(g.node(ada).is_none() && g.edge(knows).is_none());
+        assert!(g.node(bob).is_some() && g.node(root).is_some());
+    }
+}
@@ -0,0 +1,157 @@
+// Capacity-bounded memory.
+//
+// with_capacity puts a hard limit on the node count. When an insertion
+// takes the graph over the limit, nodes are evicted (with their edges)
+// until it is back at the low-water mark, lowest policy score first:
+//
+//   let graph = KnowledgeGraph::new()
+//       .with_capacity(Capacity::new(100_000, EvictionPolicy::LeastRecentlyUsed));
+//
+// Evicting down to the low-water mark (90% of the limit unless set) keeps
+// the scan over all nodes to one per batch of insertions rather than one
+// per insertion. The nodes being inserted are never evicted by their own
+// insertion. Evictions are recorded like removals, independently of decay.
+//
+// On a graph opened with open_store (store.rs) the limit applies to the
+// nodes held in memory: evicted nodes and their edges are spilled to the
+// store instead of removed, are not recorded, and stay readable.
+
+use super::graph::{KnowledgeGraph, Node, NodeId};
+use super::store::ColdTier;
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy)]
+pub enum EvictionPolicy {
+    // Oldest last_access first
+    LeastRecentlyUsed,
+    LowestWeight,
+    // Fewest incoming plus outgoing edges first
+    LeastConnected,
+    // Lowest score first
+    Custom(fn(&KnowledgeGraph, &Node) -> f64),
+}
+
+impl EvictionPolicy {
+    fn score(&self, graph: &KnowledgeGraph, node: &Node) -> f64 {
+        match self {
+            EvictionPolicy::LeastRecentlyUsed => node.last_access as f64,
+            EvictionPolicy::LowestWeight => node.weight,
+            EvictionPolicy::LeastConnected => graph.degree(node.id) as f64,
+            EvictionPolicy::Custom(f) => f(graph, node),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Capacity {
+    pub max_nodes: usize,
+    pub low_water: usize,
+    pub policy: EvictionPolicy,
+}
+
+impl Capacity {
+    pub fn new(max_nodes: usize, policy: EvictionPolicy) -> Self {
+        Self { max_nodes, low_water: max_nodes - max_nodes / 10, policy }
+    }
+
+    pub fn with_low_water(mut self, low_water: usize) -> Self {
+        self.low_water = low_water.min(self.max_nodes);
+        self
+    }
+}
+
+impl KnowledgeGraph {
+    pub fn with_capacity(mut self, capacity: Capacity) -> Self {
+        self.set_capacity(Some(capacity));
+        self
+    }
+
+    // Applies a new limit right away; None removes the limit
+    pub fn set_capacity(&mut self, capacity: Option<Capacity>) -> usize {
+        self.capacity = capacity;
+        self.enforce_capacity()
+    }
+
+    pub fn capacity(&self) -> Option<Capacity> {
+        self.capacity
+    }
+
+    // Evicts down to the low-water mark if over the limit, returns the
+    // number of nodes evicted
+    pub fn enforce_capacity(&mut self) -> usize {
+        self.evict_over_capacity(0..0)
+    }
+
+    // Same, sparing the nodes in `keep` (the ones just inserted)
+    pub(super) fn evict_over_capacity(&mut self, keep: Range<NodeId>) -> usize {
+        let Some(capacity) = self.capacity else { return 0 };
+        let spill = self.cold.as_ref().is_some_and(ColdTier::can_spill);
+        let count = if spill { self.hot_node_count() } else { self.node_count() };
+        if count <= capacity.max_nodes {
+            return 0;
+        }
+        let excess = count - capacity.low_water;
+        // nodes() yields the in-memory records first
+        let mut scored: Vec<(f64, NodeId)> = self.nodes()
+            .take(count)
+            .filter(|n| !keep.contains(&n.id))
+            .map(|n| (capacity.policy.score(self, n), n.id))
+            .collect();
+        let excess = excess.min(scored.len());
+        if excess < scored.len() {
+            scored.select_nth_unstable_by(excess, |a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+        }
+        scored.truncate(excess);
+        if spill {
+            let spilled = scored.into_iter().filter(|&(_, id)| matches!(self.spill_node(id), Ok(true))).count();
+            if let Some(cold) = &mut self.cold {
+                cold.release();
+            }
+            return spilled;
+        }
+        scored.into_iter().filter(|&(_, id)| self.remove_node(id)).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ITEM: u32 = 1;
+    const LINK: u32 = 2;
+
+    #[test]
+    fn evicts_by_policy_down_to_low_water() {
+        let mut g = KnowledgeGraph::new().with_capacity(Capacity::new(10, EvictionPolicy::LeastRecentlyUsed).with_low_water(8));
+        let mut ids = Vec::new();
+        for _ in 0..10 {
+            ids.push(g.add_node(ITEM));
+            g.tick();
+        }
+        let _ = g.node_mut(ids[0]);
+        let newest = g.add_node(ITEM);
+        assert_eq!(g.node_count(), 8);
+        assert!(g.node(ids[0]).is_some() && g.node(newest).is_some());
+        assert!(g.node(ids[1]).is_none() && g.node(ids[3]).is_none() && g.node(ids[4]).is_some());
+
+        let hub = ids[9];
+        for &id in &ids[5..9] {
+            g.add_edge(id, LINK, hub);
+        }
+        assert_eq!(g.set_capacity(Some(Capacity::new(4, EvictionPolicy::LeastConnected).with_low_water(1))), 7);
+        assert_eq!(g.nodes().map(|n| n.id).collect::<Vec<_>>(), vec![hub]);
+    }
+
+    #[test]
+    fn custom_policy_and_bulk_inserts() {
+        fn highest_id_first(_: &KnowledgeGraph, n: &Node) -> f64 {
+            -(n.id as f64)
+        }
+        let mut g = KnowledgeGraph::new().with_capacity(Capacity::new(4, EvictionPolicy::Custom(highest_id_first)).with_low_water(4));
+        g.add_nodes_bulk([ITEM; 3]);
+        let batch = g.add_nodes_bulk([ITEM; 3]);
+        assert_eq!(g.node_count(), 4);
+        assert!(batch.clone().all(|id| g.node(id).is_some()));
+        assert!(g.node(1).is_some() && g.node(3).is_none());
+    }
+}
@@ -0,0 +1,261 @@
+// GraphML, Graphviz DOT and Prolog export.
+//
+// Labels and relations are resolved through the symbol table (unknown
+// symbols print as #id), weights are always included and `attrs` selects
+// which attributes to write. Nodes and edges are written in id order.
+//
+//   std::fs::write("mem.dot", graph.to_dot(&syms, &[name]))?;
+//   std::fs::write("mem.graphml", graph.to_graphml(&syms, &[name, since]))?;
+//   std::fs::write("mem.pl", graph.to_prolog(&syms))?;
+//
+// The Prolog dump has one `relation(source, target).` fact per edge, then
+// one `attribute(node, key, value).` fact per node attribute. A node is
+// named by its label when no other node shares it, otherwise by label_id
+// (person_3). Atoms are quoted when needed, strings are double-quoted.
+
+use crate::core::{Sym, SymbolTable};
+use super::graph::{Edge, KnowledgeGraph, Node, TermSer};
+use rustc_hash::FxHashMap;
+use std::fmt::Write;
+
+fn name(syms: &SymbolTable, sym: Sym) -> String {
+    syms.resolve(sym).map_or_else(|| format!("#{}", sym), str::to_string)
+}
+
+fn value(syms: &SymbolTable, v: &TermSer) -> String {
+    match v {
+        TermSer::Atom(a) => name(syms, *a),
+        TermSer::Int(n) => n.to_string(),
+        TermSer::Str(s) => s.clone(),
+        TermSer::Bool(b) => b.to_string(),
+    }
+}
+
+fn selected<'a>(attributes: &'a [(Sym, TermSer)], attrs: &'a [Sym]) -> impl Iterator<Item = &'a (Sym, TermSer)> {
+    attributes.iter().filter(|(k, _)| attrs.contains(k))
+}
+
+fn dot_quote(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn prolog_atom(s: &str) -> String {
+    let mut chars = s.chars();
+    let plain = chars.next().is_some_and(|c| c.is_ascii_lowercase()) && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if plain {
+        return s.to_string();
+    }
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('\'');
+    for c in s.chars() {
+        match c {
+            '\'' => out.push_str("\\'"),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out.push('\'');
+    out
+}
+
+fn prolog_value(syms: &SymbolTable, v: &TermSer) -> String {
+    match v {
+        TermSer::Atom(a) => prolog_atom(&name(syms, *a)),
+        TermSer::Int(n) => n.to_string(),
+        TermSer::Str(s) => {
+            let mut out = String::with_capacity(s.len() + 2);
+            out.push('"');
+            for c in s.chars() {
+                match c {
+                    '"' => out.push_str("\\\""),
+                    '\\' => out.push_str("\\\\"),
+                    '\n' => out.push_str("\\n"),
+                    c => out.push(c),
+                }
+            }
+            out.push('"');
+            out
+        }
+        TermSer::Bool(b) => b.to_string(),
+    }
+}
+
+impl KnowledgeGraph {
+    fn sorted_nodes(&self) -> Vec<&Node> {
+        let mut nodes: Vec<&Node> = self.nodes().collect();
+        nodes.sort_unstable_by_key(|n| n.id);
+        nodes
+    }
+
+    fn sorted_edges(&self) -> Vec<&Edge> {
+        let mut edges: Vec<&Edge> = self.edges().collect();
+        edges.sort_unstable_by_key(|e| e.id);
+        edges
+    }
+
+    pub fn to_dot(&self, syms: &SymbolTable, attrs: &[Sym]) -> String {
+        let mut out = String::from("digraph koloss {\n");
+        for node in self.sorted_nodes() {
+            let _ = write!(out, "  n{} [label={}, weight={}", node.id, dot_quote(&name(syms, node.label)), node.weight);
+            for (k, v) in selected(&node.attributes, attrs) {
+                let _ = write!(out, ", {}={}", dot_quote(&name(syms, *k)), dot_quote(&value(syms, v)));
+            }
+            out.push_str("];\n");
+        }
+        for edge in self.sorted_edges() {
+            let _ = write!(out, "  n{} -> n{} [label={}, weight={}", edge.source, edge.target, dot_quote(&name(syms, edge.relation)), edge.weight);
+            for (k, v) in selected(&edge.attributes, attrs) {
+                let _ = write!(out, ", {}={}", dot_quote(&name(syms, *k)), dot_quote(&value(syms, v)));
+            }
+            out.push_str("];\n");
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    pub fn to_prolog(&self, syms: &SymbolTable) -> String {
+        let mut shared: FxHashMap<Sym, usize> = FxHashMap::default();
+        for node in self.nodes() {
+            *shared.entry(node.label).or_default() += 1;
+        }
+        let node_name = |id: u32| match self.label_of(id) {
+            Some(label) if shared[&label] == 1 => prolog_atom(&name(syms, label)),
+            Some(label) => prolog_atom(&format!("{}_{}", name(syms, label), id)),
+            None => prolog_atom(&format!("#{}", id)),
+        };
+        let mut out = String::new();
+        for edge in self.sorted_edges() {
+            let _ = writeln!(out, "{}({}, {}).", prolog_atom(&name(syms, edge.relation)), node_name(edge.source), node_name(edge.target));
+        }
+        for node in self.sorted_nodes() {
+            for (k, v) in &node.attributes {
+                let _ = writeln!(out, "attribute({}, {}, {}).", node_name(node.id), prolog_atom(&name(syms, *k)), prolog_value(syms, v));
+            }
+        }
+        out
+    }
+
+    // GraphML type of an attribute: long or boolean when every value has
+    // that type, otherwise string
+    fn graphml_type(&self, key: Sym) -> &'static str {
+        let values = self.nodes().flat_map(|n| &n.attributes).chain(self.edges().flat_map(|e| &e.attributes))
+            .filter(|(k, _)| *k == key)
+            .map(|(_, v)| v);
+        let mut kind = None;
+        for v in values {
+            let this = match v {
+                TermSer::Int(_) => "long",
+                TermSer::Bool(_) => "boolean",
+                _ => return "string",
+            };
+            if kind.is_some_and(|k| k != this) {
+                return "string";
+            }
+            kind = Some(this);
+        }
+        kind.unwrap_or("string")
+    }
+
+    pub fn to_graphml(&self, syms: &SymbolTable, attrs: &[Sym]) -> String {
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        out.push_str("  <key id=\"label\" for=\"all\" attr.name=\"label\" attr.type=\"string\"/>\n");
+        out.push_str("  <key id=\"weight\" for=\"all\" attr.name=\"weight\" attr.type=\"double\"/>\n");
+        for (i, &key) in attrs.iter().enumerate() {
+            let _ = writeln!(out, "  <key id=\"a{}\" for=\"all\" attr.name=\"{}\" attr.type=\"{}\"/>", i, xml_escape(&name(syms, key)), self.graphml_type(key));
+        }
+        out.push_str("  <graph id=\"koloss\" edgedefault=\"directed\">\n");
+        let data = |out: &mut String, label: Sym, weight: f64, attributes: &[(Sym, TermSer)]| {
+            let _ = write!(out, "<data key=\"label\">{}</data><data key=\"weight\">{}</data>", xml_escape(&name(syms, label)), weight);
+            for (k, v) in selected(attributes, attrs) {
+                let i = attrs.iter().position(|a| a == k).expect("selected attribute");
+                let _ = write!(out, "<data key=\"a{}\">{}</data>", i, xml_escape(&value(syms, v)));
+            }
+        };
+        for node in self.sorted_nodes() {
+            let _ = write!(out, "    <node id=\"n{}\">", node.id);
+            data(&mut out, node.label, node.weight, &node.attributes);
+            out.push_str("</node>\n");
+        }
+        for edge in self.sorted_edges() {
+            let _ = write!(out, "    <edge id=\"e{}\" source=\"n{}\" target=\"n{}\">", edge.id, edge.source, edge.target);
+            data(&mut out, edge.relation, edge.weight, &edge.attributes);
+            out.push_str("</edge>\n");
+        }
+        out.push_str("  </graph>\n</graphml>\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Term;
+
+    fn sample(syms: &mut SymbolTable) -> (KnowledgeGraph, Sym, Sym) {
+        let (person, knows, name, since) = (syms.intern("person"), syms.intern("knows"), syms.intern("name"), syms.intern("since"));
+        let mut g = KnowledgeGraph::new();
+        let a = g.add_node_with_attrs(person, vec![(name, Term::Str("Ada \"the\" <first>".into()))]);
+        let b = g.add_node_with_attrs(person, vec![(since, Term::Int(3))]);
+        g.add_edge_with_attrs(a, knows, b, vec![(since, Term::Int(1833))]);
+        (g, name, since)
+    }
+
+    #[test]
+    fn writes_dot() {
+        let mut syms = SymbolTable::new();
+        let (g, name, _) = sample(&mut syms);
+        let dot = g.to_dot(&syms, &[name]);
+        assert_eq!(dot, "digraph koloss {\n  n1 [label=\"person\", weight=1, \"name\"=\"Ada \\\"the\\\" <first>\"];\n  \
+            n2 [label=\"person\", weight=1];\n  n1 -> n2 [label=\"knows\", weight=1];\n}\n");
+    }
+
+    #[test]
+    fn writes_graphml() {
+        let mut syms = SymbolTable::new();
+        let (g, name, since) = sample(&mut syms);
+        let xml = g.to_graphml(&syms, &[name, since]);
+        assert!(xml.contains("<key id=\"a1\" for=\"all\" attr.name=\"since\" attr.type=\"long\"/>"));
+        assert!(xml.contains("<node id=\"n1\"><data key=\"label\">person</data><data key=\"weight\">1</data><data key=\"a0\">Ada &quot;the&quot; &lt;first&gt;</data></node>"));
+        assert!(xml.contains("<edge id=\"e1\" source=\"n1\" target=\"n2\"><data key=\"label\">knows</data><data key=\"weight\">1</data><data key=\"a1\">1833</data></edge>"));
+        assert!(xml.ends_with("</graphml>\n"));
+    }
+
+    #[test]
+    fn writes_prolog_facts() {
+        let mut syms = SymbolTable::new();
+        let (mut g, name, since) = sample(&mut syms);
+        let (company, works_at, city) = (syms.intern("company"), syms.intern("works_at"), syms.intern("New York"));
+        let acme = g.add_node_with_attrs(company, vec![(since, Term::Bool(true)), (name, Term::atom(city))]);
+        g.add_edge(1, works_at, acme);
+        assert_eq!(g.to_prolog(&syms), "knows(person_1, person_2).\nworks_at(person_1, company).\n\
+            attribute(person_1, name, \"Ada \\\"the\\\" <first>\").\nattribute(person_2, since, 3).\n\
+            attribute(company, since, true).\nattribute(company, name, 'New York').\n");
+    }
+}
@@ -0,0 +1,248 @@
+// Change feed: subscriptions to graph mutations.
+//
+// A subscriber registers an EventPattern and gets a GraphEvent for each
+// matching change, through a callback or a channel:
+//
+//   graph.subscribe(EventPattern::new().with_label(person), |e| println!("{:?}", e.kind));
+//   let (_, events) = graph.subscribe_channel(EventPattern::new().with_relation(knows));
+//   ...
+//   for event in events.try_iter() { replicate(event.change); }
+//
+// Events are published where the write-ahead log is written: right away
+// outside a transaction, on the outermost commit inside one (a rollback
+// publishes nothing), and inverted for undo. So a subscriber sees exactly
+// the changes that took effect, in order, including removals by pruning,
+// eviction or merging.
+//
+// A pattern's label matches nodes with that label and edges with it at
+// either end; its relation matches edges only. Subscribers of a cloned
+// graph are not carried over, and a channel whose receiver is dropped is
+// unsubscribed on the next event.
+
+use crate::core::Sym;
+use super::graph::{KnowledgeGraph, NodeId};
+use super::journal::Change;
+use rustc_hash::FxHashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Receiver, Sender};
+
+pub type SubscriptionId = u64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventKind {
+    Added,
+    Changed,
+    Removed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphEvent {
+    pub kind: EventKind,
+    pub change: Change,
+}
+
+impl GraphEvent {
+    fn new(change: &Change) -> Self {
+        let (before, after) = match change {
+            Change::Node { before, after, .. } => (before.is_some(), after.is_some()),
+            Change::Edge { before, after, .. } => (before.is_some(), after.is_some()),
+        };
+        let kind = match (before, after) {
+            (false, _) => EventKind::Added,
+            (true, true) => EventKind::Changed,
+            (true, false) => EventKind::Removed,
+        };
+        Self { kind, change: change.clone() }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct EventPattern {
+    pub label: Option<Sym>,
+    pub relation: Option<Sym>,
+}
+
+impl EventPattern {
+    // Matches every change
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_label(mut self, label: Sym) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    pub fn with_relation(mut self, relation: Sym) -> Self {
+        self.relation = Some(relation);
+        self
+    }
+
+    fn matches(&self, change: &Change, labels: &FxHashMap<NodeId, Sym>) -> bool {
+        let label_of = |id: &NodeId| labels.get(id).copied();
+        match change {
+            Change::Node { before, after, .. } => {
+                self.relation.is_none()
+                    && self.label.is_none_or(|l| before.iter().chain(after).any(|n| n.label == l))
+            }
+            Change::Edge { before, after, .. } => before.iter().chain(after).any(|e| {
+                self.relation.is_none_or(|r| e.relation == r)
+                    && self.label.is_none_or(|l| label_of(&e.source) == Some(l) || label_of(&e.target) == Some(l))
+            }),
+        }
+    }
+}
+
+type Callback = Box<dyn FnMut(&GraphEvent) + Send>;
+
+enum Sink {
+    // Behind a mutex only so the graph stays Sync; never contended
+    Callback(Mutex<Callback>),
+    Channel(Sender<GraphEvent>),
+}
+
+#[derive(Default)]
+pub struct Feed {
+    next_id: SubscriptionId,
+    subscribers: Vec<(SubscriptionId, EventPattern, Sink)>,
+    // Node labels while anyone subscribes, so edge events of removed nodes
+    // still match by label
+    labels: FxHashMap<NodeId, Sym>,
+}
+
+impl fmt::Debug for Feed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Feed").field("subscribers", &self.subscribers.len()).finish()
+    }
+}
+
+impl Feed {
+    pub(super) fn is_active(&self) -> bool {
+        !self.subscribers.is_empty()
+    }
+
+    pub(super) fn publish(&mut self, change: &Change) {
+        if !self.is_active() {
+            return;
+        }
+        if let Change::Node { id, after: Some(node), .. } = change {
+            self.labels.insert(*id, node.label);
+        }
+        let mut event = None;
+        let labels = &self.labels;
+        self.subscribers.retain_mut(|(_, pattern, sink)| {
+            if !pattern.matches(change, labels) {
+                return true;
+            }
+            let event = event.get_or_insert_with(|| GraphEvent::new(change));
+            match sink {
+                Sink::Callback(f) => {
+                    (f.get_mut().unwrap_or_else(|e| e.into_inner()))(event);
+                    true
+                }
+                Sink::Channel(tx) => tx.send(event.clone()).is_ok(),
+            }
+        });
+        if let Change::Node { id, after: None, .. } = change {
+            self.labels.remove(id);
+        }
+        if !self.is_active() {
+            self.labels = FxHashMap::default();
+        }
+    }
+}
+
+impl KnowledgeGraph {
+    fn add_subscriber(&mut self, pattern: EventPattern, sink: Sink) -> SubscriptionId {
+        if !self.journal.feed().is_active() {
+            let labels = self.nodes().map(|n| (n.id, n.label)).collect();
+            self.journal.feed_mut().labels = labels;
+        }
+        let feed = self.journal.feed_mut();
+        feed.next_id += 1;
+        let id = feed.next_id;
+        feed.subscribers.push((id, pattern, sink));
+        id
+    }
+
+    pub fn subscribe<F: FnMut(&GraphEvent) + Send + 'static>(&mut self, pattern: EventPattern, callback: F) -> SubscriptionId {
+        self.add_subscriber(pattern, Sink::Callback(Mutex::new(Box::new(callback))))
+    }
+
+    pub fn subscribe_channel(&mut self, pattern: EventPattern) -> (SubscriptionId, Receiver<GraphEvent>) {
+        let (tx, rx) = mpsc::channel();
+        (self.add_subscriber(pattern, Sink::Channel(tx)), rx)
+    }
+
+    // False if no such subscription
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let feed = self.journal.feed_mut();
+        let before = feed.subscribers.len();
+        feed.subscribers.retain(|(s, _, _)| *s != id);
+        if !feed.is_active() {
+            feed.labels = FxHashMap::default();
+        }
+        feed.subscribers.len() < before
+    }
+
+    pub fn subscriber_count(&self) -> usize {
+        self.journal.feed().subscribers.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    const PERSON: Sym = 1;
+    const CITY: Sym = 2;
+    const KNOWS: Sym = 3;
+    const LIVES_IN: Sym = 4;
+
+    #[test]
+    fn delivers_matching_committed_changes() {
+        let mut g = KnowledgeGraph::new().with_history();
+        let paris = g.add_node(CITY);
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = Arc::clone(&seen);
+        g.subscribe(EventPattern::new().with_label(PERSON), move |e| sink.lock().unwrap().push(e.kind));
+        let (id, knows) = g.subscribe_channel(EventPattern::new().with_relation(KNOWS));
+
+        let ada = g.add_node(PERSON);
+        let bob = g.add_node(PERSON);
+        g.add_edge(ada, KNOWS, bob);
+        g.add_edge(paris, LIVES_IN, paris);
+        assert_eq!(knows.try_iter().count(), 1);
+        g.begin();
+        g.add_edge(bob, KNOWS, ada);
+        assert_eq!(knows.try_iter().count(), 0);
+        g.rollback();
+        assert_eq!(knows.try_iter().count(), 0);
+
+        g.remove_node(ada);
+        let kinds: Vec<EventKind> = knows.try_iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, vec![EventKind::Removed]);
+        assert_eq!(*seen.lock().unwrap(), vec![
+            EventKind::Added, EventKind::Added, EventKind::Added,
+            EventKind::Removed, EventKind::Removed,
+        ]);
+
+        // The node comes back first, then its edge
+        g.undo(2);
+        assert_eq!(knows.try_iter().next().map(|e| e.kind), Some(EventKind::Added));
+        assert!(g.unsubscribe(id));
+        drop(knows);
+        assert_eq!(g.subscriber_count(), 1);
+    }
+
+    #[test]
+    fn drops_channels_whose_receiver_is_gone() {
+        let mut g = KnowledgeGraph::new();
+        let (_, events) = g.subscribe_channel(EventPattern::new());
+        drop(events);
+        g.add_node(PERSON);
+        assert_eq!(g.subscriber_count(), 0);
+    }
+}
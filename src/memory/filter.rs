@@ -0,0 +1,223 @@
+// Attribute predicates for triple queries.
+//
+// query_triple_where is query_triple plus predicates on the source node, the
+// edge and the target node. When an edge predicate is on an attribute with
+// an index (KnowledgeGraph::index_edge_attr) the candidate edges come from
+// that index; failing that, a node predicate with a (label, attribute) index
+// (KnowledgeGraph::index_node_attr) gives the candidate nodes and their
+// edges, otherwise the relation index or a scan does. Every predicate is
+// then checked on each candidate.
+//
+//   let filter = TripleFilter::new()
+//       .with_edge(AttrPredicate::Range(since, 2010, 2020))
+//       .with_target(AttrPredicate::Exists(country));
+//   let hits = graph.query_triple_where(None, Some(works_at), None, &filter);
+//
+// find_nodes applies the same predicates to the nodes of one label:
+//
+//   graph.index_node_attr(person, age);
+//   let thirties = graph.find_nodes(person, &[AttrPredicate::Range(age, 30, 39)]);
+
+use crate::core::Sym;
+use super::graph::{Edge, EdgeId, KnowledgeGraph, Node, NodeId, TermSer};
+use std::ops::Bound;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttrPredicate {
+    Exists(Sym),
+    Equals(Sym, TermSer),
+    // Integer attribute within [min, max]
+    Range(Sym, i64, i64),
+}
+
+impl AttrPredicate {
+    pub fn key(&self) -> Sym {
+        match self {
+            AttrPredicate::Exists(k) | AttrPredicate::Equals(k, _) | AttrPredicate::Range(k, _, _) => *k,
+        }
+    }
+
+    pub fn matches(&self, attributes: &[(Sym, TermSer)]) -> bool {
+        let value = attributes.iter().find(|(k, _)| *k == self.key()).map(|(_, v)| v);
+        match (self, value) {
+            (_, None) => false,
+            (AttrPredicate::Exists(_), Some(_)) => true,
+            (AttrPredicate::Equals(_, want), Some(v)) => v == want,
+            (AttrPredicate::Range(_, min, max), Some(TermSer::Int(n))) => min <= n && n <= max,
+            (AttrPredicate::Range(..), Some(_)) => false,
+        }
+    }
+
+    // Value range an index lookup covers
+    fn bounds(&self) -> (Bound<TermSer>, Bound<TermSer>) {
+        match self {
+            AttrPredicate::Exists(_) => (Bound::Unbounded, Bound::Unbounded),
+            AttrPredicate::Equals(_, v) => (Bound::Included(v.clone()), Bound::Included(v.clone())),
+            AttrPredicate::Range(_, min, max) => (Bound::Included(TermSer::Int(*min)), Bound::Included(TermSer::Int(*max))),
+        }
+    }
+
+    // Index lookup for this predicate, None when the key is not indexed
+    fn lookup(&self, graph: &KnowledgeGraph) -> Option<Vec<EdgeId>> {
+        graph.edges_with_attr(self.key(), self.bounds())
+    }
+
+    fn node_lookup(&self, graph: &KnowledgeGraph, label: Sym) -> Option<Vec<NodeId>> {
+        graph.nodes_with_attr(label, self.key(), self.bounds())
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TripleFilter {
+    pub source: Vec<AttrPredicate>,
+    pub edge: Vec<AttrPredicate>,
+    pub target: Vec<AttrPredicate>,
+}
+
+impl TripleFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_source(mut self, p: AttrPredicate) -> Self {
+        self.source.push(p);
+        self
+    }
+
+    pub fn with_edge(mut self, p: AttrPredicate) -> Self {
+        self.edge.push(p);
+        self
+    }
+
+    pub fn with_target(mut self, p: AttrPredicate) -> Self {
+        self.target.push(p);
+        self
+    }
+
+    fn accepts_node(predicates: &[AttrPredicate], node: &Node, label: Option<Sym>) -> bool {
+        label.is_none_or(|l| node.label == l) && predicates.iter().all(|p| p.matches(&node.attributes))
+    }
+
+    fn accepts_edge(&self, edge: &Edge, relation: Option<Sym>) -> bool {
+        relation.is_none_or(|r| edge.relation == r) && self.edge.iter().all(|p| p.matches(&edge.attributes))
+    }
+}
+
+impl KnowledgeGraph {
+    pub fn query_triple_where(&self, source_label: Option<Sym>, relation: Option<Sym>, target_label: Option<Sym>, filter: &TripleFilter) -> Vec<(NodeId, EdgeId, NodeId)> {
+        let indexed_nodes = |predicates: &[AttrPredicate], label: Option<Sym>| {
+            label.and_then(|l| predicates.iter().find_map(|p| p.node_lookup(self, l)))
+        };
+        let mut candidates: Vec<EdgeId> = if let Some(ids) = filter.edge.iter().find_map(|p| p.lookup(self)) {
+            ids
+        } else if let Some(nodes) = indexed_nodes(&filter.source, source_label) {
+            nodes.into_iter().flat_map(|n| self.outgoing_edges(n)).map(|e| e.id).collect()
+        } else if let Some(nodes) = indexed_nodes(&filter.target, target_label) {
+            nodes.into_iter().flat_map(|n| self.incoming_edges(n)).map(|e| e.id).collect()
+        } else if let Some(r) = relation {
+            self.edges_by_relation(r)
+        } else {
+            self.edges().map(|e| e.id).collect()
+        };
+        candidates.sort_unstable();
+        candidates.dedup();
+        candidates.into_iter()
+            .filter_map(|id| self.edge(id))
+            .filter(|e| filter.accepts_edge(e, relation))
+            .filter(|e| self.node(e.source).is_some_and(|n| TripleFilter::accepts_node(&filter.source, n, source_label)))
+            .filter(|e| self.node(e.target).is_some_and(|n| TripleFilter::accepts_node(&filter.target, n, target_label)))
+            .map(|e| (e.source, e.id, e.target))
+            .collect()
+    }
+
+    // Nodes with the label matching every predicate, sorted. Candidates
+    // come from the first indexed predicate, else from the label index.
+    pub fn find_nodes(&self, label: Sym, predicates: &[AttrPredicate]) -> Vec<NodeId> {
+        let candidates = predicates.iter()
+            .find_map(|p| p.node_lookup(self, label))
+            .unwrap_or_else(|| self.nodes_by_label(label));
+        candidates.into_iter()
+            .filter(|&id| self.node(id).is_some_and(|n| TripleFilter::accepts_node(predicates, n, Some(label))))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Term;
+
+    const PERSON: Sym = 1;
+    const COMPANY: Sym = 2;
+    const WORKS_AT: Sym = 3;
+    const SINCE: Sym = 4;
+    const ROLE: Sym = 5;
+    const CEO: Sym = 6;
+    const CITY: Sym = 7;
+
+    fn staff() -> (KnowledgeGraph, Vec<EdgeId>) {
+        let mut g = KnowledgeGraph::new();
+        let acme = g.add_node_with_attrs(COMPANY, vec![(CITY, Term::atom(CITY))]);
+        let initech = g.add_node(COMPANY);
+        let edges = [(2005, acme), (2012, acme), (2015, initech)].iter().map(|&(year, company)| {
+            let p = g.add_node(PERSON);
+            g.add_edge_with_attrs(p, WORKS_AT, company, vec![(SINCE, Term::Int(year))])
+        }).collect();
+        (g, edges)
+    }
+
+    #[test]
+    fn filters_on_edge_and_node_attributes() {
+        let (mut g, e) = staff();
+        let ids = |g: &KnowledgeGraph, f: &TripleFilter| -> Vec<EdgeId> {
+            g.query_triple_where(Some(PERSON), Some(WORKS_AT), None, f).iter().map(|t| t.1).collect()
+        };
+        let recent = TripleFilter::new().with_edge(AttrPredicate::Range(SINCE, 2010, 2020));
+        assert_eq!(ids(&g, &recent), vec![e[1], e[2]]);
+        let in_city = recent.clone().with_target(AttrPredicate::Exists(CITY));
+        assert_eq!(ids(&g, &in_city), vec![e[1]]);
+
+        g.set_edge_attr(e[2], ROLE, &Term::atom(CEO));
+        let ceo = TripleFilter::new().with_edge(AttrPredicate::Equals(ROLE, TermSer::Atom(CEO)));
+        assert_eq!(ids(&g, &ceo), vec![e[2]]);
+        assert!(ids(&g, &TripleFilter::new().with_source(AttrPredicate::Exists(ROLE))).is_empty());
+    }
+
+    #[test]
+    fn indexed_lookups_follow_updates() {
+        let (mut g, e) = staff();
+        g.index_edge_attr(SINCE);
+        assert_eq!(g.edges_with_attr(SINCE, TermSer::Int(2000)..TermSer::Int(2013)), Some(vec![e[0], e[1]]));
+        assert_eq!(g.edges_with_attr(ROLE, ..), None);
+
+        g.set_edge_attr(e[0], SINCE, &Term::Int(2019));
+        g.remove_edge(e[2]);
+        let recent = TripleFilter::new().with_edge(AttrPredicate::Range(SINCE, 2010, 2020));
+        let hits: Vec<EdgeId> = g.query_triple_where(None, None, None, &recent).iter().map(|t| t.1).collect();
+        assert_eq!(hits, vec![e[0], e[1]]);
+    }
+
+    #[test]
+    fn node_indexes_answer_range_queries() {
+        const AGE: Sym = 8;
+        let mut g = KnowledgeGraph::new();
+        let people: Vec<NodeId> = [25, 31, 38, 44].iter().map(|&age| g.add_node_with_attrs(PERSON, vec![(AGE, Term::Int(age))])).collect();
+        g.add_node_with_attrs(COMPANY, vec![(AGE, Term::Int(35))]);
+        let thirties = [AttrPredicate::Range(AGE, 30, 40)];
+        assert_eq!(g.nodes_with_attr(PERSON, AGE, ..), None);
+        assert_eq!(g.find_nodes(PERSON, &thirties), vec![people[1], people[2]]);
+
+        g.index_node_attr(PERSON, AGE);
+        assert_eq!(g.nodes_with_attr(PERSON, AGE, TermSer::Int(30)..=TermSer::Int(40)), Some(vec![people[1], people[2]]));
+        g.set_node_attr(people[0], AGE, &Term::Int(33));
+        g.remove_node(people[2]);
+        let late = g.add_node_with_attrs(PERSON, vec![(AGE, Term::Int(39))]);
+        assert_eq!(g.find_nodes(PERSON, &thirties), vec![people[0], people[1], late]);
+        assert_eq!(g.find_nodes(PERSON, &[AttrPredicate::Equals(AGE, TermSer::Int(44))]), vec![people[3]]);
+
+        let company = g.add_node(COMPANY);
+        g.add_edge(people[1], WORKS_AT, company);
+        let filter = TripleFilter::new().with_source(AttrPredicate::Range(AGE, 30, 32));
+        assert_eq!(g.query_triple_where(Some(PERSON), Some(WORKS_AT), None, &filter).len(), 1);
+    }
+}
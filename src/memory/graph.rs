@@ -1,11 +1,23 @@
 use crate::core::{Term, Sym, SymbolTable};
+use super::access::ReadLog;
+use super::ann::SimilarityIndex;
+use super::decay::DecayCurve;
+use super::eviction::Capacity;
+use super::journal::{Change, Journal};
+pub use super::mining::{GraphPattern, InferredRule};
+use super::schema::Schema;
+use super::store::ColdTier;
+use super::wal::WalRecord;
 use rustc_hash::FxHashMap;
 use serde::{Serialize, Deserialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::ops::{Range, RangeBounds};
 
 pub type NodeId = u32;
 pub type EdgeId = u32;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Node {
     pub id: NodeId,
     pub label: Sym,
@@ -14,9 +26,15 @@ pub struct Node {
     pub last_access: u64,
     pub access_count: u32,
     pub weight: f64,
+    // Edge this node makes statements about, see reify.rs
+    #[serde(default)]
+    pub reifies: Option<EdgeId>,
+    // Exempt from decay and pruning
+    #[serde(default)]
+    pub pinned: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Edge {
     pub id: EdgeId,
     pub relation: Sym,
@@ -27,10 +45,37 @@ pub struct Edge {
     pub created_at: u64,
     pub last_access: u64,
     pub access_count: u32,
+    // Ticks during which the fact holds, [valid_from, valid_to); None is unbounded
+    #[serde(default)]
+    pub valid_from: Option<u64>,
+    #[serde(default)]
+    pub valid_to: Option<u64>,
+    // Named graph the edge belongs to (source document, ingestion run,
+    // inference pass...), see context.rs
+    #[serde(default)]
+    pub context: Option<Sym>,
+    // Exempt from decay and pruning
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+impl Edge {
+    pub fn is_valid_at(&self, tick: u64) -> bool {
+        self.valid_from.is_none_or(|f| f <= tick) && self.valid_to.is_none_or(|t| tick < t)
+    }
+
+    // Valid at some tick of [from, to)
+    pub fn overlaps(&self, from: u64, to: u64) -> bool {
+        self.valid_from.is_none_or(|f| f < to) && self.valid_to.is_none_or(|t| from < t) && from < to
+    }
+
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.valid_to.is_some_and(|t| t <= now)
+    }
 }
 
 // Serializable term subset (for persistence)
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum TermSer {
     Atom(Sym),
     Int(i64),
@@ -74,6 +119,10 @@ pub struct DecayConfig {
     pub min_weight: f64,
     pub prune_threshold: f64,
     pub access_boost: f64,
+    // Shape of the decay, see decay.rs
+    pub curve: DecayCurve,
+    // (curve, rate) replacing the above for a node label or edge relation
+    pub overrides: FxHashMap<Sym, (DecayCurve, f64)>,
 }
 
 impl Default for DecayConfig {
@@ -83,6 +132,8 @@ impl Default for DecayConfig {
             min_weight: 0.0,
             prune_threshold: 0.05,
             access_boost: 0.2,
+            curve: DecayCurve::Linear,
+            overrides: FxHashMap::default(),
         }
     }
 }
@@ -98,10 +149,29 @@ pub struct KnowledgeGraph {
     incoming: FxHashMap<NodeId, Vec<EdgeId>>,
     label_index: FxHashMap<Sym, Vec<NodeId>>,
     relation_index: FxHashMap<Sym, Vec<EdgeId>>,
+    // Optional, per attribute key: value -> edges
+    edge_attr_index: FxHashMap<Sym, BTreeMap<TermSer, Vec<EdgeId>>>,
+    // Optional, per (label, attribute key): value -> nodes
+    node_attr_index: FxHashMap<(Sym, Sym), BTreeMap<TermSer, Vec<NodeId>>>,
+    context_index: FxHashMap<Sym, Vec<EdgeId>>,
+    // Reified edge -> its statement node
+    statement_index: FxHashMap<EdgeId, NodeId>,
+    // Context stamped on new edges
+    context: Option<Sym>,
     next_node_id: NodeId,
     next_edge_id: EdgeId,
     tick: u64,
     decay_config: DecayConfig,
+    // Hard node limit, see eviction.rs
+    pub(super) capacity: Option<Capacity>,
+    // Nearest-neighbour index over embed_node, see ann.rs
+    pub(super) similarity: Option<SimilarityIndex>,
+    // Declared constraints, see schema.rs
+    pub(super) schema: Option<Schema>,
+    pub(super) journal: Journal,
+    pub(super) reads: ReadLog,
+    // Records spilled to a disk store, see store.rs
+    pub(super) cold: Option<ColdTier>,
 }
 
 impl KnowledgeGraph {
@@ -113,10 +183,21 @@ impl KnowledgeGraph {
             incoming: FxHashMap::default(),
             label_index: FxHashMap::default(),
             relation_index: FxHashMap::default(),
+            edge_attr_index: FxHashMap::default(),
+            node_attr_index: FxHashMap::default(),
+            context_index: FxHashMap::default(),
+            statement_index: FxHashMap::default(),
+            context: None,
             next_node_id: 1,
             next_edge_id: 1,
             tick: 0,
             decay_config: DecayConfig::default(),
+            capacity: None,
+            similarity: None,
+            schema: None,
+            journal: Journal::default(),
+            reads: ReadLog::default(),
+            cold: None,
         }
     }
 
@@ -125,12 +206,20 @@ impl KnowledgeGraph {
         self
     }
 
+    pub fn set_decay(&mut self, config: DecayConfig) {
+        self.decay_config = config;
+    }
+
+    pub fn decay_config(&self) -> &DecayConfig {
+        &self.decay_config
+    }
+
     // --- Persistence ---
 
     pub fn save(&self) -> GraphSnapshot {
         GraphSnapshot {
-            nodes: self.nodes.values().cloned().collect(),
-            edges: self.edges.values().cloned().collect(),
+            nodes: self.nodes().cloned().collect(),
+            edges: self.edges().cloned().collect(),
             next_node_id: self.next_node_id,
             next_edge_id: self.next_edge_id,
             tick: self.tick,
@@ -142,24 +231,43 @@ impl KnowledgeGraph {
     }
 
     pub fn load(snapshot: &GraphSnapshot) -> Self {
+        Self::from_snapshot(snapshot.clone())
+    }
+
+    pub fn from_snapshot(snapshot: GraphSnapshot) -> Self {
         let mut g = Self::new();
         g.next_node_id = snapshot.next_node_id;
         g.next_edge_id = snapshot.next_edge_id;
         g.tick = snapshot.tick;
+        g.nodes.reserve(snapshot.nodes.len());
+        g.edges.reserve(snapshot.edges.len());
 
-        for node in &snapshot.nodes {
-            g.nodes.insert(node.id, node.clone());
+        for node in snapshot.nodes {
+            g.next_node_id = g.next_node_id.max(node.id + 1);
             g.label_index.entry(node.label).or_default().push(node.id);
+            if let Some(edge) = node.reifies {
+                g.statement_index.insert(edge, node.id);
+            }
+            g.nodes.insert(node.id, node);
         }
-        for edge in &snapshot.edges {
-            g.edges.insert(edge.id, edge.clone());
+        for edge in snapshot.edges {
+            g.next_edge_id = g.next_edge_id.max(edge.id + 1);
             g.outgoing.entry(edge.source).or_default().push(edge.id);
             g.incoming.entry(edge.target).or_default().push(edge.id);
             g.relation_index.entry(edge.relation).or_default().push(edge.id);
+            if let Some(ctx) = edge.context {
+                g.context_index.entry(ctx).or_default().push(edge.id);
+            }
+            g.edges.insert(edge.id, edge);
         }
         g
     }
 
+    // Ids the next add_node and add_edge will use
+    pub fn next_ids(&self) -> (NodeId, EdgeId) {
+        (self.next_node_id, self.next_edge_id)
+    }
+
     pub fn load_json(json: &str) -> Option<Self> {
         serde_json::from_str::<GraphSnapshot>(json).ok().map(|s| Self::load(&s))
     }
@@ -167,23 +275,42 @@ impl KnowledgeGraph {
     // --- Temporal Decay ---
 
     pub fn apply_decay(&mut self) {
-        let rate = self.decay_config.decay_rate;
-        let min = self.decay_config.min_weight;
+        self.flush_reads();
+        let config = &self.decay_config;
+        let min = config.min_weight;
+
+        let recording = self.journal.is_recording();
+        let mut changes = Vec::new();
 
-        for node in self.nodes.values_mut() {
+        for node in self.nodes.values_mut().filter(|n| !n.pinned) {
+            let before = recording.then(|| node.clone());
             let age = self.tick.saturating_sub(node.last_access) as f64;
-            node.weight = (node.weight - rate * age).max(min);
+            let (curve, rate) = config.rule_for(node.label);
+            node.weight = curve.apply(node.weight, rate, age).max(min);
+            if let Some(before) = before.filter(|b| b.weight != node.weight) {
+                changes.push(Change::Node { id: node.id, before: Some(before), after: Some(node.clone()) });
+            }
+        }
+        for edge in self.edges.values_mut().filter(|e| !e.pinned) {
+            let before = recording.then(|| edge.clone());
+            // An expired fact ages from its end, however often it is read
+            let since = edge.valid_to.filter(|_| edge.is_expired(self.tick)).map_or(edge.last_access, |t| t.min(edge.last_access));
+            let age = self.tick.saturating_sub(since) as f64;
+            let (curve, rate) = config.rule_for(edge.relation);
+            edge.weight = curve.apply(edge.weight, rate, age).max(min);
+            if let Some(before) = before.filter(|b| b.weight != edge.weight) {
+                changes.push(Change::Edge { id: edge.id, before: Some(before), after: Some(edge.clone()) });
+            }
         }
-        for edge in self.edges.values_mut() {
-            let age = self.tick.saturating_sub(edge.last_access) as f64;
-            edge.weight = (edge.weight - rate * age).max(min);
+        for change in changes {
+            self.journal.record(change);
         }
     }
 
     pub fn prune_weak(&mut self) -> usize {
         let threshold = self.decay_config.prune_threshold;
         let weak_nodes: Vec<NodeId> = self.nodes.values()
-            .filter(|n| n.weight < threshold)
+            .filter(|n| n.weight < threshold && !n.pinned)
             .map(|n| n.id)
             .collect();
         let mut removed = 0;
@@ -192,7 +319,7 @@ impl KnowledgeGraph {
         }
 
         let weak_edges: Vec<EdgeId> = self.edges.values()
-            .filter(|e| e.weight < threshold)
+            .filter(|e| e.weight < threshold && !e.pinned)
             .map(|e| e.id)
             .collect();
         for id in weak_edges {
@@ -202,110 +329,268 @@ impl KnowledgeGraph {
     }
 
     fn touch_node(&mut self, id: NodeId) {
-        if let Some(node) = self.nodes.get_mut(&id) {
-            node.last_access = self.tick;
-            node.access_count += 1;
-            node.weight = (node.weight + self.decay_config.access_boost).min(1.0);
-        }
+        self.touch_node_times(id, 1);
+    }
+
+    // `times` accesses at once, each boosting the weight
+    pub(super) fn touch_node_times(&mut self, id: NodeId, times: u32) -> bool {
+        let (tick, boost) = (self.tick, self.decay_config.access_boost);
+        self.update_node(id, |node| {
+            node.last_access = tick;
+            node.access_count += times;
+            node.weight = (node.weight + boost * times as f64).min(1.0);
+        })
     }
 
     pub fn touch_edge(&mut self, id: EdgeId) {
-        if let Some(edge) = self.edges.get_mut(&id) {
-            edge.last_access = self.tick;
+        let (tick, boost) = (self.tick, self.decay_config.access_boost);
+        self.update_edge(id, |edge| {
+            edge.last_access = tick;
             edge.access_count += 1;
-            edge.weight = (edge.weight + self.decay_config.access_boost).min(1.0);
-        }
-    }
-
-    // --- Graph Inference ---
-
-    pub fn extract_patterns(&self) -> Vec<GraphPattern> {
-        let mut patterns = Vec::new();
-
-        // Pattern 1: Frequent relation pairs (A--r1-->B--r2-->C)
-        for edge1 in self.edges.values() {
-            if let Some(outgoing) = self.outgoing.get(&edge1.target) {
-                for &eid2 in outgoing {
-                    if let Some(edge2) = self.edges.get(&eid2) {
-                        let s_label = self.nodes.get(&edge1.source).map(|n| n.label).unwrap_or(0);
-                        let m_label = self.nodes.get(&edge1.target).map(|n| n.label).unwrap_or(0);
-                        let t_label = self.nodes.get(&edge2.target).map(|n| n.label).unwrap_or(0);
-                        patterns.push(GraphPattern::Chain {
-                            source_label: s_label,
-                            rel1: edge1.relation,
-                            mid_label: m_label,
-                            rel2: edge2.relation,
-                            target_label: t_label,
-                        });
-                    }
-                }
+            if !edge.is_expired(tick) {
+                edge.weight = (edge.weight + boost).min(1.0);
             }
+        });
+    }
+
+    // --- Change log primitives ---
+
+    // Mutate a node in place, recording the change
+    pub(super) fn update_node<F: FnOnce(&mut Node)>(&mut self, id: NodeId, f: F) -> bool {
+        self.promote_node(id);
+        let Some(node) = self.nodes.get_mut(&id) else {
+            return false;
+        };
+        let before = self.journal.is_recording().then(|| node.clone());
+        f(node);
+        if let Some(before) = before {
+            let after = Some(node.clone());
+            self.journal.record(Change::Node { id, before: Some(before), after });
         }
+        true
+    }
 
-        // Pattern 2: Shared targets (A--r-->C and B--r-->C)
-        for (&target, incoming) in &self.incoming {
-            if incoming.len() >= 2 {
-                let t_label = self.nodes.get(&target).map(|n| n.label).unwrap_or(0);
-                let mut rels: FxHashMap<Sym, Vec<Sym>> = FxHashMap::default();
-                for &eid in incoming {
-                    if let Some(edge) = self.edges.get(&eid) {
-                        let s_label = self.nodes.get(&edge.source).map(|n| n.label).unwrap_or(0);
-                        rels.entry(edge.relation).or_default().push(s_label);
-                    }
-                }
-                for (rel, sources) in rels {
-                    if sources.len() >= 2 {
-                        patterns.push(GraphPattern::SharedTarget {
-                            relation: rel,
-                            target_label: t_label,
-                            source_labels: sources,
-                        });
-                    }
-                }
+    // Mutate an edge in place, recording the change. Attribute edits go
+    // through set_edge_attr, which also maintains the attribute index.
+    pub(super) fn update_edge<F: FnOnce(&mut Edge)>(&mut self, id: EdgeId, f: F) -> bool {
+        self.promote_edge(id);
+        let Some(edge) = self.edges.get_mut(&id) else {
+            return false;
+        };
+        let before = self.journal.is_recording().then(|| edge.clone());
+        f(edge);
+        if let Some(before) = before {
+            let after = Some(edge.clone());
+            self.journal.record(Change::Edge { id, before: Some(before), after });
+        }
+        true
+    }
+
+    // Replace a node wholesale (None removes it) with its label index entry.
+    // Not recorded: this is how the journal replays and reverts changes.
+    pub(super) fn put_node(&mut self, id: NodeId, node: Option<Node>) {
+        self.promote_node(id);
+        if let Some(old) = self.nodes.remove(&id) {
+            if let Some(ids) = self.label_index.get_mut(&old.label) {
+                ids.retain(|n| *n != id);
             }
+            self.unindex_node_attrs(&old);
+            self.unindex_statement(&old);
+        }
+        if let Some(node) = node {
+            self.index_node(&node);
+            self.nodes.insert(id, node);
         }
+    }
 
-        patterns
+    // Index entries of a node, wherever its record is kept
+    pub(super) fn index_node(&mut self, node: &Node) {
+        let id = node.id;
+        self.next_node_id = self.next_node_id.max(id + 1);
+        let ids = self.label_index.entry(node.label).or_default();
+        ids.insert(ids.partition_point(|&n| n < id), id);
+        self.index_node_attrs(node);
+        if let Some(edge) = node.reifies {
+            self.statement_index.insert(edge, id);
+        }
     }
 
-    pub fn infer_rules(&self, syms: &SymbolTable) -> Vec<InferredRule> {
-        let patterns = self.extract_patterns();
-        let mut rules = Vec::new();
+    fn unindex_statement(&mut self, node: &Node) {
+        if let Some(edge) = node.reifies {
+            if self.statement_index.get(&edge) == Some(&node.id) {
+                self.statement_index.remove(&edge);
+            }
+        }
+    }
 
-        for pattern in &patterns {
-            match pattern {
-                GraphPattern::Chain { source_label, rel1, mid_label: _, rel2, target_label } => {
-                    // If A--r1-->B--r2-->C appears, infer: transitive_r1_r2(A, C) :- r1(A, B), r2(B, C)
-                    let r1_name = syms.resolve(*rel1).unwrap_or("?");
-                    let r2_name = syms.resolve(*rel2).unwrap_or("?");
-                    rules.push(InferredRule {
-                        head: format!("chain_{}_{}", r1_name, r2_name),
-                        head_sym: (*source_label, *target_label),
-                        body_rels: vec![*rel1, *rel2],
-                        confidence: 0.5 + 0.1 * (self.edges.len() as f64).min(5.0),
-                        support: 1,
-                    });
-                }
-                GraphPattern::SharedTarget { relation, target_label, source_labels } => {
-                    let r_name = syms.resolve(*relation).unwrap_or("?");
-                    rules.push(InferredRule {
-                        head: format!("shared_{}", r_name),
-                        head_sym: (*target_label, *relation),
-                        body_rels: vec![*relation],
-                        confidence: 0.3 + 0.1 * (source_labels.len() as f64).min(7.0),
-                        support: source_labels.len(),
-                    });
+    fn index_node_attrs(&mut self, node: &Node) {
+        for (key, value) in &node.attributes {
+            if let Some(index) = self.node_attr_index.get_mut(&(node.label, *key)) {
+                let ids = index.entry(value.clone()).or_default();
+                if let Err(at) = ids.binary_search(&node.id) {
+                    ids.insert(at, node.id);
                 }
             }
         }
-        rules
+    }
+
+    fn unindex_node_attrs(&mut self, node: &Node) {
+        for (key, value) in &node.attributes {
+            if let Some(ids) = self.node_attr_index.get_mut(&(node.label, *key)).and_then(|index| index.get_mut(value)) {
+                ids.retain(|n| *n != node.id);
+            }
+        }
+    }
+
+    // Attribute edit through update_node, keeping the node attribute
+    // indexes in step
+    fn update_node_attrs<F: FnOnce(&mut Node)>(&mut self, id: NodeId, f: F) -> bool {
+        self.promote_node(id);
+        let before = (!self.node_attr_index.is_empty()).then(|| self.nodes.get(&id).cloned()).flatten();
+        if !self.update_node(id, f) {
+            return false;
+        }
+        if let Some(before) = before {
+            self.unindex_node_attrs(&before);
+            let after = self.nodes[&id].clone();
+            self.index_node_attrs(&after);
+        }
+        true
+    }
+
+    // Same for an edge and all its index entries
+    pub(super) fn put_edge(&mut self, id: EdgeId, edge: Option<Edge>) {
+        self.promote_edge(id);
+        if let Some(old) = self.edges.remove(&id) {
+            self.unindex_edge(&old);
+        }
+        if let Some(edge) = edge {
+            self.index_edge(&edge);
+            self.edges.insert(id, edge);
+        }
+    }
+
+    // Same for an edge
+    pub(super) fn index_edge(&mut self, edge: &Edge) {
+        let id = edge.id;
+        self.next_edge_id = self.next_edge_id.max(id + 1);
+        for ids in [
+            self.outgoing.entry(edge.source).or_default(),
+            self.incoming.entry(edge.target).or_default(),
+            self.relation_index.entry(edge.relation).or_default(),
+        ] {
+            ids.insert(ids.partition_point(|&e| e < id), id);
+        }
+        if let Some(ctx) = edge.context {
+            let ids = self.context_index.entry(ctx).or_default();
+            ids.insert(ids.partition_point(|&e| e < id), id);
+        }
+        for (key, value) in &edge.attributes {
+            if let Some(index) = self.edge_attr_index.get_mut(key) {
+                let ids = index.entry(value.clone()).or_default();
+                ids.insert(ids.partition_point(|&e| e < id), id);
+            }
+        }
+    }
+
+    fn unindex_edge(&mut self, edge: &Edge) {
+        let id = edge.id;
+        if let Some(out) = self.outgoing.get_mut(&edge.source) {
+            out.retain(|e| *e != id);
+        }
+        if let Some(inc) = self.incoming.get_mut(&edge.target) {
+            inc.retain(|e| *e != id);
+        }
+        if let Some(rels) = self.relation_index.get_mut(&edge.relation) {
+            rels.retain(|e| *e != id);
+        }
+        if let Some(ids) = edge.context.and_then(|ctx| self.context_index.get_mut(&ctx)) {
+            ids.retain(|e| *e != id);
+        }
+        for (key, value) in &edge.attributes {
+            if let Some(ids) = self.edge_attr_index.get_mut(key).and_then(|index| index.get_mut(value)) {
+                ids.retain(|e| *e != id);
+            }
+        }
+    }
+
+    // --- Cold tier (store.rs) ---
+
+    fn node_record(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(&id).or_else(|| self.cold.as_ref()?.node(id))
+    }
+
+    fn edge_record(&self, id: EdgeId) -> Option<&Edge> {
+        self.edges.get(&id).or_else(|| self.cold.as_ref()?.edge(id))
+    }
+
+    fn node_ids(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.nodes.keys().copied().chain(self.cold.iter().flat_map(ColdTier::node_ids))
+    }
+
+    // A cold record is moved back into memory before it changes
+    fn promote_node(&mut self, id: NodeId) {
+        if let Some(cold) = self.cold.as_mut().filter(|c| c.contains_node(id)) {
+            if let Some(node) = cold.take_node(id) {
+                self.nodes.insert(id, node);
+            }
+        }
+    }
+
+    fn promote_edge(&mut self, id: EdgeId) {
+        if let Some(cold) = self.cold.as_mut().filter(|c| c.contains_edge(id)) {
+            if let Some(edge) = cold.take_edge(id) {
+                self.edges.insert(id, edge);
+            }
+        }
+    }
+
+    pub(super) fn hot_node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub(super) fn hot_ids(&self) -> (Vec<NodeId>, Vec<EdgeId>) {
+        (self.nodes.keys().copied().collect(), self.edges.keys().copied().collect())
+    }
+
+    // Incoming plus outgoing edges, from the adjacency index alone
+    pub(super) fn degree(&self, id: NodeId) -> usize {
+        self.outgoing.get(&id).map_or(0, Vec::len) + self.incoming.get(&id).map_or(0, Vec::len)
+    }
+
+    // Writes a hot edge to the store and drops it from memory; false if it
+    // is not hot or no store is attached
+    pub(super) fn spill_edge(&mut self, id: EdgeId) -> io::Result<bool> {
+        let (Some(cold), Some(edge)) = (self.cold.as_mut().filter(|c| c.can_spill()), self.edges.get(&id)) else {
+            return Ok(false);
+        };
+        cold.spill_edge(edge)?;
+        self.edges.remove(&id);
+        Ok(true)
+    }
+
+    // Same for a node, together with its hot edges
+    pub(super) fn spill_node(&mut self, id: NodeId) -> io::Result<bool> {
+        if !self.nodes.contains_key(&id) || !self.cold.as_ref().is_some_and(ColdTier::can_spill) {
+            return Ok(false);
+        }
+        let edges: Vec<EdgeId> = self.outgoing.get(&id).into_iter().chain(self.incoming.get(&id)).flatten().copied().collect();
+        for edge in edges {
+            self.spill_edge(edge)?;
+        }
+        let (Some(cold), Some(node)) = (self.cold.as_mut(), self.nodes.get(&id)) else {
+            return Ok(false);
+        };
+        cold.spill_node(node)?;
+        self.nodes.remove(&id);
+        Ok(true)
     }
 
     // --- Symbolic Embedding ---
 
     pub fn embed_node(&self, id: NodeId, dim: usize) -> Embedding {
         let mut vec = vec![0.0f64; dim];
-        if let Some(node) = self.nodes.get(&id) {
+        if let Some(node) = self.node_record(id) {
             // Feature 0: label hash
             vec[0] = (node.label as f64) / 100.0;
             // Feature 1: degree
@@ -358,9 +643,9 @@ impl KnowledgeGraph {
 
     pub fn find_similar_nodes(&self, target: NodeId, dim: usize, top_k: usize) -> Vec<(NodeId, f64)> {
         let target_emb = self.embed_node(target, dim);
-        let mut scores: Vec<(NodeId, f64)> = self.nodes.keys()
-            .filter(|&&id| id != target)
-            .map(|&id| {
+        let mut scores: Vec<(NodeId, f64)> = self.node_ids()
+            .filter(|&id| id != target)
+            .map(|id| {
                 let emb = self.embed_node(id, dim);
                 (id, Self::similarity(&target_emb, &emb))
             })
@@ -400,24 +685,47 @@ impl KnowledgeGraph {
             last_access: self.tick,
             access_count: 0,
             weight: 1.0,
+            reifies: None,
+            pinned: false,
         };
+        if self.journal.is_recording() {
+            self.journal.record(Change::Node { id, before: None, after: Some(node.clone()) });
+        }
         self.nodes.insert(id, node);
         self.label_index.entry(label).or_default().push(id);
+        self.evict_over_capacity(id..id + 1);
         id
     }
 
     pub fn add_node_with_attrs(&mut self, label: Sym, attrs: Vec<(Sym, Term)>) -> NodeId {
         let id = self.add_node(label);
-        if let Some(node) = self.nodes.get_mut(&id) {
-            for (k, v) in attrs {
-                if let Some(ts) = TermSer::from_term(&v) {
-                    node.attributes.push((k, ts));
-                }
-            }
+        for (k, v) in attrs {
+            self.set_node_attr(id, k, &v);
         }
         id
     }
 
+    // Set or replace one attribute; false if the node is missing or the
+    // value has no TermSer form
+    pub fn set_node_attr(&mut self, id: NodeId, key: Sym, value: &Term) -> bool {
+        let Some(ts) = TermSer::from_term(value) else {
+            return false;
+        };
+        self.update_node_attrs(id, |node| match node.attributes.iter_mut().find(|(k, _)| *k == key) {
+            Some(slot) => slot.1 = ts,
+            None => node.attributes.push((key, ts)),
+        })
+    }
+
+    // Add a value for `key` next to any it already has (multi-valued
+    // attributes); an identical pair is not added twice
+    pub fn add_node_attr(&mut self, id: NodeId, key: Sym, value: TermSer) -> bool {
+        if self.node_record(id).is_some_and(|n| n.attributes.iter().any(|(k, v)| *k == key && *v == value)) {
+            return true;
+        }
+        self.update_node_attrs(id, |node| node.attributes.push((key, value)))
+    }
+
     pub fn add_edge(&mut self, source: NodeId, relation: Sym, target: NodeId) -> EdgeId {
         let id = self.next_edge_id;
         self.next_edge_id += 1;
@@ -431,25 +739,265 @@ impl KnowledgeGraph {
             created_at: self.tick,
             last_access: self.tick,
             access_count: 0,
+            valid_from: None,
+            valid_to: None,
+            context: self.context,
+            pinned: false,
         };
+        if self.journal.is_recording() {
+            self.journal.record(Change::Edge { id, before: None, after: Some(edge.clone()) });
+        }
         self.edges.insert(id, edge);
         self.outgoing.entry(source).or_default().push(id);
         self.incoming.entry(target).or_default().push(id);
         self.relation_index.entry(relation).or_default().push(id);
+        if let Some(ctx) = self.context {
+            self.context_index.entry(ctx).or_default().push(id);
+        }
         id
     }
 
-    pub fn add_edge_weighted(&mut self, source: NodeId, relation: Sym, target: NodeId, weight: f64) -> EdgeId {
+    // --- Bulk loading ---
+
+    // Nodes with consecutive ids, returned as a range. The label index is
+    // extended once per label at the end instead of once per node. With
+    // recording on, the load is one transaction (one undo unit).
+    pub fn add_nodes_bulk<I: IntoIterator<Item = Sym>>(&mut self, labels: I) -> Range<NodeId> {
+        let labels: Vec<Sym> = labels.into_iter().collect();
+        let first = self.next_node_id;
+        let recording = self.journal.is_recording();
+        if recording {
+            self.begin();
+        }
+        self.nodes.reserve(labels.len());
+        let mut by_label = Vec::with_capacity(labels.len());
+        for label in labels {
+            let id = self.next_node_id;
+            self.next_node_id += 1;
+            let node = Node {
+                id,
+                label,
+                attributes: Vec::new(),
+                created_at: self.tick,
+                last_access: self.tick,
+                access_count: 0,
+                weight: 1.0,
+                reifies: None,
+                pinned: false,
+            };
+            if recording {
+                self.journal.record(Change::Node { id, before: None, after: Some(node.clone()) });
+            }
+            self.nodes.insert(id, node);
+            by_label.push((label, id));
+        }
+        Self::extend_index(&mut self.label_index, by_label);
+        self.evict_over_capacity(first..self.next_node_id);
+        if recording {
+            self.commit();
+        }
+        first..self.next_node_id
+    }
+
+    // Edges (source, relation, target) with consecutive ids, stamped with
+    // the current context. Adjacency, relation and context indexes are
+    // sort-built at the end.
+    pub fn add_edges_bulk<I: IntoIterator<Item = (NodeId, Sym, NodeId)>>(&mut self, triples: I) -> Range<EdgeId> {
+        let triples: Vec<(NodeId, Sym, NodeId)> = triples.into_iter().collect();
+        let first = self.next_edge_id;
+        let recording = self.journal.is_recording();
+        if recording {
+            self.begin();
+        }
+        self.edges.reserve(triples.len());
+        let mut outgoing = Vec::with_capacity(triples.len());
+        let mut incoming = Vec::with_capacity(triples.len());
+        let mut relations = Vec::with_capacity(triples.len());
+        for (source, relation, target) in triples {
+            let id = self.next_edge_id;
+            self.next_edge_id += 1;
+            let edge = Edge {
+                id,
+                relation,
+                source,
+                target,
+                weight: 1.0,
+                attributes: Vec::new(),
+                created_at: self.tick,
+                last_access: self.tick,
+                access_count: 0,
+                valid_from: None,
+                valid_to: None,
+                context: self.context,
+                pinned: false,
+            };
+            if recording {
+                self.journal.record(Change::Edge { id, before: None, after: Some(edge.clone()) });
+            }
+            self.edges.insert(id, edge);
+            outgoing.push((source, id));
+            incoming.push((target, id));
+            relations.push((relation, id));
+        }
+        Self::extend_index(&mut self.outgoing, outgoing);
+        Self::extend_index(&mut self.incoming, incoming);
+        Self::extend_index(&mut self.relation_index, relations);
+        if let Some(ctx) = self.context {
+            self.context_index.entry(ctx).or_default().extend(first..self.next_edge_id);
+        }
+        if recording {
+            self.commit();
+        }
+        first..self.next_edge_id
+    }
+
+    // Append (key, id) pairs grouped by key; ids are newer than any
+    // already indexed, so each list stays sorted
+    fn extend_index(index: &mut FxHashMap<u32, Vec<u32>>, mut pairs: Vec<(u32, u32)>) {
+        pairs.sort_unstable();
+        for run in pairs.chunk_by(|a, b| a.0 == b.0) {
+            index.entry(run[0].0).or_default().extend(run.iter().map(|&(_, id)| id));
+        }
+    }
+
+    pub fn add_edge_with_attrs(&mut self, source: NodeId, relation: Sym, target: NodeId, attrs: Vec<(Sym, Term)>) -> EdgeId {
         let id = self.add_edge(source, relation, target);
-        if let Some(edge) = self.edges.get_mut(&id) {
-            edge.weight = weight;
+        for (k, v) in attrs {
+            self.set_edge_attr(id, k, &v);
+        }
+        id
+    }
+
+    // Set or replace one attribute, keeping the attribute index in step
+    pub fn set_edge_attr(&mut self, id: EdgeId, key: Sym, value: &Term) -> bool {
+        let Some(ts) = TermSer::from_term(value) else {
+            return false;
+        };
+        let mut old = None;
+        let found = self.update_edge(id, |edge| match edge.attributes.iter_mut().find(|(k, _)| *k == key) {
+            Some(slot) => old = Some(std::mem::replace(&mut slot.1, ts.clone())),
+            None => edge.attributes.push((key, ts.clone())),
+        });
+        if !found {
+            return false;
+        }
+        if let Some(index) = self.edge_attr_index.get_mut(&key) {
+            if let Some(ids) = old.and_then(|old| index.get_mut(&old)) {
+                ids.retain(|e| *e != id);
+            }
+            index.entry(ts).or_default().push(id);
+        }
+        true
+    }
+
+    pub fn set_edge_validity(&mut self, id: EdgeId, from: Option<u64>, to: Option<u64>) -> bool {
+        self.update_edge(id, |e| (e.valid_from, e.valid_to) = (from, to))
+    }
+
+    // The fact stops holding now; the edge stays as history
+    pub fn expire_edge(&mut self, id: EdgeId) -> bool {
+        let now = self.tick;
+        self.update_edge(id, |e| e.valid_to = Some(e.valid_to.map_or(now, |t| t.min(now))))
+    }
+
+    // Index the values of one edge attribute, for edges_with_attr
+    pub fn index_edge_attr(&mut self, key: Sym) {
+        let mut index: BTreeMap<TermSer, Vec<EdgeId>> = BTreeMap::new();
+        for edge in self.edges() {
+            if let Some((_, v)) = edge.attributes.iter().find(|(k, _)| *k == key) {
+                index.entry(v.clone()).or_default().push(edge.id);
+            }
+        }
+        for ids in index.values_mut() {
+            ids.sort_unstable();
+        }
+        self.edge_attr_index.insert(key, index);
+    }
+
+    // Edges whose attribute value lies in the range; None if not indexed
+    pub fn edges_with_attr<R: RangeBounds<TermSer>>(&self, key: Sym, range: R) -> Option<Vec<EdgeId>> {
+        let index = self.edge_attr_index.get(&key)?;
+        Some(index.range(range).flat_map(|(_, ids)| ids.iter().copied()).collect())
+    }
+
+    // Index the values of one attribute on the nodes with a label, for
+    // nodes_with_attr. Every value of a multi-valued attribute is indexed.
+    pub fn index_node_attr(&mut self, label: Sym, key: Sym) {
+        let mut index: BTreeMap<TermSer, Vec<NodeId>> = BTreeMap::new();
+        for &id in self.label_index.get(&label).map(Vec::as_slice).unwrap_or_default() {
+            for (_, v) in self.node_record(id).into_iter().flat_map(|n| &n.attributes).filter(|(k, _)| *k == key) {
+                index.entry(v.clone()).or_default().push(id);
+            }
         }
+        for ids in index.values_mut() {
+            ids.sort_unstable();
+            ids.dedup();
+        }
+        self.node_attr_index.insert((label, key), index);
+    }
+
+    pub fn drop_node_attr_index(&mut self, label: Sym, key: Sym) -> bool {
+        self.node_attr_index.remove(&(label, key)).is_some()
+    }
+
+    pub fn is_node_attr_indexed(&self, label: Sym, key: Sym) -> bool {
+        self.node_attr_index.contains_key(&(label, key))
+    }
+
+    // Nodes with the label whose attribute value lies in the range, sorted;
+    // None if not indexed. Values order by type first (atoms, integers,
+    // strings, booleans), so an integer range only meets integers.
+    pub fn nodes_with_attr<R: RangeBounds<TermSer>>(&self, label: Sym, key: Sym, range: R) -> Option<Vec<NodeId>> {
+        let index = self.node_attr_index.get(&(label, key))?;
+        let mut ids: Vec<NodeId> = index.range(range).flat_map(|(_, ids)| ids.iter().copied()).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        Some(ids)
+    }
+
+    // Context stamped on edges added from now on (None: no context)
+    pub fn set_context(&mut self, context: Option<Sym>) {
+        self.context = context;
+    }
+
+    pub fn context(&self) -> Option<Sym> {
+        self.context
+    }
+
+    pub fn set_edge_context(&mut self, id: EdgeId, context: Option<Sym>) -> bool {
+        let Some(old) = self.edge_record(id).map(|e| e.context) else {
+            return false;
+        };
+        if let Some(ids) = old.and_then(|ctx| self.context_index.get_mut(&ctx)) {
+            ids.retain(|e| *e != id);
+        }
+        if let Some(ctx) = context {
+            let ids = self.context_index.entry(ctx).or_default();
+            ids.insert(ids.partition_point(|&e| e < id), id);
+        }
+        self.update_edge(id, |e| e.context = context)
+    }
+
+    pub fn edges_in_context(&self, context: Sym) -> Vec<EdgeId> {
+        self.context_index.get(&context).cloned().unwrap_or_default()
+    }
+
+    // Contexts with at least one edge, sorted
+    pub fn contexts(&self) -> Vec<Sym> {
+        let mut contexts: Vec<Sym> = self.context_index.iter().filter(|(_, ids)| !ids.is_empty()).map(|(&c, _)| c).collect();
+        contexts.sort_unstable();
+        contexts
+    }
+
+    pub fn add_edge_weighted(&mut self, source: NodeId, relation: Sym, target: NodeId, weight: f64) -> EdgeId {
+        let id = self.add_edge(source, relation, target);
+        self.update_edge(id, |edge| edge.weight = weight);
         id
     }
 
     pub fn node(&self, id: NodeId) -> Option<&Node> {
         self.touch_node_read(id);
-        self.nodes.get(&id)
+        self.node_record(id)
     }
 
     pub fn node_mut(&mut self, id: NodeId) -> Option<&mut Node> {
@@ -457,13 +1005,27 @@ impl KnowledgeGraph {
         self.nodes.get_mut(&id)
     }
 
+    // Unlike node_mut this does not count as an access
+    pub fn set_node_weight(&mut self, id: NodeId, weight: f64) -> bool {
+        self.update_node(id, |n| n.weight = weight)
+    }
+
     pub fn edge(&self, id: EdgeId) -> Option<&Edge> {
-        self.edges.get(&id)
+        self.edge_record(id)
     }
 
-    fn touch_node_read(&self, _id: NodeId) {
-        // Read-only access tracking would need interior mutability
-        // For now, touch_node is called on mutable access
+    // Queued when read tracking is on, applied by flush_reads (access.rs)
+    fn touch_node_read(&self, id: NodeId) {
+        self.reads.record(id);
+    }
+
+    // Cold records are read in as the iteration reaches them
+    pub fn nodes(&self) -> impl Iterator<Item = &Node> {
+        self.nodes.values().chain(self.cold.iter().flat_map(ColdTier::nodes))
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = &Edge> {
+        self.edges.values().chain(self.cold.iter().flat_map(ColdTier::edges))
     }
 
     pub fn nodes_by_label(&self, label: Sym) -> Vec<NodeId> {
@@ -476,13 +1038,13 @@ impl KnowledgeGraph {
 
     pub fn outgoing_edges(&self, node: NodeId) -> Vec<&Edge> {
         self.outgoing.get(&node)
-            .map(|ids| ids.iter().filter_map(|id| self.edges.get(id)).collect())
+            .map(|ids| ids.iter().filter_map(|&id| self.edge_record(id)).collect())
             .unwrap_or_default()
     }
 
     pub fn incoming_edges(&self, node: NodeId) -> Vec<&Edge> {
         self.incoming.get(&node)
-            .map(|ids| ids.iter().filter_map(|id| self.edges.get(id)).collect())
+            .map(|ids| ids.iter().filter_map(|&id| self.edge_record(id)).collect())
             .unwrap_or_default()
     }
 
@@ -501,26 +1063,66 @@ impl KnowledgeGraph {
         result
     }
 
+    // Shortest path along edge directions with at most max_depth edges.
+    // Bidirectional BFS: a full level of the smaller frontier is expanded at
+    // a time (forward over outgoing, backward over incoming edges) and the
+    // best meeting node of that level joins the two halves.
     pub fn find_path(&self, from: NodeId, to: NodeId, max_depth: usize) -> Option<Vec<EdgeId>> {
-        let mut queue = std::collections::VecDeque::new();
-        let mut visited = rustc_hash::FxHashSet::default();
-        queue.push_back((from, Vec::new()));
-        visited.insert(from);
-
-        while let Some((current, path)) = queue.pop_front() {
-            if current == to {
-                return Some(path);
-            }
-            if path.len() >= max_depth {
-                continue;
+        if from == to {
+            return Some(Vec::new());
+        }
+        // Node -> (depth, edge to its parent side), per direction
+        let mut forward: FxHashMap<NodeId, (usize, Option<EdgeId>)> = FxHashMap::default();
+        let mut backward: FxHashMap<NodeId, (usize, Option<EdgeId>)> = FxHashMap::default();
+        forward.insert(from, (0, None));
+        backward.insert(to, (0, None));
+        let (mut front, mut back) = (vec![from], vec![to]);
+        let (mut depth_f, mut depth_b) = (0, 0);
+
+        while depth_f + depth_b < max_depth && !front.is_empty() && !back.is_empty() {
+            let expand_forward = front.len() <= back.len();
+            let (frontier, seen, other, depth) = if expand_forward {
+                (&mut front, &mut forward, &backward, &mut depth_f)
+            } else {
+                (&mut back, &mut backward, &forward, &mut depth_b)
+            };
+            *depth += 1;
+            let mut next = Vec::new();
+            let mut meet: Option<(usize, NodeId)> = None;
+            for &node in frontier.iter() {
+                let steps = if expand_forward {
+                    self.outgoing_edges(node).into_iter().map(|e| (e.id, e.target)).collect::<Vec<_>>()
+                } else {
+                    self.incoming_edges(node).into_iter().map(|e| (e.id, e.source)).collect()
+                };
+                for (edge, reached) in steps {
+                    if seen.contains_key(&reached) {
+                        continue;
+                    }
+                    seen.insert(reached, (*depth, Some(edge)));
+                    next.push(reached);
+                    if let Some(&(d, _)) = other.get(&reached) {
+                        if meet.is_none_or(|(best, _)| *depth + d < best) {
+                            meet = Some((*depth + d, reached));
+                        }
+                    }
+                }
             }
-            for edge in self.outgoing_edges(current) {
-                if !visited.contains(&edge.target) {
-                    visited.insert(edge.target);
-                    let mut new_path = path.clone();
-                    new_path.push(edge.id);
-                    queue.push_back((edge.target, new_path));
+            *frontier = next;
+            if let Some((_, middle)) = meet {
+                let mut path = Vec::new();
+                let mut at = middle;
+                while let Some(&(_, Some(edge))) = forward.get(&at) {
+                    path.push(edge);
+                    at = self.edge_record(edge)?.source;
                 }
+                path.reverse();
+                let mut at = middle;
+                while let Some(&(_, Some(edge))) = backward.get(&at) {
+                    path.push(edge);
+                    at = self.edge_record(edge)?.target;
+                }
+                return Some(path);
             }
         }
         None
@@ -528,15 +1130,15 @@ impl KnowledgeGraph {
 
     pub fn query_triple(&self, source_label: Option<Sym>, relation: Option<Sym>, target_label: Option<Sym>) -> Vec<(NodeId, EdgeId, NodeId)> {
         let mut results = Vec::new();
-        for edge in self.edges.values() {
+        for edge in self.edges() {
             if let Some(rel) = relation {
                 if edge.relation != rel { continue; }
             }
             if let Some(sl) = source_label {
-                if self.nodes.get(&edge.source).map(|n| n.label) != Some(sl) { continue; }
+                if self.label_of(edge.source) != Some(sl) { continue; }
             }
             if let Some(tl) = target_label {
-                if self.nodes.get(&edge.target).map(|n| n.label) != Some(tl) { continue; }
+                if self.label_of(edge.target) != Some(tl) { continue; }
             }
             results.push((edge.source, edge.id, edge.target));
         }
@@ -544,7 +1146,7 @@ impl KnowledgeGraph {
     }
 
     pub fn remove_node(&mut self, id: NodeId) -> bool {
-        if self.nodes.remove(&id).is_none() {
+        if !self.contains_node(id) {
             return false;
         }
         let edge_ids: Vec<EdgeId> = self.outgoing.remove(&id).unwrap_or_default()
@@ -557,47 +1159,86 @@ impl KnowledgeGraph {
         for ids in self.label_index.values_mut() {
             ids.retain(|n| *n != id);
         }
+        self.promote_node(id);
+        let node = self.nodes.remove(&id);
+        if let Some(node) = &node {
+            self.unindex_node_attrs(node);
+            self.unindex_statement(node);
+        }
+        if self.journal.is_recording() {
+            self.journal.record(Change::Node { id, before: node, after: None });
+        }
         true
     }
 
     pub fn remove_edge(&mut self, id: EdgeId) -> bool {
-        if let Some(edge) = self.edges.remove(&id) {
-            if let Some(out) = self.outgoing.get_mut(&edge.source) {
-                out.retain(|e| *e != id);
-            }
-            if let Some(inc) = self.incoming.get_mut(&edge.target) {
-                inc.retain(|e| *e != id);
-            }
-            if let Some(rels) = self.relation_index.get_mut(&edge.relation) {
-                rels.retain(|e| *e != id);
-            }
-            true
-        } else {
-            false
+        self.promote_edge(id);
+        let Some(edge) = self.edges.remove(&id) else {
+            return false;
+        };
+        self.unindex_edge(&edge);
+        if self.journal.is_recording() {
+            self.journal.record(Change::Edge { id, before: Some(edge), after: None });
+        }
+        // Statements about a fact go with it
+        if let Some(statement) = self.statement_index.get(&id).copied() {
+            self.remove_node(statement);
+        }
+        true
+    }
+
+    // Statement node of a reified edge
+    pub fn statement_of(&self, edge: EdgeId) -> Option<NodeId> {
+        self.statement_index.get(&edge).copied()
+    }
+
+    // Unlike node() these do not count as a read
+    pub fn contains_node(&self, id: NodeId) -> bool {
+        self.nodes.contains_key(&id) || self.cold.as_ref().is_some_and(|c| c.contains_node(id))
+    }
+
+    pub fn label_of(&self, id: NodeId) -> Option<Sym> {
+        match self.nodes.get(&id) {
+            Some(node) => Some(node.label),
+            None => self.cold.as_ref()?.label_of(id),
         }
     }
 
     pub fn node_count(&self) -> usize {
-        self.nodes.len()
+        self.nodes.len() + self.cold.as_ref().map_or(0, ColdTier::node_count)
     }
 
     pub fn edge_count(&self) -> usize {
-        self.edges.len()
+        self.edges.len() + self.cold.as_ref().map_or(0, ColdTier::edge_count)
     }
 
     pub fn tick(&mut self) {
         self.tick += 1;
+        let tick = self.tick;
+        self.journal.log(|| WalRecord::Tick(tick));
+        if let Some(timeline) = self.journal.timeline_mut() {
+            timeline.now = tick;
+        }
+        self.sync_similarity_index();
+        if let Some(cold) = &mut self.cold {
+            cold.release();
+        }
     }
 
     pub fn current_tick(&self) -> u64 {
         self.tick
     }
 
+    // Tick of a reconstructed past state, see timeline.rs
+    pub(super) fn set_tick(&mut self, tick: u64) {
+        self.tick = tick;
+    }
+
     pub fn to_terms(&self, _syms: &SymbolTable) -> Vec<Term> {
         let mut terms = Vec::new();
-        for edge in self.edges.values() {
-            let s_label = self.nodes.get(&edge.source).map(|n| n.label).unwrap_or(0);
-            let t_label = self.nodes.get(&edge.target).map(|n| n.label).unwrap_or(0);
+        for edge in self.edges() {
+            let s_label = self.label_of(edge.source).unwrap_or(0);
+            let t_label = self.label_of(edge.target).unwrap_or(0);
             terms.push(Term::compound(edge.relation, vec![
                 Term::atom(s_label),
                 Term::atom(t_label),
@@ -605,29 +1246,76 @@ impl KnowledgeGraph {
         }
         terms
     }
-}
 
-#[derive(Debug, Clone)]
-pub enum GraphPattern {
-    Chain {
-        source_label: Sym,
-        rel1: Sym,
-        mid_label: Sym,
-        rel2: Sym,
-        target_label: Sym,
-    },
-    SharedTarget {
-        relation: Sym,
-        target_label: Sym,
-        source_labels: Vec<Sym>,
-    },
+    // Same as to_terms, paired with each edge's weight as a degree of belief
+    pub fn to_weighted_terms(&self) -> Vec<(Term, f64)> {
+        self.edges()
+            .map(|edge| {
+                let s_label = self.label_of(edge.source).unwrap_or(0);
+                let t_label = self.label_of(edge.target).unwrap_or(0);
+                let term = Term::compound(edge.relation, vec![Term::atom(s_label), Term::atom(t_label)]);
+                (term, edge.weight.clamp(0.0, 1.0))
+            })
+            .collect()
+    }
 }
 
-#[derive(Debug, Clone)]
-pub struct InferredRule {
-    pub head: String,
-    pub head_sym: (Sym, Sym),
-    pub body_rels: Vec<Sym>,
-    pub confidence: f64,
-    pub support: usize,
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINK: u32 = 1;
+
+    // Nodes visited by a path, checking each edge starts where the last ended
+    fn walk(g: &KnowledgeGraph, from: NodeId, path: &[EdgeId]) -> Vec<NodeId> {
+        let mut nodes = vec![from];
+        for &id in path {
+            let edge = g.edge(id).unwrap();
+            assert_eq!(edge.source, *nodes.last().unwrap());
+            nodes.push(edge.target);
+        }
+        nodes
+    }
+
+    #[test]
+    fn find_path_respects_depth_and_direction() {
+        let mut g = KnowledgeGraph::new();
+        let n: Vec<NodeId> = (0..7).map(|_| g.add_node(0)).collect();
+        for i in 0..6 {
+            g.add_edge(n[i], LINK, n[i + 1]);
+        }
+
+        let path = g.find_path(n[0], n[6], 6).unwrap();
+        assert_eq!(walk(&g, n[0], &path), n);
+        // Exactly max_depth edges is allowed, one more is not
+        assert_eq!(g.find_path(n[0], n[6], 5), None);
+        assert_eq!(g.find_path(n[1], n[6], 5).map(|p| p.len()), Some(5));
+
+        assert_eq!(g.find_path(n[6], n[0], 10), None);
+        assert_eq!(g.find_path(n[3], n[3], 0), Some(Vec::new()));
+    }
+
+    #[test]
+    fn find_path_meets_in_the_middle() {
+        // from fans out to 4 nodes and to is reached from 4 others, so both
+        // frontiers grow; the short route runs a -> m -> b, a longer one
+        // detours through an extra hop
+        let mut g = KnowledgeGraph::new();
+        let (from, to, m, detour) = (g.add_node(0), g.add_node(0), g.add_node(0), g.add_node(0));
+        let a: Vec<NodeId> = (0..4).map(|_| g.add_node(0)).collect();
+        let b: Vec<NodeId> = (0..4).map(|_| g.add_node(0)).collect();
+        for i in 0..4 {
+            g.add_edge(from, LINK, a[i]);
+            g.add_edge(b[i], LINK, to);
+        }
+        g.add_edge(a[0], LINK, detour);
+        g.add_edge(detour, LINK, m);
+        g.add_edge(a[3], LINK, m);
+        g.add_edge(m, LINK, b[2]);
+
+        let path = g.find_path(from, to, 10).unwrap();
+        assert_eq!(walk(&g, from, &path), vec![from, a[3], m, b[2], to]);
+        assert_eq!(g.find_path(from, to, 3), None);
+        assert_eq!(g.find_path(to, from, 10), None);
+    }
 }
@@ -0,0 +1,349 @@
+// Change log with transactions and undo/redo.
+//
+// Every mutation through the graph API (adding, removing, re-weighting,
+// touching, decaying, setting attributes or validity) is recorded as the
+// before and after state of one node or edge. Recording happens inside a
+// transaction, and outside one only when history is enabled:
+//
+//   graph.begin();
+//   ... speculative changes ...
+//   graph.rollback();        // or commit()
+//
+// Transactions nest; a rollback reverts to the matching begin. With history
+// enabled each committed outermost transaction, and each change made outside
+// a transaction, is one unit for undo(n) and redo(n). Ids are never reused,
+// so undoing an insertion does not free its id. Changes made through
+// node_mut's reference bypass the log.
+//
+// With a write-ahead log attached (wal.rs) every change that takes effect is
+// also appended there: immediately outside a transaction, on the outermost
+// commit inside one, and inverted for undo. Change-feed subscribers
+// (feed.rs) are notified and the time-travel timeline (timeline.rs) is
+// extended at the same points.
+
+use super::feed::Feed;
+use super::graph::{Edge, EdgeId, KnowledgeGraph, Node, NodeId};
+use super::timeline::Timeline;
+use super::wal::{Wal, WalRecord};
+use rustc_hash::FxHashSet;
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Change {
+    // None before is an insertion, None after a removal
+    Node { id: NodeId, before: Option<Node>, after: Option<Node> },
+    Edge { id: EdgeId, before: Option<Edge>, after: Option<Edge> },
+}
+
+impl Change {
+    fn inverse(&self) -> Change {
+        match self.clone() {
+            Change::Node { id, before, after } => Change::Node { id, before: after, after: before },
+            Change::Edge { id, before, after } => Change::Edge { id, before: after, after: before },
+        }
+    }
+
+    // Nodes whose own record or edges the change affects
+    pub fn nodes(&self) -> Vec<NodeId> {
+        match self {
+            Change::Node { id, .. } => vec![*id],
+            Change::Edge { before, after, .. } => before.iter().chain(after).flat_map(|e| [e.source, e.target]).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Journal {
+    history: bool,
+    // Start of each open transaction in `pending`
+    marks: Vec<usize>,
+    pending: Vec<Change>,
+    done: Vec<Vec<Change>>,
+    undone: Vec<Vec<Change>>,
+    wal: Option<Wal>,
+    // Nodes touched by changes since last taken, when tracked
+    touched: Option<FxHashSet<NodeId>>,
+    feed: Feed,
+    // Changes with their tick, for as_of (timeline.rs)
+    timeline: Option<Timeline>,
+}
+
+// A cloned graph does not write to the original's log file nor notify its
+// subscribers
+impl Clone for Journal {
+    fn clone(&self) -> Self {
+        Self {
+            history: self.history,
+            marks: self.marks.clone(),
+            pending: self.pending.clone(),
+            done: self.done.clone(),
+            undone: self.undone.clone(),
+            wal: None,
+            touched: self.touched.clone(),
+            feed: Feed::default(),
+            timeline: self.timeline.clone(),
+        }
+    }
+}
+
+impl Journal {
+    pub fn is_recording(&self) -> bool {
+        self.history || !self.marks.is_empty() || self.wal.is_some() || self.touched.is_some() || self.feed.is_active()
+            || self.timeline.is_some()
+    }
+
+    pub fn record(&mut self, change: Change) {
+        self.touch(&change);
+        if !self.marks.is_empty() {
+            self.pending.push(change);
+        } else {
+            self.emit(&change);
+            if self.history {
+                self.done.push(vec![change]);
+            }
+        }
+        self.undone.clear();
+    }
+
+    pub(super) fn log<F: FnOnce() -> WalRecord>(&mut self, record: F) {
+        if let Some(wal) = &mut self.wal {
+            wal.append(&record());
+        }
+    }
+
+    // A change that took effect: to the WAL and the subscribers
+    fn emit(&mut self, change: &Change) {
+        self.log(|| WalRecord::Change(Box::new(change.clone())));
+        self.feed.publish(change);
+        if let Some(timeline) = &mut self.timeline {
+            timeline.entries.push((timeline.now, change.clone()));
+        }
+    }
+
+    pub(super) fn timeline(&self) -> Option<&Timeline> {
+        self.timeline.as_ref()
+    }
+
+    pub(super) fn timeline_mut(&mut self) -> Option<&mut Timeline> {
+        self.timeline.as_mut()
+    }
+
+    pub(super) fn set_timeline(&mut self, timeline: Option<Timeline>) {
+        self.timeline = timeline;
+    }
+
+    pub(super) fn feed(&self) -> &Feed {
+        &self.feed
+    }
+
+    pub(super) fn feed_mut(&mut self) -> &mut Feed {
+        &mut self.feed
+    }
+
+    pub(super) fn wal(&self) -> Option<&Wal> {
+        self.wal.as_ref()
+    }
+
+    pub(super) fn wal_mut(&mut self) -> Option<&mut Wal> {
+        self.wal.as_mut()
+    }
+
+    pub(super) fn set_wal(&mut self, wal: Option<Wal>) {
+        self.wal = wal;
+    }
+
+    fn touch(&mut self, change: &Change) {
+        if let Some(touched) = &mut self.touched {
+            touched.extend(change.nodes());
+        }
+    }
+
+    pub(super) fn track_touched(&mut self, on: bool) {
+        self.touched = on.then(FxHashSet::default);
+    }
+
+    pub(super) fn touched(&self) -> Option<&FxHashSet<NodeId>> {
+        self.touched.as_ref()
+    }
+
+    pub(super) fn take_touched(&mut self) -> FxHashSet<NodeId> {
+        self.touched.as_mut().map(std::mem::take).unwrap_or_default()
+    }
+}
+
+impl KnowledgeGraph {
+    pub(super) fn apply_change(&mut self, change: &Change, forward: bool) {
+        self.journal.touch(change);
+        match change {
+            Change::Node { id, before, after } => self.put_node(*id, if forward { after } else { before }.clone()),
+            Change::Edge { id, before, after } => self.put_edge(*id, if forward { after } else { before }.clone()),
+        }
+    }
+
+    // put_node / put_edge, recorded
+    pub(super) fn replace_node(&mut self, id: NodeId, after: Option<Node>) {
+        if self.journal.is_recording() {
+            let before = self.node(id).cloned();
+            self.journal.record(Change::Node { id, before, after: after.clone() });
+        }
+        self.put_node(id, after);
+    }
+
+    pub(super) fn replace_edge(&mut self, id: EdgeId, after: Option<Edge>) {
+        if self.journal.is_recording() {
+            let before = self.edge(id).cloned();
+            self.journal.record(Change::Edge { id, before, after: after.clone() });
+        }
+        self.put_edge(id, after);
+    }
+
+    fn revert(&mut self, changes: &[Change]) {
+        for change in changes.iter().rev() {
+            self.apply_change(change, false);
+        }
+    }
+
+    // Keep committed changes for undo from now on
+    pub fn with_history(mut self) -> Self {
+        self.journal.history = true;
+        self
+    }
+
+    pub fn set_history(&mut self, on: bool) {
+        self.journal.history = on;
+        if !on {
+            self.journal.done.clear();
+            self.journal.undone.clear();
+        }
+    }
+
+    pub fn begin(&mut self) {
+        self.journal.marks.push(self.journal.pending.len());
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        !self.journal.marks.is_empty()
+    }
+
+    // False without an open transaction
+    pub fn commit(&mut self) -> bool {
+        if self.journal.marks.pop().is_none() {
+            return false;
+        }
+        if self.journal.marks.is_empty() {
+            let unit = std::mem::take(&mut self.journal.pending);
+            for change in &unit {
+                self.journal.emit(change);
+            }
+            if self.journal.history && !unit.is_empty() {
+                self.journal.done.push(unit);
+            }
+        }
+        true
+    }
+
+    // Revert everything since the matching begin; false without one
+    pub fn rollback(&mut self) -> bool {
+        let Some(mark) = self.journal.marks.pop() else {
+            return false;
+        };
+        let changes = self.journal.pending.split_off(mark);
+        self.revert(&changes);
+        true
+    }
+
+    // Undo the last n units; returns how many were undone. Not inside a
+    // transaction.
+    pub fn undo(&mut self, n: usize) -> usize {
+        let mut count = 0;
+        while count < n && !self.in_transaction() {
+            let Some(unit) = self.journal.done.pop() else { break };
+            self.revert(&unit);
+            for change in unit.iter().rev() {
+                self.journal.emit(&change.inverse());
+            }
+            self.journal.undone.push(unit);
+            count += 1;
+        }
+        count
+    }
+
+    pub fn redo(&mut self, n: usize) -> usize {
+        let mut count = 0;
+        while count < n && !self.in_transaction() {
+            let Some(unit) = self.journal.undone.pop() else { break };
+            for change in &unit {
+                self.apply_change(change, true);
+                self.journal.emit(change);
+            }
+            self.journal.done.push(unit);
+            count += 1;
+        }
+        count
+    }
+
+    // Units available to undo
+    pub fn history_len(&self) -> usize {
+        self.journal.done.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Term;
+
+    const PERSON: u32 = 1;
+    const KNOWS: u32 = 2;
+    const AGE: u32 = 3;
+
+    #[test]
+    fn rollback_restores_the_graph() {
+        let mut g = KnowledgeGraph::new();
+        let a = g.add_node(PERSON);
+        let b = g.add_node(PERSON);
+        let ab = g.add_edge(a, KNOWS, b);
+
+        g.begin();
+        let c = g.add_node(PERSON);
+        g.add_edge(b, KNOWS, c);
+        g.begin();
+        g.set_node_weight(a, 0.25);
+        g.remove_node(b);
+        assert!(g.rollback());
+        assert_eq!(g.node(a).unwrap().weight, 1.0);
+        assert_eq!(g.outgoing_edges(a)[0].id, ab);
+        assert_eq!(g.node_count(), 3);
+        assert!(g.rollback());
+        assert_eq!(g.node(c).map(|n| n.id), None);
+        assert!(g.outgoing_edges(b).is_empty());
+        assert_eq!(g.nodes_by_label(PERSON), vec![a, b]);
+        assert!(!g.rollback());
+        assert_eq!(g.history_len(), 0);
+    }
+
+    #[test]
+    fn undo_and_redo_units() {
+        let mut g = KnowledgeGraph::new().with_history();
+        let a = g.add_node(PERSON);
+        g.begin();
+        let b = g.add_node_with_attrs(PERSON, vec![(AGE, Term::Int(30))]);
+        let ab = g.add_edge(a, KNOWS, b);
+        g.commit();
+        g.set_node_attr(b, AGE, &Term::Int(31));
+        assert_eq!(g.history_len(), 3);
+
+        assert_eq!(g.undo(1), 1);
+        assert_eq!(g.node(b).unwrap().attributes[0].1.to_term(), Term::Int(30));
+        assert_eq!(g.undo(5), 2);
+        assert_eq!(g.node_count(), 0);
+
+        assert_eq!(g.redo(2), 2);
+        assert_eq!(g.edge(ab).map(|e| (e.source, e.target)), Some((a, b)));
+        assert_eq!(g.incoming_edges(b).len(), 1);
+        // A new change drops what is left to redo
+        g.tick();
+        g.touch_edge(ab);
+        assert_eq!(g.redo(1), 0);
+    }
+}
@@ -0,0 +1,270 @@
+// Link prediction: edges that probably should exist.
+//
+// Two sources of evidence, combined as a noisy-or when both propose the
+// same (source, relation, target):
+// - closure: when chains a -r1-> b -r2-> c are often closed by an edge
+//   a -r-> c (at least MIN_SUPPORT chains), the open chains are proposed
+//   with the share of closed ones as score;
+// - analogy: nodes whose embed_node vectors are near-identical to x's
+//   (same label, cosine >= MIN_SIMILARITY) vote for their own edges,
+//   weighted by similarity, so x -r-> t scores the share of x's peers
+//   having it (counting x itself, so a single peer gives at most 1/2).
+//   Labels with more than MAX_PEERS nodes are hashed into an LSH index
+//   (ann.rs), and x is only compared with the MAX_PEERS nodes it buckets
+//   with, so a few near peers may be missed.
+//
+// Chains are enumerated twice (statistics, then the open chains of the
+// supported relation pairs) rather than held in memory.
+//
+//   for p in graph.predict_links(10) { ... }
+//   let asserted = graph.assert_predicted_links(10, 0.8, 0.3);
+//
+// Asserted edges get the given (low) weight and the current context
+// (context.rs), so they can be told apart from observed facts and
+// retracted together.
+
+use crate::core::Sym;
+use super::ann::{AnnConfig, SimilarityIndex};
+use super::graph::{Edge, EdgeId, KnowledgeGraph, NodeId};
+use rustc_hash::FxHashMap;
+
+const EMBED_DIM: usize = 8;
+const MIN_SIMILARITY: f64 = 0.9;
+const MIN_SUPPORT: usize = 2;
+const MAX_PEERS: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkPrediction {
+    pub source: NodeId,
+    pub relation: Sym,
+    pub target: NodeId,
+    pub score: f64,
+}
+
+type Candidates = FxHashMap<(NodeId, Sym, NodeId), f64>;
+
+impl KnowledgeGraph {
+    fn has_link(&self, source: NodeId, relation: Sym, target: NodeId) -> bool {
+        self.outgoing_edges(source).iter().any(|e| e.relation == relation && e.target == target)
+    }
+
+    // Every chain a -first-> b -second-> c with a != c
+    fn for_each_chain(&self, mut f: impl FnMut(&Edge, &Edge)) {
+        for first in self.edges() {
+            for second in self.outgoing_edges(first.target) {
+                if first.source != second.target {
+                    f(first, second);
+                }
+            }
+        }
+    }
+
+    fn closure_candidates(&self) -> Candidates {
+        let mut total: FxHashMap<(Sym, Sym), usize> = FxHashMap::default();
+        let mut closed: FxHashMap<(Sym, Sym), FxHashMap<Sym, usize>> = FxHashMap::default();
+        self.for_each_chain(|first, second| {
+            let key = (first.relation, second.relation);
+            *total.entry(key).or_default() += 1;
+            let mut closers: Vec<Sym> = self.outgoing_edges(first.source).iter()
+                .filter(|e| e.target == second.target)
+                .map(|e| e.relation)
+                .collect();
+            closers.sort_unstable();
+            closers.dedup();
+            for r in closers {
+                *closed.entry(key).or_default().entry(r).or_default() += 1;
+            }
+        });
+        let mut out = Candidates::default();
+        self.for_each_chain(|first, second| {
+            let key = (first.relation, second.relation);
+            let n = total[&key];
+            if n < MIN_SUPPORT {
+                return;
+            }
+            let (a, c) = (first.source, second.target);
+            for (&r, &k) in closed.get(&key).into_iter().flatten() {
+                if !self.has_link(a, r, c) {
+                    let score = out.entry((a, r, c)).or_default();
+                    *score = score.max(k as f64 / n as f64);
+                }
+            }
+        });
+        out
+    }
+
+    fn analogy_candidates(&self) -> Candidates {
+        let mut by_label: FxHashMap<Sym, Vec<NodeId>> = FxHashMap::default();
+        for node in self.nodes() {
+            by_label.entry(node.label).or_default().push(node.id);
+        }
+        let mut out = Candidates::default();
+        for ids in by_label.values() {
+            let embeddings: Vec<_> = ids.iter().map(|&id| self.embed_node(id, EMBED_DIM)).collect();
+            let index = (ids.len() > MAX_PEERS).then(|| {
+                let config = AnnConfig::default().with_dim(EMBED_DIM).with_tables(8, 6).with_max_candidates(MAX_PEERS);
+                let index = SimilarityIndex::from_embeddings(config, ids.iter().copied().zip(embeddings.iter().cloned()));
+                let position: FxHashMap<NodeId, usize> = ids.iter().enumerate().map(|(i, &id)| (id, i)).collect();
+                (index, position)
+            });
+            for (i, &x) in ids.iter().enumerate() {
+                let peers: Vec<usize> = match &index {
+                    Some((index, position)) => {
+                        let mut near: Vec<usize> = index.near(&embeddings[i]).into_iter().map(|id| position[&id]).collect();
+                        near.sort_unstable();
+                        near
+                    }
+                    None => (0..ids.len()).collect(),
+                };
+                let mut votes = Candidates::default();
+                let mut weight = 0.0;
+                for j in peers {
+                    let y = ids[j];
+                    let sim = Self::similarity(&embeddings[i], &embeddings[j]);
+                    if i == j || sim < MIN_SIMILARITY {
+                        continue;
+                    }
+                    weight += sim;
+                    for e in self.outgoing_edges(y) {
+                        if e.target != x {
+                            *votes.entry((x, e.relation, e.target)).or_default() += sim;
+                        }
+                    }
+                }
+                for (key, vote) in votes {
+                    if !self.has_link(key.0, key.1, key.2) {
+                        out.insert(key, vote / (weight + 1.0));
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    // The top_k most likely missing edges, best first
+    pub fn predict_links(&self, top_k: usize) -> Vec<LinkPrediction> {
+        let mut scores = self.closure_candidates();
+        for (key, p) in self.analogy_candidates() {
+            let q = scores.entry(key).or_default();
+            *q = 1.0 - (1.0 - *q) * (1.0 - p);
+        }
+        let mut predictions: Vec<LinkPrediction> = scores.into_iter()
+            .map(|((source, relation, target), score)| LinkPrediction { source, relation, target, score })
+            .collect();
+        predictions.sort_by(|a, b| b.score.total_cmp(&a.score).then((a.source, a.relation, a.target).cmp(&(b.source, b.relation, b.target))));
+        predictions.truncate(top_k);
+        predictions
+    }
+
+    // Adds the predictions scoring at least min_score as edges of the given
+    // weight, returns their ids
+    pub fn assert_predicted_links(&mut self, top_k: usize, min_score: f64, weight: f64) -> Vec<EdgeId> {
+        self.predict_links(top_k)
+            .into_iter()
+            .filter(|p| p.score >= min_score)
+            .map(|p| self.add_edge_weighted(p.source, p.relation, p.target, weight))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERSON: Sym = 1;
+    const CITY: Sym = 2;
+    const COUNTRY: Sym = 3;
+    const LIVES_IN: Sym = 4;
+    const IN: Sym = 5;
+    const CITIZEN_OF: Sym = 6;
+    const INFERRED: Sym = 7;
+
+    #[test]
+    fn closes_frequent_chains() {
+        let mut g = KnowledgeGraph::new();
+        let france = g.add_node(COUNTRY);
+        let paris = g.add_node(CITY);
+        let lyon = g.add_node(CITY);
+        g.add_edge(paris, IN, france);
+        g.add_edge(lyon, IN, france);
+        let people: Vec<NodeId> = (0..3).map(|_| g.add_node(PERSON)).collect();
+        g.add_edge(people[0], LIVES_IN, paris);
+        g.add_edge(people[1], LIVES_IN, lyon);
+        g.add_edge(people[2], LIVES_IN, paris);
+        g.add_edge(people[0], CITIZEN_OF, france);
+        g.add_edge(people[1], CITIZEN_OF, france);
+
+        let top = g.predict_links(1);
+        assert_eq!((top[0].source, top[0].relation, top[0].target), (people[2], CITIZEN_OF, france));
+        assert!(top[0].score > 0.6);
+
+        g.set_context(Some(INFERRED));
+        let added = g.assert_predicted_links(1, 0.6, 0.2);
+        assert_eq!(added.len(), 1);
+        assert_eq!(g.edge(added[0]).map(|e| (e.weight, e.context)), Some((0.2, Some(INFERRED))));
+        assert!(g.predict_links(5).iter().all(|p| p.relation != CITIZEN_OF || p.source != people[2]));
+    }
+
+    // Five people with distinct labels (so no analogy peers) living in two
+    // French cities; the first three are citizens, p3 and p4 are not
+    fn residents() -> (KnowledgeGraph, Vec<NodeId>, NodeId) {
+        let mut g = KnowledgeGraph::new();
+        let france = g.add_node(COUNTRY);
+        let cities = [g.add_node(CITY), g.add_node(CITY)];
+        for city in cities {
+            g.add_edge(city, IN, france);
+        }
+        let people: Vec<NodeId> = (0..5).map(|i| g.add_node(10 + i)).collect();
+        for (i, &p) in people.iter().enumerate() {
+            g.add_edge(p, LIVES_IN, cities[i % 2]);
+            if i < 3 {
+                g.add_edge(p, CITIZEN_OF, france);
+            }
+        }
+        (g, people, france)
+    }
+
+    #[test]
+    fn scores_closure_by_the_share_of_closed_chains() {
+        let (g, people, france) = residents();
+        // 3 of the 5 lives_in/in chains are closed by citizen_of
+        assert_eq!(g.predict_links(10), vec![
+            LinkPrediction { source: people[3], relation: CITIZEN_OF, target: france, score: 0.6 },
+            LinkPrediction { source: people[4], relation: CITIZEN_OF, target: france, score: 0.6 },
+        ]);
+        assert_eq!(g.predict_links(1).len(), 1);
+    }
+
+    #[test]
+    fn large_labels_compare_a_bounded_peer_set() {
+        let mut g = KnowledgeGraph::new();
+        let (paris, lyon) = (g.add_node(CITY), g.add_node(CITY));
+        let people: Vec<NodeId> = (0..200).map(|_| g.add_node(PERSON)).collect();
+        for &p in &people[1..] {
+            g.add_edge(p, LIVES_IN, paris);
+        }
+        g.add_edge(people[0], LIVES_IN, lyon);
+
+        let top = g.predict_links(1);
+        assert_eq!((top[0].source, top[0].relation, top[0].target), (people[0], LIVES_IN, paris));
+        // At most MAX_PEERS peers vote
+        assert!(top[0].score > 0.9 && top[0].score <= MAX_PEERS as f64 / (MAX_PEERS + 1) as f64);
+    }
+
+    #[test]
+    fn asserts_only_predictions_above_the_threshold() {
+        let (mut g, people, france) = residents();
+        let before = g.edge_count();
+        assert!(g.assert_predicted_links(10, 0.61, 0.1).is_empty());
+        assert_eq!(g.edge_count(), before);
+
+        let added = g.assert_predicted_links(10, 0.6, 0.1);
+        let edges: Vec<_> = added.iter().map(|&id| g.edge(id).unwrap()).collect();
+        assert_eq!(edges.iter().map(|e| (e.source, e.relation, e.target, e.weight)).collect::<Vec<_>>(), vec![
+            (people[3], CITIZEN_OF, france, 0.1),
+            (people[4], CITIZEN_OF, france, 0.1),
+        ]);
+        assert!(g.outgoing_edges(people[0]).iter().all(|e| e.weight > 0.1));
+        assert!(g.predict_links(10).is_empty());
+    }
+}
@@ -0,0 +1,196 @@
+// Entity resolution: merging duplicate nodes.
+//
+// merge_nodes(keep, other, policy) moves every edge of `other` onto `keep`
+// (edge ids and attributes are kept), folds edges that become duplicates of
+// one of keep's into it, drops edges between the two nodes, merges the
+// attributes and removes `other`. The whole merge is recorded like any other
+// mutation, so it can be rolled back or undone.
+//
+// merge_candidates proposes same-label pairs, scored by the cosine of their
+// embed_node vectors averaged with the share of common attributes that have
+// equal values (when they have any in common).
+//
+//   for (a, b, _) in graph.merge_candidates(8, 0.95) {
+//       graph.merge_nodes(a, b, MergePolicy::Keep);
+//   }
+
+use crate::core::Sym;
+use super::graph::{EdgeId, KnowledgeGraph, Node, NodeId, TermSer};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergePolicy {
+    // The kept node's value wins a conflict
+    Keep,
+    // The merged-in node's value wins
+    Replace,
+    // Both values are kept
+    Union,
+}
+
+fn merge_attrs(into: &mut Vec<(Sym, TermSer)>, from: &[(Sym, TermSer)], policy: MergePolicy) {
+    for (key, value) in from {
+        if into.iter().any(|(k, v)| k == key && v == value) {
+            continue;
+        }
+        match (into.iter_mut().find(|(k, _)| k == key), policy) {
+            (Some(slot), MergePolicy::Replace) => slot.1 = value.clone(),
+            (Some(_), MergePolicy::Keep) => {}
+            _ => into.push((*key, value.clone())),
+        }
+    }
+}
+
+// Share of the keys both have whose values agree; None without common keys
+fn attr_agreement(a: &Node, b: &Node) -> Option<f64> {
+    let mut common = 0;
+    let mut equal = 0;
+    for (key, value) in &a.attributes {
+        let values: Vec<&TermSer> = b.attributes.iter().filter(|(k, _)| k == key).map(|(_, v)| v).collect();
+        if !values.is_empty() {
+            common += 1;
+            if values.contains(&value) {
+                equal += 1;
+            }
+        }
+    }
+    (common > 0).then(|| equal as f64 / common as f64)
+}
+
+impl KnowledgeGraph {
+    // False if either node is missing or they are the same node
+    pub fn merge_nodes(&mut self, keep: NodeId, other: NodeId, policy: MergePolicy) -> bool {
+        let (Some(kept), Some(merged)) = (self.node(keep).cloned(), self.node(other).cloned()) else {
+            return false;
+        };
+        if keep == other {
+            return false;
+        }
+        let mut ids: Vec<EdgeId> = self.outgoing_edges(other).iter().chain(self.incoming_edges(other).iter()).map(|e| e.id).collect();
+        ids.sort_unstable();
+        ids.dedup();
+        for id in ids {
+            let Some(mut edge) = self.edge(id).cloned() else { continue };
+            let between = (edge.source == keep && edge.target == other) || (edge.source == other && edge.target == keep);
+            if edge.source == other {
+                edge.source = keep;
+            }
+            if edge.target == other {
+                edge.target = keep;
+            }
+            let twin = self.outgoing_edges(edge.source).iter()
+                .find(|e| e.id != id && e.relation == edge.relation && e.target == edge.target)
+                .map(|e| (*e).clone());
+            match twin {
+                _ if between => self.replace_edge(id, None),
+                Some(mut twin) => {
+                    twin.weight = twin.weight.max(edge.weight);
+                    twin.access_count += edge.access_count;
+                    twin.last_access = twin.last_access.max(edge.last_access);
+                    merge_attrs(&mut twin.attributes, &edge.attributes, policy);
+                    self.replace_edge(id, None);
+                    self.replace_edge(twin.id, Some(twin));
+                }
+                None => self.replace_edge(id, Some(edge)),
+            }
+        }
+
+        let mut node = kept;
+        merge_attrs(&mut node.attributes, &merged.attributes, policy);
+        node.weight = node.weight.max(merged.weight);
+        node.access_count += merged.access_count;
+        node.created_at = node.created_at.min(merged.created_at);
+        node.last_access = node.last_access.max(merged.last_access);
+        self.replace_node(keep, Some(node));
+        self.remove_node(other)
+    }
+
+    // Same-label pairs (a < b) scoring at least min_score, best first
+    pub fn merge_candidates(&self, dim: usize, min_score: f64) -> Vec<(NodeId, NodeId, f64)> {
+        let mut labels: Vec<Sym> = self.nodes().map(|n| n.label).collect();
+        labels.sort_unstable();
+        labels.dedup();
+        let mut pairs = Vec::new();
+        for label in labels {
+            let ids = self.nodes_by_label(label);
+            let nodes: Vec<&Node> = ids.iter().filter_map(|&id| self.node(id)).collect();
+            let embeddings: Vec<_> = ids.iter().map(|&id| self.embed_node(id, dim)).collect();
+            for i in 0..ids.len() {
+                for j in i + 1..ids.len() {
+                    let cosine = Self::similarity(&embeddings[i], &embeddings[j]);
+                    let score = match attr_agreement(nodes[i], nodes[j]) {
+                        Some(agree) => (cosine + agree) / 2.0,
+                        None => cosine,
+                    };
+                    if score >= min_score {
+                        pairs.push((ids[i], ids[j], score));
+                    }
+                }
+            }
+        }
+        pairs.sort_by(|a, b| b.2.total_cmp(&a.2).then((a.0, a.1).cmp(&(b.0, b.1))));
+        pairs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Term;
+
+    const PERSON: Sym = 1;
+    const CITY: Sym = 2;
+    const KNOWS: Sym = 3;
+    const LIVES_IN: Sym = 4;
+    const NAME: Sym = 5;
+    const AGE: Sym = 6;
+
+    #[test]
+    fn merges_edges_and_attributes() {
+        let mut g = KnowledgeGraph::new().with_history();
+        let ada = g.add_node_with_attrs(PERSON, vec![(NAME, Term::Str("Ada".into())), (AGE, Term::Int(36))]);
+        let dup = g.add_node_with_attrs(PERSON, vec![(NAME, Term::Str("Ada".into())), (AGE, Term::Int(37))]);
+        let bob = g.add_node(PERSON);
+        let london = g.add_node(CITY);
+        let lives = g.add_edge_weighted(ada, LIVES_IN, london, 0.5);
+        g.add_edge_weighted(dup, LIVES_IN, london, 0.9);
+        let knows = g.add_edge(bob, KNOWS, dup);
+        g.add_edge(ada, KNOWS, dup);
+        let units = g.history_len();
+
+        assert!(g.merge_nodes(ada, dup, MergePolicy::Union));
+        assert!(g.node(dup).is_none());
+        assert_eq!(g.edge_count(), 2);
+        assert_eq!(g.edge(lives).unwrap().weight, 0.9);
+        assert_eq!(g.edge(knows).map(|e| (e.source, e.target)), Some((bob, ada)));
+        assert_eq!(g.incoming_edges(ada).len(), 1);
+        let attrs = &g.node(ada).unwrap().attributes;
+        assert_eq!(attrs.len(), 3);
+        assert!(!g.merge_nodes(ada, dup, MergePolicy::Keep));
+
+        // One recorded unit per change, all undoable
+        g.undo(g.history_len() - units);
+        assert_eq!(g.edge_count(), 4);
+        assert_eq!(g.edge(knows).map(|e| e.target), Some(dup));
+        assert_eq!(g.node(dup).unwrap().attributes[1].1, TermSer::Int(37));
+    }
+
+    #[test]
+    fn proposes_same_label_duplicates() {
+        let mut g = KnowledgeGraph::new();
+        let city = g.add_node(CITY);
+        let a = g.add_node_with_attrs(PERSON, vec![(NAME, Term::Str("Ada".into()))]);
+        let b = g.add_node_with_attrs(PERSON, vec![(NAME, Term::Str("Ada".into()))]);
+        let c = g.add_node_with_attrs(PERSON, vec![(NAME, Term::Str("Bob".into()))]);
+        for n in [a, b, c] {
+            g.add_edge(n, LIVES_IN, city);
+        }
+        let candidates = g.merge_candidates(8, 0.9);
+        assert_eq!(candidates.len(), 1);
+        assert_eq!((candidates[0].0, candidates[0].1), (a, b));
+        assert!((candidates[0].2 - 1.0).abs() < 1e-9);
+
+        let mut policy = vec![(NAME, TermSer::Int(1))];
+        merge_attrs(&mut policy, &[(NAME, TermSer::Int(2)), (AGE, TermSer::Int(3))], MergePolicy::Replace);
+        assert_eq!(policy, vec![(NAME, TermSer::Int(2)), (AGE, TermSer::Int(3))]);
+    }
+}
@@ -0,0 +1,241 @@
+// Association-rule mining over relations.
+//
+// Candidate rules are counted on the node pairs each relation connects:
+//   implication   r2(X, Y) :- r1(X, Y)
+//   inverse       r2(Y, X) :- r1(X, Y)      (r1 = r2: symmetry)
+//   chain         r(X, Z)  :- r1(X, Y), r2(Y, Z)
+// The support of a rule is the number of distinct pairs matching both its
+// body and its head, its confidence that support over the pairs matching
+// the body. Rules below MiningConfig's thresholds are dropped; the others
+// carry a Rule ready for RuleEngine::add_rule:
+//
+//   for mined in graph.mine_rules(&syms, &MiningConfig::default().with_min_confidence(0.8)) {
+//       engine.add_rule(mined.rule);
+//   }
+//
+// extract_patterns reports the frequent building blocks the same way:
+// relation chains r1 then r2 and targets shared by several sources, each
+// with its support.
+
+use crate::core::{Sym, SymbolTable, Term};
+use crate::reasoning::rules::Rule;
+use super::graph::{KnowledgeGraph, NodeId};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GraphPattern {
+    // Distinct (x, z) pairs joined by x -rel1-> y -rel2-> z
+    Chain {
+        rel1: Sym,
+        rel2: Sym,
+        support: usize,
+    },
+    // Distinct sources with a `relation` edge to the same target node
+    SharedTarget {
+        relation: Sym,
+        target_label: Sym,
+        source_labels: Vec<Sym>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct InferredRule {
+    // Readable form, e.g. "lives_in(X, Z) :- works_at(X, Y), located_in(Y, Z)"
+    pub head: String,
+    pub head_rel: Sym,
+    pub body_rels: Vec<Sym>,
+    pub confidence: f64,
+    pub support: usize,
+    pub rule: Rule,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MiningConfig {
+    pub min_support: usize,
+    pub min_confidence: f64,
+}
+
+impl Default for MiningConfig {
+    fn default() -> Self {
+        Self { min_support: 2, min_confidence: 0.5 }
+    }
+}
+
+impl MiningConfig {
+    pub fn with_min_support(mut self, min_support: usize) -> Self {
+        self.min_support = min_support;
+        self
+    }
+
+    pub fn with_min_confidence(mut self, min_confidence: f64) -> Self {
+        self.min_confidence = min_confidence;
+        self
+    }
+}
+
+type Pairs = FxHashSet<(NodeId, NodeId)>;
+
+impl KnowledgeGraph {
+    fn relation_pairs(&self) -> FxHashMap<Sym, Pairs> {
+        let mut pairs: FxHashMap<Sym, Pairs> = FxHashMap::default();
+        for edge in self.edges() {
+            pairs.entry(edge.relation).or_default().insert((edge.source, edge.target));
+        }
+        pairs
+    }
+
+    // Distinct (x, z) pairs of each chain r1 then r2
+    fn chain_pairs(&self) -> FxHashMap<(Sym, Sym), Pairs> {
+        let mut chains: FxHashMap<(Sym, Sym), Pairs> = FxHashMap::default();
+        for first in self.edges() {
+            for second in self.outgoing_edges(first.target) {
+                if first.source != second.target {
+                    chains.entry((first.relation, second.relation)).or_default().insert((first.source, second.target));
+                }
+            }
+        }
+        chains
+    }
+
+    pub fn extract_patterns(&self) -> Vec<GraphPattern> {
+        self.extract_patterns_with(&MiningConfig::default())
+    }
+
+    pub fn extract_patterns_with(&self, config: &MiningConfig) -> Vec<GraphPattern> {
+        let mut patterns: Vec<GraphPattern> = self.chain_pairs().into_iter()
+            .filter(|(_, pairs)| pairs.len() >= config.min_support)
+            .map(|((rel1, rel2), pairs)| GraphPattern::Chain { rel1, rel2, support: pairs.len() })
+            .collect();
+
+        let mut targets: Vec<NodeId> = self.nodes().map(|n| n.id).collect();
+        targets.sort_unstable();
+        for target in targets {
+            let mut sources: FxHashMap<Sym, Vec<NodeId>> = FxHashMap::default();
+            for edge in self.incoming_edges(target) {
+                sources.entry(edge.relation).or_default().push(edge.source);
+            }
+            for (relation, mut ids) in sources {
+                ids.sort_unstable();
+                ids.dedup();
+                if ids.len() >= config.min_support.max(2) {
+                    patterns.push(GraphPattern::SharedTarget {
+                        relation,
+                        target_label: self.label_of(target).unwrap_or(0),
+                        source_labels: ids.iter().filter_map(|&id| self.label_of(id)).collect(),
+                    });
+                }
+            }
+        }
+        patterns.sort_by_key(|p| match p {
+            GraphPattern::Chain { rel1, rel2, support } => (0, usize::MAX - support, *rel1, *rel2),
+            GraphPattern::SharedTarget { relation, target_label, source_labels } => (1, usize::MAX - source_labels.len(), *relation, *target_label),
+        });
+        patterns
+    }
+
+    pub fn infer_rules(&self, syms: &SymbolTable) -> Vec<InferredRule> {
+        self.mine_rules(syms, &MiningConfig::default())
+    }
+
+    // Rules meeting the thresholds, most confident first
+    pub fn mine_rules(&self, syms: &SymbolTable, config: &MiningConfig) -> Vec<InferredRule> {
+        let name = |r: Sym| syms.resolve(r).map_or_else(|| format!("#{}", r), str::to_string);
+        let atom = |r: Sym, a: Sym, b: Sym| Term::compound(r, vec![Term::var(a), Term::var(b)]);
+        let (x, y, z) = (0, 1, 2);
+        let pairs = self.relation_pairs();
+        let mut relations: Vec<Sym> = pairs.keys().copied().collect();
+        relations.sort_unstable();
+        let mut rules = Vec::new();
+        let mut emit = |head: Term, body: Vec<Term>, text: String, body_rels: Vec<Sym>, support: usize, total: usize| {
+            let confidence = support as f64 / total.max(1) as f64;
+            if support >= config.min_support && confidence >= config.min_confidence {
+                let head_rel = match &head { Term::Compound(r, _) => *r, _ => 0 };
+                rules.push(InferredRule { head: text, head_rel, body_rels, confidence, support, rule: Rule::new(head, body) });
+            }
+        };
+
+        for &r1 in &relations {
+            let body = &pairs[&r1];
+            for &r2 in &relations {
+                let head = &pairs[&r2];
+                if r1 != r2 {
+                    let support = body.iter().filter(|p| head.contains(p)).count();
+                    emit(atom(r2, x, y), vec![atom(r1, x, y)], format!("{}(X, Y) :- {}(X, Y)", name(r2), name(r1)), vec![r1], support, body.len());
+                }
+                let support = body.iter().filter(|&&(a, b)| a != b && head.contains(&(b, a))).count();
+                emit(atom(r2, y, x), vec![atom(r1, x, y)], format!("{}(Y, X) :- {}(X, Y)", name(r2), name(r1)), vec![r1], support, body.len());
+            }
+        }
+
+        let chains = self.chain_pairs();
+        let mut keys: Vec<(Sym, Sym)> = chains.keys().copied().collect();
+        keys.sort_unstable();
+        for (r1, r2) in keys {
+            let body = &chains[&(r1, r2)];
+            for &r in &relations {
+                let support = body.iter().filter(|p| pairs[&r].contains(p)).count();
+                emit(
+                    atom(r, x, z),
+                    vec![atom(r1, x, y), atom(r2, y, z)],
+                    format!("{}(X, Z) :- {}(X, Y), {}(Y, Z)", name(r), name(r1), name(r2)),
+                    vec![r1, r2],
+                    support,
+                    body.len(),
+                );
+            }
+        }
+
+        rules.sort_by(|a, b| b.confidence.total_cmp(&a.confidence).then(b.support.cmp(&a.support)).then(a.head.cmp(&b.head)));
+        for (i, mined) in rules.iter_mut().enumerate() {
+            mined.rule.id = i;
+        }
+        rules
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reasoning::rules::RuleEngine;
+
+    #[test]
+    fn mines_chain_rules_with_real_counts() {
+        let mut syms = SymbolTable::new();
+        let (works_at, located_in, lives_in, person, company, city) =
+            (syms.intern("works_at"), syms.intern("located_in"), syms.intern("lives_in"), syms.intern("person"), syms.intern("company"), syms.intern("city"));
+        let mut g = KnowledgeGraph::new();
+        let paris = g.add_node(city);
+        let firms: Vec<NodeId> = (0..2).map(|_| g.add_node(company)).collect();
+        for &f in &firms {
+            g.add_edge(f, located_in, paris);
+        }
+        let people: Vec<NodeId> = (0..4).map(|_| g.add_node(person)).collect();
+        for (i, &p) in people.iter().enumerate() {
+            g.add_edge(p, works_at, firms[i % 2]);
+            if i < 3 {
+                g.add_edge(p, lives_in, paris);
+            }
+        }
+
+        let rules = g.mine_rules(&syms, &MiningConfig::default().with_min_confidence(0.7));
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].head, "lives_in(X, Z) :- works_at(X, Y), located_in(Y, Z)");
+        assert_eq!((rules[0].support, rules[0].confidence), (3, 0.75));
+        assert!(g.extract_patterns().contains(&GraphPattern::Chain { rel1: works_at, rel2: located_in, support: 4 }));
+
+        // Loaded as is, the rule derives lives_in/2 from the chain alone: with
+        // the observed lives_in facts left out, only the rule can answer
+        let mut engine = RuleEngine::new();
+        for t in g.to_terms(&syms) {
+            if !matches!(&t, Term::Compound(f, _) if *f == lives_in) {
+                engine.add_fact(t);
+            }
+        }
+        let query = Term::compound(lives_in, vec![Term::var(0), Term::atom(city)]);
+        assert!(engine.query(&query).is_empty());
+        engine.add_rule(rules[0].rule.clone());
+        let found = engine.query(&query);
+        assert!(!found.is_empty());
+        assert!(found.iter().all(|s| s.apply(&Term::var(0)) == Term::atom(person)));
+    }
+}
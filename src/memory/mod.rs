@@ -2,3 +2,40 @@ pub mod graph;
 pub mod compress;
 pub mod analogy;
 pub mod binary;
+pub mod taxonomy;
+pub mod query;
+pub mod pattern;
+pub mod paths;
+pub mod analytics;
+pub mod community;
+pub mod nary;
+pub mod filter;
+pub mod validity;
+pub mod journal;
+pub mod wal;
+#[cfg(feature = "compression")]
+pub mod compressed;
+pub mod view;
+pub mod rdf;
+pub mod sparql;
+pub mod export;
+pub mod merge;
+pub mod delta;
+pub mod context;
+pub mod access;
+pub mod concurrent;
+pub mod eviction;
+pub mod activation;
+pub mod working;
+pub mod links;
+pub mod walks;
+pub mod ann;
+pub mod binding;
+pub mod mining;
+pub mod schema;
+pub mod reify;
+pub mod proppath;
+pub mod feed;
+pub mod timeline;
+pub mod decay;
+pub mod store;
@@ -0,0 +1,101 @@
+// N-ary relations, reified as fact nodes.
+//
+// gave(alice, bob, book, tuesday) becomes one node labelled `gave` with an
+// edge per role, in argument order:
+//
+//   [gave] -agent-> alice, -recipient-> bob, -theme-> book, -time-> tuesday
+//
+// Nothing is lost to binarization and the fact stays visible to every other
+// graph API (paths, pattern matching, MATCH queries, decay). Any node can be
+// read back as a fact: its label is the relation and its outgoing edges are
+// the roles.
+
+use crate::core::Sym;
+use super::graph::{KnowledgeGraph, NodeId};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NaryFact {
+    pub node: NodeId,
+    pub relation: Sym,
+    // (role, filler) in argument order
+    pub roles: Vec<(Sym, NodeId)>,
+}
+
+impl NaryFact {
+    // First filler of a role
+    pub fn get(&self, role: Sym) -> Option<NodeId> {
+        self.roles.iter().find(|(r, _)| *r == role).map(|&(_, n)| n)
+    }
+
+    fn matches(&self, bindings: &[(Sym, Option<NodeId>)]) -> bool {
+        bindings.iter().all(|&(role, filler)| {
+            self.roles.iter().any(|&(r, n)| r == role && filler.is_none_or(|f| f == n))
+        })
+    }
+}
+
+impl KnowledgeGraph {
+    // Returns the fact node
+    pub fn add_nary(&mut self, relation: Sym, roles: &[(Sym, NodeId)]) -> NodeId {
+        let fact = self.add_node(relation);
+        for &(role, filler) in roles {
+            self.add_edge(fact, role, filler);
+        }
+        fact
+    }
+
+    pub fn nary(&self, node: NodeId) -> Option<NaryFact> {
+        let relation = self.node(node)?.label;
+        let roles = self.outgoing_edges(node).iter().map(|e| (e.relation, e.target)).collect();
+        Some(NaryFact { node, relation, roles })
+    }
+
+    // Facts of a relation having every listed role, filled by the given node
+    // when there is one. A bound role is looked up through the filler's
+    // incoming edges instead of scanning all facts of the relation.
+    pub fn query_nary(&self, relation: Sym, bindings: &[(Sym, Option<NodeId>)]) -> Vec<NaryFact> {
+        let mut candidates: Vec<NodeId> = match bindings.iter().find_map(|&(role, f)| f.map(|f| (role, f))) {
+            Some((role, filler)) => self.incoming_edges(filler).iter()
+                .filter(|e| e.relation == role)
+                .map(|e| e.source)
+                .collect(),
+            None => self.nodes_by_label(relation),
+        };
+        candidates.sort_unstable();
+        candidates.dedup();
+        candidates.into_iter()
+            .filter_map(|id| self.nary(id))
+            .filter(|fact| fact.relation == relation && fact.matches(bindings))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERSON: Sym = 1;
+    const GAVE: Sym = 2;
+    const AGENT: Sym = 3;
+    const RECIPIENT: Sym = 4;
+    const THEME: Sym = 5;
+
+    #[test]
+    fn stores_and_queries_facts() {
+        let mut g = KnowledgeGraph::new();
+        let [alice, bob, carol, book] = [0; 4].map(|_| g.add_node(PERSON));
+        let first = g.add_nary(GAVE, &[(AGENT, alice), (RECIPIENT, bob), (THEME, book)]);
+        let second = g.add_nary(GAVE, &[(AGENT, bob), (RECIPIENT, carol), (THEME, book)]);
+
+        let fact = g.nary(first).unwrap();
+        assert_eq!(fact.roles, vec![(AGENT, alice), (RECIPIENT, bob), (THEME, book)]);
+        assert_eq!(fact.get(RECIPIENT), Some(bob));
+
+        let to_bob = g.query_nary(GAVE, &[(RECIPIENT, Some(bob))]);
+        assert_eq!(to_bob, vec![fact]);
+        let by_bob: Vec<NodeId> = g.query_nary(GAVE, &[(AGENT, Some(bob)), (THEME, None)]).iter().map(|f| f.node).collect();
+        assert_eq!(by_bob, vec![second]);
+        assert_eq!(g.query_nary(GAVE, &[(THEME, Some(book))]).len(), 2);
+        assert!(g.query_nary(GAVE, &[(AGENT, Some(carol))]).is_empty());
+    }
+}
@@ -0,0 +1,262 @@
+// Path search beyond find_path's unweighted BFS.
+//
+// shortest_path runs Dijkstra with edge.weight as the cost of an edge
+// (negative weights count as 0), A* when given a heuristic. The heuristic
+// estimates the remaining cost from a node to the target and must never
+// overestimate it, otherwise the returned path may not be the cheapest.
+// With `directed` false an edge can also be followed from target to source.
+//
+// all_paths enumerates the simple paths (no repeated node) along edge
+// directions lazily, depth first, so a caller can stop at any point. Nodes
+// that cannot reach the target within the remaining length are never
+// entered: a backward BFS from the target bounds the search up front.
+
+use super::graph::{Edge, EdgeId, KnowledgeGraph, NodeId};
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::collections::hash_map::Entry;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WeightedPath {
+    // nodes[0] is the start, nodes[i + 1] is reached through edges[i]
+    pub nodes: Vec<NodeId>,
+    pub edges: Vec<EdgeId>,
+    pub cost: f64,
+}
+
+// Heap entry ordered so that the smallest estimate pops first
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Frontier {
+    estimate: f64,
+    cost: f64,
+    node: NodeId,
+}
+
+impl Eq for Frontier {}
+
+impl Ord for Frontier {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.estimate.total_cmp(&self.estimate).then_with(|| other.node.cmp(&self.node))
+    }
+}
+
+impl PartialOrd for Frontier {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Iterator returned by KnowledgeGraph::all_paths
+#[derive(Debug, Clone)]
+pub struct AllPaths<'a> {
+    graph: &'a KnowledgeGraph,
+    to: NodeId,
+    max_len: usize,
+    remaining: usize,
+    // Fewest edges from a node to the target, for nodes within max_len
+    distance: FxHashMap<NodeId, usize>,
+    // One frame per node on the path: the node and its edges still to try
+    stack: Vec<(NodeId, Vec<EdgeId>)>,
+    path: Vec<EdgeId>,
+    on_path: FxHashSet<NodeId>,
+}
+
+impl AllPaths<'_> {
+    fn frame(&self, node: NodeId) -> (NodeId, Vec<EdgeId>) {
+        let mut pending: Vec<EdgeId> = self.graph.outgoing_edges(node).iter().map(|e| e.id).collect();
+        pending.reverse();
+        (node, pending)
+    }
+}
+
+impl Iterator for AllPaths<'_> {
+    type Item = Vec<EdgeId>;
+
+    fn next(&mut self) -> Option<Vec<EdgeId>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        loop {
+            let (node, pending) = self.stack.last_mut()?;
+            let Some(id) = pending.pop() else {
+                self.on_path.remove(node);
+                self.stack.pop();
+                self.path.pop();
+                continue;
+            };
+            let Some(target) = self.graph.edge(id).map(|e| e.target) else { continue };
+            let left = self.max_len - self.path.len() - 1;
+            if self.on_path.contains(&target) || self.distance.get(&target).is_none_or(|&d| d > left) {
+                continue;
+            }
+            self.path.push(id);
+            if target == self.to {
+                let found = self.path.clone();
+                self.path.pop();
+                self.remaining -= 1;
+                return Some(found);
+            }
+            self.on_path.insert(target);
+            let frame = self.frame(target);
+            self.stack.push(frame);
+        }
+    }
+}
+
+impl KnowledgeGraph {
+    // Simple paths from `from` to `to` with at most max_len edges, at most
+    // max_results of them, see paths.rs
+    pub fn all_paths(&self, from: NodeId, to: NodeId, max_len: usize, max_results: usize) -> AllPaths<'_> {
+        let mut distance = FxHashMap::default();
+        let mut frontier = vec![to];
+        distance.insert(to, 0);
+        for d in 1..=max_len {
+            let mut next = Vec::new();
+            for node in frontier {
+                for edge in self.incoming_edges(node) {
+                    if let Entry::Vacant(slot) = distance.entry(edge.source) {
+                        slot.insert(d);
+                        next.push(edge.source);
+                    }
+                }
+            }
+            frontier = next;
+        }
+        let mut paths = AllPaths {
+            graph: self,
+            to,
+            max_len,
+            remaining: max_results,
+            distance,
+            stack: Vec::new(),
+            path: Vec::new(),
+            on_path: FxHashSet::default(),
+        };
+        // A path that starts at the target cannot leave it and stay simple
+        if from != to && paths.distance.contains_key(&from) {
+            paths.on_path.insert(from);
+            let frame = paths.frame(from);
+            paths.stack.push(frame);
+        }
+        paths
+    }
+
+    // Edges leaving a node, paired with the node they lead to
+    fn steps(&self, node: NodeId, directed: bool) -> Vec<(&Edge, NodeId)> {
+        let mut steps: Vec<(&Edge, NodeId)> = self.outgoing_edges(node).into_iter().map(|e| (e, e.target)).collect();
+        if !directed {
+            steps.extend(self.incoming_edges(node).into_iter().map(|e| (e, e.source)));
+        }
+        steps
+    }
+
+    pub fn shortest_path(&self, from: NodeId, to: NodeId, directed: bool) -> Option<WeightedPath> {
+        self.shortest_path_with(from, to, directed, |_| 0.0)
+    }
+
+    // A* search, see paths.rs
+    pub fn shortest_path_with<H: Fn(NodeId) -> f64>(&self, from: NodeId, to: NodeId, directed: bool, heuristic: H) -> Option<WeightedPath> {
+        self.node(from)?;
+        let mut best: FxHashMap<NodeId, f64> = FxHashMap::default();
+        let mut parent: FxHashMap<NodeId, (EdgeId, NodeId)> = FxHashMap::default();
+        let mut heap = BinaryHeap::new();
+        best.insert(from, 0.0);
+        heap.push(Frontier { estimate: heuristic(from), cost: 0.0, node: from });
+
+        while let Some(Frontier { cost, node, .. }) = heap.pop() {
+            if node == to {
+                let mut nodes = vec![to];
+                let mut edges = Vec::new();
+                let mut at = to;
+                while let Some(&(edge, prev)) = parent.get(&at) {
+                    edges.push(edge);
+                    nodes.push(prev);
+                    at = prev;
+                }
+                nodes.reverse();
+                edges.reverse();
+                return Some(WeightedPath { nodes, edges, cost });
+            }
+            if best.get(&node).is_some_and(|&b| cost > b) {
+                continue;
+            }
+            for (edge, next) in self.steps(node, directed) {
+                let next_cost = cost + edge.weight.max(0.0);
+                if best.get(&next).is_none_or(|&b| next_cost < b) {
+                    best.insert(next, next_cost);
+                    parent.insert(next, (edge.id, node));
+                    heap.push(Frontier { estimate: next_cost + heuristic(next), cost: next_cost, node: next });
+                }
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROAD: u32 = 1;
+
+    // a -1-> b -1-> c -1-> d and a shortcut a -5-> d
+    fn roads() -> (KnowledgeGraph, Vec<NodeId>) {
+        let mut g = KnowledgeGraph::new();
+        let n: Vec<NodeId> = (0..4).map(|_| g.add_node(0)).collect();
+        for i in 0..3 {
+            g.add_edge_weighted(n[i], ROAD, n[i + 1], 1.0);
+        }
+        g.add_edge_weighted(n[0], ROAD, n[3], 5.0);
+        (g, n)
+    }
+
+    #[test]
+    fn takes_the_cheapest_route() {
+        let (g, n) = roads();
+        let path = g.shortest_path(n[0], n[3], true).unwrap();
+        assert_eq!(path.nodes, n);
+        assert_eq!(path.edges.len(), 3);
+        assert_eq!(path.cost, 3.0);
+
+        // Against the edge direction only when undirected
+        assert!(g.shortest_path(n[3], n[0], true).is_none());
+        assert_eq!(g.shortest_path(n[3], n[1], false).unwrap().nodes, vec![n[3], n[2], n[1]]);
+        assert_eq!(g.shortest_path(n[2], n[2], true).unwrap().cost, 0.0);
+    }
+
+    #[test]
+    fn astar_agrees_with_dijkstra() {
+        let (g, n) = roads();
+        let remaining = |id: NodeId| (n[3] - id) as f64;
+        let path = g.shortest_path_with(n[0], n[3], true, remaining).unwrap();
+        assert_eq!(path, g.shortest_path(n[0], n[3], true).unwrap());
+    }
+
+    #[test]
+    fn enumerates_simple_paths() {
+        let (mut g, n) = roads();
+        // A cycle back to the start must not be followed
+        g.add_edge(n[2], ROAD, n[0]);
+        let all: Vec<Vec<EdgeId>> = g.all_paths(n[0], n[3], 3, 10).collect();
+        assert_eq!(all.len(), 2);
+        assert!(all.iter().all(|p| p.len() <= 3));
+        assert_eq!(g.all_paths(n[0], n[3], 2, 10).count(), 1);
+        assert_eq!(g.all_paths(n[0], n[3], 3, 1).count(), 1);
+        assert_eq!(g.all_paths(n[3], n[0], 5, 10).count(), 0);
+
+        // Lazy: a long chain of diamonds has 2^20 paths, take a few
+        let mut g = KnowledgeGraph::new();
+        let mut at = g.add_node(0);
+        let start = at;
+        for _ in 0..20 {
+            let (a, b, next) = (g.add_node(0), g.add_node(0), g.add_node(0));
+            for mid in [a, b] {
+                g.add_edge(at, ROAD, mid);
+                g.add_edge(mid, ROAD, next);
+            }
+            at = next;
+        }
+        assert_eq!(g.all_paths(start, at, 40, usize::MAX).take(5).count(), 5);
+    }
+}
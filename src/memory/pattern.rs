@@ -0,0 +1,273 @@
+// Subgraph matching: every embedding of a small query graph in the graph.
+//
+// Query nodes are variables with an optional label, query edges have an
+// optional relation and a direction. An embedding maps query nodes to
+// distinct graph nodes and query edges to distinct graph edges between the
+// images of their ends (a subgraph monomorphism: extra graph edges between
+// matched nodes are allowed).
+//
+// Ordering heuristics keep the backtracking small:
+//   - each query node's candidates are filtered by label (through the label
+//     index) and by in/out degree
+//   - the match order starts at the node with the fewest candidates and then
+//     always takes the node with the most edges into the already ordered
+//     ones, so every later node is reached through an adjacency list rather
+//     than scanned
+//   - edges are checked as soon as both their ends are mapped
+
+use crate::core::Sym;
+use super::graph::{EdgeId, KnowledgeGraph, NodeId};
+use rustc_hash::FxHashSet;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryGraph {
+    // Label of each query node (None matches any)
+    pub nodes: Vec<Option<Sym>>,
+    // (from, to, relation) over query node indices
+    pub edges: Vec<(usize, usize, Option<Sym>)>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PatternMatch {
+    // Image of each query node and query edge, by index
+    pub nodes: Vec<NodeId>,
+    pub edges: Vec<EdgeId>,
+}
+
+impl QueryGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_node(&mut self, label: Option<Sym>) -> usize {
+        self.nodes.push(label);
+        self.nodes.len() - 1
+    }
+
+    pub fn add_edge(&mut self, from: usize, to: usize, relation: Option<Sym>) -> usize {
+        self.edges.push((from, to, relation));
+        self.edges.len() - 1
+    }
+
+    // Stop after this many embeddings
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    fn candidates(&self, graph: &KnowledgeGraph, q: usize) -> Vec<NodeId> {
+        let out_degree = self.edges.iter().filter(|e| e.0 == q).count();
+        let in_degree = self.edges.iter().filter(|e| e.1 == q).count();
+        let mut ids: Vec<NodeId> = match self.nodes[q] {
+            Some(label) => graph.nodes_by_label(label),
+            None => graph.nodes().map(|n| n.id).collect(),
+        };
+        ids.retain(|&id| graph.outgoing_edges(id).len() >= out_degree && graph.incoming_edges(id).len() >= in_degree);
+        ids.sort_unstable();
+        ids
+    }
+
+    // Query nodes in match order
+    pub fn match_order(&self, graph: &KnowledgeGraph) -> Vec<usize> {
+        let sizes: Vec<usize> = (0..self.nodes.len()).map(|q| self.candidates(graph, q).len()).collect();
+        self.order(&sizes)
+    }
+
+    fn order(&self, sizes: &[usize]) -> Vec<usize> {
+        let n = self.nodes.len();
+        let mut placed = vec![false; n];
+        let mut order = Vec::with_capacity(n);
+        while order.len() < n {
+            let links = |q: usize| {
+                self.edges.iter().filter(|&&(a, b, _)| (a == q && placed[b]) || (b == q && placed[a])).count()
+            };
+            let degree = |q: usize| self.edges.iter().filter(|&&(a, b, _)| a == q || b == q).count();
+            let next = (0..n)
+                .filter(|&q| !placed[q])
+                .min_by_key(|&q| (std::cmp::Reverse(links(q)), sizes[q], std::cmp::Reverse(degree(q)), q))
+                .unwrap_or(0);
+            placed[next] = true;
+            order.push(next);
+        }
+        order
+    }
+}
+
+struct Matcher<'a> {
+    query: &'a QueryGraph,
+    graph: &'a KnowledgeGraph,
+    order: Vec<usize>,
+    candidates: Vec<FxHashSet<NodeId>>,
+    // Query edges whose later end is order[depth]
+    closing: Vec<Vec<usize>>,
+    nodes: Vec<Option<NodeId>>,
+    edges: Vec<Option<EdgeId>>,
+    used: FxHashSet<NodeId>,
+    out: Vec<PatternMatch>,
+}
+
+impl Matcher<'_> {
+    fn full(&self) -> bool {
+        self.query.limit.is_some_and(|l| self.out.len() >= l)
+    }
+
+    fn extend(&mut self, depth: usize) {
+        if self.full() {
+            return;
+        }
+        let Some(&q) = self.order.get(depth) else {
+            self.out.push(PatternMatch {
+                nodes: self.nodes.iter().map(|n| n.unwrap_or_default()).collect(),
+                edges: self.edges.iter().map(|e| e.unwrap_or_default()).collect(),
+            });
+            return;
+        };
+        // Reach q through an edge from a mapped node when there is one
+        let via = self.closing[depth].first().map(|&e| {
+            let (a, b, _) = self.query.edges[e];
+            if b == q { (a, true) } else { (b, false) }
+        });
+        let mut next: Vec<NodeId> = match via {
+            Some((anchor, forward)) => {
+                let at = self.nodes[anchor].unwrap_or_default();
+                if forward {
+                    self.graph.outgoing_edges(at).iter().map(|e| e.target).collect()
+                } else {
+                    self.graph.incoming_edges(at).iter().map(|e| e.source).collect()
+                }
+            }
+            None => self.candidates[q].iter().copied().collect(),
+        };
+        next.sort_unstable();
+        next.dedup();
+        for id in next {
+            if self.used.contains(&id) || !self.candidates[q].contains(&id) {
+                continue;
+            }
+            self.nodes[q] = Some(id);
+            self.used.insert(id);
+            self.close_edges(depth, 0);
+            self.used.remove(&id);
+            self.nodes[q] = None;
+            if self.full() {
+                return;
+            }
+        }
+    }
+
+    // Map the k-th closing edge of this depth, then the rest
+    fn close_edges(&mut self, depth: usize, k: usize) {
+        let Some(&e) = self.closing[depth].get(k) else {
+            self.extend(depth + 1);
+            return;
+        };
+        let (a, b, relation) = self.query.edges[e];
+        let (from, to) = (self.nodes[a].unwrap_or_default(), self.nodes[b].unwrap_or_default());
+        let choices: Vec<EdgeId> = self.graph.outgoing_edges(from).iter()
+            .filter(|g| g.target == to && relation.is_none_or(|r| g.relation == r))
+            .map(|g| g.id)
+            .filter(|id| !self.edges.contains(&Some(*id)))
+            .collect();
+        for id in choices {
+            self.edges[e] = Some(id);
+            self.close_edges(depth, k + 1);
+            self.edges[e] = None;
+            if self.full() {
+                return;
+            }
+        }
+    }
+}
+
+impl KnowledgeGraph {
+    // All embeddings of the query graph, see pattern.rs
+    pub fn match_pattern(&self, query: &QueryGraph) -> Vec<PatternMatch> {
+        if query.edges.iter().any(|&(a, b, _)| a >= query.nodes.len() || b >= query.nodes.len()) {
+            return Vec::new();
+        }
+        let candidates: Vec<Vec<NodeId>> = (0..query.nodes.len()).map(|q| query.candidates(self, q)).collect();
+        let order = query.order(&candidates.iter().map(|c| c.len()).collect::<Vec<_>>());
+        let mut position = vec![0; query.nodes.len()];
+        for (i, &q) in order.iter().enumerate() {
+            position[q] = i;
+        }
+        let mut closing = vec![Vec::new(); order.len()];
+        for (e, &(a, b, _)) in query.edges.iter().enumerate() {
+            closing[position[a].max(position[b])].push(e);
+        }
+        let mut matcher = Matcher {
+            query,
+            graph: self,
+            order,
+            candidates: candidates.into_iter().map(|c| c.into_iter().collect()).collect(),
+            closing,
+            nodes: vec![None; query.nodes.len()],
+            edges: vec![None; query.edges.len()],
+            used: FxHashSet::default(),
+            out: Vec::new(),
+        };
+        matcher.extend(0);
+        matcher.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERSON: Sym = 1;
+    const CITY: Sym = 2;
+    const KNOWS: Sym = 3;
+    const LIVES_IN: Sym = 4;
+
+    #[test]
+    fn finds_all_embeddings() {
+        let mut g = KnowledgeGraph::new();
+        let people: Vec<NodeId> = (0..4).map(|_| g.add_node(PERSON)).collect();
+        let paris = g.add_node(CITY);
+        // A directed 3-cycle among the first three people, plus a chord
+        g.add_edge(people[0], KNOWS, people[1]);
+        g.add_edge(people[1], KNOWS, people[2]);
+        g.add_edge(people[2], KNOWS, people[0]);
+        g.add_edge(people[3], KNOWS, people[0]);
+        for &p in &people[..2] {
+            g.add_edge(p, LIVES_IN, paris);
+        }
+
+        let mut cycle = QueryGraph::new();
+        let q: Vec<usize> = (0..3).map(|_| cycle.add_node(Some(PERSON))).collect();
+        for i in 0..3 {
+            cycle.add_edge(q[i], q[(i + 1) % 3], Some(KNOWS));
+        }
+        // One embedding per rotation of the cycle
+        let found = g.match_pattern(&cycle);
+        assert_eq!(found.len(), 3);
+        assert!(found.iter().all(|m| !m.nodes.contains(&people[3])));
+
+        // Two neighbours living in the same city
+        let mut shared = QueryGraph::new();
+        let (a, b, c) = (shared.add_node(None), shared.add_node(None), shared.add_node(Some(CITY)));
+        shared.add_edge(a, b, Some(KNOWS));
+        shared.add_edge(a, c, Some(LIVES_IN));
+        shared.add_edge(b, c, Some(LIVES_IN));
+        assert_eq!(g.match_pattern(&shared), vec![PatternMatch { nodes: vec![people[0], people[1], paris], edges: vec![1, 5, 6] }]);
+        assert_eq!(g.match_pattern(&cycle.with_limit(1)).len(), 1);
+    }
+
+    #[test]
+    fn orders_from_the_rarest_node() {
+        let mut g = KnowledgeGraph::new();
+        let city = g.add_node(CITY);
+        for _ in 0..10 {
+            let p = g.add_node(PERSON);
+            g.add_edge(p, LIVES_IN, city);
+        }
+        let mut query = QueryGraph::new();
+        let (a, b, c) = (query.add_node(Some(PERSON)), query.add_node(Some(PERSON)), query.add_node(Some(CITY)));
+        query.add_edge(a, c, Some(LIVES_IN));
+        query.add_edge(b, c, Some(LIVES_IN));
+        assert_eq!(query.match_order(&g), vec![c, a, b]);
+        assert_eq!(g.match_pattern(&query).len(), 90);
+    }
+}
@@ -0,0 +1,338 @@
+// Property paths: reachability along a regular expression over relations.
+//
+//   knows+ / works_at?       one or more knows, then an optional works_at
+//   (parent | step_parent)*  any number of either
+//   ^parent / parent         siblings (and the node itself): up, then down
+//
+// `/` is sequence, `|` alternation, `*` `+` `?` repetition and `^` follows
+// edges backwards. The expression is compiled to an automaton whose states
+// are walked together with graph nodes, breadth first over the adjacency
+// indexes, so each (node, state) pair is settled once and every reachable
+// node comes with one of its shortest example paths:
+//
+//   let ancestors = graph.path_query(ada, "parent+", &mut syms)?;
+//   for m in &ancestors { println!("{} via {:?}", m.target, m.edges); }
+//
+// A star matches the empty path, so `parent*` includes the start node.
+
+use crate::core::{KolossError, Result, Sym, SymbolTable};
+use super::graph::{EdgeId, KnowledgeGraph, NodeId};
+use rustc_hash::FxHashMap;
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PathExpr {
+    Rel(Sym),
+    // Edges followed from target to source
+    Inverse(Box<PathExpr>),
+    Seq(Vec<PathExpr>),
+    Alt(Vec<PathExpr>),
+    Star(Box<PathExpr>),
+    Plus(Box<PathExpr>),
+    Opt(Box<PathExpr>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathMatch {
+    pub target: NodeId,
+    // nodes[0] is the start, nodes[i + 1] is reached through edges[i]
+    pub nodes: Vec<NodeId>,
+    pub edges: Vec<EdgeId>,
+}
+
+fn parse_error(msg: impl Into<String>) -> KolossError {
+    KolossError::Parse(format!("property path: {}", msg.into()))
+}
+
+struct Parser<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    syms: &'a mut SymbolTable,
+}
+
+impl Parser<'_> {
+    fn peek(&mut self) -> Option<char> {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+        self.chars.get(self.pos).copied()
+    }
+
+    fn alt(&mut self) -> Result<PathExpr> {
+        let mut branches = vec![self.seq()?];
+        while self.peek() == Some('|') {
+            self.pos += 1;
+            branches.push(self.seq()?);
+        }
+        Ok(if branches.len() == 1 { branches.remove(0) } else { PathExpr::Alt(branches) })
+    }
+
+    fn seq(&mut self) -> Result<PathExpr> {
+        let mut steps = vec![self.unary()?];
+        while self.peek() == Some('/') {
+            self.pos += 1;
+            steps.push(self.unary()?);
+        }
+        Ok(if steps.len() == 1 { steps.remove(0) } else { PathExpr::Seq(steps) })
+    }
+
+    fn unary(&mut self) -> Result<PathExpr> {
+        let inverse = self.peek() == Some('^');
+        if inverse {
+            self.pos += 1;
+        }
+        let mut expr = self.primary()?;
+        loop {
+            expr = match self.peek() {
+                Some('*') => PathExpr::Star(Box::new(expr)),
+                Some('+') => PathExpr::Plus(Box::new(expr)),
+                Some('?') => PathExpr::Opt(Box::new(expr)),
+                _ => break,
+            };
+            self.pos += 1;
+        }
+        Ok(if inverse { PathExpr::Inverse(Box::new(expr)) } else { expr })
+    }
+
+    fn primary(&mut self) -> Result<PathExpr> {
+        match self.peek() {
+            Some('(') => {
+                self.pos += 1;
+                let expr = self.alt()?;
+                if self.peek() != Some(')') {
+                    return Err(parse_error(format!("expected ')' at {}", self.pos)));
+                }
+                self.pos += 1;
+                Ok(expr)
+            }
+            Some(c) if c.is_alphanumeric() || c == '_' || c == ':' => {
+                let start = self.pos;
+                while self.chars.get(self.pos).is_some_and(|&c| c.is_alphanumeric() || c == '_' || c == ':') {
+                    self.pos += 1;
+                }
+                let name: String = self.chars[start..self.pos].iter().collect();
+                Ok(PathExpr::Rel(self.syms.intern(&name)))
+            }
+            Some(c) => Err(parse_error(format!("unexpected '{}' at {}", c, self.pos))),
+            None => Err(parse_error("unexpected end")),
+        }
+    }
+}
+
+impl PathExpr {
+    pub fn parse(text: &str, syms: &mut SymbolTable) -> Result<Self> {
+        let mut parser = Parser { chars: text.chars().collect(), pos: 0, syms };
+        let expr = parser.alt()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(c) => Err(parse_error(format!("unexpected '{}' at {}", c, parser.pos))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Epsilon,
+    Forward(Sym),
+    Backward(Sym),
+}
+
+// A (node, automaton state) pair of the search
+type Pair = (NodeId, usize);
+// Pair a pair was reached from and the edge taken, None for the start
+type Via = Option<(Pair, Option<EdgeId>)>;
+
+// Thompson automaton: state 0 is the start, `accept` the only final state
+struct Automaton {
+    moves: Vec<Vec<(Step, usize)>>,
+    accept: usize,
+}
+
+impl Automaton {
+    fn compile(expr: &PathExpr) -> Self {
+        let mut a = Automaton { moves: Vec::new(), accept: 0 };
+        let start = a.state();
+        let (from, to) = a.build(expr, false);
+        a.link(start, Step::Epsilon, from);
+        a.accept = to;
+        a
+    }
+
+    fn state(&mut self) -> usize {
+        self.moves.push(Vec::new());
+        self.moves.len() - 1
+    }
+
+    fn link(&mut self, from: usize, step: Step, to: usize) {
+        self.moves[from].push((step, to));
+    }
+
+    // Entry and exit state of the fragment for `expr`; under an inverse,
+    // edges are followed backwards and sequences run in reverse
+    fn build(&mut self, expr: &PathExpr, inverse: bool) -> (usize, usize) {
+        let (from, to) = (self.state(), self.state());
+        match expr {
+            PathExpr::Rel(r) => self.link(from, if inverse { Step::Backward(*r) } else { Step::Forward(*r) }, to),
+            PathExpr::Inverse(inner) => {
+                let (s, e) = self.build(inner, !inverse);
+                self.link(from, Step::Epsilon, s);
+                self.link(e, Step::Epsilon, to);
+            }
+            PathExpr::Seq(steps) => {
+                let mut at = from;
+                let ordered: Vec<&PathExpr> = if inverse { steps.iter().rev().collect() } else { steps.iter().collect() };
+                for step in ordered {
+                    let (s, e) = self.build(step, inverse);
+                    self.link(at, Step::Epsilon, s);
+                    at = e;
+                }
+                self.link(at, Step::Epsilon, to);
+            }
+            PathExpr::Alt(branches) => {
+                for branch in branches {
+                    let (s, e) = self.build(branch, inverse);
+                    self.link(from, Step::Epsilon, s);
+                    self.link(e, Step::Epsilon, to);
+                }
+            }
+            PathExpr::Star(inner) | PathExpr::Plus(inner) | PathExpr::Opt(inner) => {
+                let (s, e) = self.build(inner, inverse);
+                self.link(from, Step::Epsilon, s);
+                self.link(e, Step::Epsilon, to);
+                if !matches!(expr, PathExpr::Plus(_)) {
+                    self.link(from, Step::Epsilon, to);
+                }
+                if !matches!(expr, PathExpr::Opt(_)) {
+                    self.link(e, Step::Epsilon, s);
+                }
+            }
+        }
+        (from, to)
+    }
+}
+
+impl KnowledgeGraph {
+    // Nodes reachable from `from` along a path matching `expr`, each with a
+    // shortest example path, in order of path length then node id
+    pub fn eval_path(&self, from: NodeId, expr: &PathExpr) -> Vec<PathMatch> {
+        if !self.contains_node(from) {
+            return Vec::new();
+        }
+        let automaton = Automaton::compile(expr);
+        // Pair -> edges from the start and how it was reached
+        let mut best: FxHashMap<Pair, (usize, Via)> = FxHashMap::default();
+        let mut matched: Vec<Pair> = Vec::new();
+        let mut queue = VecDeque::from([(from, 0, 0)]);
+        best.insert((from, 0), (0, None));
+
+        // Epsilon moves go to the front of the queue, so pairs pop in order
+        // of path length and the first pop of a pair is final
+        while let Some((node, state, len)) = queue.pop_front() {
+            if best[&(node, state)].0 < len {
+                continue;
+            }
+            if state == automaton.accept && !matched.iter().any(|&(n, _)| n == node) {
+                matched.push((node, state));
+            }
+            for &(step, next) in &automaton.moves[state] {
+                let hops: Vec<(NodeId, Option<EdgeId>)> = match step {
+                    Step::Epsilon => vec![(node, None)],
+                    Step::Forward(r) => self.outgoing_edges(node).iter().filter(|e| e.relation == r).map(|e| (e.target, Some(e.id))).collect(),
+                    Step::Backward(r) => self.incoming_edges(node).iter().filter(|e| e.relation == r).map(|e| (e.source, Some(e.id))).collect(),
+                };
+                for (to, edge) in hops {
+                    let reached = len + usize::from(edge.is_some());
+                    if best.get(&(to, next)).is_some_and(|&(known, _)| known <= reached) {
+                        continue;
+                    }
+                    best.insert((to, next), (reached, Some(((node, state), edge))));
+                    if edge.is_none() {
+                        queue.push_front((to, next, reached));
+                    } else {
+                        queue.push_back((to, next, reached));
+                    }
+                }
+            }
+        }
+
+        let mut out: Vec<PathMatch> = matched.into_iter().map(|(target, state)| {
+            let (mut nodes, mut edges) = (vec![target], Vec::new());
+            let mut at = (target, state);
+            while let Some(&(_, Some((prev, edge)))) = best.get(&at) {
+                if let Some(edge) = edge {
+                    edges.push(edge);
+                    nodes.push(prev.0);
+                }
+                at = prev;
+            }
+            nodes.reverse();
+            edges.reverse();
+            PathMatch { target, nodes, edges }
+        }).collect();
+        out.sort_by_key(|m| (m.edges.len(), m.target));
+        out
+    }
+
+    pub fn path_query(&self, from: NodeId, path: &str, syms: &mut SymbolTable) -> Result<Vec<PathMatch>> {
+        Ok(self.eval_path(from, &PathExpr::parse(path, syms)?))
+    }
+
+    pub fn path_exists(&self, from: NodeId, to: NodeId, expr: &PathExpr) -> bool {
+        self.eval_path(from, expr).iter().any(|m| m.target == to)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn targets(matches: &[PathMatch]) -> Vec<NodeId> {
+        let mut ids: Vec<NodeId> = matches.iter().map(|m| m.target).collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    #[test]
+    fn parses_operators_by_precedence() {
+        let mut syms = SymbolTable::new();
+        let expr = PathExpr::parse("^a+ / b? | (c | d)*", &mut syms).unwrap();
+        let (a, b, c, d) = (syms.intern("a"), syms.intern("b"), syms.intern("c"), syms.intern("d"));
+        assert_eq!(expr, PathExpr::Alt(vec![
+            PathExpr::Seq(vec![
+                PathExpr::Inverse(Box::new(PathExpr::Plus(Box::new(PathExpr::Rel(a))))),
+                PathExpr::Opt(Box::new(PathExpr::Rel(b))),
+            ]),
+            PathExpr::Star(Box::new(PathExpr::Alt(vec![PathExpr::Rel(c), PathExpr::Rel(d)]))),
+        ]));
+        assert!(PathExpr::parse("(a / b", &mut syms).is_err());
+        assert!(PathExpr::parse("a // b", &mut syms).is_err());
+    }
+
+    #[test]
+    fn follows_repetitions_and_inverses() {
+        let mut syms = SymbolTable::new();
+        let (person, parent, knows, works_at) = (syms.intern("person"), syms.intern("parent"), syms.intern("knows"), syms.intern("works_at"));
+        let mut g = KnowledgeGraph::new();
+        let [ada, bob, cy, dee, eve, acme] = [0; 6].map(|_| g.add_node(person));
+        g.add_edge(ada, parent, bob);
+        g.add_edge(bob, parent, cy);
+        g.add_edge(cy, parent, ada);
+        g.add_edge(dee, parent, bob);
+        g.add_edge(ada, knows, eve);
+        g.add_edge(eve, knows, dee);
+        g.add_edge(dee, works_at, acme);
+
+        let ancestors = g.path_query(dee, "parent+", &mut syms).unwrap();
+        assert_eq!(targets(&ancestors), vec![ada, bob, cy]);
+        let to_ada = ancestors.iter().find(|m| m.target == ada).unwrap();
+        assert_eq!(to_ada.nodes, vec![dee, bob, cy, ada]);
+        assert_eq!(to_ada.edges.len(), 3);
+        assert_eq!(targets(&g.path_query(dee, "parent*", &mut syms).unwrap()), vec![ada, bob, cy, dee]);
+
+        assert_eq!(targets(&g.path_query(ada, "knows+ / works_at?", &mut syms).unwrap()), vec![dee, eve, acme]);
+        // Children of bob's parent: bob himself, via cy
+        assert_eq!(targets(&g.path_query(bob, "parent / ^parent", &mut syms).unwrap()), vec![bob]);
+        assert_eq!(targets(&g.path_query(bob, "^(parent / parent)", &mut syms).unwrap()), vec![cy]);
+        assert!(g.path_exists(acme, eve, &PathExpr::parse("^works_at / ^knows", &mut syms).unwrap()));
+    }
+}
@@ -0,0 +1,807 @@
+// Cypher-like query language over the knowledge graph.
+//
+//   MATCH (a:person)-[:knows]->(b)-[r:works_at]->(c:company), (c)<--(d)
+//   WHERE a.age >= 30 AND NOT b = a
+//   RETURN a, b.name, r
+//   LIMIT 10
+//
+// A node pattern is (var:label), both parts optional; an edge pattern is
+// -[var:relation]-> , <-[...]- or -[...]- (either direction), with --> and
+// -- as anonymous shorthands. A variable used twice is the same node. As in
+// Cypher, one match never uses an edge twice but may visit a node twice.
+//
+// WHERE combines comparisons (= <> != < <= > >=) with AND, OR, NOT and
+// parentheses. Operands are variables, properties (var.key: the attribute
+// key, else the built-in id, label / relation and weight), numbers, quoted
+// strings, true / false, and bare names, which stand for symbols. Comparing
+// a missing property is false.
+//
+// The pattern is compiled into a plan: scan the node with the fewest label
+// candidates, then repeatedly follow the cheapest edge (by relation index
+// size) from a bound node through the adjacency indexes, checking edges whose
+// ends are both bound first. Each AND-ed condition runs as soon as its
+// variables are bound.
+
+use crate::core::{KolossError, Result, Sym, SymbolTable, Term};
+use super::graph::{Edge, EdgeId, KnowledgeGraph, NodeId, TermSer};
+use rustc_hash::FxHashMap;
+use std::cmp::Ordering;
+use std::fmt::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum VarRef {
+    Node(usize),
+    Edge(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NodePattern {
+    pub var: Option<String>,
+    pub label: Option<Sym>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EdgePattern {
+    pub var: Option<String>,
+    pub relation: Option<Sym>,
+    // Node pattern indices; for an undirected edge the order is irrelevant
+    pub from: usize,
+    pub to: usize,
+    pub directed: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Var(VarRef),
+    Prop(VarRef, Sym),
+    Const(Term),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Cmp(Operand, CmpOp, Operand),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+    Not(Box<Condition>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphQuery {
+    pub nodes: Vec<NodePattern>,
+    pub edges: Vec<EdgePattern>,
+    pub condition: Option<Condition>,
+    // Column name and what it shows
+    pub returns: Vec<(String, Operand)>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Node(NodeId),
+    Edge(EdgeId),
+    Term(Term),
+    Null,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+impl QueryResult {
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    // Values of one column, in row order
+    pub fn column(&self, name: &str) -> Vec<&Value> {
+        match self.columns.iter().position(|c| c == name) {
+            Some(i) => self.rows.iter().map(|r| &r[i]).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    pub fn format(&self, graph: &KnowledgeGraph, syms: &SymbolTable) -> String {
+        let mut out = self.columns.join(" | ");
+        out.push('\n');
+        for row in &self.rows {
+            let cells: Vec<String> = row.iter().map(|v| match v {
+                Value::Node(id) => {
+                    let label = graph.node(*id).and_then(|n| syms.resolve(n.label)).unwrap_or("?");
+                    format!("({}#{})", label, id)
+                }
+                Value::Edge(id) => {
+                    let rel = graph.edge(*id).and_then(|e| syms.resolve(e.relation)).unwrap_or("?");
+                    format!("[{}#{}]", rel, id)
+                }
+                Value::Term(t) => t.display(syms).to_string(),
+                Value::Null => "null".to_string(),
+            }).collect();
+            let _ = writeln!(out, "{}", cells.join(" | "));
+        }
+        out
+    }
+}
+
+// --- Parsing ---
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Punct(&'static str),
+}
+
+const PUNCT: [&str; 15] = ["<>", "<=", ">=", "!=", "(", ")", "[", "]", ":", ",", ".", "-", "<", ">", "="];
+
+fn tokenize(text: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    while let Some(c) = rest.chars().next() {
+        let len = if c.is_alphabetic() || c == '_' {
+            let len = rest.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len());
+            tokens.push(Token::Ident(rest[..len].to_string()));
+            len
+        } else if c.is_ascii_digit() {
+            let len = rest.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(rest.len());
+            let number = &rest[..len];
+            tokens.push(match number.parse() {
+                Ok(n) => Token::Int(n),
+                Err(_) => Token::Float(number.parse().map_err(|_| parse_error(format!("bad number '{}'", number)))?),
+            });
+            len
+        } else if c == '"' || c == '\'' {
+            let end = rest[1..].find(c).ok_or_else(|| parse_error("unterminated string".into()))?;
+            tokens.push(Token::Str(rest[1..end + 1].to_string()));
+            end + 2
+        } else {
+            let p = PUNCT.iter().find(|p| rest.starts_with(**p)).ok_or_else(|| parse_error(format!("unexpected '{}'", c)))?;
+            tokens.push(Token::Punct(p));
+            p.len()
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+fn parse_error(msg: String) -> KolossError {
+    KolossError::Parse(format!("graph query: {}", msg))
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    syms: &'a mut SymbolTable,
+    vars: FxHashMap<String, VarRef>,
+    query: GraphQuery,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn is_punct(&self, p: &str) -> bool {
+        matches!(self.peek(), Some(Token::Punct(q)) if *q == p)
+    }
+
+    fn eat_punct(&mut self, p: &str) -> bool {
+        let found = self.is_punct(p);
+        self.pos += found as usize;
+        found
+    }
+
+    fn expect_punct(&mut self, p: &str) -> Result<()> {
+        if self.eat_punct(p) {
+            Ok(())
+        } else {
+            Err(parse_error(format!("expected '{}' at token {}", p, self.pos + 1)))
+        }
+    }
+
+    fn is_keyword(&self, kw: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(s)) if s.eq_ignore_ascii_case(kw))
+    }
+
+    fn eat_keyword(&mut self, kw: &str) -> bool {
+        let found = self.is_keyword(kw);
+        self.pos += found as usize;
+        found
+    }
+
+    fn ident(&mut self) -> Result<String> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Ident(s)) => {
+                self.pos += 1;
+                Ok(s.clone())
+            }
+            _ => Err(parse_error(format!("expected a name at token {}", self.pos + 1))),
+        }
+    }
+
+    fn optional_ident(&mut self) -> Option<String> {
+        match self.peek() {
+            Some(Token::Ident(_)) => self.ident().ok(),
+            _ => None,
+        }
+    }
+
+    // `:name` after a variable
+    fn optional_type(&mut self) -> Result<Option<Sym>> {
+        if !self.eat_punct(":") {
+            return Ok(None);
+        }
+        let name = self.ident()?;
+        Ok(Some(self.syms.intern(&name)))
+    }
+
+    fn parse(mut self) -> Result<GraphQuery> {
+        if !self.eat_keyword("match") {
+            return Err(parse_error("query must start with MATCH".into()));
+        }
+        self.path()?;
+        while self.eat_punct(",") {
+            self.path()?;
+        }
+        if self.eat_keyword("where") {
+            self.query.condition = Some(self.or()?);
+        }
+        if !self.eat_keyword("return") {
+            return Err(parse_error(format!("expected RETURN at token {}", self.pos + 1)));
+        }
+        loop {
+            let start = self.pos;
+            let operand = self.operand()?;
+            if matches!(operand, Operand::Const(_)) {
+                return Err(parse_error(format!("RETURN expects variables or properties at token {}", start + 1)));
+            }
+            let name = self.tokens[start..self.pos].iter().map(|t| match t {
+                Token::Ident(s) => s.as_str(),
+                _ => ".",
+            }).collect();
+            self.query.returns.push((name, operand));
+            if !self.eat_punct(",") {
+                break;
+            }
+        }
+        if self.eat_keyword("limit") {
+            match self.tokens.get(self.pos) {
+                Some(Token::Int(n)) if *n >= 0 => {
+                    self.query.limit = Some(*n as usize);
+                    self.pos += 1;
+                }
+                _ => return Err(parse_error("LIMIT expects a count".into())),
+            }
+        }
+        if self.pos < self.tokens.len() {
+            return Err(parse_error(format!("unexpected input at token {}", self.pos + 1)));
+        }
+        Ok(self.query)
+    }
+
+    fn path(&mut self) -> Result<()> {
+        let mut left = self.node()?;
+        while self.is_punct("-") || self.is_punct("<") {
+            let incoming = self.eat_punct("<");
+            self.expect_punct("-")?;
+            let (mut var, mut relation) = (None, None);
+            if self.eat_punct("[") {
+                var = self.optional_ident();
+                relation = self.optional_type()?;
+                self.expect_punct("]")?;
+            }
+            self.expect_punct("-")?;
+            let outgoing = self.eat_punct(">");
+            if incoming && outgoing {
+                return Err(parse_error("an edge cannot point both ways".into()));
+            }
+            let right = self.node()?;
+            let (from, to) = if incoming { (right, left) } else { (left, right) };
+            if let Some(name) = &var {
+                if self.vars.contains_key(name) {
+                    return Err(parse_error(format!("variable '{}' is already bound", name)));
+                }
+                self.vars.insert(name.clone(), VarRef::Edge(self.query.edges.len()));
+            }
+            self.query.edges.push(EdgePattern { var, relation, from, to, directed: incoming || outgoing });
+            left = right;
+        }
+        Ok(())
+    }
+
+    fn node(&mut self) -> Result<usize> {
+        self.expect_punct("(")?;
+        let var = self.optional_ident();
+        let label = self.optional_type()?;
+        self.expect_punct(")")?;
+        match var.as_ref().and_then(|name| self.vars.get(name)) {
+            Some(VarRef::Node(i)) => {
+                let i = *i;
+                let node = &mut self.query.nodes[i];
+                match (node.label, label) {
+                    (Some(a), Some(b)) if a != b => Err(parse_error(format!("conflicting labels for '{}'", var.unwrap_or_default()))),
+                    (None, Some(_)) => {
+                        node.label = label;
+                        Ok(i)
+                    }
+                    _ => Ok(i),
+                }
+            }
+            Some(VarRef::Edge(_)) => Err(parse_error(format!("'{}' is an edge variable", var.unwrap_or_default()))),
+            None => {
+                if let Some(name) = &var {
+                    self.vars.insert(name.clone(), VarRef::Node(self.query.nodes.len()));
+                }
+                self.query.nodes.push(NodePattern { var, label });
+                Ok(self.query.nodes.len() - 1)
+            }
+        }
+    }
+
+    fn or(&mut self) -> Result<Condition> {
+        let mut cond = self.and()?;
+        while self.eat_keyword("or") {
+            cond = Condition::Or(Box::new(cond), Box::new(self.and()?));
+        }
+        Ok(cond)
+    }
+
+    fn and(&mut self) -> Result<Condition> {
+        let mut cond = self.not()?;
+        while self.eat_keyword("and") {
+            cond = Condition::And(Box::new(cond), Box::new(self.not()?));
+        }
+        Ok(cond)
+    }
+
+    fn not(&mut self) -> Result<Condition> {
+        if self.eat_keyword("not") {
+            return Ok(Condition::Not(Box::new(self.not()?)));
+        }
+        if self.eat_punct("(") {
+            let cond = self.or()?;
+            self.expect_punct(")")?;
+            return Ok(cond);
+        }
+        let left = self.operand()?;
+        let op = match self.peek() {
+            Some(Token::Punct("=")) => CmpOp::Eq,
+            Some(Token::Punct("<>" | "!=")) => CmpOp::Ne,
+            Some(Token::Punct("<")) => CmpOp::Lt,
+            Some(Token::Punct("<=")) => CmpOp::Le,
+            Some(Token::Punct(">")) => CmpOp::Gt,
+            Some(Token::Punct(">=")) => CmpOp::Ge,
+            _ => return Err(parse_error(format!("expected a comparison at token {}", self.pos + 1))),
+        };
+        self.pos += 1;
+        Ok(Condition::Cmp(left, op, self.operand()?))
+    }
+
+    fn operand(&mut self) -> Result<Operand> {
+        let negative = self.eat_punct("-");
+        let token = self.tokens.get(self.pos).cloned().ok_or_else(|| parse_error("unexpected end of query".into()))?;
+        self.pos += 1;
+        let operand = match token {
+            Token::Int(n) => Operand::Const(Term::Int(if negative { -n } else { n })),
+            Token::Float(f) => Operand::Const(Term::float(if negative { -f } else { f })),
+            _ if negative => return Err(parse_error(format!("expected a number at token {}", self.pos))),
+            Token::Str(s) => Operand::Const(Term::Str(s.into())),
+            Token::Ident(s) if s.eq_ignore_ascii_case("true") => Operand::Const(Term::Bool(true)),
+            Token::Ident(s) if s.eq_ignore_ascii_case("false") => Operand::Const(Term::Bool(false)),
+            Token::Ident(s) => match self.vars.get(&s).copied() {
+                Some(var) if self.eat_punct(".") => {
+                    let key = self.ident()?;
+                    Operand::Prop(var, self.syms.intern(&key))
+                }
+                Some(var) => Operand::Var(var),
+                None => Operand::Const(Term::Atom(self.syms.intern(&s))),
+            },
+            Token::Punct(p) => return Err(parse_error(format!("unexpected '{}'", p))),
+        };
+        Ok(operand)
+    }
+}
+
+impl GraphQuery {
+    pub fn parse(text: &str, syms: &mut SymbolTable) -> Result<Self> {
+        let parser = Parser {
+            tokens: tokenize(text)?,
+            pos: 0,
+            syms,
+            vars: FxHashMap::default(),
+            query: GraphQuery { nodes: Vec::new(), edges: Vec::new(), condition: None, returns: Vec::new(), limit: None },
+        };
+        parser.parse()
+    }
+}
+
+// --- Planning ---
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    // Bind a node pattern to every node of its label (or every node)
+    Scan(usize),
+    // Bind an edge pattern and its unbound end, from the bound `known` end
+    Expand { edge: usize, known: usize },
+    // Bind an edge pattern whose ends are both bound
+    Connect(usize),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryPlan {
+    pub steps: Vec<Step>,
+    // Conditions checked after each step (AND-ed parts of WHERE)
+    pub checks: Vec<Vec<Condition>>,
+}
+
+fn conjuncts(cond: &Condition, out: &mut Vec<Condition>) {
+    match cond {
+        Condition::And(a, b) => {
+            conjuncts(a, out);
+            conjuncts(b, out);
+        }
+        other => out.push(other.clone()),
+    }
+}
+
+fn condition_vars(cond: &Condition, out: &mut Vec<VarRef>) {
+    match cond {
+        Condition::Cmp(a, _, b) => {
+            for operand in [a, b] {
+                if let Operand::Var(v) | Operand::Prop(v, _) = operand {
+                    out.push(*v);
+                }
+            }
+        }
+        Condition::And(a, b) | Condition::Or(a, b) => {
+            condition_vars(a, out);
+            condition_vars(b, out);
+        }
+        Condition::Not(a) => condition_vars(a, out),
+    }
+}
+
+impl GraphQuery {
+    pub fn plan(&self, graph: &KnowledgeGraph) -> QueryPlan {
+        let mut node_bound = vec![false; self.nodes.len()];
+        let mut edge_done = vec![false; self.edges.len()];
+        let scan_cost = |n: &NodePattern| n.label.map_or(graph.node_count(), |l| graph.nodes_by_label(l).len());
+        let edge_cost = |e: &EdgePattern| e.relation.map_or(graph.edge_count(), |r| graph.edges_by_relation(r).len());
+        let mut steps = Vec::new();
+        while steps.len() < self.nodes.len() + self.edges.len() {
+            let connect = (0..self.edges.len())
+                .find(|&e| !edge_done[e] && node_bound[self.edges[e].from] && node_bound[self.edges[e].to]);
+            let step = if let Some(e) = connect {
+                Step::Connect(e)
+            } else if let Some(e) = (0..self.edges.len())
+                .filter(|&e| !edge_done[e] && (node_bound[self.edges[e].from] || node_bound[self.edges[e].to]))
+                .min_by_key(|&e| edge_cost(&self.edges[e]))
+            {
+                let known = if node_bound[self.edges[e].from] { self.edges[e].from } else { self.edges[e].to };
+                Step::Expand { edge: e, known }
+            } else {
+                let n = (0..self.nodes.len()).filter(|&n| !node_bound[n]).min_by_key(|&n| scan_cost(&self.nodes[n]));
+                match n {
+                    Some(n) => Step::Scan(n),
+                    None => break,
+                }
+            };
+            match step {
+                Step::Scan(n) => node_bound[n] = true,
+                Step::Expand { edge, .. } | Step::Connect(edge) => {
+                    edge_done[edge] = true;
+                    node_bound[self.edges[edge].from] = true;
+                    node_bound[self.edges[edge].to] = true;
+                }
+            }
+            steps.push(step);
+        }
+
+        // Attach each condition to the first step after which it can run
+        let mut parts = Vec::new();
+        if let Some(cond) = &self.condition {
+            conjuncts(cond, &mut parts);
+        }
+        let mut checks = vec![Vec::new(); steps.len()];
+        let mut bound_at = FxHashMap::default();
+        for (i, step) in steps.iter().enumerate() {
+            match *step {
+                Step::Scan(n) => {
+                    bound_at.entry(VarRef::Node(n)).or_insert(i);
+                }
+                Step::Expand { edge, .. } | Step::Connect(edge) => {
+                    bound_at.insert(VarRef::Edge(edge), i);
+                    bound_at.entry(VarRef::Node(self.edges[edge].from)).or_insert(i);
+                    bound_at.entry(VarRef::Node(self.edges[edge].to)).or_insert(i);
+                }
+            }
+        }
+        for part in parts {
+            let mut vars = Vec::new();
+            condition_vars(&part, &mut vars);
+            let at = vars.iter().map(|v| bound_at[v]).max().unwrap_or(0);
+            if let Some(list) = checks.get_mut(at) {
+                list.push(part);
+            }
+        }
+        QueryPlan { steps, checks }
+    }
+}
+
+// --- Execution ---
+
+struct Run<'a> {
+    query: &'a GraphQuery,
+    plan: &'a QueryPlan,
+    graph: &'a KnowledgeGraph,
+    syms: &'a SymbolTable,
+    nodes: Vec<Option<NodeId>>,
+    edges: Vec<Option<EdgeId>>,
+    rows: Vec<Vec<Value>>,
+}
+
+// Equality is defined between any two values of the same kind; None if
+// either side is missing
+fn equals(a: &Value, b: &Value) -> Option<bool> {
+    if *a == Value::Null || *b == Value::Null {
+        return None;
+    }
+    Some(order(a, b).map_or(a == b, |o| o == Ordering::Equal))
+}
+
+// Numbers and strings are ordered
+fn order(a: &Value, b: &Value) -> Option<Ordering> {
+    let number = |v: &Value| match v {
+        Value::Term(Term::Int(n)) => Some(*n as f64),
+        Value::Term(Term::Float(f)) => Some(f.val()),
+        _ => None,
+    };
+    match (a, b) {
+        (Value::Term(Term::Int(x)), Value::Term(Term::Int(y))) => Some(x.cmp(y)),
+        (Value::Term(Term::Str(x)), Value::Term(Term::Str(y))) => Some(x.cmp(y)),
+        _ => number(a)?.partial_cmp(&number(b)?),
+    }
+}
+
+fn attribute(attrs: &[(Sym, TermSer)], key: Sym) -> Option<Term> {
+    attrs.iter().find(|(k, _)| *k == key).map(|(_, v)| v.to_term())
+}
+
+// Edge pattern and edge it binds (if any), node pattern and node it binds
+type Binding = (Option<(usize, EdgeId)>, usize, NodeId);
+
+impl Run<'_> {
+    fn value(&self, operand: &Operand) -> Value {
+        match operand {
+            Operand::Const(t) => Value::Term(t.clone()),
+            Operand::Var(VarRef::Node(n)) => self.nodes[*n].map_or(Value::Null, Value::Node),
+            Operand::Var(VarRef::Edge(e)) => self.edges[*e].map_or(Value::Null, Value::Edge),
+            Operand::Prop(VarRef::Node(n), key) => {
+                let Some(node) = self.nodes[*n].and_then(|id| self.graph.node(id)) else { return Value::Null };
+                let builtin = match self.syms.resolve(*key) {
+                    Some("id") => Some(Term::Int(node.id as i64)),
+                    Some("label") => Some(Term::Atom(node.label)),
+                    Some("weight") => Some(Term::float(node.weight)),
+                    _ => None,
+                };
+                attribute(&node.attributes, *key).or(builtin).map_or(Value::Null, Value::Term)
+            }
+            Operand::Prop(VarRef::Edge(e), key) => {
+                let Some(edge) = self.edges[*e].and_then(|id| self.graph.edge(id)) else { return Value::Null };
+                let builtin = match self.syms.resolve(*key) {
+                    Some("id") => Some(Term::Int(edge.id as i64)),
+                    Some("relation") => Some(Term::Atom(edge.relation)),
+                    Some("weight") => Some(Term::float(edge.weight)),
+                    _ => None,
+                };
+                attribute(&edge.attributes, *key).or(builtin).map_or(Value::Null, Value::Term)
+            }
+        }
+    }
+
+    fn holds(&self, cond: &Condition) -> bool {
+        match cond {
+            Condition::Cmp(a, op, b) => {
+                let (a, b) = (self.value(a), self.value(b));
+                let ord = order(&a, &b);
+                match op {
+                    CmpOp::Eq => equals(&a, &b) == Some(true),
+                    CmpOp::Ne => equals(&a, &b) == Some(false),
+                    CmpOp::Lt => ord == Some(Ordering::Less),
+                    CmpOp::Le => matches!(ord, Some(Ordering::Less | Ordering::Equal)),
+                    CmpOp::Gt => ord == Some(Ordering::Greater),
+                    CmpOp::Ge => matches!(ord, Some(Ordering::Greater | Ordering::Equal)),
+                }
+            }
+            Condition::And(a, b) => self.holds(a) && self.holds(b),
+            Condition::Or(a, b) => self.holds(a) || self.holds(b),
+            Condition::Not(a) => !self.holds(a),
+        }
+    }
+
+    fn label_ok(&self, n: usize, id: NodeId) -> bool {
+        self.query.nodes[n].label.is_none_or(|l| self.graph.node(id).is_some_and(|node| node.label == l))
+    }
+
+    fn full(&self) -> bool {
+        self.query.limit.is_some_and(|l| self.rows.len() >= l)
+    }
+
+    fn step(&mut self, i: usize) {
+        if self.full() {
+            return;
+        }
+        let Some(&step) = self.plan.steps.get(i) else {
+            let row = self.query.returns.iter().map(|(_, op)| self.value(op)).collect();
+            self.rows.push(row);
+            return;
+        };
+        let mut bindings: Vec<Binding> = Vec::new();
+        match step {
+            Step::Scan(n) => {
+                let ids = match self.query.nodes[n].label {
+                    Some(l) => self.graph.nodes_by_label(l),
+                    None => {
+                        let mut ids: Vec<NodeId> = self.graph.nodes().map(|node| node.id).collect();
+                        ids.sort_unstable();
+                        ids
+                    }
+                };
+                bindings.extend(ids.into_iter().map(|id| (None, n, id)));
+            }
+            Step::Expand { edge, known } => self.edge_bindings(edge, known, &mut bindings),
+            Step::Connect(edge) => self.edge_bindings(edge, self.query.edges[edge].from, &mut bindings),
+        }
+        for (edge, n, id) in bindings {
+            let previous = self.nodes[n];
+            self.nodes[n] = Some(id);
+            if let Some((e, eid)) = edge {
+                self.edges[e] = Some(eid);
+            }
+            if self.plan.checks[i].iter().all(|c| self.holds(c)) {
+                self.step(i + 1);
+            }
+            self.nodes[n] = previous;
+            if let Some((e, _)) = edge {
+                self.edges[e] = None;
+            }
+            if self.full() {
+                return;
+            }
+        }
+    }
+
+    // Edges matching pattern `edge` from its bound end `known`
+    fn edge_bindings(&self, edge: usize, known: usize, out: &mut Vec<Binding>) {
+        let pattern = &self.query.edges[edge];
+        let other = if known == pattern.from { pattern.to } else { pattern.from };
+        let at = self.nodes[known].unwrap_or_default();
+        let mut candidates: Vec<(&Edge, NodeId)> = Vec::new();
+        if known == pattern.from || !pattern.directed {
+            candidates.extend(self.graph.outgoing_edges(at).into_iter().map(|e| (e, e.target)));
+        }
+        if known != pattern.from || !pattern.directed {
+            // Undirected: a self-loop was already found among the outgoing edges
+            candidates.extend(self.graph.incoming_edges(at).into_iter()
+                .filter(|e| pattern.directed || e.source != e.target)
+                .map(|e| (e, e.source)));
+        }
+        for (e, end) in candidates {
+            let fits = pattern.relation.is_none_or(|r| e.relation == r)
+                && !self.edges.contains(&Some(e.id))
+                && self.nodes[other].is_none_or(|id| id == end)
+                && self.label_ok(other, end);
+            if fits {
+                out.push((Some((edge, e.id)), other, end));
+            }
+        }
+    }
+}
+
+impl GraphQuery {
+    pub fn execute(&self, graph: &KnowledgeGraph, syms: &SymbolTable) -> QueryResult {
+        let plan = self.plan(graph);
+        let mut run = Run {
+            query: self,
+            plan: &plan,
+            graph,
+            syms,
+            nodes: vec![None; self.nodes.len()],
+            edges: vec![None; self.edges.len()],
+            rows: Vec::new(),
+        };
+        run.step(0);
+        QueryResult { columns: self.returns.iter().map(|(name, _)| name.clone()).collect(), rows: run.rows }
+    }
+}
+
+impl KnowledgeGraph {
+    // Parse and run a MATCH ... RETURN query, see query.rs
+    pub fn query(&self, text: &str, syms: &mut SymbolTable) -> Result<QueryResult> {
+        let query = GraphQuery::parse(text, syms)?;
+        Ok(query.execute(self, syms))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn company_graph(syms: &mut SymbolTable) -> (KnowledgeGraph, [NodeId; 5]) {
+        let (person, company, knows, works_at, age, name) =
+            (syms.intern("person"), syms.intern("company"), syms.intern("knows"), syms.intern("works_at"), syms.intern("age"), syms.intern("name"));
+        let mut g = KnowledgeGraph::new();
+        let mut add = |who: &str, years: i64| {
+            g.add_node_with_attrs(person, vec![(name, Term::Str(who.into())), (age, Term::Int(years))])
+        };
+        let (ann, bob, cy) = (add("ann", 41), add("bob", 25), add("cy", 35));
+        let acme = g.add_node(company);
+        let initech = g.add_node(company);
+        g.add_edge(ann, knows, bob);
+        g.add_edge(cy, knows, bob);
+        g.add_edge(bob, knows, cy);
+        g.add_edge(bob, works_at, acme);
+        g.add_edge(cy, works_at, initech);
+        (g, [ann, bob, cy, acme, initech])
+    }
+
+    #[test]
+    fn matches_paths_with_filters() {
+        let mut syms = SymbolTable::new();
+        let (g, [ann, bob, cy, acme, initech]) = company_graph(&mut syms);
+        let result = g.query("MATCH (a:person)-[:knows]->(b)-[:works_at]->(c) WHERE a.age > 30 RETURN a, b.name, c", &mut syms).unwrap();
+        assert_eq!(result.columns, vec!["a", "b.name", "c"]);
+        let mut rows: Vec<(Value, Value)> = result.rows.iter().map(|r| (r[0].clone(), r[2].clone())).collect();
+        rows.sort_by_key(|(a, _)| format!("{:?}", a));
+        assert_eq!(rows, vec![(Value::Node(ann), Value::Node(acme)), (Value::Node(cy), Value::Node(acme))]);
+        assert!(result.column("b.name").iter().all(|v| **v == Value::Term(Term::Str("bob".into()))));
+
+        // Undirected edges, a shared variable and symbols as constants
+        let result = g.query("MATCH (x)-[r:knows]-(y), (y)-->(c:company) WHERE c.label = company AND NOT x.name = 'ann' RETURN x, r, c", &mut syms).unwrap();
+        // bob and cy know each other both ways: two edges, each usable in
+        // either direction
+        assert_eq!(result.len(), 4);
+        assert!(result.column("c").iter().all(|v| [Value::Node(acme), Value::Node(initech)].contains(v)));
+        assert!(result.column("x").iter().all(|v| [Value::Node(bob), Value::Node(cy)].contains(v)));
+        let limited = g.query("MATCH (p:person)<-[:knows]-(q) RETURN p LIMIT 1", &mut syms).unwrap();
+        assert_eq!(limited.rows, vec![vec![Value::Node(bob)]]);
+        assert_eq!(g.query("MATCH (a)-[:knows]->(a) RETURN a", &mut syms).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn plans_from_the_most_selective_node() {
+        let mut syms = SymbolTable::new();
+        let (g, _) = company_graph(&mut syms);
+        let query = GraphQuery::parse("MATCH (a)-[:knows]->(b)-[:works_at]->(c:company) WHERE c.id = 4 RETURN a", &mut syms).unwrap();
+        let plan = query.plan(&g);
+        assert_eq!(plan.steps, vec![Step::Scan(2), Step::Expand { edge: 1, known: 2 }, Step::Expand { edge: 0, known: 1 }]);
+        assert_eq!(plan.checks[0].len(), 1);
+        assert_eq!(query.execute(&g, &syms).len(), 2);
+    }
+
+    #[test]
+    fn rejects_malformed_queries() {
+        let mut syms = SymbolTable::new();
+        for bad in ["RETURN a", "MATCH (a) RETURN", "MATCH (a)<-[:r]->(b) RETURN a", "MATCH (a:x), (a:y) RETURN a",
+                    "MATCH (a)-[r]->(b)-[r]->(c) RETURN a", "MATCH (a) WHERE a.x ~ 1 RETURN a", "MATCH (a) RETURN a LIMIT x"] {
+            assert!(GraphQuery::parse(bad, &mut syms).is_err(), "{}", bad);
+        }
+    }
+}
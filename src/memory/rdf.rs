@@ -0,0 +1,608 @@
+// RDF import from N-Triples and Turtle.
+//
+// Every IRI subject or object becomes one node whose label is the interned
+// IRI, so importing more data about the same resource extends that node
+// (found through the label index). Predicates are interned as relations.
+// A triple whose object is a literal becomes an attribute of the subject
+// keyed by the predicate; xsd integers and booleans become Int and Bool,
+// every other literal its lexical form as Str (language tags are dropped).
+// Blank nodes are scoped to one document and labelled "_:name".
+//
+//   let stats = graph.import_turtle(&mut syms, BufReader::new(File::open("slice.ttl")?))?;
+//
+// Input is read one statement at a time. Turtle support covers prefixes and
+// base (both syntaxes), `a`, predicate and object lists, blank node property
+// lists, long strings and numeric and boolean literals; collections are
+// rejected. N-Triples is read by the same parser, being a subset of Turtle.
+// Triples already in the graph are not added twice.
+
+use crate::core::{KolossError, Result, Sym, SymbolTable};
+use super::graph::{KnowledgeGraph, NodeId, TermSer};
+use rustc_hash::FxHashMap;
+use std::io::BufRead;
+
+pub(super) const RDF_TYPE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#type";
+pub(super) const XSD: &str = "http://www.w3.org/2001/XMLSchema#";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RdfStats {
+    pub triples: usize,
+    // Nodes created for resources not already in the graph
+    pub nodes: usize,
+    pub edges: usize,
+    pub literals: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum RdfTerm {
+    Iri(String),
+    Blank(String),
+    Literal { value: String, datatype: Option<String> },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Iri(String),
+    // prefix, local name
+    PName(String, String),
+    Blank(String),
+    Str(String),
+    Number(String),
+    // @prefix, @base or a language tag
+    At(String),
+    // a, true, false, PREFIX, BASE
+    Word(String),
+    DataType,
+    Dot,
+    Semicolon,
+    Comma,
+    LBracket,
+    RBracket,
+    LParen,
+}
+
+struct Lexer<R> {
+    input: R,
+    line: Vec<char>,
+    pos: usize,
+    line_no: usize,
+}
+
+impl<R: BufRead> Lexer<R> {
+    fn error(&self, msg: &str) -> KolossError {
+        KolossError::Parse(format!("rdf line {}: {}", self.line_no, msg))
+    }
+
+    // Current character, reading the next line when needed; None at the end
+    fn peek(&mut self) -> Result<Option<char>> {
+        while self.pos >= self.line.len() {
+            let mut s = String::new();
+            let n = self.input.read_line(&mut s).map_err(|e| self.error(&e.to_string()))?;
+            if n == 0 {
+                return Ok(None);
+            }
+            self.line_no += 1;
+            self.line = s.chars().collect();
+            self.pos = 0;
+        }
+        Ok(Some(self.line[self.pos]))
+    }
+
+    // Character after the current one, on the same line
+    fn peek2(&self) -> Option<char> {
+        self.line.get(self.pos + 1).copied()
+    }
+
+    fn bump(&mut self) -> Result<Option<char>> {
+        let c = self.peek()?;
+        if c.is_some() {
+            self.pos += 1;
+        }
+        Ok(c)
+    }
+
+    fn expect_char(&mut self) -> Result<char> {
+        self.bump()?.ok_or_else(|| self.error("unexpected end of input"))
+    }
+
+    fn skip_space(&mut self) -> Result<()> {
+        while let Some(c) = self.peek()? {
+            if c == '#' {
+                self.pos = self.line.len();
+            } else if c.is_whitespace() {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn escape(&mut self) -> Result<char> {
+        let c = self.expect_char()?;
+        let code = match c {
+            't' => return Ok('\t'),
+            'b' => return Ok('\u{8}'),
+            'n' => return Ok('\n'),
+            'r' => return Ok('\r'),
+            'f' => return Ok('\u{c}'),
+            'u' => 4,
+            'U' => 8,
+            _ => return Ok(c),
+        };
+        let mut hex = String::new();
+        for _ in 0..code {
+            hex.push(self.expect_char()?);
+        }
+        u32::from_str_radix(&hex, 16).ok().and_then(char::from_u32).ok_or_else(|| self.error("bad unicode escape"))
+    }
+
+    fn string(&mut self, quote: char) -> Result<String> {
+        let long = self.peek()? == Some(quote) && self.peek2() == Some(quote);
+        if self.peek()? == Some(quote) && !long {
+            self.pos += 1;
+            return Ok(String::new());
+        }
+        if long {
+            self.pos += 2;
+        }
+        let mut s = String::new();
+        loop {
+            match self.expect_char()? {
+                '\\' => s.push(self.escape()?),
+                c if c == quote && !long => return Ok(s),
+                c if c == quote && self.peek()? == Some(quote) && self.peek2() == Some(quote) => {
+                    self.pos += 2;
+                    return Ok(s);
+                }
+                '\n' if !long => return Err(self.error("unterminated string")),
+                c => s.push(c),
+            }
+        }
+    }
+
+    // Characters of a name; a trailing '.' ends the statement instead
+    fn name(&mut self) -> Result<String> {
+        let mut s = String::new();
+        while let Some(c) = self.peek()? {
+            if c == '\\' {
+                self.pos += 1;
+                s.push(self.expect_char()?);
+            } else if c.is_alphanumeric() || matches!(c, '_' | '-' | ':' | '.' | '%') || !c.is_ascii() {
+                self.pos += 1;
+                s.push(c);
+            } else {
+                break;
+            }
+        }
+        while s.ends_with('.') {
+            s.pop();
+            self.pos -= 1;
+        }
+        Ok(s)
+    }
+
+    fn number(&mut self) -> Result<String> {
+        let mut s = String::new();
+        while let Some(c) = self.peek()? {
+            let take = c.is_ascii_digit()
+                || (matches!(c, '+' | '-') && (s.is_empty() || s.ends_with(['e', 'E'])))
+                || (c == '.' && !s.contains(['.', 'e', 'E']) && self.peek2().is_some_and(|d| d.is_ascii_digit()))
+                || (matches!(c, 'e' | 'E') && !s.contains(['e', 'E']));
+            if !take {
+                break;
+            }
+            self.pos += 1;
+            s.push(c);
+        }
+        Ok(s)
+    }
+
+    fn next(&mut self) -> Result<Option<Token>> {
+        self.skip_space()?;
+        let Some(c) = self.peek()? else { return Ok(None) };
+        let token = match c {
+            '<' => {
+                self.pos += 1;
+                let mut iri = String::new();
+                loop {
+                    match self.expect_char()? {
+                        '>' => break,
+                        '\\' => iri.push(self.escape()?),
+                        c if c.is_whitespace() => return Err(self.error("space in IRI")),
+                        c => iri.push(c),
+                    }
+                }
+                Token::Iri(iri)
+            }
+            '"' | '\'' => {
+                self.pos += 1;
+                Token::Str(self.string(c)?)
+            }
+            '_' if self.peek2() == Some(':') => {
+                self.pos += 2;
+                Token::Blank(self.name()?)
+            }
+            '@' => {
+                self.pos += 1;
+                Token::At(self.name()?)
+            }
+            '^' if self.peek2() == Some('^') => {
+                self.pos += 2;
+                Token::DataType
+            }
+            '.' if !self.peek2().is_some_and(|d| d.is_ascii_digit()) => {
+                self.pos += 1;
+                Token::Dot
+            }
+            ';' | ',' | '[' | ']' | '(' => {
+                self.pos += 1;
+                match c {
+                    ';' => Token::Semicolon,
+                    ',' => Token::Comma,
+                    '[' => Token::LBracket,
+                    ']' => Token::RBracket,
+                    _ => Token::LParen,
+                }
+            }
+            c if c.is_ascii_digit() || matches!(c, '+' | '-' | '.') => Token::Number(self.number()?),
+            _ => {
+                let word = self.name()?;
+                if word.is_empty() {
+                    return Err(self.error(&format!("unexpected '{}'", c)));
+                }
+                match word.split_once(':') {
+                    Some((prefix, local)) => Token::PName(prefix.to_string(), local.to_string()),
+                    None => Token::Word(word),
+                }
+            }
+        };
+        Ok(Some(token))
+    }
+}
+
+struct TurtleParser<R> {
+    lexer: Lexer<R>,
+    peeked: Option<Token>,
+    prefixes: FxHashMap<String, String>,
+    base: Option<String>,
+    fresh: usize,
+    // Triples of the current statement
+    triples: Vec<(RdfTerm, String, RdfTerm)>,
+}
+
+impl<R: BufRead> TurtleParser<R> {
+    fn new(input: R) -> Self {
+        Self {
+            lexer: Lexer { input, line: Vec::new(), pos: 0, line_no: 0 },
+            peeked: None,
+            prefixes: FxHashMap::default(),
+            base: None,
+            fresh: 0,
+            triples: Vec::new(),
+        }
+    }
+
+    fn peek(&mut self) -> Result<Option<&Token>> {
+        if self.peeked.is_none() {
+            self.peeked = self.lexer.next()?;
+        }
+        Ok(self.peeked.as_ref())
+    }
+
+    fn next(&mut self) -> Result<Option<Token>> {
+        match self.peeked.take() {
+            Some(t) => Ok(Some(t)),
+            None => self.lexer.next(),
+        }
+    }
+
+    fn expect(&mut self) -> Result<Token> {
+        self.next()?.ok_or_else(|| self.lexer.error("unexpected end of input"))
+    }
+
+    fn expect_dot(&mut self) -> Result<()> {
+        match self.expect()? {
+            Token::Dot => Ok(()),
+            t => Err(self.lexer.error(&format!("expected '.', found {:?}", t))),
+        }
+    }
+
+    fn resolve(&self, iri: String) -> String {
+        match &self.base {
+            Some(base) if !iri.contains(':') => format!("{}{}", base, iri),
+            _ => iri,
+        }
+    }
+
+    fn iri(&self, token: Token) -> Result<String> {
+        match token {
+            Token::Iri(iri) => Ok(self.resolve(iri)),
+            Token::PName(prefix, local) => match self.prefixes.get(&prefix) {
+                Some(ns) => Ok(format!("{}{}", ns, local)),
+                None => Err(self.lexer.error(&format!("unknown prefix '{}:'", prefix))),
+            },
+            t => Err(self.lexer.error(&format!("expected an IRI, found {:?}", t))),
+        }
+    }
+
+    fn fresh_blank(&mut self) -> RdfTerm {
+        self.fresh += 1;
+        RdfTerm::Blank(format!("genid{}", self.fresh))
+    }
+
+    // The next statement's triples, None at the end of the input
+    fn statement(&mut self) -> Result<Option<Vec<(RdfTerm, String, RdfTerm)>>> {
+        loop {
+            let Some(token) = self.next()? else { return Ok(None) };
+            match token {
+                Token::At(ref d) if d == "prefix" => {
+                    self.prefix()?;
+                    self.expect_dot()?;
+                }
+                Token::At(ref d) if d == "base" => {
+                    self.base()?;
+                    self.expect_dot()?;
+                }
+                Token::Word(ref d) if d.eq_ignore_ascii_case("prefix") => self.prefix()?,
+                Token::Word(ref d) if d.eq_ignore_ascii_case("base") => self.base()?,
+                Token::LBracket => {
+                    let subject = self.property_list()?;
+                    if self.peek()? != Some(&Token::Dot) {
+                        self.predicate_objects(&subject)?;
+                    }
+                    self.expect_dot()?;
+                    return Ok(Some(std::mem::take(&mut self.triples)));
+                }
+                token => {
+                    let subject = match token {
+                        Token::Blank(b) => RdfTerm::Blank(b),
+                        t => RdfTerm::Iri(self.iri(t)?),
+                    };
+                    self.predicate_objects(&subject)?;
+                    self.expect_dot()?;
+                    return Ok(Some(std::mem::take(&mut self.triples)));
+                }
+            }
+        }
+    }
+
+    fn prefix(&mut self) -> Result<()> {
+        let Token::PName(prefix, local) = self.expect()? else {
+            return Err(self.lexer.error("expected a prefix name"));
+        };
+        if !local.is_empty() {
+            return Err(self.lexer.error("expected a prefix name"));
+        }
+        let ns = match self.expect()? {
+            Token::Iri(iri) => self.resolve(iri),
+            _ => return Err(self.lexer.error("expected a namespace IRI")),
+        };
+        self.prefixes.insert(prefix, ns);
+        Ok(())
+    }
+
+    fn base(&mut self) -> Result<()> {
+        match self.expect()? {
+            Token::Iri(iri) => {
+                self.base = Some(self.resolve(iri));
+                Ok(())
+            }
+            _ => Err(self.lexer.error("expected a base IRI")),
+        }
+    }
+
+    // After '[': a fresh blank node described by the list up to ']'
+    fn property_list(&mut self) -> Result<RdfTerm> {
+        let node = self.fresh_blank();
+        if self.peek()? != Some(&Token::RBracket) {
+            self.predicate_objects(&node)?;
+        }
+        match self.expect()? {
+            Token::RBracket => Ok(node),
+            t => Err(self.lexer.error(&format!("expected ']', found {:?}", t))),
+        }
+    }
+
+    fn predicate_objects(&mut self, subject: &RdfTerm) -> Result<()> {
+        loop {
+            let predicate = match self.expect()? {
+                Token::Word(w) if w == "a" => RDF_TYPE.to_string(),
+                t => self.iri(t)?,
+            };
+            loop {
+                let object = self.object()?;
+                self.triples.push((subject.clone(), predicate.clone(), object));
+                if self.peek()? != Some(&Token::Comma) {
+                    break;
+                }
+                self.next()?;
+            }
+            if self.peek()? != Some(&Token::Semicolon) {
+                return Ok(());
+            }
+            while self.peek()? == Some(&Token::Semicolon) {
+                self.next()?;
+            }
+            if matches!(self.peek()?, Some(Token::Dot | Token::RBracket)) {
+                return Ok(());
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<RdfTerm> {
+        let literal = |value: String, datatype: &str| RdfTerm::Literal { value, datatype: Some(format!("{}{}", XSD, datatype)) };
+        match self.expect()? {
+            Token::Blank(b) => Ok(RdfTerm::Blank(b)),
+            Token::LBracket => self.property_list(),
+            Token::LParen => Err(self.lexer.error("collections are not supported")),
+            Token::Str(value) => match self.peek()? {
+                Some(Token::At(_)) => {
+                    self.next()?;
+                    Ok(RdfTerm::Literal { value, datatype: None })
+                }
+                Some(Token::DataType) => {
+                    self.next()?;
+                    let token = self.expect()?;
+                    Ok(RdfTerm::Literal { value, datatype: Some(self.iri(token)?) })
+                }
+                _ => Ok(RdfTerm::Literal { value, datatype: None }),
+            },
+            Token::Number(n) if n.contains(['e', 'E']) => Ok(literal(n, "double")),
+            Token::Number(n) if n.contains('.') => Ok(literal(n, "decimal")),
+            Token::Number(n) => Ok(literal(n, "integer")),
+            Token::Word(w) if w == "true" || w == "false" => Ok(literal(w, "boolean")),
+            t => Ok(RdfTerm::Iri(self.iri(t)?)),
+        }
+    }
+}
+
+// Attribute value of a literal
+pub(super) fn literal_value(value: String, datatype: Option<&str>) -> TermSer {
+    let kind = datatype.and_then(|d| d.strip_prefix(XSD)).unwrap_or("string");
+    let integer = matches!(kind, "integer" | "int" | "long" | "short" | "byte" | "nonNegativeInteger"
+        | "positiveInteger" | "negativeInteger" | "nonPositiveInteger" | "unsignedInt" | "unsignedLong"
+        | "unsignedShort" | "unsignedByte");
+    match kind {
+        _ if integer => value.trim_start_matches('+').parse().map(TermSer::Int).unwrap_or(TermSer::Str(value)),
+        "boolean" => match value.as_str() {
+            "true" | "1" => TermSer::Bool(true),
+            "false" | "0" => TermSer::Bool(false),
+            _ => TermSer::Str(value),
+        },
+        _ => TermSer::Str(value),
+    }
+}
+
+struct Importer<'a> {
+    graph: &'a mut KnowledgeGraph,
+    syms: &'a mut SymbolTable,
+    iris: FxHashMap<Sym, NodeId>,
+    blanks: FxHashMap<String, NodeId>,
+    stats: RdfStats,
+}
+
+impl Importer<'_> {
+    fn node(&mut self, term: RdfTerm) -> NodeId {
+        let (label, blank) = match term {
+            RdfTerm::Iri(iri) => (self.syms.intern(&iri), None),
+            RdfTerm::Blank(b) => {
+                if let Some(&id) = self.blanks.get(&b) {
+                    return id;
+                }
+                (self.syms.intern(&format!("_:{}", b)), Some(b))
+            }
+            RdfTerm::Literal { .. } => unreachable!("literal as a node"),
+        };
+        let existing = match blank {
+            Some(_) => None,
+            None => self.iris.get(&label).copied().or_else(|| self.graph.nodes_by_label(label).first().copied()),
+        };
+        let id = existing.unwrap_or_else(|| {
+            self.stats.nodes += 1;
+            self.graph.add_node(label)
+        });
+        match blank {
+            Some(b) => self.blanks.insert(b, id),
+            None => self.iris.insert(label, id),
+        };
+        id
+    }
+
+    fn triple(&mut self, subject: RdfTerm, predicate: &str, object: RdfTerm) {
+        self.stats.triples += 1;
+        let s = self.node(subject);
+        let relation = self.syms.intern(predicate);
+        match object {
+            RdfTerm::Literal { value, datatype } => {
+                self.graph.add_node_attr(s, relation, literal_value(value, datatype.as_deref()));
+                self.stats.literals += 1;
+            }
+            object => {
+                let o = self.node(object);
+                if !self.graph.outgoing_edges(s).iter().any(|e| e.relation == relation && e.target == o) {
+                    self.graph.add_edge(s, relation, o);
+                    self.stats.edges += 1;
+                }
+            }
+        }
+    }
+}
+
+impl KnowledgeGraph {
+    pub fn import_turtle<R: BufRead>(&mut self, syms: &mut SymbolTable, input: R) -> Result<RdfStats> {
+        let mut parser = TurtleParser::new(input);
+        let mut importer = Importer { graph: self, syms, iris: FxHashMap::default(), blanks: FxHashMap::default(), stats: RdfStats::default() };
+        while let Some(triples) = parser.statement()? {
+            for (s, p, o) in triples {
+                importer.triple(s, &p, o);
+            }
+        }
+        Ok(importer.stats)
+    }
+
+    pub fn import_ntriples<R: BufRead>(&mut self, syms: &mut SymbolTable, input: R) -> Result<RdfStats> {
+        self.import_turtle(syms, input)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TURTLE: &str = r#"
+        @prefix ex: <http://example.org/> .
+        @prefix xsd: <http://www.w3.org/2001/XMLSchema#> .
+        # Two people
+        ex:ada a ex:Person ;
+            ex:name "Ada"@en, "Ada Lovelace" ;
+            ex:born 1815 ;
+            ex:height "1.65"^^xsd:decimal ;
+            ex:knows ex:charles, [ ex:name """Mary
+Somerville""" ] .
+        ex:charles a ex:Person; ex:alive false.
+    "#;
+
+    #[test]
+    fn imports_turtle() {
+        let (mut g, mut syms) = (KnowledgeGraph::new(), SymbolTable::new());
+        let stats = g.import_turtle(&mut syms, TURTLE.as_bytes()).unwrap();
+        assert_eq!(stats, RdfStats { triples: 10, nodes: 4, edges: 4, literals: 6 });
+
+        let sym = |syms: &mut SymbolTable, s: &str| syms.intern(&format!("http://example.org/{}", s));
+        let ada = g.nodes_by_label(sym(&mut syms, "ada"))[0];
+        let attrs = &g.node(ada).unwrap().attributes;
+        assert_eq!(attrs.iter().filter(|(k, _)| *k == sym(&mut syms, "name")).count(), 2);
+        assert!(attrs.contains(&(sym(&mut syms, "born"), TermSer::Int(1815))));
+        assert!(attrs.contains(&(sym(&mut syms, "height"), TermSer::Str("1.65".into()))));
+        let knows = sym(&mut syms, "knows");
+        let friends: Vec<NodeId> = g.outgoing_edges(ada).iter().filter(|e| e.relation == knows).map(|e| e.target).collect();
+        assert_eq!(friends.len(), 2);
+        let mary = g.node(friends[1]).unwrap();
+        assert_eq!(mary.attributes[0].1, TermSer::Str("Mary\nSomerville".into()));
+
+        // The same data again adds nothing
+        let again = g.import_turtle(&mut syms, TURTLE.as_bytes()).unwrap();
+        assert_eq!((again.nodes, again.edges), (1, 1));
+        assert!(g.import_turtle(&mut syms, "ex:x ex:y ex:z .".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn imports_ntriples() {
+        let doc = "<http://e.org/q42> <http://e.org/p31> <http://e.org/q5> .\n\
+                   _:b1 <http://e.org/p31> <http://e.org/q5> . # comment\n\
+                   <http://e.org/q42> <http://e.org/age> \"49\"^^<http://www.w3.org/2001/XMLSchema#integer> .\n\
+                   <http://e.org/q42> <http://e.org/label> \"Douglas \\\"Adams\\\" \\u00e9\" .\n";
+        let (mut g, mut syms) = (KnowledgeGraph::new(), SymbolTable::new());
+        let stats = g.import_ntriples(&mut syms, doc.as_bytes()).unwrap();
+        assert_eq!((stats.triples, stats.nodes, stats.edges), (4, 3, 2));
+        let q5 = g.nodes_by_label(syms.intern("http://e.org/q5"))[0];
+        assert_eq!(g.incoming_edges(q5).len(), 2);
+        let q42 = g.node(g.nodes_by_label(syms.intern("http://e.org/q42"))[0]).unwrap();
+        assert_eq!(q42.attributes[0].1, TermSer::Int(49));
+        assert_eq!(q42.attributes[1].1, TermSer::Str("Douglas \"Adams\" é".into()));
+
+        let err = g.import_ntriples(&mut syms, "<a> <b> \"open .\n".as_bytes()).unwrap_err();
+        assert!(err.to_string().contains("line 1"), "{}", err);
+    }
+}
@@ -0,0 +1,127 @@
+// Edge reification: statements about statements.
+//
+// reify(edge) gives a fact its own statement node, labelled with the edge's
+// relation and linked back to it through Node::reifies. Edges out of that
+// node are assertions about the fact (who said it, how sure, since when),
+// and are ordinary edges themselves, so they can be queried, decayed and
+// reified in turn:
+//
+//   let claim = graph.add_edge(ada, works_at, acme);
+//   graph.assert_about(claim, source, wikipedia);
+//   graph.assert_about(claim, confidence, high);
+//   for (fact, _) in graph.edges_asserted(source, Some(wikipedia)) { ... }
+//
+// Removing a reified edge removes its statement node and the assertions
+// with it.
+
+use crate::core::Sym;
+use super::graph::{Edge, EdgeId, KnowledgeGraph, NodeId};
+
+impl KnowledgeGraph {
+    // Statement node of the edge, created on first use; None if the edge is
+    // missing
+    pub fn reify(&mut self, edge: EdgeId) -> Option<NodeId> {
+        if let Some(statement) = self.statement_of(edge) {
+            return Some(statement);
+        }
+        let relation = self.edge(edge)?.relation;
+        let recording = self.journal.is_recording();
+        if recording {
+            self.begin();
+        }
+        let id = self.add_node(relation);
+        let mut node = self.node(id).cloned()?;
+        node.reifies = Some(edge);
+        self.replace_node(id, Some(node));
+        if recording {
+            self.commit();
+        }
+        Some(id)
+    }
+
+    // Edge a statement node is about
+    pub fn reified_edge(&self, node: NodeId) -> Option<EdgeId> {
+        self.node(node)?.reifies.filter(|&e| self.edge(e).is_some())
+    }
+
+    // Adds `relation -> target` to the edge's statement node
+    pub fn assert_about(&mut self, edge: EdgeId, relation: Sym, target: NodeId) -> Option<EdgeId> {
+        let statement = self.reify(edge)?;
+        Some(self.add_edge(statement, relation, target))
+    }
+
+    // Assertions made about the edge, empty if it is not reified
+    pub fn statements_about(&self, edge: EdgeId) -> Vec<&Edge> {
+        self.statement_of(edge).map(|s| self.outgoing_edges(s)).unwrap_or_default()
+    }
+
+    // Values asserted about the edge through one relation
+    pub fn asserted(&self, edge: EdgeId, relation: Sym) -> Vec<NodeId> {
+        self.statements_about(edge).iter().filter(|e| e.relation == relation).map(|e| e.target).collect()
+    }
+
+    // (reified edge, asserted value) for every `relation` assertion, limited
+    // to one value when given, in edge id order. A bound value is looked up
+    // through its incoming edges.
+    pub fn edges_asserted(&self, relation: Sym, value: Option<NodeId>) -> Vec<(EdgeId, NodeId)> {
+        let assertions: Vec<&Edge> = match value {
+            Some(v) => self.incoming_edges(v).into_iter().filter(|e| e.relation == relation).collect(),
+            None => self.edges_by_relation(relation).iter().filter_map(|&id| self.edge(id)).collect(),
+        };
+        let mut out: Vec<(EdgeId, NodeId)> = assertions.iter()
+            .filter_map(|a| Some((self.reified_edge(a.source)?, a.target)))
+            .collect();
+        out.sort_unstable();
+        out.dedup();
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERSON: Sym = 1;
+    const SOURCE: Sym = 2;
+    const WORKS_AT: Sym = 3;
+    const SAID_BY: Sym = 4;
+    const CONFIDENCE: Sym = 5;
+
+    #[test]
+    fn asserts_and_queries_facts_about_edges() {
+        let mut g = KnowledgeGraph::new().with_history();
+        let [ada, acme, wiki, blog, high] = [0; 5].map(|_| g.add_node(PERSON));
+        let claim = g.add_edge(ada, WORKS_AT, acme);
+        let other = g.add_edge(ada, WORKS_AT, wiki);
+        g.assert_about(claim, SAID_BY, wiki).unwrap();
+        g.assert_about(claim, CONFIDENCE, high).unwrap();
+        g.assert_about(other, SAID_BY, blog).unwrap();
+        assert!(g.assert_about(99, SAID_BY, blog).is_none());
+
+        let statement = g.statement_of(claim).unwrap();
+        assert_eq!(g.reify(claim), Some(statement));
+        assert_eq!(g.node(statement).unwrap().label, WORKS_AT);
+        assert_eq!(g.reified_edge(statement), Some(claim));
+        assert_eq!(g.statements_about(claim).len(), 2);
+        assert_eq!(g.asserted(claim, CONFIDENCE), vec![high]);
+        assert_eq!(g.edges_asserted(SAID_BY, None), vec![(claim, wiki), (other, blog)]);
+        assert_eq!(g.edges_asserted(SAID_BY, Some(blog)), vec![(other, blog)]);
+
+        // Statements about statements
+        let said = g.statements_about(claim)[0].id;
+        g.assert_about(said, SOURCE, blog).unwrap();
+        assert_eq!(g.asserted(said, SOURCE), vec![blog]);
+
+        // Survives a binary round trip
+        let (back, _) = KnowledgeGraph::from_binary(&g.to_binary(None)).unwrap();
+        assert_eq!(back.edges_asserted(SAID_BY, Some(wiki)), vec![(claim, wiki)]);
+
+        let units = g.history_len();
+        g.remove_edge(claim);
+        assert!(g.statement_of(claim).is_none() && g.statement_of(said).is_none());
+        assert!(g.edges_asserted(SAID_BY, Some(wiki)).is_empty());
+        g.undo(g.history_len() - units);
+        assert_eq!(g.statement_of(claim), Some(statement));
+        assert_eq!(g.asserted(said, SOURCE), vec![blog]);
+    }
+}
@@ -0,0 +1,315 @@
+// Schema declarations and constraint validation.
+//
+// A schema declares, per relation, which source labels (domain) and target
+// labels (range) it may connect and how many edges of it a node may have
+// going out or coming in, plus the attributes every node of a label must
+// carry. An empty domain or range accepts any label; with closed relations
+// an undeclared relation is itself a violation.
+//
+//   let schema = Schema::new()
+//       .with_relation(works_at, RelationSchema::new().with_domain(person).with_range(company).with_max_out(1))
+//       .with_required(person, name);
+//   let mut graph = KnowledgeGraph::new().with_schema(schema);
+//   graph.try_add_edge(ada, works_at, acme)?;   // Err(KolossError::Schema) if it breaks the schema
+//   for v in graph.validate() { eprintln!("{}", v); }
+//
+// The plain add_* methods never check: ingestion goes through try_add_node
+// and try_add_edge, which insert nothing when the result would break the
+// schema. validate() reports everything already in the graph, e.g. after
+// loading a snapshot or declaring a schema over existing data.
+
+use crate::core::{KolossError, Result, Sym, Term};
+use super::graph::{EdgeId, KnowledgeGraph, NodeId};
+use rustc_hash::FxHashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RelationSchema {
+    // Allowed source labels, empty for any
+    pub domain: Vec<Sym>,
+    // Allowed target labels, empty for any
+    pub range: Vec<Sym>,
+    // Edges of the relation per source
+    pub max_out: Option<usize>,
+    // Edges of the relation per target
+    pub max_in: Option<usize>,
+}
+
+impl RelationSchema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_domain(mut self, label: Sym) -> Self {
+        self.domain.push(label);
+        self
+    }
+
+    pub fn with_range(mut self, label: Sym) -> Self {
+        self.range.push(label);
+        self
+    }
+
+    pub fn with_max_out(mut self, max: usize) -> Self {
+        self.max_out = Some(max);
+        self
+    }
+
+    pub fn with_max_in(mut self, max: usize) -> Self {
+        self.max_in = Some(max);
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Schema {
+    pub relations: FxHashMap<Sym, RelationSchema>,
+    // Label -> attribute keys its nodes must have
+    pub required: FxHashMap<Sym, Vec<Sym>>,
+    // Reject relations without a declaration
+    pub closed: bool,
+}
+
+impl Schema {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_relation(mut self, relation: Sym, schema: RelationSchema) -> Self {
+        self.relations.insert(relation, schema);
+        self
+    }
+
+    pub fn with_required(mut self, label: Sym, key: Sym) -> Self {
+        let keys = self.required.entry(label).or_default();
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+        self
+    }
+
+    pub fn with_closed_relations(mut self, closed: bool) -> Self {
+        self.closed = closed;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Violation {
+    // Edge endpoint that is not in the graph
+    MissingNode { edge: Option<EdgeId>, node: NodeId },
+    UndeclaredRelation { edge: Option<EdgeId>, relation: Sym },
+    Domain { edge: Option<EdgeId>, relation: Sym, label: Sym },
+    Range { edge: Option<EdgeId>, relation: Sym, label: Sym },
+    // `node` has `count` outgoing (or incoming) `relation` edges, over `max`
+    Cardinality { node: NodeId, relation: Sym, outgoing: bool, count: usize, max: usize },
+    MissingAttribute { node: Option<NodeId>, label: Sym, key: Sym },
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let edge = |e: &Option<EdgeId>| e.map_or_else(|| "new edge".to_string(), |id| format!("edge {}", id));
+        match self {
+            Violation::MissingNode { edge: e, node } => write!(f, "{}: node {} does not exist", edge(e), node),
+            Violation::UndeclaredRelation { edge: e, relation } => write!(f, "{}: relation #{} is not declared", edge(e), relation),
+            Violation::Domain { edge: e, relation, label } => write!(f, "{}: source label #{} outside the domain of #{}", edge(e), label, relation),
+            Violation::Range { edge: e, relation, label } => write!(f, "{}: target label #{} outside the range of #{}", edge(e), label, relation),
+            Violation::Cardinality { node, relation, outgoing, count, max } => write!(
+                f, "node {}: {} {} #{} edges, at most {} allowed",
+                node, count, if *outgoing { "outgoing" } else { "incoming" }, relation, max,
+            ),
+            Violation::MissingAttribute { node, label, key } => match node {
+                Some(id) => write!(f, "node {}: #{} node without required attribute #{}", id, label, key),
+                None => write!(f, "new node: #{} node without required attribute #{}", label, key),
+            },
+        }
+    }
+}
+
+fn reject(violations: Vec<Violation>) -> KolossError {
+    let text: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+    KolossError::Schema(text.join("; "))
+}
+
+impl KnowledgeGraph {
+    pub fn with_schema(mut self, schema: Schema) -> Self {
+        self.schema = Some(schema);
+        self
+    }
+
+    // Replaces the schema without checking existing data (see validate)
+    pub fn set_schema(&mut self, schema: Option<Schema>) {
+        self.schema = schema;
+    }
+
+    pub fn schema(&self) -> Option<&Schema> {
+        self.schema.as_ref()
+    }
+
+    // Domain, range and declaration checks for one edge; cardinality is
+    // counted separately
+    fn edge_violations(&self, schema: &Schema, edge: Option<EdgeId>, source: NodeId, relation: Sym, target: NodeId) -> Vec<Violation> {
+        let mut out = Vec::new();
+        for node in [source, target] {
+            if !self.contains_node(node) && !out.contains(&Violation::MissingNode { edge, node }) {
+                out.push(Violation::MissingNode { edge, node });
+            }
+        }
+        let Some(decl) = schema.relations.get(&relation) else {
+            if schema.closed {
+                out.push(Violation::UndeclaredRelation { edge, relation });
+            }
+            return out;
+        };
+        if let Some(label) = self.label_of(source) {
+            if !decl.domain.is_empty() && !decl.domain.contains(&label) {
+                out.push(Violation::Domain { edge, relation, label });
+            }
+        }
+        if let Some(label) = self.label_of(target) {
+            if !decl.range.is_empty() && !decl.range.contains(&label) {
+                out.push(Violation::Range { edge, relation, label });
+            }
+        }
+        out
+    }
+
+    fn relation_count(&self, node: NodeId, relation: Sym, outgoing: bool) -> usize {
+        let edges = if outgoing { self.outgoing_edges(node) } else { self.incoming_edges(node) };
+        edges.iter().filter(|e| e.relation == relation).count()
+    }
+
+    // Every violation in the graph, nodes then edges then cardinalities, in
+    // id order; empty without a schema
+    pub fn validate(&self) -> Vec<Violation> {
+        let Some(schema) = &self.schema else {
+            return Vec::new();
+        };
+        let mut out = Vec::new();
+        let mut nodes: Vec<NodeId> = self.nodes().map(|n| n.id).collect();
+        nodes.sort_unstable();
+        for &id in &nodes {
+            let Some(node) = self.node(id) else { continue };
+            for &key in schema.required.get(&node.label).into_iter().flatten() {
+                if !node.attributes.iter().any(|(k, _)| *k == key) {
+                    out.push(Violation::MissingAttribute { node: Some(id), label: node.label, key });
+                }
+            }
+        }
+
+        let mut edges: Vec<EdgeId> = self.edges().map(|e| e.id).collect();
+        edges.sort_unstable();
+        for id in edges {
+            let Some(edge) = self.edge(id) else { continue };
+            out.extend(self.edge_violations(schema, Some(id), edge.source, edge.relation, edge.target));
+        }
+
+        let mut relations: Vec<(&Sym, &RelationSchema)> = schema.relations.iter().collect();
+        relations.sort_unstable_by_key(|(r, _)| **r);
+        for (&relation, decl) in relations {
+            for (max, outgoing) in [(decl.max_out, true), (decl.max_in, false)] {
+                let Some(max) = max else { continue };
+                for &node in &nodes {
+                    let count = self.relation_count(node, relation, outgoing);
+                    if count > max {
+                        out.push(Violation::Cardinality { node, relation, outgoing, count, max });
+                    }
+                }
+            }
+        }
+        out
+    }
+
+    // add_node_with_attrs, refused when a required attribute is missing
+    pub fn try_add_node(&mut self, label: Sym, attrs: Vec<(Sym, Term)>) -> Result<NodeId> {
+        if let Some(schema) = &self.schema {
+            let missing: Vec<Violation> = schema.required.get(&label).into_iter().flatten()
+                .filter(|&&key| !attrs.iter().any(|(k, _)| *k == key))
+                .map(|&key| Violation::MissingAttribute { node: None, label, key })
+                .collect();
+            if !missing.is_empty() {
+                return Err(reject(missing));
+            }
+        }
+        Ok(self.add_node_with_attrs(label, attrs))
+    }
+
+    // add_edge, refused when the edge would break the schema
+    pub fn try_add_edge(&mut self, source: NodeId, relation: Sym, target: NodeId) -> Result<EdgeId> {
+        if let Some(schema) = &self.schema {
+            let mut violations = self.edge_violations(schema, None, source, relation, target);
+            if let Some(decl) = schema.relations.get(&relation) {
+                for (node, max, outgoing) in [(source, decl.max_out, true), (target, decl.max_in, false)] {
+                    let Some(max) = max else { continue };
+                    let count = self.relation_count(node, relation, outgoing) + 1;
+                    if count > max {
+                        violations.push(Violation::Cardinality { node, relation, outgoing, count, max });
+                    }
+                }
+            }
+            if !violations.is_empty() {
+                return Err(reject(violations));
+            }
+        }
+        Ok(self.add_edge(source, relation, target))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PERSON: Sym = 1;
+    const COMPANY: Sym = 2;
+    const WORKS_AT: Sym = 3;
+    const NAME: Sym = 4;
+    const LIKES: Sym = 5;
+
+    fn schema() -> Schema {
+        Schema::new()
+            .with_relation(WORKS_AT, RelationSchema::new().with_domain(PERSON).with_range(COMPANY).with_max_out(1))
+            .with_required(PERSON, NAME)
+    }
+
+    #[test]
+    fn rejects_edges_and_nodes_that_break_the_schema() {
+        let mut g = KnowledgeGraph::new().with_schema(schema());
+        let ada = g.try_add_node(PERSON, vec![(NAME, Term::Str("Ada".into()))]).unwrap();
+        let acme = g.try_add_node(COMPANY, vec![]).unwrap();
+        let initech = g.add_node(COMPANY);
+        assert!(g.try_add_node(PERSON, vec![]).is_err());
+        assert_eq!(g.node_count(), 3);
+
+        g.try_add_edge(ada, WORKS_AT, acme).unwrap();
+        let err = g.try_add_edge(ada, WORKS_AT, initech).unwrap_err().to_string();
+        assert!(err.contains("at most 1"), "{}", err);
+        assert!(g.try_add_edge(acme, WORKS_AT, initech).is_err());
+        assert!(g.try_add_edge(ada, WORKS_AT, 99).is_err());
+        // Undeclared relations pass unless the schema is closed
+        g.try_add_edge(ada, LIKES, initech).unwrap();
+        g.set_schema(Some(schema().with_closed_relations(true)));
+        assert!(g.try_add_edge(ada, LIKES, acme).is_err());
+        assert_eq!(g.edge_count(), 2);
+        assert!(g.validate().iter().all(|v| matches!(v, Violation::UndeclaredRelation { .. })));
+    }
+
+    #[test]
+    fn validates_existing_data() {
+        let mut g = KnowledgeGraph::new();
+        let ada = g.add_node(PERSON);
+        let acme = g.add_node(COMPANY);
+        let initech = g.add_node(COMPANY);
+        g.add_edge(ada, WORKS_AT, acme);
+        g.add_edge(ada, WORKS_AT, initech);
+        let bad = g.add_edge(acme, WORKS_AT, ada);
+        assert!(g.validate().is_empty());
+
+        g.set_schema(Some(schema()));
+        assert_eq!(g.validate(), vec![
+            Violation::MissingAttribute { node: Some(ada), label: PERSON, key: NAME },
+            Violation::Domain { edge: Some(bad), relation: WORKS_AT, label: COMPANY },
+            Violation::Range { edge: Some(bad), relation: WORKS_AT, label: PERSON },
+            Violation::Cardinality { node: ada, relation: WORKS_AT, outgoing: true, count: 2, max: 1 },
+        ]);
+    }
+}
@@ -0,0 +1,726 @@
+// SPARQL subset over graphs imported from RDF (rdf.rs).
+//
+//   PREFIX ex: <http://example.org/>
+//   SELECT ?who ?year WHERE {
+//     ?who a ex:Person ; ex:born ?year ; ex:name ?name .
+//     FILTER (?year >= 1800 && CONTAINS(?name, "Ada"))
+//   } LIMIT 10
+//
+// Supported: PREFIX, SELECT [DISTINCT] with variables or *, basic graph
+// patterns with `;` and `,` lists and `a`, FILTER with || && ! and
+// comparisons on numbers, strings and IRIs, CONTAINS / STRSTARTS / STRENDS,
+// and LIMIT. Predicates must be IRIs. Resources are the nodes labelled with
+// their IRI, a predicate is an edge relation or, for literal objects, an
+// attribute key, following the importer's mapping.
+//
+// Patterns run one at a time, cheapest first given what is already bound:
+// a bound subject walks its outgoing edges and attributes, a bound object
+// resource its incoming edges, otherwise the relation index (plus a node
+// scan for literal values). Each FILTER runs as soon as its variables are
+// bound. Values are nodes (Value::Node) or literals (Value::Term).
+
+use crate::core::{KolossError, Result, Sym, SymbolTable};
+use super::graph::{KnowledgeGraph, NodeId, TermSer};
+use super::query::{CmpOp, QueryResult, Value};
+use super::rdf::{literal_value, RDF_TYPE, XSD};
+use rustc_hash::FxHashMap;
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PatternTerm {
+    Var(usize),
+    // Interned IRI, i.e. the label of the resource node
+    Iri(Sym),
+    Literal(TermSer),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TriplePattern {
+    pub subject: PatternTerm,
+    pub predicate: Sym,
+    pub object: PatternTerm,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StrFunc {
+    Contains,
+    StartsWith,
+    EndsWith,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Cmp(PatternTerm, CmpOp, PatternTerm),
+    Str(StrFunc, PatternTerm, PatternTerm),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Not(Box<Filter>),
+}
+
+impl Filter {
+    fn vars(&self, out: &mut Vec<usize>) {
+        let mut term = |t: &PatternTerm| {
+            if let PatternTerm::Var(v) = t {
+                out.push(*v);
+            }
+        };
+        match self {
+            Filter::Cmp(a, _, b) | Filter::Str(_, a, b) => {
+                term(a);
+                term(b);
+            }
+            Filter::And(a, b) | Filter::Or(a, b) => {
+                a.vars(out);
+                b.vars(out);
+            }
+            Filter::Not(a) => a.vars(out),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparqlQuery {
+    pub vars: Vec<String>,
+    // Selected variables, in column order
+    pub select: Vec<usize>,
+    pub distinct: bool,
+    pub patterns: Vec<TriplePattern>,
+    pub filters: Vec<Filter>,
+    pub limit: Option<usize>,
+}
+
+// --- Parsing ---
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Var(String),
+    Iri(String),
+    PName(String, String),
+    Str(String),
+    Num(String),
+    Word(String),
+    Punct(&'static str),
+}
+
+const PUNCT: [&str; 18] = ["&&", "||", "!=", "<=", ">=", "^^", "{", "}", "(", ")", ".", ";", ",", "*", "=", "<", ">", "!"];
+
+fn parse_error(msg: String) -> KolossError {
+    KolossError::Parse(format!("sparql: {}", msg))
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim_start();
+    let name_len = |s: &str| s.find(|c: char| !c.is_alphanumeric() && !matches!(c, '_' | '-' | ':' | '.')).unwrap_or(s.len());
+    while let Some(c) = rest.chars().next() {
+        let iri_end = rest.find(|c: char| c == '>' || c.is_whitespace()).filter(|&i| c == '<' && i > 1 && rest[i..].starts_with('>'));
+        let len = if let Some(end) = iri_end {
+            tokens.push(Token::Iri(rest[1..end].to_string()));
+            end + 1
+        } else if c == '?' || c == '$' {
+            let len = 1 + rest[1..].find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(rest.len() - 1);
+            tokens.push(Token::Var(rest[1..len].to_string()));
+            len
+        } else if c == '"' || c == '\'' {
+            let end = rest[1..].find(c).ok_or_else(|| parse_error("unterminated string".into()))?;
+            tokens.push(Token::Str(rest[1..end + 1].to_string()));
+            end + 2
+        } else if c.is_ascii_digit() || ((c == '-' || c == '+') && rest[1..].starts_with(|d: char| d.is_ascii_digit())) {
+            let len = 1 + rest[1..].find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(rest.len() - 1);
+            let len = if rest[..len].ends_with('.') { len - 1 } else { len };
+            tokens.push(Token::Num(rest[..len].to_string()));
+            len
+        } else if c == '#' {
+            rest.find('\n').unwrap_or(rest.len())
+        } else if c.is_alphabetic() || c == ':' || c == '_' {
+            let mut len = name_len(rest);
+            while rest[..len].ends_with('.') {
+                len -= 1;
+            }
+            let word = &rest[..len];
+            tokens.push(match word.split_once(':') {
+                Some((prefix, local)) => Token::PName(prefix.to_string(), local.to_string()),
+                None => Token::Word(word.to_string()),
+            });
+            len
+        } else if c == '@' {
+            // Language tags are ignored, as on import
+            1 + name_len(&rest[1..])
+        } else {
+            let p = PUNCT.iter().find(|p| rest.starts_with(**p)).ok_or_else(|| parse_error(format!("unexpected '{}'", c)))?;
+            tokens.push(Token::Punct(p));
+            p.len()
+        };
+        rest = rest[len..].trim_start();
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    syms: &'a mut SymbolTable,
+    prefixes: FxHashMap<String, String>,
+    query: SparqlQuery,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self.tokens.get(self.pos).cloned().ok_or_else(|| parse_error("unexpected end of query".into()))?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn is_punct(&self, p: &str) -> bool {
+        matches!(self.peek(), Some(Token::Punct(q)) if *q == p)
+    }
+
+    fn eat_punct(&mut self, p: &str) -> bool {
+        let found = self.is_punct(p);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect_punct(&mut self, p: &str) -> Result<()> {
+        if self.eat_punct(p) {
+            Ok(())
+        } else {
+            Err(parse_error(format!("expected '{}', found {:?}", p, self.peek())))
+        }
+    }
+
+    fn is_keyword(&self, kw: &str) -> bool {
+        matches!(self.peek(), Some(Token::Word(w)) if w.eq_ignore_ascii_case(kw))
+    }
+
+    fn eat_keyword(&mut self, kw: &str) -> bool {
+        let found = self.is_keyword(kw);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn var(&mut self, name: &str) -> usize {
+        match self.query.vars.iter().position(|v| v == name) {
+            Some(i) => i,
+            None => {
+                self.query.vars.push(name.to_string());
+                self.query.vars.len() - 1
+            }
+        }
+    }
+
+    fn iri(&mut self, token: Token) -> Result<Sym> {
+        let iri = match token {
+            Token::Iri(iri) => iri,
+            Token::PName(prefix, local) => match self.prefixes.get(&prefix) {
+                Some(ns) => format!("{}{}", ns, local),
+                None => return Err(parse_error(format!("unknown prefix '{}:'", prefix))),
+            },
+            Token::Word(w) if w == "a" => RDF_TYPE.to_string(),
+            t => return Err(parse_error(format!("expected an IRI, found {:?}", t))),
+        };
+        Ok(self.syms.intern(&iri))
+    }
+
+    fn term(&mut self) -> Result<PatternTerm> {
+        let token = self.next()?;
+        let number = |n: String, kind: &str| PatternTerm::Literal(literal_value(n, Some(&format!("{}{}", XSD, kind))));
+        Ok(match token {
+            Token::Var(name) => PatternTerm::Var(self.var(&name)),
+            Token::Str(s) => {
+                let datatype = if self.eat_punct("^^") {
+                    let token = self.next()?;
+                    let sym = self.iri(token)?;
+                    self.syms.resolve(sym).map(str::to_string)
+                } else {
+                    None
+                };
+                PatternTerm::Literal(literal_value(s, datatype.as_deref()))
+            }
+            Token::Num(n) if n.contains('.') => number(n, "decimal"),
+            Token::Num(n) => number(n, "integer"),
+            Token::Word(w) if w == "true" || w == "false" => number(w, "boolean"),
+            t => PatternTerm::Iri(self.iri(t)?),
+        })
+    }
+
+    fn parse(mut self) -> Result<SparqlQuery> {
+        while self.eat_keyword("PREFIX") {
+            let Token::PName(prefix, local) = self.next()? else {
+                return Err(parse_error("expected a prefix name".into()));
+            };
+            let Token::Iri(ns) = self.next()? else {
+                return Err(parse_error("expected a namespace IRI".into()));
+            };
+            if !local.is_empty() {
+                return Err(parse_error("expected a prefix name".into()));
+            }
+            self.prefixes.insert(prefix, ns);
+        }
+        if !self.eat_keyword("SELECT") {
+            return Err(parse_error("expected SELECT".into()));
+        }
+        self.query.distinct = self.eat_keyword("DISTINCT");
+        let all = self.eat_punct("*");
+        while let Some(Token::Var(name)) = self.peek().cloned() {
+            self.pos += 1;
+            let v = self.var(&name);
+            self.query.select.push(v);
+        }
+        if !all && self.query.select.is_empty() {
+            return Err(parse_error("nothing selected".into()));
+        }
+        self.eat_keyword("WHERE");
+        self.expect_punct("{")?;
+        while !self.eat_punct("}") {
+            if self.eat_keyword("FILTER") {
+                let filter = self.primary()?;
+                self.query.filters.push(filter);
+            } else {
+                self.triples()?;
+            }
+            self.eat_punct(".");
+        }
+        if self.eat_keyword("LIMIT") {
+            match self.next()? {
+                Token::Num(n) => self.query.limit = Some(n.parse().map_err(|_| parse_error(format!("bad limit {}", n)))?),
+                t => return Err(parse_error(format!("expected a limit, found {:?}", t))),
+            }
+        }
+        if let Some(t) = self.peek() {
+            return Err(parse_error(format!("unexpected {:?}", t)));
+        }
+        if all {
+            self.query.select = (0..self.query.vars.len()).collect();
+        }
+        if let Some(&v) = self.query.select.iter().find(|&&v| !self.query.patterns.iter().any(|p| p.subject == PatternTerm::Var(v) || p.object == PatternTerm::Var(v))) {
+            return Err(parse_error(format!("?{} is not in any pattern", self.query.vars[v])));
+        }
+        Ok(self.query)
+    }
+
+    fn triples(&mut self) -> Result<()> {
+        let subject = self.term()?;
+        if matches!(subject, PatternTerm::Literal(_)) {
+            return Err(parse_error("literal subject".into()));
+        }
+        loop {
+            let token = self.next()?;
+            let predicate = self.iri(token)?;
+            loop {
+                let object = self.term()?;
+                self.query.patterns.push(TriplePattern { subject: subject.clone(), predicate, object });
+                if !self.eat_punct(",") {
+                    break;
+                }
+            }
+            if !self.eat_punct(";") {
+                return Ok(());
+            }
+            if self.is_punct(".") || self.is_punct("}") {
+                return Ok(());
+            }
+        }
+    }
+
+    fn or(&mut self) -> Result<Filter> {
+        let mut left = self.and()?;
+        while self.eat_punct("||") {
+            left = Filter::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Filter> {
+        let mut left = self.unary()?;
+        while self.eat_punct("&&") {
+            left = Filter::And(Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    fn unary(&mut self) -> Result<Filter> {
+        if self.eat_punct("!") {
+            return Ok(Filter::Not(Box::new(self.unary()?)));
+        }
+        self.primary()
+    }
+
+    fn primary(&mut self) -> Result<Filter> {
+        if self.eat_punct("(") {
+            let inner = self.or()?;
+            self.expect_punct(")")?;
+            return Ok(inner);
+        }
+        let func = [("CONTAINS", StrFunc::Contains), ("STRSTARTS", StrFunc::StartsWith), ("STRENDS", StrFunc::EndsWith)]
+            .into_iter()
+            .find(|(name, _)| self.is_keyword(name));
+        if let Some((_, func)) = func {
+            self.pos += 1;
+            self.expect_punct("(")?;
+            let a = self.term()?;
+            self.expect_punct(",")?;
+            let b = self.term()?;
+            self.expect_punct(")")?;
+            return Ok(Filter::Str(func, a, b));
+        }
+        let a = self.term()?;
+        let op = match self.next()? {
+            Token::Punct("=") => CmpOp::Eq,
+            Token::Punct("!=") => CmpOp::Ne,
+            Token::Punct("<") => CmpOp::Lt,
+            Token::Punct("<=") => CmpOp::Le,
+            Token::Punct(">") => CmpOp::Gt,
+            Token::Punct(">=") => CmpOp::Ge,
+            t => return Err(parse_error(format!("expected a comparison, found {:?}", t))),
+        };
+        Ok(Filter::Cmp(a, op, self.term()?))
+    }
+}
+
+impl SparqlQuery {
+    pub fn parse(text: &str, syms: &mut SymbolTable) -> Result<Self> {
+        let parser = Parser {
+            tokens: tokenize(text)?,
+            pos: 0,
+            syms,
+            prefixes: FxHashMap::default(),
+            query: SparqlQuery { vars: Vec::new(), select: Vec::new(), distinct: false, patterns: Vec::new(), filters: Vec::new(), limit: None },
+        };
+        parser.parse()
+    }
+}
+
+// --- Execution ---
+
+#[derive(Debug, Clone, PartialEq)]
+enum Bound {
+    Node(NodeId),
+    Literal(TermSer),
+}
+
+struct Run<'a> {
+    query: &'a SparqlQuery,
+    graph: &'a KnowledgeGraph,
+    // Pattern order, and the filters to check once that many patterns ran
+    order: Vec<usize>,
+    checks: Vec<Vec<usize>>,
+    bindings: Vec<Option<Bound>>,
+    rows: Vec<Vec<Value>>,
+}
+
+fn number(v: &Bound) -> Option<f64> {
+    match v {
+        Bound::Literal(TermSer::Int(n)) => Some(*n as f64),
+        Bound::Literal(TermSer::Str(s)) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+// Numbers compare as numbers, strings as strings and resources by identity
+fn compare(a: &Bound, b: &Bound) -> Option<Ordering> {
+    match (a, b) {
+        (Bound::Node(x), Bound::Node(y)) => (x == y).then_some(Ordering::Equal),
+        _ => match (number(a), number(b)) {
+            (Some(x), Some(y)) => x.partial_cmp(&y),
+            _ => match (a, b) {
+                (Bound::Literal(x), Bound::Literal(y)) => Some(x.cmp(y)),
+                _ => None,
+            },
+        },
+    }
+}
+
+impl Run<'_> {
+    fn resource(&self, iri: Sym) -> Option<NodeId> {
+        self.graph.nodes_by_label(iri).first().copied()
+    }
+
+    // Value of a term under the current bindings; None for an unbound
+    // variable or an IRI with no node
+    fn value(&self, term: &PatternTerm) -> Option<Bound> {
+        match term {
+            PatternTerm::Var(v) => self.bindings[*v].clone(),
+            PatternTerm::Iri(iri) => self.resource(*iri).map(Bound::Node),
+            PatternTerm::Literal(l) => Some(Bound::Literal(l.clone())),
+        }
+    }
+
+    fn holds(&self, filter: &Filter) -> bool {
+        match filter {
+            Filter::Cmp(a, op, b) => {
+                let (Some(a), Some(b)) = (self.value(a), self.value(b)) else { return false };
+                let ord = compare(&a, &b);
+                match op {
+                    CmpOp::Eq => ord == Some(Ordering::Equal),
+                    CmpOp::Ne => ord != Some(Ordering::Equal),
+                    CmpOp::Lt => ord == Some(Ordering::Less),
+                    CmpOp::Le => matches!(ord, Some(Ordering::Less | Ordering::Equal)),
+                    CmpOp::Gt => ord == Some(Ordering::Greater),
+                    CmpOp::Ge => matches!(ord, Some(Ordering::Greater | Ordering::Equal)),
+                }
+            }
+            Filter::Str(func, a, b) => match (self.value(a), self.value(b)) {
+                (Some(Bound::Literal(TermSer::Str(a))), Some(Bound::Literal(TermSer::Str(b)))) => match func {
+                    StrFunc::Contains => a.contains(&b),
+                    StrFunc::StartsWith => a.starts_with(&b),
+                    StrFunc::EndsWith => a.ends_with(&b),
+                },
+                _ => false,
+            },
+            Filter::And(a, b) => self.holds(a) && self.holds(b),
+            Filter::Or(a, b) => self.holds(a) || self.holds(b),
+            Filter::Not(a) => !self.holds(a),
+        }
+    }
+
+    // Estimated matches of a pattern when `bound` variables are known
+    fn cost(&self, p: &TriplePattern, bound: &[bool]) -> usize {
+        let known = |t: &PatternTerm| match t {
+            PatternTerm::Var(v) => bound[*v],
+            _ => true,
+        };
+        if known(&p.subject) {
+            1
+        } else if known(&p.object) && !matches!(p.object, PatternTerm::Literal(_)) {
+            2
+        } else {
+            // A literal or open object may be an attribute: scan the nodes
+            self.graph.edges_by_relation(p.predicate).len() + self.graph.node_count()
+        }
+    }
+
+    fn plan(&mut self) {
+        let mut bound = vec![false; self.query.vars.len()];
+        let mut left: Vec<usize> = (0..self.query.patterns.len()).collect();
+        let mut bound_at = vec![0; self.query.vars.len()];
+        let mut unbound = vec![true; self.query.vars.len()];
+        while !left.is_empty() {
+            let (i, _) = left.iter().enumerate()
+                .min_by_key(|(_, &p)| self.cost(&self.query.patterns[p], &bound))
+                .expect("patterns left");
+            let p = left.remove(i);
+            self.order.push(p);
+            for t in [&self.query.patterns[p].subject, &self.query.patterns[p].object] {
+                if let PatternTerm::Var(v) = t {
+                    if !bound[*v] {
+                        bound[*v] = true;
+                        unbound[*v] = false;
+                        bound_at[*v] = self.order.len();
+                    }
+                }
+            }
+        }
+        self.checks = vec![Vec::new(); self.order.len() + 1];
+        for (f, filter) in self.query.filters.iter().enumerate() {
+            let mut vars = Vec::new();
+            filter.vars(&mut vars);
+            let at = vars.iter()
+                .map(|&v| if unbound[v] { self.order.len() } else { bound_at[v] })
+                .max()
+                .unwrap_or(0);
+            self.checks[at].push(f);
+        }
+    }
+
+    // (subject, object) pairs matching a pattern given the current bindings
+    fn candidates(&self, p: &TriplePattern) -> Vec<(NodeId, Bound)> {
+        let subject = match self.value(&p.subject) {
+            Some(Bound::Node(id)) => Some(id),
+            Some(Bound::Literal(_)) => return Vec::new(),
+            None if matches!(p.subject, PatternTerm::Iri(_)) => return Vec::new(),
+            None => None,
+        };
+        let object = self.value(&p.object);
+        if object.is_none() && !matches!(p.object, PatternTerm::Var(_)) {
+            return Vec::new();
+        }
+        let attrs = |id: NodeId, out: &mut Vec<(NodeId, Bound)>| {
+            if let Some(node) = self.graph.node(id) {
+                for (k, v) in &node.attributes {
+                    if *k == p.predicate {
+                        out.push((id, Bound::Literal(v.clone())));
+                    }
+                }
+            }
+        };
+        let mut out = Vec::new();
+        match (subject, &object) {
+            (Some(s), _) => {
+                for e in self.graph.outgoing_edges(s) {
+                    if e.relation == p.predicate {
+                        out.push((s, Bound::Node(e.target)));
+                    }
+                }
+                attrs(s, &mut out);
+            }
+            (None, Some(Bound::Node(o))) => {
+                for e in self.graph.incoming_edges(*o) {
+                    if e.relation == p.predicate {
+                        out.push((e.source, Bound::Node(*o)));
+                    }
+                }
+            }
+            (None, _) => {
+                for id in self.graph.edges_by_relation(p.predicate) {
+                    if let Some(e) = self.graph.edge(id) {
+                        out.push((e.source, Bound::Node(e.target)));
+                    }
+                }
+                let mut ids: Vec<NodeId> = self.graph.nodes().map(|n| n.id).collect();
+                ids.sort_unstable();
+                for id in ids {
+                    attrs(id, &mut out);
+                }
+            }
+        }
+        if let Some(o) = object {
+            out.retain(|(_, v)| compare(v, &o) == Some(Ordering::Equal));
+        }
+        out
+    }
+
+    fn bind(&mut self, term: &PatternTerm, value: Bound, set: &mut Vec<usize>) -> bool {
+        let PatternTerm::Var(v) = term else { return true };
+        match &self.bindings[*v] {
+            Some(existing) => *existing == value,
+            None => {
+                self.bindings[*v] = Some(value);
+                set.push(*v);
+                true
+            }
+        }
+    }
+
+    fn full(&self) -> bool {
+        self.query.limit.is_some_and(|l| self.rows.len() >= l)
+    }
+
+    fn step(&mut self, i: usize) {
+        if self.full() || !self.checks[i].iter().all(|&f| self.holds(&self.query.filters[f])) {
+            return;
+        }
+        if i == self.order.len() {
+            let row: Vec<Value> = self.query.select.iter().map(|&v| match &self.bindings[v] {
+                Some(Bound::Node(id)) => Value::Node(*id),
+                Some(Bound::Literal(l)) => Value::Term(l.to_term()),
+                None => Value::Null,
+            }).collect();
+            if !(self.query.distinct && self.rows.contains(&row)) {
+                self.rows.push(row);
+            }
+            return;
+        }
+        let query = self.query;
+        let pattern = &query.patterns[self.order[i]];
+        for (s, o) in self.candidates(pattern) {
+            let mut set = Vec::new();
+            if self.bind(&pattern.subject, Bound::Node(s), &mut set) && self.bind(&pattern.object, o, &mut set) {
+                self.step(i + 1);
+            }
+            for v in set {
+                self.bindings[v] = None;
+            }
+            if self.full() {
+                return;
+            }
+        }
+    }
+}
+
+impl SparqlQuery {
+    pub fn execute(&self, graph: &KnowledgeGraph) -> QueryResult {
+        let mut run = Run {
+            query: self,
+            graph,
+            order: Vec::new(),
+            checks: Vec::new(),
+            bindings: vec![None; self.vars.len()],
+            rows: Vec::new(),
+        };
+        run.plan();
+        run.step(0);
+        QueryResult { columns: self.select.iter().map(|&v| self.vars[v].clone()).collect(), rows: run.rows }
+    }
+}
+
+impl KnowledgeGraph {
+    pub fn sparql(&self, text: &str, syms: &mut SymbolTable) -> Result<QueryResult> {
+        Ok(SparqlQuery::parse(text, syms)?.execute(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Term;
+
+    const DATA: &str = r#"
+        @prefix ex: <http://example.org/> .
+        ex:ada a ex:Person ; ex:name "Ada Lovelace" ; ex:born 1815 ; ex:knows ex:charles, ex:mary .
+        ex:charles a ex:Person ; ex:name "Charles Babbage" ; ex:born 1791 .
+        ex:mary a ex:Person ; ex:name "Mary Somerville" ; ex:born 1780 ; ex:knows ex:charles .
+        ex:engine a ex:Machine ; ex:name "Analytical Engine" .
+    "#;
+
+    fn people() -> (KnowledgeGraph, SymbolTable) {
+        let (mut g, mut syms) = (KnowledgeGraph::new(), SymbolTable::new());
+        g.import_turtle(&mut syms, DATA.as_bytes()).unwrap();
+        (g, syms)
+    }
+
+    fn names(result: &QueryResult, column: &str) -> Vec<String> {
+        let mut out: Vec<String> = result.column(column).iter().map(|v| match v {
+            Value::Term(Term::Str(s)) => s.to_string(),
+            v => format!("{:?}", v),
+        }).collect();
+        out.sort();
+        out
+    }
+
+    #[test]
+    fn joins_patterns_and_filters() {
+        let (g, mut syms) = people();
+        let q = "PREFIX ex: <http://example.org/>
+                 SELECT ?name WHERE {
+                   ?p a ex:Person ; ex:born ?year ; ex:name ?name .
+                   FILTER (?year < 1800 && !CONTAINS(?name, \"Mary\"))
+                 }";
+        assert_eq!(names(&g.sparql(q, &mut syms).unwrap(), "name"), vec!["Charles Babbage"]);
+
+        let q = "PREFIX ex: <http://example.org/>
+                 SELECT DISTINCT ?friend WHERE { ?x ex:knows ?f . ?f ex:name ?friend . FILTER(STRSTARTS(?friend, \"C\")) }";
+        assert_eq!(g.sparql(q, &mut syms).unwrap().len(), 1);
+
+        let q = "SELECT * WHERE { <http://example.org/ada> <http://example.org/knows> ?f } LIMIT 1";
+        let result = g.sparql(q, &mut syms).unwrap();
+        assert_eq!((result.columns.clone(), result.len()), (vec!["f".to_string()], 1));
+
+        let q = "PREFIX ex: <http://example.org/> SELECT ?p WHERE { ?p ex:born 1815 }";
+        let ada = g.nodes_by_label(syms.intern("http://example.org/ada"))[0];
+        assert_eq!(g.sparql(q, &mut syms).unwrap().rows, vec![vec![Value::Node(ada)]]);
+    }
+
+    #[test]
+    fn plans_from_bound_terms() {
+        let (g, mut syms) = people();
+        let q = SparqlQuery::parse("PREFIX ex: <http://example.org/>
+            SELECT ?n WHERE { ?p ex:name ?n . ?p a ex:Machine }", &mut syms).unwrap();
+        let mut run = Run { query: &q, graph: &g, order: Vec::new(), checks: Vec::new(), bindings: vec![None; 2], rows: Vec::new() };
+        run.plan();
+        assert_eq!(run.order, vec![1, 0]);
+        assert_eq!(names(&q.execute(&g), "n"), vec!["Analytical Engine"]);
+
+        assert!(g.sparql("SELECT ?x WHERE { ?y <http://e/p> ?z }", &mut syms).is_err());
+        assert!(g.sparql("SELECT ?x WHERE { ?x ex:p ?z }", &mut syms).is_err());
+        assert!(g.sparql("SELECT ?x WHERE { ?x <http://e/p> ?z } LIMIT", &mut syms).is_err());
+    }
+}
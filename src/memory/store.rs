@@ -0,0 +1,1090 @@
+// Disk-backed graph store for graphs larger than memory.
+//
+// Records live in append-only segment files in a directory; memory only
+// holds where each record is plus the label, relation and adjacency
+// indexes, and a cache of the most recently read records. A KnowledgeGraph
+// opened on a directory keeps its cold records there, behind the same API:
+//
+//   let capacity = Capacity::new(1_000_000, EvictionPolicy::LeastRecentlyUsed);
+//   let mut graph = KnowledgeGraph::open_store("mem.store", config)?
+//       .with_capacity(capacity);
+//   let ada = graph.add_node(person);
+//   for edge in graph.outgoing_edges(ada) { ... }
+//   graph.sync_store()?;
+//
+// Such a graph keeps every index in memory but only its hot records: nodes
+// evicted over capacity (eviction.rs) are spilled to the store with their
+// edges instead of being removed. Reading a cold record (node, edge,
+// outgoing_edges...) fetches it from the cache or, on a miss, from disk and
+// holds it until the next tick or eviction; changing it moves it back into
+// memory. sync_store() spills everything and flushes, so reopening the
+// directory restores the graph. Decay and prune_weak only visit records in
+// memory, and full scans (nodes(), query_triple...) read every cold record.
+//
+// DiskGraph is the store itself, taking and returning owned records (node,
+// edge, label_of, nodes_by_label, outgoing_edges, query_triple...);
+// import() and to_graph() copy between it and an in-memory graph. Every
+// update appends the whole record and every removal a tombstone; the last
+// record of an id wins when the directory is reopened. A torn record at the
+// end of the last segment (crash mid-write) is cut off on open.
+//
+// Segment (NNNNNN.seg): [binary.rs header] then records
+//   [kind: u8] [len: u32] [payload: [u8; len]]
+// with node and edge payloads in the snapshot record format, an id for
+// tombstones and a u64 for ticks. compact() rewrites the live records into
+// fresh segments and deletes the old ones.
+
+use crate::core::{Sym, Term};
+use super::binary::{BinaryReader, BinaryWriter};
+use super::graph::{Edge, EdgeId, GraphSnapshot, KnowledgeGraph, Node, NodeId, TermSer};
+use rustc_hash::FxHashMap;
+use std::collections::BTreeMap;
+use std::collections::hash_map::Entry;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+const REC_NODE: u8 = 1;
+const REC_EDGE: u8 = 2;
+const REC_DROP_NODE: u8 = 3;
+const REC_DROP_EDGE: u8 = 4;
+const REC_TICK: u8 = 5;
+// kind + len
+const FRAME: u64 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StoreConfig {
+    // A new segment is started once the active one reaches this size
+    pub segment_bytes: u64,
+    // Records kept decoded in memory
+    pub cache_records: usize,
+}
+
+impl Default for StoreConfig {
+    fn default() -> Self {
+        Self { segment_bytes: 64 << 20, cache_records: 1 << 16 }
+    }
+}
+
+impl StoreConfig {
+    pub fn with_segment_bytes(mut self, bytes: u64) -> Self {
+        self.segment_bytes = bytes.max(1);
+        self
+    }
+
+    pub fn with_cache_records(mut self, records: usize) -> Self {
+        self.cache_records = records;
+        self
+    }
+}
+
+// Payload position of a record
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Loc {
+    segment: u32,
+    offset: u64,
+    len: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct NodeEntry {
+    loc: Loc,
+    label: Sym,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct EdgeEntry {
+    loc: Loc,
+    source: NodeId,
+    relation: Sym,
+    target: NodeId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Key {
+    Node(NodeId),
+    Edge(EdgeId),
+}
+
+#[derive(Debug, Clone)]
+enum Record {
+    Node(Node),
+    Edge(Edge),
+}
+
+// Least-recently-used records
+#[derive(Debug, Default)]
+struct Cache {
+    capacity: usize,
+    stamp: u64,
+    entries: FxHashMap<Key, (Record, u64)>,
+    order: BTreeMap<u64, Key>,
+    hits: u64,
+    misses: u64,
+}
+
+impl Cache {
+    fn get(&mut self, key: Key) -> Option<Record> {
+        self.stamp += 1;
+        let Some((record, stamp)) = self.entries.get_mut(&key) else {
+            self.misses += 1;
+            return None;
+        };
+        self.order.remove(stamp);
+        *stamp = self.stamp;
+        self.order.insert(self.stamp, key);
+        self.hits += 1;
+        Some(record.clone())
+    }
+
+    fn put(&mut self, key: Key, record: Record) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.remove(key);
+        self.stamp += 1;
+        self.entries.insert(key, (record, self.stamp));
+        self.order.insert(self.stamp, key);
+        while self.entries.len() > self.capacity {
+            let Some((_, oldest)) = self.order.pop_first() else { break };
+            self.entries.remove(&oldest);
+        }
+    }
+
+    fn remove(&mut self, key: Key) {
+        if let Some((_, stamp)) = self.entries.remove(&key) {
+            self.order.remove(&stamp);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Io {
+    writer: BufWriter<File>,
+    active: u32,
+    active_len: u64,
+    // Unflushed bytes in `writer`
+    dirty: bool,
+    readers: FxHashMap<u32, File>,
+    // Format version of each segment's records
+    versions: FxHashMap<u32, u8>,
+    cache: Cache,
+}
+
+#[derive(Debug)]
+pub struct DiskGraph {
+    dir: PathBuf,
+    config: StoreConfig,
+    nodes: FxHashMap<NodeId, NodeEntry>,
+    edges: FxHashMap<EdgeId, EdgeEntry>,
+    outgoing: FxHashMap<NodeId, Vec<EdgeId>>,
+    incoming: FxHashMap<NodeId, Vec<EdgeId>>,
+    by_label: FxHashMap<Sym, Vec<NodeId>>,
+    by_relation: FxHashMap<Sym, Vec<EdgeId>>,
+    next_node_id: NodeId,
+    next_edge_id: EdgeId,
+    tick: u64,
+    // Bytes of all segments, and of superseded records and tombstones
+    total_bytes: u64,
+    garbage_bytes: u64,
+    // A tick record was written, so the next one supersedes it
+    ticked: bool,
+    io: Mutex<Io>,
+}
+
+fn segment_path(dir: &Path, segment: u32) -> PathBuf {
+    dir.join(format!("{:06}.seg", segment))
+}
+
+fn segment_header() -> Vec<u8> {
+    let mut w = BinaryWriter::new();
+    w.write_header();
+    w.into_bytes()
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+fn u32_at(data: &[u8], at: usize) -> Option<u32> {
+    data.get(at..at + 4).map(|b| u32::from_le_bytes(b.try_into().expect("4 bytes")))
+}
+
+fn remove_id(index: &mut FxHashMap<u32, Vec<u32>>, key: u32, id: u32) {
+    if let Some(ids) = index.get_mut(&key) {
+        ids.retain(|&x| x != id);
+    }
+}
+
+impl DiskGraph {
+    // Opens (or creates) the store in `dir`, rebuilding the indexes from its
+    // segments
+    pub fn open<P: AsRef<Path>>(dir: P, config: StoreConfig) -> io::Result<Self> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        let mut segments: Vec<u32> = fs::read_dir(&dir)?
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.strip_suffix(".seg")?.parse().ok())
+            .collect();
+        segments.sort_unstable();
+
+        let active = segments.last().copied().unwrap_or(1);
+        let path = segment_path(&dir, active);
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let mut store = Self {
+            dir,
+            config,
+            nodes: FxHashMap::default(),
+            edges: FxHashMap::default(),
+            outgoing: FxHashMap::default(),
+            incoming: FxHashMap::default(),
+            by_label: FxHashMap::default(),
+            by_relation: FxHashMap::default(),
+            next_node_id: 1,
+            next_edge_id: 1,
+            tick: 0,
+            total_bytes: 0,
+            garbage_bytes: 0,
+            ticked: false,
+            io: Mutex::new(Io {
+                writer: BufWriter::new(file),
+                active,
+                active_len: 0,
+                dirty: false,
+                readers: FxHashMap::default(),
+                versions: FxHashMap::default(),
+                cache: Cache { capacity: config.cache_records, ..Cache::default() },
+            }),
+        };
+        for &segment in &segments {
+            store.replay(segment, segment == active)?;
+        }
+        let io = store.io.get_mut().unwrap_or_else(|e| e.into_inner());
+        io.active_len = fs::metadata(&path)?.len();
+        if io.active_len == 0 {
+            let header = segment_header();
+            io.writer.write_all(&header)?;
+            io.writer.flush()?;
+            io.active_len = header.len() as u64;
+            io.versions.insert(active, BinaryReader::new(&header).read_header().expect("own header"));
+            store.total_bytes += io.active_len;
+        }
+        Ok(store)
+    }
+
+    // Indexes the records of one segment; a torn tail of the active one is
+    // truncated
+    fn replay(&mut self, segment: u32, active: bool) -> io::Result<()> {
+        let path = segment_path(&self.dir, segment);
+        let data = fs::read(&path)?;
+        if data.is_empty() {
+            return Ok(());
+        }
+        let mut header = BinaryReader::new(&data);
+        let version = header.read_header().ok_or_else(|| invalid("not a graph store segment"))?;
+        let mut pos = data.len() - header.remaining();
+        self.io.get_mut().unwrap_or_else(|e| e.into_inner()).versions.insert(segment, version);
+        self.total_bytes += pos as u64;
+        while pos < data.len() {
+            let kind = data[pos];
+            let Some(len) = u32_at(&data, pos + 1) else { break };
+            let start = pos + FRAME as usize;
+            let Some(payload) = data.get(start..start + len as usize) else { break };
+            let loc = Loc { segment, offset: start as u64, len };
+            let ok = match kind {
+                REC_NODE => u32_at(payload, 0).zip(u32_at(payload, 4)).map(|(id, label)| {
+                    self.index_node(id, NodeEntry { loc, label });
+                }),
+                REC_EDGE => u32_at(payload, 0).zip(u32_at(payload, 4)).zip(u32_at(payload, 8).zip(u32_at(payload, 12))).map(
+                    |((id, relation), (source, target))| self.index_edge(id, EdgeEntry { loc, source, relation, target }),
+                ),
+                REC_DROP_NODE => u32_at(payload, 0).map(|id| {
+                    self.unindex_node(id);
+                    self.garbage_bytes += FRAME + 4;
+                }),
+                REC_DROP_EDGE => u32_at(payload, 0).map(|id| {
+                    self.unindex_edge(id);
+                    self.garbage_bytes += FRAME + 4;
+                }),
+                REC_TICK => payload.get(..8).map(|b| {
+                    self.tick = u64::from_le_bytes(b.try_into().expect("8 bytes"));
+                    self.note_tick();
+                }),
+                _ => None,
+            };
+            if ok.is_none() {
+                break;
+            }
+            self.total_bytes += FRAME + len as u64;
+            pos = start + len as usize;
+        }
+        if pos < data.len() {
+            if !active {
+                return Err(invalid("damaged record in a sealed segment"));
+            }
+            OpenOptions::new().write(true).open(&path)?.set_len(pos as u64)?;
+        }
+        Ok(())
+    }
+
+    fn note_tick(&mut self) {
+        if self.ticked {
+            self.garbage_bytes += FRAME + 8;
+        }
+        self.ticked = true;
+    }
+
+    fn index_node(&mut self, id: NodeId, entry: NodeEntry) {
+        match self.nodes.insert(id, entry) {
+            Some(old) => {
+                self.garbage_bytes += FRAME + old.loc.len as u64;
+                remove_id(&mut self.by_label, old.label, id);
+            }
+            None => self.next_node_id = self.next_node_id.max(id + 1),
+        }
+        self.by_label.entry(entry.label).or_default().push(id);
+    }
+
+    fn unindex_node(&mut self, id: NodeId) -> bool {
+        let Some(old) = self.nodes.remove(&id) else {
+            return false;
+        };
+        self.garbage_bytes += FRAME + old.loc.len as u64;
+        remove_id(&mut self.by_label, old.label, id);
+        true
+    }
+
+    fn index_edge(&mut self, id: EdgeId, entry: EdgeEntry) {
+        if self.edges.contains_key(&id) {
+            self.unindex_edge(id);
+        }
+        self.next_edge_id = self.next_edge_id.max(id + 1);
+        self.outgoing.entry(entry.source).or_default().push(id);
+        self.incoming.entry(entry.target).or_default().push(id);
+        self.by_relation.entry(entry.relation).or_default().push(id);
+        self.edges.insert(id, entry);
+    }
+
+    fn unindex_edge(&mut self, id: EdgeId) -> bool {
+        let Some(old) = self.edges.remove(&id) else {
+            return false;
+        };
+        self.garbage_bytes += FRAME + old.loc.len as u64;
+        remove_id(&mut self.outgoing, old.source, id);
+        remove_id(&mut self.incoming, old.target, id);
+        remove_id(&mut self.by_relation, old.relation, id);
+        true
+    }
+
+    // --- Writing ---
+
+    fn append(&mut self, kind: u8, payload: &[u8]) -> io::Result<Loc> {
+        let (dir, limit) = (self.dir.clone(), self.config.segment_bytes);
+        let io = self.io.get_mut().unwrap_or_else(|e| e.into_inner());
+        if io.active_len >= limit {
+            io.writer.flush()?;
+            io.active += 1;
+            let file = OpenOptions::new().create(true).append(true).open(segment_path(&dir, io.active))?;
+            io.writer = BufWriter::new(file);
+            let header = segment_header();
+            io.writer.write_all(&header)?;
+            io.versions.insert(io.active, BinaryReader::new(&header).read_header().expect("own header"));
+            io.active_len = header.len() as u64;
+            self.total_bytes += header.len() as u64;
+        }
+        io.writer.write_all(&[kind])?;
+        io.writer.write_all(&(payload.len() as u32).to_le_bytes())?;
+        io.writer.write_all(payload)?;
+        io.dirty = true;
+        let loc = Loc { segment: io.active, offset: io.active_len + FRAME, len: payload.len() as u32 };
+        io.active_len += FRAME + payload.len() as u64;
+        self.total_bytes += FRAME + payload.len() as u64;
+        Ok(loc)
+    }
+
+    // Inserts or replaces the node with this id
+    pub fn put_node(&mut self, node: Node) -> io::Result<()> {
+        let mut w = BinaryWriter::new();
+        w.write_node(&node);
+        let loc = self.append(REC_NODE, &w.into_bytes())?;
+        self.index_node(node.id, NodeEntry { loc, label: node.label });
+        self.cache().put(Key::Node(node.id), Record::Node(node));
+        Ok(())
+    }
+
+    // Inserts or replaces the edge with this id
+    pub fn put_edge(&mut self, edge: Edge) -> io::Result<()> {
+        let mut w = BinaryWriter::new();
+        w.write_edge(&edge);
+        let loc = self.append(REC_EDGE, &w.into_bytes())?;
+        self.index_edge(edge.id, EdgeEntry { loc, source: edge.source, relation: edge.relation, target: edge.target });
+        self.cache().put(Key::Edge(edge.id), Record::Edge(edge));
+        Ok(())
+    }
+
+    pub fn add_node(&mut self, label: Sym) -> io::Result<NodeId> {
+        self.add_node_with_attrs(label, Vec::new())
+    }
+
+    // Attributes without a TermSer form are skipped
+    pub fn add_node_with_attrs(&mut self, label: Sym, attrs: Vec<(Sym, Term)>) -> io::Result<NodeId> {
+        let id = self.next_node_id;
+        let attributes = attrs.iter().filter_map(|(k, v)| Some((*k, TermSer::from_term(v)?))).collect();
+        self.put_node(Node {
+            id,
+            label,
+            attributes,
+            created_at: self.tick,
+            last_access: self.tick,
+            access_count: 0,
+            weight: 1.0,
+            reifies: None,
+            pinned: false,
+        })?;
+        Ok(id)
+    }
+
+    pub fn add_edge(&mut self, source: NodeId, relation: Sym, target: NodeId) -> io::Result<EdgeId> {
+        let id = self.next_edge_id;
+        self.put_edge(Edge {
+            id,
+            relation,
+            source,
+            target,
+            weight: 1.0,
+            attributes: Vec::new(),
+            created_at: self.tick,
+            last_access: self.tick,
+            access_count: 0,
+            valid_from: None,
+            valid_to: None,
+            context: None,
+            pinned: false,
+        })?;
+        Ok(id)
+    }
+
+    // Set or replace one attribute; false if the node is missing or the
+    // value has no TermSer form
+    pub fn set_node_attr(&mut self, id: NodeId, key: Sym, value: &Term) -> io::Result<bool> {
+        let (Some(mut node), Some(ts)) = (self.node(id), TermSer::from_term(value)) else {
+            return Ok(false);
+        };
+        match node.attributes.iter_mut().find(|(k, _)| *k == key) {
+            Some(slot) => slot.1 = ts,
+            None => node.attributes.push((key, ts)),
+        }
+        self.put_node(node)?;
+        Ok(true)
+    }
+
+    pub fn remove_edge(&mut self, id: EdgeId) -> io::Result<bool> {
+        self.drop_record(Key::Edge(id))
+    }
+
+    // Tombstone for one record; the edges of a node are left alone
+    fn drop_record(&mut self, key: Key) -> io::Result<bool> {
+        let (kind, id, found) = match key {
+            Key::Node(id) => (REC_DROP_NODE, id, self.nodes.contains_key(&id)),
+            Key::Edge(id) => (REC_DROP_EDGE, id, self.edges.contains_key(&id)),
+        };
+        if !found {
+            return Ok(false);
+        }
+        self.append(kind, &id.to_le_bytes())?;
+        match key {
+            Key::Node(id) => self.unindex_node(id),
+            Key::Edge(id) => self.unindex_edge(id),
+        };
+        self.garbage_bytes += FRAME + 4;
+        self.cache().remove(key);
+        Ok(true)
+    }
+
+    // Removes the node with its edges
+    pub fn remove_node(&mut self, id: NodeId) -> io::Result<bool> {
+        if !self.nodes.contains_key(&id) {
+            return Ok(false);
+        }
+        let mut edges: Vec<EdgeId> = self.outgoing.get(&id).into_iter().chain(self.incoming.get(&id)).flatten().copied().collect();
+        edges.sort_unstable();
+        edges.dedup();
+        for edge in edges {
+            self.remove_edge(edge)?;
+        }
+        self.drop_record(Key::Node(id))?;
+        self.outgoing.remove(&id);
+        self.incoming.remove(&id);
+        Ok(true)
+    }
+
+    pub fn tick(&mut self) -> io::Result<()> {
+        self.set_tick(self.tick + 1)
+    }
+
+    fn set_tick(&mut self, tick: u64) -> io::Result<()> {
+        self.tick = tick;
+        self.append(REC_TICK, &tick.to_le_bytes())?;
+        self.note_tick();
+        Ok(())
+    }
+
+    pub fn current_tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        let io = self.io.get_mut().unwrap_or_else(|e| e.into_inner());
+        io.writer.flush()?;
+        io.dirty = false;
+        io.writer.get_ref().sync_data()
+    }
+
+    // Share of segment bytes held by replaced or removed records
+    pub fn garbage_ratio(&self) -> f64 {
+        if self.total_bytes == 0 {
+            return 0.0;
+        }
+        self.garbage_bytes as f64 / self.total_bytes as f64
+    }
+
+    // Rewrites the live records into new segments and deletes the old ones.
+    // Records are streamed one at a time in segment order, so only the
+    // location index is held in memory, never the graph.
+    pub fn compact(&mut self) -> io::Result<()> {
+        self.flush()?;
+        let mut nodes: Vec<(NodeId, Loc)> = std::mem::take(&mut self.nodes).into_iter().map(|(id, e)| (id, e.loc)).collect();
+        let mut edges: Vec<(EdgeId, Loc)> = std::mem::take(&mut self.edges).into_iter().map(|(id, e)| (id, e.loc)).collect();
+        nodes.sort_unstable_by_key(|(_, loc)| (loc.segment, loc.offset));
+        edges.sort_unstable_by_key(|(_, loc)| (loc.segment, loc.offset));
+
+        let old_last = self.io.get_mut().unwrap_or_else(|e| e.into_inner()).active;
+        let (next_ids, tick) = ((self.next_node_id, self.next_edge_id), self.tick);
+        for index in [&mut self.outgoing, &mut self.incoming, &mut self.by_label, &mut self.by_relation] {
+            index.clear();
+        }
+        self.total_bytes = 0;
+        self.garbage_bytes = 0;
+        self.ticked = false;
+        // Force a fresh segment
+        self.io.get_mut().unwrap_or_else(|e| e.into_inner()).active_len = u64::MAX;
+        for (id, loc) in nodes {
+            match self.read(Key::Node(id), loc) {
+                Some(Record::Node(node)) => self.put_node(node)?,
+                _ => return Err(invalid("unreadable node")),
+            }
+        }
+        for (id, loc) in edges {
+            match self.read(Key::Edge(id), loc) {
+                Some(Record::Edge(edge)) => self.put_edge(edge)?,
+                _ => return Err(invalid("unreadable edge")),
+            }
+        }
+        self.append(REC_TICK, &tick.to_le_bytes())?;
+        self.ticked = true;
+        (self.next_node_id, self.next_edge_id) = next_ids;
+        self.flush()?;
+
+        let io = self.io.get_mut().unwrap_or_else(|e| e.into_inner());
+        let old: Vec<u32> = io.versions.keys().copied().filter(|&s| s <= old_last).collect();
+        for segment in old {
+            io.readers.remove(&segment);
+            io.versions.remove(&segment);
+            fs::remove_file(segment_path(&self.dir, segment))?;
+        }
+        Ok(())
+    }
+
+    // --- Reading ---
+
+    fn cache(&mut self) -> &mut Cache {
+        &mut self.io.get_mut().unwrap_or_else(|e| e.into_inner()).cache
+    }
+
+    fn read(&self, key: Key, loc: Loc) -> Option<Record> {
+        let mut io = self.io.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(record) = io.cache.get(key) {
+            return Some(record);
+        }
+        if loc.segment == io.active && io.dirty {
+            io.writer.flush().ok()?;
+            io.dirty = false;
+        }
+        let version = *io.versions.get(&loc.segment)?;
+        let file = match io.readers.entry(loc.segment) {
+            Entry::Occupied(slot) => slot.into_mut(),
+            Entry::Vacant(slot) => slot.insert(File::open(segment_path(&self.dir, loc.segment)).ok()?),
+        };
+        let mut payload = vec![0; loc.len as usize];
+        file.seek(SeekFrom::Start(loc.offset)).ok()?;
+        file.read_exact(&mut payload).ok()?;
+        let mut reader = BinaryReader::new(&payload).with_version(version);
+        let record = match key {
+            Key::Node(_) => Record::Node(reader.read_node()?),
+            Key::Edge(_) => Record::Edge(reader.read_edge()?),
+        };
+        io.cache.put(key, record.clone());
+        Some(record)
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<Node> {
+        match self.read(Key::Node(id), self.nodes.get(&id)?.loc)? {
+            Record::Node(node) => Some(node),
+            Record::Edge(_) => None,
+        }
+    }
+
+    pub fn edge(&self, id: EdgeId) -> Option<Edge> {
+        match self.read(Key::Edge(id), self.edges.get(&id)?.loc)? {
+            Record::Edge(edge) => Some(edge),
+            Record::Node(_) => None,
+        }
+    }
+
+    // From the in-memory index, without reading the record
+    pub fn label_of(&self, id: NodeId) -> Option<Sym> {
+        self.nodes.get(&id).map(|n| n.label)
+    }
+
+    pub fn contains_node(&self, id: NodeId) -> bool {
+        self.nodes.contains_key(&id)
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub fn next_ids(&self) -> (NodeId, EdgeId) {
+        (self.next_node_id, self.next_edge_id)
+    }
+
+    pub fn nodes_by_label(&self, label: Sym) -> Vec<NodeId> {
+        self.by_label.get(&label).cloned().unwrap_or_default()
+    }
+
+    pub fn edges_by_relation(&self, relation: Sym) -> Vec<EdgeId> {
+        self.by_relation.get(&relation).cloned().unwrap_or_default()
+    }
+
+    pub fn outgoing_edges(&self, node: NodeId) -> Vec<Edge> {
+        self.outgoing.get(&node).into_iter().flatten().filter_map(|&id| self.edge(id)).collect()
+    }
+
+    pub fn incoming_edges(&self, node: NodeId) -> Vec<Edge> {
+        self.incoming.get(&node).into_iter().flatten().filter_map(|&id| self.edge(id)).collect()
+    }
+
+    // Without reading any record
+    pub fn neighbors(&self, node: NodeId) -> Vec<NodeId> {
+        let mut ids: Vec<NodeId> = self.outgoing.get(&node).into_iter().flatten().map(|e| self.edges[e].target)
+            .chain(self.incoming.get(&node).into_iter().flatten().map(|e| self.edges[e].source))
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    // Same as KnowledgeGraph::query_triple, answered from the indexes
+    pub fn query_triple(&self, source_label: Option<Sym>, relation: Option<Sym>, target_label: Option<Sym>) -> Vec<(NodeId, EdgeId, NodeId)> {
+        let ids: Vec<EdgeId> = match relation {
+            Some(r) => self.edges_by_relation(r),
+            None => self.edges.keys().copied().collect(),
+        };
+        let mut out: Vec<(NodeId, EdgeId, NodeId)> = ids.into_iter()
+            .filter_map(|id| {
+                let e = self.edges.get(&id)?;
+                let fits = |label: Option<Sym>, node: NodeId| label.is_none_or(|l| self.label_of(node) == Some(l));
+                (fits(source_label, e.source) && fits(target_label, e.target)).then_some((e.source, id, e.target))
+            })
+            .collect();
+        out.sort_unstable_by_key(|&(_, id, _)| id);
+        out
+    }
+
+    // (hits, misses) of the record cache
+    pub fn cache_stats(&self) -> (u64, u64) {
+        let io = self.io.lock().unwrap_or_else(|e| e.into_inner());
+        (io.cache.hits, io.cache.misses)
+    }
+
+    // Everything loaded into memory
+    pub fn to_graph(&self) -> io::Result<KnowledgeGraph> {
+        let nodes = self.nodes.keys().map(|&id| self.node(id).ok_or_else(|| invalid("unreadable node"))).collect::<io::Result<_>>()?;
+        let edges = self.edges.keys().map(|&id| self.edge(id).ok_or_else(|| invalid("unreadable edge"))).collect::<io::Result<_>>()?;
+        Ok(KnowledgeGraph::from_snapshot(GraphSnapshot {
+            nodes,
+            edges,
+            next_node_id: self.next_node_id,
+            next_edge_id: self.next_edge_id,
+            tick: self.tick,
+        }))
+    }
+
+    // Copies a graph into the store, keeping ids
+    pub fn import(&mut self, graph: &KnowledgeGraph) -> io::Result<()> {
+        let mut nodes: Vec<&Node> = graph.nodes().collect();
+        let mut edges: Vec<&Edge> = graph.edges().collect();
+        nodes.sort_unstable_by_key(|n| n.id);
+        edges.sort_unstable_by_key(|e| e.id);
+        for node in nodes {
+            self.put_node(node.clone())?;
+        }
+        for edge in edges {
+            self.put_edge(edge.clone())?;
+        }
+        let (next_node, next_edge) = graph.next_ids();
+        self.next_node_id = self.next_node_id.max(next_node);
+        self.next_edge_id = self.next_edge_id.max(next_edge);
+        while self.tick < graph.current_tick() {
+            self.tick()?;
+        }
+        Ok(())
+    }
+}
+
+// --- Cold tier of a KnowledgeGraph ---
+
+// Records of a graph that live in a store. A slot is filled from the store
+// on first read through &self, so references to it stay valid until the
+// graph releases the slots (tick, eviction) under &mut.
+#[derive(Debug, Default)]
+pub(super) struct ColdTier {
+    // None once detached by a clone, which holds every record in its slots
+    store: Option<DiskGraph>,
+    nodes: FxHashMap<NodeId, OnceLock<Option<Box<Node>>>>,
+    edges: FxHashMap<EdgeId, OnceLock<Option<Box<Edge>>>>,
+    // Slots filled since the last release
+    faulted: Mutex<Vec<Key>>,
+}
+
+impl Clone for ColdTier {
+    fn clone(&self) -> Self {
+        Self {
+            store: None,
+            nodes: self.nodes.keys().map(|&id| (id, OnceLock::from(self.node(id).cloned().map(Box::new)))).collect(),
+            edges: self.edges.keys().map(|&id| (id, OnceLock::from(self.edge(id).cloned().map(Box::new)))).collect(),
+            faulted: Mutex::default(),
+        }
+    }
+}
+
+impl ColdTier {
+    fn fault(&self, key: Key) {
+        self.faulted.lock().unwrap_or_else(|e| e.into_inner()).push(key);
+    }
+
+    pub(super) fn node(&self, id: NodeId) -> Option<&Node> {
+        self.nodes.get(&id)?.get_or_init(|| {
+            self.fault(Key::Node(id));
+            self.store.as_ref()?.node(id).map(Box::new)
+        }).as_deref()
+    }
+
+    pub(super) fn edge(&self, id: EdgeId) -> Option<&Edge> {
+        self.edges.get(&id)?.get_or_init(|| {
+            self.fault(Key::Edge(id));
+            self.store.as_ref()?.edge(id).map(Box::new)
+        }).as_deref()
+    }
+
+    pub(super) fn nodes(&self) -> impl Iterator<Item = &Node> {
+        self.nodes.keys().filter_map(|&id| self.node(id))
+    }
+
+    pub(super) fn edges(&self) -> impl Iterator<Item = &Edge> {
+        self.edges.keys().filter_map(|&id| self.edge(id))
+    }
+
+    pub(super) fn node_ids(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.nodes.keys().copied()
+    }
+
+    // From the store's index, without reading the record
+    pub(super) fn label_of(&self, id: NodeId) -> Option<Sym> {
+        match &self.store {
+            Some(store) => store.label_of(id),
+            None => self.node(id).map(|n| n.label),
+        }
+    }
+
+    pub(super) fn contains_node(&self, id: NodeId) -> bool {
+        self.nodes.contains_key(&id)
+    }
+
+    pub(super) fn contains_edge(&self, id: EdgeId) -> bool {
+        self.edges.contains_key(&id)
+    }
+
+    pub(super) fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub(super) fn edge_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub(super) fn can_spill(&self) -> bool {
+        self.store.is_some()
+    }
+
+    pub(super) fn spill_node(&mut self, node: &Node) -> io::Result<()> {
+        let store = self.store.as_mut().ok_or_else(|| invalid("no store attached"))?;
+        store.put_node(node.clone())?;
+        self.nodes.insert(node.id, OnceLock::new());
+        Ok(())
+    }
+
+    pub(super) fn spill_edge(&mut self, edge: &Edge) -> io::Result<()> {
+        let store = self.store.as_mut().ok_or_else(|| invalid("no store attached"))?;
+        store.put_edge(edge.clone())?;
+        self.edges.insert(edge.id, OnceLock::new());
+        Ok(())
+    }
+
+    // Removes the record from the tier (tombstoning it in the store) and
+    // returns it; None if it cannot be read or the tombstone not written
+    pub(super) fn take_node(&mut self, id: NodeId) -> Option<Node> {
+        let node = match self.nodes.get_mut(&id)?.take() {
+            Some(Some(node)) => *node,
+            _ => self.store.as_ref()?.node(id)?,
+        };
+        if let Some(store) = &mut self.store {
+            store.drop_record(Key::Node(id)).ok()?;
+        }
+        self.nodes.remove(&id);
+        Some(node)
+    }
+
+    pub(super) fn take_edge(&mut self, id: EdgeId) -> Option<Edge> {
+        let edge = match self.edges.get_mut(&id)?.take() {
+            Some(Some(edge)) => *edge,
+            _ => self.store.as_ref()?.edge(id)?,
+        };
+        if let Some(store) = &mut self.store {
+            store.drop_record(Key::Edge(id)).ok()?;
+        }
+        self.edges.remove(&id);
+        Some(edge)
+    }
+
+    // Empties the slots filled by reads; the records stay in the store and
+    // its cache
+    pub(super) fn release(&mut self) {
+        if self.store.is_none() {
+            return;
+        }
+        for key in self.faulted.get_mut().unwrap_or_else(|e| e.into_inner()).drain(..) {
+            match key {
+                Key::Node(id) => drop(self.nodes.get_mut(&id).and_then(OnceLock::take)),
+                Key::Edge(id) => drop(self.edges.get_mut(&id).and_then(OnceLock::take)),
+            }
+        }
+    }
+}
+
+impl KnowledgeGraph {
+    // Graph over the store in `dir` (created if missing), with every record
+    // cold. The indexes are rebuilt by reading each record once.
+    pub fn open_store<P: AsRef<Path>>(dir: P, config: StoreConfig) -> io::Result<Self> {
+        let store = DiskGraph::open(dir, config)?;
+        let mut graph = KnowledgeGraph::new();
+        let mut cold = ColdTier::default();
+        let mut nodes: Vec<NodeId> = store.nodes.keys().copied().collect();
+        let mut edges: Vec<EdgeId> = store.edges.keys().copied().collect();
+        nodes.sort_unstable();
+        edges.sort_unstable();
+        for id in nodes {
+            graph.index_node(&store.node(id).ok_or_else(|| invalid("unreadable node"))?);
+            cold.nodes.insert(id, OnceLock::new());
+        }
+        for id in edges {
+            graph.index_edge(&store.edge(id).ok_or_else(|| invalid("unreadable edge"))?);
+            cold.edges.insert(id, OnceLock::new());
+        }
+        graph.set_tick(store.current_tick());
+        cold.store = Some(store);
+        graph.cold = Some(cold);
+        Ok(graph)
+    }
+
+    // Spills every record in memory and flushes the store, which then holds
+    // the whole graph; false if the graph has no store
+    pub fn sync_store(&mut self) -> io::Result<bool> {
+        if !self.cold.as_ref().is_some_and(ColdTier::can_spill) {
+            return Ok(false);
+        }
+        let (nodes, edges) = self.hot_ids();
+        for id in nodes {
+            self.spill_node(id)?;
+        }
+        for id in edges {
+            self.spill_edge(id)?;
+        }
+        let tick = self.current_tick();
+        let Some(store) = self.cold.as_mut().and_then(|c| c.store.as_mut()) else {
+            return Ok(false);
+        };
+        if store.current_tick() != tick {
+            store.set_tick(tick)?;
+        }
+        store.flush()?;
+        Ok(true)
+    }
+
+    pub fn store(&self) -> Option<&DiskGraph> {
+        self.cold.as_ref()?.store.as_ref()
+    }
+
+    pub fn compact_store(&mut self) -> io::Result<()> {
+        match self.cold.as_mut().and_then(|c| c.store.as_mut()) {
+            Some(store) => store.compact(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::eviction::{Capacity, EvictionPolicy};
+
+    const PERSON: Sym = 1;
+    const KNOWS: Sym = 2;
+    const AGE: Sym = 3;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("koloss-store-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn persists_and_reopens() {
+        let dir = scratch("reopen");
+        let config = StoreConfig::default().with_segment_bytes(256).with_cache_records(2);
+        let mut store = DiskGraph::open(&dir, config).unwrap();
+        let people: Vec<NodeId> = (0..20).map(|i| store.add_node_with_attrs(PERSON, vec![(AGE, Term::Int(i))]).unwrap()).collect();
+        for pair in people.windows(2) {
+            store.add_edge(pair[0], KNOWS, pair[1]).unwrap();
+        }
+        store.set_node_attr(people[3], AGE, &Term::Int(99)).unwrap();
+        store.remove_node(people[10]).unwrap();
+        store.tick().unwrap();
+
+        // Misses go to disk, through several segments
+        assert_eq!(store.node(people[0]).unwrap().attributes[0].1, TermSer::Int(0));
+        assert_eq!(store.outgoing_edges(people[9]).len(), 0);
+        assert!(fs::read_dir(&dir).unwrap().count() > 2);
+        assert!(store.cache_stats().1 > 0);
+        drop(store);
+
+        let mut store = DiskGraph::open(&dir, config).unwrap();
+        assert_eq!((store.node_count(), store.edge_count(), store.current_tick()), (19, 17, 1));
+        assert_eq!(store.node(people[3]).unwrap().attributes, vec![(AGE, TermSer::Int(99))]);
+        assert_eq!(store.neighbors(people[5]), vec![people[4], people[6]]);
+        assert_eq!(store.query_triple(Some(PERSON), Some(KNOWS), None).len(), 17);
+        assert!(store.add_node(PERSON).unwrap() > people[19]);
+        assert_eq!(store.to_graph().unwrap().edge_count(), 17);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn compaction_drops_garbage_and_torn_tails() {
+        let dir = scratch("compact");
+        let mut store = DiskGraph::open(&dir, StoreConfig::default()).unwrap();
+        let a = store.add_node(PERSON).unwrap();
+        for i in 0..50 {
+            store.set_node_attr(a, AGE, &Term::Int(i)).unwrap();
+        }
+        assert!(store.garbage_ratio() > 0.9);
+        store.compact().unwrap();
+        assert_eq!(store.garbage_ratio(), 0.0);
+        assert_eq!(store.node(a).unwrap().attributes[0].1, TermSer::Int(49));
+        store.add_edge(a, KNOWS, a).unwrap();
+        store.flush().unwrap();
+        drop(store);
+
+        // A half-written record at the end is cut off on open
+        let last = fs::read_dir(&dir).unwrap().filter_map(|e| Some(e.ok()?.path())).max().unwrap();
+        OpenOptions::new().append(true).open(&last).unwrap().write_all(&[REC_NODE, 40, 0]).unwrap();
+        let store = DiskGraph::open(&dir, StoreConfig::default()).unwrap();
+        assert_eq!((store.node_count(), store.edge_count()), (1, 1));
+        assert_eq!(store.outgoing_edges(a)[0].target, a);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn compaction_streams_across_segments() {
+        let dir = scratch("stream");
+        let config = StoreConfig::default().with_segment_bytes(256).with_cache_records(1);
+        let mut store = DiskGraph::open(&dir, config).unwrap();
+        let people: Vec<NodeId> = (0..40).map(|_| store.add_node(PERSON).unwrap()).collect();
+        for pair in people.windows(2) {
+            store.add_edge(pair[0], KNOWS, pair[1]).unwrap();
+        }
+        for (i, &p) in people.iter().enumerate().step_by(2) {
+            store.set_node_attr(p, AGE, &Term::Int(i as i64)).unwrap();
+        }
+        store.remove_node(people[39]).unwrap();
+        let before = store.to_graph().unwrap();
+
+        store.compact().unwrap();
+        assert_eq!(store.garbage_ratio(), 0.0);
+        let after = store.to_graph().unwrap();
+        assert_eq!((after.node_count(), after.edge_count()), (before.node_count(), before.edge_count()));
+        assert_eq!(store.node(people[10]).unwrap().attributes[0].1, TermSer::Int(10));
+        assert_eq!(store.outgoing_edges(people[0])[0].target, people[1]);
+        drop(store);
+
+        let store = DiskGraph::open(&dir, config).unwrap();
+        assert_eq!((store.node_count(), store.edge_count()), (39, 38));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn graph_spills_to_the_store_and_reads_through() {
+        let dir = scratch("tier");
+        let config = StoreConfig::default().with_segment_bytes(512).with_cache_records(4);
+        let capacity = Capacity::new(10, EvictionPolicy::LeastRecentlyUsed).with_low_water(5);
+        let mut g = KnowledgeGraph::open_store(&dir, config).unwrap().with_capacity(capacity);
+        let mut people = Vec::new();
+        for i in 0..30 {
+            let id = g.add_node_with_attrs(PERSON, vec![(AGE, Term::Int(i))]);
+            if let Some(&last) = people.last() {
+                g.add_edge(last, KNOWS, id);
+            }
+            people.push(id);
+            g.tick();
+        }
+
+        // Evicted nodes went to disk with their edges and read through
+        assert_eq!((g.node_count(), g.edge_count()), (30, 29));
+        assert!(g.hot_node_count() <= 10 && g.store().unwrap().edge_count() > 0);
+        let (first, fifth) = (g.node(people[0]).unwrap(), g.node(people[5]).unwrap());
+        assert_eq!((&first.attributes[0].1, fifth.label), (&TermSer::Int(0), PERSON));
+        assert_eq!(g.outgoing_edges(people[1])[0].target, people[2]);
+        assert_eq!(g.find_path(people[0], people[29], 30).map(|p| p.len()), Some(29));
+        assert_eq!(g.query_triple(Some(PERSON), Some(KNOWS), None).len(), 29);
+
+        // Changes promote cold records, removals cascade through the store
+        assert!(g.set_node_attr(people[0], AGE, &Term::Int(99)));
+        assert!(g.remove_node(people[1]));
+        assert_eq!((g.node_count(), g.edge_count()), (29, 27));
+        assert!(g.sync_store().unwrap());
+        assert_eq!(g.hot_node_count(), 0);
+        drop(g);
+
+        let g = KnowledgeGraph::open_store(&dir, config).unwrap();
+        assert_eq!((g.node_count(), g.edge_count(), g.current_tick()), (29, 27, 30));
+        assert_eq!(g.node(people[0]).unwrap().attributes, vec![(AGE, TermSer::Int(99))]);
+        assert_eq!(g.neighbors(people[5]), vec![people[6], people[4]]);
+        let copy = g.clone();
+        drop(g);
+        let _ = fs::remove_dir_all(&dir);
+        assert!(copy.store().is_none() && copy.node(people[29]).is_some());
+    }
+}
@@ -0,0 +1,271 @@
+// Description-logic style taxonomy over the knowledge graph.
+//
+// Classes and individuals are ordinary nodes; three relations (chosen by the
+// caller) carry the terminology:
+//   C subclass_of D      every instance of C is an instance of D
+//   x instance_of C      asserted membership
+//   C disjoint_with D    no individual may belong to both
+// Classification computes the subsumption closure, the inferred memberships
+// and any disjointness clashes; materialize() writes the inferred memberships
+// back as edges, and to_facts() exposes everything to the RuleEngine.
+
+use crate::core::{Term, Sym};
+use crate::reasoning::rules::RuleEngine;
+use super::graph::{KnowledgeGraph, NodeId};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Taxonomy {
+    pub subclass_of: Sym,
+    pub instance_of: Sym,
+    pub disjoint_with: Sym,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Clash {
+    // An individual belongs to two disjoint classes
+    Individual { individual: NodeId, a: NodeId, b: NodeId },
+    // A class is subsumed by two disjoint classes, so it can have no instances
+    UnsatisfiableClass { class: NodeId, a: NodeId, b: NodeId },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Classification {
+    // Every class with its strict superclasses
+    pub ancestors: FxHashMap<NodeId, Vec<NodeId>>,
+    // (individual, class) memberships implied but not asserted
+    pub inferred: Vec<(NodeId, NodeId)>,
+    pub clashes: Vec<Clash>,
+}
+
+impl Classification {
+    pub fn is_consistent(&self) -> bool {
+        self.clashes.is_empty()
+    }
+}
+
+impl Taxonomy {
+    pub fn new(subclass_of: Sym, instance_of: Sym, disjoint_with: Sym) -> Self {
+        Self { subclass_of, instance_of, disjoint_with }
+    }
+
+    fn targets(&self, graph: &KnowledgeGraph, node: NodeId, relation: Sym) -> Vec<NodeId> {
+        graph.outgoing_edges(node).iter()
+            .filter(|e| e.relation == relation)
+            .map(|e| e.target)
+            .collect()
+    }
+
+    // Strict superclasses of `class`, nearest first
+    pub fn superclasses(&self, graph: &KnowledgeGraph, class: NodeId) -> Vec<NodeId> {
+        let mut out = Vec::new();
+        let mut seen = FxHashSet::default();
+        seen.insert(class);
+        let mut queue = std::collections::VecDeque::from([class]);
+        while let Some(c) = queue.pop_front() {
+            for sup in self.targets(graph, c, self.subclass_of) {
+                if seen.insert(sup) {
+                    out.push(sup);
+                    queue.push_back(sup);
+                }
+            }
+        }
+        out
+    }
+
+    pub fn subsumes(&self, graph: &KnowledgeGraph, sup: NodeId, sub: NodeId) -> bool {
+        sup == sub || self.superclasses(graph, sub).contains(&sup)
+    }
+
+    // All classes of an individual, asserted and inferred
+    pub fn types_of(&self, graph: &KnowledgeGraph, individual: NodeId) -> Vec<NodeId> {
+        let mut out = Vec::new();
+        for c in self.targets(graph, individual, self.instance_of) {
+            if !out.contains(&c) {
+                out.push(c);
+            }
+            for sup in self.superclasses(graph, c) {
+                if !out.contains(&sup) {
+                    out.push(sup);
+                }
+            }
+        }
+        out
+    }
+
+    pub fn classify(&self, graph: &KnowledgeGraph) -> Classification {
+        let mut result = Classification::default();
+
+        let mut classes: FxHashSet<NodeId> = FxHashSet::default();
+        let mut individuals: FxHashSet<NodeId> = FxHashSet::default();
+        for id in graph.edges_by_relation(self.subclass_of) {
+            if let Some(e) = graph.edge(id) {
+                classes.insert(e.source);
+                classes.insert(e.target);
+            }
+        }
+        for id in graph.edges_by_relation(self.instance_of) {
+            if let Some(e) = graph.edge(id) {
+                individuals.insert(e.source);
+                classes.insert(e.target);
+            }
+        }
+
+        let mut disjoint: FxHashSet<(NodeId, NodeId)> = FxHashSet::default();
+        for id in graph.edges_by_relation(self.disjoint_with) {
+            if let Some(e) = graph.edge(id) {
+                disjoint.insert((e.source, e.target));
+                disjoint.insert((e.target, e.source));
+                classes.insert(e.source);
+                classes.insert(e.target);
+            }
+        }
+
+        let mut classes: Vec<NodeId> = classes.into_iter().collect();
+        classes.sort_unstable();
+        for &c in &classes {
+            result.ancestors.insert(c, self.superclasses(graph, c));
+        }
+
+        // Classes a node belongs to (itself included) that clash pairwise
+        let find_clash = |types: &[NodeId]| -> Option<(NodeId, NodeId)> {
+            for (i, &a) in types.iter().enumerate() {
+                for &b in &types[i + 1..] {
+                    if disjoint.contains(&(a, b)) {
+                        return Some((a, b));
+                    }
+                }
+            }
+            None
+        };
+
+        for &c in &classes {
+            let mut types = vec![c];
+            types.extend(result.ancestors[&c].iter().copied());
+            if let Some((a, b)) = find_clash(&types) {
+                result.clashes.push(Clash::UnsatisfiableClass { class: c, a, b });
+            }
+        }
+
+        let mut individuals: Vec<NodeId> = individuals.into_iter().collect();
+        individuals.sort_unstable();
+        for ind in individuals {
+            let asserted = self.targets(graph, ind, self.instance_of);
+            let types = self.types_of(graph, ind);
+            for &t in &types {
+                if !asserted.contains(&t) {
+                    result.inferred.push((ind, t));
+                }
+            }
+            if let Some((a, b)) = find_clash(&types) {
+                result.clashes.push(Clash::Individual { individual: ind, a, b });
+            }
+        }
+        result
+    }
+
+    // Add the inferred instance_of edges to the graph. Returns the
+    // classification that was applied.
+    pub fn materialize(&self, graph: &mut KnowledgeGraph) -> Classification {
+        let result = self.classify(graph);
+        for &(ind, class) in &result.inferred {
+            graph.add_edge(ind, self.instance_of, class);
+        }
+        result
+    }
+
+    // instance_of(Individual, Class) for every (asserted or inferred)
+    // membership and subclass_of(C, D) for the subsumption closure, over node labels
+    pub fn to_facts(&self, graph: &KnowledgeGraph) -> Vec<Term> {
+        let label = |id: NodeId| Term::atom(graph.node(id).map(|n| n.label).unwrap_or(0));
+        let result = self.classify(graph);
+        let mut facts = Vec::new();
+        for (&c, sups) in &result.ancestors {
+            for &d in sups {
+                facts.push(Term::compound(self.subclass_of, vec![label(c), label(d)]));
+            }
+        }
+        for id in graph.edges_by_relation(self.instance_of) {
+            if let Some(e) = graph.edge(id) {
+                facts.push(Term::compound(self.instance_of, vec![label(e.source), label(e.target)]));
+            }
+        }
+        for &(ind, class) in &result.inferred {
+            facts.push(Term::compound(self.instance_of, vec![label(ind), label(class)]));
+        }
+        let mut seen = FxHashSet::default();
+        facts.retain(|t| seen.insert(t.clone()));
+        facts
+    }
+
+    // Assert to_facts() into a rule engine. Returns the number of new facts.
+    pub fn load_into(&self, graph: &KnowledgeGraph, engine: &mut RuleEngine) -> usize {
+        let mut added = 0;
+        for fact in self.to_facts(graph) {
+            if !engine.has_fact(&fact) {
+                engine.add_fact(fact);
+                added += 1;
+            }
+        }
+        added
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SUB: Sym = 1;
+    const INST: Sym = 2;
+    const DISJ: Sym = 3;
+
+    #[test]
+    fn infers_memberships_through_hierarchy() {
+        let mut g = KnowledgeGraph::new();
+        let animal = g.add_node(10);
+        let bird = g.add_node(11);
+        let penguin = g.add_node(12);
+        let tweety = g.add_node(13);
+        g.add_edge(bird, SUB, animal);
+        g.add_edge(penguin, SUB, bird);
+        g.add_edge(tweety, INST, penguin);
+
+        let tax = Taxonomy::new(SUB, INST, DISJ);
+        assert!(tax.subsumes(&g, animal, penguin));
+        let result = tax.materialize(&mut g);
+        assert_eq!(result.inferred, vec![(tweety, bird), (tweety, animal)]);
+        assert!(result.is_consistent());
+        // Already materialized: nothing new to infer
+        assert!(tax.classify(&g).inferred.is_empty());
+    }
+
+    #[test]
+    fn detects_disjointness_clash() {
+        let mut g = KnowledgeGraph::new();
+        let plant = g.add_node(10);
+        let animal = g.add_node(11);
+        let dog = g.add_node(12);
+        let rex = g.add_node(13);
+        g.add_edge(plant, DISJ, animal);
+        g.add_edge(dog, SUB, animal);
+        g.add_edge(rex, INST, dog);
+        g.add_edge(rex, INST, plant);
+
+        let result = Taxonomy::new(SUB, INST, DISJ).classify(&g);
+        assert!(result.clashes.iter().any(|c| matches!(c, Clash::Individual { individual, .. } if *individual == rex)));
+    }
+
+    #[test]
+    fn exposes_facts_to_engine() {
+        let mut g = KnowledgeGraph::new();
+        let a = g.add_node(10);
+        let b = g.add_node(11);
+        let x = g.add_node(12);
+        g.add_edge(a, SUB, b);
+        g.add_edge(x, INST, a);
+        let mut engine = RuleEngine::new();
+        let tax = Taxonomy::new(SUB, INST, DISJ);
+        assert_eq!(tax.load_into(&g, &mut engine), 3);
+        assert!(engine.has_fact(&Term::compound(INST, vec![Term::atom(12), Term::atom(11)])));
+    }
+}
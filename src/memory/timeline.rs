@@ -0,0 +1,137 @@
+// Time travel: the graph as it was at an earlier tick.
+//
+// with_time_travel keeps every change that takes effect together with the
+// tick it happened at (the same stream the WAL and the change feed see:
+// committed transactions, undo and redo included). as_of(t) copies the
+// graph and reverts, newest first, the changes made after tick t, giving
+// an ordinary graph for any query API:
+//
+//   let graph = KnowledgeGraph::new().with_time_travel();
+//   ...
+//   let before = graph.as_of(checkpoint).expect("recorded since then");
+//   before.query_triple(Some(person), Some(works_at), None);
+//
+// A past state is the state at the end of its tick. Only ticks from the
+// moment recording started can be reconstructed; forget_before trims the
+// changes older queries would need.
+
+use super::graph::KnowledgeGraph;
+use super::journal::{Change, Journal};
+
+#[derive(Debug, Clone, Default)]
+pub struct Timeline {
+    // Earliest tick as_of can reconstruct
+    pub(super) start: u64,
+    pub(super) now: u64,
+    // (tick, change) in the order the changes took effect
+    pub(super) entries: Vec<(u64, Change)>,
+}
+
+impl KnowledgeGraph {
+    pub fn with_time_travel(mut self) -> Self {
+        self.set_time_travel(true);
+        self
+    }
+
+    // Turning it off drops the recorded changes
+    pub fn set_time_travel(&mut self, on: bool) {
+        let now = self.current_tick();
+        self.journal.set_timeline(on.then(|| Timeline { start: now, now, entries: Vec::new() }));
+    }
+
+    // Earliest tick as_of can answer for, None when not recording
+    pub fn earliest_tick(&self) -> Option<u64> {
+        self.journal.timeline().map(|t| t.start)
+    }
+
+    // The graph at the end of `tick`; None when not recording or when the
+    // tick precedes the recording. The copy keeps the capacity, schema and
+    // indexes configured here, without history, WAL or subscribers.
+    pub fn as_of(&self, tick: u64) -> Option<KnowledgeGraph> {
+        let timeline = self.journal.timeline()?;
+        if tick < timeline.start {
+            return None;
+        }
+        let mut past = self.clone();
+        past.journal = Journal::default();
+        past.similarity = None;
+        let after = timeline.entries.partition_point(|(t, _)| *t <= tick);
+        for (_, change) in timeline.entries[after..].iter().rev() {
+            match change {
+                Change::Node { id, before, .. } => past.put_node(*id, before.clone()),
+                Change::Edge { id, before, .. } => past.put_edge(*id, before.clone()),
+            }
+        }
+        past.set_tick(tick.min(self.current_tick()));
+        Some(past)
+    }
+
+    // Changes made after `tick`, oldest first
+    pub fn changes_since(&self, tick: u64) -> Vec<&Change> {
+        let Some(timeline) = self.journal.timeline() else {
+            return Vec::new();
+        };
+        let after = timeline.entries.partition_point(|(t, _)| *t <= tick);
+        timeline.entries[after..].iter().map(|(_, c)| c).collect()
+    }
+
+    // Drops what only ticks before `tick` need; as_of answers from `tick` on
+    pub fn forget_before(&mut self, tick: u64) {
+        if let Some(timeline) = self.journal.timeline_mut() {
+            let tick = tick.min(timeline.now);
+            if tick > timeline.start {
+                let keep = timeline.entries.partition_point(|(t, _)| *t <= tick);
+                timeline.entries.drain(..keep);
+                timeline.start = tick;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Term;
+
+    const PERSON: u32 = 1;
+    const WORKS_AT: u32 = 2;
+    const AGE: u32 = 3;
+
+    #[test]
+    fn reconstructs_earlier_ticks() {
+        let mut g = KnowledgeGraph::new().with_time_travel().with_history();
+        let ada = g.add_node(PERSON);
+        let acme = g.add_node(PERSON);
+        let job = g.add_edge(ada, WORKS_AT, acme);
+        g.set_node_attr(ada, AGE, &Term::Int(36));
+        g.tick();
+        g.remove_edge(job);
+        let initech = g.add_node(PERSON);
+        g.add_edge(ada, WORKS_AT, initech);
+        g.set_node_attr(ada, AGE, &Term::Int(37));
+        g.tick();
+        g.begin();
+        g.remove_node(initech);
+        g.rollback();
+        g.undo(1);
+
+        let then = g.as_of(0).unwrap();
+        assert_eq!(then.current_tick(), 0);
+        assert_eq!(then.outgoing_edges(ada).iter().map(|e| e.target).collect::<Vec<_>>(), vec![acme]);
+        assert_eq!(then.node(ada).unwrap().attributes[0].1.to_term(), Term::Int(36));
+        assert!(then.node(initech).is_none());
+
+        // The undo at tick 2 reverted the age change of tick 1
+        let now = g.as_of(2).unwrap();
+        assert_eq!(now.outgoing_edges(ada)[0].target, initech);
+        assert_eq!(now.node(ada).unwrap().attributes[0].1.to_term(), Term::Int(36));
+        assert_eq!(g.as_of(1).unwrap().node(ada).unwrap().attributes[0].1.to_term(), Term::Int(37));
+        assert_eq!(g.changes_since(1).len(), 1);
+
+        g.forget_before(1);
+        assert!(g.as_of(0).is_none());
+        assert_eq!(g.earliest_tick(), Some(1));
+        assert!(g.as_of(1).is_some());
+        assert!(KnowledgeGraph::new().as_of(0).is_none());
+    }
+}
@@ -0,0 +1,85 @@
+// Time-scoped queries over edge validity intervals.
+//
+// An edge holds during [valid_from, valid_to) in graph ticks, unbounded on
+// a side that is None. expire_edge closes the interval at the current tick
+// instead of deleting the edge, so the graph keeps its history; apply_decay
+// then ages the expired edge from that tick and reads no longer boost it,
+// so prune_weak removes it eventually.
+
+use crate::core::Sym;
+use super::graph::{Edge, EdgeId, KnowledgeGraph, NodeId};
+
+impl KnowledgeGraph {
+    pub fn outgoing_edges_at(&self, node: NodeId, tick: u64) -> Vec<&Edge> {
+        self.outgoing_edges(node).into_iter().filter(|e| e.is_valid_at(tick)).collect()
+    }
+
+    pub fn incoming_edges_at(&self, node: NodeId, tick: u64) -> Vec<&Edge> {
+        self.incoming_edges(node).into_iter().filter(|e| e.is_valid_at(tick)).collect()
+    }
+
+    // query_triple restricted to edges valid at the tick
+    pub fn query_triple_at(&self, source_label: Option<Sym>, relation: Option<Sym>, target_label: Option<Sym>, tick: u64) -> Vec<(NodeId, EdgeId, NodeId)> {
+        self.query_triple_valid(source_label, relation, target_label, |e| e.is_valid_at(tick))
+    }
+
+    // query_triple restricted to edges valid at some tick of [from, to)
+    pub fn query_triple_during(&self, source_label: Option<Sym>, relation: Option<Sym>, target_label: Option<Sym>, from: u64, to: u64) -> Vec<(NodeId, EdgeId, NodeId)> {
+        self.query_triple_valid(source_label, relation, target_label, |e| e.overlaps(from, to))
+    }
+
+    fn query_triple_valid<F: Fn(&Edge) -> bool>(&self, source_label: Option<Sym>, relation: Option<Sym>, target_label: Option<Sym>, valid: F) -> Vec<(NodeId, EdgeId, NodeId)> {
+        let mut hits: Vec<(NodeId, EdgeId, NodeId)> = self.query_triple(source_label, relation, target_label)
+            .into_iter()
+            .filter(|(_, id, _)| self.edge(*id).is_some_and(&valid))
+            .collect();
+        hits.sort_unstable_by_key(|t| t.1);
+        hits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::graph::DecayConfig;
+
+    const PERSON: Sym = 1;
+    const WORKS_AT: Sym = 2;
+
+    #[test]
+    fn answers_by_tick_and_interval() {
+        let mut g = KnowledgeGraph::new();
+        let (alice, acme, initech) = (g.add_node(PERSON), g.add_node(0), g.add_node(0));
+        let old = g.add_edge(alice, WORKS_AT, acme);
+        let new = g.add_edge(alice, WORKS_AT, initech);
+        g.set_edge_validity(old, Some(0), Some(10));
+        g.set_edge_validity(new, Some(10), None);
+
+        let ids = |hits: Vec<(NodeId, EdgeId, NodeId)>| hits.iter().map(|t| t.1).collect::<Vec<_>>();
+        assert_eq!(ids(g.query_triple_at(Some(PERSON), Some(WORKS_AT), None, 5)), vec![old]);
+        assert_eq!(ids(g.query_triple_at(None, Some(WORKS_AT), None, 10)), vec![new]);
+        assert_eq!(ids(g.query_triple_during(None, None, None, 8, 12)), vec![old, new]);
+        assert!(g.query_triple_during(None, None, None, 12, 12).is_empty());
+        assert_eq!(g.outgoing_edges_at(alice, 3).len(), 1);
+        assert_eq!(g.incoming_edges_at(initech, 3).len(), 0);
+    }
+
+    #[test]
+    fn expired_edges_age_out() {
+        let config = DecayConfig { decay_rate: 0.1, prune_threshold: 0.5, ..DecayConfig::default() };
+        let mut g = KnowledgeGraph::new().with_decay(config);
+        let (a, b) = (g.add_node(PERSON), g.add_node(PERSON));
+        let live = g.add_edge(a, WORKS_AT, b);
+        let gone = g.add_edge(a, WORKS_AT, b);
+        g.expire_edge(gone);
+        for _ in 0..3 {
+            g.tick();
+            g.touch_edge(live);
+            g.touch_edge(gone);
+            g.apply_decay();
+        }
+        assert_eq!(g.edge(live).unwrap().weight, 1.0);
+        assert!(g.edge(gone).unwrap().weight < 0.5);
+        assert!(!g.edge(gone).unwrap().is_valid_at(g.current_tick()));
+    }
+}
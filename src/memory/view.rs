@@ -0,0 +1,314 @@
+// Read-only view over a binary graph snapshot (binary.rs).
+//
+// Opening a view only walks the section headers, so queries can start right
+// away on a large store. Records are decoded when they are read; the id,
+// label, relation and adjacency indexes are built on the first query that
+// needs them, nodes and edges separately:
+//
+//   let mapped = MappedGraph::open("mem.kols")?;     // feature "mmap"
+//   let view = mapped.view().expect("graph snapshot");
+//   let people = view.nodes_by_label(person);        // builds the node index
+//   let knows = view.outgoing_edges(people[0]);      // builds the edge index
+//
+// A view works over any byte slice, e.g. a snapshot already in memory. A
+// damaged record ends the scan of its section.
+
+use crate::core::{Sym, SymbolTable};
+use super::binary::{BinaryReader, SECTION_EDGES, SECTION_GRAPH, SECTION_NODES, SECTION_SYMBOLS};
+use super::graph::{Edge, EdgeId, KnowledgeGraph, Node, NodeId};
+use rustc_hash::FxHashMap;
+use std::ops::Range;
+use std::sync::OnceLock;
+
+#[derive(Debug, Default)]
+struct NodeIndex {
+    // Record offset and label of each node
+    nodes: FxHashMap<NodeId, (usize, Sym)>,
+    by_label: FxHashMap<Sym, Vec<NodeId>>,
+}
+
+#[derive(Debug, Default)]
+struct EdgeIndex {
+    edges: FxHashMap<EdgeId, usize>,
+    outgoing: FxHashMap<NodeId, Vec<EdgeId>>,
+    incoming: FxHashMap<NodeId, Vec<EdgeId>>,
+    by_relation: FxHashMap<Sym, Vec<EdgeId>>,
+}
+
+#[derive(Debug)]
+pub struct GraphView<'a> {
+    data: &'a [u8],
+    version: u8,
+    symbols: Option<Range<usize>>,
+    node_sections: Vec<Range<usize>>,
+    edge_sections: Vec<Range<usize>>,
+    node_count: usize,
+    edge_count: usize,
+    next_ids: (NodeId, EdgeId),
+    tick: u64,
+    node_index: OnceLock<NodeIndex>,
+    edge_index: OnceLock<EdgeIndex>,
+}
+
+impl<'a> GraphView<'a> {
+    // None when `data` is not a graph snapshot
+    pub fn new(data: &'a [u8]) -> Option<Self> {
+        let mut r = BinaryReader::new(data);
+        let version = r.read_header()?;
+        let sections = r.read_section_count()?;
+        let mut view = Self {
+            data,
+            version,
+            symbols: None,
+            node_sections: Vec::new(),
+            edge_sections: Vec::new(),
+            node_count: 0,
+            edge_count: 0,
+            next_ids: (1, 1),
+            tick: 0,
+            node_index: OnceLock::new(),
+            edge_index: OnceLock::new(),
+        };
+        for _ in 0..sections {
+            let (kind, mut s) = r.read_section()?;
+            let end = data.len() - r.remaining();
+            let range = end - s.remaining()..end;
+            match kind {
+                SECTION_SYMBOLS => view.symbols = Some(range),
+                SECTION_GRAPH => {
+                    view.next_ids = (s.read_u32()?, s.read_u32()?);
+                    view.tick = s.read_u64()?;
+                }
+                SECTION_NODES => {
+                    view.node_count += s.read_u32()? as usize;
+                    view.node_sections.push(range);
+                }
+                SECTION_EDGES => {
+                    view.edge_count += s.read_u32()? as usize;
+                    view.edge_sections.push(range);
+                }
+                _ => {}
+            }
+        }
+        Some(view)
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.node_count
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.edge_count
+    }
+
+    pub fn next_ids(&self) -> (NodeId, EdgeId) {
+        self.next_ids
+    }
+
+    pub fn current_tick(&self) -> u64 {
+        self.tick
+    }
+
+    pub fn symbols(&self) -> Option<SymbolTable> {
+        let range = self.symbols.clone()?;
+        let mut table = SymbolTable::new();
+        for name in BinaryReader::new(&self.data[range]).read_symbol_table()? {
+            table.intern(&name);
+        }
+        Some(table)
+    }
+
+    // Every record of the given sections with its offset, in snapshot order
+    fn records<'s, T: 's>(&'s self, sections: &'s [Range<usize>], read: fn(&mut BinaryReader<'s>) -> Option<T>) -> impl Iterator<Item = (usize, T)> + 's {
+        let (data, version) = (self.data, self.version);
+        sections.iter().flat_map(move |range| {
+            let mut r = BinaryReader::new(&data[range.clone()]).with_version(version);
+            let end = range.end;
+            let count = r.read_u32().unwrap_or(0);
+            (0..count).map_while(move |_| {
+                let at = end - r.remaining();
+                read(&mut r).map(|record| (at, record))
+            })
+        })
+    }
+
+    pub fn nodes(&self) -> impl Iterator<Item = Node> + '_ {
+        self.records(&self.node_sections, BinaryReader::read_node).map(|(_, n)| n)
+    }
+
+    pub fn edges(&self) -> impl Iterator<Item = Edge> + '_ {
+        self.records(&self.edge_sections, BinaryReader::read_edge).map(|(_, e)| e)
+    }
+
+    fn node_index(&self) -> &NodeIndex {
+        self.node_index.get_or_init(|| {
+            let mut index = NodeIndex::default();
+            for (at, node) in self.records(&self.node_sections, BinaryReader::read_node) {
+                index.nodes.insert(node.id, (at, node.label));
+                index.by_label.entry(node.label).or_default().push(node.id);
+            }
+            index
+        })
+    }
+
+    fn edge_index(&self) -> &EdgeIndex {
+        self.edge_index.get_or_init(|| {
+            let mut index = EdgeIndex::default();
+            for (at, edge) in self.records(&self.edge_sections, BinaryReader::read_edge) {
+                index.edges.insert(edge.id, at);
+                index.outgoing.entry(edge.source).or_default().push(edge.id);
+                index.incoming.entry(edge.target).or_default().push(edge.id);
+                index.by_relation.entry(edge.relation).or_default().push(edge.id);
+            }
+            index
+        })
+    }
+
+    pub fn node(&self, id: NodeId) -> Option<Node> {
+        let &(at, _) = self.node_index().nodes.get(&id)?;
+        BinaryReader::new(&self.data[at..]).with_version(self.version).read_node()
+    }
+
+    pub fn edge(&self, id: EdgeId) -> Option<Edge> {
+        let &at = self.edge_index().edges.get(&id)?;
+        BinaryReader::new(&self.data[at..]).with_version(self.version).read_edge()
+    }
+
+    pub fn label_of(&self, id: NodeId) -> Option<Sym> {
+        self.node_index().nodes.get(&id).map(|&(_, label)| label)
+    }
+
+    pub fn nodes_by_label(&self, label: Sym) -> Vec<NodeId> {
+        self.node_index().by_label.get(&label).cloned().unwrap_or_default()
+    }
+
+    pub fn edges_by_relation(&self, relation: Sym) -> Vec<EdgeId> {
+        self.edge_index().by_relation.get(&relation).cloned().unwrap_or_default()
+    }
+
+    pub fn outgoing_edges(&self, node: NodeId) -> Vec<Edge> {
+        self.edge_index().outgoing.get(&node)
+            .map(|ids| ids.iter().filter_map(|&id| self.edge(id)).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn incoming_edges(&self, node: NodeId) -> Vec<Edge> {
+        self.edge_index().incoming.get(&node)
+            .map(|ids| ids.iter().filter_map(|&id| self.edge(id)).collect())
+            .unwrap_or_default()
+    }
+
+    pub fn neighbors(&self, node: NodeId) -> Vec<NodeId> {
+        let mut result = Vec::new();
+        for edge in self.outgoing_edges(node) {
+            if !result.contains(&edge.target) {
+                result.push(edge.target);
+            }
+        }
+        for edge in self.incoming_edges(node) {
+            if !result.contains(&edge.source) {
+                result.push(edge.source);
+            }
+        }
+        result
+    }
+
+    pub fn query_triple(&self, source_label: Option<Sym>, relation: Option<Sym>, target_label: Option<Sym>) -> Vec<(NodeId, EdgeId, NodeId)> {
+        let edges: Vec<Edge> = match relation {
+            Some(r) => self.edges_by_relation(r).into_iter().filter_map(|id| self.edge(id)).collect(),
+            None => self.edges().collect(),
+        };
+        edges.into_iter()
+            .filter(|e| source_label.is_none_or(|l| self.label_of(e.source) == Some(l)))
+            .filter(|e| target_label.is_none_or(|l| self.label_of(e.target) == Some(l)))
+            .map(|e| (e.source, e.id, e.target))
+            .collect()
+    }
+
+    // Decode everything into a mutable graph
+    pub fn to_graph(&self) -> Option<KnowledgeGraph> {
+        KnowledgeGraph::from_binary(self.data).map(|(g, _)| g)
+    }
+}
+
+// A snapshot file mapped into memory
+#[cfg(feature = "mmap")]
+pub struct MappedGraph {
+    map: memmap2::Mmap,
+}
+
+#[cfg(feature = "mmap")]
+impl MappedGraph {
+    pub fn open<P: AsRef<std::path::Path>>(path: P) -> std::io::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        // The file must not be modified while mapped; views only read it
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        Ok(Self { map })
+    }
+
+    pub fn view(&self) -> Option<GraphView<'_>> {
+        GraphView::new(&self.map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Term;
+
+    const PERSON: Sym = 1;
+    const CITY: Sym = 2;
+    const KNOWS: Sym = 3;
+    const LIVES_IN: Sym = 4;
+    const NAME: Sym = 5;
+
+    fn town() -> KnowledgeGraph {
+        let mut g = KnowledgeGraph::new();
+        let a = g.add_node_with_attrs(PERSON, vec![(NAME, Term::Str("ada".into()))]);
+        let b = g.add_node(PERSON);
+        let paris = g.add_node(CITY);
+        g.add_edge(a, KNOWS, b);
+        g.add_edge(a, LIVES_IN, paris);
+        g.add_edge(b, LIVES_IN, paris);
+        g.tick();
+        g
+    }
+
+    #[test]
+    fn answers_like_the_graph() {
+        let g = town();
+        let bytes = g.to_binary(None);
+        let view = GraphView::new(&bytes).unwrap();
+        assert_eq!((view.node_count(), view.edge_count(), view.current_tick()), (3, 3, 1));
+        assert!(view.node_index.get().is_none() && view.edge_index.get().is_none());
+
+        let mut people = view.nodes_by_label(PERSON);
+        people.sort_unstable();
+        assert_eq!(people, vec![1, 2]);
+        assert_eq!(view.node(1).as_ref(), g.node(1));
+        assert!(view.edge_index.get().is_none());
+
+        let mut expected = g.query_triple(Some(PERSON), Some(LIVES_IN), Some(CITY));
+        let mut got = view.query_triple(Some(PERSON), Some(LIVES_IN), Some(CITY));
+        expected.sort_unstable();
+        got.sort_unstable();
+        assert_eq!(got, expected);
+        assert_eq!(view.incoming_edges(3).len(), 2);
+        assert_eq!(view.neighbors(2).len(), 2);
+        assert_eq!(view.to_graph().unwrap().edge_count(), 3);
+        assert!(GraphView::new(&bytes[..7]).is_none());
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn maps_a_snapshot_file() {
+        let path = std::env::temp_dir().join(format!("koloss-view-{}.kols", std::process::id()));
+        std::fs::write(&path, town().to_binary(None)).unwrap();
+        let mapped = MappedGraph::open(&path).unwrap();
+        let view = mapped.view().unwrap();
+        assert_eq!(view.outgoing_edges(1).len(), 2);
+        assert_eq!(view.label_of(3), Some(CITY));
+        drop(mapped);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,317 @@
+// Random-walk node embeddings (node2vec-style).
+//
+// Walks start from every node and step to a neighbour chosen in proportion
+// to the edge weight, biased by node2vec's return (p) and in-out (q)
+// parameters: stepping back to the previous node is weighted 1/p, to a
+// neighbour of it 1, further away 1/q. Nodes that co-occur within `window`
+// steps are pulled together by skip-gram with negative sampling, so nodes
+// with similar surroundings end up with similar vectors.
+//
+// Training is incremental: refresh walks only from the nodes that have no
+// vector yet and their neighbours, continuing from the vectors learned so
+// far, and forgets removed nodes.
+//
+//   let mut emb = WalkEmbeddings::new(WalkConfig::default().with_dim(64));
+//   emb.train(&graph);
+//   ... graph grows ...
+//   emb.refresh(&graph);
+//   let near = emb.most_similar(node, 10);
+
+use super::graph::{KnowledgeGraph, NodeId};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WalkConfig {
+    pub dim: usize,
+    pub walks_per_node: usize,
+    pub walk_length: usize,
+    pub window: usize,
+    pub negatives: usize,
+    pub learning_rate: f64,
+    // Return and in-out parameters; 1 and 1 is a plain weighted walk
+    pub p: f64,
+    pub q: f64,
+    // Follow edge directions only, otherwise both ways
+    pub directed: bool,
+    pub seed: u64,
+}
+
+impl Default for WalkConfig {
+    fn default() -> Self {
+        Self {
+            dim: 32,
+            walks_per_node: 10,
+            walk_length: 20,
+            window: 4,
+            negatives: 4,
+            learning_rate: 0.025,
+            p: 1.0,
+            q: 1.0,
+            directed: false,
+            seed: 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+}
+
+impl WalkConfig {
+    pub fn with_dim(mut self, dim: usize) -> Self {
+        self.dim = dim.max(1);
+        self
+    }
+
+    pub fn with_walks(mut self, walks_per_node: usize, walk_length: usize) -> Self {
+        self.walks_per_node = walks_per_node;
+        self.walk_length = walk_length;
+        self
+    }
+
+    pub fn with_window(mut self, window: usize) -> Self {
+        self.window = window;
+        self
+    }
+
+    pub fn with_bias(mut self, p: f64, q: f64) -> Self {
+        self.p = p;
+        self.q = q;
+        self
+    }
+
+    pub fn with_directed(mut self, directed: bool) -> Self {
+        self.directed = directed;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WalkEmbeddings {
+    config: WalkConfig,
+    vectors: FxHashMap<NodeId, Vec<f64>>,
+    // Skip-gram output vectors
+    contexts: FxHashMap<NodeId, Vec<f64>>,
+    rng: u64,
+}
+
+fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x.clamp(-30.0, 30.0)).exp())
+}
+
+impl WalkEmbeddings {
+    pub fn new(config: WalkConfig) -> Self {
+        let rng = config.seed | 1;
+        Self { config, vectors: FxHashMap::default(), contexts: FxHashMap::default(), rng }
+    }
+
+    pub fn config(&self) -> &WalkConfig {
+        &self.config
+    }
+
+    pub fn len(&self) -> usize {
+        self.vectors.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vectors.is_empty()
+    }
+
+    pub fn vector(&self, id: NodeId) -> Option<&[f64]> {
+        self.vectors.get(&id).map(Vec::as_slice)
+    }
+
+    // xorshift64
+    fn next(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+
+    fn unit(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    // Walks from every node
+    pub fn train(&mut self, graph: &KnowledgeGraph) {
+        let mut starts: Vec<NodeId> = graph.nodes().map(|n| n.id).collect();
+        starts.sort_unstable();
+        self.train_from(graph, &starts);
+    }
+
+    // Walks from the nodes without a vector and their neighbours; drops
+    // vectors of removed nodes. Returns the number of new nodes.
+    pub fn refresh(&mut self, graph: &KnowledgeGraph) -> usize {
+        let live: FxHashSet<NodeId> = graph.nodes().map(|n| n.id).collect();
+        self.vectors.retain(|id, _| live.contains(id));
+        self.contexts.retain(|id, _| live.contains(id));
+        let mut fresh: Vec<NodeId> = live.into_iter().filter(|id| !self.vectors.contains_key(id)).collect();
+        fresh.sort_unstable();
+        let mut starts: FxHashSet<NodeId> = fresh.iter().copied().collect();
+        for &id in &fresh {
+            starts.extend(self.neighbours(graph, id).into_iter().map(|(n, _)| n));
+        }
+        let mut starts: Vec<NodeId> = starts.into_iter().collect();
+        starts.sort_unstable();
+        self.train_from(graph, &starts);
+        fresh.len()
+    }
+
+    fn neighbours(&self, graph: &KnowledgeGraph, id: NodeId) -> Vec<(NodeId, f64)> {
+        let mut out: Vec<(NodeId, f64)> = graph.outgoing_edges(id).iter().map(|e| (e.target, e.weight.max(0.0))).collect();
+        if !self.config.directed {
+            out.extend(graph.incoming_edges(id).iter().map(|e| (e.source, e.weight.max(0.0))));
+        }
+        out
+    }
+
+    fn walk(&mut self, graph: &KnowledgeGraph, start: NodeId) -> Vec<NodeId> {
+        let mut walk = vec![start];
+        let mut previous: Option<(NodeId, Vec<(NodeId, f64)>)> = None;
+        while walk.len() < self.config.walk_length {
+            let current = walk[walk.len() - 1];
+            let neighbours = self.neighbours(graph, current);
+            let mut options = neighbours.clone();
+            if let Some((prev, prev_neighbours)) = &previous {
+                for (node, w) in &mut options {
+                    *w *= if node == prev {
+                        1.0 / self.config.p
+                    } else if prev_neighbours.iter().any(|(n, _)| n == node) {
+                        1.0
+                    } else {
+                        1.0 / self.config.q
+                    };
+                }
+            }
+            let total: f64 = options.iter().map(|o| o.1).sum();
+            if total <= 0.0 {
+                break;
+            }
+            let mut pick = self.unit() * total;
+            let next = options.iter().find(|o| {
+                pick -= o.1;
+                pick < 0.0
+            }).unwrap_or(&options[options.len() - 1]).0;
+            previous = Some((current, neighbours));
+            walk.push(next);
+        }
+        walk
+    }
+
+    fn train_from(&mut self, graph: &KnowledgeGraph, starts: &[NodeId]) {
+        let mut all: Vec<NodeId> = graph.nodes().map(|n| n.id).collect();
+        all.sort_unstable();
+        if all.is_empty() {
+            return;
+        }
+        let dim = self.config.dim;
+        for &id in &all {
+            if !self.vectors.contains_key(&id) {
+                let v = (0..dim).map(|_| (self.unit() - 0.5) / dim as f64).collect();
+                self.vectors.insert(id, v);
+                self.contexts.insert(id, vec![0.0; dim]);
+            }
+        }
+        for _ in 0..self.config.walks_per_node {
+            for &start in starts {
+                let walk = self.walk(graph, start);
+                for (i, &center) in walk.iter().enumerate() {
+                    let lo = i.saturating_sub(self.config.window);
+                    let hi = (i + self.config.window + 1).min(walk.len());
+                    for (j, &context) in walk.iter().enumerate().take(hi).skip(lo) {
+                        if j != i && context != center {
+                            self.train_pair(center, context, &all);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // One skip-gram step: pull center towards context, push it away from
+    // random nodes
+    fn train_pair(&mut self, center: NodeId, context: NodeId, all: &[NodeId]) {
+        let lr = self.config.learning_rate;
+        let mut grad = vec![0.0; self.config.dim];
+        let input = self.vectors[&center].clone();
+        for k in 0..=self.config.negatives {
+            let (target, label) = if k == 0 {
+                (context, 1.0)
+            } else {
+                let n = all[self.next() as usize % all.len()];
+                if n == context || n == center {
+                    continue;
+                }
+                (n, 0.0)
+            };
+            let output = self.contexts.get_mut(&target).expect("vector for every node");
+            let dot: f64 = input.iter().zip(output.iter()).map(|(a, b)| a * b).sum();
+            let g = lr * (label - sigmoid(dot));
+            for d in 0..input.len() {
+                grad[d] += g * output[d];
+                output[d] += g * input[d];
+            }
+        }
+        for (v, g) in self.vectors.get_mut(&center).expect("vector for every node").iter_mut().zip(grad) {
+            *v += g;
+        }
+    }
+
+    // Nodes by cosine similarity of their vectors, best first
+    pub fn most_similar(&self, id: NodeId, top_k: usize) -> Vec<(NodeId, f64)> {
+        let Some(target) = self.vectors.get(&id) else {
+            return Vec::new();
+        };
+        let mut scores: Vec<(NodeId, f64)> = self.vectors.iter()
+            .filter(|(&other, _)| other != id)
+            .map(|(&other, v)| (other, KnowledgeGraph::similarity(target, v)))
+            .collect();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        scores.truncate(top_k);
+        scores
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Sym;
+
+    const ITEM: Sym = 1;
+    const LINK: Sym = 2;
+
+    fn clique(g: &mut KnowledgeGraph, n: usize) -> Vec<NodeId> {
+        let ids: Vec<NodeId> = (0..n).map(|_| g.add_node(ITEM)).collect();
+        for (i, &a) in ids.iter().enumerate() {
+            for &b in &ids[i + 1..] {
+                g.add_edge(a, LINK, b);
+            }
+        }
+        ids
+    }
+
+    #[test]
+    fn walks_separate_communities_and_grow_incrementally() {
+        let mut g = KnowledgeGraph::new();
+        let left = clique(&mut g, 5);
+        let right = clique(&mut g, 5);
+        g.add_edge(left[0], LINK, right[0]);
+        let mut emb = WalkEmbeddings::new(WalkConfig::default().with_dim(16).with_walks(20, 10));
+        emb.train(&g);
+        assert_eq!(emb.len(), 10);
+        let near: Vec<NodeId> = emb.most_similar(left[2], 3).into_iter().map(|(id, _)| id).collect();
+        assert!(near.iter().all(|id| left.contains(id)), "{:?}", near);
+
+        let newcomer = g.add_node(ITEM);
+        for &id in &right[1..4] {
+            g.add_edge(newcomer, LINK, id);
+        }
+        g.remove_node(left[4]);
+        assert_eq!(emb.refresh(&g), 1);
+        assert_eq!(emb.len(), 10);
+        assert!(emb.vector(left[4]).is_none());
+        assert!(right.contains(&emb.most_similar(newcomer, 1)[0].0));
+    }
+}
@@ -0,0 +1,201 @@
+// Working memory: a small buffer of recent facts in front of the graph.
+//
+// Items are terms with an activation that follows the graph's decay model
+// (DecayConfig): each tick an item decays along the configured curve by the
+// ticks since its last use, each use boosts it by access_boost, and items
+// below prune_threshold are forgotten. When the buffer is full the least active item makes room.
+//
+// consolidate promotes items used at least promote_after times into the
+// graph and drops them from the buffer. A fact rel(a, b) over atoms becomes
+// a `rel` edge between the first nodes labelled a and b (created when
+// missing), weighted by the item's activation; an edge already there is
+// touched instead. Other terms stay in working memory.
+//
+//   let mut wm = WorkingMemory::new(32).with_promote_after(3);
+//   wm.insert(Term::compound(likes, vec![Term::atom(ada), Term::atom(tea)]));
+//   ...
+//   wm.tick();
+//   wm.consolidate(&mut graph);
+
+use crate::core::{Sym, Term};
+use super::graph::{DecayConfig, EdgeId, KnowledgeGraph, NodeId};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkingItem {
+    pub term: Term,
+    pub activation: f64,
+    pub uses: u32,
+    pub last_use: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct WorkingMemory {
+    items: Vec<WorkingItem>,
+    capacity: usize,
+    promote_after: u32,
+    decay: DecayConfig,
+    tick: u64,
+}
+
+impl WorkingMemory {
+    pub fn new(capacity: usize) -> Self {
+        Self { items: Vec::new(), capacity: capacity.max(1), promote_after: 2, decay: DecayConfig::default(), tick: 0 }
+    }
+
+    pub fn with_decay(mut self, decay: DecayConfig) -> Self {
+        self.decay = decay;
+        self
+    }
+
+    pub fn with_promote_after(mut self, uses: u32) -> Self {
+        self.promote_after = uses;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn items(&self) -> &[WorkingItem] {
+        &self.items
+    }
+
+    pub fn get(&self, term: &Term) -> Option<&WorkingItem> {
+        self.items.iter().find(|i| &i.term == term)
+    }
+
+    // Adds the term, or uses it if already present. Returns the evicted
+    // term when the buffer was full.
+    pub fn insert(&mut self, term: Term) -> Option<Term> {
+        if self.touch(&term) {
+            return None;
+        }
+        let evicted = if self.items.len() >= self.capacity {
+            self.least_active().map(|i| self.items.swap_remove(i).term)
+        } else {
+            None
+        };
+        self.items.push(WorkingItem { term, activation: 1.0, uses: 1, last_use: self.tick });
+        evicted
+    }
+
+    // One use of an item already present
+    pub fn touch(&mut self, term: &Term) -> bool {
+        let (tick, boost) = (self.tick, self.decay.access_boost);
+        let Some(item) = self.items.iter_mut().find(|i| &i.term == term) else {
+            return false;
+        };
+        item.uses += 1;
+        item.last_use = tick;
+        item.activation = (item.activation + boost).min(1.0);
+        true
+    }
+
+    fn least_active(&self) -> Option<usize> {
+        (0..self.items.len()).min_by(|&a, &b| {
+            let (a, b) = (&self.items[a], &self.items[b]);
+            a.activation.total_cmp(&b.activation).then(a.last_use.cmp(&b.last_use))
+        })
+    }
+
+    // Decays every item and forgets the ones below the prune threshold;
+    // returns how many were forgotten
+    pub fn tick(&mut self) -> usize {
+        self.tick += 1;
+        let (tick, config) = (self.tick, &self.decay);
+        for item in &mut self.items {
+            let age = tick.saturating_sub(item.last_use) as f64;
+            item.activation = config.curve.apply(item.activation, config.decay_rate, age).max(config.min_weight);
+        }
+        let before = self.items.len();
+        self.items.retain(|i| i.activation >= config.prune_threshold);
+        before - self.items.len()
+    }
+
+    // Promotes the items used often enough, returns the edges created or
+    // touched
+    pub fn consolidate(&mut self, graph: &mut KnowledgeGraph) -> Vec<EdgeId> {
+        let mut edges = Vec::new();
+        let promote_after = self.promote_after;
+        self.items.retain(|item| {
+            let Some((relation, a, b)) = as_fact(&item.term).filter(|_| item.uses >= promote_after) else {
+                return true;
+            };
+            let (source, target) = (node_for(graph, a), node_for(graph, b));
+            let existing = graph.outgoing_edges(source).iter().find(|e| e.relation == relation && e.target == target).map(|e| e.id);
+            edges.push(match existing {
+                Some(id) => {
+                    graph.touch_edge(id);
+                    id
+                }
+                None => graph.add_edge_weighted(source, relation, target, item.activation.clamp(0.0, 1.0)),
+            });
+            false
+        });
+        edges
+    }
+}
+
+// rel(a, b) with atom arguments
+fn as_fact(term: &Term) -> Option<(Sym, Sym, Sym)> {
+    match term {
+        Term::Compound(rel, args) => match args.as_slice() {
+            [Term::Atom(a), Term::Atom(b)] => Some((*rel, *a, *b)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// First node with the label, created when there is none
+pub(super) fn node_for(graph: &mut KnowledgeGraph, label: Sym) -> NodeId {
+    match graph.nodes_by_label(label).first() {
+        Some(&id) => id,
+        None => graph.add_node(label),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIKES: Sym = 1;
+    const ADA: Sym = 2;
+    const TEA: Sym = 3;
+    const BOB: Sym = 4;
+
+    fn likes(a: Sym, b: Sym) -> Term {
+        Term::compound(LIKES, vec![Term::atom(a), Term::atom(b)])
+    }
+
+    #[test]
+    fn decays_evicts_and_consolidates() {
+        let decay = DecayConfig { decay_rate: 0.2, prune_threshold: 0.3, ..DecayConfig::default() };
+        let mut wm = WorkingMemory::new(2).with_decay(decay).with_promote_after(2);
+        wm.insert(likes(ADA, TEA));
+        wm.insert(likes(BOB, TEA));
+        wm.tick();
+        wm.insert(likes(ADA, TEA));
+        assert_eq!(wm.get(&likes(ADA, TEA)).map(|i| i.uses), Some(2));
+        assert_eq!(wm.insert(Term::atom(TEA)), Some(likes(BOB, TEA)));
+
+        let mut graph = KnowledgeGraph::new();
+        let edges = wm.consolidate(&mut graph);
+        assert_eq!(edges.len(), 1);
+        assert_eq!(graph.query_triple(Some(ADA), Some(LIKES), Some(TEA)).len(), 1);
+        assert_eq!(wm.items().iter().map(|i| &i.term).collect::<Vec<_>>(), vec![&Term::atom(TEA)]);
+
+        // Promoting the same fact again reinforces the edge
+        wm.insert(likes(ADA, TEA));
+        wm.insert(likes(ADA, TEA));
+        assert_eq!(wm.consolidate(&mut graph), edges);
+        assert_eq!(graph.edge(edges[0]).unwrap().access_count, 1);
+
+        assert_eq!(wm.tick() + wm.tick() + wm.tick(), 1);
+        assert!(wm.is_empty());
+    }
+}
@@ -20,58 +20,117 @@ pub fn load_arc_tasks(path: &str) -> anyhow::Result<Vec<ArcTask>> {
     Ok(tasks)
 }
 
-pub fn load_arc_task(path: &str) -> anyhow::Result<ArcTask> {
-    let content = std::fs::read_to_string(path)?;
-    let raw: serde_json::Value = serde_json::from_str(&content)?;
-
-    let mut train = Vec::new();
-    if let Some(train_arr) = raw.get("train").and_then(|v| v.as_array()) {
-        for ex in train_arr {
-            if let (Some(input), Some(output)) = (ex.get("input"), ex.get("output")) {
-                train.push(ArcExample {
-                    input: parse_grid(input),
-                    output: parse_grid(output),
-                });
-            }
-        }
+// Tasks in the official ARC-AGI JSON format: {"train": [{"input": grid,
+// "output": grid}, ...], "test": [...]}, one task per file named by its id.
+// Grids must be rectangular, 1x1 to 30x30, with colors 0-9. Test pairs may
+// omit their output (hidden evaluation sets); it is then an empty grid.
+//
+//   let tasks = load_arc_dir("data/arc-agi/data/training")?;
+//   for task in &tasks { let examples = task.train_pairs(); ... }
+
+pub const MAX_GRID_SIDE: usize = 30;
+pub const MAX_COLOR: u8 = 9;
+
+impl ArcTask {
+    pub fn train_pairs(&self) -> Vec<(Grid, Grid)> {
+        self.train.iter().map(|ex| (ex.input.clone(), ex.output.clone())).collect()
     }
 
-    let mut test = Vec::new();
-    if let Some(test_arr) = raw.get("test").and_then(|v| v.as_array()) {
-        for ex in test_arr {
-            if let (Some(input), Some(output)) = (ex.get("input"), ex.get("output")) {
-                test.push(ArcExample {
-                    input: parse_grid(input),
-                    output: parse_grid(output),
-                });
-            }
-        }
+    pub fn test_pairs(&self) -> Vec<(Grid, Grid)> {
+        self.test.iter().map(|ex| (ex.input.clone(), ex.output.clone())).collect()
     }
+}
 
+pub fn load_arc_task(path: &str) -> anyhow::Result<ArcTask> {
+    let content = std::fs::read_to_string(path)?;
     let id = std::path::Path::new(path)
         .file_stem()
         .and_then(|s| s.to_str())
-        .unwrap_or("unknown")
-        .to_string();
+        .unwrap_or("unknown");
+    parse_arc_task(id, &content).map_err(|e| anyhow::anyhow!("{}: {}", path, e))
+}
+
+pub fn parse_arc_task(id: &str, json: &str) -> anyhow::Result<ArcTask> {
+    let raw: serde_json::Value = serde_json::from_str(json)?;
+    let train = parse_examples(&raw, "train", true)?;
+    if train.is_empty() {
+        anyhow::bail!("task {} has no training pairs", id);
+    }
+    let test = parse_examples(&raw, "test", false)?;
+    Ok(ArcTask { id: id.to_string(), train, test })
+}
 
-    Ok(ArcTask { id, train, test })
+// Every *.json task in the directory, sorted by id
+pub fn load_arc_dir(dir: &str) -> anyhow::Result<Vec<ArcTask>> {
+    let mut paths: Vec<_> = std::fs::read_dir(dir)?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    paths.sort();
+    paths.iter().map(|p| load_arc_task(&p.to_string_lossy())).collect()
 }
 
-fn parse_grid(val: &serde_json::Value) -> Grid {
-    val.as_array()
-        .map(|rows| {
-            rows.iter().map(|row| {
-                row.as_array()
-                    .map(|cells| cells.iter().map(|c| c.as_u64().unwrap_or(0) as u8).collect())
-                    .unwrap_or_default()
-            }).collect()
-        })
-        .unwrap_or_default()
+fn parse_examples(raw: &serde_json::Value, key: &str, needs_output: bool) -> anyhow::Result<Vec<ArcExample>> {
+    let Some(arr) = raw.get(key) else {
+        anyhow::bail!("missing \"{}\"", key);
+    };
+    let arr = arr.as_array().ok_or_else(|| anyhow::anyhow!("\"{}\" is not an array", key))?;
+    arr.iter().enumerate().map(|(i, ex)| {
+        let at = |what: &str| format!("{}[{}].{}", key, i, what);
+        let input = ex.get("input").ok_or_else(|| anyhow::anyhow!("missing {}", at("input")))?;
+        let input = parse_grid(input).map_err(|e| anyhow::anyhow!("{}: {}", at("input"), e))?;
+        let output = match ex.get("output") {
+            Some(v) => parse_grid(v).map_err(|e| anyhow::anyhow!("{}: {}", at("output"), e))?,
+            None if needs_output => anyhow::bail!("missing {}", at("output")),
+            None => Grid::default(),
+        };
+        Ok(ArcExample { input, output })
+    }).collect()
+}
+
+fn parse_grid(val: &serde_json::Value) -> Result<Grid, String> {
+    let rows = val.as_array().ok_or("not an array of rows")?;
+    if rows.is_empty() || rows.len() > MAX_GRID_SIDE {
+        return Err(format!("{} rows, expected 1 to {}", rows.len(), MAX_GRID_SIDE));
+    }
+    let mut grid = Grid::default();
+    for (r, row) in rows.iter().enumerate() {
+        let cells = row.as_array().ok_or_else(|| format!("row {} is not an array", r))?;
+        if cells.is_empty() || cells.len() > MAX_GRID_SIDE {
+            return Err(format!("row {} has {} cells, expected 1 to {}", r, cells.len(), MAX_GRID_SIDE));
+        }
+        if r > 0 && cells.len() != grid_dimensions(&grid).1 {
+            return Err(format!("row {} has {} cells, row 0 has {}", r, cells.len(), grid_dimensions(&grid).1));
+        }
+        let row: Vec<u8> = cells.iter().enumerate().map(|(c, v)| match v.as_u64() {
+            Some(color) if color <= MAX_COLOR as u64 => Ok(color as u8),
+            _ => Err(format!("cell ({}, {}) is {}, expected a color 0-{}", r, c, v, MAX_COLOR)),
+        }).collect::<Result<_, _>>()?;
+        grid.push_row(&row);
+    }
+    Ok(grid)
+}
+
+// Predictions in the ARC Prize submission format: {"task_id": [{"attempt_1":
+// grid, "attempt_2": grid}, ...]}, one entry per test input, in order. A
+// missing second attempt repeats the first, an input with no candidate
+// gets [[0]].
+pub fn submission_json(predictions: &[(String, Vec<Vec<Grid>>)]) -> String {
+    let tasks: serde_json::Map<String, serde_json::Value> = predictions.iter().map(|(id, per_input)| {
+        let entries = per_input.iter().map(|attempts| {
+            let first = attempts.first().cloned().unwrap_or_else(|| Grid::new(1, 1, 0));
+            let second = attempts.get(1).cloned().unwrap_or_else(|| first.clone());
+            serde_json::json!({ "attempt_1": first, "attempt_2": second })
+        }).collect();
+        (id.clone(), serde_json::Value::Array(entries))
+    }).collect();
+    serde_json::Value::Object(tasks).to_string()
 }
 
 pub fn grid_to_string(grid: &Grid) -> String {
     grid.iter()
-        .map(|row: &Vec<u8>| row.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(" "))
+        .map(|row: &[u8]| row.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(" "))
         .collect::<Vec<_>>()
         .join("\n")
 }
@@ -93,3 +152,29 @@ pub fn unique_colors(grid: &Grid) -> Vec<u8> {
     colors.sort();
     colors
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_validates_tasks() {
+        let task = parse_arc_task("t1", r#"{"train": [{"input": [[1, 0], [0, 1]], "output": [[2]]}],
+            "test": [{"input": [[3, 3]]}]}"#).unwrap();
+        assert_eq!(task.id, "t1");
+        assert_eq!(task.train_pairs(), vec![(Grid::from(vec![vec![1, 0], vec![0, 1]]), Grid::from(vec![vec![2]]))]);
+        assert_eq!(task.test_pairs(), vec![(Grid::from(vec![vec![3, 3]]), Grid::default())]);
+
+        let bad = |json: &str| parse_arc_task("t", json).unwrap_err().to_string();
+        assert!(bad(r#"{"train": [{"input": [[1, 0], [0]], "output": [[1]]}], "test": []}"#).contains("row 1 has 1 cells"));
+        assert!(bad(r#"{"train": [{"input": [[12]], "output": [[1]]}], "test": []}"#).contains("train[0].input: cell (0, 0) is 12"));
+        assert!(bad(r#"{"train": [{"input": [[1]]}], "test": []}"#).contains("missing train[0].output"));
+        assert!(bad(r#"{"train": [], "test": []}"#).contains("no training pairs"));
+    }
+
+    #[test]
+    fn writes_two_attempts_per_test_input() {
+        let json = submission_json(&[("t1".into(), vec![vec![Grid::from(vec![vec![1]]), Grid::from(vec![vec![2]])], vec![Grid::from(vec![vec![3]])], vec![]])]);
+        assert_eq!(json, r#"{"t1":[{"attempt_1":[[1]],"attempt_2":[[2]]},{"attempt_1":[[3]],"attempt_2":[[3]]},{"attempt_1":[[0]],"attempt_2":[[0]]}]}"#);
+    }
+}
@@ -0,0 +1,427 @@
+// Answer-set programming (stable model semantics).
+//
+// A program is a set of rules `head :- pos..., not neg...` where the head is
+// either a single atom, a choice `{ a; b; ... }` or empty (integrity constraint).
+// Solving works in two phases:
+// 1. Grounding: instantiate rules over the atoms that can possibly be derived
+//    (negation ignored), evaluating builtin comparisons along the way.
+// 2. Guess & check: encode the Clark completion into CNF, enumerate its models
+//    with the SAT solver, and keep those that equal the least model of their
+//    Gelfond-Lifschitz reduct.
+
+use crate::core::{Term, Sym};
+use super::unifier::{Substitution, unify};
+use super::builtins::{BuiltinRegistry, BuiltinResult, eval_builtin};
+use super::backend::SatBackend;
+use super::cdcl::CdclSolver;
+use super::solver::{Clause, Literal};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AspHead {
+    Atom(Term),
+    Choice(Vec<Term>),
+    Constraint,
+}
+
+#[derive(Debug, Clone)]
+pub struct AspRule {
+    pub head: AspHead,
+    pub pos: Vec<Term>,
+    pub neg: Vec<Term>,
+}
+
+impl AspRule {
+    pub fn fact(head: Term) -> Self {
+        Self { head: AspHead::Atom(head), pos: Vec::new(), neg: Vec::new() }
+    }
+
+    pub fn normal(head: Term, pos: Vec<Term>, neg: Vec<Term>) -> Self {
+        Self { head: AspHead::Atom(head), pos, neg }
+    }
+
+    pub fn choice(elements: Vec<Term>, pos: Vec<Term>, neg: Vec<Term>) -> Self {
+        Self { head: AspHead::Choice(elements), pos, neg }
+    }
+
+    pub fn constraint(pos: Vec<Term>, neg: Vec<Term>) -> Self {
+        Self { head: AspHead::Constraint, pos, neg }
+    }
+
+    fn head_atoms(&self) -> Vec<&Term> {
+        match &self.head {
+            AspHead::Atom(t) => vec![t],
+            AspHead::Choice(ts) => ts.iter().collect(),
+            AspHead::Constraint => Vec::new(),
+        }
+    }
+}
+
+// A fully instantiated rule over atom indices
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct GroundRule {
+    head: GroundHead,
+    pos: Vec<usize>,
+    neg: Vec<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum GroundHead {
+    Atom(usize),
+    Choice(Vec<usize>),
+    Constraint,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GroundProgram {
+    atoms: Vec<Term>,
+    atom_index: FxHashMap<Term, usize>,
+    rules: Vec<GroundRule>,
+}
+
+impl GroundProgram {
+    pub fn atoms(&self) -> &[Term] {
+        &self.atoms
+    }
+
+    pub fn num_rules(&self) -> usize {
+        self.rules.len()
+    }
+
+    fn intern(&mut self, atom: Term) -> usize {
+        if let Some(&i) = self.atom_index.get(&atom) {
+            return i;
+        }
+        let i = self.atoms.len();
+        self.atom_index.insert(atom.clone(), i);
+        self.atoms.push(atom);
+        i
+    }
+}
+
+pub type AnswerSet = Vec<Term>;
+
+#[derive(Debug, Clone)]
+pub struct AspProgram {
+    rules: Vec<AspRule>,
+    builtins: BuiltinRegistry,
+}
+
+impl AspProgram {
+    pub fn new() -> Self {
+        Self { rules: Vec::new(), builtins: BuiltinRegistry::new() }
+    }
+
+    pub fn with_builtins(mut self, builtins: BuiltinRegistry) -> Self {
+        self.builtins = builtins;
+        self
+    }
+
+    pub fn add_rule(&mut self, rule: AspRule) {
+        self.rules.push(rule);
+    }
+
+    pub fn rules(&self) -> &[AspRule] {
+        &self.rules
+    }
+
+    fn is_builtin(&self, t: &Term) -> bool {
+        matches!(t, Term::Compound(f, _) if self.builtins.is_builtin(*f))
+    }
+
+    // --- Grounding ---
+
+    pub fn ground(&self) -> GroundProgram {
+        let mut possible: FxHashSet<Term> = FxHashSet::default();
+        let mut by_pred: FxHashMap<(Sym, usize), Vec<Term>> = FxHashMap::default();
+
+        // Fixpoint over possibly-true atoms (negative literals ignored)
+        loop {
+            let mut added = false;
+            for rule in &self.rules {
+                for sub in self.instances(rule, &by_pred) {
+                    for head in rule.head_atoms() {
+                        let atom = sub.apply(head);
+                        if atom.is_ground() && possible.insert(atom.clone()) {
+                            by_pred.entry(pred_key(&atom)).or_default().push(atom);
+                            added = true;
+                        }
+                    }
+                }
+            }
+            if !added {
+                break;
+            }
+        }
+
+        let mut program = GroundProgram::default();
+        let mut seen: FxHashSet<GroundRule> = FxHashSet::default();
+        for rule in &self.rules {
+            for sub in self.instances(rule, &by_pred) {
+                let pos: Vec<usize> = rule.pos.iter()
+                    .filter(|t| !self.is_builtin(t))
+                    .map(|t| program.intern(sub.apply(t)))
+                    .collect();
+                let mut neg = Vec::new();
+                let mut unsafe_rule = false;
+                for t in &rule.neg {
+                    let atom = sub.apply(t);
+                    if !atom.is_ground() {
+                        unsafe_rule = true;
+                        break;
+                    }
+                    // Atoms that can never be derived are trivially false
+                    if possible.contains(&atom) {
+                        neg.push(program.intern(atom));
+                    }
+                }
+                if unsafe_rule {
+                    continue;
+                }
+                let head = match &rule.head {
+                    AspHead::Atom(h) => GroundHead::Atom(program.intern(sub.apply(h))),
+                    AspHead::Choice(hs) => {
+                        GroundHead::Choice(hs.iter().map(|h| program.intern(sub.apply(h))).collect())
+                    }
+                    AspHead::Constraint => GroundHead::Constraint,
+                };
+                let ground = GroundRule { head, pos, neg };
+                if seen.insert(ground.clone()) {
+                    program.rules.push(ground);
+                }
+            }
+        }
+        program
+    }
+
+    // All substitutions satisfying the positive body against the possible atoms
+    fn instances(&self, rule: &AspRule, by_pred: &FxHashMap<(Sym, usize), Vec<Term>>) -> Vec<Substitution> {
+        let (builtin_lits, atom_lits): (Vec<&Term>, Vec<&Term>) = rule.pos.iter()
+            .partition(|t| self.is_builtin(t));
+
+        let mut subs = vec![Substitution::new()];
+        for lit in atom_lits {
+            let candidates = match by_pred.get(&pred_key(lit)) {
+                Some(c) => c,
+                None => return Vec::new(),
+            };
+            let mut next = Vec::new();
+            for sub in &subs {
+                for atom in candidates {
+                    if let Ok(s) = unify(lit, atom, sub) {
+                        next.push(s);
+                    }
+                }
+            }
+            subs = next;
+            if subs.is_empty() {
+                return subs;
+            }
+        }
+
+        for lit in builtin_lits {
+            let mut next = Vec::new();
+            for sub in &subs {
+                if let Term::Compound(f, args) = lit {
+                    match eval_builtin(*f, args, sub, &self.builtins) {
+                        Some(BuiltinResult::Success(s)) => next.push(s),
+                        Some(BuiltinResult::Multi(ss)) => next.extend(ss),
+                        _ => {}
+                    }
+                }
+            }
+            subs = next;
+        }
+        subs
+    }
+
+    // --- Solving ---
+
+    pub fn solve(&self, max_models: usize) -> Vec<AnswerSet> {
+        self.solve_with(&mut CdclSolver::new(0), max_models)
+    }
+
+    // solve() on the given backend, which must not hold any clauses yet
+    pub fn solve_with<B: SatBackend>(&self, backend: &mut B, max_models: usize) -> Vec<AnswerSet> {
+        let ground = self.ground();
+        stable_models(&ground, max_models, backend)
+            .into_iter()
+            .map(|model| model.into_iter().map(|i| ground.atoms[i].clone()).collect())
+            .collect()
+    }
+}
+
+impl Default for AspProgram {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn pred_key(t: &Term) -> (Sym, usize) {
+    match t {
+        Term::Compound(f, args) => (*f, args.len()),
+        Term::Atom(a) => (*a, 0),
+        _ => (Sym::MAX, 0),
+    }
+}
+
+// Enumerate stable models as sorted lists of atom indices
+fn stable_models<B: SatBackend>(program: &GroundProgram, max_models: usize, backend: &mut B) -> Vec<Vec<usize>> {
+    let n_atoms = program.atoms.len();
+    let atom_var = |i: usize| (i + 1) as Literal;
+    let body_var = |r: usize| (n_atoms + r + 1) as Literal;
+
+    let mut clauses: Vec<Clause> = Vec::new();
+    let mut supports: Vec<Vec<Literal>> = vec![Vec::new(); n_atoms];
+
+    for (r, rule) in program.rules.iter().enumerate() {
+        let b = body_var(r);
+        // b <-> pos /\ not neg
+        let mut big = vec![b];
+        for &p in &rule.pos {
+            clauses.push(vec![-b, atom_var(p)]);
+            big.push(-atom_var(p));
+        }
+        for &q in &rule.neg {
+            clauses.push(vec![-b, -atom_var(q)]);
+            big.push(atom_var(q));
+        }
+        clauses.push(big);
+
+        match &rule.head {
+            GroundHead::Atom(h) => {
+                clauses.push(vec![-b, atom_var(*h)]);
+                supports[*h].push(b);
+            }
+            GroundHead::Choice(hs) => {
+                for &h in hs {
+                    supports[h].push(b);
+                }
+            }
+            GroundHead::Constraint => clauses.push(vec![-b]),
+        }
+    }
+
+    // Completion: an atom is true only if some rule supports it
+    for (a, bodies) in supports.iter().enumerate() {
+        let mut clause = vec![-atom_var(a)];
+        clause.extend(bodies.iter().copied());
+        clauses.push(clause);
+    }
+
+    let mut models = Vec::new();
+    if !clauses.iter().all(|c| backend.add_clause(c)) {
+        return models;
+    }
+    loop {
+        if models.len() >= max_models || !backend.solve(&[]) {
+            break;
+        }
+        let Some(assignment) = backend.model() else { break };
+        let model: Vec<usize> = (0..n_atoms)
+            .filter(|&i| assignment.get(&(atom_var(i) as u32)).copied().unwrap_or(false))
+            .collect();
+
+        // Block this assignment of atoms
+        let in_model: FxHashSet<usize> = model.iter().copied().collect();
+        let block: Clause = (0..n_atoms)
+            .map(|i| if in_model.contains(&i) { -atom_var(i) } else { atom_var(i) })
+            .collect();
+        backend.add_clause(&block);
+
+        if is_stable(program, &in_model) {
+            models.push(model);
+        }
+    }
+    models
+}
+
+// Least model of the reduct must coincide with the candidate
+fn is_stable(program: &GroundProgram, model: &FxHashSet<usize>) -> bool {
+    let reduct: Vec<&GroundRule> = program.rules.iter()
+        .filter(|r| r.neg.iter().all(|q| !model.contains(q)))
+        .collect();
+
+    let mut derived: FxHashSet<usize> = FxHashSet::default();
+    loop {
+        let mut changed = false;
+        for rule in &reduct {
+            if !rule.pos.iter().all(|p| derived.contains(p)) {
+                continue;
+            }
+            match &rule.head {
+                GroundHead::Atom(h) => changed |= derived.insert(*h),
+                GroundHead::Choice(hs) => {
+                    for h in hs {
+                        if model.contains(h) {
+                            changed |= derived.insert(*h);
+                        }
+                    }
+                }
+                GroundHead::Constraint => {}
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    derived == *model
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::SymbolTable;
+
+    fn atom(syms: &mut SymbolTable, name: &str) -> Term {
+        Term::compound(syms.intern(name), vec![])
+    }
+
+    #[test]
+    fn even_loop_has_two_models() {
+        let mut syms = SymbolTable::new();
+        let a = atom(&mut syms, "a");
+        let b = atom(&mut syms, "b");
+        let mut prog = AspProgram::new();
+        prog.add_rule(AspRule::normal(a.clone(), vec![], vec![b.clone()]));
+        prog.add_rule(AspRule::normal(b.clone(), vec![], vec![a.clone()]));
+        let models = prog.solve(10);
+        assert_eq!(models.len(), 2);
+        assert!(models.contains(&vec![a]));
+        assert!(models.contains(&vec![b]));
+    }
+
+    #[test]
+    fn self_support_is_not_stable() {
+        let mut syms = SymbolTable::new();
+        let a = atom(&mut syms, "a");
+        let mut prog = AspProgram::new();
+        // a :- a.  has supported model {a} but only stable model {}
+        prog.add_rule(AspRule::normal(a.clone(), vec![a.clone()], vec![]));
+        let models = prog.solve(10);
+        assert_eq!(models, vec![Vec::<Term>::new()]);
+    }
+
+    #[test]
+    fn choice_with_constraint() {
+        let mut syms = SymbolTable::new();
+        let node = syms.intern("node");
+        let pick = syms.intern("pick");
+        let mut prog = AspProgram::new();
+        for i in 1..=3 {
+            prog.add_rule(AspRule::fact(Term::compound(node, vec![Term::int(i)])));
+        }
+        // { pick(X) } :- node(X).
+        prog.add_rule(AspRule::choice(
+            vec![Term::compound(pick, vec![Term::var(0)])],
+            vec![Term::compound(node, vec![Term::var(0)])],
+            vec![],
+        ));
+        // :- pick(1).
+        prog.add_rule(AspRule::constraint(vec![Term::compound(pick, vec![Term::int(1)])], vec![]));
+        let models = prog.solve(100);
+        // 2^2 subsets of {pick(2), pick(3)}
+        assert_eq!(models.len(), 4);
+        assert!(models.iter().all(|m| !m.contains(&Term::compound(pick, vec![Term::int(1)]))));
+    }
+}
@@ -0,0 +1,137 @@
+// Pluggable SAT backends.
+//
+// Code that builds SAT instances incrementally (planner, ASP, MaxSAT) talks
+// to a SatBackend instead of CdclSolver directly, so an external solver (an
+// IPASIR binding over FFI, say) can be dropped in without touching it:
+//
+//   let plan = planner.plan_with(&mut MyIpasirSolver::new(), &init, &goal);
+//
+// Literals and variables are the DIMACS ones of solver.rs. A backend is
+// incremental: clauses stay added across solve calls, and assumptions only
+// hold for the call they are passed to.
+
+use super::cdcl::CdclSolver;
+use super::solver::{Assignment, Literal, SatResult};
+
+pub trait SatBackend {
+    // Returns false once the clauses are known to be unsatisfiable
+    fn add_clause(&mut self, clause: &[Literal]) -> bool;
+
+    // Require an odd number of the literals to be true. The default expands
+    // the XOR into the 2^(n-1) clauses that exclude each even assignment, so
+    // backends with native parity reasoning should override it.
+    fn add_xor(&mut self, xor: &[Literal]) -> bool {
+        let mut vars: Vec<Literal> = Vec::new();
+        let mut odd = true;
+        for &l in xor.iter().filter(|&&l| l != 0) {
+            odd ^= l < 0;
+            match vars.iter().position(|&v| v == l.abs()) {
+                Some(i) => {
+                    vars.swap_remove(i);
+                }
+                None => vars.push(l.abs()),
+            }
+        }
+        for mask in 0u64..1 << vars.len() {
+            // Clause falsified exactly by the assignment `mask` (bit i: vars[i]
+            // true) when that assignment has the wrong parity
+            if (mask.count_ones() % 2 == 1) != odd {
+                let clause: Vec<Literal> =
+                    vars.iter().enumerate().map(|(i, &v)| if mask >> i & 1 == 1 { -v } else { v }).collect();
+                if !self.add_clause(&clause) {
+                    return false;
+                }
+            }
+        }
+        true
+    }
+
+    // True if the clauses and the assumptions are satisfiable together
+    fn solve(&mut self, assumptions: &[Literal]) -> bool;
+
+    // Model of the last call to solve, if it was satisfiable
+    fn model(&self) -> Option<&Assignment>;
+
+    // After an unsatisfiable call: assumptions that are inconsistent with the
+    // clauses (empty if the clauses alone are unsatisfiable)
+    fn core(&self) -> &[Literal];
+}
+
+impl SatBackend for CdclSolver {
+    fn add_clause(&mut self, clause: &[Literal]) -> bool {
+        CdclSolver::add_clause(self, clause)
+    }
+
+    fn add_xor(&mut self, xor: &[Literal]) -> bool {
+        CdclSolver::add_xor(self, xor)
+    }
+
+    fn solve(&mut self, assumptions: &[Literal]) -> bool {
+        matches!(self.solve_with_assumptions(assumptions), SatResult::Sat(_))
+    }
+
+    fn model(&self) -> Option<&Assignment> {
+        self.last_model()
+    }
+
+    fn core(&self) -> &[Literal] {
+        self.failed_assumptions()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Clause-only backend, to exercise the default XOR expansion
+    struct CnfOnly(CdclSolver);
+
+    impl SatBackend for CnfOnly {
+        fn add_clause(&mut self, clause: &[Literal]) -> bool {
+            self.0.add_clause(clause)
+        }
+
+        fn solve(&mut self, assumptions: &[Literal]) -> bool {
+            SatBackend::solve(&mut self.0, assumptions)
+        }
+
+        fn model(&self) -> Option<&Assignment> {
+            SatBackend::model(&self.0)
+        }
+
+        fn core(&self) -> &[Literal] {
+            self.0.failed_assumptions()
+        }
+    }
+
+    fn parity_models<B: SatBackend>(backend: &mut B) -> usize {
+        // x1 xor -x2 xor x3 xor x3: x1 == x2, x3 free
+        backend.add_xor(&[1, -2, 3, 3]);
+        let mut count = 0;
+        while backend.solve(&[]) {
+            let model = backend.model().unwrap().clone();
+            assert_eq!(model[&1], model[&2]);
+            count += 1;
+            let block: Vec<Literal> = (1..=3).map(|v| if model[&(v as u32)] { -v } else { v }).collect();
+            backend.add_clause(&block);
+        }
+        count
+    }
+
+    #[test]
+    fn native_and_expanded_xors_agree() {
+        assert_eq!(parity_models(&mut CdclSolver::new(3)), 4);
+        assert_eq!(parity_models(&mut CnfOnly(CdclSolver::new(3))), 4);
+    }
+
+    #[test]
+    fn cores_name_failed_assumptions() {
+        let mut solver = CdclSolver::new(3);
+        SatBackend::add_clause(&mut solver, &[-1, -2]);
+        assert!(!SatBackend::solve(&mut solver, &[1, 2, 3]));
+        assert!(SatBackend::model(&solver).is_none());
+        let mut core = solver.core().to_vec();
+        core.sort_unstable();
+        assert_eq!(core, vec![1, 2]);
+    }
+}
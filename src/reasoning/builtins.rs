@@ -1,5 +1,8 @@
-use crate::core::{Term, Sym, OrderedFloat};
+use crate::core::{Term, Sym, OrderedFloat, SymbolTable};
 use super::unifier::Substitution;
+use super::temporal::{AllenRelation, Interval};
+use rustc_hash::FxHashMap;
+use std::sync::Arc;
 
 pub const BUILTIN_IS: &str = "is";
 pub const BUILTIN_GT: &str = ">";
@@ -8,6 +11,8 @@ pub const BUILTIN_GTE: &str = ">=";
 pub const BUILTIN_LTE: &str = "<=";
 pub const BUILTIN_EQ: &str = "=:=";
 pub const BUILTIN_NEQ: &str = "=\\=";
+pub const BUILTIN_UNIFY: &str = "=";
+pub const BUILTIN_NOT_UNIFY: &str = "\\=";
 pub const BUILTIN_PLUS: &str = "+";
 pub const BUILTIN_MINUS: &str = "-";
 pub const BUILTIN_MUL: &str = "*";
@@ -17,6 +22,7 @@ pub const BUILTIN_ABS: &str = "abs";
 pub const BUILTIN_MAX: &str = "max";
 pub const BUILTIN_MIN: &str = "min";
 pub const BUILTIN_NOT: &str = "not";
+pub const BUILTIN_NAF: &str = "\\+";
 pub const BUILTIN_CUT: &str = "!";
 pub const BUILTIN_TRUE: &str = "true";
 pub const BUILTIN_FAIL: &str = "fail";
@@ -29,28 +35,162 @@ pub const BUILTIN_LENGTH: &str = "length";
 pub const BUILTIN_APPEND: &str = "append";
 pub const BUILTIN_MEMBER: &str = "member";
 pub const BUILTIN_BETWEEN: &str = "between";
+pub const BUILTIN_NUMLIST: &str = "numlist";
+pub const BUILTIN_NTH0: &str = "nth0";
+pub const BUILTIN_LAST: &str = "last";
+pub const BUILTIN_REVERSE: &str = "reverse";
+pub const BUILTIN_SUM_LIST: &str = "sum_list";
+pub const BUILTIN_MAX_LIST: &str = "max_list";
 pub const BUILTIN_SUCC: &str = "succ";
 pub const BUILTIN_PLUS_OP: &str = "plus";
 pub const BUILTIN_WRITE: &str = "write";
 pub const BUILTIN_NL: &str = "nl";
 pub const BUILTIN_GROUND: &str = "ground";
 pub const BUILTIN_COPY_TERM: &str = "copy_term";
+pub const BUILTIN_UNIV: &str = "=..";
+pub const BUILTIN_TERM_VARIABLES: &str = "term_variables";
 pub const BUILTIN_FUNCTOR: &str = "functor";
 pub const BUILTIN_ARG: &str = "arg";
 pub const BUILTIN_FINDALL: &str = "findall";
-
-#[derive(Debug, Clone)]
+pub const BUILTIN_CALL: &str = "call";
+pub const BUILTIN_NB_SETVAL: &str = "nb_setval";
+pub const BUILTIN_NB_GETVAL: &str = "nb_getval";
+pub const BUILTIN_FREEZE: &str = "freeze";
+pub const BUILTIN_FROZEN: &str = "frozen";
+pub const BUILTIN_WHEN: &str = "when";
+pub const BUILTIN_CONJ: &str = ",";
+pub const BUILTIN_PUT_ATTR: &str = "put_attr";
+pub const BUILTIN_GET_ATTR: &str = "get_attr";
+pub const BUILTIN_DEL_ATTR: &str = "del_attr";
+pub const BUILTIN_GET_DICT: &str = "get_dict";
+pub const BUILTIN_PUT_DICT: &str = "put_dict";
+pub const BUILTIN_DICT_PAIRS: &str = "dict_pairs";
+pub const BUILTIN_AGGREGATE_ALL: &str = "aggregate_all";
+pub const BUILTIN_COUNT: &str = "count";
+pub const BUILTIN_SUM: &str = "sum";
+pub const BUILTIN_BAG: &str = "bag";
+pub const BUILTIN_SET: &str = "set";
+pub const BUILTIN_NOW: &str = "now";
+pub const BUILTIN_HOLDS_AT: &str = "holds_at";
+pub const BUILTIN_HOLDS_NOW: &str = "holds_now";
+
+// Everything registered by BuiltinRegistry::register_standard. Negation is
+// handled by the engine (see RuleEngine::with_standard_builtins); the
+// temporal builtins stay opt-in since their names are common words.
+pub const STANDARD_BUILTINS: &[&str] = &[
+    BUILTIN_IS, BUILTIN_GT, BUILTIN_LT, BUILTIN_GTE, BUILTIN_LTE, BUILTIN_EQ, BUILTIN_NEQ,
+    BUILTIN_UNIFY, BUILTIN_NOT_UNIFY,
+    BUILTIN_PLUS, BUILTIN_MINUS, BUILTIN_MUL, BUILTIN_DIV, BUILTIN_MOD, BUILTIN_ABS,
+    BUILTIN_MAX, BUILTIN_MIN, BUILTIN_CUT, BUILTIN_TRUE, BUILTIN_FAIL,
+    BUILTIN_VAR, BUILTIN_NONVAR, BUILTIN_ATOM, BUILTIN_INTEGER, BUILTIN_IS_LIST, BUILTIN_GROUND,
+    BUILTIN_LENGTH, BUILTIN_APPEND, BUILTIN_MEMBER, BUILTIN_BETWEEN, BUILTIN_SUCC, BUILTIN_PLUS_OP,
+    BUILTIN_NUMLIST, BUILTIN_NTH0, BUILTIN_LAST, BUILTIN_REVERSE, BUILTIN_SUM_LIST, BUILTIN_MAX_LIST,
+    BUILTIN_WRITE, BUILTIN_NL, BUILTIN_COPY_TERM, BUILTIN_UNIV, BUILTIN_TERM_VARIABLES,
+    BUILTIN_FUNCTOR, BUILTIN_ARG, BUILTIN_CALL, BUILTIN_FINDALL, BUILTIN_AGGREGATE_ALL,
+    BUILTIN_NB_SETVAL, BUILTIN_NB_GETVAL,
+    BUILTIN_FREEZE, BUILTIN_FROZEN, BUILTIN_WHEN,
+    BUILTIN_PUT_ATTR, BUILTIN_GET_ATTR, BUILTIN_DEL_ATTR,
+    BUILTIN_GET_DICT, BUILTIN_PUT_DICT, BUILTIN_DICT_PAIRS,
+];
+
+// Names the engine only matches structurally: the ','/2 control construct
+// and the aggregate_all/3 specs. Registering one binds its symbol for
+// name_of without making it a callable builtin, so user facts such as
+// count(3) or set(1, 2) stay reachable.
+pub const STRUCTURAL_NAMES: &[&str] = &[
+    BUILTIN_CONJ, BUILTIN_COUNT, BUILTIN_SUM, BUILTIN_BAG, BUILTIN_SET,
+];
+
+// Allen interval relations, each usable as a binary builtin: before(I1, I2)
+pub const ALLEN_RELATIONS: [&str; 13] = [
+    "before", "meets", "overlaps", "starts", "during", "finishes", "equals",
+    "finished_by", "contains", "started_by", "overlapped_by", "met_by", "after",
+];
+
+// Longest list numlist/3 builds; wider ranges fail rather than allocate
+// without bound
+pub const MAX_NUMLIST_LEN: i64 = 1 << 20;
+
+// User-supplied predicate: receives the raw call arguments and the current
+// substitution (use `sub.apply` to resolve them).
+pub type BuiltinFn = Arc<dyn Fn(&[Term], &Substitution) -> BuiltinResult + Send + Sync>;
+
+#[derive(Clone)]
 pub struct BuiltinRegistry {
     symbols: Vec<(String, Sym)>,
+    // Bindings of STRUCTURAL_NAMES, known by name but never called
+    structural: Vec<(String, Sym)>,
+    // Closures by symbol, and whether register_fn added the symbol entry
+    // (false when the closure overrides an existing builtin)
+    custom: FxHashMap<Sym, (BuiltinFn, bool)>,
+}
+
+impl std::fmt::Debug for BuiltinRegistry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("BuiltinRegistry")
+            .field("symbols", &self.symbols)
+            .field("structural", &self.structural)
+            .field("custom", &self.custom.keys().collect::<Vec<_>>())
+            .finish()
+    }
 }
 
 impl BuiltinRegistry {
     pub fn new() -> Self {
-        Self { symbols: Vec::new() }
+        Self { symbols: Vec::new(), structural: Vec::new(), custom: FxHashMap::default() }
     }
 
     pub fn register(&mut self, name: &str, sym: Sym) {
-        self.symbols.push((name.to_string(), sym));
+        if STRUCTURAL_NAMES.contains(&name) {
+            self.structural.push((name.to_string(), sym));
+        } else {
+            self.symbols.push((name.to_string(), sym));
+        }
+    }
+
+    // Intern and register every name of STANDARD_BUILTINS and
+    // STRUCTURAL_NAMES not already present
+    pub fn register_standard(&mut self, syms: &mut SymbolTable) {
+        for name in STANDARD_BUILTINS.iter().chain(STRUCTURAL_NAMES) {
+            if self.sym_of(name).is_none() {
+                let sym = syms.intern(name);
+                self.register(name, sym);
+            }
+        }
+    }
+
+    pub fn register_fn<F>(&mut self, name: &str, sym: Sym, f: F)
+    where
+        F: Fn(&[Term], &Substitution) -> BuiltinResult + Send + Sync + 'static,
+    {
+        let added = self.custom.get(&sym).map_or(!self.is_builtin(sym), |(_, added)| *added);
+        if !self.is_builtin(sym) {
+            self.symbols.push((name.to_string(), sym));
+        }
+        self.custom.insert(sym, (Arc::new(f), added));
+    }
+
+    // Forget a builtin by name; returns the symbol it was bound to
+    pub fn unregister(&mut self, name: &str) -> Option<Sym> {
+        let sym = self.sym_of(name)?;
+        self.symbols.retain(|(n, _)| n != name);
+        self.structural.retain(|(n, _)| n != name);
+        self.custom.remove(&sym);
+        Some(sym)
+    }
+
+    // Drop a closure; an overridden builtin comes back as it was
+    pub fn unregister_fn(&mut self, sym: Sym) -> bool {
+        match self.custom.remove(&sym) {
+            Some((_, true)) => self.symbols.retain(|(_, s)| *s != sym),
+            Some((_, false)) => {}
+            None => return false,
+        }
+        true
+    }
+
+    pub fn custom(&self, functor: Sym) -> Option<&BuiltinFn> {
+        self.custom.get(&functor).map(|(f, _)| f)
     }
 
     pub fn is_builtin(&self, functor: Sym) -> bool {
@@ -58,11 +198,24 @@ impl BuiltinRegistry {
     }
 
     pub fn name_of(&self, functor: Sym) -> Option<&str> {
-        self.symbols.iter().find(|(_, s)| *s == functor).map(|(n, _)| n.as_str())
+        self.bindings().find(|(_, s)| *s == functor).map(|(n, _)| n.as_str())
     }
 
     pub fn sym_of(&self, name: &str) -> Option<Sym> {
-        self.symbols.iter().find(|(n, _)| n == name).map(|(_, s)| *s)
+        self.bindings().find(|(n, _)| n == name).map(|(_, s)| *s)
+    }
+
+    // Name bindings in registration order, structural names last
+    pub fn registrations(&self) -> impl Iterator<Item = (&str, Sym)> {
+        self.bindings().map(|(n, s)| (n.as_str(), *s))
+    }
+
+    fn bindings(&self) -> impl Iterator<Item = &(String, Sym)> {
+        self.symbols.iter().chain(&self.structural)
+    }
+
+    pub fn is_custom(&self, functor: Sym) -> bool {
+        self.custom.contains_key(&functor)
     }
 }
 
@@ -141,6 +294,22 @@ pub fn term_from_number(n: f64) -> Term {
     }
 }
 
+// Elements of a proper list ([] included)
+fn list_items(term: &Term) -> Option<Vec<Term>> {
+    match term {
+        Term::List(items) => Some(items.clone()),
+        Term::Nil => Some(Vec::new()),
+        _ => None,
+    }
+}
+
+fn unify_result(target: &Term, value: Term, sub: &Substitution) -> Option<BuiltinResult> {
+    match super::unifier::unify(target, &value, sub) {
+        Ok(s) => Some(BuiltinResult::Success(s)),
+        Err(_) => Some(BuiltinResult::Fail),
+    }
+}
+
 pub enum BuiltinResult {
     Success(Substitution),
     Fail,
@@ -154,8 +323,20 @@ pub fn eval_builtin(
     sub: &Substitution,
     builtins: &BuiltinRegistry,
 ) -> Option<BuiltinResult> {
+    if let Some(f) = builtins.custom(functor) {
+        return Some(f(args, sub));
+    }
+
     let name = builtins.name_of(functor)?;
 
+    if let Some(rel) = AllenRelation::from_name(name) {
+        if args.len() != 2 { return Some(BuiltinResult::Fail); }
+        let a = Interval::from_term(&args[0], sub)?;
+        let b = Interval::from_term(&args[1], sub)?;
+        return if a.relation(&b) == rel { Some(BuiltinResult::Success(sub.clone())) }
+        else { Some(BuiltinResult::Fail) };
+    }
+
     match name {
         BUILTIN_TRUE => Some(BuiltinResult::Success(sub.clone())),
         BUILTIN_FAIL => Some(BuiltinResult::Fail),
@@ -269,6 +450,20 @@ pub fn eval_builtin(
             else { Some(BuiltinResult::Fail) }
         }
 
+        BUILTIN_UNIFY => {
+            if args.len() != 2 { return Some(BuiltinResult::Fail); }
+            match super::unifier::unify(&args[0], &args[1], sub) {
+                Ok(s) => Some(BuiltinResult::Success(s)),
+                Err(_) => Some(BuiltinResult::Fail),
+            }
+        }
+
+        BUILTIN_NOT_UNIFY => {
+            if args.len() != 2 { return Some(BuiltinResult::Fail); }
+            if super::unifier::unify(&args[0], &args[1], sub).is_ok() { Some(BuiltinResult::Fail) }
+            else { Some(BuiltinResult::Success(sub.clone())) }
+        }
+
         BUILTIN_GROUND => {
             if args.len() != 1 { return Some(BuiltinResult::Fail); }
             let resolved = sub.apply(&args[0]);
@@ -371,6 +566,69 @@ pub fn eval_builtin(
             }
         }
 
+        BUILTIN_NUMLIST => {
+            if args.len() != 3 { return Some(BuiltinResult::Fail); }
+            let lo = eval_arithmetic(&args[0], sub, builtins)? as i64;
+            let hi = eval_arithmetic(&args[1], sub, builtins)? as i64;
+            if lo > hi || hi.saturating_sub(lo) >= MAX_NUMLIST_LEN { return Some(BuiltinResult::Fail); }
+            unify_result(&args[2], Term::List((lo..=hi).map(Term::Int).collect()), sub)
+        }
+
+        // nth0(Index, List, Elem), enumerating indices when Index is unbound
+        BUILTIN_NTH0 => {
+            if args.len() != 3 { return Some(BuiltinResult::Fail); }
+            let items = list_items(&sub.apply(&args[1]))?;
+            match sub.apply(&args[0]) {
+                Term::Int(i) => match usize::try_from(i).ok().and_then(|i| items.get(i)) {
+                    Some(item) => unify_result(&args[2], item.clone(), sub),
+                    None => Some(BuiltinResult::Fail),
+                },
+                Term::Var(_) => {
+                    let results: Vec<Substitution> = items.iter().enumerate()
+                        .filter_map(|(i, item)| {
+                            let s = super::unifier::unify(&args[0], &Term::Int(i as i64), sub).ok()?;
+                            super::unifier::unify(&args[2], item, &s).ok()
+                        })
+                        .collect();
+                    if results.is_empty() { Some(BuiltinResult::Fail) } else { Some(BuiltinResult::Multi(results)) }
+                }
+                _ => Some(BuiltinResult::Fail),
+            }
+        }
+
+        BUILTIN_LAST => {
+            if args.len() != 2 { return Some(BuiltinResult::Fail); }
+            let items = list_items(&sub.apply(&args[0]))?;
+            match items.last() {
+                Some(item) => unify_result(&args[1], item.clone(), sub),
+                None => Some(BuiltinResult::Fail),
+            }
+        }
+
+        // Works in both directions
+        BUILTIN_REVERSE => {
+            if args.len() != 2 { return Some(BuiltinResult::Fail); }
+            let (known, other) = match list_items(&sub.apply(&args[0])) {
+                Some(items) => (items, &args[1]),
+                None => (list_items(&sub.apply(&args[1]))?, &args[0]),
+            };
+            unify_result(other, Term::List(known.into_iter().rev().collect()), sub)
+        }
+
+        BUILTIN_SUM_LIST | BUILTIN_MAX_LIST => {
+            if args.len() != 2 { return Some(BuiltinResult::Fail); }
+            let items = list_items(&sub.apply(&args[0]))?;
+            let values = items.iter()
+                .map(|t| eval_arithmetic(t, sub, builtins))
+                .collect::<Option<Vec<f64>>>()?;
+            let value = if name == BUILTIN_SUM_LIST {
+                values.iter().sum()
+            } else {
+                values.into_iter().reduce(f64::max)?
+            };
+            unify_result(&args[1], term_from_number(value), sub)
+        }
+
         BUILTIN_WRITE => {
             if args.len() != 1 { return Some(BuiltinResult::Fail); }
             let resolved = sub.apply(&args[0]);
@@ -419,6 +677,182 @@ pub fn eval_builtin(
             Some(BuiltinResult::Fail)
         }
 
+        // f(a, b) =.. [f, a, b], in either direction
+        BUILTIN_UNIV => {
+            if args.len() != 2 { return Some(BuiltinResult::Fail); }
+            let parts = match sub.apply(&args[0]) {
+                Term::Var(_) => {
+                    let items = match sub.apply(&args[1]) {
+                        Term::List(items) => items,
+                        _ => return Some(BuiltinResult::Fail),
+                    };
+                    let built = match items.split_first() {
+                        Some((Term::Atom(f), [])) => Term::Atom(*f),
+                        Some((Term::Atom(f), rest)) => Term::Compound(*f, rest.to_vec()),
+                        Some((atomic, [])) if !matches!(atomic, Term::Var(_) | Term::Compound(..)) => atomic.clone(),
+                        _ => return Some(BuiltinResult::Fail),
+                    };
+                    return match super::unifier::unify(&args[0], &built, sub) {
+                        Ok(s) => Some(BuiltinResult::Success(s)),
+                        Err(_) => Some(BuiltinResult::Fail),
+                    };
+                }
+                Term::Compound(f, a) => {
+                    let mut items = vec![Term::Atom(f)];
+                    items.extend(a);
+                    Term::List(items)
+                }
+                other => Term::List(vec![other]),
+            };
+            match super::unifier::unify(&args[1], &parts, sub) {
+                Ok(s) => Some(BuiltinResult::Success(s)),
+                Err(_) => Some(BuiltinResult::Fail),
+            }
+        }
+
+        // get_dict(?Key, +Dict, ?Value); an unbound Key enumerates the pairs
+        BUILTIN_GET_DICT => {
+            if args.len() != 3 { return Some(BuiltinResult::Fail); }
+            let Term::Dict(pairs) = sub.apply(&args[1]) else { return Some(BuiltinResult::Fail) };
+            match sub.walk(&args[0]) {
+                Term::Atom(k) => match Term::Dict(pairs).dict_get(k) {
+                    Some(v) => unify_result(&args[2], v.clone(), sub),
+                    None => Some(BuiltinResult::Fail),
+                },
+                Term::Var(_) => Some(BuiltinResult::Multi(
+                    pairs.into_iter()
+                        .filter_map(|(k, v)| {
+                            let s = super::unifier::unify(&args[0], &Term::Atom(k), sub).ok()?;
+                            super::unifier::unify(&args[2], &v, &s).ok()
+                        })
+                        .collect(),
+                )),
+                _ => Some(BuiltinResult::Fail),
+            }
+        }
+
+        // put_dict(+Key, +Dict, +Value, -NewDict) and put_dict(+New, +Dict, -NewDict),
+        // where the pairs of New override those of Dict
+        BUILTIN_PUT_DICT => {
+            let (updates, dict, out) = match args {
+                [key, dict, value, out] => match sub.walk(key) {
+                    Term::Atom(k) => (vec![(k, sub.apply(value))], dict, out),
+                    _ => return Some(BuiltinResult::Fail),
+                },
+                [new, dict, out] => match sub.apply(new) {
+                    Term::Dict(pairs) => (pairs, dict, out),
+                    _ => return Some(BuiltinResult::Fail),
+                },
+                _ => return Some(BuiltinResult::Fail),
+            };
+            let mut result = sub.apply(dict);
+            for (k, v) in updates {
+                match result.dict_put(k, v) {
+                    Some(d) => result = d,
+                    None => return Some(BuiltinResult::Fail),
+                }
+            }
+            unify_result(out, result, sub)
+        }
+
+        // dict_pairs(?Dict, ?Pairs) with Pairs a list of Key-Value terms
+        BUILTIN_DICT_PAIRS => {
+            if args.len() != 2 { return Some(BuiltinResult::Fail); }
+            let minus = builtins.sym_of(BUILTIN_MINUS)?;
+            match sub.apply(&args[0]) {
+                Term::Dict(pairs) => {
+                    let list = pairs.into_iter().map(|(k, v)| Term::Compound(minus, vec![Term::Atom(k), v])).collect();
+                    unify_result(&args[1], Term::List(list), sub)
+                }
+                Term::Var(_) => {
+                    let items = match sub.apply(&args[1]) {
+                        Term::List(items) => items,
+                        Term::Nil => Vec::new(),
+                        _ => return Some(BuiltinResult::Fail),
+                    };
+                    let pairs = items.into_iter()
+                        .map(|item| match item {
+                            Term::Compound(f, mut kv) if f == minus && kv.len() == 2 => match kv[0] {
+                                Term::Atom(k) => Some((k, kv.pop()?)),
+                                _ => None,
+                            },
+                            _ => None,
+                        })
+                        .collect::<Option<Vec<_>>>();
+                    match pairs {
+                        Some(pairs) => unify_result(&args[0], Term::dict(pairs), sub),
+                        None => Some(BuiltinResult::Fail),
+                    }
+                }
+                _ => Some(BuiltinResult::Fail),
+            }
+        }
+
+        // Distinct unbound variables of a term, in depth-first order
+        BUILTIN_TERM_VARIABLES => {
+            if args.len() != 2 { return Some(BuiltinResult::Fail); }
+            let vars = Term::List(sub.apply(&args[0]).vars().into_iter().map(Term::Var).collect());
+            match super::unifier::unify(&args[1], &vars, sub) {
+                Ok(s) => Some(BuiltinResult::Success(s)),
+                Err(_) => Some(BuiltinResult::Fail),
+            }
+        }
+
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reasoning::rules::RuleEngine;
+    use crate::reasoning::unifier::unify;
+
+    #[test]
+    fn closures_run_as_builtins() {
+        let mut syms = SymbolTable::new();
+        let [double, digits, is] = ["double", "digits", "is"].map(|n| syms.intern(n));
+        let mut engine = RuleEngine::new().with_standard_builtins(&mut syms);
+
+        // double(X, Y): Y = 2 * X
+        engine.builtins_mut().register_fn("double", double, |args, sub| match sub.apply(&args[0]) {
+            Term::Int(n) => unify_result(&args[1], Term::Int(2 * n), sub).unwrap(),
+            _ => BuiltinResult::Fail,
+        });
+        // digits(D): one answer per decimal digit
+        engine.builtins_mut().register_fn("digits", digits, |args, sub| {
+            BuiltinResult::Multi((0..10).filter_map(|d| unify(&args[0], &Term::Int(d), sub).ok()).collect())
+        });
+        assert!(engine.builtins().is_custom(double));
+
+        let found = engine.query(&Term::compound(double, vec![Term::int(21), Term::var(0)]));
+        assert_eq!(found[0].apply(&Term::var(0)), Term::int(42));
+        assert!(engine.query(&Term::compound(double, vec![Term::var(1), Term::var(0)])).is_empty());
+        assert_eq!(engine.query(&Term::compound(digits, vec![Term::var(0)])).len(), 10);
+
+        // Closures compose with the standard builtins in a conjunction
+        let found = engine.query_all(&[
+            Term::compound(digits, vec![Term::var(0)]),
+            Term::compound(double, vec![Term::var(0), Term::int(14)]),
+            Term::compound(is, vec![Term::var(1), Term::var(0)]),
+        ]);
+        assert_eq!(found.iter().map(|s| s.apply(&Term::var(1))).collect::<Vec<_>>(), vec![Term::int(7)]);
+
+        // Unregistered, the name is an ordinary predicate again
+        assert!(engine.builtins_mut().unregister_fn(double));
+        assert!(!engine.builtins().is_builtin(double));
+        engine.add_fact(Term::compound(double, vec![Term::int(1), Term::int(3)]));
+        let found = engine.query(&Term::compound(double, vec![Term::int(1), Term::var(0)]));
+        assert_eq!(found[0].apply(&Term::var(0)), Term::int(3));
+
+        // Overriding a standard builtin, then dropping the override, restores it
+        let last = syms.intern("last");
+        let last_of = Term::compound(last, vec![Term::List(vec![Term::int(1), Term::int(2)]), Term::var(0)]);
+        engine.builtins_mut().register_fn("last", last, |_, _| BuiltinResult::Fail);
+        engine.builtins_mut().register_fn("last", last, |_, _| BuiltinResult::Fail);
+        assert!(engine.query(&last_of).is_empty());
+        assert!(engine.builtins_mut().unregister_fn(last));
+        assert!(engine.builtins().is_builtin(last));
+        assert_eq!(engine.query(&last_of)[0].apply(&Term::var(0)), Term::int(2));
+    }
+}
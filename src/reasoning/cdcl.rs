@@ -0,0 +1,1075 @@
+// Conflict-driven clause learning SAT solver (the engine behind SatProblem).
+//
+// Literals are encoded as 2 * var + sign (sign 1 = negated), so the negation
+// of a literal is `lit ^ 1`. Every clause of two or more literals watches its
+// first two; a clause is only visited when one of its watches becomes false.
+// Conflicts are analysed up to the first unique implication point, the learnt
+// clause is added and the search backjumps to its second-highest level.
+//
+// Branching picks the unassigned variable with the highest activity (VSIDS or
+// EVSIDS, see SatConfig) in its last polarity. The search restarts after a
+// Luby-scaled number of conflicts; at a restart the learnt clause database is
+// halved once it outgrows its limit, keeping the clauses of lowest LBD
+// (number of distinct decision levels).
+//
+// The solver is incremental: clauses can be added between calls, learnt
+// clauses are kept, and solve_with_assumptions decides the assumed literals
+// first. push() opens a scope whose clauses are guarded by a fresh selector
+// variable (C becomes C or not s, with s assumed while solving); pop() asserts
+// not s, which retires those clauses and every clause learnt from them.
+// Selectors are internal variables, so problem variables are mapped to
+// internal ones and never collide with them.
+//
+// XOR clauses (add_xor) are kept apart as rows of a parity matrix over their
+// variables. Whenever clause propagation reaches a fixpoint, the rows are
+// Gauss-Jordan eliminated on the unassigned variables: a row left with no
+// unassigned variable and the wrong parity is a conflict, one left with a
+// single unassigned variable forces it. The reason (or conflict) is the row
+// as a clause over the assigned variables, added as a learnt clause so
+// conflict analysis treats it like any other.
+//
+// With SatConfig::drat the solver writes a DRAT proof (learnt clauses, the
+// learnt clauses it deletes as "d" lines, and the empty clause once the
+// clauses are refuted) that drat-trim can check against the input CNF.
+// Opening a scope or adding an XOR clause ends the proof: selectors have no
+// DIMACS variable, the clauses retired by pop() cannot be expressed in DRAT,
+// and clauses derived from XOR rows are not RUP steps. An Unsat answer that
+// only holds under assumptions adds no empty clause.
+
+use super::solver::{Assignment, Literal, SatResult};
+use std::fmt::Write;
+
+type Lit = u32;
+
+const UNDEF: u8 = 2;
+
+fn var(l: Lit) -> usize {
+    (l >> 1) as usize
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Branching {
+    // Activities grow by 1 per conflict and are halved every 256 conflicts
+    Vsids,
+    // Activities grow by an increment that is divided by var_decay after
+    // every conflict, favouring recent conflicts exponentially
+    Evsids,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SatConfig {
+    pub branching: Branching,
+    // EVSIDS decay, in (0, 1)
+    pub var_decay: f64,
+    // Conflicts per unit of the Luby sequence; None never restarts
+    pub restart_unit: Option<u64>,
+    // Learnt clauses kept before the first reduction, and the growth of
+    // that limit after each one; None keeps every learnt clause
+    pub reduce_first: Option<usize>,
+    pub reduce_increment: usize,
+    // Record a DRAT proof, see CdclSolver::proof
+    pub drat: bool,
+}
+
+impl Default for SatConfig {
+    fn default() -> Self {
+        Self {
+            branching: Branching::Evsids,
+            var_decay: 0.95,
+            restart_unit: Some(100),
+            reduce_first: Some(2000),
+            reduce_increment: 300,
+            drat: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SolverStats {
+    pub decisions: u64,
+    pub propagations: u64,
+    pub conflicts: u64,
+    pub learnt: u64,
+    pub restarts: u64,
+    // Learnt clauses dropped by database reductions
+    pub deleted: u64,
+}
+
+// 1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8, ... for i = 0, 1, 2, ...
+fn luby(mut i: u64) -> u64 {
+    let (mut size, mut seq) = (1, 0);
+    while size < i + 1 {
+        seq += 1;
+        size = 2 * size + 1;
+    }
+    while size - 1 != i {
+        size = (size - 1) / 2;
+        seq -= 1;
+        i %= size;
+    }
+    1 << seq
+}
+
+#[derive(Debug, Clone)]
+struct ClauseData {
+    lits: Vec<Lit>,
+    learnt: bool,
+    lbd: u32,
+}
+
+// Binary max-heap of variables keyed by activity
+#[derive(Debug, Clone, Default)]
+struct VarOrder {
+    heap: Vec<usize>,
+    // Position of each variable in heap, usize::MAX when absent
+    index: Vec<usize>,
+}
+
+impl VarOrder {
+    fn contains(&self, v: usize) -> bool {
+        self.index.get(v).is_some_and(|&i| i != usize::MAX)
+    }
+
+    fn push(&mut self, v: usize, activity: &[f64]) {
+        if self.index.len() <= v {
+            self.index.resize(v + 1, usize::MAX);
+        }
+        if self.contains(v) {
+            return;
+        }
+        self.index[v] = self.heap.len();
+        self.heap.push(v);
+        self.sift_up(self.heap.len() - 1, activity);
+    }
+
+    fn pop(&mut self, activity: &[f64]) -> Option<usize> {
+        let top = *self.heap.first()?;
+        let last = self.heap.pop()?;
+        self.index[top] = usize::MAX;
+        if !self.heap.is_empty() {
+            self.heap[0] = last;
+            self.index[last] = 0;
+            self.sift_down(0, activity);
+        }
+        Some(top)
+    }
+
+    // Restore the heap after the activity of `v` increased
+    fn bumped(&mut self, v: usize, activity: &[f64]) {
+        if self.contains(v) {
+            self.sift_up(self.index[v], activity);
+        }
+    }
+
+    fn sift_up(&mut self, mut i: usize, activity: &[f64]) {
+        let v = self.heap[i];
+        while i > 0 {
+            let parent = (i - 1) / 2;
+            if activity[self.heap[parent]] >= activity[v] {
+                break;
+            }
+            self.heap[i] = self.heap[parent];
+            self.index[self.heap[i]] = i;
+            i = parent;
+        }
+        self.heap[i] = v;
+        self.index[v] = i;
+    }
+
+    fn sift_down(&mut self, mut i: usize, activity: &[f64]) {
+        let v = self.heap[i];
+        loop {
+            let left = 2 * i + 1;
+            if left >= self.heap.len() {
+                break;
+            }
+            let right = left + 1;
+            let child = if right < self.heap.len() && activity[self.heap[right]] > activity[self.heap[left]] {
+                right
+            } else {
+                left
+            };
+            if activity[self.heap[child]] <= activity[v] {
+                break;
+            }
+            self.heap[i] = self.heap[child];
+            self.index[self.heap[i]] = i;
+            i = child;
+        }
+        self.heap[i] = v;
+        self.index[v] = i;
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CdclSolver {
+    config: SatConfig,
+    clauses: Vec<ClauseData>,
+    // Clauses watching each literal
+    watches: Vec<Vec<usize>>,
+    // Per variable: 0 false, 1 true, UNDEF
+    values: Vec<u8>,
+    levels: Vec<usize>,
+    reasons: Vec<Option<usize>>,
+    // Last value of each variable, reused when it is branched on again
+    phases: Vec<bool>,
+    trail: Vec<Lit>,
+    trail_lim: Vec<usize>,
+    qhead: usize,
+    seen: Vec<bool>,
+    activity: Vec<f64>,
+    bump: f64,
+    order: VarOrder,
+    num_learnt: usize,
+    max_learnt: Option<usize>,
+    // Problem variable -> internal variable (0 when not allocated yet) and
+    // back (0 for selectors)
+    internal: Vec<usize>,
+    external: Vec<u32>,
+    // Selector literal of each open scope, innermost last
+    scopes: Vec<Lit>,
+    assumptions: Vec<Lit>,
+    failed: Vec<Literal>,
+    // Parity rows (bits over xor_vars, right-hand side) and the internal
+    // variable of each column
+    xor_rows: Vec<(Vec<u64>, bool)>,
+    xor_vars: Vec<usize>,
+    // Cleared once a conflict is found at level 0
+    ok: bool,
+    stats: SolverStats,
+    // Model of the last call, if it was satisfiable
+    model: Option<Assignment>,
+    proof: Option<String>,
+}
+
+impl CdclSolver {
+    pub fn new(num_vars: u32) -> Self {
+        Self::with_config(num_vars, SatConfig::default())
+    }
+
+    pub fn with_config(num_vars: u32, config: SatConfig) -> Self {
+        let mut solver = Self {
+            max_learnt: config.reduce_first,
+            proof: config.drat.then(String::new),
+            config,
+            clauses: Vec::new(),
+            watches: Vec::new(),
+            values: Vec::new(),
+            levels: Vec::new(),
+            reasons: Vec::new(),
+            phases: Vec::new(),
+            trail: Vec::new(),
+            trail_lim: Vec::new(),
+            qhead: 0,
+            seen: Vec::new(),
+            activity: Vec::new(),
+            bump: 1.0,
+            order: VarOrder::default(),
+            num_learnt: 0,
+            internal: vec![0],
+            external: Vec::new(),
+            scopes: Vec::new(),
+            assumptions: Vec::new(),
+            failed: Vec::new(),
+            xor_rows: Vec::new(),
+            xor_vars: Vec::new(),
+            ok: true,
+            stats: SolverStats::default(),
+            model: None,
+        };
+        solver.new_var();
+        solver.reserve(num_vars);
+        solver
+    }
+
+    // Problem variables are 1..=num_vars
+    pub fn num_vars(&self) -> u32 {
+        self.internal.len() as u32 - 1
+    }
+
+    pub fn stats(&self) -> SolverStats {
+        self.stats
+    }
+
+    // DRAT proof so far, one clause per line; None unless SatConfig::drat
+    // is set, or once a scope has been opened
+    pub fn proof(&self) -> Option<&str> {
+        self.proof.as_deref()
+    }
+
+    pub fn take_proof(&mut self) -> Option<String> {
+        self.proof.take()
+    }
+
+    fn log_proof(&mut self, lits: &[Lit], delete: bool) {
+        let Some(mut proof) = self.proof.take() else { return };
+        if delete {
+            proof.push_str("d ");
+        }
+        for &l in lits {
+            if let Some(ext) = self.external_lit(l) {
+                let _ = write!(proof, "{} ", ext);
+            }
+        }
+        proof.push_str("0\n");
+        self.proof = Some(proof);
+    }
+
+    // Allocate an internal variable (index 0 is never used)
+    fn new_var(&mut self) -> usize {
+        let v = self.values.len();
+        self.values.push(UNDEF);
+        self.levels.push(0);
+        self.reasons.push(None);
+        self.phases.push(false);
+        self.seen.push(false);
+        self.activity.push(0.0);
+        self.external.push(0);
+        self.watches.push(Vec::new());
+        self.watches.push(Vec::new());
+        if v > 0 {
+            self.order.push(v, &self.activity);
+        }
+        v
+    }
+
+    fn reserve(&mut self, num_vars: u32) {
+        while self.internal.len() <= num_vars as usize {
+            let v = self.new_var();
+            self.external[v] = self.internal.len() as u32;
+            self.internal.push(v);
+        }
+    }
+
+    fn internal_lit(&mut self, l: Literal) -> Lit {
+        self.reserve(l.unsigned_abs());
+        2 * self.internal[l.unsigned_abs() as usize] as Lit + (l < 0) as Lit
+    }
+
+    fn external_lit(&self, l: Lit) -> Option<Literal> {
+        match self.external[var(l)] as Literal {
+            0 => None,
+            v if l & 1 == 1 => Some(-v),
+            v => Some(v),
+        }
+    }
+
+    fn value(&self, l: Lit) -> u8 {
+        value_of(&self.values, l)
+    }
+
+    fn decision_level(&self) -> usize {
+        self.trail_lim.len()
+    }
+
+    // Returns false once the clause set is known to be unsatisfiable
+    pub fn add_clause(&mut self, clause: &[Literal]) -> bool {
+        if !self.ok {
+            return false;
+        }
+        self.cancel_until(0);
+        let lits: Vec<Lit> = clause.iter().filter(|&&l| l != 0).map(|&l| self.internal_lit(l)).collect();
+        self.add_internal(lits)
+    }
+
+    fn add_internal(&mut self, mut lits: Vec<Lit>) -> bool {
+        lits.extend(self.scopes.last().map(|&s| s ^ 1));
+        lits.sort_unstable();
+        lits.dedup();
+        if lits.windows(2).any(|w| w[0] ^ 1 == w[1]) || lits.iter().any(|&l| self.value(l) == 1) {
+            return true;
+        }
+        lits.retain(|&l| self.value(l) == UNDEF);
+        match lits.len() {
+            0 => {
+                self.ok = false;
+                self.log_proof(&[], false);
+            }
+            1 => {
+                self.enqueue(lits[0], None);
+                self.ok = self.propagate().is_none();
+                if !self.ok {
+                    self.log_proof(&[], false);
+                }
+            }
+            _ => {
+                self.attach(lits, false, 0);
+            }
+        }
+        self.ok
+    }
+
+    // Require an odd number of the literals to be true (x1 xor -x2 xor x3);
+    // returns false once the constraints are known to be unsatisfiable
+    pub fn add_xor(&mut self, xor: &[Literal]) -> bool {
+        if !self.ok {
+            return false;
+        }
+        self.cancel_until(0);
+        self.proof = None;
+        let mut rhs = true;
+        let mut vars = Vec::new();
+        for &l in xor.iter().filter(|&&l| l != 0) {
+            let l = self.internal_lit(l);
+            rhs ^= l & 1 == 1;
+            vars.push(var(l));
+        }
+        // Inside a scope the row gets a fresh variable y, false while the
+        // scope is open and free once it is popped
+        let guard = (!self.scopes.is_empty()).then(|| self.new_var());
+        vars.extend(guard);
+        vars.sort_unstable();
+        let mut row = vec![0u64; self.xor_vars.len().div_ceil(64)];
+        for v in vars {
+            let col = match self.xor_vars.iter().position(|&x| x == v) {
+                Some(col) => col,
+                None => {
+                    self.xor_vars.push(v);
+                    let words = self.xor_vars.len().div_ceil(64);
+                    row.resize(words, 0);
+                    for (bits, _) in &mut self.xor_rows {
+                        bits.resize(words, 0);
+                    }
+                    self.xor_vars.len() - 1
+                }
+            };
+            // A repeated variable cancels out
+            row[col / 64] ^= 1 << (col % 64);
+        }
+        self.xor_rows.push((row, rhs));
+        self.ok = self.propagate().is_none();
+        match guard {
+            Some(y) if self.ok => self.add_internal(vec![2 * y as Lit + 1]),
+            _ => self.ok,
+        }
+    }
+
+    fn attach(&mut self, lits: Vec<Lit>, learnt: bool, lbd: u32) -> usize {
+        let ci = self.clauses.len();
+        self.watches[lits[0] as usize].push(ci);
+        self.watches[lits[1] as usize].push(ci);
+        self.clauses.push(ClauseData { lits, learnt, lbd });
+        self.num_learnt += learnt as usize;
+        ci
+    }
+
+    fn enqueue(&mut self, l: Lit, reason: Option<usize>) {
+        let v = var(l);
+        self.values[v] = 1 ^ (l & 1) as u8;
+        self.levels[v] = self.decision_level();
+        self.reasons[v] = reason;
+        self.trail.push(l);
+    }
+
+    // Clause and XOR propagation to a fixpoint; returns a falsified clause
+    fn propagate(&mut self) -> Option<usize> {
+        loop {
+            if let Some(conflict) = self.propagate_clauses() {
+                return Some(conflict);
+            }
+            if self.xor_rows.is_empty() {
+                return None;
+            }
+            match self.propagate_xors() {
+                Err(conflict) => return Some(conflict),
+                Ok(false) => return None,
+                Ok(true) => {}
+            }
+        }
+    }
+
+    // Unit propagation over the watch lists; returns a falsified clause
+    fn propagate_clauses(&mut self) -> Option<usize> {
+        while self.qhead < self.trail.len() {
+            let false_lit = self.trail[self.qhead] ^ 1;
+            self.qhead += 1;
+            self.stats.propagations += 1;
+            let mut watching = std::mem::take(&mut self.watches[false_lit as usize]);
+            let mut kept = 0;
+            let mut conflict = None;
+            let mut i = 0;
+            while i < watching.len() {
+                let ci = watching[i];
+                i += 1;
+                let clause = &mut self.clauses[ci].lits;
+                if clause[0] == false_lit {
+                    clause.swap(0, 1);
+                }
+                let first = clause[0];
+                if value_of(&self.values, first) == 1 {
+                    watching[kept] = ci;
+                    kept += 1;
+                    continue;
+                }
+                if let Some(k) = (2..clause.len()).find(|&k| value_of(&self.values, clause[k]) != 0) {
+                    clause.swap(1, k);
+                    self.watches[clause[1] as usize].push(ci);
+                    continue;
+                }
+                watching[kept] = ci;
+                kept += 1;
+                if value_of(&self.values, first) == 0 {
+                    conflict = Some(ci);
+                    while i < watching.len() {
+                        watching[kept] = watching[i];
+                        kept += 1;
+                        i += 1;
+                    }
+                } else {
+                    self.enqueue(first, Some(ci));
+                }
+            }
+            watching.truncate(kept);
+            self.watches[false_lit as usize] = watching;
+            if conflict.is_some() {
+                return conflict;
+            }
+        }
+        None
+    }
+
+    // Eliminate the XOR rows on the unassigned variables and enqueue what
+    // they force; Ok(false) when nothing is forced
+    fn propagate_xors(&mut self) -> Result<bool, usize> {
+        let mut rows = self.xor_rows.clone();
+        let mut rank = 0;
+        for (col, &v) in self.xor_vars.iter().enumerate() {
+            if self.values[v] != UNDEF {
+                continue;
+            }
+            let (w, bit) = (col / 64, 1u64 << (col % 64));
+            let Some(r) = (rank..rows.len()).find(|&r| rows[r].0[w] & bit != 0) else { continue };
+            rows.swap(rank, r);
+            let (pivot, pivot_rhs) = rows[rank].clone();
+            for (i, (bits, rhs)) in rows.iter_mut().enumerate() {
+                if i != rank && bits[w] & bit != 0 {
+                    for (b, p) in bits.iter_mut().zip(&pivot) {
+                        *b ^= p;
+                    }
+                    *rhs ^= pivot_rhs;
+                }
+            }
+            rank += 1;
+        }
+        let mut forced = Vec::new();
+        for (bits, rhs) in &rows {
+            // Each assigned variable as its false literal
+            let mut lits: Vec<Lit> = Vec::new();
+            let mut parity = *rhs;
+            let mut free = None;
+            let mut num_free = 0;
+            for (w, &word) in bits.iter().enumerate() {
+                let mut word = word;
+                while word != 0 && num_free < 2 {
+                    let v = self.xor_vars[w * 64 + word.trailing_zeros() as usize];
+                    word &= word - 1;
+                    match self.values[v] {
+                        UNDEF => {
+                            num_free += 1;
+                            free = Some(v);
+                        }
+                        value => {
+                            parity ^= value == 1;
+                            lits.push(2 * v as Lit + value as Lit);
+                        }
+                    }
+                }
+            }
+            match (num_free, free) {
+                (0, _) if parity => return Err(self.add_xor_conflict(lits)),
+                (1, Some(v)) => {
+                    lits.insert(0, 2 * v as Lit + !parity as Lit);
+                    forced.push(lits);
+                }
+                _ => {}
+            }
+        }
+        let progress = !forced.is_empty();
+        for mut lits in forced {
+            lits[1..].sort_by_key(|&l| std::cmp::Reverse(self.levels[var(l)]));
+            let implied = lits[0];
+            let ci = self.add_xor_clause(lits);
+            self.enqueue(implied, Some(ci));
+        }
+        Ok(progress)
+    }
+
+    // Backjump to the highest level of the falsified row so that the
+    // conflict has a literal of the current level
+    fn add_xor_conflict(&mut self, mut lits: Vec<Lit>) -> usize {
+        lits.sort_by_key(|&l| std::cmp::Reverse(self.levels[var(l)]));
+        let level = lits.first().map_or(0, |&l| self.levels[var(l)]);
+        self.cancel_until(level);
+        self.add_xor_clause(lits)
+    }
+
+    // Clauses of fewer than two literals are stored without watches; they
+    // only serve as reasons
+    fn add_xor_clause(&mut self, lits: Vec<Lit>) -> usize {
+        let lbd = self.lbd(&lits);
+        if lits.len() >= 2 {
+            return self.attach(lits, true, lbd);
+        }
+        self.clauses.push(ClauseData { lits, learnt: true, lbd });
+        self.num_learnt += 1;
+        self.clauses.len() - 1
+    }
+
+    // First-UIP learning: the asserting literal comes first, a literal of the
+    // backjump level second
+    fn analyze(&mut self, mut conflict: usize) -> (Vec<Lit>, usize) {
+        let mut learnt: Vec<Lit> = vec![0];
+        let mut pending = 0;
+        let mut index = self.trail.len();
+        let mut implied: Option<Lit> = None;
+        loop {
+            let skip = implied.is_some() as usize;
+            for k in skip..self.clauses[conflict].lits.len() {
+                let q = self.clauses[conflict].lits[k];
+                let v = var(q);
+                if !self.seen[v] && self.levels[v] > 0 {
+                    self.seen[v] = true;
+                    self.bump_var(v);
+                    if self.levels[v] >= self.decision_level() {
+                        pending += 1;
+                    } else {
+                        learnt.push(q);
+                    }
+                }
+            }
+            loop {
+                index -= 1;
+                if self.seen[var(self.trail[index])] {
+                    break;
+                }
+            }
+            let p = self.trail[index];
+            self.seen[var(p)] = false;
+            pending -= 1;
+            implied = Some(p);
+            if pending == 0 {
+                learnt[0] = p ^ 1;
+                break;
+            }
+            conflict = self.reasons[var(p)].expect("implied literal without reason");
+        }
+        for &l in &learnt[1..] {
+            self.seen[var(l)] = false;
+        }
+        let mut level = 0;
+        if learnt.len() > 1 {
+            let max = (1..learnt.len()).max_by_key(|&i| self.levels[var(learnt[i])]).unwrap_or(1);
+            learnt.swap(1, max);
+            level = self.levels[var(learnt[1])];
+        }
+        (learnt, level)
+    }
+
+    fn bump_var(&mut self, v: usize) {
+        self.activity[v] += self.bump;
+        if self.activity[v] > 1e100 {
+            for a in &mut self.activity {
+                *a *= 1e-100;
+            }
+            self.bump *= 1e-100;
+        }
+        self.order.bumped(v, &self.activity);
+    }
+
+    // Called once per conflict, after the bumps
+    fn decay_activities(&mut self) {
+        match self.config.branching {
+            Branching::Evsids => self.bump /= self.config.var_decay,
+            Branching::Vsids => {
+                if self.stats.conflicts.is_multiple_of(256) {
+                    for a in &mut self.activity {
+                        *a *= 0.5;
+                    }
+                }
+            }
+        }
+    }
+
+    fn lbd(&self, lits: &[Lit]) -> u32 {
+        let mut levels: Vec<usize> = lits.iter().map(|&l| self.levels[var(l)]).collect();
+        levels.sort_unstable();
+        levels.dedup();
+        levels.len() as u32
+    }
+
+    // Drop the worse half of the learnt clauses (by LBD, then age), keeping
+    // those of LBD <= 2. Only called at level 0, where no clause is the
+    // reason of an assignment that analysis could still visit.
+    fn reduce_db(&mut self) {
+        let mut learnt: Vec<usize> = (0..self.clauses.len())
+            .filter(|&ci| self.clauses[ci].learnt && self.clauses[ci].lbd > 2)
+            .collect();
+        learnt.sort_by_key(|&ci| (std::cmp::Reverse(self.clauses[ci].lbd), ci));
+        let mut delete = vec![false; self.clauses.len()];
+        for &ci in &learnt[..learnt.len() / 2] {
+            delete[ci] = true;
+        }
+        let mut remap = vec![usize::MAX; self.clauses.len()];
+        let mut kept = Vec::with_capacity(self.clauses.len());
+        for (ci, clause) in std::mem::take(&mut self.clauses).into_iter().enumerate() {
+            if delete[ci] {
+                self.log_proof(&clause.lits, true);
+                self.stats.deleted += 1;
+                self.num_learnt -= 1;
+            } else {
+                remap[ci] = kept.len();
+                kept.push(clause);
+            }
+        }
+        self.clauses = kept;
+        for list in &mut self.watches {
+            list.retain_mut(|ci| {
+                *ci = remap[*ci];
+                *ci != usize::MAX
+            });
+        }
+        for reason in &mut self.reasons {
+            *reason = None;
+        }
+    }
+
+    fn cancel_until(&mut self, level: usize) {
+        if self.decision_level() <= level {
+            return;
+        }
+        let start = self.trail_lim[level];
+        for &l in &self.trail[start..] {
+            let v = var(l);
+            self.phases[v] = l & 1 == 0;
+            self.values[v] = UNDEF;
+            self.reasons[v] = None;
+            self.order.push(v, &self.activity);
+        }
+        self.trail.truncate(start);
+        self.trail_lim.truncate(level);
+        self.qhead = start;
+    }
+
+    fn pick_branch(&mut self) -> Option<Lit> {
+        while let Some(v) = self.order.pop(&self.activity) {
+            if self.values[v] == UNDEF {
+                return Some(2 * v as Lit + !self.phases[v] as Lit);
+            }
+        }
+        None
+    }
+
+    // Conflicts until the next restart
+    fn restart_budget(&self, restarts: u64) -> u64 {
+        self.config.restart_unit.map_or(u64::MAX, |unit| unit * luby(restarts))
+    }
+
+    fn restart(&mut self) {
+        self.stats.restarts += 1;
+        self.cancel_until(0);
+        if let Some(max) = self.max_learnt {
+            if self.num_learnt >= max {
+                self.reduce_db();
+                self.max_learnt = Some(max + self.config.reduce_increment);
+            }
+        }
+    }
+
+    // Open a scope: clauses added until the matching pop() are retracted by it
+    pub fn push(&mut self) {
+        self.proof = None;
+        let v = self.new_var();
+        self.scopes.push(2 * v as Lit);
+    }
+
+    // Returns false when no scope is open
+    pub fn pop(&mut self) -> bool {
+        let Some(selector) = self.scopes.pop() else { return false };
+        self.cancel_until(0);
+        if self.ok && self.value(selector) == UNDEF {
+            self.enqueue(selector ^ 1, None);
+            self.ok = self.propagate().is_none();
+            if !self.ok {
+                self.log_proof(&[], false);
+            }
+        }
+        true
+    }
+
+    pub fn num_scopes(&self) -> usize {
+        self.scopes.len()
+    }
+
+    // After an Unsat answer of solve_with_assumptions: a subset of the
+    // assumptions that is inconsistent with the clauses (empty if the clauses
+    // alone are unsatisfiable)
+    pub fn failed_assumptions(&self) -> &[Literal] {
+        &self.failed
+    }
+
+    pub fn last_model(&self) -> Option<&Assignment> {
+        self.model.as_ref()
+    }
+
+    pub fn solve(&mut self) -> SatResult {
+        self.solve_with_assumptions(&[])
+    }
+
+    // Solve with the given literals forced true for this call only
+    pub fn solve_with_assumptions(&mut self, assumptions: &[Literal]) -> SatResult {
+        self.failed.clear();
+        self.model = None;
+        if !self.ok {
+            return SatResult::Unsat;
+        }
+        self.cancel_until(0);
+        let mut assumed: Vec<Lit> = self.scopes.clone();
+        assumed.extend(assumptions.iter().map(|&l| self.internal_lit(l)));
+        self.assumptions = assumed;
+        let result = self.search();
+        self.cancel_until(0);
+        if let SatResult::Sat(model) = &result {
+            self.model = Some(model.clone());
+        }
+        result
+    }
+
+    // Collect the falsified assumption p and the assumptions its negation
+    // was implied from
+    fn analyze_final(&mut self, p: Lit) {
+        self.failed = self.external_lit(p).into_iter().collect();
+        if self.decision_level() == 0 {
+            return;
+        }
+        self.seen[var(p)] = true;
+        for i in (self.trail_lim[0]..self.trail.len()).rev() {
+            let x = self.trail[i];
+            let v = var(x);
+            if !self.seen[v] {
+                continue;
+            }
+            match self.reasons[v] {
+                None => self.failed.extend(self.external_lit(x)),
+                Some(ci) => {
+                    for k in 1..self.clauses[ci].lits.len() {
+                        let q = self.clauses[ci].lits[k];
+                        if self.levels[var(q)] > 0 {
+                            self.seen[var(q)] = true;
+                        }
+                    }
+                }
+            }
+            self.seen[v] = false;
+        }
+        self.seen[var(p)] = false;
+    }
+
+    fn search(&mut self) -> SatResult {
+        let mut restarts = 0;
+        let mut budget = self.restart_budget(restarts);
+        loop {
+            if let Some(conflict) = self.propagate() {
+                self.stats.conflicts += 1;
+                if self.decision_level() == 0 {
+                    self.ok = false;
+                    self.log_proof(&[], false);
+                    return SatResult::Unsat;
+                }
+                let (learnt, level) = self.analyze(conflict);
+                self.log_proof(&learnt, false);
+                self.decay_activities();
+                let lbd = self.lbd(&learnt);
+                self.cancel_until(level);
+                let asserting = learnt[0];
+                if learnt.len() == 1 {
+                    self.enqueue(asserting, None);
+                } else {
+                    self.stats.learnt += 1;
+                    let ci = self.attach(learnt, true, lbd);
+                    self.enqueue(asserting, Some(ci));
+                }
+                budget = budget.saturating_sub(1);
+                continue;
+            }
+            if budget == 0 {
+                restarts += 1;
+                budget = self.restart_budget(restarts);
+                self.restart();
+                continue;
+            }
+            let mut next = None;
+            while self.decision_level() < self.assumptions.len() {
+                let p = self.assumptions[self.decision_level()];
+                match self.value(p) {
+                    1 => self.trail_lim.push(self.trail.len()),
+                    0 => {
+                        self.analyze_final(p);
+                        return SatResult::Unsat;
+                    }
+                    _ => {
+                        next = Some(p);
+                        break;
+                    }
+                }
+            }
+            match next.or_else(|| self.pick_branch()) {
+                Some(l) => {
+                    self.stats.decisions += 1;
+                    self.trail_lim.push(self.trail.len());
+                    self.enqueue(l, None);
+                }
+                None => {
+                    let model: Assignment = (1..self.internal.len())
+                        .map(|e| (e as u32, self.values[self.internal[e]] == 1))
+                        .collect();
+                    return SatResult::Sat(model);
+                }
+            }
+        }
+    }
+}
+
+// 1 if the literal is true, 0 if false, UNDEF otherwise
+fn value_of(values: &[u8], l: Lit) -> u8 {
+    match values[var(l)] {
+        UNDEF => UNDEF,
+        v => v ^ (l & 1) as u8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reasoning::solver::{Clause, SatProblem};
+
+    // n + 1 pigeons into n holes; variable p * n + h + 1 puts pigeon p in hole h
+    fn pigeonhole(n: i32) -> Vec<Clause> {
+        let x = |p: i32, h: i32| p * n + h + 1;
+        let mut clauses: Vec<Clause> = (0..=n).map(|p| (0..n).map(|h| x(p, h)).collect()).collect();
+        for h in 0..n {
+            for p in 0..=n {
+                for q in p + 1..=n {
+                    clauses.push(vec![-x(p, h), -x(q, h)]);
+                }
+            }
+        }
+        clauses
+    }
+
+    fn satisfies(clauses: &[Clause], model: &Assignment) -> bool {
+        clauses.iter().all(|c| c.iter().any(|&l| model.get(&l.unsigned_abs()) == Some(&(l > 0))))
+    }
+
+    #[test]
+    fn proves_pigeonhole_unsat() {
+        let problem = SatProblem::from_clauses(42, pigeonhole(6));
+        assert_eq!(problem.solve(), SatResult::Unsat);
+    }
+
+    #[test]
+    fn drat_proof_ends_in_empty_clause() {
+        let problem = SatProblem::from_clauses(20, pigeonhole(4));
+        let (result, proof) = problem.solve_with_proof();
+        assert_eq!(result, SatResult::Unsat);
+        let lines: Vec<&str> = proof.lines().collect();
+        assert_eq!(lines.last(), Some(&"0"));
+        assert!(lines.len() > 1 && lines.iter().all(|l| l.ends_with('0')));
+        assert!(SatProblem::from_clauses(20, pigeonhole(4)).solver().proof().is_none());
+    }
+
+    #[test]
+    fn xor_chains() {
+        // x1 xor x2, x2 xor x3, ..., x40 xor x1 sum to 0, so an odd number
+        // of odd rows is inconsistent
+        let mut problem = SatProblem::new(40);
+        for v in 1..=40 {
+            problem.add_xor_clause(vec![v, v % 40 + 1]);
+        }
+        let model = match problem.solve() {
+            SatResult::Sat(m) => m,
+            SatResult::Unsat => panic!("even parity is satisfiable"),
+        };
+        assert!((1..40).all(|v| model[&v] != model[&(v + 1)]));
+        problem.add_xor_clause(vec![-1, 2]);
+        assert_eq!(problem.solve(), SatResult::Unsat);
+
+        let mut solver = CdclSolver::new(3);
+        solver.add_clause(&[1, 2]);
+        solver.push();
+        solver.add_xor(&[1, 2, 3]);
+        solver.add_xor(&[-1, 2, 3]);
+        assert_eq!(solver.solve_with_assumptions(&[-2]), SatResult::Unsat);
+        assert!(solver.pop());
+        assert!(matches!(solver.solve_with_assumptions(&[-2]), SatResult::Sat(_)));
+    }
+
+    #[test]
+    fn finds_models_of_satisfiable_instances() {
+        // Pseudo-random 3-SAT below the threshold ratio
+        let mut seed: u64 = 7;
+        let mut next = |m: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((seed >> 33) % m) as i32
+        };
+        let clauses: Vec<Clause> = (0..300)
+            .map(|_| (0..3).map(|_| (next(100) + 1) * if next(2) == 0 { 1 } else { -1 }).collect())
+            .collect();
+        match SatProblem::from_clauses(100, clauses.clone()).solve() {
+            SatResult::Sat(model) => assert!(satisfies(&clauses, &model)),
+            SatResult::Unsat => panic!("expected a model"),
+        }
+    }
+
+    #[test]
+    fn luby_sequence() {
+        let seq: Vec<u64> = (0..15).map(luby).collect();
+        assert_eq!(seq, vec![1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8]);
+    }
+
+    #[test]
+    fn every_configuration_agrees() {
+        let configs = [
+            SatConfig::default(),
+            SatConfig { branching: Branching::Vsids, ..SatConfig::default() },
+            SatConfig { restart_unit: Some(1), reduce_first: Some(10), reduce_increment: 1, ..SatConfig::default() },
+            SatConfig { restart_unit: None, reduce_first: None, ..SatConfig::default() },
+        ];
+        for config in configs {
+            let mut solver = CdclSolver::with_config(42, config);
+            for clause in pigeonhole(6) {
+                solver.add_clause(&clause);
+            }
+            assert_eq!(solver.solve(), SatResult::Unsat);
+        }
+    }
+
+    #[test]
+    fn assumptions_and_scopes() {
+        // x1 -> x2, x2 -> x3
+        let mut solver = CdclSolver::new(3);
+        solver.add_clause(&[-1, 2]);
+        solver.add_clause(&[-2, 3]);
+        assert!(matches!(solver.solve_with_assumptions(&[1]), SatResult::Sat(m) if m[&3]));
+        assert_eq!(solver.solve_with_assumptions(&[1, -3]), SatResult::Unsat);
+        let mut core = solver.failed_assumptions().to_vec();
+        core.sort();
+        assert_eq!(core, vec![-3, 1]);
+
+        solver.push();
+        solver.add_clause(&[-3]);
+        assert_eq!(solver.solve_with_assumptions(&[1]), SatResult::Unsat);
+        assert_eq!(solver.failed_assumptions(), &[1]);
+        assert!(solver.pop());
+        assert!(matches!(solver.solve_with_assumptions(&[1]), SatResult::Sat(m) if m.len() == 3));
+        assert!(!solver.pop());
+    }
+
+    #[test]
+    fn conflicting_units_are_unsat() {
+        let mut solver = CdclSolver::new(2);
+        assert!(solver.add_clause(&[1, 2]));
+        assert!(solver.add_clause(&[-1]));
+        assert!(!solver.add_clause(&[-2]));
+        assert_eq!(solver.solve(), SatResult::Unsat);
+    }
+}
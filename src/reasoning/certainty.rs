@@ -0,0 +1,231 @@
+// Certainty factors: facts and rules carry a degree of belief in [0, 1].
+//
+// A rule instance concludes its head with degree
+//   conj(rule degree, body degree 1, body degree 2, ...)
+// and several proofs of the same answer are merged with `disj`. With the
+// defaults (product / probabilistic sum) this is the MYCIN-style combination;
+// min / max gives Gödel fuzzy logic. Builtins are crisp (degree 1).
+
+use crate::core::{Term, Sym};
+use super::unifier::{Substitution, unify};
+use super::rules::Rule;
+use super::builtins::{BuiltinRegistry, BuiltinResult, eval_builtin};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conjunction {
+    Min,
+    Product,
+}
+
+impl Conjunction {
+    pub fn combine(self, a: f64, b: f64) -> f64 {
+        match self {
+            Conjunction::Min => a.min(b),
+            Conjunction::Product => a * b,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Disjunction {
+    Max,
+    // a + b - a*b
+    ProbabilisticSum,
+}
+
+impl Disjunction {
+    pub fn combine(self, a: f64, b: f64) -> f64 {
+        match self {
+            Disjunction::Max => a.max(b),
+            Disjunction::ProbabilisticSum => a + b - a * b,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CertaintyEngine {
+    facts: Vec<(Term, f64)>,
+    rules: Vec<(Rule, f64)>,
+    conj: Conjunction,
+    disj: Disjunction,
+    builtins: BuiltinRegistry,
+    // Answers below this degree are dropped
+    threshold: f64,
+    max_depth: usize,
+    var_counter: Sym,
+}
+
+impl Default for CertaintyEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CertaintyEngine {
+    pub fn new() -> Self {
+        Self {
+            facts: Vec::new(),
+            rules: Vec::new(),
+            conj: Conjunction::Product,
+            disj: Disjunction::ProbabilisticSum,
+            builtins: BuiltinRegistry::new(),
+            threshold: 0.0,
+            max_depth: 32,
+            var_counter: 10000,
+        }
+    }
+
+    pub fn with_combination(mut self, conj: Conjunction, disj: Disjunction) -> Self {
+        self.conj = conj;
+        self.disj = disj;
+        self
+    }
+
+    pub fn with_threshold(mut self, threshold: f64) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    pub fn with_builtins(mut self, builtins: BuiltinRegistry) -> Self {
+        self.builtins = builtins;
+        self
+    }
+
+    pub fn with_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn add_fact(&mut self, fact: Term, degree: f64) {
+        self.facts.push((fact, degree.clamp(0.0, 1.0)));
+    }
+
+    pub fn add_rule(&mut self, rule: Rule, degree: f64) {
+        self.rules.push((rule, degree.clamp(0.0, 1.0)));
+    }
+
+    pub fn num_facts(&self) -> usize {
+        self.facts.len()
+    }
+
+    pub fn num_rules(&self) -> usize {
+        self.rules.len()
+    }
+
+    // One entry per distinct instance of `goal`, with its combined degree,
+    // strongest first
+    pub fn query(&mut self, goal: &Term) -> Vec<(Substitution, f64)> {
+        let mut answers = self.solve_one(goal, &Substitution::new(), 0);
+        answers.retain(|(_, d)| *d > self.threshold);
+        answers.sort_by(|a, b| b.1.total_cmp(&a.1));
+        answers
+    }
+
+    // Degree of a ground goal (0 when it cannot be derived)
+    pub fn degree(&mut self, goal: &Term) -> f64 {
+        self.query(goal).first().map_or(0.0, |(_, d)| *d)
+    }
+
+    fn solve(&mut self, goals: &[Term], sub: &Substitution, depth: usize) -> Vec<(Substitution, f64)> {
+        let (goal, rest) = match goals.split_first() {
+            Some(split) => split,
+            None => return vec![(sub.clone(), 1.0)],
+        };
+        let mut out = Vec::new();
+        for (s, d) in self.solve_one(goal, sub, depth) {
+            for (s2, d2) in self.solve(rest, &s, depth) {
+                out.push((s2, self.conj.combine(d, d2)));
+            }
+        }
+        out
+    }
+
+    fn solve_one(&mut self, goal: &Term, sub: &Substitution, depth: usize) -> Vec<(Substitution, f64)> {
+        if depth > self.max_depth {
+            return Vec::new();
+        }
+        let goal = sub.apply(goal);
+        if let Term::Compound(f, args) = &goal {
+            if self.builtins.is_builtin(*f) {
+                return match eval_builtin(*f, args, sub, &self.builtins) {
+                    Some(BuiltinResult::Success(s)) => vec![(s, 1.0)],
+                    Some(BuiltinResult::Multi(subs)) => subs.into_iter().map(|s| (s, 1.0)).collect(),
+                    _ => Vec::new(),
+                };
+            }
+        }
+
+        // Merge proofs of the same instance
+        let mut merged: Vec<(Term, Substitution, f64)> = Vec::new();
+        let mut add = |s: Substitution, d: f64, disj: Disjunction| {
+            let instance = s.apply(&goal);
+            match merged.iter_mut().find(|(t, _, _)| *t == instance) {
+                Some(entry) => entry.2 = disj.combine(entry.2, d),
+                None => merged.push((instance, s, d)),
+            }
+        };
+
+        for (fact, d) in &self.facts {
+            if let Ok(s) = unify(&goal, fact, sub) {
+                add(s, *d, self.disj);
+            }
+        }
+
+        for i in 0..self.rules.len() {
+            self.var_counter += 100;
+            let renamed = self.rules[i].0.rename(self.var_counter);
+            let rule_degree = self.rules[i].1;
+            let s0 = match unify(&goal, &renamed.head, sub) {
+                Ok(s) => s,
+                Err(_) => continue,
+            };
+            for (s, d) in self.solve(&renamed.body, &s0, depth + 1) {
+                add(s, self.conj.combine(rule_degree, d), self.disj);
+            }
+        }
+
+        merged.into_iter().map(|(_, s, d)| (s, d)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const P: Sym = 1;
+    const Q: Sym = 2;
+    const R: Sym = 3;
+
+    fn engine(conj: Conjunction, disj: Disjunction) -> CertaintyEngine {
+        let x = Term::var(0);
+        let mut e = CertaintyEngine::new().with_combination(conj, disj);
+        e.add_fact(Term::compound(P, vec![Term::int(1)]), 0.8);
+        e.add_fact(Term::compound(Q, vec![Term::int(1)]), 0.5);
+        // r(X) :- p(X).  (0.9)     r(X) :- q(X).  (1.0)
+        e.add_rule(Rule::new(Term::compound(R, vec![x.clone()]), vec![Term::compound(P, vec![x.clone()])]), 0.9);
+        e.add_rule(Rule::new(Term::compound(R, vec![x.clone()]), vec![Term::compound(Q, vec![x])]), 1.0);
+        e
+    }
+
+    #[test]
+    fn product_and_probabilistic_sum() {
+        let mut e = engine(Conjunction::Product, Disjunction::ProbabilisticSum);
+        let d = e.degree(&Term::compound(R, vec![Term::int(1)]));
+        // 0.72 and 0.5 combined
+        assert!((d - (0.72 + 0.5 - 0.36)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn min_and_max() {
+        let mut e = engine(Conjunction::Min, Disjunction::Max);
+        let answers = e.query(&Term::compound(R, vec![Term::var(5)]));
+        assert_eq!(answers.len(), 1);
+        assert!((answers[0].1 - 0.8).abs() < 1e-9);
+    }
+
+    #[test]
+    fn threshold_drops_weak_answers() {
+        let mut e = engine(Conjunction::Min, Disjunction::Max).with_threshold(0.6);
+        assert!(e.query(&Term::compound(Q, vec![Term::var(0)])).is_empty());
+    }
+}
@@ -0,0 +1,400 @@
+// Constraint Handling Rules over a constraint store.
+//
+//   simplification   H1, ..., Hn <=> Guard | Body
+//   propagation      H1, ..., Hn ==> Guard | Body
+//   simpagation      K1, ..., Km \ H1, ..., Hn <=> Guard | Body
+//
+// Heads match store constraints one-way (they never bind the constraint's
+// variables). Guards and the non-constraint body goals are run on a host
+// RuleEngine, so builtins and ordinary predicates are available to both; the
+// bindings a body goal makes wake up the constraints that mention them.
+// Each propagation rule fires at most once per combination of constraints.
+//
+// Execution follows the refined operational semantics: a new constraint is
+// activated immediately and tried against the rules in order, and a fired
+// rule's body runs before the active constraint continues.
+
+use crate::core::{Term, Sym};
+use super::unifier::{Substitution, rename_vars};
+use super::rules::RuleEngine;
+use super::builtins;
+use rustc_hash::FxHashSet;
+
+// Rule variables are renamed above this offset for every match attempt
+const RENAME_BASE: Sym = 0x4000_0000;
+
+#[derive(Debug, Clone)]
+pub struct ChrRule {
+    pub kept: Vec<Term>,
+    pub removed: Vec<Term>,
+    pub guard: Vec<Term>,
+    pub body: Vec<Term>,
+}
+
+impl ChrRule {
+    pub fn simplification(heads: Vec<Term>, guard: Vec<Term>, body: Vec<Term>) -> Self {
+        Self { kept: Vec::new(), removed: heads, guard, body }
+    }
+
+    pub fn propagation(heads: Vec<Term>, guard: Vec<Term>, body: Vec<Term>) -> Self {
+        Self { kept: heads, removed: Vec::new(), guard, body }
+    }
+
+    pub fn simpagation(kept: Vec<Term>, removed: Vec<Term>, guard: Vec<Term>, body: Vec<Term>) -> Self {
+        Self { kept, removed, guard, body }
+    }
+
+    pub fn is_propagation(&self) -> bool {
+        self.removed.is_empty()
+    }
+
+    fn heads(&self) -> impl Iterator<Item = &Term> {
+        self.kept.iter().chain(self.removed.iter())
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Constraint {
+    term: Term,
+    alive: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct ChrEngine {
+    host: RuleEngine,
+    rules: Vec<ChrRule>,
+    constraints: FxHashSet<(Sym, usize)>,
+    store: Vec<Constraint>,
+    bindings: Substitution,
+    history: FxHashSet<(usize, Vec<usize>)>,
+    renames: Sym,
+    steps: usize,
+    max_steps: usize,
+}
+
+fn key(term: &Term) -> Option<(Sym, usize)> {
+    match term {
+        Term::Compound(f, args) => Some((*f, args.len())),
+        Term::Atom(f) => Some((*f, 0)),
+        _ => None,
+    }
+}
+
+// One-way matching: binds only variables of `pattern`
+fn match_head(pattern: &Term, term: &Term, sub: &mut Substitution) -> bool {
+    match (pattern, term) {
+        (Term::Var(v), _) => match sub.lookup(*v) {
+            Some(bound) => sub.apply(bound) == *term,
+            None => {
+                sub.bind(*v, term.clone());
+                true
+            }
+        },
+        (Term::Compound(f, a), Term::Compound(g, b)) if f == g && a.len() == b.len() => {
+            a.iter().zip(b).all(|(p, t)| match_head(p, t, sub))
+        }
+        (Term::List(a), Term::List(b)) if a.len() == b.len() => {
+            a.iter().zip(b).all(|(p, t)| match_head(p, t, sub))
+        }
+        (Term::Dict(a), Term::Dict(b)) if a.len() == b.len() => {
+            a.iter().zip(b).all(|((k, p), (l, t))| k == l && match_head(p, t, sub))
+        }
+        _ => pattern == term,
+    }
+}
+
+impl ChrEngine {
+    // The host engine evaluates guards and body goals; register the
+    // builtins it needs (e.g. with_standard_builtins) beforehand
+    pub fn new(host: RuleEngine) -> Self {
+        Self {
+            host,
+            rules: Vec::new(),
+            constraints: FxHashSet::default(),
+            store: Vec::new(),
+            bindings: Substitution::new(),
+            history: FxHashSet::default(),
+            renames: 0,
+            steps: 0,
+            max_steps: 100_000,
+        }
+    }
+
+    // Upper bound on rule firings per tell()
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps;
+        self
+    }
+
+    // Functors in rule heads are constraints automatically; declare the
+    // ones that only appear in bodies or queries
+    pub fn declare(&mut self, functor: Sym, arity: usize) {
+        self.constraints.insert((functor, arity));
+    }
+
+    pub fn add_rule(&mut self, rule: ChrRule) {
+        for head in rule.heads() {
+            if let Some(k) = key(head) {
+                self.constraints.insert(k);
+            }
+        }
+        self.rules.push(rule);
+    }
+
+    pub fn host(&self) -> &RuleEngine {
+        &self.host
+    }
+
+    pub fn host_mut(&mut self) -> &mut RuleEngine {
+        &mut self.host
+    }
+
+    pub fn is_constraint(&self, term: &Term) -> bool {
+        key(term).is_some_and(|k| self.constraints.contains(&k))
+    }
+
+    // Post goals (constraints or host goals) and run to a fixpoint.
+    // Returns false if the store became inconsistent.
+    pub fn tell(&mut self, goals: &[Term]) -> bool {
+        self.steps = 0;
+        goals.iter().all(|g| self.solve_goal(g))
+    }
+
+    // Live constraints, with the current bindings applied
+    pub fn store(&self) -> Vec<Term> {
+        self.store.iter()
+            .filter(|c| c.alive)
+            .map(|c| self.bindings.apply(&c.term))
+            .collect()
+    }
+
+    pub fn bindings(&self) -> &Substitution {
+        &self.bindings
+    }
+
+    // Number of rule firings of the last tell()
+    pub fn steps(&self) -> usize {
+        self.steps
+    }
+
+    pub fn clear_store(&mut self) {
+        self.store.clear();
+        self.bindings = Substitution::new();
+        self.history.clear();
+    }
+
+    fn solve_goal(&mut self, goal: &Term) -> bool {
+        let goal = self.bindings.apply(goal);
+        match key(&goal).and_then(|(f, n)| Some((self.host.builtins().name_of(f)?, n))) {
+            Some((builtins::BUILTIN_TRUE, 0)) => return true,
+            Some((builtins::BUILTIN_FAIL, 0)) => return false,
+            _ => {}
+        }
+        if self.is_constraint(&goal) {
+            self.store.push(Constraint { term: goal, alive: true });
+            return self.activate(self.store.len() - 1);
+        }
+
+        // Host goal: commit to its first solution
+        let solution = match self.host.query_first(&goal) {
+            Some(s) => s,
+            None => return false,
+        };
+        let mut bound = Vec::new();
+        for v in goal.vars() {
+            let value = solution.apply(&Term::Var(v));
+            if value != Term::Var(v) {
+                self.bindings.bind(v, value);
+                bound.push(v);
+            }
+        }
+        if bound.is_empty() {
+            return true;
+        }
+        // Wake up the constraints whose variables were bound
+        let woken: Vec<usize> = (0..self.store.len())
+            .filter(|&i| self.store[i].alive && self.store[i].term.vars().iter().any(|v| bound.contains(v)))
+            .collect();
+        woken.into_iter().all(|id| self.activate(id))
+    }
+
+    fn activate(&mut self, id: usize) -> bool {
+        while self.store[id].alive && self.steps < self.max_steps {
+            let (rule, ids, body) = match self.find_firing(id) {
+                Some(f) => f,
+                None => break,
+            };
+            self.steps += 1;
+            let r = &self.rules[rule];
+            let kept = r.kept.len();
+            if r.is_propagation() {
+                self.history.insert((rule, ids.clone()));
+            }
+            for &i in &ids[kept..] {
+                self.store[i].alive = false;
+            }
+            if !body.iter().all(|g| self.solve_goal(g)) {
+                return false;
+            }
+        }
+        true
+    }
+
+    // First rule (in order) with a head matching the active constraint, the
+    // other heads matching distinct live constraints and the guard entailed
+    fn find_firing(&mut self, active: usize) -> Option<(usize, Vec<usize>, Vec<Term>)> {
+        let active_term = self.bindings.apply(&self.store[active].term);
+        let active_key = key(&active_term)?;
+        for r in 0..self.rules.len() {
+            self.renames += 1;
+            let offset = RENAME_BASE + (self.renames % 0x100_0000) * 64;
+            let heads: Vec<Term> = self.rules[r].heads().map(|h| rename_vars(h, offset)).collect();
+            let guard: Vec<Term> = self.rules[r].guard.iter().map(|g| rename_vars(g, offset)).collect();
+            let body: Vec<Term> = self.rules[r].body.iter().map(|g| rename_vars(g, offset)).collect();
+            for pos in 0..heads.len() {
+                if key(&heads[pos]) != Some(active_key) {
+                    continue;
+                }
+                let mut sub = Substitution::new();
+                if !match_head(&heads[pos], &active_term, &mut sub) {
+                    continue;
+                }
+                let mut ids = vec![usize::MAX; heads.len()];
+                ids[pos] = active;
+                if let Some((ids, sub)) = self.match_rest(r, &heads, &guard, ids, sub, 0) {
+                    let body = body.iter().map(|g| sub.apply(g)).collect();
+                    return Some((r, ids, body));
+                }
+            }
+        }
+        None
+    }
+
+    fn match_rest(
+        &mut self,
+        rule: usize,
+        heads: &[Term],
+        guard: &[Term],
+        ids: Vec<usize>,
+        sub: Substitution,
+        from: usize,
+    ) -> Option<(Vec<usize>, Substitution)> {
+        let pos = match (from..heads.len()).find(|&p| ids[p] == usize::MAX) {
+            Some(p) => p,
+            None => {
+                if self.rules[rule].is_propagation() && self.history.contains(&(rule, ids.clone())) {
+                    return None;
+                }
+                return self.check_guard(guard, sub).map(|s| (ids, s));
+            }
+        };
+        for cand in 0..self.store.len() {
+            if !self.store[cand].alive || ids.contains(&cand) {
+                continue;
+            }
+            let term = self.bindings.apply(&self.store[cand].term);
+            let mut extended = sub.clone();
+            if !match_head(&heads[pos], &term, &mut extended) {
+                continue;
+            }
+            let mut next = ids.clone();
+            next[pos] = cand;
+            if let Some(found) = self.match_rest(rule, heads, guard, next, extended, pos + 1) {
+                return Some(found);
+            }
+        }
+        None
+    }
+
+    // Guards run on the host one goal at a time, keeping the first solution.
+    // A guard may bind rule variables but not the constraints' own.
+    fn check_guard(&mut self, guard: &[Term], mut sub: Substitution) -> Option<Substitution> {
+        for g in guard {
+            let goal = sub.apply(g);
+            let solution = self.host.query_first(&goal)?;
+            for v in goal.vars() {
+                let value = solution.apply(&Term::Var(v));
+                if value != Term::Var(v) {
+                    if v < RENAME_BASE {
+                        return None;
+                    }
+                    sub.bind(v, value);
+                }
+            }
+        }
+        Some(sub)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::SymbolTable;
+
+    fn v(id: Sym) -> Term {
+        Term::var(id)
+    }
+
+    #[test]
+    fn gcd_by_simpagation() {
+        let mut syms = SymbolTable::new();
+        let host = RuleEngine::new().with_standard_builtins(&mut syms);
+        let (gcd, is, le, modulo) = (syms.intern("gcd"), syms.intern("is"), syms.intern("<="), syms.intern("mod"));
+        let mut chr = ChrEngine::new(host);
+        // gcd(0) <=> true.
+        chr.add_rule(ChrRule::simplification(vec![Term::compound(gcd, vec![Term::int(0)])], vec![], vec![]));
+        // gcd(N) \ gcd(M) <=> N <= M | L is M mod N, gcd(L).
+        chr.add_rule(ChrRule::simpagation(
+            vec![Term::compound(gcd, vec![v(0)])],
+            vec![Term::compound(gcd, vec![v(1)])],
+            vec![Term::compound(le, vec![v(0), v(1)])],
+            vec![
+                Term::compound(is, vec![v(2), Term::compound(modulo, vec![v(1), v(0)])]),
+                Term::compound(gcd, vec![v(2)]),
+            ],
+        ));
+        assert!(chr.tell(&[Term::compound(gcd, vec![Term::int(9)]), Term::compound(gcd, vec![Term::int(6)])]));
+        assert_eq!(chr.store(), vec![Term::compound(gcd, vec![Term::int(3)])]);
+    }
+
+    #[test]
+    fn propagation_fires_once_per_combination() {
+        let mut chr = ChrEngine::new(RuleEngine::new());
+        let (edge, path) = (1, 2);
+        // edge(X, Y) ==> path(X, Y).
+        chr.add_rule(ChrRule::propagation(
+            vec![Term::compound(edge, vec![v(0), v(1)])],
+            vec![],
+            vec![Term::compound(path, vec![v(0), v(1)])],
+        ));
+        // path(X, Y), edge(Y, Z) ==> path(X, Z).
+        chr.add_rule(ChrRule::propagation(
+            vec![Term::compound(path, vec![v(0), v(1)]), Term::compound(edge, vec![v(1), v(2)])],
+            vec![],
+            vec![Term::compound(path, vec![v(0), v(2)])],
+        ));
+        let edges: Vec<Term> = [(10, 11), (11, 12)].iter()
+            .map(|&(a, b)| Term::compound(edge, vec![Term::atom(a), Term::atom(b)]))
+            .collect();
+        assert!(chr.tell(&edges));
+        let paths = chr.store().into_iter().filter(|t| matches!(t, Term::Compound(f, _) if *f == path)).count();
+        assert_eq!(paths, 3);
+    }
+
+    #[test]
+    fn body_binding_wakes_constraints() {
+        let mut syms = SymbolTable::new();
+        let host = RuleEngine::new().with_standard_builtins(&mut syms);
+        let (dom, eq, fail) = (syms.intern("dom"), syms.intern("="), syms.intern("fail"));
+        let mut chr = ChrEngine::new(host);
+        // dom(7) <=> fail.
+        chr.add_rule(ChrRule::simplification(
+            vec![Term::compound(dom, vec![Term::int(7)])],
+            vec![],
+            vec![Term::compound(fail, vec![])],
+        ));
+        assert!(chr.tell(&[Term::compound(dom, vec![v(0)])]));
+        assert!(chr.tell(&[Term::compound(eq, vec![v(1), Term::int(7)])]));
+        assert!(!chr.tell(&[Term::compound(eq, vec![v(0), v(1)])]));
+    }
+}
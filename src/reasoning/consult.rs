@@ -0,0 +1,131 @@
+// Conflict detection when several rule files are consulted into one engine.
+//
+// Each RuleEngine::consult call names its source (a file or module name).
+// Instead of silently concatenating clause lists, the engine compares the
+// incoming clauses with what is already loaded and reports:
+//   Redefinition    a predicate (functor/arity) already defined by another
+//                   source gets clauses from this one
+//   DuplicateFact   a fact that is already present (it is not added twice)
+//   Contradiction   a fact p next to its negation not(p) / \+ p, in either
+//                   direction
+// ConsultMode decides what happens to redefined predicates.
+
+use crate::core::{Term, Sym, SymbolTable};
+use std::fmt::Write;
+
+// Source name of clauses added outside consult (add_rule, add_fact)
+pub const UNNAMED_SOURCE: &str = "user";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConsultMode {
+    // Keep the old clauses and append the new ones
+    #[default]
+    Append,
+    // Drop the old definition of every redefined predicate first
+    Replace,
+    // Load nothing if any conflict is found
+    Reject,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Conflict {
+    Redefinition { functor: Sym, arity: usize, previous: String },
+    DuplicateFact(Term),
+    // `fact` comes from the consulted source, `existing` contradicts it
+    Contradiction { fact: Term, existing: Term },
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConsultReport {
+    pub source: String,
+    pub conflicts: Vec<Conflict>,
+    pub rules_added: usize,
+    pub facts_added: usize,
+    // Clauses dropped by ConsultMode::Replace
+    pub clauses_removed: usize,
+}
+
+impl ConsultReport {
+    pub fn new(source: &str) -> Self {
+        Self { source: source.to_string(), ..Self::default() }
+    }
+
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+
+    pub fn redefinitions(&self) -> impl Iterator<Item = (Sym, usize)> + '_ {
+        self.conflicts.iter().filter_map(|c| match c {
+            Conflict::Redefinition { functor, arity, .. } => Some((*functor, *arity)),
+            _ => None,
+        })
+    }
+
+    pub fn format(&self, syms: &SymbolTable) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "consulted {}: +{} rules, +{} facts, -{} clauses, {} conflicts",
+            self.source, self.rules_added, self.facts_added, self.clauses_removed, self.conflicts.len()
+        );
+        for c in &self.conflicts {
+            let _ = match c {
+                Conflict::Redefinition { functor, arity, previous } => writeln!(
+                    out,
+                    "  ~ {}/{} redefined (previously from {})",
+                    syms.resolve(*functor).unwrap_or("?"), arity, previous
+                ),
+                Conflict::DuplicateFact(fact) => writeln!(out, "  = {} duplicate fact", fact.display(syms)),
+                Conflict::Contradiction { fact, existing } => {
+                    writeln!(out, "  ! {} contradicts {}", fact.display(syms), existing.display(syms))
+                }
+            };
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reasoning::rules::{Rule, RuleEngine};
+
+    const P: Sym = 1;
+    const Q: Sym = 2;
+    const NOT: Sym = 3;
+
+    fn p(x: u32) -> Term {
+        Term::compound(P, vec![Term::atom(x)])
+    }
+
+    #[test]
+    fn reports_duplicates_and_contradictions() {
+        let mut engine = RuleEngine::new();
+        engine.set_not_sym(NOT);
+        let first = engine.consult("a.pl", vec![], vec![p(10), p(11)], ConsultMode::Append);
+        assert!(first.is_clean());
+
+        let not_p11 = Term::compound(NOT, vec![p(11)]);
+        let second = engine.consult("b.pl", vec![], vec![p(10), not_p11.clone()], ConsultMode::Append);
+        assert!(second.conflicts.contains(&Conflict::DuplicateFact(p(10))));
+        assert!(second.conflicts.contains(&Conflict::Contradiction { fact: not_p11, existing: p(11) }));
+        assert_eq!(second.redefinitions().collect::<Vec<_>>(), vec![(P, 1)]);
+        assert_eq!(engine.num_facts(), 3);
+    }
+
+    #[test]
+    fn replace_drops_old_definition() {
+        let mut engine = RuleEngine::new();
+        let q = Rule::new(Term::compound(Q, vec![Term::var(0)]), vec![Term::compound(P, vec![Term::var(0)])]);
+        engine.consult("a.pl", vec![q], vec![p(10)], ConsultMode::Append);
+        let q20 = Rule::fact(Term::compound(Q, vec![Term::atom(20)]));
+        let report = engine.consult("b.pl", vec![q20], vec![], ConsultMode::Replace);
+        assert_eq!(report.redefinitions().collect::<Vec<_>>(), vec![(Q, 1)]);
+        assert_eq!((report.clauses_removed, engine.num_rules(), engine.num_facts()), (1, 1, 1));
+        assert_eq!(engine.predicate_source(Q, 1), Some("b.pl"));
+
+        let rejected = engine.consult("c.pl", vec![], vec![p(10), p(12)], ConsultMode::Reject);
+        assert!(!rejected.is_clean());
+        assert_eq!((rejected.facts_added, engine.num_facts()), (0, 1));
+    }
+}
@@ -0,0 +1,205 @@
+// Debugger hooks on the four ports of the procedure box model.
+//
+//   Call  a goal is about to be resolved
+//   Exit  the goal succeeded (the substitution holds its bindings)
+//   Redo  backtracking asks the goal for another solution
+//   Fail  the goal has no (more) solutions
+//
+// Install a Tracer on the RuleEngine (RuleEngine::set_tracer) to receive the
+// ports. Spy points restrict reporting to chosen predicates; without any,
+// every goal is traced.
+
+use crate::core::{Term, Sym, SymbolTable};
+use super::unifier::Substitution;
+use std::sync::{Arc, Mutex};
+
+// Reserved functors of the marker goals the engine threads through the
+// continuation while tracing or profiling (next to core::CONS = Sym::MAX)
+pub(crate) const EXIT_PORT: Sym = Sym::MAX - 1;
+pub(crate) const REDO_PORT: Sym = Sym::MAX - 2;
+pub(crate) const FAIL_PORT: Sym = Sym::MAX - 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Port {
+    Call,
+    Exit,
+    Redo,
+    Fail,
+}
+
+impl Port {
+    pub fn name(self) -> &'static str {
+        match self {
+            Port::Call => "Call",
+            Port::Exit => "Exit",
+            Port::Redo => "Redo",
+            Port::Fail => "Fail",
+        }
+    }
+
+    pub(crate) fn from_marker(functor: Sym) -> Option<Self> {
+        match functor {
+            EXIT_PORT => Some(Port::Exit),
+            REDO_PORT => Some(Port::Redo),
+            FAIL_PORT => Some(Port::Fail),
+            _ => None,
+        }
+    }
+}
+
+// Every callback defaults to port(); override either
+pub trait Tracer {
+    fn port(&mut self, _port: Port, _goal: &Term, _depth: usize, _sub: &Substitution) {}
+
+    fn call(&mut self, goal: &Term, depth: usize, sub: &Substitution) {
+        self.port(Port::Call, goal, depth, sub);
+    }
+
+    fn exit(&mut self, goal: &Term, depth: usize, sub: &Substitution) {
+        self.port(Port::Exit, goal, depth, sub);
+    }
+
+    fn redo(&mut self, goal: &Term, depth: usize, sub: &Substitution) {
+        self.port(Port::Redo, goal, depth, sub);
+    }
+
+    fn fail(&mut self, goal: &Term, depth: usize, sub: &Substitution) {
+        self.port(Port::Fail, goal, depth, sub);
+    }
+}
+
+pub type TracerHandle = Arc<Mutex<dyn Tracer + Send>>;
+
+// The installed tracer, opaque to Debug
+#[derive(Clone)]
+pub(crate) struct ActiveTracer(pub TracerHandle);
+
+impl std::fmt::Debug for ActiveTracer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ActiveTracer")
+    }
+}
+
+// Prints one line per port to stderr, indented by depth:
+//    Call: (1) parent(tom, _G10001)
+pub struct ConsoleTracer {
+    syms: SymbolTable,
+}
+
+impl ConsoleTracer {
+    pub fn new(syms: &SymbolTable) -> Self {
+        Self { syms: syms.clone() }
+    }
+
+    pub fn format(&self, port: Port, goal: &Term, depth: usize) -> String {
+        format!("{}{}: ({}) {}", "  ".repeat(depth), port.name(), depth, goal.display(&self.syms))
+    }
+}
+
+impl Tracer for ConsoleTracer {
+    fn port(&mut self, port: Port, goal: &Term, depth: usize, _sub: &Substitution) {
+        eprintln!("{}", self.format(port, goal, depth));
+    }
+}
+
+// Records every port, e.g. for assertions or post-mortem inspection
+#[derive(Debug, Clone, Default)]
+pub struct TraceLog {
+    pub events: Vec<(Port, Term, usize)>,
+}
+
+impl TraceLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Tracer for TraceLog {
+    fn port(&mut self, port: Port, goal: &Term, depth: usize, _sub: &Substitution) {
+        self.events.push((port, goal.clone(), depth));
+    }
+}
+
+// Predicates to report; an arity of None spies every arity
+#[derive(Debug, Clone, Default)]
+pub struct SpyPoints {
+    points: Vec<(Sym, Option<usize>)>,
+}
+
+impl SpyPoints {
+    pub fn add(&mut self, functor: Sym, arity: Option<usize>) {
+        if !self.points.contains(&(functor, arity)) {
+            self.points.push((functor, arity));
+        }
+    }
+
+    pub fn remove(&mut self, functor: Sym) {
+        self.points.retain(|(f, _)| *f != functor);
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    // With no spy points every goal is reported
+    pub fn reports(&self, goal: &Term) -> bool {
+        let (f, arity) = match goal {
+            Term::Compound(f, args) => (*f, args.len()),
+            Term::Atom(f) => (*f, 0),
+            _ => return self.points.is_empty(),
+        };
+        self.points.is_empty() || self.points.iter().any(|&(g, a)| g == f && a.is_none_or(|a| a == arity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reasoning::rules::{Rule, RuleEngine};
+
+    const PARENT: Sym = 1;
+    const ANCESTOR: Sym = 2;
+
+    fn engine() -> RuleEngine {
+        let mut engine = RuleEngine::new();
+        for (a, b) in [(10, 11), (11, 12)] {
+            engine.add_fact(Term::compound(PARENT, vec![Term::atom(a), Term::atom(b)]));
+        }
+        engine.add_rule(Rule::new(
+            Term::compound(ANCESTOR, vec![Term::var(0), Term::var(1)]),
+            vec![Term::compound(PARENT, vec![Term::var(0), Term::var(1)])],
+        ));
+        engine
+    }
+
+    #[test]
+    fn reports_four_ports() {
+        let mut engine = engine();
+        let log = Arc::new(Mutex::new(TraceLog::new()));
+        engine.set_tracer(log.clone());
+        let results = engine.query(&Term::compound(PARENT, vec![Term::var(0), Term::var(1)]));
+        assert_eq!(results.len(), 2);
+
+        let ports: Vec<Port> = log.lock().unwrap().events.iter().map(|e| e.0).collect();
+        assert_eq!(ports, vec![Port::Call, Port::Exit, Port::Redo, Port::Exit, Port::Fail]);
+        let goal = log.lock().unwrap().events[1].1.clone();
+        assert_eq!(goal, Term::compound(PARENT, vec![Term::atom(10), Term::atom(11)]));
+    }
+
+    #[test]
+    fn spy_points_filter_predicates() {
+        let mut engine = engine();
+        let log = Arc::new(Mutex::new(TraceLog::new()));
+        engine.set_tracer(log.clone());
+        engine.spy(ANCESTOR, Some(2));
+        engine.query(&Term::compound(ANCESTOR, vec![Term::atom(10), Term::var(0)]));
+        let log = log.lock().unwrap();
+        let events = &log.events;
+        assert!(events.iter().all(|(_, g, _)| matches!(g, Term::Compound(ANCESTOR, _))));
+        assert_eq!(events.iter().map(|e| e.0).collect::<Vec<_>>(), vec![Port::Call, Port::Exit, Port::Fail]);
+    }
+}
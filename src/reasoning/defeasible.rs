@@ -0,0 +1,256 @@
+// Defeasible logic: strict rules, defeasible rules and defeaters with a
+// priority ordering, resolved at query time.
+//
+// Literals are atoms or their classical negation `neg(A)` (the negation
+// functor is chosen by the caller). A literal is concluded
+// - strictly, from a fact or a strict rule whose body is concluded;
+// - defeasibly, from a defeasible rule whose body is concluded, provided no
+//   applicable rule for the complement is strict or has a priority at least as
+//   high. Defeaters never conclude anything, they only attack.
+//
+// So "birds fly" (priority 0) is overridden by "penguins don't fly"
+// (priority 1) without the user ordering NAF goals by hand.
+
+use crate::core::{Term, Sym};
+use super::unifier::{Substitution, unify};
+use super::rules::Rule;
+use super::builtins::{BuiltinRegistry, BuiltinResult, eval_builtin};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RuleKind {
+    Strict,
+    Defeasible,
+    Defeater,
+}
+
+#[derive(Debug, Clone)]
+pub struct DefeasibleRule {
+    pub rule: Rule,
+    pub kind: RuleKind,
+    pub priority: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct DefeasibleTheory {
+    facts: Vec<Term>,
+    rules: Vec<DefeasibleRule>,
+    neg_sym: Sym,
+    naf_sym: Option<Sym>,
+    builtins: BuiltinRegistry,
+    max_depth: usize,
+    var_counter: Sym,
+}
+
+impl DefeasibleTheory {
+    pub fn new(neg_sym: Sym) -> Self {
+        Self {
+            facts: Vec::new(),
+            rules: Vec::new(),
+            neg_sym,
+            naf_sym: None,
+            builtins: BuiltinRegistry::new(),
+            max_depth: 64,
+            var_counter: 10000,
+        }
+    }
+
+    pub fn with_builtins(mut self, builtins: BuiltinRegistry) -> Self {
+        self.builtins = builtins;
+        self
+    }
+
+    pub fn with_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    // Negation as failure inside rule bodies: naf(G) holds when G is not concluded
+    pub fn set_naf_sym(&mut self, sym: Sym) {
+        self.naf_sym = Some(sym);
+    }
+
+    pub fn add_fact(&mut self, fact: Term) {
+        self.facts.push(fact);
+    }
+
+    pub fn add_strict(&mut self, rule: Rule) {
+        self.add(rule, RuleKind::Strict, 0);
+    }
+
+    pub fn add_defeasible(&mut self, rule: Rule, priority: i32) {
+        self.add(rule, RuleKind::Defeasible, priority);
+    }
+
+    pub fn add_defeater(&mut self, rule: Rule, priority: i32) {
+        self.add(rule, RuleKind::Defeater, priority);
+    }
+
+    pub fn add(&mut self, rule: Rule, kind: RuleKind, priority: i32) {
+        self.rules.push(DefeasibleRule { rule, kind, priority });
+    }
+
+    pub fn rules(&self) -> &[DefeasibleRule] {
+        &self.rules
+    }
+
+    pub fn negate(&self, literal: &Term) -> Term {
+        match literal {
+            Term::Compound(f, args) if *f == self.neg_sym && args.len() == 1 => args[0].clone(),
+            other => Term::compound(self.neg_sym, vec![other.clone()]),
+        }
+    }
+
+    // Every substitution under which `goal` is (defeasibly or strictly) concluded
+    pub fn query(&mut self, goal: &Term) -> Vec<Substitution> {
+        self.solve(std::slice::from_ref(goal), &Substitution::new(), 0)
+    }
+
+    pub fn holds(&mut self, goal: &Term) -> bool {
+        !self.query(goal).is_empty()
+    }
+
+    fn solve(&mut self, goals: &[Term], sub: &Substitution, depth: usize) -> Vec<Substitution> {
+        let (goal, rest) = match goals.split_first() {
+            Some(split) => split,
+            None => return vec![sub.clone()],
+        };
+        if depth > self.max_depth {
+            return Vec::new();
+        }
+        let mut out = Vec::new();
+        for s in self.solve_one(goal, sub, depth) {
+            out.extend(self.solve(rest, &s, depth));
+        }
+        out
+    }
+
+    fn solve_one(&mut self, goal: &Term, sub: &Substitution, depth: usize) -> Vec<Substitution> {
+        let goal = sub.apply(goal);
+        if let Term::Compound(f, args) = &goal {
+            if self.naf_sym == Some(*f) && args.len() == 1 {
+                let inner = self.solve_one(&args[0], sub, depth + 1);
+                return if inner.is_empty() { vec![sub.clone()] } else { Vec::new() };
+            }
+            if self.builtins.is_builtin(*f) {
+                return match eval_builtin(*f, args, sub, &self.builtins) {
+                    Some(BuiltinResult::Success(s)) => vec![s],
+                    Some(BuiltinResult::Multi(subs)) => subs,
+                    _ => Vec::new(),
+                };
+            }
+        }
+
+        let mut out: Vec<Substitution> = Vec::new();
+        let mut concluded: Vec<Term> = Vec::new();
+        for fact in &self.facts {
+            if let Ok(s) = unify(&goal, fact, sub) {
+                if !concluded.contains(fact) {
+                    concluded.push(fact.clone());
+                    out.push(s);
+                }
+            }
+        }
+
+        for i in 0..self.rules.len() {
+            let kind = self.rules[i].kind;
+            if kind == RuleKind::Defeater {
+                continue;
+            }
+            let priority = self.rules[i].priority;
+            let renamed = self.rename(i);
+            let s0 = match unify(&goal, &renamed.head, sub) {
+                Ok(s) => s,
+                Err(_) => continue,
+            };
+            for s in self.solve(&renamed.body, &s0, depth + 1) {
+                let literal = s.apply(&goal);
+                if concluded.contains(&literal) {
+                    continue;
+                }
+                if kind == RuleKind::Defeasible && self.defeated(&literal, priority, depth) {
+                    continue;
+                }
+                concluded.push(literal);
+                out.push(s);
+            }
+        }
+        out
+    }
+
+    // Is there an applicable rule for the complement of `literal` that is
+    // strict or at least as strong as `priority`?
+    fn defeated(&mut self, literal: &Term, priority: i32, depth: usize) -> bool {
+        let complement = self.negate(literal);
+        if self.facts.iter().any(|f| unify(&complement, f, &Substitution::new()).is_ok()) {
+            return true;
+        }
+        for i in 0..self.rules.len() {
+            let attacker = &self.rules[i];
+            if attacker.kind != RuleKind::Strict && attacker.priority < priority {
+                continue;
+            }
+            let renamed = self.rename(i);
+            if let Ok(s) = unify(&complement, &renamed.head, &Substitution::new()) {
+                if !self.solve(&renamed.body, &s, depth + 1).is_empty() {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn rename(&mut self, i: usize) -> Rule {
+        self.var_counter += 100;
+        self.rules[i].rule.rename(self.var_counter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NEG: Sym = 1;
+    const BIRD: Sym = 2;
+    const PENGUIN: Sym = 3;
+    const FLIES: Sym = 4;
+
+    fn lit(f: Sym, x: Term) -> Term {
+        Term::compound(f, vec![x])
+    }
+
+    fn theory() -> DefeasibleTheory {
+        let mut th = DefeasibleTheory::new(NEG);
+        let x = Term::var(0);
+        // penguin(X) -> bird(X)
+        th.add_strict(Rule::new(lit(BIRD, x.clone()), vec![lit(PENGUIN, x.clone())]));
+        // bird(X) => flies(X)
+        th.add_defeasible(Rule::new(lit(FLIES, x.clone()), vec![lit(BIRD, x.clone())]), 0);
+        // penguin(X) => ~flies(X), stronger
+        th.add_defeasible(Rule::new(lit(NEG, lit(FLIES, x.clone())), vec![lit(PENGUIN, x)]), 1);
+        th.add_fact(lit(BIRD, Term::atom(10)));
+        th.add_fact(lit(PENGUIN, Term::atom(11)));
+        th
+    }
+
+    #[test]
+    fn exception_overrides_default() {
+        let mut th = theory();
+        assert!(th.holds(&lit(FLIES, Term::atom(10))));
+        assert!(!th.holds(&lit(FLIES, Term::atom(11))));
+        assert!(th.holds(&lit(NEG, lit(FLIES, Term::atom(11)))));
+        let fliers = th.query(&lit(FLIES, Term::var(7)));
+        assert_eq!(fliers.len(), 1);
+        assert_eq!(fliers[0].apply(&Term::var(7)), Term::atom(10));
+    }
+
+    #[test]
+    fn equal_priorities_block_each_other() {
+        let mut th = DefeasibleTheory::new(NEG);
+        let x = Term::var(0);
+        th.add_defeasible(Rule::new(lit(FLIES, x.clone()), vec![lit(BIRD, x.clone())]), 0);
+        th.add_defeater(Rule::new(lit(NEG, lit(FLIES, x.clone())), vec![lit(BIRD, x)]), 0);
+        th.add_fact(lit(BIRD, Term::atom(10)));
+        assert!(!th.holds(&lit(FLIES, Term::atom(10))));
+        assert!(!th.holds(&lit(NEG, lit(FLIES, Term::atom(10)))));
+    }
+}
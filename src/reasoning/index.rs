@@ -0,0 +1,380 @@
+// Discrimination tree: a trie over the preorder traversal of terms.
+//
+// Each stored term is flattened into a key sequence (functor/arity, constants,
+// `*` for variables) and inserted as a path. Retrieval walks the trie with the
+// flattened query: a variable in the query skips a whole stored subterm, a
+// variable in a stored term skips a whole query subterm. The result is a
+// superset of the entries unifiable with the query; callers still unify.
+//
+// Keys follow unify's list equivalences: Nil is the empty list, and a
+// [H|T] cell, which unifies with any list of one or more items, is a `*`.
+//
+// Rule heads are indexed more coarsely, by predicate only (RuleIndex): a call
+// renames apart just the rules that define its predicate.
+
+use crate::core::{Term, Sym, CONS};
+use super::profile::predicate_key;
+use super::rules::Rule;
+use rustc_hash::FxHashMap;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Key {
+    Star,
+    Atom(Sym),
+    Int(i64),
+    Float(u64),
+    Str(Box<str>),
+    Bool(bool),
+    Functor(Sym, usize),
+    List(usize),
+    // Key set of a dict; its values follow in key order
+    Dict(Box<[Sym]>),
+}
+
+impl Key {
+    fn arity(&self) -> usize {
+        match self {
+            Key::Functor(_, n) | Key::List(n) => *n,
+            Key::Dict(keys) => keys.len(),
+            _ => 0,
+        }
+    }
+}
+
+fn flatten(term: &Term, out: &mut Vec<Key>) {
+    match term {
+        Term::Var(_) => out.push(Key::Star),
+        Term::Atom(a) => out.push(Key::Atom(*a)),
+        Term::Int(n) => out.push(Key::Int(*n)),
+        Term::Float(f) => out.push(Key::Float(f.0)),
+        Term::Str(s) => out.push(Key::Str(s.clone())),
+        Term::Bool(b) => out.push(Key::Bool(*b)),
+        Term::Nil => out.push(Key::List(0)),
+        Term::Compound(CONS, args) if args.len() == 2 => out.push(Key::Star),
+        Term::Compound(f, args) => {
+            out.push(Key::Functor(*f, args.len()));
+            for a in args {
+                flatten(a, out);
+            }
+        }
+        Term::List(items) => {
+            out.push(Key::List(items.len()));
+            for item in items {
+                flatten(item, out);
+            }
+        }
+        Term::Dict(pairs) => {
+            out.push(Key::Dict(pairs.iter().map(|(k, _)| *k).collect()));
+            for (_, v) in pairs {
+                flatten(v, out);
+            }
+        }
+    }
+}
+
+// Index just past the subterm starting at `i`
+fn skip_subterm(keys: &[Key], i: usize) -> usize {
+    let mut pending = 1;
+    let mut j = i;
+    while pending > 0 {
+        pending = pending - 1 + keys[j].arity();
+        j += 1;
+    }
+    j
+}
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    children: FxHashMap<Key, usize>,
+    values: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TermIndex {
+    nodes: Vec<Node>,
+    len: usize,
+}
+
+impl Default for TermIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TermIndex {
+    pub fn new() -> Self {
+        Self { nodes: vec![Node::default()], len: 0 }
+    }
+
+    pub fn from_terms(terms: &[Term]) -> Self {
+        let mut index = Self::new();
+        for (i, t) in terms.iter().enumerate() {
+            index.insert(t, i);
+        }
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::new();
+    }
+
+    pub fn insert(&mut self, term: &Term, value: usize) {
+        let mut keys = Vec::new();
+        flatten(term, &mut keys);
+        let mut node = 0;
+        for key in keys {
+            node = match self.nodes[node].children.get(&key) {
+                Some(&child) => child,
+                None => {
+                    let child = self.nodes.len();
+                    self.nodes.push(Node::default());
+                    self.nodes[node].children.insert(key, child);
+                    child
+                }
+            };
+        }
+        self.nodes[node].values.push(value);
+        self.len += 1;
+    }
+
+    pub fn remove(&mut self, term: &Term, value: usize) -> bool {
+        let mut keys = Vec::new();
+        flatten(term, &mut keys);
+        let mut node = 0;
+        for key in &keys {
+            node = match self.nodes[node].children.get(key) {
+                Some(&child) => child,
+                None => return false,
+            };
+        }
+        let values = &mut self.nodes[node].values;
+        match values.iter().position(|&v| v == value) {
+            Some(pos) => {
+                values.remove(pos);
+                self.len -= 1;
+                true
+            }
+            None => false,
+        }
+    }
+
+    // Values of entries that may unify with `query`, in ascending order
+    pub fn candidates(&self, query: &Term) -> Vec<usize> {
+        let mut keys = Vec::new();
+        flatten(query, &mut keys);
+        let mut out = Vec::new();
+        self.retrieve(0, &keys, 0, &mut out);
+        out.sort_unstable();
+        out.dedup();
+        out
+    }
+
+    fn retrieve(&self, node: usize, query: &[Key], i: usize, out: &mut Vec<usize>) {
+        if i == query.len() {
+            out.extend_from_slice(&self.nodes[node].values);
+            return;
+        }
+        let children = &self.nodes[node].children;
+        match &query[i] {
+            Key::Star => self.skip_stored(node, 1, query, i + 1, out),
+            key => {
+                if let Some(&child) = children.get(key) {
+                    self.retrieve(child, query, i + 1, out);
+                }
+                if let Some(&child) = children.get(&Key::Star) {
+                    self.retrieve(child, query, skip_subterm(query, i), out);
+                }
+            }
+        }
+    }
+
+    // Consume `pending` complete stored subterms starting at `node`
+    fn skip_stored(&self, node: usize, pending: usize, query: &[Key], resume: usize, out: &mut Vec<usize>) {
+        for (key, &child) in &self.nodes[node].children {
+            let left = pending - 1 + key.arity();
+            if left == 0 {
+                self.retrieve(child, query, resume, out);
+            } else {
+                self.skip_stored(child, left, query, resume, out);
+            }
+        }
+    }
+}
+
+// Rules by the predicate (functor, arity) of their head, plus the variable
+// span of each: one past its highest variable id. Rules whose head has no
+// predicate (a bare variable) may match any call.
+#[derive(Debug, Clone, Default)]
+pub struct RuleIndex {
+    by_predicate: FxHashMap<(Sym, usize), Vec<usize>>,
+    any: Vec<usize>,
+    spans: Vec<Sym>,
+}
+
+impl RuleIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn from_rules(rules: &[Rule]) -> Self {
+        let mut index = Self::new();
+        for rule in rules {
+            index.push(rule);
+        }
+        index
+    }
+
+    pub fn len(&self) -> usize {
+        self.spans.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spans.is_empty()
+    }
+
+    // Index the rule appended after every rule indexed so far
+    pub fn push(&mut self, rule: &Rule) {
+        let i = self.spans.len();
+        match predicate_key(&rule.head) {
+            Some(key) => self.by_predicate.entry(key).or_default().push(i),
+            None => self.any.push(i),
+        }
+        let span = std::iter::once(&rule.head).chain(&rule.body)
+            .flat_map(|t| t.vars())
+            .max()
+            .map_or(0, |v| v + 1);
+        self.spans.push(span);
+    }
+
+    pub fn span(&self, rule: usize) -> Sym {
+        self.spans[rule]
+    }
+
+    // Rules that may resolve `goal`, in program order
+    pub fn candidates(&self, goal: &Term) -> Vec<usize> {
+        let Some(key) = predicate_key(goal) else {
+            return (0..self.len()).collect();
+        };
+        let mut out = self.by_predicate.get(&key).cloned().unwrap_or_default();
+        if !self.any.is_empty() {
+            out.extend(&self.any);
+            out.sort_unstable();
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn f(args: Vec<Term>) -> Term {
+        Term::compound(1, args)
+    }
+
+    #[test]
+    fn retrieves_by_constant_and_variable() {
+        let terms = vec![
+            f(vec![Term::atom(10), Term::int(1)]),
+            f(vec![Term::atom(11), Term::int(2)]),
+            f(vec![Term::atom(10), Term::int(3)]),
+            Term::compound(2, vec![Term::atom(10)]),
+        ];
+        let index = TermIndex::from_terms(&terms);
+        assert_eq!(index.candidates(&f(vec![Term::atom(10), Term::var(0)])), vec![0, 2]);
+        assert_eq!(index.candidates(&f(vec![Term::var(0), Term::var(1)])), vec![0, 1, 2]);
+        assert_eq!(index.candidates(&f(vec![Term::var(0), Term::int(2)])), vec![1]);
+        assert!(index.candidates(&f(vec![Term::atom(12), Term::var(0)])).is_empty());
+    }
+
+    #[test]
+    fn stored_variables_match_any_subterm() {
+        let terms = vec![
+            f(vec![Term::var(0), Term::int(1)]),
+            f(vec![f(vec![Term::int(5), Term::int(6)]), Term::int(1)]),
+        ];
+        let index = TermIndex::from_terms(&terms);
+        let query = f(vec![f(vec![Term::int(5), Term::var(3)]), Term::int(1)]);
+        assert_eq!(index.candidates(&query), vec![0, 1]);
+        // Query variable skips the nested compound
+        assert_eq!(index.candidates(&f(vec![Term::var(9), Term::int(1)])), vec![0, 1]);
+    }
+
+    #[test]
+    fn dicts_match_on_keys_and_values() {
+        let d = |a: u32, b: i64| f(vec![Term::dict(vec![(2, Term::int(b)), (1, Term::atom(a))])]);
+        let terms = vec![d(10, 1), d(11, 2), f(vec![Term::dict(vec![(1, Term::atom(10))])])];
+        let index = TermIndex::from_terms(&terms);
+        let query = f(vec![Term::dict(vec![(1, Term::atom(10)), (2, Term::var(0))])]);
+        assert_eq!(index.candidates(&query), vec![0]);
+        assert_eq!(index.candidates(&f(vec![Term::var(0)])), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn remove_entry() {
+        let t = f(vec![Term::int(1)]);
+        let mut index = TermIndex::new();
+        index.insert(&t, 0);
+        assert!(index.remove(&t, 0));
+        assert!(index.candidates(&t).is_empty());
+        assert!(index.is_empty());
+    }
+
+    #[test]
+    fn lists_match_cons_cells_and_nil() {
+        let list = f(vec![Term::list(vec![Term::int(1), Term::int(2)])]);
+        let open = f(vec![Term::Compound(CONS, vec![Term::var(0), Term::var(1)])]);
+        let nil = f(vec![Term::Nil]);
+        let empty = f(vec![Term::list(vec![])]);
+        let index = TermIndex::from_terms(&[list.clone(), open.clone(), nil.clone()]);
+        assert_eq!(index.candidates(&list), vec![0, 1]);
+        assert_eq!(index.candidates(&empty), vec![1, 2]);
+        assert_eq!(index.candidates(&nil), vec![1, 2]);
+        // A cell matches any list; unify then rejects [H|T] against []
+        assert_eq!(index.candidates(&open), vec![0, 1, 2]);
+    }
+
+    #[test]
+    fn rules_are_found_by_head_predicate() {
+        use crate::reasoning::rules::Rule;
+        let rules = vec![
+            Rule::new(f(vec![Term::var(0)]), vec![Term::compound(2, vec![Term::var(0), Term::var(4)])]),
+            Rule::new(Term::compound(2, vec![Term::var(0), Term::var(1)]), vec![]),
+            Rule::new(Term::var(0), vec![]),
+            Rule::new(f(vec![Term::atom(10)]), vec![]),
+        ];
+        let index = RuleIndex::from_rules(&rules);
+        assert_eq!(index.candidates(&f(vec![Term::atom(11)])), vec![0, 2, 3]);
+        assert_eq!(index.candidates(&Term::compound(2, vec![Term::var(0), Term::var(1)])), vec![1, 2]);
+        assert_eq!(index.candidates(&f(vec![Term::var(0), Term::var(1)])), vec![2]);
+        assert_eq!(index.candidates(&Term::var(0)), vec![0, 1, 2, 3]);
+        assert_eq!((0..4).map(|i| index.span(i)).collect::<Vec<_>>(), vec![5, 2, 1, 0]);
+    }
+
+    #[test]
+    fn engine_finds_list_facts_through_the_index() {
+        use crate::reasoning::rules::RuleEngine;
+        let route = 1;
+        let mut engine = RuleEngine::new();
+        engine.add_fact(Term::compound(route, vec![Term::list(vec![Term::int(1), Term::int(2)])]));
+        engine.add_fact(Term::compound(route, vec![Term::Nil]));
+
+        // route([H|T]) finds route([1, 2])
+        let open = Term::compound(route, vec![Term::Compound(CONS, vec![Term::var(0), Term::var(1)])]);
+        let found = engine.query(&open);
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].apply(&Term::var(0)), Term::int(1));
+        assert_eq!(found[0].apply(&Term::var(1)), Term::list(vec![Term::int(2)]));
+
+        // route([]) finds route(Nil)
+        assert_eq!(engine.query(&Term::compound(route, vec![Term::list(vec![])])).len(), 1);
+    }
+}
@@ -0,0 +1,160 @@
+// Library predicates written as ordinary rules.
+//
+// Each function interns the names it needs and returns the clauses; load them
+// with RuleEngine::add_rule (or the RuleEngine::load_* helpers). They rely on
+// the standard builtins (call/N in particular) being registered.
+
+use crate::core::{Term, SymbolTable};
+use super::builtins;
+use super::rules::Rule;
+
+fn v(id: u32) -> Term {
+    Term::var(id)
+}
+
+// member/2, append/3, select/3, permutation/2 plus higher_order_rules().
+// Unlike the member/append builtins these also work on unbound lists, e.g.
+// append(X, Y, [1, 2]) enumerates every split.
+pub fn prelude_rules(syms: &mut SymbolTable) -> Vec<Rule> {
+    let member = syms.intern(builtins::BUILTIN_MEMBER);
+    let append = syms.intern(builtins::BUILTIN_APPEND);
+    let select = syms.intern("select");
+    let permutation = syms.intern("permutation");
+    let nil = || Term::list(Vec::new());
+    let cons = |h: u32, t: u32| Term::cons(v(h), v(t));
+
+    let mut rules = vec![
+        // member(X, [X|_]).
+        Rule::fact(Term::compound(member, vec![v(0), cons(0, 1)])),
+        // member(X, [_|T]) :- member(X, T).
+        Rule::new(
+            Term::compound(member, vec![v(0), cons(1, 2)]),
+            vec![Term::compound(member, vec![v(0), v(2)])],
+        ),
+        // append([], L, L).
+        Rule::fact(Term::compound(append, vec![nil(), v(0), v(0)])),
+        // append([H|T], L, [H|R]) :- append(T, L, R).
+        Rule::new(
+            Term::compound(append, vec![cons(0, 1), v(2), cons(0, 3)]),
+            vec![Term::compound(append, vec![v(1), v(2), v(3)])],
+        ),
+        // select(X, [X|T], T).
+        Rule::fact(Term::compound(select, vec![v(0), cons(0, 1), v(1)])),
+        // select(X, [H|T], [H|R]) :- select(X, T, R).
+        Rule::new(
+            Term::compound(select, vec![v(0), cons(1, 2), cons(1, 3)]),
+            vec![Term::compound(select, vec![v(0), v(2), v(3)])],
+        ),
+        // permutation([], []).
+        Rule::fact(Term::compound(permutation, vec![nil(), nil()])),
+        // permutation(L, [H|T]) :- select(H, L, R), permutation(R, T).
+        Rule::new(
+            Term::compound(permutation, vec![v(0), cons(1, 2)]),
+            vec![
+                Term::compound(select, vec![v(1), v(0), v(3)]),
+                Term::compound(permutation, vec![v(3), v(2)]),
+            ],
+        ),
+    ];
+    rules.extend(higher_order_rules(syms));
+    rules
+}
+
+// maplist/2..4 and foldl/4
+pub fn higher_order_rules(syms: &mut SymbolTable) -> Vec<Rule> {
+    let call = syms.intern(builtins::BUILTIN_CALL);
+    let maplist = syms.intern("maplist");
+    let foldl = syms.intern("foldl");
+    let nil = || Term::list(Vec::new());
+    let cons = |h: u32, t: u32| Term::cons(v(h), v(t));
+
+    vec![
+        // maplist(_, []).
+        Rule::fact(Term::compound(maplist, vec![v(0), nil()])),
+        // maplist(G, [X|Xs]) :- call(G, X), maplist(G, Xs).
+        Rule::new(
+            Term::compound(maplist, vec![v(0), cons(1, 2)]),
+            vec![
+                Term::compound(call, vec![v(0), v(1)]),
+                Term::compound(maplist, vec![v(0), v(2)]),
+            ],
+        ),
+        // maplist(_, [], []).
+        Rule::fact(Term::compound(maplist, vec![v(0), nil(), nil()])),
+        // maplist(G, [X|Xs], [Y|Ys]) :- call(G, X, Y), maplist(G, Xs, Ys).
+        Rule::new(
+            Term::compound(maplist, vec![v(0), cons(1, 2), cons(3, 4)]),
+            vec![
+                Term::compound(call, vec![v(0), v(1), v(3)]),
+                Term::compound(maplist, vec![v(0), v(2), v(4)]),
+            ],
+        ),
+        // maplist(_, [], [], []).
+        Rule::fact(Term::compound(maplist, vec![v(0), nil(), nil(), nil()])),
+        // maplist(G, [X|Xs], [Y|Ys], [Z|Zs]) :- call(G, X, Y, Z), maplist(G, Xs, Ys, Zs).
+        Rule::new(
+            Term::compound(maplist, vec![v(0), cons(1, 2), cons(3, 4), cons(5, 6)]),
+            vec![
+                Term::compound(call, vec![v(0), v(1), v(3), v(5)]),
+                Term::compound(maplist, vec![v(0), v(2), v(4), v(6)]),
+            ],
+        ),
+        // foldl(_, [], V, V).
+        Rule::fact(Term::compound(foldl, vec![v(0), nil(), v(1), v(1)])),
+        // foldl(G, [X|Xs], V0, V) :- call(G, X, V0, V1), foldl(G, Xs, V1, V).
+        Rule::new(
+            Term::compound(foldl, vec![v(0), cons(1, 2), v(3), v(4)]),
+            vec![
+                Term::compound(call, vec![v(0), v(1), v(3), v(5)]),
+                Term::compound(foldl, vec![v(0), v(2), v(5), v(4)]),
+            ],
+        ),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::rules::RuleEngine;
+
+    fn ints(xs: &[i64]) -> Term {
+        Term::list(xs.iter().map(|&x| Term::int(x)).collect())
+    }
+
+    #[test]
+    fn prelude_lists_work_in_every_mode() {
+        let mut syms = SymbolTable::new();
+        let mut engine = RuleEngine::new().with_standard_builtins(&mut syms);
+        engine.load_prelude(&mut syms);
+        let [member, append, permutation] = ["member", "append", "permutation"].map(|n| syms.intern(n));
+        let (x, y) = (v(0), v(1));
+
+        // append(X, Y, [1, 2]) enumerates every split, in order
+        let splits: Vec<(Term, Term)> = engine.query(&Term::compound(append, vec![x.clone(), y.clone(), ints(&[1, 2])]))
+            .iter()
+            .map(|s| (s.apply(&x), s.apply(&y)))
+            .collect();
+        assert_eq!(splits, vec![
+            (ints(&[]), ints(&[1, 2])),
+            (ints(&[1]), ints(&[2])),
+            (ints(&[1, 2]), ints(&[])),
+        ]);
+        let joined = engine.query(&Term::compound(append, vec![ints(&[1]), ints(&[2, 3]), x.clone()]));
+        assert_eq!(joined[0].apply(&x), ints(&[1, 2, 3]));
+
+        let members: Vec<Term> = engine.query(&Term::compound(member, vec![x.clone(), ints(&[4, 5, 6])]))
+            .iter()
+            .map(|s| s.apply(&x))
+            .collect();
+        assert_eq!(members, vec![Term::int(4), Term::int(5), Term::int(6)]);
+        assert!(engine.query(&Term::compound(member, vec![Term::int(7), ints(&[4, 5, 6])])).is_empty());
+
+        let perms: Vec<Term> = engine.query(&Term::compound(permutation, vec![ints(&[1, 2, 3]), x.clone()]))
+            .iter()
+            .map(|s| s.apply(&x))
+            .collect();
+        assert_eq!(perms.len(), 6);
+        assert!(perms.iter().enumerate().all(|(i, p)| !perms[..i].contains(p)));
+        assert!(perms.contains(&ints(&[3, 1, 2])));
+    }
+}
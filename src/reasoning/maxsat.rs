@@ -0,0 +1,176 @@
+// Weighted partial MaxSAT, core-guided in the style of RC2 (OLL).
+//
+// Every soft clause gets an assumption literal: the clause itself when it is
+// a unit, otherwise a fresh b added to the clause as (C or not b). The solver
+// is called with all assumptions of positive weight. An unsatisfiable core
+// K costs at least min weight m of K: the cost grows by m, the weights in K
+// drop by m, and a totalizer over the negations of K counts how many of its
+// literals are violated. Assuming not o2 (at most one violated) costs m;
+// when such a bound shows up in a later core it is relaxed to the next one.
+// The first satisfiable call gives an optimum.
+
+use super::backend::SatBackend;
+use super::cdcl::CdclSolver;
+use super::solver::{Assignment, Clause, Literal, SatProblem};
+use rustc_hash::FxHashMap;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum MaxSatResult {
+    // `cost` is the total weight of the falsified soft clauses
+    Optimum { assignment: Assignment, cost: u64 },
+    // The hard clauses are unsatisfiable
+    Unsat,
+}
+
+// Outputs of a totalizer: outputs[j] is implied once j + 1 inputs are true
+fn totalizer<B: SatBackend>(inputs: &[Literal], next_var: &mut Literal, solver: &mut B) -> Vec<Literal> {
+    if inputs.len() == 1 {
+        return inputs.to_vec();
+    }
+    let (left, right) = inputs.split_at(inputs.len() / 2);
+    let left = totalizer(left, next_var, solver);
+    let right = totalizer(right, next_var, solver);
+    let outputs: Vec<Literal> = (0..inputs.len()).map(|i| *next_var + i as Literal).collect();
+    *next_var += inputs.len() as Literal;
+    for a in 0..=left.len() {
+        for b in 0..=right.len() {
+            if a + b == 0 {
+                continue;
+            }
+            let mut clause: Clause = Vec::with_capacity(3);
+            if a > 0 {
+                clause.push(-left[a - 1]);
+            }
+            if b > 0 {
+                clause.push(-right[b - 1]);
+            }
+            clause.push(outputs[a + b - 1]);
+            solver.add_clause(&clause);
+        }
+    }
+    outputs
+}
+
+pub fn solve_maxsat(problem: &SatProblem) -> MaxSatResult {
+    let mut solver = CdclSolver::with_config(problem.num_vars(), problem.config().clone());
+    solve_maxsat_with(problem, &mut solver)
+}
+
+// solve_maxsat on the given backend, which must not hold any clauses yet
+pub fn solve_maxsat_with<B: SatBackend>(problem: &SatProblem, solver: &mut B) -> MaxSatResult {
+    problem.load_into(solver);
+    let soft = problem.soft_clauses();
+    let max_var = soft.iter().flat_map(|(c, _)| c).map(|l| l.unsigned_abs()).max().unwrap_or(0);
+    let mut next_var = problem.num_vars().max(max_var) as Literal + 1;
+
+    let mut weights: FxHashMap<Literal, u64> = FxHashMap::default();
+    for (clause, weight) in soft {
+        if *weight == 0 {
+            continue;
+        }
+        let assumption = match clause.as_slice() {
+            [unit] => *unit,
+            _ => {
+                let b = next_var;
+                next_var += 1;
+                let mut relaxed = clause.clone();
+                relaxed.push(-b);
+                solver.add_clause(&relaxed);
+                b
+            }
+        };
+        *weights.entry(assumption).or_default() += weight;
+    }
+    // Bound assumption not o_j -> (totalizer outputs, j)
+    let mut bounds: FxHashMap<Literal, (Vec<Literal>, usize)> = FxHashMap::default();
+
+    loop {
+        let mut assumptions: Vec<Literal> = weights.iter().filter(|(_, &w)| w > 0).map(|(&l, _)| l).collect();
+        assumptions.sort_unstable();
+        if solver.solve(&assumptions) {
+            let mut assignment = solver.model().cloned().unwrap_or_default();
+            assignment.retain(|&v, _| v <= problem.num_vars());
+            let cost = soft.iter()
+                .filter(|(c, _)| !c.iter().any(|&l| assignment.get(&l.unsigned_abs()) == Some(&(l > 0))))
+                .map(|(_, w)| w)
+                .sum();
+            return MaxSatResult::Optimum { assignment, cost };
+        }
+        let core = solver.core().to_vec();
+        if core.is_empty() {
+            return MaxSatResult::Unsat;
+        }
+        let min = core.iter().map(|l| weights[l]).min().unwrap_or(0);
+        for l in &core {
+            if let Some(w) = weights.get_mut(l) {
+                *w -= min;
+            }
+            // Relax a violated bound: at most j + 1 instead of at most j
+            if let Some((outputs, j)) = bounds.get(l).cloned() {
+                if j < outputs.len() {
+                    let next = -outputs[j];
+                    *weights.entry(next).or_default() += min;
+                    bounds.insert(next, (outputs, j + 1));
+                }
+            }
+        }
+        if core.len() > 1 {
+            let violated: Vec<Literal> = core.iter().map(|l| -l).collect();
+            let outputs = totalizer(&violated, &mut next_var, solver);
+            let bound = -outputs[1];
+            *weights.entry(bound).or_default() += min;
+            bounds.insert(bound, (outputs, 2));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn optimum_cost(problem: &SatProblem) -> Option<u64> {
+        match problem.solve_optimal() {
+            MaxSatResult::Optimum { cost, .. } => Some(cost),
+            MaxSatResult::Unsat => None,
+        }
+    }
+
+    #[test]
+    fn prefers_heavier_soft_clauses() {
+        // At most one of x1, x2, x3; wanting x2 (5) beats x1 (2) plus x3 (2)
+        let mut problem = SatProblem::new(3);
+        problem.add_clause(vec![-1, -2]);
+        problem.add_clause(vec![-1, -3]);
+        problem.add_clause(vec![-2, -3]);
+        problem.add_soft_clause(vec![1], 2);
+        problem.add_soft_clause(vec![2], 5);
+        problem.add_soft_clause(vec![3], 2);
+        match problem.solve_optimal() {
+            MaxSatResult::Optimum { assignment, cost } => {
+                assert_eq!(cost, 4);
+                assert!(assignment[&2]);
+            }
+            MaxSatResult::Unsat => panic!("hard clauses are satisfiable"),
+        }
+    }
+
+    #[test]
+    fn cardinality_cores() {
+        // Five soft units, hard clauses allow at most two of them
+        let mut problem = SatProblem::new(5);
+        for i in 1..=5 {
+            for j in i + 1..=5 {
+                for k in j + 1..=5 {
+                    problem.add_clause(vec![-i, -j, -k]);
+                }
+            }
+            problem.add_soft_clause(vec![i], 1);
+        }
+        problem.add_soft_clause(vec![-1, -2], 10);
+        assert_eq!(optimum_cost(&problem), Some(3));
+
+        problem.add_clause(vec![1]);
+        problem.add_clause(vec![-1]);
+        assert_eq!(optimum_cost(&problem), None);
+    }
+}
@@ -1,5 +1,26 @@
 pub mod unifier;
 pub mod solver;
+pub mod cdcl;
+pub mod backend;
+pub mod maxsat;
+pub mod tseitin;
+pub mod planner;
 pub mod rules;
 pub mod search;
 pub mod builtins;
+pub mod asp;
+pub mod modes;
+pub mod partial_eval;
+pub mod index;
+pub mod tms;
+pub mod defeasible;
+pub mod certainty;
+pub mod temporal;
+pub mod signatures;
+pub mod library;
+pub mod why_not;
+pub mod debugger;
+pub mod persist;
+pub mod chr;
+pub mod profile;
+pub mod consult;
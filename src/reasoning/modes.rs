@@ -0,0 +1,251 @@
+// Mode and determinism declarations (`:- mode parent(+, -) is det.`).
+//
+// A declaration fixes, per argument, whether the caller must supply it (+),
+// the predicate produces it (-) or either (?), and how many solutions a call
+// may have. Rules are checked statically by a left-to-right dataflow over
+// their bodies; the engine can additionally assert modes and solution counts
+// at call time (see RuleEngine::with_mode_checks).
+
+use crate::core::{Term, Sym};
+use super::rules::Rule;
+use super::builtins::BuiltinRegistry;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    In,
+    Out,
+    Any,
+}
+
+impl Mode {
+    pub fn parse(c: char) -> Option<Self> {
+        match c {
+            '+' => Some(Mode::In),
+            '-' => Some(Mode::Out),
+            '?' => Some(Mode::Any),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Determinism {
+    Det,     // exactly one solution
+    Semidet, // at most one
+    Multi,   // at least one
+    Nondet,  // any number
+}
+
+impl Determinism {
+    pub fn allows(self, solutions: usize) -> bool {
+        match self {
+            Determinism::Det => solutions == 1,
+            Determinism::Semidet => solutions <= 1,
+            Determinism::Multi => solutions >= 1,
+            Determinism::Nondet => true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModeDecl {
+    pub functor: Sym,
+    pub modes: Vec<Mode>,
+    pub det: Determinism,
+}
+
+impl ModeDecl {
+    pub fn new(functor: Sym, modes: Vec<Mode>, det: Determinism) -> Self {
+        Self { functor, modes, det }
+    }
+
+    // "+,-,?" (whitespace ignored)
+    pub fn parse(functor: Sym, spec: &str, det: Determinism) -> Option<Self> {
+        let modes = spec.split(',')
+            .map(|m| m.trim())
+            .filter(|m| !m.is_empty())
+            .map(|m| {
+                let mut chars = m.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Mode::parse(c),
+                    _ => None,
+                }
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self { functor, modes, det })
+    }
+
+    pub fn arity(&self) -> usize {
+        self.modes.len()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModeViolation {
+    // A rule head disagrees with the declared arity
+    ArityMismatch { rule_id: usize, functor: Sym, expected: usize, found: usize },
+    // A `+` argument may be unbound at the call site
+    UnboundInput { rule_id: Option<usize>, functor: Sym, arg: usize },
+    // A `-` argument of the head is never bound by the body
+    UnboundOutput { rule_id: usize, functor: Sym, arg: usize },
+    // A call produced a number of solutions its determinism forbids
+    Determinism { functor: Sym, declared: Determinism, solutions: usize },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ModeTable {
+    decls: FxHashMap<Sym, ModeDecl>,
+}
+
+impl ModeTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn declare(&mut self, decl: ModeDecl) {
+        self.decls.insert(decl.functor, decl);
+    }
+
+    pub fn get(&self, functor: Sym) -> Option<&ModeDecl> {
+        self.decls.get(&functor)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.decls.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.decls.len()
+    }
+
+    // `+` arguments of a call that are not instantiated
+    pub fn check_call(&self, goal: &Term) -> Vec<ModeViolation> {
+        let (f, args) = match goal {
+            Term::Compound(f, args) => (*f, args),
+            _ => return Vec::new(),
+        };
+        let decl = match self.decls.get(&f) {
+            Some(d) if d.arity() == args.len() => d,
+            _ => return Vec::new(),
+        };
+        decl.modes.iter().zip(args.iter()).enumerate()
+            .filter(|(_, (m, a))| **m == Mode::In && matches!(a, Term::Var(_)))
+            .map(|(i, _)| ModeViolation::UnboundInput { rule_id: None, functor: f, arg: i })
+            .collect()
+    }
+}
+
+// Static check of every rule whose head or body goals carry a declaration
+pub fn check_rules(rules: &[Rule], table: &ModeTable, builtins: &BuiltinRegistry) -> Vec<ModeViolation> {
+    let mut violations = Vec::new();
+    for rule in rules {
+        check_rule(rule, table, builtins, &mut violations);
+    }
+    violations
+}
+
+fn check_rule(rule: &Rule, table: &ModeTable, builtins: &BuiltinRegistry, out: &mut Vec<ModeViolation>) {
+    let mut bound: FxHashSet<Sym> = FxHashSet::default();
+    let head_decl = match &rule.head {
+        Term::Compound(f, args) => table.get(*f).map(|d| (d, args)),
+        _ => None,
+    };
+
+    if let Some((decl, args)) = head_decl {
+        if decl.arity() != args.len() {
+            out.push(ModeViolation::ArityMismatch {
+                rule_id: rule.id,
+                functor: decl.functor,
+                expected: decl.arity(),
+                found: args.len(),
+            });
+            return;
+        }
+        for (mode, arg) in decl.modes.iter().zip(args.iter()) {
+            if *mode == Mode::In {
+                bound.extend(arg.vars());
+            }
+        }
+    }
+
+    for goal in &rule.body {
+        let (f, args) = match goal {
+            Term::Compound(f, args) => (*f, args),
+            _ => continue,
+        };
+        match table.get(f) {
+            Some(decl) if decl.arity() == args.len() && !builtins.is_builtin(f) => {
+                for (i, (mode, arg)) in decl.modes.iter().zip(args.iter()).enumerate() {
+                    if *mode == Mode::In && arg.vars().iter().any(|v| !bound.contains(v)) {
+                        out.push(ModeViolation::UnboundInput { rule_id: Some(rule.id), functor: f, arg: i });
+                    }
+                }
+                for arg in args {
+                    bound.extend(arg.vars());
+                }
+            }
+            // Undeclared goals and builtins may bind anything they mention
+            _ => bound.extend(goal.vars()),
+        }
+    }
+
+    if let Some((decl, args)) = head_decl {
+        for (i, (mode, arg)) in decl.modes.iter().zip(args.iter()).enumerate() {
+            if *mode == Mode::Out && arg.vars().iter().any(|v| !bound.contains(v)) {
+                out.push(ModeViolation::UnboundOutput { rule_id: rule.id, functor: decl.functor, arg: i });
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_modes() {
+        let decl = ModeDecl::parse(1, "+, -, ?", Determinism::Det).unwrap();
+        assert_eq!(decl.modes, vec![Mode::In, Mode::Out, Mode::Any]);
+        assert!(ModeDecl::parse(1, "+x", Determinism::Det).is_none());
+    }
+
+    #[test]
+    fn determinism_counts() {
+        assert!(Determinism::Det.allows(1));
+        assert!(!Determinism::Det.allows(2));
+        assert!(Determinism::Semidet.allows(0));
+        assert!(!Determinism::Multi.allows(0));
+        assert!(Determinism::Nondet.allows(7));
+    }
+
+    #[test]
+    fn output_never_bound() {
+        let (p, q) = (1, 2);
+        let mut table = ModeTable::new();
+        table.declare(ModeDecl::parse(p, "+,-", Determinism::Det).unwrap());
+        table.declare(ModeDecl::parse(q, "+", Determinism::Semidet).unwrap());
+        // p(X, Y) :- q(X).   -- Y is never produced
+        let rule = Rule::new(
+            Term::compound(p, vec![Term::var(0), Term::var(1)]),
+            vec![Term::compound(q, vec![Term::var(0)])],
+        );
+        let v = check_rules(&[rule], &table, &BuiltinRegistry::new());
+        assert_eq!(v, vec![ModeViolation::UnboundOutput { rule_id: 0, functor: p, arg: 1 }]);
+    }
+
+    #[test]
+    fn input_unbound_in_body() {
+        let (p, q) = (1, 2);
+        let mut table = ModeTable::new();
+        table.declare(ModeDecl::parse(q, "+", Determinism::Semidet).unwrap());
+        // p(X) :- q(Y).
+        let rule = Rule::new(
+            Term::compound(p, vec![Term::var(0)]),
+            vec![Term::compound(q, vec![Term::var(1)])],
+        );
+        let v = check_rules(&[rule], &table, &BuiltinRegistry::new());
+        assert_eq!(v.len(), 1);
+        assert!(matches!(v[0], ModeViolation::UnboundInput { arg: 0, .. }));
+    }
+}
@@ -0,0 +1,328 @@
+// Partial evaluation: unfold calls to non-recursive, rule-defined predicates
+// into the bodies of their callers, and specialize a rule base for a goal
+// pattern. Ground builtin tests are decided at specialization time.
+//
+// Only predicates defined purely by rules, outside any call-graph cycle and
+// without cut in their clauses are unfolded; everything else (facts,
+// recursive predicates, builtins, negated goals) stays a residual call.
+
+use crate::core::{Term, Sym};
+use super::unifier::{Substitution, unify, rename_vars};
+use super::rules::Rule;
+use super::builtins::{self, BuiltinRegistry, BuiltinResult, eval_builtin};
+use rustc_hash::{FxHashMap, FxHashSet};
+
+#[derive(Debug, Clone)]
+pub struct PartialEvalConfig {
+    // Maximum unfolding passes over the rule base
+    pub max_passes: usize,
+    // Give up unfolding a rule once it expands into more clauses than this
+    pub max_clauses_per_rule: usize,
+}
+
+impl Default for PartialEvalConfig {
+    fn default() -> Self {
+        Self { max_passes: 8, max_clauses_per_rule: 64 }
+    }
+}
+
+pub struct PartialEvaluator<'a> {
+    rules: &'a [Rule],
+    facts: &'a [Term],
+    builtins: &'a BuiltinRegistry,
+    config: PartialEvalConfig,
+    unfoldable: FxHashSet<Sym>,
+    next_var: Sym,
+}
+
+impl<'a> PartialEvaluator<'a> {
+    pub fn new(rules: &'a [Rule], facts: &'a [Term], builtins: &'a BuiltinRegistry) -> Self {
+        let max_var = rules.iter()
+            .flat_map(|r| std::iter::once(&r.head).chain(r.body.iter()))
+            .flat_map(|t| t.vars())
+            .max()
+            .unwrap_or(0);
+        let mut pe = Self {
+            rules,
+            facts,
+            builtins,
+            config: PartialEvalConfig::default(),
+            unfoldable: FxHashSet::default(),
+            next_var: max_var.max(1_000_000) + 1,
+        };
+        pe.unfoldable = pe.find_unfoldable();
+        pe
+    }
+
+    pub fn with_config(mut self, config: PartialEvalConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn is_unfoldable(&self, functor: Sym) -> bool {
+        self.unfoldable.contains(&functor)
+    }
+
+    // Unfold every rule of the program
+    pub fn unfold_all(&mut self) -> Vec<Rule> {
+        let rules = self.rules;
+        let mut out = Vec::new();
+        for rule in rules {
+            out.extend(self.unfold_rule(rule.clone()));
+        }
+        renumber(out)
+    }
+
+    // Residual program for calls matching `goal`: every returned rule's head
+    // is an instance of `goal`
+    pub fn specialize(&mut self, goal: &Term) -> Vec<Rule> {
+        let rules = self.rules;
+        let mut out = Vec::new();
+        for rule in rules {
+            let renamed = self.rename(rule);
+            if let Ok(sub) = unify(goal, &renamed.head, &Substitution::new()) {
+                let instance = Rule::new(
+                    sub.apply(&renamed.head),
+                    renamed.body.iter().map(|t| sub.apply(t)).collect(),
+                ).with_id(rule.id);
+                out.extend(self.unfold_rule(instance));
+            }
+        }
+        renumber(out)
+    }
+
+    fn unfold_rule(&mut self, rule: Rule) -> Vec<Rule> {
+        let mut pending = vec![rule];
+        for _ in 0..self.config.max_passes {
+            let mut next = Vec::new();
+            let mut changed = false;
+            for r in pending {
+                match self.unfold_once(&r) {
+                    Some(expanded) => {
+                        changed = true;
+                        next.extend(expanded);
+                    }
+                    None => next.push(r),
+                }
+                if next.len() > self.config.max_clauses_per_rule {
+                    return next;
+                }
+            }
+            pending = next;
+            if !changed {
+                break;
+            }
+        }
+        pending
+    }
+
+    // Unfold the first reducible body goal. None when nothing can be reduced.
+    // Some(vec![]) means the rule can never succeed.
+    fn unfold_once(&mut self, rule: &Rule) -> Option<Vec<Rule>> {
+        for (i, goal) in rule.body.iter().enumerate() {
+            let (f, args) = match goal {
+                Term::Compound(f, args) => (*f, args),
+                _ => continue,
+            };
+
+            if self.builtins.is_builtin(f) {
+                match self.decide_builtin(f, args) {
+                    Some(true) => {
+                        let mut body = rule.body.clone();
+                        body.remove(i);
+                        return Some(vec![Rule::new(rule.head.clone(), body).with_id(rule.id)]);
+                    }
+                    Some(false) => return Some(Vec::new()),
+                    None => continue,
+                }
+            }
+
+            if !self.unfoldable.contains(&f) {
+                continue;
+            }
+
+            let clauses: Vec<Rule> = self.rules.iter()
+                .filter(|r| head_functor(&r.head) == Some(f))
+                .cloned()
+                .collect();
+            let mut expanded = Vec::new();
+            for clause in &clauses {
+                let renamed = self.rename(clause);
+                if let Ok(sub) = unify(goal, &renamed.head, &Substitution::new()) {
+                    let mut body: Vec<Term> = rule.body[..i].iter().map(|t| sub.apply(t)).collect();
+                    body.extend(renamed.body.iter().map(|t| sub.apply(t)));
+                    body.extend(rule.body[i + 1..].iter().map(|t| sub.apply(t)));
+                    expanded.push(Rule::new(sub.apply(&rule.head), body).with_id(rule.id));
+                }
+            }
+            return Some(expanded);
+        }
+        None
+    }
+
+    // Ground, deterministic builtin tests can be decided now
+    fn decide_builtin(&self, f: Sym, args: &[Term]) -> Option<bool> {
+        if !args.iter().all(|a| a.is_ground()) {
+            return None;
+        }
+        match self.builtins.name_of(f)? {
+            builtins::BUILTIN_CUT | builtins::BUILTIN_WRITE | builtins::BUILTIN_NL => return None,
+            _ => {}
+        }
+        match eval_builtin(f, args, &Substitution::new(), self.builtins)? {
+            BuiltinResult::Success(_) => Some(true),
+            BuiltinResult::Fail => Some(false),
+            _ => None,
+        }
+    }
+
+    fn rename(&mut self, rule: &Rule) -> Rule {
+        let offset = self.next_var;
+        let span = std::iter::once(&rule.head).chain(rule.body.iter())
+            .flat_map(|t| t.vars())
+            .max()
+            .map_or(1, |m| m + 1);
+        self.next_var += span;
+        Rule::new(
+            rename_vars(&rule.head, offset),
+            rule.body.iter().map(|t| rename_vars(t, offset)).collect(),
+        ).with_id(rule.id)
+    }
+
+    fn find_unfoldable(&self) -> FxHashSet<Sym> {
+        let fact_functors: FxHashSet<Sym> = self.facts.iter().filter_map(head_functor).collect();
+        let mut calls: FxHashMap<Sym, FxHashSet<Sym>> = FxHashMap::default();
+        let mut has_cut: FxHashSet<Sym> = FxHashSet::default();
+        for rule in self.rules {
+            let f = match head_functor(&rule.head) {
+                Some(f) => f,
+                None => continue,
+            };
+            let callees = calls.entry(f).or_default();
+            for goal in &rule.body {
+                if let Some(g) = head_functor(goal) {
+                    if self.builtins.name_of(g) == Some(builtins::BUILTIN_CUT) {
+                        has_cut.insert(f);
+                    }
+                    callees.insert(g);
+                }
+            }
+        }
+
+        calls.keys()
+            .copied()
+            .filter(|f| !fact_functors.contains(f) && !has_cut.contains(f))
+            .filter(|f| !self.builtins.is_builtin(*f))
+            .filter(|&f| !reaches(&calls, f, f))
+            .collect()
+    }
+}
+
+fn head_functor(t: &Term) -> Option<Sym> {
+    match t {
+        Term::Compound(f, _) | Term::Atom(f) => Some(*f),
+        _ => None,
+    }
+}
+
+// Is `target` reachable from the callees of `from`?
+fn reaches(calls: &FxHashMap<Sym, FxHashSet<Sym>>, from: Sym, target: Sym) -> bool {
+    let mut stack: Vec<Sym> = calls.get(&from).map(|c| c.iter().copied().collect()).unwrap_or_default();
+    let mut visited = FxHashSet::default();
+    while let Some(f) = stack.pop() {
+        if f == target {
+            return true;
+        }
+        if visited.insert(f) {
+            if let Some(c) = calls.get(&f) {
+                stack.extend(c.iter().copied());
+            }
+        }
+    }
+    false
+}
+
+// Fresh ids, and variables compacted back to 0.. so the engine's per-call
+// renaming offsets stay valid
+fn renumber(rules: Vec<Rule>) -> Vec<Rule> {
+    rules.into_iter().enumerate().map(|(i, r)| {
+        let mut map: FxHashMap<Sym, Sym> = FxHashMap::default();
+        for t in std::iter::once(&r.head).chain(r.body.iter()) {
+            for v in t.vars() {
+                let next = map.len() as Sym;
+                map.entry(v).or_insert(next);
+            }
+        }
+        Rule::new(
+            map_vars(&r.head, &map),
+            r.body.iter().map(|t| map_vars(t, &map)).collect(),
+        ).with_id(i)
+    }).collect()
+}
+
+fn map_vars(term: &Term, map: &FxHashMap<Sym, Sym>) -> Term {
+    match term {
+        Term::Var(v) => Term::Var(map.get(v).copied().unwrap_or(*v)),
+        Term::Compound(f, args) => Term::Compound(*f, args.iter().map(|a| map_vars(a, map)).collect()),
+        Term::List(items) => Term::List(items.iter().map(|a| map_vars(a, map)).collect()),
+        Term::Dict(pairs) => Term::Dict(pairs.iter().map(|(k, v)| (*k, map_vars(v, map))).collect()),
+        other => other.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unfolds_non_recursive_call() {
+        let (p, q, r) = (1, 2, 3);
+        // p(X) :- q(X).   q(Y) :- r(Y).   r is extensional
+        let rules = vec![
+            Rule::new(Term::compound(p, vec![Term::var(0)]), vec![Term::compound(q, vec![Term::var(0)])]),
+            Rule::new(Term::compound(q, vec![Term::var(0)]), vec![Term::compound(r, vec![Term::var(0)])]),
+        ];
+        let facts = vec![Term::compound(r, vec![Term::int(1)])];
+        let builtins = BuiltinRegistry::new();
+        let mut pe = PartialEvaluator::new(&rules, &facts, &builtins);
+        let out = pe.unfold_all();
+        assert_eq!(out.len(), 2);
+        assert_eq!(head_functor(&out[0].body[0]), Some(r));
+    }
+
+    #[test]
+    fn recursive_predicates_stay_residual() {
+        let (anc, par) = (1, 2);
+        let rules = vec![
+            Rule::new(Term::compound(anc, vec![Term::var(0), Term::var(1)]),
+                vec![Term::compound(par, vec![Term::var(0), Term::var(1)])]),
+            Rule::new(Term::compound(anc, vec![Term::var(0), Term::var(2)]),
+                vec![Term::compound(par, vec![Term::var(0), Term::var(1)]),
+                     Term::compound(anc, vec![Term::var(1), Term::var(2)])]),
+        ];
+        let builtins = BuiltinRegistry::new();
+        let pe = PartialEvaluator::new(&rules, &[], &builtins);
+        assert!(!pe.is_unfoldable(anc));
+    }
+
+    #[test]
+    fn specialize_decides_ground_tests() {
+        let (p, gt) = (1, 2);
+        let mut builtins = BuiltinRegistry::new();
+        builtins.register(builtins::BUILTIN_GT, gt);
+        // p(X, big) :- X > 10.   p(X, small) :- 10 > X.
+        let big = Term::atom(10);
+        let small = Term::atom(11);
+        let rules = vec![
+            Rule::new(Term::compound(p, vec![Term::var(0), big.clone()]),
+                vec![Term::compound(gt, vec![Term::var(0), Term::int(10)])]),
+            Rule::new(Term::compound(p, vec![Term::var(0), small]),
+                vec![Term::compound(gt, vec![Term::int(10), Term::var(0)])]),
+        ];
+        let mut pe = PartialEvaluator::new(&rules, &[], &builtins);
+        let out = pe.specialize(&Term::compound(p, vec![Term::int(42), Term::var(5)]));
+        assert_eq!(out.len(), 1);
+        assert!(out[0].is_fact());
+        assert_eq!(out[0].head, Term::compound(p, vec![Term::int(42), big]));
+    }
+}
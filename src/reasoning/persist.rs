@@ -0,0 +1,271 @@
+// Saving and reloading a RuleEngine.
+//
+// EngineSnapshot captures the clause store and the engine configuration that
+// is plain data: rules, facts, builtin name bindings, tabled functors, the
+// NAF/temporal symbols, limits and globals. Custom builtin closures, mode and
+// signature declarations, the TMS and answer tables are not saved; register
+// closures again after loading. Symbols are stored as ids, so the
+// SymbolTable they were interned in must be persisted alongside.
+//
+// Two encodings: JSON through serde, and the compact KOLS format of
+// memory::binary, where every section is a list of terms.
+//
+// EngineCheckpoint is the in-memory counterpart for what-if reasoning: it
+// shares the clause store with the engine (Arc, copy-on-write), so taking one
+// is O(1) and restoring it discards every assert/retract made since.
+
+use crate::core::{Term, Sym};
+use crate::memory::binary::{BinaryWriter, BinaryReader};
+use super::rules::Rule;
+use super::index::{TermIndex, RuleIndex};
+use super::tms::Tms;
+use rustc_hash::FxHashMap;
+use serde::{Serialize, Deserialize};
+use std::sync::Arc;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EngineSnapshot {
+    pub rules: Vec<Rule>,
+    pub facts: Vec<Term>,
+    pub builtins: Vec<(String, Sym)>,
+    pub tabling_enabled: bool,
+    pub tabled_functors: Vec<Sym>,
+    pub not_sym: Option<Sym>,
+    pub naf_sym: Option<Sym>,
+    pub holds_sym: Option<Sym>,
+    pub max_depth: usize,
+    pub var_counter: Sym,
+    pub tick: u64,
+    pub globals: Vec<(Sym, Term)>,
+}
+
+// See RuleEngine::snapshot and RuleEngine::restore
+#[derive(Debug, Clone)]
+pub struct EngineCheckpoint {
+    pub(crate) rules: Arc<Vec<Rule>>,
+    pub(crate) rule_index: Arc<RuleIndex>,
+    pub(crate) facts: Arc<Vec<Term>>,
+    pub(crate) fact_index: Arc<TermIndex>,
+    pub(crate) tms: Option<Tms>,
+    pub(crate) tick: u64,
+    pub(crate) globals: FxHashMap<Sym, Term>,
+}
+
+impl EngineCheckpoint {
+    pub fn num_rules(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn num_facts(&self) -> usize {
+        self.facts.len()
+    }
+}
+
+fn opt_sym(s: Option<Sym>) -> Term {
+    s.map(Term::Atom).unwrap_or(Term::Nil)
+}
+
+fn read_opt_sym(t: &Term) -> Option<Option<Sym>> {
+    match t {
+        Term::Atom(s) => Some(Some(*s)),
+        Term::Nil => Some(None),
+        _ => None,
+    }
+}
+
+fn int(t: &Term) -> Option<i64> {
+    match t {
+        Term::Int(n) => Some(*n),
+        _ => None,
+    }
+}
+
+fn atom(t: &Term) -> Option<Sym> {
+    match t {
+        Term::Atom(s) => Some(*s),
+        _ => None,
+    }
+}
+
+impl EngineSnapshot {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+
+    pub fn from_json(json: &str) -> Option<Self> {
+        serde_json::from_str(json).ok()
+    }
+
+    // Sections, in order: settings, facts, rules as [Head, Body, Id],
+    // builtins as [Name, Sym], tabled functors, globals as [Key, Value]
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut w = BinaryWriter::new();
+        w.write_header();
+        w.write_terms(&[
+            Term::Bool(self.tabling_enabled),
+            opt_sym(self.not_sym),
+            opt_sym(self.naf_sym),
+            opt_sym(self.holds_sym),
+            Term::Int(self.max_depth as i64),
+            Term::Int(self.var_counter as i64),
+            Term::Int(self.tick as i64),
+        ]);
+        w.write_terms(&self.facts);
+        let rules: Vec<Term> = self.rules.iter()
+            .map(|r| Term::List(vec![r.head.clone(), Term::List(r.body.clone()), Term::Int(r.id as i64)]))
+            .collect();
+        w.write_terms(&rules);
+        let builtins: Vec<Term> = self.builtins.iter()
+            .map(|(name, sym)| Term::List(vec![Term::Str(name.as_str().into()), Term::Atom(*sym)]))
+            .collect();
+        w.write_terms(&builtins);
+        let tabled: Vec<Term> = self.tabled_functors.iter().map(|f| Term::Atom(*f)).collect();
+        w.write_terms(&tabled);
+        let globals: Vec<Term> = self.globals.iter()
+            .map(|(k, v)| Term::List(vec![Term::Atom(*k), v.clone()]))
+            .collect();
+        w.write_terms(&globals);
+        w.into_bytes()
+    }
+
+    pub fn from_binary(data: &[u8]) -> Option<Self> {
+        let mut r = BinaryReader::new(data);
+        r.read_header()?;
+        let settings = r.read_terms()?;
+        let [tabling, not_sym, naf_sym, holds_sym, max_depth, var_counter, tick] = settings.as_slice() else {
+            return None;
+        };
+        let facts = r.read_terms()?;
+        let rules = r.read_terms()?.into_iter()
+            .map(|t| match t {
+                Term::List(mut parts) if parts.len() == 3 => {
+                    let id = int(&parts[2])? as usize;
+                    let body = match parts.remove(1) {
+                        Term::List(body) => body,
+                        Term::Nil => Vec::new(),
+                        _ => return None,
+                    };
+                    Some(Rule { head: parts.remove(0), body, id })
+                }
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let builtins = r.read_terms()?.iter()
+            .map(|t| match t {
+                Term::List(p) if p.len() == 2 => match &p[0] {
+                    Term::Str(name) => Some((name.to_string(), atom(&p[1])?)),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let tabled_functors = r.read_terms()?.iter().map(atom).collect::<Option<Vec<_>>>()?;
+        let globals = r.read_terms()?.into_iter()
+            .map(|t| match t {
+                Term::List(mut p) if p.len() == 2 => Some((atom(&p[0])?, p.pop()?)),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self {
+            rules,
+            facts,
+            builtins,
+            tabling_enabled: matches!(tabling, Term::Bool(true)),
+            tabled_functors,
+            not_sym: read_opt_sym(not_sym)?,
+            naf_sym: read_opt_sym(naf_sym)?,
+            holds_sym: read_opt_sym(holds_sym)?,
+            max_depth: int(max_depth)? as usize,
+            var_counter: int(var_counter)? as Sym,
+            tick: int(tick)? as u64,
+            globals,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot() -> EngineSnapshot {
+        EngineSnapshot {
+            rules: vec![
+                Rule::new(Term::compound(1, vec![Term::var(0)]), vec![Term::compound(2, vec![Term::var(0)])]),
+                Rule::fact(Term::compound(1, vec![Term::list(vec![Term::int(1), Term::Str("x".into())])])),
+            ],
+            facts: vec![Term::compound(2, vec![Term::atom(7)])],
+            builtins: vec![("is".to_string(), 3)],
+            tabling_enabled: true,
+            tabled_functors: vec![1],
+            not_sym: Some(4),
+            naf_sym: None,
+            holds_sym: None,
+            max_depth: 32,
+            var_counter: 12345,
+            tick: 9,
+            globals: vec![(5, Term::float(0.5))],
+        }
+    }
+
+    #[test]
+    fn binary_round_trip() {
+        let snap = snapshot();
+        assert_eq!(EngineSnapshot::from_binary(&snap.to_binary()), Some(snap));
+    }
+
+    #[test]
+    fn json_round_trip() {
+        let snap = snapshot();
+        assert_eq!(EngineSnapshot::from_json(&snap.to_json()), Some(snap));
+    }
+
+    #[test]
+    fn rejects_truncated_data() {
+        let bytes = snapshot().to_binary();
+        assert!(EngineSnapshot::from_binary(&bytes[..bytes.len() - 3]).is_none());
+    }
+
+    #[test]
+    fn engine_resumes_from_snapshot() {
+        use crate::core::SymbolTable;
+        use crate::reasoning::rules::RuleEngine;
+        let mut syms = SymbolTable::new();
+        let (edge, path) = (syms.intern("edge"), syms.intern("path"));
+        let mut engine = RuleEngine::new().with_standard_builtins(&mut syms);
+        engine.add_fact(Term::compound(edge, vec![Term::atom(1), Term::atom(2)]));
+        engine.add_fact(Term::compound(edge, vec![Term::atom(2), Term::atom(3)]));
+        engine.add_rule(Rule::new(
+            Term::compound(path, vec![Term::var(0), Term::var(1)]),
+            vec![Term::compound(edge, vec![Term::var(0), Term::var(1)])],
+        ));
+        engine.add_rule(Rule::new(
+            Term::compound(path, vec![Term::var(0), Term::var(2)]),
+            vec![
+                Term::compound(edge, vec![Term::var(0), Term::var(1)]),
+                Term::compound(path, vec![Term::var(1), Term::var(2)]),
+            ],
+        ));
+        let query = Term::compound(path, vec![Term::atom(1), Term::var(9)]);
+        let expected = engine.query(&query).len();
+
+        let mut restored = RuleEngine::load_binary(&engine.save_binary()).unwrap();
+        assert_eq!(restored.query(&query).len(), expected);
+        assert!(restored.builtins().sym_of("is").is_some());
+    }
+
+    #[test]
+    fn restore_discards_changes() {
+        use crate::reasoning::rules::RuleEngine;
+        let mut engine = RuleEngine::new();
+        engine.add_fact(Term::compound(1, vec![Term::atom(10)]));
+        let checkpoint = engine.snapshot();
+        engine.add_fact(Term::compound(1, vec![Term::atom(11)]));
+        engine.add_rule(Rule::fact(Term::compound(2, vec![Term::atom(12)])));
+        engine.retract(&Term::compound(1, vec![Term::atom(10)]));
+        assert_eq!(checkpoint.num_facts(), 1);
+
+        engine.restore(&checkpoint);
+        assert_eq!((engine.num_facts(), engine.num_rules()), (1, 0));
+        assert_eq!(engine.query(&Term::compound(1, vec![Term::var(0)])).len(), 1);
+    }
+}
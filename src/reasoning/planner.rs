@@ -0,0 +1,347 @@
+// Bounded-horizon planning as satisfiability (SATPLAN style).
+//
+// An action schema is a name term with preconditions, add and delete lists,
+// all of them Terms that share the schema's variables:
+//
+//   move(B, From, To)  pre: on(B, From), clear(B), clear(To)
+//                      add: on(B, To), clear(From)
+//                      del: on(B, From), clear(To)
+//
+// Schemas are grounded over the planner's objects, and ground actions whose
+// preconditions are unreachable from the initial state (ignoring deletes)
+// are dropped. The state is the set of ground fluents that hold (closed
+// world); preconditions and goals are positive fluents, and a fluent that is
+// both added and deleted by an action holds afterwards.
+//
+// Horizon t has a variable per fluent at times 0..=t and per action at steps
+// 0..t. Each step may run several actions as long as none deletes what
+// another needs or adds, so every ordering of a step is a valid sequence.
+// Explanatory frame axioms require a fluent that changes to be changed by an
+// action of that step. Horizons are tried in increasing order on one
+// incremental solver, with the goal at time t passed as assumptions, so the
+// first plan found has the fewest steps.
+
+use crate::core::Term;
+use super::backend::SatBackend;
+use super::cdcl::CdclSolver;
+use super::solver::Literal;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Action {
+    pub name: Term,
+    pub pre: Vec<Term>,
+    pub add: Vec<Term>,
+    pub del: Vec<Term>,
+}
+
+impl Action {
+    pub fn new(name: Term) -> Self {
+        Self { name, pre: Vec::new(), add: Vec::new(), del: Vec::new() }
+    }
+
+    pub fn with_pre(mut self, pre: Vec<Term>) -> Self {
+        self.pre = pre;
+        self
+    }
+
+    pub fn with_add(mut self, add: Vec<Term>) -> Self {
+        self.add = add;
+        self
+    }
+
+    pub fn with_del(mut self, del: Vec<Term>) -> Self {
+        self.del = del;
+        self
+    }
+
+    fn vars(&self) -> Vec<u32> {
+        let mut vars = self.name.vars();
+        for t in self.pre.iter().chain(&self.add).chain(&self.del) {
+            for v in t.vars() {
+                if !vars.contains(&v) {
+                    vars.push(v);
+                }
+            }
+        }
+        vars
+    }
+
+    fn substitute(&self, var: u32, value: &Term) -> Action {
+        let sub = |ts: &[Term]| ts.iter().map(|t| t.substitute(var, value)).collect();
+        Action {
+            name: self.name.substitute(var, value),
+            pre: sub(&self.pre),
+            add: sub(&self.add),
+            del: sub(&self.del),
+        }
+    }
+
+    // Every instance of the schema with its variables bound to objects
+    pub fn ground(&self, objects: &[Term]) -> Vec<Action> {
+        let mut out = vec![self.clone()];
+        for v in self.vars() {
+            out = out.iter().flat_map(|a| objects.iter().map(move |o| a.substitute(v, o))).collect();
+        }
+        out
+    }
+
+    pub fn is_applicable(&self, state: &FxHashSet<Term>) -> bool {
+        self.pre.iter().all(|p| state.contains(p))
+    }
+
+    pub fn apply(&self, state: &mut FxHashSet<Term>) {
+        for d in &self.del {
+            state.remove(d);
+        }
+        state.extend(self.add.iter().cloned());
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Plan {
+    // Ground actions of each step; actions within a step are independent
+    pub steps: Vec<Vec<Action>>,
+}
+
+impl Plan {
+    pub fn len(&self) -> usize {
+        self.steps.iter().map(|s| s.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The plan as one action sequence
+    pub fn actions(&self) -> Vec<&Term> {
+        self.steps.iter().flatten().map(|a| &a.name).collect()
+    }
+
+    // Final state, or None if some action is not applicable when it runs
+    pub fn execute(&self, init: &[Term]) -> Option<FxHashSet<Term>> {
+        let mut state: FxHashSet<Term> = init.iter().cloned().collect();
+        for action in self.steps.iter().flatten() {
+            if !action.is_applicable(&state) {
+                return None;
+            }
+            action.apply(&mut state);
+        }
+        Some(state)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Planner {
+    schemas: Vec<Action>,
+    objects: Vec<Term>,
+    max_horizon: usize,
+}
+
+impl Planner {
+    pub fn new(schemas: Vec<Action>) -> Self {
+        Self { schemas, objects: Vec::new(), max_horizon: 20 }
+    }
+
+    // Values the schema variables range over
+    pub fn with_objects(mut self, objects: Vec<Term>) -> Self {
+        self.objects = objects;
+        self
+    }
+
+    // Most steps (not actions) a plan may take
+    pub fn with_max_horizon(mut self, max_horizon: usize) -> Self {
+        self.max_horizon = max_horizon;
+        self
+    }
+
+    // Ground actions reachable from init when deletes are ignored
+    pub fn ground_actions(&self, init: &[Term]) -> Vec<Action> {
+        let mut candidates: Vec<Action> = self.schemas.iter().flat_map(|s| s.ground(&self.objects)).collect();
+        let mut reached: FxHashSet<Term> = init.iter().cloned().collect();
+        let mut actions = Vec::new();
+        loop {
+            let (ready, waiting): (Vec<Action>, Vec<Action>) =
+                candidates.into_iter().partition(|a| a.is_applicable(&reached));
+            if ready.is_empty() {
+                return actions;
+            }
+            for a in &ready {
+                reached.extend(a.add.iter().cloned());
+            }
+            actions.extend(ready);
+            candidates = waiting;
+        }
+    }
+
+    // Shortest plan (in steps) from init to a state containing every goal
+    pub fn plan(&self, init: &[Term], goal: &[Term]) -> Option<Plan> {
+        self.plan_with(&mut CdclSolver::new(0), init, goal)
+    }
+
+    // plan() on the given backend, which must not hold any clauses yet
+    pub fn plan_with<B: SatBackend>(&self, solver: &mut B, init: &[Term], goal: &[Term]) -> Option<Plan> {
+        let actions = self.ground_actions(init);
+        let mut index: FxHashMap<&Term, usize> = FxHashMap::default();
+        let fluents = init.iter().chain(goal).chain(actions.iter().flat_map(|a| a.pre.iter().chain(&a.add).chain(&a.del)));
+        for f in fluents {
+            let next = index.len();
+            index.entry(f).or_insert(next);
+        }
+        let (nf, na) = (index.len(), actions.len());
+        let ids = |ts: &[Term]| -> Vec<usize> { ts.iter().map(|t| index[t]).collect() };
+        let mut adders = vec![Vec::new(); nf];
+        let mut deleters = vec![Vec::new(); nf];
+        let mut users = vec![Vec::new(); nf];
+        let mut encoded = Vec::with_capacity(na);
+        for (a, action) in actions.iter().enumerate() {
+            let (pre, add) = (ids(&action.pre), ids(&action.add));
+            let del: Vec<usize> = ids(&action.del).into_iter().filter(|f| !add.contains(f)).collect();
+            for &f in &add {
+                adders[f].push(a);
+            }
+            for &f in &del {
+                deleters[f].push(a);
+            }
+            for &f in pre.iter().chain(&add) {
+                users[f].push(a);
+            }
+            encoded.push((pre, add, del));
+        }
+        let mut interfering: FxHashSet<(usize, usize)> = FxHashSet::default();
+        for f in 0..nf {
+            for &a in &deleters[f] {
+                for &b in &users[f] {
+                    if a != b {
+                        interfering.insert((a.min(b), a.max(b)));
+                    }
+                }
+            }
+        }
+
+        let width = nf + na;
+        let fluent = |f: usize, t: usize| (t * width + f + 1) as Literal;
+        let action = |a: usize, t: usize| (t * width + nf + a + 1) as Literal;
+        let initial: FxHashSet<usize> = ids(init).into_iter().collect();
+        for f in 0..nf {
+            let l = fluent(f, 0);
+            solver.add_clause(&[if initial.contains(&f) { l } else { -l }]);
+        }
+        let goal = ids(goal);
+        for t in 0..=self.max_horizon {
+            let assumptions: Vec<Literal> = goal.iter().map(|&g| fluent(g, t)).collect();
+            if solver.solve(&assumptions) {
+                let model = solver.model()?;
+                let taken = |a: usize, s: usize| model.get(&(action(a, s) as u32)) == Some(&true);
+                let steps = (0..t)
+                    .map(|s| (0..na).filter(|&a| taken(a, s)).map(|a| actions[a].clone()).collect())
+                    .collect();
+                return Some(Plan { steps });
+            }
+            if solver.core().is_empty() {
+                return None;
+            }
+            // Step t, from time t to t + 1
+            for (a, (pre, add, del)) in encoded.iter().enumerate() {
+                let x = action(a, t);
+                for &f in pre {
+                    solver.add_clause(&[-x, fluent(f, t)]);
+                }
+                for &f in add {
+                    solver.add_clause(&[-x, fluent(f, t + 1)]);
+                }
+                for &f in del {
+                    solver.add_clause(&[-x, -fluent(f, t + 1)]);
+                }
+            }
+            for &(a, b) in &interfering {
+                solver.add_clause(&[-action(a, t), -action(b, t)]);
+            }
+            for f in 0..nf {
+                let mut gained = vec![fluent(f, t), -fluent(f, t + 1)];
+                gained.extend(adders[f].iter().map(|&a| action(a, t)));
+                solver.add_clause(&gained);
+                let mut lost = vec![-fluent(f, t), fluent(f, t + 1)];
+                lost.extend(deleters[f].iter().map(|&a| action(a, t)));
+                solver.add_clause(&lost);
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ON: u32 = 1;
+    const CLEAR: u32 = 2;
+    const ONTABLE: u32 = 3;
+    const MOVE: u32 = 4;
+    const UNSTACK: u32 = 5;
+    const STACK: u32 = 6;
+    const A: u32 = 10;
+    const B: u32 = 11;
+    const C: u32 = 12;
+
+    fn t(f: u32, args: &[Term]) -> Term {
+        Term::compound(f, args.to_vec())
+    }
+
+    fn blocks_world() -> Planner {
+        let (x, y, z) = (Term::var(0), Term::var(1), Term::var(2));
+        let on = |a: &Term, b: &Term| t(ON, &[a.clone(), b.clone()]);
+        let clear = |a: &Term| t(CLEAR, std::slice::from_ref(a));
+        let ontable = |a: &Term| t(ONTABLE, std::slice::from_ref(a));
+        Planner::new(vec![
+            Action::new(t(MOVE, &[x.clone(), y.clone(), z.clone()]))
+                .with_pre(vec![on(&x, &y), clear(&x), clear(&z)])
+                .with_add(vec![on(&x, &z), clear(&y)])
+                .with_del(vec![on(&x, &y), clear(&z)]),
+            Action::new(t(UNSTACK, &[x.clone(), y.clone()]))
+                .with_pre(vec![on(&x, &y), clear(&x)])
+                .with_add(vec![ontable(&x), clear(&y)])
+                .with_del(vec![on(&x, &y)]),
+            Action::new(t(STACK, &[x.clone(), y.clone()]))
+                .with_pre(vec![ontable(&x), clear(&x), clear(&y)])
+                .with_add(vec![on(&x, &y)])
+                .with_del(vec![ontable(&x), clear(&y)]),
+        ])
+        .with_objects(vec![Term::atom(A), Term::atom(B), Term::atom(C)])
+    }
+
+    #[test]
+    fn solves_sussman_anomaly() {
+        let (a, b, c) = (Term::atom(A), Term::atom(B), Term::atom(C));
+        let init = vec![
+            t(ON, &[c.clone(), a.clone()]),
+            t(ONTABLE, std::slice::from_ref(&a)),
+            t(ONTABLE, std::slice::from_ref(&b)),
+            t(CLEAR, std::slice::from_ref(&b)),
+            t(CLEAR, std::slice::from_ref(&c)),
+        ];
+        let goal = vec![t(ON, &[a.clone(), b.clone()]), t(ON, &[b.clone(), c.clone()])];
+        let plan = blocks_world().plan(&init, &goal).unwrap();
+        assert_eq!((plan.steps.len(), plan.len()), (3, 3));
+        assert_eq!(plan.actions()[0], &t(UNSTACK, &[c, a]));
+        let state = plan.execute(&init).unwrap();
+        assert!(goal.iter().all(|g| state.contains(g)));
+
+        assert!(blocks_world().plan(&init, &init).unwrap().is_empty());
+    }
+
+    #[test]
+    fn unreachable_goals_and_short_horizons() {
+        let (a, b) = (Term::atom(A), Term::atom(B));
+        let init = vec![
+            t(ON, &[a.clone(), b.clone()]),
+            t(ONTABLE, std::slice::from_ref(&b)),
+            t(CLEAR, std::slice::from_ref(&a)),
+        ];
+        // Nothing ever adds ontable(c)
+        assert_eq!(blocks_world().plan(&init, &[t(ONTABLE, &[Term::atom(C)])]), None);
+        let swap = [t(ON, &[b.clone(), a.clone()])];
+        assert_eq!(blocks_world().with_max_horizon(2).plan(&init, &swap).map(|p| p.len()), Some(2));
+        assert_eq!(blocks_world().with_max_horizon(1).plan(&init, &swap), None);
+    }
+}
@@ -0,0 +1,202 @@
+// Per-predicate profiling counters for the rule engine.
+//
+// With profiling on (RuleEngine::with_profiling), every call is charged to
+// the functor/arity of its goal:
+//   calls          goals of this predicate the machine resolved
+//   unifications   clause heads (facts and rules) tried against them
+//   successes      exits: solutions the calls returned
+//   time           inclusive time, from each call to its last exit or fail,
+//                  subgoals included; recursive calls count again
+//
+// The engine reports the ports through the same marker goals as the tracer
+// (see debugger.rs), under every search strategy.
+
+use crate::core::{Term, Sym, SymbolTable};
+use rustc_hash::FxHashMap;
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PredicateStats {
+    pub calls: u64,
+    pub unifications: u64,
+    pub successes: u64,
+    pub time: Duration,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    stats: FxHashMap<(Sym, usize), PredicateStats>,
+    // Calls of the current query by id: predicate and last time charged
+    open: Vec<((Sym, usize), Instant)>,
+}
+
+pub(crate) fn predicate_key(goal: &Term) -> Option<(Sym, usize)> {
+    match goal {
+        Term::Compound(f, args) => Some((*f, args.len())),
+        Term::Atom(f) => Some((*f, 0)),
+        _ => None,
+    }
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn entry(&mut self, functor: Sym, arity: usize) -> &mut PredicateStats {
+        self.stats.entry((functor, arity)).or_default()
+    }
+
+    pub fn get(&self, functor: Sym, arity: usize) -> Option<&PredicateStats> {
+        self.stats.get(&(functor, arity))
+    }
+
+    pub fn clear(&mut self) {
+        self.stats.clear();
+        self.open.clear();
+    }
+
+    // Call port: returns the id its Exit and Fail ports are charged to
+    pub(crate) fn call(&mut self, key: (Sym, usize)) -> usize {
+        self.stats.entry(key).or_default().calls += 1;
+        self.open.push((key, Instant::now()));
+        self.open.len() - 1
+    }
+
+    pub(crate) fn exit(&mut self, call: usize) {
+        if let Some(stats) = self.charge(call) {
+            stats.successes += 1;
+        }
+    }
+
+    pub(crate) fn fail(&mut self, call: usize) {
+        self.charge(call);
+    }
+
+    // Extend the call's time up to now
+    fn charge(&mut self, call: usize) -> Option<&mut PredicateStats> {
+        let (key, since) = self.open.get_mut(call)?;
+        let now = Instant::now();
+        let stats = self.stats.entry(*key).or_default();
+        stats.time += now - *since;
+        *since = now;
+        Some(stats)
+    }
+
+    // Call ids restart with every top-level query
+    pub(crate) fn end_calls(&mut self) {
+        self.open.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stats.is_empty()
+    }
+
+    // Predicates ordered by time, then calls (most expensive first)
+    pub fn report(&self) -> ProfileReport {
+        let mut rows: Vec<((Sym, usize), PredicateStats)> = self.stats.iter().map(|(k, v)| (*k, *v)).collect();
+        rows.sort_by(|a, b| b.1.time.cmp(&a.1.time).then(b.1.calls.cmp(&a.1.calls)).then(a.0.cmp(&b.0)));
+        ProfileReport { rows }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProfileReport {
+    pub rows: Vec<((Sym, usize), PredicateStats)>,
+}
+
+impl ProfileReport {
+    pub fn total_time(&self) -> Duration {
+        self.rows.iter().map(|(_, s)| s.time).sum()
+    }
+
+    pub fn format(&self, syms: &SymbolTable) -> String {
+        let total = self.total_time().as_secs_f64().max(f64::MIN_POSITIVE);
+        let mut out = String::new();
+        let _ = writeln!(out, "{:<24} {:>10} {:>12} {:>10} {:>12} {:>6}", "predicate", "calls", "unify", "succeed", "time (us)", "%");
+        for ((f, arity), s) in &self.rows {
+            let name = format!("{}/{}", syms.resolve(*f).unwrap_or("?"), arity);
+            let _ = writeln!(
+                out,
+                "{:<24} {:>10} {:>12} {:>10} {:>12} {:>5.1}%",
+                name,
+                s.calls,
+                s.unifications,
+                s.successes,
+                s.time.as_micros(),
+                100.0 * s.time.as_secs_f64() / total,
+            );
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reasoning::debugger::TraceLog;
+    use crate::reasoning::rules::{Rule, RuleEngine, SearchStrategy};
+    use std::sync::{Arc, Mutex};
+
+    const PARENT: Sym = 1;
+    const GRANDPARENT: Sym = 2;
+
+    fn family(engine: &mut RuleEngine) {
+        for (a, b) in [(10, 11), (11, 12), (11, 13)] {
+            engine.add_fact(Term::compound(PARENT, vec![Term::atom(a), Term::atom(b)]));
+        }
+        engine.add_rule(Rule::new(
+            Term::compound(GRANDPARENT, vec![Term::var(0), Term::var(2)]),
+            vec![
+                Term::compound(PARENT, vec![Term::var(0), Term::var(1)]),
+                Term::compound(PARENT, vec![Term::var(1), Term::var(2)]),
+            ],
+        ));
+    }
+
+    #[test]
+    fn counts_calls_and_unifications() {
+        let mut engine = RuleEngine::new().with_profiling();
+        family(&mut engine);
+        let answers = engine.query(&Term::compound(GRANDPARENT, vec![Term::atom(10), Term::var(5)]));
+        assert_eq!(answers.len(), 2);
+
+        let profile = engine.profile().unwrap();
+        let gp = profile.get(GRANDPARENT, 2).unwrap();
+        assert_eq!((gp.calls, gp.successes), (1, 2));
+        let p = profile.get(PARENT, 2).unwrap();
+        assert_eq!((p.calls, p.successes), (2, 3));
+        assert!(p.unifications >= 2);
+        assert_eq!(engine.profile_report().rows.len(), 2);
+    }
+
+    #[test]
+    fn profiles_every_strategy_alongside_the_tracer() {
+        for strategy in [SearchStrategy::DepthFirst, SearchStrategy::BreadthFirst] {
+            let log = Arc::new(Mutex::new(TraceLog::new()));
+            let mut engine = RuleEngine::new().with_profiling().with_strategy(strategy);
+            engine.set_tracer(Arc::clone(&log));
+            family(&mut engine);
+            assert_eq!(engine.query(&Term::compound(GRANDPARENT, vec![Term::atom(10), Term::var(5)])).len(), 2);
+
+            let profile = engine.profile().unwrap();
+            let gp = profile.get(GRANDPARENT, 2).unwrap();
+            assert_eq!((gp.calls, gp.successes), (1, 2), "{:?}", strategy);
+            let p = profile.get(PARENT, 2).unwrap();
+            assert_eq!((p.calls, p.successes), (2, 3), "{:?}", strategy);
+            assert!(gp.time > Duration::ZERO && p.time > Duration::ZERO, "{:?}", strategy);
+            assert!(!log.lock().unwrap().events.is_empty());
+        }
+    }
+
+    #[test]
+    fn profiles_failure_analysis() {
+        let mut engine = RuleEngine::new().with_profiling();
+        family(&mut engine);
+        assert!(engine.why_not(&Term::compound(GRANDPARENT, vec![Term::atom(12), Term::var(5)])).is_some());
+        let profile = engine.profile().unwrap();
+        assert_eq!(profile.get(GRANDPARENT, 2).map(|s| (s.calls, s.successes)), Some((1, 0)));
+        assert_eq!(profile.get(PARENT, 2).map(|s| (s.calls, s.successes)), Some((1, 0)));
+    }
+}
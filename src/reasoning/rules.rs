@@ -1,9 +1,33 @@
-use crate::core::{Term, Sym, Result, KolossError};
+use crate::core::{Term, Sym, Result, KolossError, SymbolTable};
 use super::unifier::{Substitution, unify, rename_vars};
-use super::builtins::{BuiltinRegistry, BuiltinResult, eval_builtin};
-use rustc_hash::FxHashMap;
-
-#[derive(Debug, Clone)]
+use super::builtins::{self, BuiltinRegistry, BuiltinResult, eval_builtin, eval_arithmetic, term_from_number};
+use super::asp::{AspProgram, AspRule, AnswerSet};
+use super::modes::{ModeDecl, ModeTable, ModeViolation, check_rules};
+use super::partial_eval::PartialEvaluator;
+use super::index::{TermIndex, RuleIndex};
+use super::tms::{Tms, Justification, Support};
+use super::temporal;
+use super::library;
+use super::signatures::{PredicateSig, SignatureTable, SignatureError, UnknownPredicates};
+use super::why_not::{FailureTrace, FailureReason};
+use super::debugger::{self, Port, Tracer, ActiveTracer, SpyPoints};
+use super::persist::{EngineSnapshot, EngineCheckpoint};
+use super::profile::{Profile, ProfileReport, predicate_key};
+use super::consult::{Conflict, ConsultMode, ConsultReport, UNNAMED_SOURCE};
+use rustc_hash::{FxHashMap, FxHashSet};
+use serde::{Serialize, Deserialize};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+// Variables renamed apart during a query are numbered from here up; the
+// counter restarts at every top-level query
+const FIRST_FRESH_VAR: Sym = 10000;
+
+// Functor of the internal goal '$length'(L, N, From) that retries length/2
+// with lists of From or more fresh variables (next to debugger's markers)
+const LENGTH_FROM: Sym = Sym::MAX - 4;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Rule {
     pub head: Term,
     pub body: Vec<Term>,
@@ -37,10 +61,17 @@ impl Rule {
     }
 }
 
-// Tabling: cache for memoized query results
+// Tabling: cache for memoized query results. Answers are stored as
+// instantiated goals so they can be replayed under any calling substitution.
+#[derive(Debug, Clone)]
+struct TableEntry {
+    functor: Sym,
+    answers: Vec<Term>,
+}
+
 #[derive(Debug, Clone, Default)]
 struct Table {
-    entries: FxHashMap<u64, Vec<Substitution>>,
+    entries: FxHashMap<u64, TableEntry>,
 }
 
 impl Table {
@@ -51,12 +82,18 @@ impl Table {
         hasher.finish()
     }
 
-    fn get(&self, goal: &Term) -> Option<&Vec<Substitution>> {
-        self.entries.get(&Self::key(goal))
+    fn get(&self, goal: &Term) -> Option<&Vec<Term>> {
+        self.entries.get(&Self::key(goal)).map(|e| &e.answers)
+    }
+
+    fn insert(&mut self, goal: &Term, functor: Sym, answers: Vec<Term>) {
+        self.entries.insert(Self::key(goal), TableEntry { functor, answers });
     }
 
-    fn insert(&mut self, goal: &Term, results: Vec<Substitution>) {
-        self.entries.insert(Self::key(goal), results);
+    fn invalidate(&mut self, functors: &FxHashSet<Sym>) -> usize {
+        let before = self.entries.len();
+        self.entries.retain(|_, e| !functors.contains(&e.functor));
+        before - self.entries.len()
     }
 
     fn clear(&mut self) {
@@ -68,13 +105,57 @@ impl Table {
     }
 }
 
-// Signal for cut propagation
-struct CutSignal;
+// How the resolution machine explores alternatives
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SearchStrategy {
+    // Classic Prolog order, bounded by max_depth
+    #[default]
+    DepthFirst,
+    // Depth-first with a bound raised by `step` up to max_depth while any
+    // branch was cut off; answers come shallowest first, without repeats
+    IterativeDeepening { step: usize },
+    // Alternatives are explored in FIFO order, so an infinite branch cannot
+    // starve its siblings. Cut has no pruning effect in this mode.
+    BreadthFirst,
+}
+
+// --- Resolution machine ---
+//
+// Resolution runs on an explicit stack of choicepoints instead of the Rust
+// call stack, so recursion depth is bounded only by `max_depth` and memory.
+// A choicepoint is a pending continuation (persistent list of goals) plus the
+// substitution to resume it with. Each goal remembers the stack height at the
+// time its parent predicate was called: executing `!` truncates the stack back
+// to that height, discarding the parent's remaining alternatives.
+
+#[derive(Debug)]
+struct GoalNode {
+    goal: Term,
+    depth: usize,
+    cut_barrier: usize,
+    next: Cont,
+}
+
+type Cont = Option<Rc<GoalNode>>;
+
+fn push_goals(goals: &[Term], depth: usize, cut_barrier: usize, next: Cont) -> Cont {
+    goals.iter().rev().fold(next, |next, goal| {
+        Some(Rc::new(GoalNode { goal: goal.clone(), depth, cut_barrier, next }))
+    })
+}
+
+struct Choice {
+    cont: Cont,
+    sub: Substitution,
+}
 
 #[derive(Debug, Clone)]
 pub struct RuleEngine {
-    rules: Vec<Rule>,
-    facts: Vec<Term>,
+    // Clause store, shared copy-on-write between clones and checkpoints
+    rules: Arc<Vec<Rule>>,
+    rule_index: Arc<RuleIndex>,
+    facts: Arc<Vec<Term>>,
+    fact_index: Arc<TermIndex>,
     max_depth: usize,
     var_counter: Sym,
     builtins: BuiltinRegistry,
@@ -83,21 +164,60 @@ pub struct RuleEngine {
     tabled_functors: Vec<Sym>,
     not_sym: Option<Sym>,
     naf_sym: Option<Sym>,
+    modes: ModeTable,
+    mode_checks: bool,
+    mode_violations: Vec<ModeViolation>,
+    tms: Option<Tms>,
+    holds_sym: Option<Sym>,
+    tick: u64,
+    signatures: SignatureTable,
+    unknown_predicates: UnknownPredicates,
+    signature_warnings: Vec<SignatureError>,
+    globals: FxHashMap<Sym, Term>,
+    attr_hooks: FxHashMap<Sym, Sym>,
+    failure_log: Option<FailureTrace>,
+    strategy: SearchStrategy,
+    depth_cutoffs: usize,
+    profile: Option<Profile>,
+    // Source that last consulted each predicate (functor, arity)
+    sources: FxHashMap<(Sym, usize), String>,
+    tracer: Option<ActiveTracer>,
+    spy_points: SpyPoints,
 }
 
 impl RuleEngine {
     pub fn new() -> Self {
         Self {
-            rules: Vec::new(),
-            facts: Vec::new(),
+            rules: Arc::new(Vec::new()),
+            rule_index: Arc::new(RuleIndex::new()),
+            facts: Arc::new(Vec::new()),
+            fact_index: Arc::new(TermIndex::new()),
             max_depth: 64,
-            var_counter: 10000,
+            var_counter: FIRST_FRESH_VAR,
             builtins: BuiltinRegistry::new(),
             table: Table::default(),
             tabling_enabled: false,
             tabled_functors: Vec::new(),
             not_sym: None,
             naf_sym: None,
+            modes: ModeTable::new(),
+            mode_checks: false,
+            mode_violations: Vec::new(),
+            tms: None,
+            holds_sym: None,
+            tick: 0,
+            signatures: SignatureTable::new(),
+            unknown_predicates: UnknownPredicates::Fail,
+            signature_warnings: Vec::new(),
+            globals: FxHashMap::default(),
+            attr_hooks: FxHashMap::default(),
+            failure_log: None,
+            strategy: SearchStrategy::DepthFirst,
+            depth_cutoffs: 0,
+            profile: None,
+            sources: FxHashMap::default(),
+            tracer: None,
+            spy_points: SpyPoints::default(),
         }
     }
 
@@ -106,6 +226,83 @@ impl RuleEngine {
         self
     }
 
+    pub fn with_strategy(mut self, strategy: SearchStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    pub fn set_strategy(&mut self, strategy: SearchStrategy) {
+        self.strategy = strategy;
+    }
+
+    pub fn strategy(&self) -> SearchStrategy {
+        self.strategy
+    }
+
+    // Count calls, head unifications, successes and time per predicate
+    pub fn with_profiling(mut self) -> Self {
+        self.profile = Some(Profile::new());
+        self
+    }
+
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profile = if enabled { Some(self.profile.take().unwrap_or_default()) } else { None };
+    }
+
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_ref()
+    }
+
+    pub fn reset_profile(&mut self) {
+        if let Some(p) = &mut self.profile {
+            p.clear();
+        }
+    }
+
+    // Empty unless profiling is enabled
+    pub fn profile_report(&self) -> ProfileReport {
+        self.profile.as_ref().map(|p| p.report()).unwrap_or_default()
+    }
+
+    // Goals abandoned at max_depth during the last query. Non-zero means
+    // answers may be missing.
+    pub fn depth_cutoffs(&self) -> usize {
+        self.depth_cutoffs
+    }
+
+    // Intern and register the standard builtins, with not/1 and \+/1 as
+    // negation as failure
+    pub fn with_standard_builtins(mut self, syms: &mut SymbolTable) -> Self {
+        self.builtins.register_standard(syms);
+        self.not_sym = Some(syms.intern(builtins::BUILTIN_NOT));
+        self.naf_sym = Some(syms.intern(builtins::BUILTIN_NAF));
+        self
+    }
+
+    // maplist/2..4 and foldl/4 as library rules (registers call/N if needed)
+    pub fn load_higher_order(&mut self, syms: &mut SymbolTable) {
+        if self.builtins.sym_of(builtins::BUILTIN_CALL).is_none() {
+            self.builtins.register(builtins::BUILTIN_CALL, syms.intern(builtins::BUILTIN_CALL));
+        }
+        for rule in library::higher_order_rules(syms) {
+            self.add_rule(rule);
+        }
+    }
+
+    // List library as rules (see library::prelude_rules). The member/2 and
+    // append/3 builtins are dropped in favour of the relational versions.
+    // Includes everything load_higher_order provides.
+    pub fn load_prelude(&mut self, syms: &mut SymbolTable) {
+        self.builtins.unregister(builtins::BUILTIN_MEMBER);
+        self.builtins.unregister(builtins::BUILTIN_APPEND);
+        if self.builtins.sym_of(builtins::BUILTIN_CALL).is_none() {
+            self.builtins.register(builtins::BUILTIN_CALL, syms.intern(builtins::BUILTIN_CALL));
+        }
+        for rule in library::prelude_rules(syms) {
+            self.add_rule(rule);
+        }
+    }
+
     pub fn with_tabling(mut self) -> Self {
         self.tabling_enabled = true;
         self
@@ -118,6 +315,101 @@ impl RuleEngine {
         self.tabling_enabled = true;
     }
 
+    // Assert declared modes and determinism on every call at runtime.
+    // Violations are collected, not raised; see mode_violations().
+    pub fn with_mode_checks(mut self) -> Self {
+        self.mode_checks = true;
+        self
+    }
+
+    // Record a justification for every fact derived by forward_chain, so that
+    // retracting a fact also removes everything that lost its support.
+    // Facts already present become premises.
+    pub fn with_tms(mut self) -> Self {
+        let mut tms = Tms::new();
+        for fact in self.facts.iter() {
+            tms.add_premise(fact);
+        }
+        self.tms = Some(tms);
+        self
+    }
+
+    pub fn tms(&self) -> Option<&Tms> {
+        self.tms.as_ref()
+    }
+
+    pub fn justifications(&self, fact: &Term) -> Vec<Justification> {
+        self.tms.as_ref().map(|t| t.justifications(fact)).unwrap_or_default()
+    }
+
+    pub fn explain(&self, fact: &Term) -> Option<Support> {
+        self.tms.as_ref()?.explain(fact)
+    }
+
+    pub fn declare_mode(&mut self, decl: ModeDecl) {
+        self.modes.declare(decl);
+    }
+
+    pub fn modes(&self) -> &ModeTable {
+        &self.modes
+    }
+
+    // Static mode analysis of the current rule base
+    pub fn check_modes(&self) -> Vec<ModeViolation> {
+        check_rules(&self.rules, &self.modes, &self.builtins)
+    }
+
+    pub fn mode_violations(&self) -> &[ModeViolation] {
+        &self.mode_violations
+    }
+
+    pub fn clear_mode_violations(&mut self) {
+        self.mode_violations.clear();
+    }
+
+    pub fn with_unknown_predicates(mut self, mode: UnknownPredicates) -> Self {
+        self.unknown_predicates = mode;
+        self
+    }
+
+    pub fn declare_predicate(&mut self, sig: PredicateSig) {
+        self.signatures.declare(sig);
+    }
+
+    pub fn signatures(&self) -> &SignatureTable {
+        &self.signatures
+    }
+
+    // Check every fact and rule head against the declared signatures
+    pub fn validate_signatures(&self) -> Vec<SignatureError> {
+        self.signatures.validate(&self.facts, &self.rules)
+    }
+
+    pub fn signature_warnings(&self) -> &[SignatureError] {
+        &self.signature_warnings
+    }
+
+    pub fn clear_signature_warnings(&mut self) {
+        self.signature_warnings.clear();
+    }
+
+    fn is_unknown_predicate(&self, goal: &Term) -> bool {
+        let (f, arity) = match goal {
+            Term::Compound(f, args) => (*f, args.len()),
+            Term::Atom(f) => (*f, 0),
+            _ => return false,
+        };
+        let matches = |t: &Term| match t {
+            Term::Compound(g, args) => *g == f && args.len() == arity,
+            Term::Atom(g) => *g == f && arity == 0,
+            _ => false,
+        };
+        !self.builtins.is_builtin(f)
+            && self.signatures.get(f, arity).is_none()
+            && !self.facts.iter().any(matches)
+            && !self.rules.iter().any(|r| matches(&r.head))
+    }
+
     pub fn set_not_sym(&mut self, sym: Sym) {
         self.not_sym = Some(sym);
     }
@@ -126,6 +418,82 @@ impl RuleEngine {
         self.naf_sym = Some(sym);
     }
 
+    // Functor of temporal facts `holds(Fact, From, To)`, used by holds_at/2
+    // and holds_now/1
+    pub fn set_holds_sym(&mut self, sym: Sym) {
+        self.holds_sym = Some(sym);
+    }
+
+    // Keep in step with the knowledge graph: engine.set_tick(graph.current_tick())
+    pub fn set_tick(&mut self, tick: u64) {
+        if tick != self.tick {
+            self.tick = tick;
+            self.table.clear();
+        }
+    }
+
+    pub fn advance_tick(&mut self) {
+        self.set_tick(self.tick + 1);
+    }
+
+    pub fn current_tick(&self) -> u64 {
+        self.tick
+    }
+
+    // Global values shared with nb_setval/2 and nb_getval/2. They survive
+    // backtracking and persist across queries.
+    pub fn set_global(&mut self, key: Sym, value: Term) {
+        self.globals.insert(key, value);
+    }
+
+    pub fn global(&self, key: Sym) -> Option<&Term> {
+        self.globals.get(&key)
+    }
+
+    pub fn clear_globals(&mut self) {
+        self.globals.clear();
+    }
+
+    // When a variable carrying attribute `module` is bound, run
+    // hook(AttributeValue, Other) where Other is the term it was bound to.
+    // Attributes of modules without a hook are dropped on binding.
+    pub fn set_attr_hook(&mut self, module: Sym, hook: Sym) {
+        self.attr_hooks.insert(module, hook);
+    }
+
+    // Debugger: report the call/exit/redo/fail ports of every goal (or of
+    // the spied predicates only) to `tracer`. Keep a clone of the Arc to
+    // inspect the tracer afterwards.
+    pub fn set_tracer<T: Tracer + Send + 'static>(&mut self, tracer: Arc<Mutex<T>>) {
+        self.tracer = Some(ActiveTracer(tracer));
+    }
+
+    pub fn with_tracer<T: Tracer + Send + 'static>(mut self, tracer: T) -> Self {
+        self.set_tracer(Arc::new(Mutex::new(tracer)));
+        self
+    }
+
+    pub fn clear_tracer(&mut self) {
+        self.tracer = None;
+    }
+
+    pub fn is_tracing(&self) -> bool {
+        self.tracer.is_some()
+    }
+
+    // Spy on functor/arity (every arity if None)
+    pub fn spy(&mut self, functor: Sym, arity: Option<usize>) {
+        self.spy_points.add(functor, arity);
+    }
+
+    pub fn nospy(&mut self, functor: Sym) {
+        self.spy_points.remove(functor);
+    }
+
+    pub fn nospy_all(&mut self) {
+        self.spy_points.clear();
+    }
+
     pub fn builtins_mut(&mut self) -> &mut BuiltinRegistry {
         &mut self.builtins
     }
@@ -143,11 +511,257 @@ impl RuleEngine {
     }
 
     pub fn add_rule(&mut self, rule: Rule) {
-        self.rules.push(rule);
+        self.invalidate_for(&rule.head);
+        Arc::make_mut(&mut self.rule_index).push(&rule);
+        Arc::make_mut(&mut self.rules).push(rule);
     }
 
     pub fn add_fact(&mut self, fact: Term) {
-        self.facts.push(fact);
+        if let Some(tms) = &mut self.tms {
+            tms.add_premise(&fact);
+        }
+        self.insert_fact(fact);
+    }
+
+    fn insert_fact(&mut self, fact: Term) {
+        self.invalidate_for(&fact);
+        Arc::make_mut(&mut self.fact_index).insert(&fact, self.facts.len());
+        Arc::make_mut(&mut self.facts).push(fact);
+    }
+
+    pub fn has_fact(&self, fact: &Term) -> bool {
+        self.fact_index.candidates(fact).into_iter().any(|i| self.facts[i] == *fact)
+    }
+
+    // --- Persistence ---
+
+    // Plain-data state of the engine; see persist.rs for what is left out.
+    // Builtins backed by custom closures are skipped.
+    pub fn save(&self) -> EngineSnapshot {
+        let mut globals: Vec<(Sym, Term)> = self.globals.iter().map(|(k, v)| (*k, v.clone())).collect();
+        globals.sort_by_key(|(k, _)| *k);
+        EngineSnapshot {
+            rules: self.rules.to_vec(),
+            facts: self.facts.to_vec(),
+            builtins: self.builtins.registrations()
+                .filter(|(_, s)| !self.builtins.is_custom(*s))
+                .map(|(n, s)| (n.to_string(), s))
+                .collect(),
+            tabling_enabled: self.tabling_enabled,
+            tabled_functors: self.tabled_functors.clone(),
+            not_sym: self.not_sym,
+            naf_sym: self.naf_sym,
+            holds_sym: self.holds_sym,
+            max_depth: self.max_depth,
+            var_counter: self.var_counter,
+            tick: self.tick,
+            globals,
+        }
+    }
+
+    pub fn load(snapshot: &EngineSnapshot) -> Self {
+        let mut engine = Self::new();
+        engine.rules = Arc::new(snapshot.rules.clone());
+        engine.facts = Arc::new(snapshot.facts.clone());
+        engine.reindex_rules();
+        engine.reindex_facts();
+        for (name, sym) in &snapshot.builtins {
+            engine.builtins.register(name, *sym);
+        }
+        engine.tabling_enabled = snapshot.tabling_enabled;
+        engine.tabled_functors = snapshot.tabled_functors.clone();
+        engine.not_sym = snapshot.not_sym;
+        engine.naf_sym = snapshot.naf_sym;
+        engine.holds_sym = snapshot.holds_sym;
+        engine.max_depth = snapshot.max_depth;
+        engine.var_counter = snapshot.var_counter;
+        engine.tick = snapshot.tick;
+        engine.globals = snapshot.globals.iter().cloned().collect();
+        engine
+    }
+
+    pub fn save_json(&self) -> String {
+        self.save().to_json()
+    }
+
+    pub fn load_json(json: &str) -> Option<Self> {
+        EngineSnapshot::from_json(json).map(|s| Self::load(&s))
+    }
+
+    pub fn save_binary(&self) -> Vec<u8> {
+        self.save().to_binary()
+    }
+
+    pub fn load_binary(data: &[u8]) -> Option<Self> {
+        EngineSnapshot::from_binary(data).map(|s| Self::load(&s))
+    }
+
+    // O(1) checkpoint of the clause store (plus TMS, tick and globals) for
+    // what-if reasoning; the first change after it copies the store once
+    pub fn snapshot(&self) -> EngineCheckpoint {
+        EngineCheckpoint {
+            rules: Arc::clone(&self.rules),
+            rule_index: Arc::clone(&self.rule_index),
+            facts: Arc::clone(&self.facts),
+            fact_index: Arc::clone(&self.fact_index),
+            tms: self.tms.clone(),
+            tick: self.tick,
+            globals: self.globals.clone(),
+        }
+    }
+
+    // Roll back to a checkpoint. Answer tables are dropped.
+    pub fn restore(&mut self, checkpoint: &EngineCheckpoint) {
+        self.rules = Arc::clone(&checkpoint.rules);
+        self.rule_index = Arc::clone(&checkpoint.rule_index);
+        self.facts = Arc::clone(&checkpoint.facts);
+        self.fact_index = Arc::clone(&checkpoint.fact_index);
+        self.tms = checkpoint.tms.clone();
+        self.tick = checkpoint.tick;
+        self.globals = checkpoint.globals.clone();
+        self.table.clear();
+    }
+
+    // --- Consulting ---
+
+    // Load the clauses of one file/module, reporting redefinitions, duplicate
+    // and contradictory facts; see consult.rs. Duplicate facts are never added.
+    pub fn consult(&mut self, source: &str, rules: Vec<Rule>, facts: Vec<Term>, mode: ConsultMode) -> ConsultReport {
+        let mut report = ConsultReport::new(source);
+        let defined: FxHashSet<(Sym, usize)> = self.rules.iter().map(|r| &r.head)
+            .chain(self.facts.iter())
+            .filter_map(predicate_key)
+            .collect();
+        let mut incoming: Vec<(Sym, usize)> = Vec::new();
+        for key in rules.iter().map(|r| &r.head).chain(facts.iter()).filter_map(predicate_key) {
+            if !incoming.contains(&key) {
+                incoming.push(key);
+            }
+        }
+        let redefined: Vec<(Sym, usize)> = incoming.iter().copied()
+            .filter(|key| defined.contains(key) && self.predicate_source(key.0, key.1) != Some(source))
+            .collect();
+        for &(functor, arity) in &redefined {
+            let previous = self.predicate_source(functor, arity).unwrap_or(UNNAMED_SOURCE).to_string();
+            report.conflicts.push(Conflict::Redefinition { functor, arity, previous });
+        }
+
+        if mode == ConsultMode::Replace && !redefined.is_empty() {
+            let is_redefined = |t: &Term| predicate_key(t).is_some_and(|k| redefined.contains(&k));
+            let before = self.rules.len() + self.facts.len();
+            Arc::make_mut(&mut self.rules).retain(|r| !is_redefined(&r.head));
+            Arc::make_mut(&mut self.facts).retain(|f| !is_redefined(f));
+            report.clauses_removed = before - self.rules.len() - self.facts.len();
+            self.reindex_rules();
+            self.reindex_facts();
+            self.table.clear();
+        }
+
+        let mut accepted: Vec<Term> = Vec::new();
+        for fact in facts {
+            if self.has_fact(&fact) || accepted.contains(&fact) {
+                report.conflicts.push(Conflict::DuplicateFact(fact));
+                continue;
+            }
+            let opposite = self.contradicting(&fact).into_iter()
+                .find(|t| self.has_fact(t) || accepted.contains(t));
+            if let Some(existing) = opposite {
+                report.conflicts.push(Conflict::Contradiction { fact: fact.clone(), existing });
+            }
+            accepted.push(fact);
+        }
+
+        if mode == ConsultMode::Reject && !report.is_clean() {
+            return report;
+        }
+        report.rules_added = rules.len();
+        report.facts_added = accepted.len();
+        for rule in rules {
+            self.add_rule(rule);
+        }
+        for fact in accepted {
+            self.add_fact(fact);
+        }
+        for key in incoming {
+            self.sources.insert(key, source.to_string());
+        }
+        report
+    }
+
+    pub fn consult_snapshot(&mut self, source: &str, snapshot: &EngineSnapshot, mode: ConsultMode) -> ConsultReport {
+        self.consult(source, snapshot.rules.clone(), snapshot.facts.clone(), mode)
+    }
+
+    // Source that consulted the predicate, UNNAMED_SOURCE if it was defined
+    // directly, None if nothing defines it
+    pub fn predicate_source(&self, functor: Sym, arity: usize) -> Option<&str> {
+        if let Some(source) = self.sources.get(&(functor, arity)) {
+            return Some(source);
+        }
+        let key = Some((functor, arity));
+        let defined = self.rules.iter().any(|r| predicate_key(&r.head) == key)
+            || self.facts.iter().any(|f| predicate_key(f) == key);
+        defined.then_some(UNNAMED_SOURCE)
+    }
+
+    // Facts that would contradict `fact`: its negations, or for a negation
+    // not(P) / \+ P the plain P
+    fn contradicting(&self, fact: &Term) -> Vec<Term> {
+        match fact {
+            Term::Compound(f, args) if args.len() == 1 && self.is_negation(*f) => vec![args[0].clone()],
+            _ => [self.not_sym, self.naf_sym].into_iter()
+                .flatten()
+                .map(|neg| Term::compound(neg, vec![fact.clone()]))
+                .collect(),
+        }
+    }
+
+    fn reindex_rules(&mut self) {
+        self.rule_index = Arc::new(RuleIndex::from_rules(&self.rules));
+    }
+
+    fn reindex_facts(&mut self) {
+        self.fact_index = Arc::new(TermIndex::from_terms(&self.facts));
+    }
+
+    // Drop memoized answers of every tabled predicate that (transitively)
+    // depends on the predicate of `changed`. Returns the number of entries removed.
+    pub fn invalidate_for(&mut self, changed: &Term) -> usize {
+        if self.table.len() == 0 {
+            return 0;
+        }
+        let functor = match changed {
+            Term::Compound(f, _) | Term::Atom(f) => *f,
+            _ => return 0,
+        };
+        let affected: FxHashSet<Sym> = self.tabled_functors.iter()
+            .copied()
+            .filter(|&t| self.depends_on(t, functor))
+            .collect();
+        self.table.invalidate(&affected)
+    }
+
+    fn depends_on(&self, from: Sym, target: Sym) -> bool {
+        let mut visited = FxHashSet::default();
+        let mut stack = vec![from];
+        while let Some(f) = stack.pop() {
+            if f == target {
+                return true;
+            }
+            if !visited.insert(f) {
+                continue;
+            }
+            for rule in self.rules.iter() {
+                if let Term::Compound(h, _) | Term::Atom(h) = &rule.head {
+                    if *h == f {
+                        for goal in &rule.body {
+                            collect_functors(goal, &mut stack);
+                        }
+                    }
+                }
+            }
+        }
+        false
     }
 
     pub fn num_rules(&self) -> usize {
@@ -159,278 +773,859 @@ impl RuleEngine {
     }
 
     pub fn query(&mut self, goal: &Term) -> Vec<Substitution> {
-        let sub = Substitution::new();
-        self.solve(goal, &sub, 0).unwrap_or_default()
+        self.query_all(std::slice::from_ref(goal))
+    }
+
+    // Like query, but refuses calls to unknown predicates when the engine
+    // runs with UnknownPredicates::Error
+    pub fn query_checked(&mut self, goal: &Term) -> Result<Vec<Substitution>> {
+        let previous = std::mem::take(&mut self.signature_warnings);
+        let results = self.query(goal);
+        let raised = std::mem::replace(&mut self.signature_warnings, previous);
+        for w in &raised {
+            if !self.signature_warnings.contains(w) {
+                self.signature_warnings.push(w.clone());
+            }
+        }
+        if self.unknown_predicates == UnknownPredicates::Error {
+            if let Some(SignatureError::UnknownPredicate { functor, arity }) = raised.first() {
+                return Err(KolossError::NoRuleMatch(format!("unknown predicate {}/{}", functor, arity)));
+            }
+        }
+        Ok(results)
+    }
+
+    // Failure analysis: None if the goal has a solution, otherwise the
+    // deepest failing subgoals and why they failed
+    pub fn why_not(&mut self, goal: &Term) -> Option<FailureTrace> {
+        self.why_not_all(std::slice::from_ref(goal))
+    }
+
+    pub fn why_not_all(&mut self, goals: &[Term]) -> Option<FailureTrace> {
+        let previous = self.failure_log.replace(FailureTrace::new(goals));
+        self.start_query(goals);
+        let found = !self.solve_goals(goals, &Substitution::new(), 0, Some(1)).is_empty();
+        let trace = std::mem::replace(&mut self.failure_log, previous);
+        if found { None } else { trace }
     }
 
     pub fn query_first(&mut self, goal: &Term) -> Option<Substitution> {
-        let sub = Substitution::new();
-        self.solve_first(goal, &sub, 0)
+        self.solve_query(std::slice::from_ref(goal), Some(1)).into_iter().next()
     }
 
     pub fn query_all(&mut self, goals: &[Term]) -> Vec<Substitution> {
-        let sub = Substitution::new();
-        self.solve_conjunction(goals, &sub, 0).unwrap_or_default()
+        self.solve_query(goals, None)
     }
 
-    // Core solver — returns Err(CutSignal) if cut encountered
-    fn solve(&mut self, goal: &Term, sub: &Substitution, depth: usize) -> std::result::Result<Vec<Substitution>, CutSignal> {
-        if depth > self.max_depth {
-            return Ok(Vec::new());
+    // Top-level entry point: applies the search strategy
+    fn solve_query(&mut self, goals: &[Term], limit: Option<usize>) -> Vec<Substitution> {
+        self.depth_cutoffs = 0;
+        self.start_query(goals);
+        let sub = Substitution::new();
+        match self.strategy {
+            SearchStrategy::IterativeDeepening { step } => self.solve_iterative(goals, step.max(1), limit),
+            _ => self.solve_goals(goals, &sub, 0, limit),
         }
+    }
 
-        let resolved = sub.apply(goal);
-
-        // Check NAF: \+(Goal) or not(Goal)
-        if let Term::Compound(f, args) = &resolved {
-            if args.len() == 1 {
-                let is_not = self.not_sym.map_or(false, |s| *f == s);
-                let is_naf = self.naf_sym.map_or(false, |s| *f == s);
-                if is_not || is_naf {
-                    return Ok(self.solve_naf(&args[0], sub, depth));
+    fn solve_iterative(&mut self, goals: &[Term], step: usize, limit: Option<usize>) -> Vec<Substitution> {
+        let max_depth = self.max_depth;
+        let mut bound = step.min(max_depth);
+        let mut seen: FxHashSet<Vec<Term>> = FxHashSet::default();
+        let mut solutions = Vec::new();
+        loop {
+            self.max_depth = bound;
+            self.depth_cutoffs = 0;
+            for s in self.solve_goals(goals, &Substitution::new(), 0, None) {
+                let answer: Vec<Term> = goals.iter().map(|g| s.apply(g)).collect();
+                if seen.insert(answer) {
+                    solutions.push(s);
+                    if limit.is_some_and(|l| solutions.len() >= l) {
+                        self.max_depth = max_depth;
+                        return solutions;
+                    }
                 }
             }
+            if self.depth_cutoffs == 0 || bound >= max_depth {
+                break;
+            }
+            bound = (bound + step).min(max_depth);
         }
+        self.max_depth = max_depth;
+        solutions
+    }
 
-        // Check builtins
-        if let Term::Compound(f, args) = &resolved {
-            if self.builtins.is_builtin(*f) {
-                return self.solve_builtin(*f, args, sub);
-            }
+    // Run a conjunction to exhaustion (or until `limit` solutions are found)
+    fn solve_goals(&mut self, goals: &[Term], sub: &Substitution, depth: usize, limit: Option<usize>) -> Vec<Substitution> {
+        let stack = vec![Choice { cont: push_goals(goals, depth, 0, None), sub: sub.clone() }];
+        self.run(stack, limit)
+    }
+
+    fn run(&mut self, mut stack: Vec<Choice>, limit: Option<usize>) -> Vec<Substitution> {
+        if self.strategy == SearchStrategy::BreadthFirst {
+            return self.run_breadth_first(stack, limit);
         }
+        let mut solutions = Vec::new();
 
-        // Check tabling
-        if self.tabling_enabled {
-            if let Term::Compound(f, _) = &resolved {
-                if self.tabled_functors.contains(f) {
-                    if let Some(cached) = self.table.get(&resolved) {
-                        return Ok(cached.clone());
+        while let Some(mut choice) = stack.pop() {
+            if choice.sub.has_attrs() {
+                choice.cont = self.wake(&mut choice.sub, choice.cont, stack.len());
+            }
+            let node = match choice.cont {
+                None => {
+                    solutions.push(choice.sub);
+                    if limit.is_some_and(|l| solutions.len() >= l) {
+                        break;
                     }
+                    continue;
                 }
-            }
+                Some(node) => node,
+            };
+            self.resolve(&node, choice.sub, &mut stack);
         }
+        solutions
+    }
 
-        let mut results = Vec::new();
+    // One resolution step, with the tracer, profiler and failure analysis
+    // that are switched on
+    fn resolve(&mut self, node: &GoalNode, sub: Substitution, stack: &mut Vec<Choice>) {
+        if self.tracer.is_some() || self.profile.is_some() {
+            self.instrumented_step(node, sub, stack);
+        } else {
+            self.logged_step(node, sub, stack);
+        }
+    }
 
-        // Facts
-        for fact in self.facts.clone() {
-            if let Ok(s) = unify(&resolved, &fact, sub) {
-                results.push(s);
-            }
+    // Failure analysis: a goal that pushed no alternative failed
+    // (cut always succeeds, but may leave the stack shorter)
+    fn logged_step(&mut self, node: &GoalNode, sub: Substitution, stack: &mut Vec<Choice>) {
+        if self.failure_log.is_none() {
+            self.step(node, sub, stack);
+            return;
         }
+        let goal = sub.apply(&node.goal);
+        let before = stack.len();
+        self.step(node, sub, stack);
+        if stack.len() <= before && !self.is_cut(&goal) {
+            self.record_failure(goal, node.depth);
+        }
+    }
 
-        // Rules
-        let rules: Vec<Rule> = self.rules.clone();
-        let mut cut = false;
-        for rule in &rules {
-            if cut { break; }
-            self.var_counter += 100;
-            let renamed = rule.rename(self.var_counter);
+    // FIFO exploration. Each step pushes onto an empty scratch stack, so cut
+    // finds nothing to truncate and simply succeeds.
+    fn run_breadth_first(&mut self, stack: Vec<Choice>, limit: Option<usize>) -> Vec<Substitution> {
+        let mut queue: std::collections::VecDeque<Choice> = stack.into();
+        let mut scratch = Vec::new();
+        let mut solutions = Vec::new();
+        while let Some(mut choice) = queue.pop_front() {
+            if choice.sub.has_attrs() {
+                choice.cont = self.wake(&mut choice.sub, choice.cont, 0);
+            }
+            match choice.cont {
+                None => {
+                    solutions.push(choice.sub);
+                    if limit.is_some_and(|l| solutions.len() >= l) {
+                        break;
+                    }
+                }
+                Some(node) => {
+                    self.resolve(&node, choice.sub, &mut scratch);
+                    queue.extend(scratch.drain(..).rev());
+                }
+            }
+        }
+        solutions
+    }
 
-            if let Ok(s) = unify(&resolved, &renamed.head, sub) {
-                if renamed.body.is_empty() {
-                    results.push(s);
-                } else {
-                    match self.solve_conjunction(&renamed.body, &s, depth + 1) {
-                        Ok(body_results) => results.extend(body_results),
-                        Err(CutSignal) => {
-                            // Cut propagates: stop trying more rules, keep results found so far
-                            // But we need to also get results from the cut branch
-                            // Re-run but capture partial results up to cut
-                            let partial = self.solve_conjunction_with_cut(&renamed.body, &s, depth + 1);
-                            results.extend(partial);
-                            cut = true;
-                        }
+    // Tracing and profiling thread marker goals through the continuation: a
+    // Fail marker choicepoint below the goal's alternatives, Redo markers on
+    // all but the first alternative and an Exit marker in front of the goal's
+    // continuation. Markers carry the goal and its profiler call id (Nil when
+    // not profiled), and are transparent to cut barriers. Breadth-first
+    // search reaches a Fail marker once the call's alternatives are queued,
+    // so its ports interleave with those of other branches.
+    fn instrumented_step(&mut self, node: &GoalNode, sub: Substitution, stack: &mut Vec<Choice>) {
+        if let Term::Compound(marker, args) = &node.goal {
+            // length/2 retries belong to the call already reported
+            if *marker == LENGTH_FROM {
+                self.logged_step(node, sub, stack);
+                return;
+            }
+            if let Some(port) = Port::from_marker(*marker) {
+                self.report(port, &args[0], node.depth, &sub);
+                if let (Term::Int(call), Some(profile)) = (&args[1], &mut self.profile) {
+                    match port {
+                        Port::Exit => profile.exit(*call as usize),
+                        Port::Fail => profile.fail(*call as usize),
+                        Port::Call | Port::Redo => {}
                     }
                 }
+                if port != Port::Fail {
+                    stack.push(Choice { cont: node.next.clone(), sub });
+                }
+                return;
             }
         }
+        self.report(Port::Call, &node.goal, node.depth, &sub);
+        let call = match (&mut self.profile, predicate_key(&sub.walk(&node.goal))) {
+            (Some(profile), Some(key)) => Term::int(profile.call(key) as i64),
+            _ => Term::Nil,
+        };
+        let marker = |port: Sym, next: Cont| {
+            Some(Rc::new(GoalNode {
+                goal: Term::compound(port, vec![node.goal.clone(), call.clone()]),
+                depth: node.depth,
+                cut_barrier: node.cut_barrier,
+                next,
+            }))
+        };
+        stack.push(Choice { cont: marker(debugger::FAIL_PORT, None), sub: sub.clone() });
+        let before = stack.len();
+        let wrapped = GoalNode {
+            goal: node.goal.clone(),
+            depth: node.depth,
+            cut_barrier: node.cut_barrier,
+            next: marker(debugger::EXIT_PORT, node.next.clone()),
+        };
+        self.logged_step(&wrapped, sub, stack);
+        if stack.len() > before + 1 {
+            let last = stack.len() - 1;
+            for choice in &mut stack[before..last] {
+                choice.cont = marker(debugger::REDO_PORT, choice.cont.take());
+            }
+        }
+    }
 
-        // Cache if tabled
-        if self.tabling_enabled {
-            if let Term::Compound(f, _) = &resolved {
-                if self.tabled_functors.contains(f) {
-                    self.table.insert(&resolved, results.clone());
+    fn report(&mut self, port: Port, goal: &Term, depth: usize, sub: &Substitution) {
+        if self.tracer.is_none() {
+            return;
+        }
+        let goal = sub.apply(goal);
+        if !self.spy_points.reports(&goal) {
+            return;
+        }
+        if let Some(ActiveTracer(tracer)) = &self.tracer {
+            let Ok(mut tracer) = tracer.lock() else { return };
+            match port {
+                Port::Call => tracer.call(&goal, depth, sub),
+                Port::Exit => tracer.exit(&goal, depth, sub),
+                Port::Redo => tracer.redo(&goal, depth, sub),
+                Port::Fail => tracer.fail(&goal, depth, sub),
+            }
+        }
+    }
+
+    // Goals woken by the last binding step run before the continuation:
+    // frozen goals of bound variables and attribute hooks. A variable bound
+    // to another unbound variable hands its attributes over.
+    fn wake(&self, sub: &mut Substitution, cont: Cont, barrier: usize) -> Cont {
+        let vars = sub.bound_attributed_vars();
+        if vars.is_empty() {
+            return cont;
+        }
+        let freeze = self.builtins.sym_of(builtins::BUILTIN_FREEZE);
+        let mut goals = Vec::new();
+        for v in vars {
+            let value = sub.walk(&Term::Var(v));
+            for (module, attr) in sub.take_attrs(v) {
+                let hook = self.attr_hooks.get(&module).copied();
+                match (&value, hook) {
+                    (Term::Var(w), _) if Some(module) == freeze => {
+                        let mut frozen = list_goals(sub.get_attr(*w, module));
+                        frozen.extend(list_goals(Some(&attr)));
+                        sub.put_attr(*w, module, Term::List(frozen));
+                    }
+                    (Term::Var(w), Some(hook)) if sub.get_attr(*w, module).is_some() => {
+                        goals.push(Term::compound(hook, vec![attr, value.clone()]));
+                    }
+                    (Term::Var(w), _) => sub.put_attr(*w, module, attr),
+                    _ if Some(module) == freeze => goals.extend(list_goals(Some(&attr))),
+                    (_, Some(hook)) => goals.push(Term::compound(hook, vec![attr, value.clone()])),
+                    _ => {}
                 }
             }
         }
+        let depth = cont.as_ref().map_or(0, |n| n.depth);
+        push_goals(&goals, depth, barrier, cont)
+    }
 
-        Ok(results)
+    // Add `goal` to the goals frozen on `var`
+    fn suspend(&self, sub: &mut Substitution, var: Sym, goal: Term) -> bool {
+        let Some(freeze) = self.builtins.sym_of(builtins::BUILTIN_FREEZE) else { return false };
+        let mut frozen = list_goals(sub.get_attr(var, freeze));
+        frozen.push(goal);
+        sub.put_attr(var, freeze, Term::List(frozen));
+        true
     }
 
-    fn solve_first(&mut self, goal: &Term, sub: &Substitution, depth: usize) -> Option<Substitution> {
-        if depth > self.max_depth {
-            return None;
+    // Condition of when/2: Ok if it holds, Err(var) to wait on var.
+    // Supports nonvar/1, ground/1 and ','/2; None for anything else.
+    fn when_condition(&self, cond: &Term, sub: &Substitution) -> Option<std::result::Result<(), Sym>> {
+        let (f, args) = match sub.walk(cond) {
+            Term::Compound(f, args) => (f, args),
+            _ => return None,
+        };
+        match (self.builtins.name_of(f)?, args.len()) {
+            (builtins::BUILTIN_NONVAR, 1) => Some(match sub.walk(&args[0]) {
+                Term::Var(v) => Err(v),
+                _ => Ok(()),
+            }),
+            (builtins::BUILTIN_GROUND, 1) => Some(match sub.apply(&args[0]).vars().first() {
+                Some(v) => Err(*v),
+                None => Ok(()),
+            }),
+            (builtins::BUILTIN_CONJ, 2) => match self.when_condition(&args[0], sub)? {
+                Ok(()) => self.when_condition(&args[1], sub),
+                Err(v) => Some(Err(v)),
+            },
+            _ => None,
         }
+    }
 
-        let resolved = sub.apply(goal);
+    fn is_cut(&self, goal: &Term) -> bool {
+        matches!(goal, Term::Compound(f, args) if args.is_empty() && self.builtins.name_of(*f) == Some(builtins::BUILTIN_CUT))
+    }
 
-        // NAF
-        if let Term::Compound(f, args) = &resolved {
-            if args.len() == 1 {
-                let is_not = self.not_sym.map_or(false, |s| *f == s);
-                let is_naf = self.naf_sym.map_or(false, |s| *f == s);
-                if is_not || is_naf {
-                    let naf_results = self.solve_naf(&args[0], sub, depth);
-                    return naf_results.into_iter().next();
-                }
-            }
+    fn record_failure(&mut self, goal: Term, depth: usize) {
+        let reason = match &goal {
+            _ if depth > self.max_depth => FailureReason::DepthLimit,
+            Term::Compound(f, args) if args.len() == 1 && self.is_negation(*f) => FailureReason::NegatedGoalSucceeded,
+            Term::Compound(f, _) if self.builtins.is_builtin(*f) => FailureReason::BuiltinFailed,
+            Term::Compound(f, args) => self.clause_failure(*f, args.len()),
+            Term::Atom(f) => self.clause_failure(*f, 0),
+            _ => FailureReason::BuiltinFailed,
+        };
+        if let Some(trace) = &mut self.failure_log {
+            trace.record(goal, depth, reason);
         }
+    }
+
+    fn clause_failure(&self, f: Sym, arity: usize) -> FailureReason {
+        let matches = |t: &Term| match t {
+            Term::Compound(g, args) => *g == f && args.len() == arity,
+            Term::Atom(g) => *g == f && arity == 0,
+            _ => false,
+        };
+        let rules: Vec<usize> = self.rules.iter().enumerate()
+            .filter(|(_, r)| matches(&r.head))
+            .map(|(i, _)| i)
+            .collect();
+        let facts = self.facts.iter().filter(|t| matches(t)).count();
+        if rules.is_empty() && facts == 0 {
+            FailureReason::UnknownPredicate
+        } else {
+            FailureReason::NoMatchingClause { rules, facts }
+        }
+    }
+
+    // Resolve the first goal of a continuation, pushing one choicepoint per alternative
+    fn step(&mut self, node: &GoalNode, sub: Substitution, stack: &mut Vec<Choice>) {
+        let depth = node.depth;
+        if depth > self.max_depth {
+            self.depth_cutoffs += 1;
+            return;
+        }
+        let resolved = sub.apply(&node.goal);
+        let rest = &node.next;
 
-        // Builtins
         if let Term::Compound(f, args) = &resolved {
-            if self.builtins.is_builtin(*f) {
-                if let Ok(results) = self.solve_builtin(*f, args, sub) {
-                    return results.into_iter().next();
+            // Cut: commit to this clause
+            if args.is_empty() && self.builtins.name_of(*f) == Some(builtins::BUILTIN_CUT) {
+                stack.truncate(node.cut_barrier);
+                stack.push(Choice { cont: rest.clone(), sub });
+                return;
+            }
+
+            // call/1..8: extend the goal with the extra arguments and run it.
+            // Cut inside the called goal is local to it.
+            if (1..=8).contains(&args.len()) && self.builtins.name_of(*f) == Some(builtins::BUILTIN_CALL) {
+                if let Some(goal) = add_args(&args[0], &args[1..]) {
+                    let cont = Some(Rc::new(GoalNode { goal, depth: depth + 1, cut_barrier: stack.len(), next: rest.clone() }));
+                    stack.push(Choice { cont, sub });
                 }
-                return None;
+                return;
             }
-        }
 
-        // Facts
-        for fact in self.facts.clone() {
-            if let Ok(s) = unify(&resolved, &fact, sub) {
-                return Some(s);
+            // length(L, N) with L unbound: a list of N fresh variables, or
+            // with N unbound too, lists of growing length up to the remaining
+            // depth budget, one choicepoint at a time
+            if args.len() == 3 && *f == LENGTH_FROM {
+                let Term::Int(from) = args[2] else { return };
+                let limit = self.max_depth.saturating_sub(depth).min(builtins::MAX_NUMLIST_LEN as usize);
+                if (from as usize) < limit {
+                    let goal = Term::compound(LENGTH_FROM, vec![args[0].clone(), args[1].clone(), Term::int(from + 1)]);
+                    let cont = Some(Rc::new(GoalNode { goal, depth, cut_barrier: node.cut_barrier, next: rest.clone() }));
+                    stack.push(Choice { cont, sub: sub.clone() });
+                }
+                if let Some(s) = self.fresh_list(&args[0], &args[1], from, &sub) {
+                    stack.push(Choice { cont: rest.clone(), sub: s });
+                }
+                return;
+            }
+            let name = self.builtins.name_of(*f);
+            if args.len() == 2 && name == Some(builtins::BUILTIN_LENGTH) && matches!(sub.walk(&args[0]), Term::Var(_)) {
+                match sub.walk(&args[1]) {
+                    Term::Int(n) if (0..=builtins::MAX_NUMLIST_LEN).contains(&n) => {
+                        if let Some(s) = self.fresh_list(&args[0], &args[1], n, &sub) {
+                            stack.push(Choice { cont: rest.clone(), sub: s });
+                        }
+                    }
+                    Term::Var(_) => {
+                        let goal = Term::compound(LENGTH_FROM, vec![args[0].clone(), args[1].clone(), Term::int(0)]);
+                        let cont = Some(Rc::new(GoalNode { goal, depth, cut_barrier: node.cut_barrier, next: rest.clone() }));
+                        stack.push(Choice { cont, sub });
+                    }
+                    _ => {}
+                }
+                return;
             }
-        }
 
-        // Rules
-        let rules: Vec<Rule> = self.rules.clone();
-        for rule in &rules {
-            self.var_counter += 100;
-            let renamed = rule.rename(self.var_counter);
+            // ','(A, B): run A then B. Cut inside either is the clause's cut.
+            if args.len() == 2 && name == Some(builtins::BUILTIN_CONJ) {
+                let barrier = node.cut_barrier;
+                let then = Rc::new(GoalNode { goal: args[1].clone(), depth: depth + 1, cut_barrier: barrier, next: rest.clone() });
+                let cont = Some(Rc::new(GoalNode { goal: args[0].clone(), depth: depth + 1, cut_barrier: barrier, next: Some(then) }));
+                stack.push(Choice { cont, sub });
+                return;
+            }
 
-            if let Ok(s) = unify(&resolved, &renamed.head, sub) {
-                if renamed.body.is_empty() {
-                    return Some(s);
+            // freeze(X, Goal) and when(Cond, Goal): run Goal now, or suspend
+            // it on a variable until the binding that wakes it
+            if args.len() == 2 && matches!(name, Some(builtins::BUILTIN_FREEZE | builtins::BUILTIN_WHEN)) {
+                let is_when = name == Some(builtins::BUILTIN_WHEN);
+                let wait = if is_when {
+                    self.when_condition(&args[0], &sub)
+                } else {
+                    match sub.walk(&args[0]) {
+                        Term::Var(v) => Some(Err(v)),
+                        _ => Some(Ok(())),
+                    }
+                };
+                match wait {
+                    Some(Ok(())) => {
+                        let goal = args[1].clone();
+                        let cont = Some(Rc::new(GoalNode { goal, depth: depth + 1, cut_barrier: stack.len(), next: rest.clone() }));
+                        stack.push(Choice { cont, sub });
+                    }
+                    Some(Err(v)) => {
+                        // when/2 re-checks its whole condition on wake-up
+                        let goal = if is_when { resolved.clone() } else { args[1].clone() };
+                        let mut sub = sub;
+                        if self.suspend(&mut sub, v, goal) {
+                            stack.push(Choice { cont: rest.clone(), sub });
+                        }
+                    }
+                    None => {}
                 }
-                if let Some(result) = self.solve_conjunction_first(&renamed.body, &s, depth + 1) {
-                    return Some(result);
+                return;
+            }
+
+            // NAF: \+(Goal) or not(Goal)
+            if args.len() == 1 && self.is_negation(*f) {
+                // Failures inside a negation are what makes it succeed
+                let log = self.failure_log.take();
+                let proved = !self.solve_goals(&args[..1], &sub, depth + 1, Some(1)).is_empty();
+                self.failure_log = log;
+                if !proved {
+                    stack.push(Choice { cont: rest.clone(), sub });
                 }
+                return;
             }
         }
 
-        None
-    }
+        // Aggregates run their inner goal to exhaustion before binding
+        if let Some(results) = self.solve_aggregate(&resolved, &sub, depth) {
+            push_alternatives(stack, rest, results);
+            return;
+        }
 
-    // Negation as Failure: \+(Goal) succeeds iff Goal has no solutions
-    fn solve_naf(&mut self, inner_goal: &Term, sub: &Substitution, depth: usize) -> Vec<Substitution> {
-        let results = self.solve(inner_goal, sub, depth + 1).unwrap_or_default();
-        if results.is_empty() {
-            // Goal failed → negation succeeds (with original substitution, no new bindings)
-            vec![sub.clone()]
-        } else {
-            // Goal succeeded → negation fails
-            Vec::new()
+        if let Some(results) = self.solve_temporal(&resolved, &sub, depth) {
+            push_alternatives(stack, rest, results);
+            return;
         }
-    }
 
-    fn solve_builtin(&mut self, functor: Sym, args: &[Term], sub: &Substitution) -> std::result::Result<Vec<Substitution>, CutSignal> {
-        match eval_builtin(functor, args, sub, &self.builtins) {
-            Some(BuiltinResult::Success(s)) => Ok(vec![s]),
-            Some(BuiltinResult::Fail) => Ok(Vec::new()),
-            Some(BuiltinResult::Cut) => Err(CutSignal),
-            Some(BuiltinResult::Multi(subs)) => Ok(subs),
-            None => Ok(Vec::new()),
+        if let Some(results) = self.solve_meta(&resolved, &sub) {
+            push_alternatives(stack, rest, results);
+            return;
         }
-    }
 
-    fn solve_conjunction(&mut self, goals: &[Term], sub: &Substitution, depth: usize) -> std::result::Result<Vec<Substitution>, CutSignal> {
-        if goals.is_empty() {
-            return Ok(vec![sub.clone()]);
+        // Builtins
+        if let Term::Compound(f, args) = &resolved {
+            if self.builtins.is_builtin(*f) {
+                match eval_builtin(*f, args, &sub, &self.builtins) {
+                    Some(BuiltinResult::Success(s)) => stack.push(Choice { cont: rest.clone(), sub: s }),
+                    Some(BuiltinResult::Multi(subs)) => push_alternatives(stack, rest, subs),
+                    Some(BuiltinResult::Cut) => {
+                        stack.truncate(node.cut_barrier);
+                        stack.push(Choice { cont: rest.clone(), sub });
+                    }
+                    Some(BuiltinResult::Fail) | None => {}
+                }
+                return;
+            }
+        }
+
+        // Tabling: answers are computed once per call variant and replayed
+        if let Some(answers) = self.tabled_answers(&resolved, depth) {
+            let results = answers.iter()
+                .filter_map(|a| unify(&resolved, &self.fresh_copy(a), &sub).ok())
+                .collect();
+            push_alternatives(stack, rest, results);
+            return;
         }
-        let first = sub.apply(&goals[0]);
-        let rest = &goals[1..];
-        let mut results = Vec::new();
 
-        // Check if first goal is a cut
-        if let Term::Compound(f, args) = &first {
-            if args.is_empty() && self.builtins.name_of(*f) == Some("!") {
-                // Cut: succeed once, then signal cut to parent
-                let rest_results = self.solve_conjunction(rest, sub, depth)?;
-                results.extend(rest_results);
-                return Err(CutSignal);
+        // Runtime mode assertions: count the call's solutions in isolation
+        if self.mode_checks {
+            if let Some(results) = self.checked_call(&resolved, &sub, depth) {
+                push_alternatives(stack, rest, results);
+                return;
             }
         }
 
-        for s in self.solve(&first, sub, depth)? {
-            match self.solve_conjunction(rest, &s, depth) {
-                Ok(rest_results) => results.extend(rest_results),
-                Err(CutSignal) => return Err(CutSignal),
+        if self.unknown_predicates != UnknownPredicates::Fail && self.is_unknown_predicate(&resolved) {
+            let (functor, arity) = match &resolved {
+                Term::Compound(f, args) => (*f, args.len()),
+                Term::Atom(f) => (*f, 0),
+                _ => unreachable!(),
+            };
+            let warning = SignatureError::UnknownPredicate { functor, arity };
+            if !self.signature_warnings.contains(&warning) {
+                self.signature_warnings.push(warning);
             }
+            return;
         }
 
-        Ok(results)
+        self.push_clauses(&resolved, &sub, depth, rest, stack);
     }
 
-    // Variant that catches cut and returns partial results
-    fn solve_conjunction_with_cut(&mut self, goals: &[Term], sub: &Substitution, depth: usize) -> Vec<Substitution> {
-        if goals.is_empty() {
-            return vec![sub.clone()];
+    // Facts, then rules, explored in order
+    fn push_clauses(&mut self, resolved: &Term, sub: &Substitution, depth: usize, rest: &Cont, stack: &mut Vec<Choice>) {
+        let barrier = stack.len();
+        let mut alternatives = Vec::new();
+        let candidates = self.fact_index.candidates(resolved);
+        for &i in &candidates {
+            if let Ok(s) = unify(resolved, &self.facts[i], sub) {
+                alternatives.push(Choice { cont: rest.clone(), sub: s });
+            }
         }
-        let first = sub.apply(&goals[0]);
-        let rest = &goals[1..];
-        let mut results = Vec::new();
-
-        // Handle cut goal
-        if let Term::Compound(f, args) = &first {
-            if args.is_empty() && self.builtins.name_of(*f) == Some("!") {
-                results.extend(self.solve_conjunction_with_cut(rest, sub, depth));
-                return results;
+        let rules = self.rule_index.candidates(resolved);
+        if let (Some(profile), Some((f, arity))) = (&mut self.profile, predicate_key(resolved)) {
+            profile.entry(f, arity).unifications += (candidates.len() + rules.len()) as u64;
+        }
+        for i in rules {
+            let renamed = self.renamed_rule(i);
+            if let Ok(s) = unify(resolved, &renamed.head, sub) {
+                let cont = push_goals(&renamed.body, depth + 1, barrier, rest.clone());
+                alternatives.push(Choice { cont, sub: s });
             }
         }
+        stack.extend(alternatives.into_iter().rev());
+    }
+
+    // Rule `i` with its variables renamed apart from every other variable
+    // of the current query
+    fn renamed_rule(&mut self, i: usize) -> Rule {
+        let offset = self.fresh_vars(self.rule_index.span(i));
+        self.rules[i].rename(offset)
+    }
+
+    // Reserve `n` fresh variable ids, returning the first
+    fn fresh_vars(&mut self, n: Sym) -> Sym {
+        let first = self.var_counter;
+        self.var_counter += n;
+        first
+    }
 
-        let first_results = self.solve(&first, sub, depth).unwrap_or_default();
-        for s in first_results {
-            results.extend(self.solve_conjunction_with_cut(rest, &s, depth));
+    // Restart the fresh variable ids past every variable of a new top-level
+    // query, and the profiler's call ids. Terms kept across queries (answer
+    // tables, globals) are copied with fresh variables when they are used.
+    fn start_query(&mut self, goals: &[Term]) {
+        let highest = goals.iter().flat_map(|g| g.vars()).max();
+        self.var_counter = highest.map_or(FIRST_FRESH_VAR, |v| FIRST_FRESH_VAR.max(v + 1));
+        if let Some(profile) = &mut self.profile {
+            profile.end_calls();
         }
+    }
 
-        results
+    // `term` with its variables replaced by fresh ones
+    fn fresh_copy(&mut self, term: &Term) -> Term {
+        let vars = term.vars();
+        if vars.is_empty() {
+            return term.clone();
+        }
+        let first = self.fresh_vars(vars.len() as Sym);
+        let fresh: FxHashMap<Sym, Term> = vars.iter().enumerate()
+            .map(|(i, v)| (*v, Term::Var(first + i as Sym)))
+            .collect();
+        replace_vars(term, &fresh)
     }
 
-    fn solve_conjunction_first(&mut self, goals: &[Term], sub: &Substitution, depth: usize) -> Option<Substitution> {
-        if goals.is_empty() {
-            return Some(sub.clone());
+    fn checked_call(&mut self, goal: &Term, sub: &Substitution, depth: usize) -> Option<Vec<Substitution>> {
+        let decl = match goal {
+            Term::Compound(f, _) => self.modes.get(*f)?.clone(),
+            _ => return None,
+        };
+        self.mode_violations.extend(self.modes.check_call(goal));
+
+        let mut stack = Vec::new();
+        self.push_clauses(goal, sub, depth, &None, &mut stack);
+        let results = self.run(stack, None);
+
+        if !decl.det.allows(results.len()) {
+            self.mode_violations.push(ModeViolation::Determinism {
+                functor: decl.functor,
+                declared: decl.det,
+                solutions: results.len(),
+            });
         }
-        let first = sub.apply(&goals[0]);
-        let rest = &goals[1..];
+        Some(results)
+    }
 
-        // Handle cut goal
-        if let Term::Compound(f, args) = &first {
-            if args.is_empty() && self.builtins.name_of(*f) == Some("!") {
-                return self.solve_conjunction_first(rest, sub, depth);
+    fn tabled_answers(&mut self, goal: &Term, depth: usize) -> Option<Vec<Term>> {
+        let f = match goal {
+            Term::Compound(f, _) if self.tabling_enabled && self.tabled_functors.contains(f) => *f,
+            _ => return None,
+        };
+        if let Some(cached) = self.table.get(goal) {
+            return Some(cached.clone());
+        }
+        // Resolve the call once, bypassing the table for this variant
+        let saved = std::mem::take(&mut self.tabled_functors);
+        let results = self.solve_goals(std::slice::from_ref(goal), &Substitution::new(), depth, None);
+        self.tabled_functors = saved;
+
+        let mut answers: Vec<Term> = Vec::new();
+        for s in results {
+            let answer = s.apply(goal);
+            if !answers.contains(&answer) {
+                answers.push(answer);
             }
         }
+        self.table.insert(goal, f, answers.clone());
+        Some(answers)
+    }
+
+    // Bind `list` to `n` fresh variables and `len` to n; n is at most
+    // MAX_NUMLIST_LEN
+    fn fresh_list(&mut self, list: &Term, len: &Term, n: i64, sub: &Substitution) -> Option<Substitution> {
+        let first = self.fresh_vars(n as Sym);
+        let items = (first..first + n as Sym).map(Term::Var).collect();
+        unify(list, &Term::List(items), sub).and_then(|s| unify(len, &Term::Int(n), &s)).ok()
+    }
 
-        for s in self.solve(&first, sub, depth).unwrap_or_default() {
-            if let Some(result) = self.solve_conjunction_first(rest, &s, depth) {
-                return Some(result);
+    // Builtins that need engine state: copy_term/2 draws fresh variables,
+    // nb_setval/nb_getval use the globals
+    fn solve_meta(&mut self, goal: &Term, sub: &Substitution) -> Option<Vec<Substitution>> {
+        let (f, args) = match goal {
+            Term::Compound(f, args) => (*f, args),
+            _ => return None,
+        };
+        match (self.builtins.name_of(f)?, args.len()) {
+            (builtins::BUILTIN_COPY_TERM, 2) => {
+                let copy = self.fresh_copy(&sub.apply(&args[0]));
+                Some(unify(&args[1], &copy, sub).into_iter().collect())
+            }
+            // nb_setval(Key, Value): not undone on backtracking
+            (builtins::BUILTIN_NB_SETVAL, 2) => match sub.apply(&args[0]) {
+                Term::Atom(key) => {
+                    self.globals.insert(key, sub.apply(&args[1]));
+                    Some(vec![sub.clone()])
+                }
+                _ => Some(Vec::new()),
+            },
+            (builtins::BUILTIN_NB_GETVAL, 2) => match sub.apply(&args[0]) {
+                Term::Atom(key) => {
+                    let value = self.globals.get(&key).cloned().map(|v| self.fresh_copy(&v));
+                    Some(value.and_then(|v| unify(&args[1], &v, sub).ok()).into_iter().collect())
+                }
+                _ => Some(Vec::new()),
+            },
+            // Attributed variables: put_attr(Var, Module, Value),
+            // get_attr(Var, Module, Value), del_attr(Var, Module)
+            (builtins::BUILTIN_PUT_ATTR, 3) => match (sub.walk(&args[0]), sub.walk(&args[1])) {
+                (Term::Var(v), Term::Atom(module)) => {
+                    let mut s = sub.clone();
+                    s.put_attr(v, module, sub.apply(&args[2]));
+                    Some(vec![s])
+                }
+                _ => Some(Vec::new()),
+            },
+            (builtins::BUILTIN_GET_ATTR, 3) => match (sub.walk(&args[0]), sub.walk(&args[1])) {
+                (Term::Var(v), Term::Atom(module)) => {
+                    let value = sub.get_attr(v, module).cloned();
+                    Some(value.and_then(|a| unify(&args[2], &a, sub).ok()).into_iter().collect())
+                }
+                _ => Some(Vec::new()),
+            },
+            (builtins::BUILTIN_DEL_ATTR, 2) => match (sub.walk(&args[0]), sub.walk(&args[1])) {
+                (Term::Var(v), Term::Atom(module)) => {
+                    let mut s = sub.clone();
+                    s.del_attr(v, module);
+                    Some(vec![s])
+                }
+                _ => Some(vec![sub.clone()]),
+            },
+            // frozen(X, Goals): the list of goals suspended on X
+            (builtins::BUILTIN_FROZEN, 2) => {
+                let goals = match sub.walk(&args[0]) {
+                    Term::Var(v) => list_goals(sub.get_attr(v, self.builtins.sym_of(builtins::BUILTIN_FREEZE)?)),
+                    _ => Vec::new(),
+                };
+                Some(unify(&args[1], &Term::List(goals), sub).into_iter().collect())
             }
+            _ => None,
         }
+    }
+
+    // now(T), holds_at(Fact, T), holds_now(Fact)
+    fn solve_temporal(&mut self, goal: &Term, sub: &Substitution, depth: usize) -> Option<Vec<Substitution>> {
+        let (f, args) = match goal {
+            Term::Compound(f, args) => (*f, args),
+            _ => return None,
+        };
+        let name = self.builtins.name_of(f)?;
+        let now = Term::Int(self.tick as i64);
+        let (fact, at) = match (name, args.len()) {
+            (builtins::BUILTIN_NOW, 1) => return Some(unify(&args[0], &now, sub).into_iter().collect()),
+            (builtins::BUILTIN_HOLDS_AT, 2) => (&args[0], sub.apply(&args[1])),
+            (builtins::BUILTIN_HOLDS_NOW, 1) => (&args[0], now),
+            _ => return None,
+        };
+        let holds = self.holds_sym?;
+        let t = match at {
+            Term::Int(n) => n as f64,
+            Term::Float(x) => x.val(),
+            _ => return Some(Vec::new()),
+        };
+        let first = self.fresh_vars(2);
+        let (from, to) = (Term::var(first), Term::var(first + 1));
+        let query = Term::compound(holds, vec![fact.clone(), from.clone(), to.clone()]);
+        let results = self.solve_goals(std::slice::from_ref(&query), sub, depth + 1, None)
+            .into_iter()
+            .filter(|s| temporal::validity(&s.apply(&from), &s.apply(&to)).is_some_and(|i| i.contains_point(t)))
+            .collect();
+        Some(results)
+    }
+
+    // aggregate_all(Spec, Goal, Result), aggregate_all(Spec, Key, Goal, Groups)
+    // and findall(Template, Goal, List). Spec is one of count, count(X), sum(X),
+    // max(X), min(X), bag(X) or set(X). The grouped form binds Groups to a list
+    // of [Key, Result] pairs in first-seen key order.
+    fn solve_aggregate(&mut self, goal: &Term, sub: &Substitution, depth: usize) -> Option<Vec<Substitution>> {
+        let (f, args) = match goal {
+            Term::Compound(f, args) => (*f, args),
+            _ => return None,
+        };
+        let name = self.builtins.name_of(f)?;
+        let (spec, key, inner, target) = match (name, args.len()) {
+            (builtins::BUILTIN_AGGREGATE_ALL, 3) => (args[0].clone(), None, &args[1], &args[2]),
+            (builtins::BUILTIN_AGGREGATE_ALL, 4) => (args[0].clone(), Some(&args[1]), &args[2], &args[3]),
+            (builtins::BUILTIN_FINDALL, 3) => {
+                let bag = self.builtins.sym_of(builtins::BUILTIN_BAG)?;
+                (Term::compound(bag, vec![args[0].clone()]), None, &args[1], &args[2])
+            }
+            _ => return None,
+        };
+
+        let solutions = self.solve_goals(std::slice::from_ref(inner), sub, depth + 1, None);
 
-        None
+        let value = match key {
+            None => self.aggregate(&spec, &solutions),
+            Some(key) => {
+                let mut groups: Vec<(Term, Vec<Substitution>)> = Vec::new();
+                for s in solutions {
+                    let k = s.apply(key);
+                    match groups.iter_mut().find(|(gk, _)| *gk == k) {
+                        Some((_, members)) => members.push(s),
+                        None => groups.push((k, vec![s])),
+                    }
+                }
+                let mut pairs = Vec::with_capacity(groups.len());
+                for (k, members) in groups {
+                    match self.aggregate(&spec, &members) {
+                        Some(v) => pairs.push(Term::list(vec![k, v])),
+                        None => return Some(Vec::new()),
+                    }
+                }
+                Some(Term::list(pairs))
+            }
+        };
+
+        Some(match value {
+            Some(v) => unify(target, &v, sub).into_iter().collect(),
+            None => Vec::new(),
+        })
+    }
+
+    fn aggregate(&self, spec: &Term, solutions: &[Substitution]) -> Option<Term> {
+        let (name, template) = match spec {
+            Term::Atom(a) => (self.builtins.name_of(*a)?, None),
+            Term::Compound(f, a) if a.is_empty() => (self.builtins.name_of(*f)?, None),
+            Term::Compound(f, a) if a.len() == 1 => (self.builtins.name_of(*f)?, Some(&a[0])),
+            _ => return None,
+        };
+
+        let numbers = |t: &Term| -> Option<Vec<f64>> {
+            solutions.iter()
+                .map(|s| eval_arithmetic(&s.apply(t), s, &self.builtins))
+                .collect()
+        };
+
+        match (name, template) {
+            (builtins::BUILTIN_COUNT, _) => Some(Term::Int(solutions.len() as i64)),
+            (builtins::BUILTIN_SUM, Some(t)) => Some(term_from_number(numbers(t)?.iter().sum())),
+            (builtins::BUILTIN_MAX, Some(t)) => {
+                numbers(t)?.into_iter().reduce(f64::max).map(term_from_number)
+            }
+            (builtins::BUILTIN_MIN, Some(t)) => {
+                numbers(t)?.into_iter().reduce(f64::min).map(term_from_number)
+            }
+            (builtins::BUILTIN_BAG, Some(t)) => {
+                Some(Term::list(solutions.iter().map(|s| s.apply(t)).collect()))
+            }
+            (builtins::BUILTIN_SET, Some(t)) => {
+                let mut items: Vec<Term> = Vec::new();
+                for s in solutions {
+                    let item = s.apply(t);
+                    if !items.contains(&item) {
+                        items.push(item);
+                    }
+                }
+                Some(Term::list(items))
+            }
+            _ => None,
+        }
     }
 
     pub fn forward_chain(&mut self, max_iterations: usize) -> usize {
         let mut new_facts = 0;
         for _ in 0..max_iterations {
             let mut added = false;
-            let rules: Vec<Rule> = self.rules.clone();
+            let rules = Arc::clone(&self.rules);
 
-            for rule in &rules {
+            for (i, rule) in rules.iter().enumerate() {
                 if rule.body.is_empty() {
                     continue;
                 }
 
-                self.var_counter += 100;
-                let renamed = rule.rename(self.var_counter);
+                self.start_query(&[]);
+                let renamed = self.renamed_rule(i);
                 let sub = Substitution::new();
-                let solutions = self.solve_conjunction(&renamed.body, &sub, 0).unwrap_or_default();
+                let solutions = self.solve_goals(&renamed.body, &sub, 0, None);
 
                 for s in solutions {
                     let new_fact = s.apply(&renamed.head);
-                    if new_fact.is_ground() && !self.facts.contains(&new_fact) {
-                        self.facts.push(new_fact);
+                    if !new_fact.is_ground() {
+                        continue;
+                    }
+                    if self.tms.is_some() {
+                        let antecedents = self.antecedents(&renamed.body, &s);
+                        if let Some(tms) = &mut self.tms {
+                            tms.justify(&new_fact, &antecedents, Some(rule.id));
+                        }
+                    }
+                    if !self.has_fact(&new_fact) {
+                        self.insert_fact(new_fact);
                         new_facts += 1;
                         added = true;
                     }
@@ -444,20 +1639,97 @@ impl RuleEngine {
         new_facts
     }
 
+    // Instantiated body goals that are stored facts. Builtins and negated
+    // goals are not tracked: the TMS only records monotonic support.
+    fn antecedents(&self, body: &[Term], sub: &Substitution) -> Vec<Term> {
+        body.iter()
+            .filter(|g| match g {
+                Term::Compound(f, _) | Term::Atom(f) => !self.builtins.is_builtin(*f) && !self.is_negation(*f),
+                _ => false,
+            })
+            .map(|g| sub.apply(g))
+            .filter(|g| g.is_ground())
+            .collect()
+    }
+
     pub fn assert_fact(&mut self, fact: Term) -> Result<()> {
         if !fact.is_ground() {
             return Err(KolossError::InvalidTerm("fact must be ground".into()));
         }
-        if !self.facts.contains(&fact) {
-            self.facts.push(fact);
+        if let Some(err) = self.signatures.check_head(&fact, None).into_iter().next() {
+            return Err(KolossError::InvalidTerm(format!("{:?}", err)));
+        }
+        if !self.has_fact(&fact) {
+            self.add_fact(fact);
         }
         Ok(())
     }
 
+    // With a TMS attached, facts that depended on `fact` are removed too
     pub fn retract(&mut self, fact: &Term) -> bool {
+        let mut gone = vec![fact.clone()];
+        if let Some(tms) = &mut self.tms {
+            gone.extend(tms.retract(fact));
+        }
         let before = self.facts.len();
-        self.facts.retain(|f| f != fact);
-        self.facts.len() < before
+        Arc::make_mut(&mut self.facts).retain(|f| !gone.contains(f));
+        let removed = self.facts.len() < before;
+        if removed {
+            self.reindex_facts();
+            for g in &gone {
+                self.invalidate_for(g);
+            }
+        }
+        removed
+    }
+
+    // --- Partial evaluation ---
+
+    // Residual rules for calls matching `goal`, with non-recursive
+    // predicates unfolded
+    pub fn specialize(&self, goal: &Term) -> Vec<Rule> {
+        PartialEvaluator::new(&self.rules, &self.facts, &self.builtins).specialize(goal)
+    }
+
+    // Replace the rule base by its unfolded equivalent
+    pub fn unfold_rules(&mut self) {
+        let unfolded = PartialEvaluator::new(&self.rules, &self.facts, &self.builtins).unfold_all();
+        self.rules = Arc::new(unfolded);
+        self.reindex_rules();
+        self.table.clear();
+    }
+
+    // --- Answer-set mode ---
+
+    // Read the rule base as a normal logic program (not/1 and \+/1 body
+    // literals become default negation) and compute its stable models.
+    pub fn to_asp(&self) -> AspProgram {
+        let mut program = AspProgram::new().with_builtins(self.builtins.clone());
+        for fact in self.facts.iter() {
+            program.add_rule(AspRule::fact(fact.clone()));
+        }
+        for rule in self.rules.iter() {
+            let mut pos = Vec::new();
+            let mut neg = Vec::new();
+            for goal in &rule.body {
+                match goal {
+                    Term::Compound(f, args) if args.len() == 1 && self.is_negation(*f) => {
+                        neg.push(args[0].clone());
+                    }
+                    _ => pos.push(goal.clone()),
+                }
+            }
+            program.add_rule(AspRule::normal(rule.head.clone(), pos, neg));
+        }
+        program
+    }
+
+    pub fn stable_models(&self, max_models: usize) -> Vec<AnswerSet> {
+        self.to_asp().solve(max_models)
+    }
+
+    fn is_negation(&self, functor: Sym) -> bool {
+        self.not_sym == Some(functor) || self.naf_sym == Some(functor)
     }
 
     pub fn facts(&self) -> &[Term] {
@@ -468,3 +1740,513 @@ impl RuleEngine {
         &self.rules
     }
 }
+
+// Closure `G` applied to extra arguments: call(foo(a), b) runs foo(a, b)
+fn add_args(closure: &Term, extra: &[Term]) -> Option<Term> {
+    match closure {
+        Term::Atom(f) if extra.is_empty() => Some(Term::Atom(*f)),
+        Term::Atom(f) => Some(Term::Compound(*f, extra.to_vec())),
+        Term::Compound(f, args) => {
+            let mut all = args.clone();
+            all.extend_from_slice(extra);
+            Some(Term::Compound(*f, all))
+        }
+        _ => None,
+    }
+}
+
+fn replace_vars(term: &Term, map: &FxHashMap<Sym, Term>) -> Term {
+    match term {
+        Term::Var(v) => map.get(v).cloned().unwrap_or_else(|| term.clone()),
+        Term::Compound(f, args) => Term::Compound(*f, args.iter().map(|a| replace_vars(a, map)).collect()),
+        Term::List(items) => Term::List(items.iter().map(|a| replace_vars(a, map)).collect()),
+        Term::Dict(pairs) => Term::Dict(pairs.iter().map(|(k, v)| (*k, replace_vars(v, map))).collect()),
+        other => other.clone(),
+    }
+}
+
+fn list_goals(attr: Option<&Term>) -> Vec<Term> {
+    match attr {
+        Some(Term::List(goals)) => goals.clone(),
+        _ => Vec::new(),
+    }
+}
+
+fn push_alternatives(stack: &mut Vec<Choice>, cont: &Cont, subs: Vec<Substitution>) {
+    stack.extend(subs.into_iter().rev().map(|sub| Choice { cont: cont.clone(), sub }));
+}
+
+// Functors of every compound/atom inside a goal, including wrapped goals
+// such as not(G) or aggregate_all(count, G, N)
+fn collect_functors(term: &Term, out: &mut Vec<Sym>) {
+    match term {
+        Term::Atom(a) => out.push(*a),
+        Term::Compound(f, args) => {
+            out.push(*f);
+            for a in args {
+                collect_functors(a, out);
+            }
+        }
+        Term::List(items) => {
+            for item in items {
+                collect_functors(item, out);
+            }
+        }
+        Term::Dict(pairs) => {
+            for (_, v) in pairs {
+                collect_functors(v, out);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Engine with the standard builtins, and a lookup for their symbols
+    fn standard() -> (RuleEngine, SymbolTable) {
+        let mut syms = SymbolTable::new();
+        let engine = RuleEngine::new().with_standard_builtins(&mut syms);
+        (engine, syms)
+    }
+
+    #[test]
+    fn aggregate_spec_names_stay_user_predicates() {
+        let (mut engine, mut syms) = standard();
+        let (count, set, aggregate_all) = (syms.intern("count"), syms.intern("set"), syms.intern("aggregate_all"));
+        engine.add_fact(Term::compound(count, vec![Term::int(3)]));
+        engine.add_fact(Term::compound(set, vec![Term::int(1), Term::int(2)]));
+
+        let found = engine.query(&Term::compound(count, vec![Term::var(0)]));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].apply(&Term::var(0)), Term::int(3));
+        assert_eq!(engine.query(&Term::compound(set, vec![Term::var(0), Term::var(1)])).len(), 1);
+
+        // ...and still work as specs inside aggregate_all/3
+        let goal = Term::compound(aggregate_all, vec![
+            Term::atom(count),
+            Term::compound(set, vec![Term::var(0), Term::var(1)]),
+            Term::var(2),
+        ]);
+        assert_eq!(engine.query(&goal)[0].apply(&Term::var(2)), Term::int(1));
+    }
+
+    #[test]
+    fn conjunction_terms_run_as_goals() {
+        let (mut engine, mut syms) = standard();
+        let (p, q, comma, call, cut) = (syms.intern("p"), syms.intern("q"), syms.intern(","), syms.intern("call"), syms.intern("!"));
+        for i in 1..=3 {
+            engine.add_fact(Term::compound(p, vec![Term::int(i)]));
+        }
+        engine.add_fact(Term::compound(q, vec![Term::int(2)]));
+        engine.add_fact(Term::compound(q, vec![Term::int(3)]));
+        let both = Term::compound(comma, vec![Term::compound(p, vec![Term::var(0)]), Term::compound(q, vec![Term::var(0)])]);
+
+        let xs: Vec<Term> = engine.query(&both).iter().map(|s| s.apply(&Term::var(0))).collect();
+        assert_eq!(xs, vec![Term::int(2), Term::int(3)]);
+        assert_eq!(engine.query(&Term::compound(call, vec![both])).len(), 2);
+
+        // Cut inside a conjunction commits the clause: first(X) :- (p(X), !).
+        let first = syms.intern("first");
+        let body = Term::compound(comma, vec![Term::compound(p, vec![Term::var(0)]), Term::compound(cut, vec![])]);
+        engine.add_rule(Rule::new(Term::compound(first, vec![Term::var(0)]), vec![body]));
+        let found = engine.query(&Term::compound(first, vec![Term::var(0)]));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].apply(&Term::var(0)), Term::int(1));
+    }
+
+    #[test]
+    fn freeze_and_when_suspend_until_bound() {
+        let (mut engine, mut syms) = standard();
+        let [freeze, when, is, eq, plus, ground, comma, p, f] =
+            ["freeze", "when", "is", "=", "+", "ground", ",", "p", "f"].map(|n| syms.intern(n));
+        for i in 1..=3 {
+            engine.add_fact(Term::compound(p, vec![Term::int(i)]));
+        }
+
+        // freeze(X, Y is X + 1), p(X): the goal runs once p/1 binds X
+        let succ = Term::compound(is, vec![Term::var(1), Term::compound(plus, vec![Term::var(0), Term::int(1)])]);
+        let found = engine.query_all(&[
+            Term::compound(freeze, vec![Term::var(0), succ]),
+            Term::compound(p, vec![Term::var(0)]),
+        ]);
+        let ys: Vec<Term> = found.iter().map(|s| s.apply(&Term::var(1))).collect();
+        assert_eq!(ys, vec![Term::int(2), Term::int(3), Term::int(4)]);
+
+        // when((ground(f(X, Y)), ground(f(X, Y))), Z = done) waits for both
+        let cond = Term::compound(ground, vec![Term::compound(f, vec![Term::var(0), Term::var(1)])]);
+        let wait = Term::compound(when, vec![
+            Term::compound(comma, vec![cond.clone(), cond]),
+            Term::compound(eq, vec![Term::var(2), Term::int(99)]),
+        ]);
+        let bind_x = Term::compound(eq, vec![Term::var(0), Term::int(1)]);
+        let half = engine.query_all(&[wait.clone(), bind_x.clone()]);
+        assert_eq!(half[0].apply(&Term::var(2)), Term::var(2));
+        let full = engine.query_all(&[wait, bind_x, Term::compound(eq, vec![Term::var(1), Term::int(2)])]);
+        assert_eq!(full[0].apply(&Term::var(2)), Term::int(99));
+    }
+
+    #[test]
+    fn attribute_hooks_run_on_binding() {
+        let (mut engine, mut syms) = standard();
+        let [put_attr, member, p, dom, hook] = ["put_attr", "member", "p", "dom", "dom_hook"].map(|n| syms.intern(n));
+        for i in 1..=3 {
+            engine.add_fact(Term::compound(p, vec![Term::int(i)]));
+        }
+        // dom_hook(Values, X) :- member(X, Values).
+        engine.add_rule(Rule::new(
+            Term::compound(hook, vec![Term::var(0), Term::var(1)]),
+            vec![Term::compound(member, vec![Term::var(1), Term::var(0)])],
+        ));
+        engine.set_attr_hook(dom, hook);
+
+        let found = engine.query_all(&[
+            Term::compound(put_attr, vec![Term::var(0), Term::atom(dom), Term::list(vec![Term::int(2), Term::int(3)])]),
+            Term::compound(p, vec![Term::var(0)]),
+        ]);
+        let xs: Vec<Term> = found.iter().map(|s| s.apply(&Term::var(0))).collect();
+        assert_eq!(xs, vec![Term::int(2), Term::int(3)]);
+    }
+    #[test]
+    fn call_n_and_higher_order_library() {
+        let (mut engine, mut syms) = standard();
+        engine.load_higher_order(&mut syms);
+        let [succ_of, add, is, plus, call, maplist, foldl] =
+            ["succ_of", "add", "is", "+", "call", "maplist", "foldl"].map(|n| syms.intern(n));
+        let sum = |a: Term, b: Term| Term::compound(plus, vec![a, b]);
+        // succ_of(X, Y) :- Y is X + 1.    add(X, A0, A) :- A is A0 + X.
+        engine.add_rule(Rule::new(Term::compound(succ_of, vec![Term::var(0), Term::var(1)]),
+            vec![Term::compound(is, vec![Term::var(1), sum(Term::var(0), Term::int(1))])]));
+        engine.add_rule(Rule::new(Term::compound(add, vec![Term::var(0), Term::var(1), Term::var(2)]),
+            vec![Term::compound(is, vec![Term::var(2), sum(Term::var(1), Term::var(0))])]));
+
+        // call(succ_of, 4, Y) adds the extra arguments to the closure
+        let found = engine.query(&Term::compound(call, vec![Term::atom(succ_of), Term::int(4), Term::var(0)]));
+        assert_eq!(found[0].apply(&Term::var(0)), Term::int(5));
+
+        let list = Term::list(vec![Term::int(1), Term::int(2), Term::int(3)]);
+        let mapped = engine.query(&Term::compound(maplist, vec![Term::atom(succ_of), list.clone(), Term::var(0)]));
+        assert_eq!(mapped.len(), 1);
+        assert_eq!(mapped[0].apply(&Term::var(0)), Term::list(vec![Term::int(2), Term::int(3), Term::int(4)]));
+        let folded = engine.query(&Term::compound(foldl, vec![Term::atom(add), list, Term::int(0), Term::var(0)]));
+        assert_eq!(folded[0].apply(&Term::var(0)), Term::int(6));
+    }
+
+    #[test]
+    fn copy_term_univ_and_term_variables() {
+        let (mut engine, mut syms) = standard();
+        let [copy_term, univ, term_variables, f, g, a, b] =
+            ["copy_term", "=..", "term_variables", "f", "g", "a", "b"].map(|n| syms.intern(n));
+
+        // copy_term(f(X, Y, X), C): fresh variables, sharing kept
+        let original = Term::compound(f, vec![Term::var(0), Term::var(1), Term::var(0)]);
+        let found = engine.query(&Term::compound(copy_term, vec![original, Term::var(2)]));
+        let Term::Compound(_, args) = found[0].apply(&Term::var(2)) else { panic!("copy is not a compound") };
+        assert!(matches!(args[0], Term::Var(v) if v != 0 && v != 1));
+        assert!(matches!(args[1], Term::Var(v) if v != 0 && v != 1));
+        assert_eq!(args[0], args[2]);
+        assert_ne!(args[0], args[1]);
+
+        // f(a, b) =.. L, and T =.. [g, 1]
+        let parts = engine.query(&Term::compound(univ, vec![
+            Term::compound(f, vec![Term::atom(a), Term::atom(b)]),
+            Term::var(0),
+        ]));
+        assert_eq!(parts[0].apply(&Term::var(0)), Term::List(vec![Term::atom(f), Term::atom(a), Term::atom(b)]));
+        let built = engine.query(&Term::compound(univ, vec![Term::var(0), Term::List(vec![Term::atom(g), Term::int(1)])]));
+        assert_eq!(built[0].apply(&Term::var(0)), Term::compound(g, vec![Term::int(1)]));
+
+        // term_variables(f(X, g(Y, X)), Vs) lists each variable once, in order
+        let term = Term::compound(f, vec![Term::var(0), Term::compound(g, vec![Term::var(1), Term::var(0)])]);
+        let vars = engine.query(&Term::compound(term_variables, vec![term, Term::var(2)]));
+        assert_eq!(vars[0].apply(&Term::var(2)), Term::List(vec![Term::var(0), Term::var(1)]));
+    }
+
+    #[test]
+    fn aggregate_all_specs_and_groups() {
+        let (mut engine, mut syms) = standard();
+        let [aggregate_all, findall, count, sum, max, bag, set, sale, apple, pear] =
+            ["aggregate_all", "findall", "count", "sum", "max", "bag", "set", "sale", "apple", "pear"]
+                .map(|n| syms.intern(n));
+        for (item, price) in [(apple, 3), (pear, 5), (apple, 4), (pear, 5)] {
+            engine.add_fact(Term::compound(sale, vec![Term::atom(item), Term::int(price)]));
+        }
+        let (item, price, out) = (Term::var(0), Term::var(1), Term::var(2));
+        let sales = Term::compound(sale, vec![item.clone(), price.clone()]);
+        let mut run = |spec: Term| {
+            let goal = Term::compound(aggregate_all, vec![spec, sales.clone(), out.clone()]);
+            engine.query(&goal).first().map(|s| s.apply(&out))
+        };
+
+        assert_eq!(run(Term::atom(count)), Some(Term::int(4)));
+        assert_eq!(run(Term::compound(sum, vec![price.clone()])), Some(Term::int(17)));
+        assert_eq!(run(Term::compound(max, vec![price.clone()])), Some(Term::int(5)));
+        let items = |names: &[Sym]| Term::list(names.iter().map(|&n| Term::atom(n)).collect());
+        assert_eq!(run(Term::compound(bag, vec![item.clone()])), Some(items(&[apple, pear, apple, pear])));
+        assert_eq!(run(Term::compound(set, vec![item.clone()])), Some(items(&[apple, pear])));
+
+        // aggregate_all(sum(P), I, sale(I, P), G): [Key, Sum] in first-seen order
+        let grouped = Term::compound(aggregate_all, vec![
+            Term::compound(sum, vec![price.clone()]), item.clone(), sales.clone(), out.clone(),
+        ]);
+        let groups = Term::list(vec![
+            Term::list(vec![Term::atom(apple), Term::int(7)]),
+            Term::list(vec![Term::atom(pear), Term::int(10)]),
+        ]);
+        assert_eq!(engine.query(&grouped)[0].apply(&out), groups);
+
+        // findall(P, sale(apple, P), L); max over no solutions fails
+        let apples = Term::compound(sale, vec![Term::atom(apple), price.clone()]);
+        let found = engine.query(&Term::compound(findall, vec![price.clone(), apples, out.clone()]));
+        assert_eq!(found[0].apply(&out), Term::list(vec![Term::int(3), Term::int(4)]));
+        let none = Term::compound(sale, vec![Term::atom(count), price.clone()]);
+        assert!(engine.query(&Term::compound(aggregate_all, vec![Term::compound(max, vec![price]), none, out])).is_empty());
+    }
+
+    #[test]
+    fn tabled_answers_follow_assert_and_retract() {
+        let mut syms = SymbolTable::new();
+        let [path, edge, other, a, b, c, d] = ["path", "edge", "other", "a", "b", "c", "d"].map(|n| syms.intern(n));
+        let mut engine = RuleEngine::new();
+        let (x, y, z) = (Term::var(0), Term::var(1), Term::var(2));
+        let link = |from: Sym, to: Sym| Term::compound(edge, vec![Term::atom(from), Term::atom(to)]);
+        engine.add_rule(Rule::new(
+            Term::compound(path, vec![x.clone(), y.clone()]),
+            vec![Term::compound(edge, vec![x.clone(), y.clone()])],
+        ));
+        engine.add_rule(Rule::new(
+            Term::compound(path, vec![x.clone(), y.clone()]),
+            vec![Term::compound(edge, vec![x.clone(), z.clone()]), Term::compound(path, vec![z, y.clone()])],
+        ));
+        engine.table_functor(path);
+        engine.add_fact(link(a, b));
+        engine.add_fact(link(b, c));
+
+        let from_a = Term::compound(path, vec![Term::atom(a), y.clone()]);
+        let reach = |engine: &mut RuleEngine| {
+            let mut ends: Vec<Term> = engine.query(&from_a).iter().map(|s| s.apply(&y)).collect();
+            ends.sort_by_key(|t| format!("{:?}", t));
+            ends
+        };
+        assert_eq!(reach(&mut engine), vec![Term::atom(b), Term::atom(c)]);
+        assert!(engine.table_size() > 0);
+
+        // A predicate path/2 does not depend on leaves the table alone
+        engine.assert_fact(Term::compound(other, vec![Term::atom(a)])).unwrap();
+        assert!(engine.table_size() > 0);
+
+        // edge/2 feeds path/2, so asserting and retracting drop its answers
+        engine.assert_fact(link(c, d)).unwrap();
+        assert_eq!(engine.table_size(), 0);
+        assert_eq!(reach(&mut engine), vec![Term::atom(b), Term::atom(c), Term::atom(d)]);
+        assert!(engine.retract(&link(b, c)));
+        assert_eq!(engine.table_size(), 0);
+        assert_eq!(reach(&mut engine), vec![Term::atom(b)]);
+    }
+
+    #[test]
+    fn tail_recursion_runs_without_growing_the_stack() {
+        let mut syms = SymbolTable::new();
+        let [countdown, gt, is, minus] = ["countdown", ">", "is", "-"].map(|n| syms.intern(n));
+        let mut engine = RuleEngine::new().with_standard_builtins(&mut syms).with_depth(1_000_000);
+        let (n, m) = (Term::var(0), Term::var(1));
+        // countdown(0). countdown(N) :- N > 0, M is N - 1, countdown(M).
+        engine.add_fact(Term::compound(countdown, vec![Term::int(0)]));
+        engine.add_rule(Rule::new(Term::compound(countdown, vec![n.clone()]), vec![
+            Term::compound(gt, vec![n.clone(), Term::int(0)]),
+            Term::compound(is, vec![m.clone(), Term::compound(minus, vec![n, Term::int(1)])]),
+            Term::compound(countdown, vec![m]),
+        ]));
+        assert_eq!(engine.query(&Term::compound(countdown, vec![Term::int(100_000)])).len(), 1);
+        assert_eq!(engine.depth_cutoffs(), 0);
+    }
+
+    #[test]
+    fn calls_rename_only_the_rules_of_their_predicate() {
+        let mut syms = SymbolTable::new();
+        let [countdown, gt, is, minus, other] = ["countdown", ">", "is", "-", "other"].map(|n| syms.intern(n));
+        let mut engine = RuleEngine::new().with_standard_builtins(&mut syms).with_depth(10_000);
+        let (n, m) = (Term::var(0), Term::var(1));
+        engine.add_fact(Term::compound(countdown, vec![Term::int(0)]));
+        engine.add_rule(Rule::new(Term::compound(countdown, vec![n.clone()]), vec![
+            Term::compound(gt, vec![n.clone(), Term::int(0)]),
+            Term::compound(is, vec![m.clone(), Term::compound(minus, vec![n.clone(), Term::int(1)])]),
+            Term::compound(countdown, vec![m]),
+        ]));
+        for i in 0..1000 {
+            engine.add_rule(Rule::new(Term::compound(other, vec![n.clone()]), vec![Term::compound(gt, vec![n.clone(), Term::int(i)])]));
+        }
+        let query = Term::compound(countdown, vec![Term::int(1000)]);
+        assert_eq!(engine.query(&query).len(), 1);
+        // 1001 calls, each renaming the one countdown rule (two variables)
+        assert_eq!(engine.var_counter, FIRST_FRESH_VAR + 1001 * 2);
+        // Every top-level query starts over
+        assert_eq!(engine.query(&query).len(), 1);
+        assert_eq!(engine.var_counter, FIRST_FRESH_VAR + 1001 * 2);
+    }
+
+    #[test]
+    fn cut_commits_to_the_clause_and_stays_local() {
+        let (mut engine, mut syms) = standard();
+        let [larger, gte, cut, p, first, pair] = ["larger", ">=", "!", "p", "first", "pair"].map(|n| syms.intern(n));
+        let (x, y) = (Term::var(0), Term::var(1));
+        let cut = Term::compound(cut, vec![]);
+        for i in 1..=3 {
+            engine.add_fact(Term::compound(p, vec![Term::int(i)]));
+        }
+
+        // larger(X, Y, X) :- X >= Y, !.  larger(X, Y, Y).
+        engine.add_rule(Rule::new(
+            Term::compound(larger, vec![x.clone(), y.clone(), x.clone()]),
+            vec![Term::compound(gte, vec![x.clone(), y.clone()]), cut.clone()],
+        ));
+        // Facts are tried before rules, so the fallback clauses are rules too
+        engine.add_rule(Rule::new(Term::compound(larger, vec![x.clone(), y.clone(), y.clone()]), vec![]));
+        for (a, b) in [(3, 1), (1, 3)] {
+            let found = engine.query(&Term::compound(larger, vec![Term::int(a), Term::int(b), Term::var(2)]));
+            assert_eq!(found.len(), 1);
+            assert_eq!(found[0].apply(&Term::var(2)), Term::int(3));
+        }
+
+        // first(X) :- p(X), !.  first(0).  The cut prunes p/1 and the later clause
+        engine.add_rule(Rule::new(
+            Term::compound(first, vec![x.clone()]),
+            vec![Term::compound(p, vec![x.clone()]), cut],
+        ));
+        engine.add_rule(Rule::new(Term::compound(first, vec![Term::int(0)]), vec![]));
+        let found = engine.query(&Term::compound(first, vec![x.clone()]));
+        assert_eq!(found.iter().map(|s| s.apply(&x)).collect::<Vec<_>>(), vec![Term::int(1)]);
+
+        // ...but not the caller's choices: pair(X, Y) :- first(X), p(Y).
+        engine.add_rule(Rule::new(
+            Term::compound(pair, vec![x.clone(), y.clone()]),
+            vec![Term::compound(first, vec![x.clone()]), Term::compound(p, vec![y.clone()])],
+        ));
+        assert_eq!(engine.query(&Term::compound(pair, vec![x, y])).len(), 3);
+    }
+
+    #[test]
+    fn list_library_builtins() {
+        let (mut engine, mut syms) = standard();
+        let [length, numlist, nth0, last, reverse, sum_list, max_list] =
+            ["length", "numlist", "nth0", "last", "reverse", "sum_list", "max_list"].map(|n| syms.intern(n));
+        let ints = |xs: &[i64]| Term::List(xs.iter().map(|&x| Term::int(x)).collect());
+        let out = Term::var(0);
+        let mut first = |goal: Term| engine.query(&goal).first().map(|s| s.apply(&out));
+
+        assert_eq!(first(Term::compound(numlist, vec![Term::int(1), Term::int(4), out.clone()])), Some(ints(&[1, 2, 3, 4])));
+        assert_eq!(first(Term::compound(nth0, vec![Term::int(2), ints(&[5, 6, 7]), out.clone()])), Some(Term::int(7)));
+        assert_eq!(first(Term::compound(nth0, vec![Term::int(3), ints(&[5, 6, 7]), out.clone()])), None);
+        assert_eq!(first(Term::compound(last, vec![ints(&[5, 6, 7]), out.clone()])), Some(Term::int(7)));
+        assert_eq!(first(Term::compound(reverse, vec![out.clone(), ints(&[1, 2, 3])])), Some(ints(&[3, 2, 1])));
+        assert_eq!(first(Term::compound(sum_list, vec![ints(&[1, 2, 3]), out.clone()])), Some(Term::int(6)));
+        assert_eq!(first(Term::compound(max_list, vec![ints(&[4, 9, 2]), out.clone()])), Some(Term::int(9)));
+        assert_eq!(first(Term::compound(max_list, vec![ints(&[]), out.clone()])), None);
+
+        // numlist/3 refuses ranges past MAX_NUMLIST_LEN instead of allocating them
+        let huge = Term::compound(numlist, vec![Term::int(0), Term::int(i64::MAX), out.clone()]);
+        assert_eq!(first(huge), None);
+
+        // nth0/3 with an unbound index enumerates positions
+        let positions = engine.query(&Term::compound(nth0, vec![out.clone(), ints(&[5, 6, 7]), Term::var(1)]));
+        assert_eq!(positions.iter().map(|s| s.apply(&out)).collect::<Vec<_>>(), vec![Term::int(0), Term::int(1), Term::int(2)]);
+
+        // length/2 on an unbound list: fresh variables for a given length,
+        // every length up to the depth budget otherwise
+        let found = engine.query(&Term::compound(length, vec![out.clone(), Term::int(3)]));
+        let Term::List(items) = found[0].apply(&out) else { panic!("length/2 did not build a list") };
+        assert_eq!(items.len(), 3);
+        assert!(items.iter().all(|t| matches!(t, Term::Var(_))) && items[0] != items[1]);
+        let mut engine = RuleEngine::new().with_standard_builtins(&mut syms).with_depth(5);
+        let lengths = engine.query(&Term::compound(length, vec![out.clone(), Term::var(1)]));
+        assert_eq!(lengths.len(), 6);
+        assert_eq!(lengths.last().unwrap().apply(&Term::var(1)), Term::int(5));
+
+        // Lengths are tried one at a time, so the first answer does not
+        // build every list the depth budget allows
+        let mut engine = RuleEngine::new().with_standard_builtins(&mut syms).with_depth(usize::MAX);
+        let shortest = engine.query_first(&Term::compound(length, vec![out.clone(), Term::var(1)])).unwrap();
+        assert_eq!(shortest.apply(&out), Term::list(vec![]));
+        assert_eq!(engine.var_counter, FIRST_FRESH_VAR);
+        let too_long = Term::int(builtins::MAX_NUMLIST_LEN + 1);
+        assert!(engine.query(&Term::compound(length, vec![out.clone(), too_long])).is_empty());
+        let wraps = Term::int((1 << 32) + 2);
+        assert!(engine.query(&Term::compound(length, vec![out.clone(), wraps])).is_empty());
+    }
+
+    #[test]
+    fn globals_survive_backtracking_and_queries() {
+        let (mut engine, mut syms) = standard();
+        let [nb_setval, nb_getval, is, plus, fail, p, total] =
+            ["nb_setval", "nb_getval", "is", "+", "fail", "p", "total"].map(|n| syms.intern(n));
+        for i in 1..=3 {
+            engine.add_fact(Term::compound(p, vec![Term::int(i)]));
+        }
+        let (x, old, new) = (Term::var(0), Term::var(1), Term::var(2));
+        assert_eq!(engine.query(&Term::compound(nb_setval, vec![Term::atom(total), Term::int(0)])).len(), 1);
+
+        // p(X), nb_getval(total, Old), New is Old + X, nb_setval(total, New), fail
+        let sum_all = [
+            Term::compound(p, vec![x.clone()]),
+            Term::compound(nb_getval, vec![Term::atom(total), old.clone()]),
+            Term::compound(is, vec![new.clone(), Term::compound(plus, vec![old, x.clone()])]),
+            Term::compound(nb_setval, vec![Term::atom(total), new]),
+            Term::atom(fail),
+        ];
+        assert!(engine.query_all(&sum_all).is_empty());
+        let found = engine.query(&Term::compound(nb_getval, vec![Term::atom(total), x.clone()]));
+        assert_eq!(found[0].apply(&x), Term::int(6));
+        assert_eq!(engine.global(total), Some(&Term::int(6)));
+
+        // nb_setval/2 stores the value resolved; set_global/2 feeds nb_getval/2
+        let eq = syms.intern("=");
+        engine.query_all(&[
+            Term::compound(eq, vec![x.clone(), Term::int(5)]),
+            Term::compound(nb_setval, vec![Term::atom(total), Term::compound(p, vec![x.clone()])]),
+        ]);
+        assert_eq!(engine.global(total), Some(&Term::compound(p, vec![Term::int(5)])));
+        engine.set_global(total, Term::compound(p, vec![Term::int(9)]));
+        let found = engine.query(&Term::compound(nb_getval, vec![Term::atom(total), Term::compound(p, vec![x.clone()])]));
+        assert_eq!(found[0].apply(&x), Term::int(9));
+        engine.clear_globals();
+        assert!(engine.query(&Term::compound(nb_getval, vec![Term::atom(total), x.clone()])).is_empty());
+        assert!(engine.query(&Term::compound(nb_getval, vec![x.clone(), Term::int(1)])).is_empty());
+    }
+
+    #[test]
+    fn iterative_deepening_and_breadth_first_strategies() {
+        let mut syms = SymbolTable::new();
+        let [f, g, h, p, deep, shallow, ok] = ["f", "g", "h", "p", "deep", "shallow", "ok"].map(|n| syms.intern(n));
+        let x = Term::var(0);
+        // f(X) :- g(X).  g(X) :- h(X).  h(deep).  f(shallow).
+        let mut engine = RuleEngine::new();
+        engine.add_rule(Rule::new(Term::compound(f, vec![x.clone()]), vec![Term::compound(g, vec![x.clone()])]));
+        engine.add_rule(Rule::new(Term::compound(g, vec![x.clone()]), vec![Term::compound(h, vec![x.clone()])]));
+        engine.add_fact(Term::compound(h, vec![Term::atom(deep)]));
+        engine.add_rule(Rule::new(Term::compound(f, vec![Term::atom(shallow)]), vec![]));
+        // p(X) :- p(X).  p(ok).  An infinite branch ahead of the answer
+        engine.add_rule(Rule::new(Term::compound(p, vec![x.clone()]), vec![Term::compound(p, vec![x.clone()])]));
+        engine.add_rule(Rule::new(Term::compound(p, vec![Term::atom(ok)]), vec![]));
+        let answers = |engine: &mut RuleEngine| -> Vec<Term> {
+            engine.query(&Term::compound(f, vec![x.clone()])).iter().map(|s| s.apply(&x)).collect()
+        };
+
+        assert_eq!(answers(&mut engine), vec![Term::atom(deep), Term::atom(shallow)]);
+        let mut engine = engine.with_depth(50);
+        assert_eq!(engine.query_first(&Term::compound(p, vec![x.clone()])).unwrap().apply(&x), Term::atom(ok));
+        assert!(engine.depth_cutoffs() > 0);
+
+        // Shallowest answers first, each once, though every round re-runs the shallower ones
+        let mut engine = engine.with_strategy(SearchStrategy::IterativeDeepening { step: 1 });
+        assert_eq!(answers(&mut engine), vec![Term::atom(shallow), Term::atom(deep)]);
+        assert_eq!(engine.query_first(&Term::compound(p, vec![x.clone()])).unwrap().apply(&x), Term::atom(ok));
+
+        // Breadth-first reaches p(ok) without following p(X) :- p(X) down to the bound
+        let mut engine = engine.with_strategy(SearchStrategy::BreadthFirst).with_depth(1_000_000);
+        assert_eq!(engine.query_first(&Term::compound(p, vec![x.clone()])).unwrap().apply(&x), Term::atom(ok));
+        assert_eq!(engine.depth_cutoffs(), 0);
+        assert_eq!(answers(&mut engine), vec![Term::atom(shallow), Term::atom(deep)]);
+    }
+}
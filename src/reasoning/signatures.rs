@@ -0,0 +1,187 @@
+// Predicate signatures: name, arity and argument types.
+//
+// Declarations are optional. Declared predicates have their facts and rule
+// heads validated at load time; calls to a predicate that is neither declared,
+// defined nor a builtin can be reported as "unknown predicate" instead of
+// failing silently (see RuleEngine::with_unknown_predicates).
+
+use crate::core::{Term, Sym};
+use super::rules::Rule;
+use rustc_hash::FxHashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgType {
+    Any,
+    Atom,
+    Int,
+    Number,
+    Str,
+    Bool,
+    List,
+    Compound,
+    Dict,
+}
+
+impl ArgType {
+    // Unbound arguments are accepted by every type
+    pub fn accepts(self, term: &Term) -> bool {
+        matches!(
+            (self, term),
+            (_, Term::Var(_))
+                | (ArgType::Any, _)
+                | (ArgType::Atom, Term::Atom(_))
+                | (ArgType::Int, Term::Int(_))
+                | (ArgType::Number, Term::Int(_) | Term::Float(_))
+                | (ArgType::Str, Term::Str(_))
+                | (ArgType::Bool, Term::Bool(_))
+                | (ArgType::List, Term::List(_) | Term::Nil)
+                | (ArgType::Compound, Term::Compound(..))
+                | (ArgType::Dict, Term::Dict(_))
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PredicateSig {
+    pub functor: Sym,
+    pub args: Vec<ArgType>,
+}
+
+impl PredicateSig {
+    pub fn new(functor: Sym, args: Vec<ArgType>) -> Self {
+        Self { functor, args }
+    }
+
+    // Declared arity only, every argument untyped
+    pub fn untyped(functor: Sym, arity: usize) -> Self {
+        Self { functor, args: vec![ArgType::Any; arity] }
+    }
+
+    pub fn arity(&self) -> usize {
+        self.args.len()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SignatureError {
+    // Called with an arity that has no declaration, fact or rule
+    UnknownPredicate { functor: Sym, arity: usize },
+    // A fact or rule head disagrees with every declared arity
+    ArityMismatch { functor: Sym, expected: Vec<usize>, found: usize, rule_id: Option<usize> },
+    // An argument has the wrong type
+    TypeMismatch { functor: Sym, arg: usize, expected: ArgType, rule_id: Option<usize> },
+}
+
+// What to do when a query calls an unknown predicate
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownPredicates {
+    // Fail silently (classic behaviour)
+    #[default]
+    Fail,
+    // Fail, and record a warning
+    Warn,
+    // Refuse the query (see RuleEngine::query_checked)
+    Error,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SignatureTable {
+    sigs: FxHashMap<Sym, Vec<PredicateSig>>,
+}
+
+impl SignatureTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // A functor may be declared at several arities
+    pub fn declare(&mut self, sig: PredicateSig) {
+        let entry = self.sigs.entry(sig.functor).or_default();
+        entry.retain(|s| s.arity() != sig.arity());
+        entry.push(sig);
+    }
+
+    pub fn get(&self, functor: Sym, arity: usize) -> Option<&PredicateSig> {
+        self.sigs.get(&functor)?.iter().find(|s| s.arity() == arity)
+    }
+
+    pub fn is_declared(&self, functor: Sym) -> bool {
+        self.sigs.contains_key(&functor)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sigs.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sigs.values().map(|v| v.len()).sum()
+    }
+
+    // Check one fact or rule head against the declarations of its functor
+    pub fn check_head(&self, head: &Term, rule_id: Option<usize>) -> Vec<SignatureError> {
+        let (f, args): (Sym, &[Term]) = match head {
+            Term::Compound(f, args) => (*f, args),
+            Term::Atom(f) => (*f, &[]),
+            _ => return Vec::new(),
+        };
+        let declared = match self.sigs.get(&f) {
+            Some(d) => d,
+            None => return Vec::new(),
+        };
+        let sig = match declared.iter().find(|s| s.arity() == args.len()) {
+            Some(s) => s,
+            None => {
+                return vec![SignatureError::ArityMismatch {
+                    functor: f,
+                    expected: declared.iter().map(|s| s.arity()).collect(),
+                    found: args.len(),
+                    rule_id,
+                }];
+            }
+        };
+        sig.args.iter().zip(args.iter()).enumerate()
+            .filter(|(_, (ty, a))| !ty.accepts(a))
+            .map(|(i, (ty, _))| SignatureError::TypeMismatch { functor: f, arg: i, expected: *ty, rule_id })
+            .collect()
+    }
+
+    // Load-time validation of a whole knowledge base
+    pub fn validate(&self, facts: &[Term], rules: &[Rule]) -> Vec<SignatureError> {
+        let mut errors = Vec::new();
+        for fact in facts {
+            errors.extend(self.check_head(fact, None));
+        }
+        for rule in rules {
+            errors.extend(self.check_head(&rule.head, Some(rule.id)));
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARENT: Sym = 1;
+
+    #[test]
+    fn arity_and_types_checked() {
+        let mut table = SignatureTable::new();
+        table.declare(PredicateSig::new(PARENT, vec![ArgType::Atom, ArgType::Atom]));
+        let ok = Term::compound(PARENT, vec![Term::atom(10), Term::atom(11)]);
+        let bad_arity = Term::compound(PARENT, vec![Term::atom(10), Term::atom(11), Term::atom(12)]);
+        let bad_type = Term::compound(PARENT, vec![Term::atom(10), Term::int(3)]);
+        let errors = table.validate(&[ok, bad_arity, bad_type], &[]);
+        assert_eq!(errors.len(), 2);
+        assert!(matches!(errors[0], SignatureError::ArityMismatch { found: 3, .. }));
+        assert!(matches!(errors[1], SignatureError::TypeMismatch { arg: 1, expected: ArgType::Atom, .. }));
+    }
+
+    #[test]
+    fn variables_accepted_in_rule_heads() {
+        let mut table = SignatureTable::new();
+        table.declare(PredicateSig::new(PARENT, vec![ArgType::Int]));
+        let rule = Rule::new(Term::compound(PARENT, vec![Term::var(0)]), vec![]);
+        assert!(table.validate(&[], &[rule]).is_empty());
+    }
+}
@@ -1,4 +1,9 @@
+use crate::core::{KolossError, Result};
 use rustc_hash::FxHashMap;
+use super::backend::SatBackend;
+use super::cdcl::{CdclSolver, SatConfig};
+use super::maxsat::{self, MaxSatResult};
+use std::fmt::Write;
 
 pub type Literal = i32;
 pub type Clause = Vec<Literal>;
@@ -8,6 +13,11 @@ pub type Assignment = FxHashMap<u32, bool>;
 pub struct SatProblem {
     clauses: Vec<Clause>,
     num_vars: u32,
+    config: SatConfig,
+    // Weighted soft clauses, only used by solve_optimal
+    soft: Vec<(Clause, u64)>,
+    // XOR clauses: an odd number of the literals is true
+    xors: Vec<Clause>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -18,135 +28,178 @@ pub enum SatResult {
 
 impl SatProblem {
     pub fn new(num_vars: u32) -> Self {
-        Self { clauses: Vec::new(), num_vars }
+        Self { clauses: Vec::new(), num_vars, config: SatConfig::default(), soft: Vec::new(), xors: Vec::new() }
+    }
+
+    // Branching heuristic, restart and clause database policy of solve()
+    pub fn with_config(mut self, config: SatConfig) -> Self {
+        self.config = config;
+        self
+    }
+
+    pub fn config(&self) -> &SatConfig {
+        &self.config
     }
 
     pub fn add_clause(&mut self, clause: Clause) {
         self.clauses.push(clause);
     }
 
-    pub fn from_clauses(num_vars: u32, clauses: Vec<Clause>) -> Self {
-        Self { clauses, num_vars }
+    pub fn add_xor_clause(&mut self, xor: Clause) {
+        self.xors.push(xor);
     }
 
-    pub fn solve(&self) -> SatResult {
-        let mut assignment = Assignment::default();
-        if dpll(&self.clauses, &mut assignment, self.num_vars) {
-            SatResult::Sat(assignment)
-        } else {
-            SatResult::Unsat
-        }
+    pub fn xor_clauses(&self) -> &[Clause] {
+        &self.xors
     }
 
-    pub fn num_vars(&self) -> u32 {
-        self.num_vars
+    pub fn add_soft_clause(&mut self, clause: Clause, weight: u64) {
+        self.soft.push((clause, weight));
     }
 
-    pub fn num_clauses(&self) -> usize {
-        self.clauses.len()
+    pub fn soft_clauses(&self) -> &[(Clause, u64)] {
+        &self.soft
     }
-}
 
-fn dpll(clauses: &[Clause], assignment: &mut Assignment, num_vars: u32) -> bool {
-    let simplified = simplify(clauses, assignment);
+    // Assignment of minimum falsified soft weight subject to the hard
+    // clauses; see maxsat.rs
+    pub fn solve_optimal(&self) -> MaxSatResult {
+        maxsat::solve_maxsat(self)
+    }
 
-    if simplified.is_empty() {
-        return true;
+    pub fn from_clauses(num_vars: u32, clauses: Vec<Clause>) -> Self {
+        Self { clauses, num_vars, config: SatConfig::default(), soft: Vec::new(), xors: Vec::new() }
     }
-    if simplified.iter().any(|c| c.is_empty()) {
-        return false;
+
+    // Complete assignment of 1..=num_vars (and of any larger variable the
+    // clauses mention) or Unsat; see cdcl.rs. Soft clauses are ignored.
+    pub fn solve(&self) -> SatResult {
+        self.solver().solve()
     }
 
-    if let Some(unit) = find_unit(&simplified) {
-        let var = unit.unsigned_abs();
-        let val = unit > 0;
-        assignment.insert(var, val);
-        return dpll(&simplified, assignment, num_vars);
+    pub fn solve_with_assumptions(&self, assumptions: &[Literal]) -> SatResult {
+        self.solver().solve_with_assumptions(assumptions)
     }
 
-    if let Some(pure) = find_pure_literal(&simplified, num_vars) {
-        let var = pure.unsigned_abs();
-        let val = pure > 0;
-        assignment.insert(var, val);
-        return dpll(&simplified, assignment, num_vars);
+    // Solve with DRAT logging on; for Unsat the proof (ending in the empty
+    // clause) can be checked with drat-trim against to_dimacs(). The proof
+    // is empty when the problem has XOR clauses.
+    pub fn solve_with_proof(&self) -> (SatResult, String) {
+        let mut solver = self.load(SatConfig { drat: true, ..self.config.clone() });
+        let result = solver.solve();
+        (result, solver.take_proof().unwrap_or_default())
     }
 
-    let var = pick_variable(&simplified, assignment, num_vars);
-    if var == 0 {
-        return false;
+    // Incremental solver loaded with the clauses, for repeated solving
+    // (assumptions, push/pop) that keeps learnt clauses between calls
+    pub fn solver(&self) -> CdclSolver {
+        self.load(self.config.clone())
     }
 
-    assignment.insert(var, true);
-    if dpll(&simplified, assignment, num_vars) {
-        return true;
+    fn load(&self, config: SatConfig) -> CdclSolver {
+        let mut solver = CdclSolver::with_config(self.num_vars, config);
+        self.load_into(&mut solver);
+        solver
     }
 
-    assignment.insert(var, false);
-    if dpll(&simplified, assignment, num_vars) {
-        return true;
+    // Add the clauses and XOR clauses to a backend; false once it reports
+    // them unsatisfiable
+    pub fn load_into<B: SatBackend>(&self, backend: &mut B) -> bool {
+        self.clauses.iter().all(|c| backend.add_clause(c)) && self.xors.iter().all(|x| backend.add_xor(x))
     }
 
-    assignment.remove(&var);
-    false
-}
+    pub fn num_vars(&self) -> u32 {
+        self.num_vars
+    }
+
+    pub fn num_clauses(&self) -> usize {
+        self.clauses.len()
+    }
 
-fn simplify(clauses: &[Clause], assignment: &Assignment) -> Vec<Clause> {
-    let mut result = Vec::new();
-    for clause in clauses {
-        let mut satisfied = false;
-        let mut remaining = Vec::new();
-        for &lit in clause {
-            let var = lit.unsigned_abs();
-            let pos = lit > 0;
-            match assignment.get(&var) {
-                Some(&val) => {
-                    if val == pos {
-                        satisfied = true;
-                        break;
+    pub fn clauses(&self) -> &[Clause] {
+        &self.clauses
+    }
+
+    // DIMACS CNF: `c` comment lines, a `p cnf <vars> <clauses>` header, then
+    // clauses as literals terminated by 0 (possibly spanning lines). A `%`
+    // line, as in the SATLIB benchmarks, ends the input. A clause starting
+    // with `x` (x1 -2 3 0, the CryptoMiniSat extension) is an XOR clause;
+    // the header count includes them.
+    pub fn from_dimacs(text: &str) -> Result<Self> {
+        let err = |line: usize, msg: String| KolossError::Parse(format!("dimacs line {}: {}", line + 1, msg));
+        let mut header: Option<(u32, usize)> = None;
+        let mut clauses: Vec<Clause> = Vec::new();
+        let mut xors: Vec<Clause> = Vec::new();
+        let mut current: Clause = Vec::new();
+        let mut is_xor = false;
+        for (n, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('c') {
+                continue;
+            }
+            if line.starts_with('%') {
+                break;
+            }
+            if let Some(rest) = line.strip_prefix('p') {
+                let fields: Vec<&str> = rest.split_whitespace().collect();
+                let parsed = match fields.as_slice() {
+                    ["cnf", vars, count] => vars.parse().ok().zip(count.parse().ok()),
+                    _ => None,
+                };
+                if header.is_some() || parsed.is_none() {
+                    return Err(err(n, format!("bad header '{}'", line)));
+                }
+                header = parsed;
+                continue;
+            }
+            let (num_vars, _) = header.ok_or_else(|| err(n, "clause before the 'p cnf' header".into()))?;
+            for mut token in line.split_whitespace() {
+                if let Some(rest) = token.strip_prefix('x').filter(|_| current.is_empty() && !is_xor) {
+                    is_xor = true;
+                    token = rest;
+                    if token.is_empty() {
+                        continue;
                     }
                 }
-                None => remaining.push(lit),
+                let lit: Literal = token.parse().map_err(|_| err(n, format!("bad literal '{}'", token)))?;
+                if lit == 0 {
+                    let clause = std::mem::take(&mut current);
+                    if std::mem::take(&mut is_xor) { xors.push(clause) } else { clauses.push(clause) }
+                } else if lit.unsigned_abs() > num_vars {
+                    return Err(err(n, format!("variable {} exceeds the declared {}", lit.unsigned_abs(), num_vars)));
+                } else {
+                    current.push(lit);
+                }
             }
         }
-        if !satisfied {
-            result.push(remaining);
-        }
-    }
-    result
-}
-
-fn find_unit(clauses: &[Clause]) -> Option<Literal> {
-    clauses.iter().find(|c| c.len() == 1).map(|c| c[0])
-}
-
-fn find_pure_literal(clauses: &[Clause], num_vars: u32) -> Option<Literal> {
-    for v in 1..=num_vars {
-        let pos = clauses.iter().any(|c| c.contains(&(v as Literal)));
-        let neg = clauses.iter().any(|c| c.contains(&-(v as Literal)));
-        if pos && !neg {
-            return Some(v as Literal);
+        let (num_vars, count) = header.ok_or_else(|| KolossError::Parse("dimacs: missing 'p cnf' header".into()))?;
+        // Tolerate a missing 0 after the last clause
+        if !current.is_empty() {
+            if is_xor { xors.push(current) } else { clauses.push(current) }
         }
-        if neg && !pos {
-            return Some(-(v as Literal));
+        let found = clauses.len() + xors.len();
+        if found != count {
+            return Err(KolossError::Parse(format!("dimacs: header declares {} clauses, found {}", count, found)));
         }
+        let mut problem = Self::from_clauses(num_vars, clauses);
+        problem.xors = xors;
+        Ok(problem)
     }
-    None
-}
 
-fn pick_variable(clauses: &[Clause], assignment: &Assignment, _num_vars: u32) -> u32 {
-    let mut counts: FxHashMap<u32, usize> = FxHashMap::default();
-    for clause in clauses {
-        for &lit in clause {
-            let var = lit.unsigned_abs();
-            if !assignment.contains_key(&var) {
-                *counts.entry(var).or_default() += 1;
+    pub fn to_dimacs(&self) -> String {
+        let max_var = self.clauses.iter().chain(&self.xors).flatten().map(|l| l.unsigned_abs()).max().unwrap_or(0);
+        let mut out = String::new();
+        let _ = writeln!(out, "p cnf {} {}", self.num_vars.max(max_var), self.clauses.len() + self.xors.len());
+        let xors = self.xors.iter().map(|c| (c, "x"));
+        for (clause, prefix) in self.clauses.iter().map(|c| (c, "")).chain(xors) {
+            out.push_str(prefix);
+            for lit in clause {
+                let _ = write!(out, "{} ", lit);
             }
+            out.push_str("0\n");
         }
+        out
     }
-    counts.into_iter()
-        .max_by_key(|&(_, count)| count)
-        .map(|(var, _)| var)
-        .unwrap_or(0)
 }
 
 #[derive(Debug, Clone)]
@@ -255,3 +308,31 @@ impl ConstraintSolver {
         true
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dimacs_round_trip() {
+        let text = "c example\np cnf 3 3\n1 -2 0\n2 3\n-1 0\n-3 0\n%\n0\n";
+        let problem = SatProblem::from_dimacs(text).unwrap();
+        assert_eq!(problem.clauses(), &[vec![1, -2], vec![2, 3, -1], vec![-3]]);
+        assert_eq!(problem.to_dimacs(), "p cnf 3 3\n1 -2 0\n2 3 -1 0\n-3 0\n");
+        let again = SatProblem::from_dimacs(&problem.to_dimacs()).unwrap();
+        assert_eq!((again.num_vars(), again.clauses()), (3, problem.clauses()));
+        assert!(matches!(again.solve(), SatResult::Sat(_)));
+
+        let xor = SatProblem::from_dimacs("p cnf 3 2\nx1 -2 0\nx 2 3 0\n").unwrap();
+        assert_eq!(xor.xor_clauses(), &[vec![1, -2], vec![2, 3]]);
+        assert_eq!(xor.to_dimacs(), "p cnf 3 2\nx1 -2 0\nx2 3 0\n");
+    }
+
+    #[test]
+    fn dimacs_errors() {
+        assert!(SatProblem::from_dimacs("1 2 0\n").is_err());
+        assert!(SatProblem::from_dimacs("p cnf 2 1\n1 3 0\n").is_err());
+        assert!(SatProblem::from_dimacs("p cnf 2 2\n1 2 0\n").is_err());
+        assert!(SatProblem::from_dimacs("p cnf 2 1\n1 x 0\n").is_err());
+    }
+}
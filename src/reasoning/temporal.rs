@@ -0,0 +1,174 @@
+// Time intervals and Allen's interval algebra.
+//
+// Temporal facts are stored as `holds(Fact, From, To)` with half-open
+// validity [From, To); a non-numeric `To` (e.g. the atom `inf`) means the
+// fact is still valid. Intervals passed to the Allen builtins are any binary
+// term with numeric arguments (`i(1, 5)`) or a two-element list (`[1, 5]`).
+
+use crate::core::Term;
+use super::unifier::Substitution;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Interval {
+    pub from: f64,
+    pub to: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllenRelation {
+    Before,
+    Meets,
+    Overlaps,
+    Starts,
+    During,
+    Finishes,
+    Equals,
+    FinishedBy,
+    Contains,
+    StartedBy,
+    OverlappedBy,
+    MetBy,
+    After,
+}
+
+impl AllenRelation {
+    pub fn inverse(self) -> Self {
+        use AllenRelation::*;
+        match self {
+            Before => After,
+            Meets => MetBy,
+            Overlaps => OverlappedBy,
+            Starts => StartedBy,
+            During => Contains,
+            Finishes => FinishedBy,
+            Equals => Equals,
+            FinishedBy => Finishes,
+            Contains => During,
+            StartedBy => Starts,
+            OverlappedBy => Overlaps,
+            MetBy => Meets,
+            After => Before,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        use AllenRelation::*;
+        Some(match name {
+            "before" => Before,
+            "meets" => Meets,
+            "overlaps" => Overlaps,
+            "starts" => Starts,
+            "during" => During,
+            "finishes" => Finishes,
+            "equals" => Equals,
+            "finished_by" => FinishedBy,
+            "contains" => Contains,
+            "started_by" => StartedBy,
+            "overlapped_by" => OverlappedBy,
+            "met_by" => MetBy,
+            "after" => After,
+            _ => return None,
+        })
+    }
+}
+
+impl Interval {
+    pub fn new(from: f64, to: f64) -> Self {
+        Self { from, to }
+    }
+
+    // Open-ended interval starting at `from`
+    pub fn since(from: f64) -> Self {
+        Self { from, to: f64::INFINITY }
+    }
+
+    pub fn contains_point(&self, t: f64) -> bool {
+        self.from <= t && t < self.to
+    }
+
+    // The unique Allen relation between self and other
+    pub fn relation(&self, other: &Interval) -> AllenRelation {
+        use AllenRelation::*;
+        let (a, b) = (self, other);
+        if a.to < b.from {
+            Before
+        } else if a.to == b.from {
+            Meets
+        } else if b.to < a.from {
+            After
+        } else if b.to == a.from {
+            MetBy
+        } else if a.from == b.from && a.to == b.to {
+            Equals
+        } else if a.from == b.from {
+            if a.to < b.to { Starts } else { StartedBy }
+        } else if a.to == b.to {
+            if a.from > b.from { Finishes } else { FinishedBy }
+        } else if a.from > b.from && a.to < b.to {
+            During
+        } else if a.from < b.from && a.to > b.to {
+            Contains
+        } else if a.from < b.from {
+            Overlaps
+        } else {
+            OverlappedBy
+        }
+    }
+
+    pub fn from_term(term: &Term, sub: &Substitution) -> Option<Self> {
+        match sub.apply(term) {
+            Term::Compound(_, args) | Term::List(args) if args.len() == 2 => {
+                let from = number(&args[0])?;
+                let to = number(&args[1]).unwrap_or(f64::INFINITY);
+                Some(Self { from, to })
+            }
+            _ => None,
+        }
+    }
+}
+
+fn number(t: &Term) -> Option<f64> {
+    match t {
+        Term::Int(n) => Some(*n as f64),
+        Term::Float(f) => Some(f.val()),
+        _ => None,
+    }
+}
+
+// Validity of a `holds(Fact, From, To)` entry
+pub fn validity(from: &Term, to: &Term) -> Option<Interval> {
+    Some(Interval::new(number(from)?, number(to).unwrap_or(f64::INFINITY)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use AllenRelation::*;
+
+    #[test]
+    fn basic_relations() {
+        let a = Interval::new(1.0, 3.0);
+        assert_eq!(a.relation(&Interval::new(4.0, 6.0)), Before);
+        assert_eq!(a.relation(&Interval::new(3.0, 6.0)), Meets);
+        assert_eq!(a.relation(&Interval::new(2.0, 6.0)), Overlaps);
+        assert_eq!(a.relation(&Interval::new(0.0, 6.0)), During);
+        assert_eq!(a.relation(&Interval::new(1.0, 6.0)), Starts);
+        assert_eq!(a.relation(&Interval::new(0.0, 3.0)), Finishes);
+        assert_eq!(a.relation(&a), Equals);
+    }
+
+    #[test]
+    fn relation_inverse_is_symmetric() {
+        let a = Interval::new(1.0, 5.0);
+        let b = Interval::new(2.0, 8.0);
+        assert_eq!(b.relation(&a), a.relation(&b).inverse());
+    }
+
+    #[test]
+    fn open_interval_from_term() {
+        let t = Term::compound(1, vec![Term::int(4), Term::atom(2)]);
+        let i = Interval::from_term(&t, &Substitution::new()).unwrap();
+        assert!(i.contains_point(1e9));
+        assert!(!i.contains_point(3.0));
+    }
+}
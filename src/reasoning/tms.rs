@@ -0,0 +1,291 @@
+// Justification-based truth maintenance (JTMS).
+//
+// Every fact is a node labelled IN or OUT. Premises are IN unconditionally;
+// a derived node is IN while at least one of its justifications has all
+// antecedents IN. Retracting a node relabels everything downstream of it from
+// scratch, so circular support cannot keep a conclusion alive.
+
+use crate::core::Term;
+use rustc_hash::{FxHashMap, FxHashSet};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Justification {
+    pub consequent: Term,
+    pub antecedents: Vec<Term>,
+    // Id of the rule that produced this justification, if any
+    pub informant: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct TmsNode {
+    term: Term,
+    premise: bool,
+    label_in: bool,
+    justifications: Vec<usize>,
+    consequences: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+struct Just {
+    consequent: usize,
+    antecedents: Vec<usize>,
+    informant: Option<usize>,
+    active: bool,
+}
+
+// Proof tree for an IN node: the first justification whose antecedents are IN
+#[derive(Debug, Clone, PartialEq)]
+pub enum Support {
+    Premise(Term),
+    Derived {
+        term: Term,
+        informant: Option<usize>,
+        antecedents: Vec<Support>,
+    },
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Tms {
+    nodes: Vec<TmsNode>,
+    index: FxHashMap<Term, usize>,
+    justs: Vec<Just>,
+}
+
+impl Tms {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn node(&mut self, term: &Term) -> usize {
+        if let Some(&i) = self.index.get(term) {
+            return i;
+        }
+        let i = self.nodes.len();
+        self.nodes.push(TmsNode {
+            term: term.clone(),
+            premise: false,
+            label_in: false,
+            justifications: Vec::new(),
+            consequences: Vec::new(),
+        });
+        self.index.insert(term.clone(), i);
+        i
+    }
+
+    pub fn add_premise(&mut self, term: &Term) {
+        let i = self.node(term);
+        self.nodes[i].premise = true;
+        self.propagate_in(i);
+    }
+
+    // Record `antecedents => consequent`. Returns true if the consequent is IN.
+    pub fn justify(&mut self, consequent: &Term, antecedents: &[Term], informant: Option<usize>) -> bool {
+        let c = self.node(consequent);
+        let ants: Vec<usize> = antecedents.iter().map(|a| self.node(a)).collect();
+        let duplicate = self.nodes[c].justifications.iter()
+            .any(|&j| self.justs[j].active && self.justs[j].antecedents == ants);
+        if !duplicate {
+            let j = self.justs.len();
+            self.justs.push(Just { consequent: c, antecedents: ants.clone(), informant, active: true });
+            self.nodes[c].justifications.push(j);
+            for a in ants {
+                self.nodes[a].consequences.push(j);
+            }
+            if self.just_valid(j) {
+                self.propagate_in(c);
+            }
+        }
+        self.nodes[c].label_in
+    }
+
+    pub fn is_in(&self, term: &Term) -> bool {
+        self.index.get(term).is_some_and(|&i| self.nodes[i].label_in)
+    }
+
+    pub fn is_premise(&self, term: &Term) -> bool {
+        self.index.get(term).is_some_and(|&i| self.nodes[i].premise)
+    }
+
+    // Withdraw a node: it stops being a premise and loses its justifications.
+    // Returns every term that went OUT as a consequence (including itself).
+    pub fn retract(&mut self, term: &Term) -> Vec<Term> {
+        let i = match self.index.get(term) {
+            Some(&i) => i,
+            None => return Vec::new(),
+        };
+        self.nodes[i].premise = false;
+        for j in std::mem::take(&mut self.nodes[i].justifications) {
+            self.justs[j].active = false;
+        }
+        self.relabel_from(i)
+    }
+
+    pub fn justifications(&self, term: &Term) -> Vec<Justification> {
+        let i = match self.index.get(term) {
+            Some(&i) => i,
+            None => return Vec::new(),
+        };
+        self.nodes[i].justifications.iter()
+            .map(|&j| &self.justs[j])
+            .filter(|j| j.active)
+            .map(|j| Justification {
+                consequent: self.nodes[j.consequent].term.clone(),
+                antecedents: j.antecedents.iter().map(|&a| self.nodes[a].term.clone()).collect(),
+                informant: j.informant,
+            })
+            .collect()
+    }
+
+    pub fn explain(&self, term: &Term) -> Option<Support> {
+        let i = *self.index.get(term)?;
+        let mut visiting = FxHashSet::default();
+        self.support(i, &mut visiting)
+    }
+
+    fn support(&self, i: usize, visiting: &mut FxHashSet<usize>) -> Option<Support> {
+        let node = &self.nodes[i];
+        if !node.label_in {
+            return None;
+        }
+        if node.premise {
+            return Some(Support::Premise(node.term.clone()));
+        }
+        if !visiting.insert(i) {
+            return None;
+        }
+        let mut result = None;
+        for &j in &node.justifications {
+            let just = &self.justs[j];
+            if !just.active {
+                continue;
+            }
+            let ants: Option<Vec<Support>> = just.antecedents.iter()
+                .map(|&a| self.support(a, visiting))
+                .collect();
+            if let Some(antecedents) = ants {
+                result = Some(Support::Derived { term: node.term.clone(), informant: just.informant, antecedents });
+                break;
+            }
+        }
+        visiting.remove(&i);
+        result
+    }
+
+    pub fn in_terms(&self) -> Vec<Term> {
+        self.nodes.iter().filter(|n| n.label_in).map(|n| n.term.clone()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    fn just_valid(&self, j: usize) -> bool {
+        let just = &self.justs[j];
+        just.active && just.antecedents.iter().all(|&a| self.nodes[a].label_in)
+    }
+
+    fn propagate_in(&mut self, start: usize) {
+        let mut queue = vec![start];
+        while let Some(i) = queue.pop() {
+            if self.nodes[i].label_in {
+                continue;
+            }
+            let supported = self.nodes[i].premise
+                || self.nodes[i].justifications.iter().any(|&j| self.just_valid(j));
+            if !supported {
+                continue;
+            }
+            self.nodes[i].label_in = true;
+            for &j in &self.nodes[i].consequences {
+                if self.just_valid(j) {
+                    queue.push(self.justs[j].consequent);
+                }
+            }
+        }
+    }
+
+    fn relabel_from(&mut self, start: usize) -> Vec<Term> {
+        // Everything downstream loses its label...
+        let mut affected = Vec::new();
+        let mut seen = FxHashSet::default();
+        let mut stack = vec![start];
+        while let Some(i) = stack.pop() {
+            if !seen.insert(i) || !self.nodes[i].label_in {
+                continue;
+            }
+            self.nodes[i].label_in = false;
+            affected.push(i);
+            for &j in &self.nodes[i].consequences {
+                stack.push(self.justs[j].consequent);
+            }
+        }
+        // ...and regains it only through well-founded support
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &i in &affected {
+                if self.nodes[i].label_in {
+                    continue;
+                }
+                let supported = self.nodes[i].premise
+                    || self.nodes[i].justifications.iter().any(|&j| self.just_valid(j));
+                if supported {
+                    self.nodes[i].label_in = true;
+                    changed = true;
+                }
+            }
+        }
+        affected.into_iter()
+            .filter(|&i| !self.nodes[i].label_in)
+            .map(|i| self.nodes[i].term.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn t(n: u32) -> Term {
+        Term::compound(n, vec![])
+    }
+
+    #[test]
+    fn derived_follows_premise() {
+        let mut tms = Tms::new();
+        tms.add_premise(&t(1));
+        assert!(tms.justify(&t(2), &[t(1)], Some(0)));
+        assert!(tms.justify(&t(3), &[t(2)], Some(1)));
+        let out = tms.retract(&t(1));
+        assert_eq!(out.len(), 3);
+        assert!(!tms.is_in(&t(3)));
+    }
+
+    #[test]
+    fn alternative_support_survives() {
+        let mut tms = Tms::new();
+        tms.add_premise(&t(1));
+        tms.add_premise(&t(2));
+        tms.justify(&t(3), &[t(1)], None);
+        tms.justify(&t(3), &[t(2)], None);
+        tms.retract(&t(1));
+        assert!(tms.is_in(&t(3)));
+        assert!(matches!(tms.explain(&t(3)), Some(Support::Derived { .. })));
+    }
+
+    #[test]
+    fn circular_support_is_dropped() {
+        let mut tms = Tms::new();
+        tms.add_premise(&t(1));
+        tms.justify(&t(2), &[t(1)], None);
+        tms.justify(&t(3), &[t(2)], None);
+        tms.justify(&t(2), &[t(3)], None);
+        tms.retract(&t(1));
+        assert!(!tms.is_in(&t(2)));
+        assert!(!tms.is_in(&t(3)));
+    }
+}
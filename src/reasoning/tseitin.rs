@@ -0,0 +1,212 @@
+// Tseitin transformation of boolean Term formulas into CNF.
+//
+// Connectives are compounds named and/N, or/N, not/1, implies/2, iff/2 and
+// xor/2; Term::Bool is a constant. Every other term (atom, compound, variable)
+// is a propositional atom and gets its own SAT variable, so p(a) and p(b) are
+// independent. Each connective node gets a variable defined equivalent to its
+// subformula, which keeps the CNF linear in the size of the formula;
+// identical subformulas share one variable.
+//
+//   let mut enc = TseitinEncoder::new(&mut syms);
+//   enc.assert(&formula);
+//   if let SatResult::Sat(model) = enc.problem().solve() {
+//       let holds = enc.true_atoms(&model);
+//   }
+
+use crate::core::{Term, Sym, SymbolTable};
+use super::solver::{Assignment, Clause, Literal, SatProblem};
+use rustc_hash::FxHashMap;
+
+#[derive(Debug, Clone)]
+pub struct TseitinEncoder {
+    and: Sym,
+    or: Sym,
+    not: Sym,
+    implies: Sym,
+    iff: Sym,
+    xor: Sym,
+    clauses: Vec<Clause>,
+    // Source atom of each variable (None for connective nodes), index var - 1
+    atoms: Vec<Option<Term>>,
+    // Atoms and subformulas already encoded
+    cache: FxHashMap<Term, Literal>,
+    truth: Option<Literal>,
+}
+
+impl TseitinEncoder {
+    pub fn new(syms: &mut SymbolTable) -> Self {
+        Self {
+            and: syms.intern("and"),
+            or: syms.intern("or"),
+            not: syms.intern("not"),
+            implies: syms.intern("implies"),
+            iff: syms.intern("iff"),
+            xor: syms.intern("xor"),
+            clauses: Vec::new(),
+            atoms: Vec::new(),
+            cache: FxHashMap::default(),
+            truth: None,
+        }
+    }
+
+    fn fresh(&mut self, atom: Option<Term>) -> Literal {
+        self.atoms.push(atom);
+        self.atoms.len() as Literal
+    }
+
+    // Variable that is always true
+    fn truth(&mut self) -> Literal {
+        if let Some(t) = self.truth {
+            return t;
+        }
+        let t = self.fresh(None);
+        self.clauses.push(vec![t]);
+        self.truth = Some(t);
+        t
+    }
+
+    fn is_connective(&self, f: Sym, arity: usize) -> bool {
+        (f == self.and || f == self.or) && arity > 0
+            || f == self.not && arity == 1
+            || (f == self.implies || f == self.iff || f == self.xor) && arity == 2
+    }
+
+    // Literal equivalent to the formula
+    pub fn literal(&mut self, formula: &Term) -> Literal {
+        if let Some(&l) = self.cache.get(formula) {
+            return l;
+        }
+        let l = match formula {
+            Term::Bool(b) => {
+                let t = self.truth();
+                if *b { t } else { -t }
+            }
+            Term::Compound(f, args) if self.is_connective(*f, args.len()) => {
+                let f = *f;
+                let inner: Vec<Literal> = args.iter().map(|a| self.literal(a)).collect();
+                if f == self.not {
+                    -inner[0]
+                } else if f == self.implies {
+                    self.define_or(&[-inner[0], inner[1]])
+                } else if f == self.and {
+                    let negated: Vec<Literal> = inner.iter().map(|l| -l).collect();
+                    -self.define_or(&negated)
+                } else if f == self.or {
+                    self.define_or(&inner)
+                } else {
+                    let x = self.define_xor(inner[0], inner[1]);
+                    if f == self.iff { -x } else { x }
+                }
+            }
+            atom => self.fresh(Some(atom.clone())),
+        };
+        self.cache.insert(formula.clone(), l);
+        l
+    }
+
+    // x <-> (l1 or ... or ln)
+    fn define_or(&mut self, lits: &[Literal]) -> Literal {
+        if let [single] = lits {
+            return *single;
+        }
+        let x = self.fresh(None);
+        let mut clause = vec![-x];
+        clause.extend_from_slice(lits);
+        self.clauses.push(clause);
+        for &l in lits {
+            self.clauses.push(vec![x, -l]);
+        }
+        x
+    }
+
+    // x <-> (a xor b)
+    fn define_xor(&mut self, a: Literal, b: Literal) -> Literal {
+        let x = self.fresh(None);
+        self.clauses.extend([vec![-x, a, b], vec![-x, -a, -b], vec![x, -a, b], vec![x, a, -b]]);
+        x
+    }
+
+    // Require the formula to hold; top-level conjunctions are split
+    pub fn assert(&mut self, formula: &Term) {
+        match formula {
+            Term::Compound(f, args) if *f == self.and && !args.is_empty() => {
+                for a in args {
+                    self.assert(a);
+                }
+            }
+            _ => {
+                let l = self.literal(formula);
+                self.clauses.push(vec![l]);
+            }
+        }
+    }
+
+    pub fn var_of(&self, atom: &Term) -> Option<u32> {
+        self.cache.get(atom)
+            .filter(|l| **l > 0 && self.atom_of(l.unsigned_abs()) == Some(atom))
+            .map(|l| l.unsigned_abs())
+    }
+
+    pub fn atom_of(&self, var: u32) -> Option<&Term> {
+        self.atoms.get((var as usize).checked_sub(1)?)?.as_ref()
+    }
+
+    pub fn num_vars(&self) -> u32 {
+        self.atoms.len() as u32
+    }
+
+    pub fn clauses(&self) -> &[Clause] {
+        &self.clauses
+    }
+
+    pub fn problem(&self) -> SatProblem {
+        SatProblem::from_clauses(self.num_vars(), self.clauses.clone())
+    }
+
+    // Source atoms assigned true, in order of first occurrence
+    pub fn true_atoms(&self, model: &Assignment) -> Vec<Term> {
+        self.atoms.iter().enumerate()
+            .filter(|(i, _)| model.get(&(*i as u32 + 1)) == Some(&true))
+            .filter_map(|(_, atom)| atom.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reasoning::solver::SatResult;
+
+    #[test]
+    fn encodes_connectives() {
+        let mut syms = SymbolTable::new();
+        let mut enc = TseitinEncoder::new(&mut syms);
+        let [rain, wet, sprinkler] = ["rain", "wet", "sprinkler"].map(|name| Term::atom(syms.intern(name)));
+        let c = |name: &str, args: Vec<Term>, syms: &mut SymbolTable| Term::compound(syms.intern(name), args);
+        // (rain or sprinkler) -> wet, not wet, rain xor sprinkler is unsatisfiable
+        let cause = c("or", vec![rain.clone(), sprinkler.clone()], &mut syms);
+        enc.assert(&c("implies", vec![cause, wet.clone()], &mut syms));
+        enc.assert(&c("not", vec![wet.clone()], &mut syms));
+        assert!(matches!(enc.problem().solve(), SatResult::Sat(m) if enc.true_atoms(&m).is_empty()));
+
+        enc.assert(&c("xor", vec![rain.clone(), sprinkler], &mut syms));
+        assert_eq!(enc.problem().solve(), SatResult::Unsat);
+        assert_eq!(enc.atom_of(enc.var_of(&rain).unwrap()), Some(&rain));
+        assert!(enc.var_of(&wet).is_some());
+    }
+
+    #[test]
+    fn iff_and_constants() {
+        let mut syms = SymbolTable::new();
+        let mut enc = TseitinEncoder::new(&mut syms);
+        let p = syms.intern("p");
+        let (a, b) = (Term::compound(p, vec![Term::atom(1)]), Term::compound(p, vec![Term::atom(2)]));
+        let iff = Term::compound(syms.intern("iff"), vec![a.clone(), Term::compound(syms.intern("and"), vec![b.clone(), Term::Bool(true)])]);
+        enc.assert(&iff);
+        enc.assert(&b);
+        match enc.problem().solve() {
+            SatResult::Sat(m) => assert_eq!(enc.true_atoms(&m), vec![a, b]),
+            SatResult::Unsat => panic!("satisfiable"),
+        }
+    }
+}
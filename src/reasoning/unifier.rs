@@ -1,9 +1,38 @@
-use crate::core::{Term, Sym, Result, KolossError};
+use crate::core::{Term, Sym, Result, KolossError, SymbolTable, VarNames, CONS};
 use rustc_hash::FxHashMap;
+use std::sync::{Arc, OnceLock};
+
+// Bindings live in a persistent stack of frozen frames plus a small mutable
+// head. Cloning copies only the head and shares the frozen frames, so the
+// solver can branch without duplicating the whole map. Variable-to-variable
+// bindings always point at the representative of the target (union-find), so
+// chains stay short; `walk` is iterative.
+//
+// Attributes of unbound variables (see RuleEngine: freeze/2, put_attr/3)
+// travel with the substitution, so backtracking restores them too. They are
+// shared copy-on-write between clones.
+//
+// bindings() hands out the flattened map, built on first use after a bind
+// and shared between clones until the next one.
+const HEAD_LIMIT: usize = 16;
+
+#[derive(Debug)]
+struct Frame {
+    bindings: FxHashMap<Sym, Term>,
+    parent: Option<Arc<Frame>>,
+    depth: usize,
+}
+
+// Attributed variable -> (module, value) pairs
+type AttrMap = FxHashMap<Sym, Vec<(Sym, Term)>>;
 
 #[derive(Debug, Clone, Default)]
 pub struct Substitution {
-    bindings: FxHashMap<Sym, Term>,
+    frozen: Option<Arc<Frame>>,
+    head: FxHashMap<Sym, Term>,
+    len: usize,
+    attrs: Option<Arc<AttrMap>>,
+    view: OnceLock<Arc<FxHashMap<Sym, Term>>>,
 }
 
 impl Substitution {
@@ -12,21 +41,90 @@ impl Substitution {
     }
 
     pub fn bind(&mut self, var: Sym, term: Term) {
-        self.bindings.insert(var, term);
+        // Path compression: link to the representative, never to a chain
+        let term = match term {
+            Term::Var(w) => match self.find(w) {
+                Term::Var(root) if root == var => return,
+                root => root,
+            },
+            other => other,
+        };
+        if self.lookup(var).is_none() {
+            self.len += 1;
+        }
+        self.head.insert(var, term);
+        self.view = OnceLock::new();
+        if self.head.len() >= HEAD_LIMIT {
+            self.freeze();
+        }
+    }
+
+    fn freeze(&mut self) {
+        let mut bindings = std::mem::take(&mut self.head);
+        let mut parent = self.frozen.take();
+        // Merge in frames no larger than the new one (like a binary counter),
+        // so the stack stays logarithmic in the number of bindings
+        while let Some(frame) = parent.take() {
+            if frame.bindings.len() > bindings.len() {
+                parent = Some(frame);
+                break;
+            }
+            let (mut older, grand) = match Arc::try_unwrap(frame) {
+                Ok(frame) => (frame.bindings, frame.parent),
+                Err(shared) => (shared.bindings.clone(), shared.parent.clone()),
+            };
+            older.extend(bindings);
+            bindings = older;
+            parent = grand;
+        }
+        let depth = parent.as_ref().map_or(0, |f| f.depth + 1);
+        self.frozen = Some(Arc::new(Frame { bindings, parent, depth }));
+    }
+
+    fn flatten(&self) -> FxHashMap<Sym, Term> {
+        let mut frames = Vec::new();
+        let mut cur = self.frozen.as_deref();
+        while let Some(frame) = cur {
+            frames.push(frame);
+            cur = frame.parent.as_deref();
+        }
+        let mut all = FxHashMap::default();
+        for frame in frames.into_iter().rev() {
+            all.extend(frame.bindings.iter().map(|(k, v)| (*k, v.clone())));
+        }
+        all.extend(self.head.iter().map(|(k, v)| (*k, v.clone())));
+        all
     }
 
     pub fn lookup(&self, var: Sym) -> Option<&Term> {
-        self.bindings.get(&var)
+        if let Some(t) = self.head.get(&var) {
+            return Some(t);
+        }
+        let mut cur = self.frozen.as_deref();
+        while let Some(frame) = cur {
+            if let Some(t) = frame.bindings.get(&var) {
+                return Some(t);
+            }
+            cur = frame.parent.as_deref();
+        }
+        None
+    }
+
+    // Representative of a variable: the first non-variable or unbound variable
+    fn find(&self, var: Sym) -> Term {
+        let mut v = var;
+        loop {
+            match self.lookup(v) {
+                Some(Term::Var(next)) => v = *next,
+                Some(bound) => return bound.clone(),
+                None => return Term::Var(v),
+            }
+        }
     }
 
     pub fn walk(&self, term: &Term) -> Term {
         match term {
-            Term::Var(v) => {
-                match self.bindings.get(v) {
-                    Some(bound) => self.walk(bound),
-                    None => term.clone(),
-                }
-            }
+            Term::Var(v) => self.find(*v),
             _ => term.clone(),
         }
     }
@@ -34,12 +132,19 @@ impl Substitution {
     pub fn walk_deep(&self, term: &Term) -> Term {
         let walked = self.walk(term);
         match walked {
+            // A cons cell whose tail is now a list folds back into the list
+            Term::Compound(CONS, args) if args.len() == 2 => {
+                Term::cons(self.walk_deep(&args[0]), self.walk_deep(&args[1]))
+            }
             Term::Compound(f, args) => {
                 Term::Compound(f, args.iter().map(|a| self.walk_deep(a)).collect())
             }
             Term::List(items) => {
                 Term::List(items.iter().map(|a| self.walk_deep(a)).collect())
             }
+            Term::Dict(pairs) => {
+                Term::Dict(pairs.iter().map(|(k, v)| (*k, self.walk_deep(v))).collect())
+            }
             other => other,
         }
     }
@@ -50,27 +155,98 @@ impl Substitution {
 
     pub fn compose(&self, other: &Substitution) -> Substitution {
         let mut result = Substitution::new();
-        for (&var, term) in &self.bindings {
+        let mine = self.flatten();
+        for (&var, term) in &mine {
             result.bind(var, other.apply(term));
         }
-        for (&var, term) in &other.bindings {
-            if !result.bindings.contains_key(&var) {
-                result.bind(var, term.clone());
+        for (var, term) in other.flatten() {
+            if !mine.contains_key(&var) {
+                result.bind(var, term);
             }
         }
+        result.attrs = self.attrs.clone();
         result
     }
 
+    // Flattened view of every binding (newer bindings shadow older ones)
     pub fn bindings(&self) -> &FxHashMap<Sym, Term> {
-        &self.bindings
+        self.view.get_or_init(|| Arc::new(self.flatten()))
+    }
+
+    // Bindings of the named query variables, fully resolved, in declaration order.
+    // Unbound variables are reported as themselves.
+    pub fn bindings_named(&self, names: &VarNames) -> Vec<(String, Term)> {
+        names.iter()
+            .map(|(v, name)| (name.to_string(), self.apply(&Term::Var(v))))
+            .collect()
+    }
+
+    // "X = bob, Y = 3" (or "true" when the query has no named variables)
+    pub fn format_named(&self, names: &VarNames, syms: &SymbolTable) -> String {
+        let parts: Vec<String> = self.bindings_named(names)
+            .iter()
+            .map(|(name, t)| format!("{} = {}", name, t.display_named(syms, names)))
+            .collect();
+        if parts.is_empty() { "true".into() } else { parts.join(", ") }
     }
 
     pub fn len(&self) -> usize {
-        self.bindings.len()
+        self.len
+    }
+
+    // --- Attributed variables ---
+
+    pub fn has_attrs(&self) -> bool {
+        self.attrs.as_ref().is_some_and(|a| !a.is_empty())
+    }
+
+    pub fn put_attr(&mut self, var: Sym, module: Sym, value: Term) {
+        let attrs = Arc::make_mut(self.attrs.get_or_insert_with(Default::default));
+        let entry = attrs.entry(var).or_default();
+        match entry.iter_mut().find(|(m, _)| *m == module) {
+            Some(slot) => slot.1 = value,
+            None => entry.push((module, value)),
+        }
+    }
+
+    pub fn get_attr(&self, var: Sym, module: Sym) -> Option<&Term> {
+        self.attrs.as_ref()?.get(&var)?.iter().find(|(m, _)| *m == module).map(|(_, v)| v)
+    }
+
+    pub fn del_attr(&mut self, var: Sym, module: Sym) -> bool {
+        if self.get_attr(var, module).is_none() {
+            return false;
+        }
+        let attrs = Arc::make_mut(self.attrs.as_mut().unwrap());
+        if let Some(entry) = attrs.get_mut(&var) {
+            entry.retain(|(m, _)| *m != module);
+            if entry.is_empty() {
+                attrs.remove(&var);
+            }
+        }
+        true
+    }
+
+    // Remove and return every attribute of `var`
+    pub fn take_attrs(&mut self, var: Sym) -> Vec<(Sym, Term)> {
+        match &mut self.attrs {
+            Some(attrs) if attrs.contains_key(&var) => Arc::make_mut(attrs).remove(&var).unwrap_or_default(),
+            _ => Vec::new(),
+        }
+    }
+
+    // Attributed variables that are no longer unbound representatives
+    pub fn bound_attributed_vars(&self) -> Vec<Sym> {
+        let mut vars: Vec<Sym> = match &self.attrs {
+            Some(attrs) => attrs.keys().copied().filter(|&v| self.find(v) != Term::Var(v)).collect(),
+            None => Vec::new(),
+        };
+        vars.sort_unstable();
+        vars
     }
 
     pub fn is_empty(&self) -> bool {
-        self.bindings.is_empty()
+        self.len == 0
     }
 }
 
@@ -116,6 +292,20 @@ pub fn unify(t1: &Term, t2: &Term, sub: &Substitution) -> Result<Substitution> {
             Ok(s)
         }
 
+        (Term::Nil, Term::List(l)) | (Term::List(l), Term::Nil) if l.is_empty() => Ok(sub.clone()),
+
+        // [H|T] against a flat list
+        (Term::List(items), Term::Compound(CONS, cell)) | (Term::Compound(CONS, cell), Term::List(items))
+            if cell.len() == 2 =>
+        {
+            let (head, tail) = match items.split_first() {
+                Some(split) => split,
+                None => return Err(KolossError::UnificationFail("[H|T] against []".into())),
+            };
+            let s = unify(&cell[0], head, sub)?;
+            unify(&cell[1], &Term::List(tail.to_vec()), &s)
+        }
+
         (Term::List(l1), Term::List(l2)) => {
             if l1.len() != l2.len() {
                 return Err(KolossError::UnificationFail(
@@ -129,6 +319,18 @@ pub fn unify(t1: &Term, t2: &Term, sub: &Substitution) -> Result<Substitution> {
             Ok(s)
         }
 
+        // Dicts unify when they have the same keys and unifiable values
+        (Term::Dict(d1), Term::Dict(d2)) => {
+            if d1.len() != d2.len() || d1.iter().zip(d2.iter()).any(|((k1, _), (k2, _))| k1 != k2) {
+                return Err(KolossError::UnificationFail("dict key mismatch".into()));
+            }
+            let mut s = sub.clone();
+            for ((_, a), (_, b)) in d1.iter().zip(d2.iter()) {
+                s = unify(a, b, &s)?;
+            }
+            Ok(s)
+        }
+
         _ => Err(KolossError::UnificationFail(
             format!("cannot unify {} with {}", w1, w2)
         )),
@@ -142,6 +344,7 @@ fn occurs_check(var: Sym, term: &Term, sub: &Substitution) -> bool {
         Term::Compound(_, args) | Term::List(args) => {
             args.iter().any(|a| occurs_check(var, a, sub))
         }
+        Term::Dict(pairs) => pairs.iter().any(|(_, v)| occurs_check(var, v, sub)),
         _ => false,
     }
 }
@@ -163,6 +366,80 @@ pub fn rename_vars(term: &Term, offset: Sym) -> Term {
         Term::List(items) => {
             Term::List(items.iter().map(|a| rename_vars(a, offset)).collect())
         }
+        Term::Dict(pairs) => {
+            Term::Dict(pairs.iter().map(|(k, v)| (*k, rename_vars(v, offset))).collect())
+        }
         other => other.clone(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deep_variable_chains_resolve_iteratively() {
+        // Binding each variable to the next, still unbound one builds a chain
+        // that union-find cannot shorten
+        let n = 10_000;
+        let mut sub = Substitution::new();
+        for v in 0..n {
+            sub.bind(v, Term::var(v + 1));
+        }
+        sub.bind(n, Term::int(7));
+        assert_eq!(sub.walk(&Term::var(0)), Term::int(7));
+        let nested = Term::compound(n + 1, vec![Term::var(0), Term::var(n / 2)]);
+        assert_eq!(sub.apply(&nested), Term::compound(n + 1, vec![Term::int(7), Term::int(7)]));
+        assert_eq!(sub.len(), n as usize + 1);
+    }
+
+    #[test]
+    fn merged_frames_keep_every_binding() {
+        let n = (HEAD_LIMIT * 1_500) as Sym;
+        let mut sub = Substitution::new();
+        let mut snapshot = None;
+        for v in 0..n {
+            sub.bind(v, Term::int(v as i64));
+            if v == n / 2 {
+                snapshot = Some(sub.clone());
+            }
+        }
+        // 1500 frozen heads merge into one frame per set bit of 1500
+        assert_eq!(sub.frozen.as_ref().unwrap().depth + 1, 1_500u32.count_ones() as usize);
+        assert_eq!(sub.len(), n as usize);
+        assert!((0..n).all(|v| sub.lookup(v) == Some(&Term::int(v as i64))));
+
+        // A clone taken before later merges keeps its own bindings
+        let snapshot = snapshot.unwrap();
+        assert_eq!(snapshot.len(), n as usize / 2 + 1);
+        assert_eq!(snapshot.lookup(n / 2), Some(&Term::int(n as i64 / 2)));
+        assert_eq!(snapshot.lookup(n - 1), None);
+
+        // Rebinding shadows the old value and refreshes the borrowed view
+        assert_eq!(sub.bindings().len(), n as usize);
+        sub.bind(0, Term::int(-1));
+        assert_eq!(sub.bindings().get(&0), Some(&Term::int(-1)));
+        assert_eq!(sub.bindings().len(), n as usize);
+        assert_eq!(snapshot.bindings().len(), n as usize / 2 + 1);
+    }
+
+    #[test]
+    fn answers_use_the_query_variable_names() {
+        let mut syms = SymbolTable::new();
+        let [likes, f, bob] = ["likes", "f", "bob"].map(|n| syms.intern(n));
+        let mut names = VarNames::new();
+        let (x, who, y) = (names.var("X"), names.var("Who"), names.var("Y"));
+        assert_eq!(names.var("X"), x);
+
+        // likes(X, Who) = likes(bob, f(Y, _G7))
+        let query = Term::compound(likes, vec![x, who]);
+        let fact = Term::compound(likes, vec![Term::atom(bob), Term::compound(f, vec![y.clone(), Term::var(7)])]);
+        let sub = unify(&query, &fact, &Substitution::new()).unwrap();
+
+        let named = sub.bindings_named(&names);
+        assert_eq!(named[0], ("X".to_string(), Term::atom(bob)));
+        assert_eq!(named[2], ("Y".to_string(), y));
+        assert_eq!(sub.format_named(&names, &syms), "X = bob, Who = f(Y, _G7), Y = Y");
+        assert_eq!(sub.format_named(&VarNames::new(), &syms), "true");
+    }
+}
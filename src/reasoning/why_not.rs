@@ -0,0 +1,123 @@
+// Failure analysis ("why not?") for queries without answers.
+//
+// While a query runs in failure-analysis mode, every goal that produces no
+// alternative is recorded with the reason it failed. Only the deepest
+// failures are kept: they are usually the closest to the missing fact or the
+// wrong clause. See RuleEngine::why_not.
+
+use crate::core::{Term, SymbolTable};
+use std::fmt::Write;
+
+// Upper bound on the failures kept at the deepest level
+const MAX_FAILURES: usize = 16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FailureReason {
+    // The depth budget ran out before the goal could be resolved
+    DepthLimit,
+    // not(G) / \+ G failed because G has a solution
+    NegatedGoalSucceeded,
+    // A builtin test or computation failed
+    BuiltinFailed,
+    // Clauses for the predicate exist but none unifies with the goal.
+    // `rules` are indices into RuleEngine::rules()
+    NoMatchingClause { rules: Vec<usize>, facts: usize },
+    // Nothing defines the predicate at this arity
+    UnknownPredicate,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailedGoal {
+    pub goal: Term,
+    pub depth: usize,
+    pub reason: FailureReason,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct FailureTrace {
+    pub query: Vec<Term>,
+    pub depth: usize,
+    pub failures: Vec<FailedGoal>,
+    // Total number of failing goals seen, at every depth
+    pub total: usize,
+}
+
+impl FailureTrace {
+    pub fn new(query: &[Term]) -> Self {
+        Self { query: query.to_vec(), ..Self::default() }
+    }
+
+    pub fn record(&mut self, goal: Term, depth: usize, reason: FailureReason) {
+        self.total += 1;
+        if depth < self.depth {
+            return;
+        }
+        if depth > self.depth {
+            self.depth = depth;
+            self.failures.clear();
+        }
+        let failure = FailedGoal { goal, depth, reason };
+        if self.failures.len() < MAX_FAILURES && !self.failures.contains(&failure) {
+            self.failures.push(failure);
+        }
+    }
+
+    pub fn format(&self, syms: &SymbolTable) -> String {
+        let mut out = String::new();
+        let query: Vec<String> = self.query.iter().map(|g| g.display(syms).to_string()).collect();
+        let _ = writeln!(out, "no answer for: {}", query.join(", "));
+        let _ = writeln!(out, "deepest failures (depth {}, {} failing goals in total):", self.depth, self.total);
+        for f in &self.failures {
+            let reason = match &f.reason {
+                FailureReason::DepthLimit => "depth limit reached".to_string(),
+                FailureReason::NegatedGoalSucceeded => "negated goal has a solution".to_string(),
+                FailureReason::BuiltinFailed => "builtin failed".to_string(),
+                FailureReason::NoMatchingClause { rules, facts } => {
+                    format!("no clause matches ({} rules, {} facts tried)", rules.len(), facts)
+                }
+                FailureReason::UnknownPredicate => "unknown predicate".to_string(),
+            };
+            let _ = writeln!(out, "  {}: {}", f.goal.display(syms), reason);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reasoning::rules::{Rule, RuleEngine};
+
+    #[test]
+    fn keeps_only_deepest() {
+        let mut trace = FailureTrace::new(&[Term::atom(1)]);
+        trace.record(Term::atom(2), 1, FailureReason::BuiltinFailed);
+        trace.record(Term::atom(3), 3, FailureReason::UnknownPredicate);
+        trace.record(Term::atom(4), 2, FailureReason::BuiltinFailed);
+        trace.record(Term::atom(3), 3, FailureReason::UnknownPredicate);
+        assert_eq!(trace.depth, 3);
+        assert_eq!(trace.failures.len(), 1);
+        assert_eq!(trace.total, 4);
+    }
+
+    #[test]
+    fn reports_deepest_missing_clause() {
+        let (parent, grandparent, a, b) = (1, 2, 10, 11);
+        let mut engine = RuleEngine::new();
+        engine.add_fact(Term::compound(parent, vec![Term::atom(a), Term::atom(b)]));
+        engine.add_rule(Rule::new(
+            Term::compound(grandparent, vec![Term::var(0), Term::var(2)]),
+            vec![
+                Term::compound(parent, vec![Term::var(0), Term::var(1)]),
+                Term::compound(parent, vec![Term::var(1), Term::var(2)]),
+            ],
+        ));
+        assert!(engine.why_not(&Term::compound(parent, vec![Term::atom(a), Term::var(0)])).is_none());
+
+        let trace = engine.why_not(&Term::compound(grandparent, vec![Term::atom(a), Term::var(0)])).unwrap();
+        assert_eq!(trace.failures.len(), 1);
+        let failed = &trace.failures[0];
+        assert!(matches!(&failed.goal, Term::Compound(f, args) if *f == parent && args[0] == Term::atom(b)));
+        assert_eq!(failed.reason, FailureReason::NoMatchingClause { rules: vec![], facts: 1 });
+    }
+}
@@ -113,15 +113,17 @@ pub fn hill_climb(
         let mut best_mutation = None;
         let mut best_fitness = current_fitness;
 
+        // Try each mutation in place and roll back, instead of cloning
+        let checkpoint = engine.snapshot();
         for mutation in &mutations {
-            let mut candidate = engine.clone();
-            if apply_mutation(&mut candidate, mutation) {
-                let fitness = evaluate_engine(&mut candidate, test_cases);
+            if apply_mutation(engine, mutation) {
+                let fitness = evaluate_engine(engine, test_cases);
                 if fitness > best_fitness + 0.001 {
                     best_fitness = fitness;
                     best_mutation = Some(mutation.clone());
                 }
             }
+            engine.restore(&checkpoint);
         }
 
         if let Some(mutation) = best_mutation {
@@ -11,8 +11,20 @@
 // 3. Extract frequent sub-programs as new library primitives
 // 4. Re-index the DSL with compressed programs
 // 5. Repeat — the library grows, search space shrinks
+//
+// Sub-programs that recur with different constants (a color, an offset)
+// are anti-unified (see antiunify.rs) into entries with holes, which the
+// searches see as one primitive per filling of the holes.
 
 use super::dsl::{Prim, Grid};
+use super::antiunify::{anti_unify_all, skeleton, Param, Template};
+use super::compression::description_length;
+use super::grammar::Grammar;
+use super::normalize::normalize;
+use super::parallel::Workers;
+use super::bidir::state_hash;
+use std::borrow::Cow;
+use std::time::{Duration, Instant};
 use rustc_hash::FxHashMap;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -26,20 +38,43 @@ pub struct LibEntry {
     pub program: Prim,
     pub usage_count: usize,
     pub compression: usize, // how many nodes it saves vs inline
+    // Constants of `program` that are parameters, empty for a closed entry
+    pub holes: Vec<usize>,
 }
 
+impl LibEntry {
+    pub fn template(&self) -> Template {
+        Template { program: self.program.clone(), holes: self.holes.clone() }
+    }
+
+    // Name of the entry applied to `args`, e.g. lib_2(3)
+    fn call_name(&self, args: &[Param]) -> String {
+        if args.is_empty() { return self.name.clone(); }
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        format!("{}({})", self.name, args.join(","))
+    }
+}
+
+// Instances a parameterized entry expands to, at most
+const MAX_INSTANCES: usize = 100;
+
 impl Library {
     pub fn new() -> Self {
         Self { entries: Vec::new() }
     }
 
     pub fn add(&mut self, name: String, program: Prim) {
-        let compression = program.size();
+        self.add_template(name, Template { program, holes: Vec::new() });
+    }
+
+    pub fn add_template(&mut self, name: String, template: Template) {
+        let compression = template.program.size();
         self.entries.push(LibEntry {
             name,
-            program,
+            program: template.program,
             usage_count: 0,
             compression,
+            holes: template.holes,
         });
     }
 
@@ -58,6 +93,40 @@ impl Library {
     pub fn total_compression(&self) -> usize {
         self.entries.iter().map(|e| e.usage_count * e.compression.saturating_sub(1)).sum()
     }
+
+    // Each entry as a one-step primitive for the searches, a parameterized
+    // entry once per filling of its holes
+    pub fn primitives(&self) -> Vec<Prim> {
+        self.entries.iter().flat_map(|e| {
+            let instances = if e.holes.is_empty() {
+                vec![(Vec::new(), e.program.clone())]
+            } else {
+                e.template().instances(MAX_INSTANCES)
+            };
+            instances.into_iter().map(move |(args, body)| Prim::LibRef(e.call_name(&args), Box::new(body)))
+        }).collect()
+    }
+
+    // Library entries first, then the base primitives
+    pub fn with_primitives(&self, base: &[Prim]) -> Vec<Prim> {
+        let mut prims = self.primitives();
+        prims.extend_from_slice(base);
+        prims
+    }
+
+    // Adds the entries of `other` whose programs are new, renamed to keep
+    // names unique
+    pub fn merge(&mut self, other: Library) {
+        for entry in other.entries {
+            let known = self.entries.iter()
+                .any(|e| e.holes == entry.holes && e.template().match_args(&entry.program).is_some());
+            if known { continue; }
+            let name = (self.entries.len()..).map(|i| format!("lib_{}", i))
+                .find(|name| self.get(name).is_none())
+                .unwrap_or_default();
+            self.entries.push(LibEntry { name, ..entry });
+        }
+    }
 }
 
 // Extract sub-programs from a program tree
@@ -76,6 +145,10 @@ fn extract_subprograms(prog: &Prim, min_size: usize) -> Vec<Prim> {
             subs.extend(extract_subprograms(b, min_size));
             subs.extend(extract_subprograms(c, min_size));
         }
+        Prim::Binary(_, a, b) => {
+            subs.extend(extract_subprograms(a, min_size));
+            subs.extend(extract_subprograms(b, min_size));
+        }
         _ => {}
     }
     subs
@@ -108,7 +181,9 @@ fn hash_prim(p: &Prim) -> u64 {
 // Wake phase: extract library from solved programs
 pub fn wake_extract(solved_programs: &[Prim], min_freq: usize, min_size: usize, max_entries: usize) -> Library {
     let mut lib = Library::new();
-    let freqs = count_subprogram_frequency(solved_programs, min_size);
+    // Equivalent programs written differently should count as one
+    let solved: Vec<Prim> = solved_programs.iter().map(normalize).collect();
+    let freqs = count_subprogram_frequency(&solved, min_size);
 
     for (i, (prog, count)) in freqs.iter().enumerate() {
         if *count < min_freq { break; }
@@ -123,6 +198,31 @@ pub fn wake_extract(solved_programs: &[Prim], min_freq: usize, min_size: usize,
         }
     }
 
+    // Sub-programs sharing a tree but not their constants: one entry with
+    // holes per group, counting every occurrence of its members
+    let mut groups: FxHashMap<u64, Vec<(Prim, usize)>> = FxHashMap::default();
+    for (prog, count) in &freqs {
+        if prog.size() <= 1 { continue; }
+        groups.entry(hash_prim(&skeleton(prog))).or_default().push((prog.clone(), *count));
+    }
+    let mut templates: Vec<(Template, usize)> = groups.into_values()
+        .filter(|members| members.len() >= 2)
+        .filter_map(|members| {
+            let total = members.iter().map(|(_, count)| count).sum();
+            let programs: Vec<Prim> = members.into_iter().map(|(prog, _)| prog).collect();
+            anti_unify_all(&programs).map(|template| (template, total))
+        })
+        .filter(|(_, total)| *total >= min_freq)
+        .collect();
+    templates.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| hash_prim(&a.0.program).cmp(&hash_prim(&b.0.program))));
+    for (k, (template, count)) in templates.into_iter().enumerate() {
+        if lib.len() >= max_entries { break; }
+        lib.add_template(format!("tpl_{}", k), template);
+        if let Some(entry) = lib.entries.last_mut() {
+            entry.usage_count = count;
+        }
+    }
+
     lib
 }
 
@@ -130,10 +230,8 @@ pub fn wake_extract(solved_programs: &[Prim], min_freq: usize, min_size: usize,
 pub fn sleep_compress(program: &Prim, library: &Library) -> Prim {
     // Try to match each library entry against the program
     for entry in &library.entries {
-        if *program == entry.program {
-            // In a full implementation, this would return a LibRef
-            // For now, we just track that it was matched
-            return program.clone();
+        if let Some(args) = entry.template().match_args(program) {
+            return Prim::LibRef(entry.call_name(&args), Box::new(program.clone()));
         }
     }
 
@@ -149,121 +247,267 @@ pub fn sleep_compress(program: &Prim, library: &Library) -> Prim {
             let cc = sleep_compress(c, library);
             Prim::Conditional(Box::new(ca), Box::new(cb), Box::new(cc))
         }
+        Prim::Binary(op, a, b) => {
+            let ca = sleep_compress(a, library);
+            let cb = sleep_compress(b, library);
+            Prim::Binary(*op, Box::new(ca), Box::new(cb))
+        }
         other => other.clone(),
     }
 }
 
 // DAG-based search (Icecuber-style)
-// Store intermediate grid results in a DAG, greedily compose primitives
+// Store intermediate grid results in a DAG, greedily compose primitives.
+// With a beam width, each depth keeps only the W new nodes with the lowest
+// MDL prior (program description length plus the bits to fix the cells
+// that still differ from the target), so the budget goes to promising
+// branches instead of the whole breadth. Each depth expands its frontier
+// on `threads` workers (see parallel.rs) and merges the new nodes in order.
+// With a grammar (see grammar.rs), likely programs are expanded first and
+// each node tries its likely next steps first.
+// Duplicate grids are found through a hash index, verified on collision.
+// A time budget stops the search between frontier nodes; search_scored then
+// still returns the closest programs found so far.
 #[derive(Debug)]
 pub struct SearchDag {
     nodes: Vec<DagNode>,
+    // Grid hash -> indices of the nodes with that hash
+    seen: FxHashMap<u64, Vec<usize>>,
     max_nodes: usize,
+    beam_width: Option<usize>,
+    workers: Workers,
+    time_budget: Option<Duration>,
+    timed_out: bool,
+    // Learned step weights ordering the enumeration, if any
+    grammar: Option<Grammar>,
 }
 
 #[derive(Debug, Clone)]
 struct DagNode {
-    grid: Grid,
+    // One grid per training input
+    grids: Vec<Grid>,
+    hash: u64,
     program: Prim,
     depth: usize,
 }
 
+impl DagNode {
+    fn new(grids: Vec<Grid>, program: Prim, depth: usize) -> Self {
+        Self { hash: state_hash(&grids), grids, program, depth }
+    }
+}
+
+// Whether `nodes` has a node with these grids, looked up through `index`
+fn indexed(index: &FxHashMap<u64, Vec<usize>>, nodes: &[DagNode], grids: &[Grid], hash: u64) -> bool {
+    index.get(&hash).is_some_and(|ids| ids.iter().any(|&i| nodes[i].grids == grids))
+}
+
 impl SearchDag {
     pub fn new(max_nodes: usize) -> Self {
-        Self { nodes: Vec::new(), max_nodes }
+        Self {
+            nodes: Vec::new(),
+            seen: FxHashMap::default(),
+            max_nodes,
+            beam_width: None,
+            workers: Workers::default(),
+            time_budget: None,
+            timed_out: false,
+            grammar: None,
+        }
     }
 
-    pub fn search(&mut self, input: &Grid, target: &Grid, primitives: &[Prim], max_depth: usize) -> Option<Prim> {
+    // Deadline of a search starting now
+    fn reset(&mut self, inputs: Vec<Grid>) -> Option<Instant> {
         self.nodes.clear();
-        self.nodes.push(DagNode {
-            grid: input.clone(),
-            program: Prim::Identity,
-            depth: 0,
-        });
+        self.seen.clear();
+        self.timed_out = false;
+        self.insert(DagNode::new(inputs, Prim::Identity, 0));
+        self.time_budget.map(|budget| Instant::now() + budget)
+    }
 
-        // Check identity
-        if input == target {
-            return Some(Prim::Identity);
-        }
+    fn insert(&mut self, node: DagNode) {
+        self.seen.entry(node.hash).or_default().push(self.nodes.len());
+        self.nodes.push(node);
+    }
 
-        for depth in 0..max_depth {
-            let current_count = self.nodes.len();
-            let mut new_nodes = Vec::new();
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.workers = Workers::new(threads);
+        self
+    }
 
-            for node_idx in 0..current_count {
-                if self.nodes[node_idx].depth != depth { continue; }
-                let grid = self.nodes[node_idx].grid.clone();
-                let prog = self.nodes[node_idx].program.clone();
+    pub fn with_time_budget(mut self, budget: Duration) -> Self {
+        self.time_budget = Some(budget);
+        self
+    }
 
-                for prim in primitives {
-                    let result = prim.apply(&grid);
+    // Whether the last search stopped on its time budget
+    pub fn timed_out(&self) -> bool {
+        self.timed_out
+    }
 
-                    // Check if we found the target
-                    if result == *target {
-                        if depth == 0 {
-                            return Some(prim.clone());
-                        } else {
-                            return Some(Prim::Compose(Box::new(prog.clone()), Box::new(prim.clone())));
-                        }
-                    }
+    pub fn with_beam(mut self, width: usize) -> Self {
+        self.beam_width = Some(width.max(1));
+        self
+    }
 
-                    // Avoid duplicates: check if this grid already exists
-                    let is_dup = self.nodes.iter().any(|n| n.grid == result)
-                        || new_nodes.iter().any(|n: &DagNode| n.grid == result);
-                    if is_dup { continue; }
+    // Expand likely programs first, and try their likely next steps first
+    pub fn with_grammar(mut self, grammar: Grammar) -> Self {
+        self.grammar = Some(grammar);
+        self
+    }
 
-                    // Only keep if it changes something (avoid identity loops)
-                    if result == grid { continue; }
+    // Indices of the nodes at `depth`, most likely program first
+    fn frontier(&self, depth: usize) -> Vec<usize> {
+        let mut ids: Vec<usize> = (0..self.nodes.len()).filter(|&i| self.nodes[i].depth == depth).collect();
+        if let Some(grammar) = &self.grammar {
+            let mut scored: Vec<(f64, usize)> = ids.iter().map(|&i| (grammar.log_prob(&self.nodes[i].program), i)).collect();
+            scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+            ids = scored.into_iter().map(|(_, i)| i).collect();
+        }
+        ids
+    }
 
-                    let new_prog = if depth == 0 {
+    // The primitives to try after `program`, in the grammar's order
+    fn next_steps<'a>(grammar: Option<&Grammar>, program: &Prim, primitives: &'a [Prim]) -> Cow<'a, [Prim]> {
+        match grammar {
+            Some(grammar) => Cow::Owned(grammar.order(program, primitives)),
+            None => Cow::Borrowed(primitives),
+        }
+    }
+
+    // Keeps the best `beam_width` nodes of a depth, if beam search is on
+    fn prune_to_beam(&self, nodes: &mut Vec<DagNode>, targets: &[Grid]) {
+        let Some(width) = self.beam_width else { return };
+        if nodes.len() <= width { return; }
+        let mut scored: Vec<(f64, DagNode)> = nodes.drain(..).map(|n| (beam_score(&n, targets), n)).collect();
+        scored.sort_by(|a, b| a.0.total_cmp(&b.0));
+        nodes.extend(scored.into_iter().take(width).map(|(_, n)| n));
+    }
+
+    pub fn search(&mut self, input: &Grid, target: &Grid, primitives: &[Prim], max_depth: usize) -> Option<Prim> {
+        self.search_states(vec![input.clone()], std::slice::from_ref(target), primitives, max_depth)
+    }
+
+    // Program mapping every input to its output: each node carries the
+    // grids of all the inputs, so only programs consistent with every pair
+    // reach the target
+    pub fn search_all(&mut self, examples: &[(Grid, Grid)], primitives: &[Prim], max_depth: usize) -> Option<Prim> {
+        let (inputs, targets): (Vec<Grid>, Vec<Grid>) = examples.iter().cloned().unzip();
+        if inputs.is_empty() { return None; }
+        self.search_states(inputs, &targets, primitives, max_depth)
+    }
+
+    fn search_states(&mut self, inputs: Vec<Grid>, targets: &[Grid], primitives: &[Prim], max_depth: usize) -> Option<Prim> {
+        // Check identity
+        let identity = inputs == targets;
+        let deadline = self.reset(inputs);
+        let expired = || deadline.is_some_and(|d| Instant::now() >= d);
+        if identity {
+            return Some(Prim::Identity);
+        }
+
+        for depth in 0..max_depth {
+            if expired() {
+                self.timed_out = true;
+                break;
+            }
+            // Expand: per frontier node, the new grids and the program that
+            // reaches the targets, if any
+            let frontier: Vec<&DagNode> = self.frontier(depth).into_iter().map(|i| &self.nodes[i]).collect();
+            let (known, seen, grammar) = (&self.nodes, &self.seen, self.grammar.as_ref());
+            let expansions = self.workers.map(&frontier, |node| {
+                let mut grown: Vec<DagNode> = Vec::new();
+                if expired() {
+                    return (grown, None);
+                }
+                for prim in Self::next_steps(grammar, &node.program, primitives).iter() {
+                    let result = apply_all(prim, &node.grids);
+                    let program = if depth == 0 {
                         prim.clone()
                     } else {
-                        Prim::Compose(Box::new(prog.clone()), Box::new(prim.clone()))
+                        Prim::Compose(Box::new(node.program.clone()), Box::new(prim.clone()))
                     };
 
-                    new_nodes.push(DagNode {
-                        grid: result,
-                        program: new_prog,
-                        depth: depth + 1,
-                    });
+                    // Check if we found the target
+                    if result == targets {
+                        return (grown, Some(program));
+                    }
 
+                    // Only keep if it changes something (avoid identity loops)
+                    if result == node.grids { continue; }
+
+                    // Avoid duplicates: check if these grids already exist
+                    let hash = state_hash(&result);
+                    let is_dup = indexed(seen, known, &result, hash)
+                        || grown.iter().any(|n| n.hash == hash && n.grids == result);
+                    if is_dup { continue; }
+
+                    grown.push(DagNode { grids: result, hash, program, depth: depth + 1 });
+                }
+                (grown, None)
+            });
+
+            // Merge in frontier order, up to the node cap
+            let mut new_nodes: Vec<DagNode> = Vec::new();
+            let mut new_seen: FxHashMap<u64, Vec<usize>> = FxHashMap::default();
+            let mut full = false;
+            for (grown, found) in expansions {
+                for node in grown {
+                    if indexed(&new_seen, &new_nodes, &node.grids, node.hash) { continue; }
+                    new_seen.entry(node.hash).or_default().push(new_nodes.len());
+                    new_nodes.push(node);
                     if self.nodes.len() + new_nodes.len() >= self.max_nodes {
+                        full = true;
                         break;
                     }
                 }
-
-                if self.nodes.len() + new_nodes.len() >= self.max_nodes {
-                    break;
-                }
+                if full { break; }
+                if found.is_some() { return found; }
             }
 
-            self.nodes.extend(new_nodes);
+            self.prune_to_beam(&mut new_nodes, targets);
+            for node in new_nodes {
+                self.insert(node);
+            }
         }
 
+        self.timed_out |= expired();
         None
     }
 
     pub fn search_scored(&mut self, input: &Grid, target: &Grid, primitives: &[Prim], max_depth: usize) -> Vec<(Prim, f64)> {
-        self.nodes.clear();
-        self.nodes.push(DagNode {
-            grid: input.clone(),
-            program: Prim::Identity,
-            depth: 0,
-        });
+        self.scored_states(vec![input.clone()], std::slice::from_ref(target), primitives, max_depth)
+    }
+
+    // search_scored over every pair, scored by mean similarity
+    pub fn search_all_scored(&mut self, examples: &[(Grid, Grid)], primitives: &[Prim], max_depth: usize) -> Vec<(Prim, f64)> {
+        let (inputs, targets): (Vec<Grid>, Vec<Grid>) = examples.iter().cloned().unzip();
+        if inputs.is_empty() { return Vec::new(); }
+        self.scored_states(inputs, &targets, primitives, max_depth)
+    }
+
+    fn scored_states(&mut self, inputs: Vec<Grid>, targets: &[Grid], primitives: &[Prim], max_depth: usize) -> Vec<(Prim, f64)> {
+        let deadline = self.reset(inputs);
 
         let mut scored = Vec::new();
 
-        for depth in 0..max_depth {
-            let current_count = self.nodes.len();
+        'search: for depth in 0..max_depth {
             let mut new_nodes = Vec::new();
+            let mut new_seen: FxHashMap<u64, Vec<usize>> = FxHashMap::default();
 
-            for node_idx in 0..current_count {
-                if self.nodes[node_idx].depth != depth { continue; }
-                let grid = self.nodes[node_idx].grid.clone();
+            for node_idx in self.frontier(depth) {
+                if deadline.is_some_and(|d| Instant::now() >= d) {
+                    self.timed_out = true;
+                    for node in new_nodes {
+                        self.insert(node);
+                    }
+                    break 'search;
+                }
+                let grids = self.nodes[node_idx].grids.clone();
                 let prog = self.nodes[node_idx].program.clone();
 
-                for prim in primitives {
-                    let result = prim.apply(&grid);
+                for prim in Self::next_steps(self.grammar.as_ref(), &prog, primitives).iter() {
+                    let result = apply_all(prim, &grids);
 
                     let new_prog = if depth == 0 {
                         prim.clone()
@@ -271,23 +515,21 @@ impl SearchDag {
                         Prim::Compose(Box::new(prog.clone()), Box::new(prim.clone()))
                     };
 
-                    if result == *target {
+                    if result == targets {
                         return vec![(new_prog, 1.0)];
                     }
 
-                    let sim = grid_similarity(&result, target);
+                    let sim = state_similarity(&result, targets);
                     if sim > 0.0 {
                         scored.push((new_prog.clone(), sim));
                     }
 
-                    let is_dup = self.nodes.iter().any(|n| n.grid == result)
-                        || new_nodes.iter().any(|n: &DagNode| n.grid == result);
-                    if !is_dup && result != grid {
-                        new_nodes.push(DagNode {
-                            grid: result,
-                            program: new_prog,
-                            depth: depth + 1,
-                        });
+                    let hash = state_hash(&result);
+                    let is_dup = indexed(&self.seen, &self.nodes, &result, hash)
+                        || indexed(&new_seen, &new_nodes, &result, hash);
+                    if !is_dup && result != grids {
+                        new_seen.entry(hash).or_default().push(new_nodes.len());
+                        new_nodes.push(DagNode { grids: result, hash, program: new_prog, depth: depth + 1 });
                     }
 
                     if self.nodes.len() + new_nodes.len() >= self.max_nodes {
@@ -296,7 +538,10 @@ impl SearchDag {
                 }
             }
 
-            self.nodes.extend(new_nodes);
+            self.prune_to_beam(&mut new_nodes, targets);
+            for node in new_nodes {
+                self.insert(node);
+            }
         }
 
         scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
@@ -309,6 +554,25 @@ impl SearchDag {
     }
 }
 
+// Program bits plus ~3.3 bits per target cell the grids still get wrong
+fn beam_score(node: &DagNode, targets: &[Grid]) -> f64 {
+    let wrong: f64 = node.grids.iter().zip(targets).map(|(grid, target)| {
+        let cells = target.iter().map(|r| r.len()).sum::<usize>() as f64;
+        (1.0 - grid_similarity(grid, target)) * cells
+    }).sum();
+    description_length(&node.program) + wrong * 3.3
+}
+
+fn apply_all(prim: &Prim, grids: &[Grid]) -> Vec<Grid> {
+    grids.iter().map(|g| prim.apply(g)).collect()
+}
+
+// Mean cell similarity over the pairs
+fn state_similarity(grids: &[Grid], targets: &[Grid]) -> f64 {
+    let total: f64 = grids.iter().zip(targets).map(|(g, t)| grid_similarity(g, t)).sum();
+    total / targets.len().max(1) as f64
+}
+
 fn grid_similarity(a: &Grid, b: &Grid) -> f64 {
     if a.is_empty() || b.is_empty() { return 0.0; }
     if a.len() != b.len() || a[0].len() != b[0].len() { return 0.0; }
@@ -348,6 +612,28 @@ pub fn wake_sleep_cycle(
     (library, solutions)
 }
 
+// Repeated wake-sleep: each round searches with the library learned so far
+// as extra one-step primitives, so a program built from entries fits in a
+// shallower search than its inlined form. Solutions come from the last round.
+pub fn wake_sleep_rounds(
+    tasks: &[(Grid, Grid)],
+    primitives: &[Prim],
+    max_dag_nodes: usize,
+    max_depth: usize,
+    min_freq: usize,
+    rounds: usize,
+) -> (Library, Vec<Option<Prim>>) {
+    let mut library = Library::new();
+    let mut solutions = Vec::new();
+    for _ in 0..rounds.max(1) {
+        let prims = library.with_primitives(primitives);
+        let (learned, solved) = wake_sleep_cycle(tasks, &prims, max_dag_nodes, max_depth, min_freq);
+        library.merge(learned);
+        solutions = solved;
+    }
+    (library, solutions)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -390,7 +676,7 @@ mod tests {
 
     #[test]
     fn search_dag_identity() {
-        let grid = vec![vec![1, 2], vec![3, 4]];
+        let grid = Grid::from(vec![vec![1, 2], vec![3, 4]]);
         let mut dag = SearchDag::new(100);
         let result = dag.search(&grid, &grid, &[Prim::FlipH], 3);
         assert!(result.is_some());
@@ -399,7 +685,7 @@ mod tests {
 
     #[test]
     fn search_dag_single_step() {
-        let input = vec![vec![1, 2], vec![3, 4]];
+        let input = Grid::from(vec![vec![1, 2], vec![3, 4]]);
         let target = Prim::FlipH.apply(&input);
         let prims = vec![Prim::FlipH, Prim::FlipV, Prim::RotateCW];
         let mut dag = SearchDag::new(1000);
@@ -408,9 +694,56 @@ mod tests {
         assert_eq!(result.unwrap().apply(&input), target);
     }
 
+    #[test]
+    fn beam_search_keeps_closest_branches() {
+        let input = Grid::from(vec![vec![1, 2, 3], vec![4, 5, 6]]);
+        let target = Prim::FlipV.apply(&Prim::FlipH.apply(&input));
+        let prims = vec![Prim::FlipH, Prim::FlipV, Prim::RotateCW, Prim::RotateCCW, Prim::Transpose];
+        let mut dag = SearchDag::new(5000).with_beam(2);
+        let result = dag.search(&input, &target, &prims, 3).unwrap();
+        assert_eq!(result.apply(&input), target);
+        assert!(dag.nodes_explored() <= 1 + 2 * 2);
+    }
+
+    #[test]
+    fn search_dag_threads_match_sequential() {
+        let input = Grid::from(vec![vec![1, 2, 3], vec![4, 5, 6]]);
+        let target = Prim::Transpose.apply(&Prim::FlipH.apply(&input));
+        let prims = vec![Prim::FlipH, Prim::FlipV, Prim::RotateCW, Prim::GravityDown, Prim::Transpose];
+        let mut one = SearchDag::new(5000);
+        let mut four = SearchDag::new(5000).with_threads(4);
+        assert_eq!(four.search(&input, &target, &prims, 3), one.search(&input, &target, &prims, 3));
+        assert_eq!(four.nodes_explored(), one.nodes_explored());
+    }
+
+    #[test]
+    fn search_dag_skips_seen_grids() {
+        let input = Grid::from(vec![vec![1, 2], vec![3, 4]]);
+        let mut dag = SearchDag::new(1000);
+        // Flips and half turns only ever reach four grids
+        let prims = vec![Prim::FlipH, Prim::FlipV, Prim::Rotate180];
+        assert!(dag.search(&input, &Grid::from(vec![vec![9]]), &prims, 4).is_none());
+        assert_eq!(dag.nodes_explored(), 4);
+    }
+
+    #[test]
+    fn search_dag_stops_on_time_budget() {
+        let input = Grid::from(vec![vec![1, 2, 3], vec![4, 5, 6]]);
+        let target = Prim::FlipV.apply(&Prim::FlipH.apply(&input));
+        let prims = vec![Prim::FlipH, Prim::FlipV, Prim::RotateCW];
+        let mut dag = SearchDag::new(5000).with_time_budget(std::time::Duration::ZERO);
+        assert!(dag.search(&input, &target, &prims, 3).is_none());
+        assert!(dag.timed_out());
+        assert!(dag.search_scored(&input, &target, &prims, 3).is_empty());
+
+        let mut dag = SearchDag::new(5000).with_time_budget(std::time::Duration::from_secs(60));
+        assert!(dag.search(&input, &target, &prims, 3).is_some());
+        assert!(!dag.timed_out());
+    }
+
     #[test]
     fn search_dag_two_step() {
-        let input = vec![vec![1, 2, 3], vec![4, 5, 6]];
+        let input = Grid::from(vec![vec![1, 2, 3], vec![4, 5, 6]]);
         let mid = Prim::FlipH.apply(&input);
         let target = Prim::FlipV.apply(&mid);
         let prims = vec![Prim::FlipH, Prim::FlipV, Prim::RotateCW, Prim::RotateCCW];
@@ -420,10 +753,26 @@ mod tests {
         assert_eq!(result.unwrap().apply(&input), target);
     }
 
+    #[test]
+    fn search_dag_tries_likely_compositions_first() {
+        let input = Grid::from(vec![vec![1, 2, 3], vec![4, 5, 6]]);
+        let target = Prim::Transpose.apply(&input);
+        let prims = vec![Prim::FlipH, Prim::FlipV, Prim::RotateCW, Prim::RotateCCW];
+
+        let plain = SearchDag::new(1000).search(&input, &target, &prims, 2).unwrap();
+        assert_eq!(plain, Prim::Compose(Box::new(Prim::FlipH), Box::new(Prim::RotateCCW)));
+
+        // Solved programs that rotate then flip make that order the likely one
+        let rotate_flip = Prim::Compose(Box::new(Prim::RotateCW), Box::new(Prim::FlipH));
+        let grammar = Grammar::fit([&rotate_flip, &rotate_flip]);
+        let learned = SearchDag::new(1000).with_grammar(grammar).search(&input, &target, &prims, 2).unwrap();
+        assert_eq!(learned, rotate_flip);
+    }
+
     #[test]
     fn search_dag_scored() {
-        let input = vec![vec![1, 2], vec![3, 4]];
-        let target = vec![vec![4, 3], vec![2, 1]];
+        let input = Grid::from(vec![vec![1, 2], vec![3, 4]]);
+        let target = Grid::from(vec![vec![4, 3], vec![2, 1]]);
         let prims = vec![Prim::FlipH, Prim::FlipV, Prim::RotateCW];
         let mut dag = SearchDag::new(1000);
         let scored = dag.search_scored(&input, &target, &prims, 2);
@@ -442,22 +791,72 @@ mod tests {
         assert_eq!(compressed, prog);
     }
 
+    #[test]
+    fn sleep_compress_uses_lib_refs() {
+        let entry = Prim::Compose(Box::new(Prim::FlipH), Box::new(Prim::RotateCW));
+        let mut lib = Library::new();
+        lib.add("flip_rot".into(), entry.clone());
+        let prog = Prim::Compose(Box::new(entry.clone()), Box::new(Prim::GravityDown));
+        let compressed = sleep_compress(&prog, &lib);
+        let lib_ref = Prim::LibRef("flip_rot".into(), Box::new(entry));
+        assert_eq!(compressed, Prim::Compose(Box::new(lib_ref), Box::new(Prim::GravityDown)));
+        assert_eq!(compressed.size(), 3);
+        let grid = Grid::from(vec![vec![1, 0, 2], vec![0, 3, 0]]);
+        assert_eq!(compressed.apply(&grid), prog.apply(&grid));
+    }
+
+    #[test]
+    fn wake_sleep_rounds_reuse_the_library() {
+        let grid = |seed: u8| Grid::from(vec![vec![seed, 0, 2], vec![0, 3, 0], vec![4, 0, 0]]);
+        let step = Prim::Compose(Box::new(Prim::GravityDown), Box::new(Prim::RotateCW));
+        let deep = Prim::Compose(Box::new(step.clone()), Box::new(Prim::FlipH));
+        let tasks: Vec<(Grid, Grid)> = vec![
+            (grid(1), step.apply(&grid(1))),
+            (grid(5), step.apply(&grid(5))),
+            (grid(6), deep.apply(&grid(6))),
+        ];
+        let prims = vec![Prim::FlipH, Prim::RotateCW, Prim::GravityDown];
+        let (_, once) = wake_sleep_cycle(&tasks, &prims, 5_000, 2, 2);
+        assert!(once[2].is_none());
+
+        let (lib, solutions) = wake_sleep_rounds(&tasks, &prims, 5_000, 2, 2, 2);
+        assert!(!lib.is_empty());
+        let found = solutions[2].as_ref().unwrap();
+        assert_eq!(found.apply(&tasks[2].0), tasks[2].1);
+    }
+
+    #[test]
+    fn wake_extract_abstracts_differing_colors() {
+        let outline_then_fill = |c: u8| Prim::Compose(Box::new(Prim::OutlineObjects(c)), Box::new(Prim::FillInsideObjects(4)));
+        let programs = vec![outline_then_fill(2), outline_then_fill(3), outline_then_fill(6)];
+        let lib = wake_extract(&programs, 2, 2, 10);
+        let entry = lib.entries.iter().find(|e| !e.holes.is_empty()).unwrap();
+        assert_eq!(entry.holes, vec![0]);
+        assert_eq!(entry.usage_count, 3);
+
+        // The unseen color 7 is one of the entry's instances
+        let unseen = outline_then_fill(7);
+        let compressed = sleep_compress(&unseen, &lib);
+        assert_eq!(compressed, Prim::LibRef(format!("{}(7)", entry.name), Box::new(unseen.clone())));
+        assert!(lib.primitives().contains(&compressed));
+    }
+
     #[test]
     fn grid_similarity_identical() {
-        let g = vec![vec![1, 2], vec![3, 4]];
+        let g = Grid::from(vec![vec![1, 2], vec![3, 4]]);
         assert_eq!(grid_similarity(&g, &g), 1.0);
     }
 
     #[test]
     fn grid_similarity_different_dims() {
-        let a = vec![vec![1, 2]];
-        let b = vec![vec![1, 2], vec![3, 4]];
+        let a = Grid::from(vec![vec![1, 2]]);
+        let b = Grid::from(vec![vec![1, 2], vec![3, 4]]);
         assert_eq!(grid_similarity(&a, &b), 0.0);
     }
 
     #[test]
     fn wake_sleep_cycle_basic() {
-        let input = vec![vec![1, 2], vec![3, 4]];
+        let input = Grid::from(vec![vec![1, 2], vec![3, 4]]);
         let target = Prim::FlipH.apply(&input);
         let tasks = vec![(input, target)];
         let prims = vec![Prim::FlipH, Prim::FlipV, Prim::RotateCW];
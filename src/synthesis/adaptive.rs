@@ -12,6 +12,7 @@
 //    in failed tasks and propose new primitives
 
 use super::dsl::{Grid, Prim};
+use super::normalize::normalize;
 use rustc_hash::FxHashMap;
 
 /// Transform type classification — what kind of problem is this?
@@ -168,7 +169,7 @@ impl SolutionCache {
 
     pub fn add(&mut self, program: Prim, task_id: String, tt: TransformType) {
         self.by_type.entry(tt).or_default().push(CachedSolution {
-            program, task_id, transform_type: tt,
+            program: normalize(&program), task_id, transform_type: tt,
         });
     }
 
@@ -182,6 +183,11 @@ impl SolutionCache {
         })
     }
 
+    /// Every cached program, the corpus the primitive grammar is fitted on.
+    pub fn programs(&self) -> impl Iterator<Item = &Prim> {
+        self.by_type.values().flatten().map(|sol| &sol.program)
+    }
+
     pub fn total_cached(&self) -> usize {
         self.by_type.values().map(|v| v.len()).sum()
     }
@@ -223,7 +229,7 @@ mod tests {
     #[test]
     fn classify_color_remap() {
         let examples = vec![
-            (vec![vec![1, 2]], vec![vec![3, 4]]),
+            (Grid::from(vec![vec![1, 2]]), Grid::from(vec![vec![3, 4]])),
         ];
         assert_eq!(classify_transform(&examples), TransformType::ColorRemap);
     }
@@ -232,29 +238,29 @@ mod tests {
     fn classify_geometric() {
         // Use repeated colors so color map is inconsistent (1 at (0,0)→1, 1 at (1,1)→1)
         // but cell positions change (geometric transform)
-        let input = vec![vec![1, 2, 1], vec![3, 1, 3]];
-        let output = vec![vec![1, 2, 1], vec![3, 1, 3]]; // FlipH of symmetric = same
+        let input = Grid::from(vec![vec![1, 2, 1], vec![3, 1, 3]]);
+        let output = Grid::from(vec![vec![1, 2, 1], vec![3, 1, 3]]); // FlipH of symmetric = same
         // Better: FlipV
-        let input2 = vec![vec![1, 2], vec![3, 1]];
-        let output2 = vec![vec![3, 1], vec![1, 2]]; // FlipV
+        let input2 = Grid::from(vec![vec![1, 2], vec![3, 1]]);
+        let output2 = Grid::from(vec![vec![3, 1], vec![1, 2]]); // FlipV
         // color_map would need 1→3 AND 1→1 → inconsistent
         assert_eq!(classify_transform(&[(input2, output2)]), TransformType::Geometric);
     }
 
     #[test]
     fn classify_tiling() {
-        let input = vec![vec![1, 2], vec![3, 4]]; // 2x2
-        let output = vec![
+        let input = Grid::from(vec![vec![1, 2], vec![3, 4]]); // 2x2
+        let output = Grid::from(vec![
             vec![1, 2, 1, 2], vec![3, 4, 3, 4],
             vec![1, 2, 1, 2], vec![3, 4, 3, 4],
-        ]; // 4x4
+        ]); // 4x4
         assert_eq!(classify_transform(&[(input, output)]), TransformType::Tiling);
     }
 
     #[test]
     fn classify_resizing() {
-        let input = vec![vec![1, 2, 3], vec![4, 5, 6]]; // 2x3
-        let output = vec![vec![1, 2]]; // 1x2
+        let input = Grid::from(vec![vec![1, 2, 3], vec![4, 5, 6]]); // 2x3
+        let output = Grid::from(vec![vec![1, 2]]); // 1x2
         assert_eq!(classify_transform(&[(input, output)]), TransformType::Resizing);
     }
 
@@ -276,7 +282,7 @@ mod tests {
         cache.add(Prim::FlipH, "task1".into(), TransformType::Geometric);
 
         let examples = vec![
-            (vec![vec![1, 2], vec![3, 4]], vec![vec![2, 1], vec![4, 3]]),
+            (Grid::from(vec![vec![1, 2], vec![3, 4]]), Grid::from(vec![vec![2, 1], vec![4, 3]])),
         ];
         let found = cache.try_cached(TransformType::Geometric, &examples);
         assert!(found.is_some());
@@ -0,0 +1,250 @@
+// Anti-unification of programs over their constants.
+//
+// Two programs with the same tree that differ only in a color, offset or
+// count generalize to a template with a hole at each differing constant:
+//
+//   OutlineObjects(2) ; FillInsideObjects(4)
+//   OutlineObjects(3) ; FillInsideObjects(4)
+//     => OutlineObjects(?0) ; FillInsideObjects(4)
+//
+//   let template = anti_unify(&a, &b)?;
+//   let program = template.instantiate(&[Param::Color(7)]);
+//
+// Constants are numbered in a fixed pre-order walk of the tree, so a hole is
+// just the index of the constant it replaces. Only constants become holes:
+// programs whose trees differ in shape do not anti-unify.
+
+use super::dsl::{GridOp, Prim};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Param {
+    Color(u8),
+    Offset(i32),
+    Count(usize),
+}
+
+impl Param {
+    // Values a hole of this kind ranges over when the template is expanded
+    pub fn domain(self) -> Vec<Param> {
+        match self {
+            Param::Color(_) => (0..=9).map(Param::Color).collect(),
+            Param::Offset(_) => (-3..=3).map(Param::Offset).collect(),
+            Param::Count(_) => (1..=4).map(Param::Count).collect(),
+        }
+    }
+
+    fn same_kind(self, other: Param) -> bool {
+        std::mem::discriminant(&self) == std::mem::discriminant(&other)
+    }
+}
+
+impl std::fmt::Display for Param {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Param::Color(c) => write!(f, "{}", c),
+            Param::Offset(d) => write!(f, "{}", d),
+            Param::Count(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+// A program with holes at some of its constants
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Template {
+    pub program: Prim,
+    // Indices of the constants that are holes, ascending
+    pub holes: Vec<usize>,
+}
+
+impl Template {
+    pub fn arity(&self) -> usize {
+        self.holes.len()
+    }
+
+    // The program with the holes filled by `args`, in hole order. A missing
+    // or mistyped argument leaves the template's own constant.
+    pub fn instantiate(&self, args: &[Param]) -> Prim {
+        let mut program = self.program.clone();
+        let mut index = 0;
+        visit_params(&mut program, &mut |param| {
+            let hole = self.holes.iter().position(|&h| h == index);
+            index += 1;
+            match hole.and_then(|i| args.get(i)) {
+                Some(&arg) if arg.same_kind(param) => arg,
+                _ => param,
+            }
+        });
+        program
+    }
+
+    // Arguments that instantiate the template to `program`, if it does
+    pub fn match_args(&self, program: &Prim) -> Option<Vec<Param>> {
+        if skeleton(program) != skeleton(&self.program) {
+            return None;
+        }
+        let ours = params(&self.program);
+        let theirs = params(program);
+        let fixed_agree = ours.iter().zip(&theirs).enumerate()
+            .all(|(i, (a, b))| self.holes.contains(&i) || a == b);
+        fixed_agree.then(|| self.holes.iter().map(|&h| theirs[h]).collect())
+    }
+
+    // Every instance over the holes' domains, or none past `limit`
+    pub fn instances(&self, limit: usize) -> Vec<(Vec<Param>, Prim)> {
+        let consts = params(&self.program);
+        let mut args: Vec<Vec<Param>> = vec![Vec::new()];
+        for &hole in &self.holes {
+            let domain = consts[hole].domain();
+            if args.len() * domain.len() > limit {
+                return Vec::new();
+            }
+            args = args.iter()
+                .flat_map(|prefix| domain.iter().map(move |&v| {
+                    let mut next = prefix.clone();
+                    next.push(v);
+                    next
+                }))
+                .collect();
+        }
+        args.into_iter().map(|a| {
+            let program = self.instantiate(&a);
+            (a, program)
+        }).collect()
+    }
+}
+
+// Least general template covering both programs: holes where their
+// constants differ. None when the trees differ in anything but constants.
+pub fn anti_unify(a: &Prim, b: &Prim) -> Option<Template> {
+    anti_unify_all(&[a.clone(), b.clone()])
+}
+
+pub fn anti_unify_all(programs: &[Prim]) -> Option<Template> {
+    let first = programs.first()?;
+    let shape = skeleton(first);
+    if programs.iter().any(|p| skeleton(p) != shape) {
+        return None;
+    }
+    let base = params(first);
+    let others: Vec<Vec<Param>> = programs[1..].iter().map(params).collect();
+    let holes = (0..base.len())
+        .filter(|&i| others.iter().any(|o| o[i] != base[i]))
+        .collect();
+    Some(Template { program: first.clone(), holes })
+}
+
+// Constants of a program in walk order
+pub fn params(program: &Prim) -> Vec<Param> {
+    let mut found = Vec::new();
+    let mut copy = program.clone();
+    visit_params(&mut copy, &mut |param| {
+        found.push(param);
+        param
+    });
+    found
+}
+
+// The program with every constant zeroed, equal for programs that differ
+// only in constants
+pub fn skeleton(program: &Prim) -> Prim {
+    let mut shape = program.clone();
+    visit_params(&mut shape, &mut |param| match param {
+        Param::Color(_) => Param::Color(0),
+        Param::Offset(_) => Param::Offset(0),
+        Param::Count(_) => Param::Count(0),
+    });
+    shape
+}
+
+// Calls `f` on each constant in pre-order and stores what it returns.
+// Library references are opaque: their bodies are not walked.
+fn visit_params(program: &mut Prim, f: &mut impl FnMut(Param) -> Param) {
+    match program {
+        Prim::FillColor(c) | Prim::FilterColor(c) | Prim::BorderFill(c) | Prim::RemoveColor(c)
+        | Prim::OutlineObjects(c) | Prim::FillInsideObjects(c) | Prim::FillEnclosed(c) => visit_color(c, f),
+        Prim::ReplaceColor(from, to) => {
+            visit_color(from, f);
+            visit_color(to, f);
+        }
+        Prim::Crop(r, c, h, w) => {
+            for n in [r, c, h, w] { visit_count(n, f); }
+        }
+        Prim::Pad(n, c) => {
+            visit_count(n, f);
+            visit_color(c, f);
+        }
+        Prim::Scale(n) | Prim::ExtractObject(n) | Prim::RepeatH(n) | Prim::RepeatV(n)
+        | Prim::UpscaleObjects(n) | Prim::SubGrid(n) => visit_count(n, f),
+        Prim::FloodFill(r, c, color_param) => {
+            visit_count(r, f);
+            visit_count(c, f);
+            visit_color(color_param, f);
+        }
+        Prim::Translate(dr, dc) => {
+            for d in [dr, dc] {
+                if let Param::Offset(v) = f(Param::Offset(*d)) { *d = v; }
+            }
+        }
+        Prim::WithBackground(bg, inner) => {
+            visit_color(bg, f);
+            visit_params(inner, f);
+        }
+        Prim::Binary(op, a, b) => {
+            if let GridOp::Diff(mark) = op { visit_color(mark, f); }
+            visit_params(a, f);
+            visit_params(b, f);
+        }
+        Prim::Compose(a, b) => {
+            visit_params(a, f);
+            visit_params(b, f);
+        }
+        Prim::Conditional(a, b, c) => {
+            visit_params(a, f);
+            visit_params(b, f);
+            visit_params(c, f);
+        }
+        _ => {}
+    }
+}
+
+fn visit_color(c: &mut u8, f: &mut impl FnMut(Param) -> Param) {
+    if let Param::Color(v) = f(Param::Color(*c)) { *c = v; }
+}
+
+fn visit_count(n: &mut usize, f: &mut impl FnMut(Param) -> Param) {
+    if let Param::Count(v) = f(Param::Count(*n)) { *n = v; }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn outline_then_fill(outline: u8, fill: u8) -> Prim {
+        Prim::Compose(Box::new(Prim::OutlineObjects(outline)), Box::new(Prim::FillInsideObjects(fill)))
+    }
+
+    #[test]
+    fn holes_at_differing_constants() {
+        let template = anti_unify(&outline_then_fill(2, 4), &outline_then_fill(3, 4)).unwrap();
+        assert_eq!(template.holes, vec![0]);
+        assert_eq!(template.instantiate(&[Param::Color(7)]), outline_then_fill(7, 4));
+        assert_eq!(template.match_args(&outline_then_fill(5, 4)), Some(vec![Param::Color(5)]));
+        assert_eq!(template.match_args(&outline_then_fill(5, 1)), None);
+        assert_eq!(template.instances(100).len(), 10);
+
+        // Different trees do not anti-unify
+        let other = Prim::Compose(Box::new(Prim::OutlineObjects(2)), Box::new(Prim::FillEnclosed(4)));
+        assert!(anti_unify(&outline_then_fill(2, 4), &other).is_none());
+    }
+
+    #[test]
+    fn offsets_and_counts_are_holes_too() {
+        let a = Prim::Compose(Box::new(Prim::Translate(1, 0)), Box::new(Prim::Scale(2)));
+        let b = Prim::Compose(Box::new(Prim::Translate(-2, 0)), Box::new(Prim::Scale(3)));
+        let template = anti_unify(&a, &b).unwrap();
+        assert_eq!(template.holes, vec![0, 2]);
+        assert_eq!(template.instantiate(&[Param::Offset(3), Param::Count(4)]),
+            Prim::Compose(Box::new(Prim::Translate(3, 0)), Box::new(Prim::Scale(4))));
+        assert!(template.instances(10).is_empty());
+    }
+}
@@ -16,7 +16,9 @@
 // The backward frontier uses only invertible primitives.
 
 use super::dsl::{Prim, Grid};
+use super::parallel::Workers;
 use rustc_hash::FxHashMap;
+use std::time::{Duration, Instant};
 
 /// Get the inverse of a primitive, if it exists.
 /// Returns None for non-invertible operations (lossy transforms).
@@ -54,14 +56,21 @@ pub fn invertible_subset(prims: &[Prim]) -> Vec<(Prim, Prim)> {
 
 #[derive(Debug, Clone)]
 struct BidirNode {
-    grid: Grid,
+    // One grid per example
+    grids: Vec<Grid>,
     program: Prim,
     depth: usize,
 }
 
+// New nodes of one frontier node, and the meeting it found if any
+// (program, forward depth, backward depth)
+type Expansion = (Vec<(u64, BidirNode)>, Option<(Prim, usize, usize)>);
+
 #[derive(Debug)]
 pub struct BidirSearch {
     max_nodes: usize,
+    workers: Workers,
+    time_budget: Option<Duration>,
 }
 
 #[derive(Debug, Clone)]
@@ -75,7 +84,21 @@ pub struct BidirResult {
 
 impl BidirSearch {
     pub fn new(max_nodes: usize) -> Self {
-        Self { max_nodes }
+        Self { max_nodes, workers: Workers::default(), time_budget: None }
+    }
+
+    /// Stop expanding once the search has run this long, whatever the node
+    /// count.
+    pub fn with_time_budget(mut self, budget: Duration) -> Self {
+        self.time_budget = Some(budget);
+        self
+    }
+
+    /// Expand each frontier on `threads` workers (see parallel.rs); the
+    /// result does not depend on the thread count.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.workers = Workers::new(threads);
+        self
     }
 
     /// Bidirectional search: expand forward from input AND backward from output.
@@ -86,9 +109,22 @@ impl BidirSearch {
         target: &Grid,
         forward_prims: &[Prim],
         max_depth: usize,
+    ) -> Option<BidirResult> {
+        self.search_states(vec![input.clone()], vec![target.clone()], forward_prims, max_depth)
+    }
+
+    /// Search over all examples at once: each node holds one grid per
+    /// example, so both frontiers only meet on a program consistent with
+    /// every pair.
+    fn search_states(
+        &self,
+        inputs: Vec<Grid>,
+        targets: Vec<Grid>,
+        forward_prims: &[Prim],
+        max_depth: usize,
     ) -> Option<BidirResult> {
         // Identity check
-        if input == target {
+        if inputs == targets {
             return Some(BidirResult {
                 program: Prim::Identity,
                 method: "identity",
@@ -106,29 +142,34 @@ impl BidirSearch {
         let mut forward: FxHashMap<u64, BidirNode> = FxHashMap::default();
         let mut backward: FxHashMap<u64, BidirNode> = FxHashMap::default();
 
-        let input_fp = grid_hash(input);
-        let target_fp = grid_hash(target);
+        let input_fp = state_hash(&inputs);
+        let target_fp = state_hash(&targets);
 
         forward.insert(input_fp, BidirNode {
-            grid: input.clone(),
+            grids: inputs,
             program: Prim::Identity,
             depth: 0,
         });
 
         backward.insert(target_fp, BidirNode {
-            grid: target.clone(),
+            grids: targets,
             program: Prim::Identity,
             depth: 0,
         });
 
         let mut total_nodes = 2;
         let half_depth = (max_depth + 1) / 2;
+        let deadline = self.time_budget.map(|budget| Instant::now() + budget);
 
         // Alternate forward and backward expansion
         for depth in 0..half_depth {
+            if expired(deadline) {
+                break;
+            }
+
             // Forward expansion
             if let Some(result) = self.expand_forward(
-                &mut forward, &backward, forward_prims, depth, &mut total_nodes,
+                &mut forward, &backward, forward_prims, depth, &mut total_nodes, deadline,
             ) {
                 return Some(result);
             }
@@ -136,7 +177,7 @@ impl BidirSearch {
             // Backward expansion (using inverse primitives)
             if !backward_prims.is_empty() {
                 if let Some(result) = self.expand_backward(
-                    &forward, &mut backward, &backward_prims, depth, &mut total_nodes,
+                    &forward, &mut backward, &backward_prims, depth, &mut total_nodes, deadline,
                 ) {
                     return Some(result);
                 }
@@ -157,22 +198,24 @@ impl BidirSearch {
         prims: &[Prim],
         depth: usize,
         total_nodes: &mut usize,
+        deadline: Option<Instant>,
     ) -> Option<BidirResult> {
-        let current: Vec<(u64, Grid, Prim)> = forward.iter()
-            .filter(|(_, n)| n.depth == depth)
-            .map(|(k, n)| (*k, n.grid.clone(), n.program.clone()))
-            .collect();
+        let current: Vec<&BidirNode> = forward.values().filter(|n| n.depth == depth).collect();
 
-        for (_fp, grid, prog) in &current {
+        let expansions = self.workers.map(&current, |node| {
+            let mut grown = Vec::new();
+            if expired(deadline) {
+                return (grown, None);
+            }
             for prim in prims {
-                let result = prim.apply(grid);
-                let result_fp = grid_hash(&result);
+                let result: Vec<Grid> = node.grids.iter().map(|g| prim.apply(g)).collect();
+                let result_fp = state_hash(&result);
 
                 // Check if backward frontier reached this state
                 if let Some(back_node) = backward.get(&result_fp) {
                     // Verify actual grid equality (hash collision check)
-                    if result == back_node.grid {
-                        let forward_prog = compose_programs(prog, prim);
+                    if result == back_node.grids {
+                        let forward_prog = compose_programs(&node.program, prim);
                         let full_prog = if back_node.depth == 0 {
                             forward_prog
                         } else {
@@ -182,13 +225,7 @@ impl BidirSearch {
                                 Box::new(invert_program(&back_node.program)),
                             )
                         };
-                        return Some(BidirResult {
-                            program: full_prog,
-                            method: "bidirectional",
-                            forward_depth: depth + 1,
-                            backward_depth: back_node.depth,
-                            nodes_explored: *total_nodes,
-                        });
+                        return (grown, Some((full_prog, depth + 1, back_node.depth)));
                     }
                 }
 
@@ -196,22 +233,18 @@ impl BidirSearch {
                 if forward.contains_key(&result_fp) { continue; }
 
                 // Skip if grid unchanged
-                if result == *grid { continue; }
+                if result == node.grids { continue; }
 
-                let new_prog = compose_programs(prog, prim);
-                forward.insert(result_fp, BidirNode {
-                    grid: result,
+                let new_prog = compose_programs(&node.program, prim);
+                grown.push((result_fp, BidirNode {
+                    grids: result,
                     program: new_prog,
                     depth: depth + 1,
-                });
-                *total_nodes += 1;
-
-                if *total_nodes >= self.max_nodes {
-                    return None;
-                }
+                }));
             }
-        }
-        None
+            (grown, None)
+        });
+        self.merge(forward, expansions, total_nodes)
     }
 
     fn expand_backward(
@@ -221,23 +254,25 @@ impl BidirSearch {
         inv_prims: &[(Prim, Prim)],
         depth: usize,
         total_nodes: &mut usize,
+        deadline: Option<Instant>,
     ) -> Option<BidirResult> {
-        let current: Vec<(u64, Grid, Prim)> = backward.iter()
-            .filter(|(_, n)| n.depth == depth)
-            .map(|(k, n)| (*k, n.grid.clone(), n.program.clone()))
-            .collect();
+        let current: Vec<&BidirNode> = backward.values().filter(|n| n.depth == depth).collect();
 
-        for (_fp, grid, back_prog) in &current {
+        let expansions = self.workers.map(&current, |node| {
+            let mut grown = Vec::new();
+            if expired(deadline) {
+                return (grown, None);
+            }
             for (forward_prim, inv_prim) in inv_prims {
                 // Apply inverse to go backward from target
-                let result = inv_prim.apply(grid);
-                let result_fp = grid_hash(&result);
+                let result: Vec<Grid> = node.grids.iter().map(|g| inv_prim.apply(g)).collect();
+                let result_fp = state_hash(&result);
 
                 // Check if forward frontier reached this state
                 if let Some(fwd_node) = forward.get(&result_fp) {
-                    if result == fwd_node.grid {
+                    if result == fwd_node.grids {
                         // Build the forward primitive path
-                        let back_forward = compose_programs(back_prog, forward_prim);
+                        let back_forward = compose_programs(&node.program, forward_prim);
                         let full_prog = if fwd_node.depth == 0 {
                             invert_program(&back_forward)
                         } else {
@@ -246,37 +281,60 @@ impl BidirSearch {
                                 Box::new(invert_program(&back_forward)),
                             )
                         };
-                        return Some(BidirResult {
-                            program: full_prog,
-                            method: "bidirectional",
-                            forward_depth: fwd_node.depth,
-                            backward_depth: depth + 1,
-                            nodes_explored: *total_nodes,
-                        });
+                        return (grown, Some((full_prog, fwd_node.depth, depth + 1)));
                     }
                 }
 
                 if backward.contains_key(&result_fp) { continue; }
-                if result == *grid { continue; }
+                if result == node.grids { continue; }
 
                 // Track which forward primitive was used (for reconstruction)
-                let new_back_prog = compose_programs(back_prog, forward_prim);
-                backward.insert(result_fp, BidirNode {
-                    grid: result,
+                let new_back_prog = compose_programs(&node.program, forward_prim);
+                grown.push((result_fp, BidirNode {
+                    grids: result,
                     program: new_back_prog,
                     depth: depth + 1,
-                });
+                }));
+            }
+            (grown, None)
+        });
+        self.merge(backward, expansions, total_nodes)
+    }
+
+    /// Insert the new nodes of each frontier node in order, up to the node
+    /// cap, and stop at the first frontier meeting.
+    fn merge(
+        &self,
+        frontier: &mut FxHashMap<u64, BidirNode>,
+        expansions: Vec<Expansion>,
+        total_nodes: &mut usize,
+    ) -> Option<BidirResult> {
+        for (grown, meeting) in expansions {
+            for (fp, node) in grown {
+                if frontier.contains_key(&fp) { continue; }
+                frontier.insert(fp, node);
                 *total_nodes += 1;
 
                 if *total_nodes >= self.max_nodes {
                     return None;
                 }
             }
+            if let Some((program, forward_depth, backward_depth)) = meeting {
+                return Some(BidirResult {
+                    program,
+                    method: "bidirectional",
+                    forward_depth,
+                    backward_depth,
+                    nodes_explored: *total_nodes,
+                });
+            }
         }
         None
     }
 
-    /// Multi-example search: find a program that works for all examples.
+    /// Multi-example search: find a program that works for all examples,
+    /// searching all of them jointly rather than solving the first and
+    /// verifying the rest.
     pub fn search_all(
         &self,
         examples: &[(Grid, Grid)],
@@ -284,19 +342,8 @@ impl BidirSearch {
         max_depth: usize,
     ) -> Option<BidirResult> {
         if examples.is_empty() { return None; }
-        if examples.len() == 1 {
-            return self.search(&examples[0].0, &examples[0].1, prims, max_depth);
-        }
-
-        // Strategy: solve first example, verify against rest
-        let result = self.search(&examples[0].0, &examples[0].1, prims, max_depth)?;
-
-        // Verify on all other examples
-        let all_match = examples[1..].iter().all(|(input, output)| {
-            result.program.apply(input) == *output
-        });
-
-        if all_match { Some(result) } else { None }
+        let (inputs, targets) = examples.iter().cloned().unzip();
+        self.search_states(inputs, targets, prims, max_depth)
     }
 }
 
@@ -320,6 +367,15 @@ fn invert_program(prog: &Prim) -> Prim {
     }
 }
 
+fn expired(deadline: Option<Instant>) -> bool {
+    deadline.is_some_and(|d| Instant::now() >= d)
+}
+
+/// Hash of a tuple of grids; a single grid hashes as itself.
+pub(super) fn state_hash(grids: &[Grid]) -> u64 {
+    grids.iter().map(grid_hash).reduce(|h, g| h.rotate_left(5).wrapping_mul(0x100000001b3) ^ g).unwrap_or(0)
+}
+
 fn grid_hash(grid: &Grid) -> u64 {
     let mut h: u64 = 0xcbf29ce484222325;
     for (r, row) in grid.iter().enumerate() {
@@ -336,6 +392,7 @@ fn grid_hash(grid: &Grid) -> u64 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::synthesis::abstraction::SearchDag;
 
     #[test]
     fn inverse_rotate() {
@@ -366,7 +423,7 @@ mod tests {
 
     #[test]
     fn bidir_finds_identity() {
-        let grid = vec![vec![1, 2], vec![3, 4]];
+        let grid = Grid::from(vec![vec![1, 2], vec![3, 4]]);
         let bidir = BidirSearch::new(1000);
         let prims = vec![Prim::RotateCW, Prim::FlipH];
         let result = bidir.search(&grid, &grid, &prims, 4);
@@ -376,7 +433,7 @@ mod tests {
 
     #[test]
     fn bidir_finds_single_step() {
-        let input = vec![vec![1, 2], vec![3, 4]];
+        let input = Grid::from(vec![vec![1, 2], vec![3, 4]]);
         let target = Prim::FlipH.apply(&input);
         let bidir = BidirSearch::new(1000);
         let prims = vec![Prim::RotateCW, Prim::FlipH, Prim::FlipV, Prim::Transpose];
@@ -388,7 +445,7 @@ mod tests {
 
     #[test]
     fn bidir_finds_two_step() {
-        let input = vec![vec![1, 2, 3], vec![4, 5, 6]];
+        let input = Grid::from(vec![vec![1, 2, 3], vec![4, 5, 6]]);
         // FlipH then FlipV
         let mid = Prim::FlipH.apply(&input);
         let target = Prim::FlipV.apply(&mid);
@@ -404,9 +461,9 @@ mod tests {
     #[test]
     fn bidir_multi_example() {
         // FlipH should work on both
-        let ex1_in = vec![vec![1, 2], vec![3, 4]];
+        let ex1_in = Grid::from(vec![vec![1, 2], vec![3, 4]]);
         let ex1_out = Prim::FlipH.apply(&ex1_in);
-        let ex2_in = vec![vec![5, 6], vec![7, 8]];
+        let ex2_in = Grid::from(vec![vec![5, 6], vec![7, 8]]);
         let ex2_out = Prim::FlipH.apply(&ex2_in);
 
         let bidir = BidirSearch::new(5000);
@@ -415,6 +472,31 @@ mod tests {
         assert!(result.is_some());
     }
 
+    #[test]
+    fn threads_do_not_change_the_result() {
+        let input = Grid::from(vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 0]]);
+        let target = Prim::Transpose.apply(&Prim::RotateCW.apply(&Prim::FlipV.apply(&input)));
+        let prims = vec![Prim::RotateCW, Prim::RotateCCW, Prim::FlipH, Prim::FlipV,
+                         Prim::Transpose, Prim::Rotate180, Prim::GravityDown];
+        let one = BidirSearch::new(5000).search(&input, &target, &prims, 4).unwrap();
+        let four = BidirSearch::new(5000).with_threads(4).search(&input, &target, &prims, 4).unwrap();
+        assert_eq!(four.program.apply(&input), target);
+        assert_eq!((four.program, four.nodes_explored), (one.program, one.nodes_explored));
+    }
+
+    #[test]
+    fn bidir_joint_search_skips_spurious_first_solution() {
+        // FlipH and FlipV both explain the first pair, only FlipV the second
+        let examples = vec![
+            (Grid::from(vec![vec![1, 2], vec![2, 1]]), Grid::from(vec![vec![2, 1], vec![1, 2]])),
+            (Grid::from(vec![vec![1, 2], vec![3, 4]]), Prim::FlipV.apply(&Grid::from(vec![vec![1, 2], vec![3, 4]]))),
+        ];
+        let prims = vec![Prim::FlipH, Prim::FlipV];
+        let result = BidirSearch::new(5000).search_all(&examples, &prims, 2).unwrap();
+        assert!(examples.iter().all(|(i, o)| result.program.apply(i) == *o));
+        assert_eq!(SearchDag::new(5000).search_all(&examples, &prims, 2), Some(Prim::FlipV));
+    }
+
     #[test]
     fn invertible_subset_filters() {
         let prims = vec![Prim::RotateCW, Prim::GravityDown, Prim::FlipH, Prim::FillColor(1)];
@@ -15,7 +15,8 @@
 // Also implements: delta encoding between grids (for efficient caching)
 // and run-length encoding for grid storage.
 
-use super::dsl::{Grid, Prim};
+use super::dsl::{Grid, GridOp, Prim};
+use super::normalize::normalize;
 
 /// Compute description length of a grid transformation.
 /// Lower = simpler, more compressible.
@@ -30,6 +31,16 @@ pub fn description_length(program: &Prim) -> f64 {
         Prim::Conditional(a, b, c) => {
             2.0 + description_length(a) + description_length(b) + description_length(c)
         }
+        // Background color param on top of the inner program
+        Prim::WithBackground(_, p) => 3.3 + description_length(p),
+        // Combiner node plus the operator: 2 bits, a color for Diff
+        Prim::Binary(op, a, b) => {
+            let op_bits = if matches!(op, GridOp::Diff(_)) { 2.0 + 3.3 } else { 2.0 };
+            1.0 + op_bits + description_length(a) + description_length(b)
+        }
+        Prim::SubGrid(_) => 4.0 + 2.0,
+        // A library entry is named like any other primitive
+        Prim::LibRef(_, _) => 4.0,
         // Simple transforms: ~4 bits (16 basic ops)
         Prim::RotateCW | Prim::RotateCCW | Prim::Rotate180
         | Prim::FlipH | Prim::FlipV | Prim::Transpose
@@ -65,7 +76,8 @@ pub fn description_length(program: &Prim) -> f64 {
 /// `mdl_score = -log P(examples | program) + description_length(program)`
 /// Lower MDL = better program.
 pub fn mdl_score(program: &Prim, examples: &[(Grid, Grid)]) -> f64 {
-    let dl = description_length(program);
+    // Programs are charged for what they compute, not for redundant steps
+    let dl = description_length(&normalize(program));
     let fit = data_fit(program, examples);
     dl + fit
 }
@@ -153,10 +165,8 @@ pub fn delta_apply(base: &Grid, diffs: &[(u16, u16, u8)]) -> Grid {
     let mut result = base.clone();
     for &(r, c, v) in diffs {
         if (r as usize) < result.len() {
-            if let Some(row) = result.get_mut(r as usize) {
-                if (c as usize) < row.len() {
-                    row[c as usize] = v;
-                }
+            if let Some(cell) = result[r as usize].get_mut(c as usize) {
+                *cell = v;
             }
         }
     }
@@ -221,7 +231,7 @@ mod tests {
 
     #[test]
     fn mdl_prefers_simpler() {
-        let input = vec![vec![1, 2], vec![3, 4]];
+        let input = Grid::from(vec![vec![1, 2], vec![3, 4]]);
         let output = Prim::FlipH.apply(&input);
         let examples = vec![(input, output)];
 
@@ -256,8 +266,8 @@ mod tests {
 
     #[test]
     fn delta_encode_roundtrip() {
-        let base = vec![vec![1, 2], vec![3, 4]];
-        let target = vec![vec![1, 5], vec![3, 4]]; // one cell changed
+        let base = Grid::from(vec![vec![1, 2], vec![3, 4]]);
+        let target = Grid::from(vec![vec![1, 5], vec![3, 4]]); // one cell changed
         let diffs = delta_encode(&base, &target);
         assert_eq!(diffs.len(), 1);
         assert_eq!(delta_apply(&base, &diffs), target);
@@ -265,42 +275,42 @@ mod tests {
 
     #[test]
     fn delta_identical_no_diffs() {
-        let g = vec![vec![1, 2], vec![3, 4]];
+        let g = Grid::from(vec![vec![1, 2], vec![3, 4]]);
         let diffs = delta_encode(&g, &g);
         assert!(diffs.is_empty());
     }
 
     #[test]
     fn compression_ratio_uniform() {
-        let grid = vec![vec![0; 10]; 10]; // all zeros
+        let grid = Grid::from(vec![vec![0; 10]; 10]); // all zeros
         let ratio = compression_ratio(&grid);
         assert!(ratio < 0.5); // should compress well
     }
 
     #[test]
     fn entropy_uniform() {
-        let grid = vec![vec![5; 10]; 10]; // all same color
+        let grid = Grid::from(vec![vec![5; 10]; 10]); // all same color
         let e = grid_entropy(&grid);
         assert!(e < 0.01); // near zero entropy
     }
 
     #[test]
     fn entropy_mixed() {
-        let grid = vec![vec![1, 2, 3, 4]]; // 4 distinct colors
+        let grid = Grid::from(vec![vec![1, 2, 3, 4]]); // 4 distinct colors
         let e = grid_entropy(&grid);
         assert!(e > 1.0); // should have significant entropy
     }
 
     #[test]
     fn grid_error_identical() {
-        let g = vec![vec![1, 2], vec![3, 4]];
+        let g = Grid::from(vec![vec![1, 2], vec![3, 4]]);
         assert_eq!(grid_error(&g, &g), 0.0);
     }
 
     #[test]
     fn grid_error_dimension_mismatch() {
-        let a = vec![vec![1, 2]];
-        let b = vec![vec![1, 2], vec![3, 4]];
+        let a = Grid::from(vec![vec![1, 2]]);
+        let b = Grid::from(vec![vec![1, 2], vec![3, 4]]);
         assert!(grid_error(&a, &b) > 50.0); // heavy penalty
     }
 }
@@ -457,46 +457,46 @@ mod tests {
 
     #[test]
     fn connect_h_pair() {
-        let input = vec![
+        let input = Grid::from(vec![
             vec![0, 0, 0, 0, 0],
             vec![0, 3, 0, 3, 0],
             vec![0, 0, 0, 0, 0],
-        ];
-        let output = vec![
+        ]);
+        let output = Grid::from(vec![
             vec![0, 0, 0, 0, 0],
             vec![0, 3, 7, 3, 0],
             vec![0, 0, 0, 0, 0],
-        ];
+        ]);
         let result = apply_connect_pairs(&input, 3, 7, ConnectMode::HLine);
         assert_eq!(result, output);
     }
 
     #[test]
     fn connect_v_pair() {
-        let input = vec![
+        let input = Grid::from(vec![
             vec![0, 3, 0],
             vec![0, 0, 0],
             vec![0, 3, 0],
-        ];
-        let expected = vec![
+        ]);
+        let expected = Grid::from(vec![
             vec![0, 3, 0],
             vec![0, 7, 0],
             vec![0, 3, 0],
-        ];
+        ]);
         let result = apply_connect_pairs(&input, 3, 7, ConnectMode::VLine);
         assert_eq!(result, expected);
     }
 
     #[test]
     fn fill_between_row() {
-        let input = vec![
+        let input = Grid::from(vec![
             vec![0, 2, 0, 0, 2, 0],
             vec![0, 0, 0, 0, 0, 0],
-        ];
-        let expected = vec![
+        ]);
+        let expected = Grid::from(vec![
             vec![0, 2, 2, 2, 2, 0],
             vec![0, 0, 0, 0, 0, 0],
-        ];
+        ]);
         let examples = vec![(input.clone(), expected.clone())];
         let sol = try_fill_between(&examples);
         assert!(sol.is_some());
@@ -505,16 +505,16 @@ mod tests {
 
     #[test]
     fn extend_to_full_row() {
-        let input = vec![
+        let input = Grid::from(vec![
             vec![0, 0, 0],
             vec![0, 5, 0],
             vec![0, 0, 0],
-        ];
-        let expected = vec![
+        ]);
+        let expected = Grid::from(vec![
             vec![0, 0, 0],
             vec![5, 5, 5],
             vec![0, 0, 0],
-        ];
+        ]);
         let result = apply_extend_markers(&input, ConnectMode::FullRow);
         assert_eq!(result, expected);
     }
@@ -1,6 +1,173 @@
 use serde::{Serialize, Deserialize};
+use super::partition::partition_grid;
+
+// Row-major cells in one allocation, so cloning a grid in the search loops
+// is a single memcpy instead of one allocation per row. Rows index as
+// slices (grid[r][c]) and iterate like the nested Vec<Vec<u8>> this
+// replaced; serialized as nested arrays, the ARC JSON layout.
+#[derive(Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "Vec<Vec<u8>>", into = "Vec<Vec<u8>>")]
+pub struct Grid {
+    cells: Vec<u8>,
+    height: usize,
+    width: usize,
+}
+
+impl Grid {
+    // A grid without rows has width 0, as an empty Vec<Vec<u8>> had
+    pub fn new(height: usize, width: usize, fill: u8) -> Self {
+        let width = if height == 0 { 0 } else { width };
+        Self { cells: vec![fill; height * width], height, width }
+    }
+
+    // Rows must all have the first row's width; short rows are padded with 0
+    // and long ones truncated
+    pub fn from_rows(rows: Vec<Vec<u8>>) -> Self {
+        let height = rows.len();
+        let width = rows.first().map_or(0, |row| row.len());
+        let mut cells = Vec::with_capacity(height * width);
+        for mut row in rows {
+            row.resize(width, 0);
+            cells.extend(row);
+        }
+        Self { cells, height, width }
+    }
+
+    pub fn height(&self) -> usize { self.height }
+    pub fn width(&self) -> usize { self.width }
+    pub fn cells(&self) -> &[u8] { &self.cells }
+
+    // Number of rows, as with the nested representation
+    pub fn len(&self) -> usize { self.height }
+    pub fn is_empty(&self) -> bool { self.height == 0 }
+
+    pub fn get(&self, r: usize, c: usize) -> Option<u8> {
+        (r < self.height && c < self.width).then(|| self.cells[r * self.width + c])
+    }
+
+    pub fn iter(&self) -> Rows<'_> {
+        Rows { grid: self, front: 0, back: self.height }
+    }
+
+    // Same shape, each cell mapped through `f`
+    pub fn map(&self, f: impl FnMut(u8) -> u8) -> Grid {
+        Grid { cells: self.cells.iter().copied().map(f).collect(), height: self.height, width: self.width }
+    }
+
+    // Rows in `range` as a grid of their own
+    pub fn slice_rows(&self, range: std::ops::Range<usize>) -> Grid {
+        let end = range.end.min(self.height);
+        let start = range.start.min(end);
+        let width = if end > start { self.width } else { 0 };
+        Grid { cells: self.cells[start * self.width..end * self.width].to_vec(), height: end - start, width }
+    }
+
+    pub fn to_rows(&self) -> Vec<Vec<u8>> {
+        self.iter().map(<[u8]>::to_vec).collect()
+    }
+
+    pub fn push_row(&mut self, row: &[u8]) {
+        if self.height == 0 { self.width = row.len(); }
+        self.cells.extend((0..self.width).map(|c| row.get(c).copied().unwrap_or(0)));
+        self.height += 1;
+    }
+
+    pub fn swap_rows(&mut self, a: usize, b: usize) {
+        if a == b { return; }
+        let w = self.width;
+        let (lo, hi) = (a.min(b), a.max(b));
+        let (head, tail) = self.cells.split_at_mut(hi * w);
+        head[lo * w..(lo + 1) * w].swap_with_slice(&mut tail[..w]);
+    }
+}
+
+impl std::ops::Index<usize> for Grid {
+    type Output = [u8];
+    fn index(&self, r: usize) -> &[u8] {
+        assert!(r < self.height, "row {} out of bounds for {} rows", r, self.height);
+        &self.cells[r * self.width..(r + 1) * self.width]
+    }
+}
+
+impl std::ops::IndexMut<usize> for Grid {
+    fn index_mut(&mut self, r: usize) -> &mut [u8] {
+        assert!(r < self.height, "row {} out of bounds for {} rows", r, self.height);
+        &mut self.cells[r * self.width..(r + 1) * self.width]
+    }
+}
+
+impl From<Vec<Vec<u8>>> for Grid {
+    fn from(rows: Vec<Vec<u8>>) -> Self { Self::from_rows(rows) }
+}
 
-pub type Grid = Vec<Vec<u8>>;
+impl From<Grid> for Vec<Vec<u8>> {
+    fn from(grid: Grid) -> Self { grid.to_rows() }
+}
+
+impl FromIterator<Vec<u8>> for Grid {
+    fn from_iter<I: IntoIterator<Item = Vec<u8>>>(rows: I) -> Self {
+        let mut grid = Grid::default();
+        for row in rows { grid.push_row(&row); }
+        grid
+    }
+}
+
+// Appends rows, as push_row
+impl<'a> Extend<&'a [u8]> for Grid {
+    fn extend<I: IntoIterator<Item = &'a [u8]>>(&mut self, rows: I) {
+        for row in rows { self.push_row(row); }
+    }
+}
+
+impl<'a> IntoIterator for &'a Grid {
+    type Item = &'a [u8];
+    type IntoIter = Rows<'a>;
+    fn into_iter(self) -> Rows<'a> { self.iter() }
+}
+
+// Row slices of a grid, top to bottom
+#[derive(Clone)]
+pub struct Rows<'a> {
+    grid: &'a Grid,
+    front: usize,
+    back: usize,
+}
+
+impl<'a> Iterator for Rows<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        if self.front == self.back { return None; }
+        self.front += 1;
+        Some(&self.grid[self.front - 1])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.back - self.front, Some(self.back - self.front))
+    }
+}
+
+impl DoubleEndedIterator for Rows<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.front == self.back { return None; }
+        self.back -= 1;
+        Some(&self.grid[self.back])
+    }
+}
+
+impl ExactSizeIterator for Rows<'_> {}
+
+impl PartialEq<Vec<Vec<u8>>> for Grid {
+    fn eq(&self, rows: &Vec<Vec<u8>>) -> bool {
+        self.height == rows.len() && self.iter().zip(rows).all(|(a, b)| a == b.as_slice())
+    }
+}
+
+impl std::fmt::Debug for Grid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Object {
@@ -28,7 +195,7 @@ impl Object {
     pub fn to_grid(&self) -> Grid {
         let h = self.height();
         let w = self.width();
-        let mut g = vec![vec![0u8; w]; h];
+        let mut g = Grid::new(h, w, 0);
         for &(r, c) in &self.cells {
             g[r - self.min_r][c - self.min_c] = self.color;
         }
@@ -44,6 +211,45 @@ impl Object {
     }
 }
 
+// Cell-wise combination of two grids, over the area they share. Background
+// cells are empty: Overlay lets the first grid show through them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum GridOp {
+    Overlay,  // second grid on top of the first
+    Xor,      // cells set in exactly one grid, the larger color when both differ
+    And,      // first grid's cells where both are set
+    Diff(u8), // cells where the grids differ, in the given color
+}
+
+impl GridOp {
+    pub fn apply(self, a: &Grid, b: &Grid, bg: u8) -> Grid {
+        let rows = a.height().min(b.height());
+        let cols = a.width().min(b.width());
+        let mut result = Grid::new(rows, cols, bg);
+        for r in 0..rows {
+            for c in 0..cols {
+                let (x, y) = (a[r][c], b[r][c]);
+                result[r][c] = match self {
+                    GridOp::Overlay => if y != bg { y } else { x },
+                    GridOp::Xor if x == y => bg,
+                    GridOp::Xor if x == bg => y,
+                    GridOp::Xor if y == bg => x,
+                    GridOp::Xor => x.max(y),
+                    GridOp::And => if x != bg && y != bg { x } else { bg },
+                    GridOp::Diff(mark) => if x != y { mark } else { bg },
+                };
+            }
+        }
+        result
+    }
+
+    pub fn all(colors: &[u8]) -> Vec<GridOp> {
+        let mut ops = vec![GridOp::Overlay, GridOp::Xor, GridOp::And];
+        ops.extend(colors.iter().map(|&c| GridOp::Diff(c)));
+        ops
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Prim {
     Identity,
@@ -90,12 +296,21 @@ pub enum Prim {
     DiagFillTR,                  // fill diagonal stripes top-right
     FillEnclosed(u8),            // fill regions enclosed by a specific wall color
     UpscaleObjects(usize),       // upscale each object to fill its bounding box × factor
+    WithBackground(u8, Box<Prim>), // run the inner program with this background color
+    SubGrid(usize),              // i-th region between separator lines, the grid if none
+    Binary(GridOp, Box<Prim>, Box<Prim>), // combine the outputs of two programs on the input
+    LibRef(String, Box<Prim>),   // learned library entry, one step however large its body
     Compose(Box<Prim>, Box<Prim>),
     Conditional(Box<Prim>, Box<Prim>, Box<Prim>),
 }
 
 impl Prim {
     pub fn apply(&self, grid: &Grid) -> Grid {
+        self.apply_bg(grid, 0)
+    }
+
+    // Object, gravity, crop and fill primitives treat `bg` as empty space
+    pub fn apply_bg(&self, grid: &Grid, bg: u8) -> Grid {
         match self {
             Prim::Identity => grid.clone(),
             Prim::RotateCW => rotate_cw(grid),
@@ -104,46 +319,52 @@ impl Prim {
             Prim::FlipH => flip_h(grid),
             Prim::FlipV => flip_v(grid),
             Prim::Transpose => transpose(grid),
-            Prim::FillColor(c) => fill_color(grid, *c),
+            Prim::FillColor(c) => fill_color(grid, *c, bg),
             Prim::ReplaceColor(from, to) => replace_color(grid, *from, *to),
             Prim::Crop(r, c, h, w) => crop(grid, *r, *c, *h, *w),
             Prim::Pad(n, c) => pad(grid, *n, *c),
             Prim::Scale(s) => scale(grid, *s),
-            Prim::FilterColor(c) => filter_color(grid, *c),
-            Prim::GravityDown => gravity_down(grid),
-            Prim::GravityUp => flip_v(&gravity_down(&flip_v(grid))),
-            Prim::GravityLeft => transpose(&gravity_down(&transpose(grid))),
-            Prim::GravityRight => transpose(&flip_v(&gravity_down(&flip_v(&transpose(grid))))),
-            Prim::MostFrequentColor => most_frequent_fill(grid),
+            Prim::FilterColor(c) => filter_color(grid, *c, bg),
+            Prim::GravityDown => gravity_down(grid, bg),
+            Prim::GravityUp => flip_v(&gravity_down(&flip_v(grid), bg)),
+            Prim::GravityLeft => transpose(&gravity_down(&transpose(grid), bg)),
+            Prim::GravityRight => transpose(&flip_v(&gravity_down(&flip_v(&transpose(grid)), bg))),
+            Prim::MostFrequentColor => most_frequent_fill(grid, bg),
             Prim::BorderFill(c) => border_fill(grid, *c),
             Prim::FloodFill(r, c, color) => flood_fill(grid, *r, *c, *color),
-            Prim::ExtractObject(idx) => extract_object(grid, *idx),
-            Prim::Overlay => grid.clone(), // Overlay needs two grids, handled separately
+            Prim::ExtractObject(idx) => extract_object(grid, *idx, bg),
+            Prim::Overlay => grid.clone(), // unary stub, see Binary(GridOp::Overlay, ..)
             Prim::MirrorH => mirror_h(grid),
             Prim::MirrorV => mirror_v(grid),
             Prim::RepeatH(n) => repeat_h(grid, *n),
             Prim::RepeatV(n) => repeat_v(grid, *n),
-            Prim::Invert => invert(grid),
-            Prim::SortRowsByColor => sort_rows_by_color(grid),
-            Prim::SortColsByColor => sort_cols_by_color(grid),
-            Prim::RemoveColor(c) => replace_color(grid, *c, 0),
-            Prim::KeepLargestObject => keep_largest_object(grid),
-            Prim::KeepSmallestObject => keep_smallest_object(grid),
-            Prim::OutlineObjects(c) => outline_objects(grid, *c),
-            Prim::FillInsideObjects(c) => fill_inside_objects(grid, *c),
-            Prim::Translate(dr, dc) => translate(grid, *dr, *dc),
-            Prim::CropToBBox => crop_to_bbox(grid),
-            Prim::ExtendHLines => extend_h_lines(grid),
-            Prim::ExtendVLines => extend_v_lines(grid),
-            Prim::ExtendCross => extend_cross(grid),
-            Prim::DiagFillTL => diag_fill_tl(grid),
-            Prim::DiagFillTR => diag_fill_tr(grid),
-            Prim::FillEnclosed(wall) => fill_enclosed(grid, *wall),
-            Prim::UpscaleObjects(f) => upscale_objects(grid, *f),
-            Prim::Compose(a, b) => b.apply(&a.apply(grid)),
+            Prim::Invert => invert(grid, bg),
+            Prim::SortRowsByColor => sort_rows_by_color(grid, bg),
+            Prim::SortColsByColor => sort_cols_by_color(grid, bg),
+            Prim::RemoveColor(c) => replace_color(grid, *c, bg),
+            Prim::KeepLargestObject => keep_largest_object(grid, bg),
+            Prim::KeepSmallestObject => keep_smallest_object(grid, bg),
+            Prim::OutlineObjects(c) => outline_objects(grid, *c, bg),
+            Prim::FillInsideObjects(c) => fill_inside_objects(grid, *c, bg),
+            Prim::Translate(dr, dc) => translate(grid, *dr, *dc, bg),
+            Prim::CropToBBox => crop_to_bbox(grid, bg),
+            Prim::ExtendHLines => extend_h_lines(grid, bg),
+            Prim::ExtendVLines => extend_v_lines(grid, bg),
+            Prim::ExtendCross => extend_cross(grid, bg),
+            Prim::DiagFillTL => diag_fill_tl(grid, bg),
+            Prim::DiagFillTR => diag_fill_tr(grid, bg),
+            Prim::FillEnclosed(wall) => fill_enclosed(grid, *wall, bg),
+            Prim::UpscaleObjects(f) => upscale_objects(grid, *f, bg),
+            Prim::WithBackground(inner_bg, p) => p.apply_bg(grid, *inner_bg),
+            Prim::SubGrid(i) => partition_grid(grid)
+                .and_then(|part| part.sub_grids.into_iter().nth(*i))
+                .unwrap_or_else(|| grid.clone()),
+            Prim::Binary(op, a, b) => op.apply(&a.apply_bg(grid, bg), &b.apply_bg(grid, bg), bg),
+            Prim::LibRef(_, body) => body.apply_bg(grid, bg),
+            Prim::Compose(a, b) => b.apply_bg(&a.apply_bg(grid, bg), bg),
             Prim::Conditional(cond, then_p, else_p) => {
-                let result = cond.apply(grid);
-                if result != *grid { then_p.apply(grid) } else { else_p.apply(grid) }
+                let result = cond.apply_bg(grid, bg);
+                if result != *grid { then_p.apply_bg(grid, bg) } else { else_p.apply_bg(grid, bg) }
             }
         }
     }
@@ -152,6 +373,8 @@ impl Prim {
         match self {
             Prim::Compose(a, b) => 1 + a.size() + b.size(),
             Prim::Conditional(a, b, c) => 1 + a.size() + b.size() + c.size(),
+            Prim::WithBackground(_, p) => 1 + p.size(),
+            Prim::Binary(_, a, b) => 1 + a.size() + b.size(),
             _ => 1,
         }
     }
@@ -199,6 +422,11 @@ impl Prim {
 // --- Grid analysis functions (public for use by other modules) ---
 
 pub fn connected_components(grid: &Grid, ignore_bg: bool) -> Vec<Object> {
+    connected_components_bg(grid, ignore_bg.then_some(0))
+}
+
+// Components of every color except `bg`, all colors when None
+pub fn connected_components_bg(grid: &Grid, bg: Option<u8>) -> Vec<Object> {
     if grid.is_empty() { return Vec::new(); }
     let rows = grid.len();
     let cols = grid[0].len();
@@ -209,7 +437,7 @@ pub fn connected_components(grid: &Grid, ignore_bg: bool) -> Vec<Object> {
         for c in 0..cols {
             if visited[r][c] { continue; }
             let color = grid[r][c];
-            if ignore_bg && color == 0 { continue; }
+            if bg == Some(color) { continue; }
 
             let mut cells = Vec::new();
             let mut stack = vec![(r, c)];
@@ -236,6 +464,11 @@ pub fn connected_components(grid: &Grid, ignore_bg: bool) -> Vec<Object> {
 }
 
 pub fn connected_components_8(grid: &Grid, ignore_bg: bool) -> Vec<Object> {
+    connected_components_8_bg(grid, ignore_bg.then_some(0))
+}
+
+// Components of every color except `bg`, all colors when None
+pub fn connected_components_8_bg(grid: &Grid, bg: Option<u8>) -> Vec<Object> {
     if grid.is_empty() { return Vec::new(); }
     let rows = grid.len();
     let cols = grid[0].len();
@@ -246,7 +479,7 @@ pub fn connected_components_8(grid: &Grid, ignore_bg: bool) -> Vec<Object> {
         for c in 0..cols {
             if visited[r][c] { continue; }
             let color = grid[r][c];
-            if ignore_bg && color == 0 { continue; }
+            if bg == Some(color) { continue; }
 
             let mut cells = Vec::new();
             let mut stack = vec![(r, c)];
@@ -275,6 +508,29 @@ pub fn connected_components_8(grid: &Grid, ignore_bg: bool) -> Vec<Object> {
     objects
 }
 
+// Background color of a grid: the most frequent color, border cells
+// counting twice so a frame-like background beats a large object
+pub fn detect_background(grid: &Grid) -> u8 {
+    detect_background_all(std::iter::once(grid))
+}
+
+// Shared background over several grids, e.g. all inputs of a task
+pub fn detect_background_all<'a>(grids: impl IntoIterator<Item = &'a Grid>) -> u8 {
+    let mut counts = [0usize; 256];
+    for grid in grids {
+        let rows = grid.len();
+        for (r, row) in grid.iter().enumerate() {
+            let cols = row.len();
+            for (c, &color) in row.iter().enumerate() {
+                let border = r == 0 || r + 1 == rows || c == 0 || c + 1 == cols;
+                counts[color as usize] += if border { 2 } else { 1 };
+            }
+        }
+    }
+    // Lowest color wins ties, so 0 stays the default
+    (0..=255u8).rev().max_by_key(|&c| counts[c as usize]).unwrap_or(0)
+}
+
 pub fn count_objects(grid: &Grid) -> usize {
     connected_components(grid, true).len()
 }
@@ -301,7 +557,7 @@ pub fn overlay_grids(base: &Grid, top: &Grid) -> Grid {
     if base.is_empty() { return top.clone(); }
     let rows = base.len().max(top.len());
     let cols = base[0].len().max(if top.is_empty() { 0 } else { top[0].len() });
-    let mut result = vec![vec![0u8; cols]; rows];
+    let mut result = Grid::new(rows, cols, 0);
     for r in 0..rows {
         for c in 0..cols {
             let base_val = if r < base.len() && c < base[0].len() { base[r][c] } else { 0 };
@@ -395,87 +651,102 @@ pub fn distance_between(a: &Object, b: &Object) -> f64 {
 // --- Internal primitive implementations ---
 
 fn rotate_cw(g: &Grid) -> Grid {
-    if g.is_empty() { return g.clone(); }
-    let rows = g.len();
-    let cols = g[0].len();
-    (0..cols).map(|c| (0..rows).rev().map(|r| g[r][c]).collect()).collect()
+    let (rows, cols) = (g.height(), g.width());
+    let mut result = Grid::new(cols, rows, 0);
+    for r in 0..rows {
+        for c in 0..cols {
+            result[c][rows - 1 - r] = g[r][c];
+        }
+    }
+    result
 }
 
 fn rotate_ccw(g: &Grid) -> Grid {
-    if g.is_empty() { return g.clone(); }
-    let rows = g.len();
-    let cols = g[0].len();
-    (0..cols).rev().map(|c| (0..rows).map(|r| g[r][c]).collect()).collect()
+    let (rows, cols) = (g.height(), g.width());
+    let mut result = Grid::new(cols, rows, 0);
+    for r in 0..rows {
+        for c in 0..cols {
+            result[cols - 1 - c][r] = g[r][c];
+        }
+    }
+    result
 }
 
 fn flip_h(g: &Grid) -> Grid {
-    g.iter().map(|row| row.iter().rev().cloned().collect()).collect()
+    let mut result = g.clone();
+    for r in 0..g.height() {
+        result[r].reverse();
+    }
+    result
 }
 
 fn flip_v(g: &Grid) -> Grid {
-    g.iter().rev().cloned().collect()
+    let mut result = Grid::default();
+    result.extend(g.iter().rev());
+    result
 }
 
 fn transpose(g: &Grid) -> Grid {
-    if g.is_empty() { return g.clone(); }
-    let cols = g[0].len();
-    (0..cols).map(|c| g.iter().map(|row| row[c]).collect()).collect()
+    let (rows, cols) = (g.height(), g.width());
+    let mut result = Grid::new(cols, rows, 0);
+    for r in 0..rows {
+        for c in 0..cols {
+            result[c][r] = g[r][c];
+        }
+    }
+    result
 }
 
-fn fill_color(g: &Grid, color: u8) -> Grid {
-    g.iter().map(|row| row.iter().map(|&c| if c != 0 { color } else { 0 }).collect()).collect()
+fn fill_color(g: &Grid, color: u8, bg: u8) -> Grid {
+    g.map(|c| if c != bg { color } else { bg })
 }
 
 fn replace_color(g: &Grid, from: u8, to: u8) -> Grid {
-    g.iter().map(|row| row.iter().map(|&c| if c == from { to } else { c }).collect()).collect()
+    g.map(|c| if c == from { to } else { c })
 }
 
 fn crop(g: &Grid, r: usize, c: usize, h: usize, w: usize) -> Grid {
-    g.iter().skip(r).take(h).map(|row| row.iter().skip(c).take(w).cloned().collect()).collect()
+    let rows = h.min(g.height().saturating_sub(r));
+    let cols = w.min(g.width().saturating_sub(c));
+    let mut result = Grid::new(rows, cols, 0);
+    for i in 0..rows {
+        result[i].copy_from_slice(&g[r + i][c..c + cols]);
+    }
+    result
 }
 
 fn pad(g: &Grid, n: usize, color: u8) -> Grid {
     if g.is_empty() { return g.clone(); }
-    let new_cols = g[0].len() + 2 * n;
-    let mut result = Vec::new();
-    for _ in 0..n {
-        result.push(vec![color; new_cols]);
-    }
-    for row in g {
-        let mut new_row = vec![color; n];
-        new_row.extend(row);
-        new_row.extend(vec![color; n]);
-        result.push(new_row);
-    }
-    for _ in 0..n {
-        result.push(vec![color; new_cols]);
+    let mut result = Grid::new(g.len() + 2 * n, g[0].len() + 2 * n, color);
+    for (r, row) in g.iter().enumerate() {
+        result[r + n][n..n + row.len()].copy_from_slice(row);
     }
     result
 }
 
 fn scale(g: &Grid, s: usize) -> Grid {
-    let mut result = Vec::new();
+    let mut result = Grid::default();
     for row in g {
         let scaled_row: Vec<u8> = row.iter().flat_map(|&c| std::iter::repeat(c).take(s)).collect();
         for _ in 0..s {
-            result.push(scaled_row.clone());
+            result.push_row(&scaled_row);
         }
     }
     result
 }
 
-fn filter_color(g: &Grid, color: u8) -> Grid {
-    g.iter().map(|row| row.iter().map(|&c| if c == color { c } else { 0 }).collect()).collect()
+fn filter_color(g: &Grid, color: u8, bg: u8) -> Grid {
+    g.map(|c| if c == color { c } else { bg })
 }
 
-fn gravity_down(g: &Grid) -> Grid {
+fn gravity_down(g: &Grid, bg: u8) -> Grid {
     if g.is_empty() { return g.clone(); }
     let rows = g.len();
     let cols = g[0].len();
-    let mut result = vec![vec![0u8; cols]; rows];
+    let mut result = Grid::new(rows, cols, bg);
     for c in 0..cols {
         let non_zero: Vec<u8> = (0..rows).filter_map(|r| {
-            if g[r][c] != 0 { Some(g[r][c]) } else { None }
+            if g[r][c] != bg { Some(g[r][c]) } else { None }
         }).collect();
         let offset = rows - non_zero.len();
         for (i, &val) in non_zero.iter().enumerate() {
@@ -485,16 +756,16 @@ fn gravity_down(g: &Grid) -> Grid {
     result
 }
 
-fn most_frequent_fill(g: &Grid) -> Grid {
+fn most_frequent_fill(g: &Grid, bg: u8) -> Grid {
     let mut counts = [0u32; 10];
     for row in g {
         for &c in row {
             if (c as usize) < 10 { counts[c as usize] += 1; }
         }
     }
-    counts[0] = 0;
+    if (bg as usize) < 10 { counts[bg as usize] = 0; }
     let mfc = counts.iter().enumerate().max_by_key(|(_, &c)| c).map(|(i, _)| i as u8).unwrap_or(0);
-    fill_color(g, mfc)
+    fill_color(g, mfc, bg)
 }
 
 fn border_fill(g: &Grid, color: u8) -> Grid {
@@ -533,8 +804,8 @@ fn flood_fill(g: &Grid, sr: usize, sc: usize, new_color: u8) -> Grid {
     result
 }
 
-fn extract_object(g: &Grid, idx: usize) -> Grid {
-    let objects = connected_components(g, true);
+fn extract_object(g: &Grid, idx: usize, bg: u8) -> Grid {
+    let objects = connected_components_bg(g, Some(bg));
     if idx >= objects.len() { return g.clone(); }
     let obj = &objects[idx];
     obj.to_grid()
@@ -544,7 +815,7 @@ fn mirror_h(g: &Grid) -> Grid {
     if g.is_empty() { return g.clone(); }
     let cols = g[0].len();
     g.iter().map(|row| {
-        let mut new_row = row.clone();
+        let mut new_row = row.to_vec();
         new_row.extend(row.iter().rev());
         new_row.truncate(cols * 2);
         new_row
@@ -553,8 +824,7 @@ fn mirror_h(g: &Grid) -> Grid {
 
 fn mirror_v(g: &Grid) -> Grid {
     let mut result = g.clone();
-    let reversed: Vec<Vec<u8>> = g.iter().rev().cloned().collect();
-    result.extend(reversed);
+    result.extend(g.iter().rev());
     result
 }
 
@@ -567,37 +837,37 @@ fn repeat_h(g: &Grid, n: usize) -> Grid {
 }
 
 fn repeat_v(g: &Grid, n: usize) -> Grid {
-    let mut result = Vec::new();
-    for _ in 0..n { result.extend(g.iter().cloned()); }
+    let mut result = Grid::default();
+    for _ in 0..n { result.extend(g.iter()); }
     result
 }
 
-fn invert(g: &Grid) -> Grid {
+fn invert(g: &Grid, bg: u8) -> Grid {
     let max_color = g.iter().flat_map(|r| r.iter()).max().copied().unwrap_or(1);
-    g.iter().map(|row| {
-        row.iter().map(|&c| if c == 0 { max_color } else { 0 }).collect()
-    }).collect()
+    g.map(|c| if c == bg { max_color } else { bg })
 }
 
-fn sort_rows_by_color(g: &Grid) -> Grid {
-    let mut result = g.clone();
-    result.sort_by_key(|row| {
-        row.iter().filter(|&&c| c != 0).next().copied().unwrap_or(255)
+fn sort_rows_by_color(g: &Grid, bg: u8) -> Grid {
+    let mut rows: Vec<&[u8]> = g.iter().collect();
+    rows.sort_by_key(|row| {
+        row.iter().find(|&&c| c != bg).copied().unwrap_or(255)
     });
+    let mut result = Grid::default();
+    result.extend(rows);
     result
 }
 
-fn sort_cols_by_color(g: &Grid) -> Grid {
-    transpose(&sort_rows_by_color(&transpose(g)))
+fn sort_cols_by_color(g: &Grid, bg: u8) -> Grid {
+    transpose(&sort_rows_by_color(&transpose(g), bg))
 }
 
-fn keep_largest_object(g: &Grid) -> Grid {
-    let objects = connected_components(g, true);
+fn keep_largest_object(g: &Grid, bg: u8) -> Grid {
+    let objects = connected_components_bg(g, Some(bg));
     let largest = objects.iter().max_by_key(|o| o.area());
     match largest {
         Some(obj) => {
             let (rows, cols) = grid_dimensions(g);
-            let mut result = vec![vec![0u8; cols]; rows];
+            let mut result = Grid::new(rows, cols, bg);
             for &(r, c) in &obj.cells {
                 result[r][c] = obj.color;
             }
@@ -607,13 +877,13 @@ fn keep_largest_object(g: &Grid) -> Grid {
     }
 }
 
-fn keep_smallest_object(g: &Grid) -> Grid {
-    let objects = connected_components(g, true);
+fn keep_smallest_object(g: &Grid, bg: u8) -> Grid {
+    let objects = connected_components_bg(g, Some(bg));
     let smallest = objects.iter().min_by_key(|o| o.area());
     match smallest {
         Some(obj) => {
             let (rows, cols) = grid_dimensions(g);
-            let mut result = vec![vec![0u8; cols]; rows];
+            let mut result = Grid::new(rows, cols, bg);
             for &(r, c) in &obj.cells {
                 result[r][c] = obj.color;
             }
@@ -623,19 +893,19 @@ fn keep_smallest_object(g: &Grid) -> Grid {
     }
 }
 
-fn outline_objects(g: &Grid, outline_color: u8) -> Grid {
+fn outline_objects(g: &Grid, outline_color: u8, bg: u8) -> Grid {
     if g.is_empty() { return g.clone(); }
     let rows = g.len();
     let cols = g[0].len();
     let mut result = g.clone();
     for r in 0..rows {
         for c in 0..cols {
-            if g[r][c] != 0 {
+            if g[r][c] != bg {
                 let on_border = [(0i32, 1i32), (0, -1), (1, 0), (-1, 0)].iter().any(|&(dr, dc)| {
                     let nr = r as i32 + dr;
                     let nc = c as i32 + dc;
                     nr < 0 || nr >= rows as i32 || nc < 0 || nc >= cols as i32
-                        || g[nr as usize][nc as usize] == 0
+                        || g[nr as usize][nc as usize] == bg
                 });
                 if on_border { result[r][c] = outline_color; }
             }
@@ -644,14 +914,14 @@ fn outline_objects(g: &Grid, outline_color: u8) -> Grid {
     result
 }
 
-fn translate(g: &Grid, dr: i32, dc: i32) -> Grid {
+fn translate(g: &Grid, dr: i32, dc: i32, bg: u8) -> Grid {
     if g.is_empty() { return g.clone(); }
     let rows = g.len();
     let cols = g[0].len();
-    let mut result = vec![vec![0u8; cols]; rows];
+    let mut result = Grid::new(rows, cols, bg);
     for r in 0..rows {
         for c in 0..cols {
-            if g[r][c] != 0 {
+            if g[r][c] != bg {
                 let nr = r as i32 + dr;
                 let nc = c as i32 + dc;
                 if nr >= 0 && (nr as usize) < rows && nc >= 0 && (nc as usize) < cols {
@@ -663,7 +933,7 @@ fn translate(g: &Grid, dr: i32, dc: i32) -> Grid {
     result
 }
 
-fn crop_to_bbox(g: &Grid) -> Grid {
+fn crop_to_bbox(g: &Grid, bg: u8) -> Grid {
     if g.is_empty() { return g.clone(); }
     let rows = g.len();
     let cols = g[0].len();
@@ -673,7 +943,7 @@ fn crop_to_bbox(g: &Grid) -> Grid {
     let mut max_c = 0;
     for r in 0..rows {
         for c in 0..cols {
-            if g[r][c] != 0 {
+            if g[r][c] != bg {
                 min_r = min_r.min(r);
                 max_r = max_r.max(r);
                 min_c = min_c.min(c);
@@ -681,18 +951,18 @@ fn crop_to_bbox(g: &Grid) -> Grid {
             }
         }
     }
-    if min_r > max_r { return vec![vec![0]]; }
+    if min_r > max_r { return Grid::new(1, 1, bg); }
     crop(g, min_r, min_c, max_r - min_r + 1, max_c - min_c + 1)
 }
 
-fn extend_h_lines(g: &Grid) -> Grid {
+fn extend_h_lines(g: &Grid, bg: u8) -> Grid {
     if g.is_empty() { return g.clone(); }
     let rows = g.len();
     let cols = g[0].len();
-    let mut result = vec![vec![0u8; cols]; rows];
+    let mut result = Grid::new(rows, cols, bg);
     for r in 0..rows {
         for c in 0..cols {
-            if g[r][c] != 0 {
+            if g[r][c] != bg {
                 for cc in 0..cols { result[r][cc] = g[r][c]; }
             }
         }
@@ -700,14 +970,14 @@ fn extend_h_lines(g: &Grid) -> Grid {
     result
 }
 
-fn extend_v_lines(g: &Grid) -> Grid {
+fn extend_v_lines(g: &Grid, bg: u8) -> Grid {
     if g.is_empty() { return g.clone(); }
     let rows = g.len();
     let cols = g[0].len();
-    let mut result = vec![vec![0u8; cols]; rows];
+    let mut result = Grid::new(rows, cols, bg);
     for r in 0..rows {
         for c in 0..cols {
-            if g[r][c] != 0 {
+            if g[r][c] != bg {
                 for rr in 0..rows { result[rr][c] = g[r][c]; }
             }
         }
@@ -715,19 +985,19 @@ fn extend_v_lines(g: &Grid) -> Grid {
     result
 }
 
-fn extend_cross(g: &Grid) -> Grid {
+fn extend_cross(g: &Grid, bg: u8) -> Grid {
     if g.is_empty() { return g.clone(); }
     let rows = g.len();
     let cols = g[0].len();
     let mut result = g.clone();
     for r in 0..rows {
         for c in 0..cols {
-            if g[r][c] != 0 {
+            if g[r][c] != bg {
                 for cc in 0..cols {
-                    if result[r][cc] == 0 { result[r][cc] = g[r][c]; }
+                    if result[r][cc] == bg { result[r][cc] = g[r][c]; }
                 }
                 for rr in 0..rows {
-                    if result[rr][c] == 0 { result[rr][c] = g[r][c]; }
+                    if result[rr][c] == bg { result[rr][c] = g[r][c]; }
                 }
             }
         }
@@ -735,19 +1005,19 @@ fn extend_cross(g: &Grid) -> Grid {
     result
 }
 
-fn diag_fill_tl(g: &Grid) -> Grid {
+fn diag_fill_tl(g: &Grid, bg: u8) -> Grid {
     if g.is_empty() { return g.clone(); }
     let rows = g.len();
     let cols = g[0].len();
     let mut result = g.clone();
     for r in 0..rows {
         for c in 0..cols {
-            if g[r][c] != 0 {
+            if g[r][c] != bg {
                 let color = g[r][c];
                 let mut nr = r as i32 + 1;
                 let mut nc = c as i32 + 1;
                 while nr < rows as i32 && nc < cols as i32 {
-                    if result[nr as usize][nc as usize] == 0 {
+                    if result[nr as usize][nc as usize] == bg {
                         result[nr as usize][nc as usize] = color;
                     }
                     nr += 1; nc += 1;
@@ -755,7 +1025,7 @@ fn diag_fill_tl(g: &Grid) -> Grid {
                 let mut nr = r as i32 - 1;
                 let mut nc = c as i32 - 1;
                 while nr >= 0 && nc >= 0 {
-                    if result[nr as usize][nc as usize] == 0 {
+                    if result[nr as usize][nc as usize] == bg {
                         result[nr as usize][nc as usize] = color;
                     }
                     nr -= 1; nc -= 1;
@@ -766,19 +1036,19 @@ fn diag_fill_tl(g: &Grid) -> Grid {
     result
 }
 
-fn diag_fill_tr(g: &Grid) -> Grid {
+fn diag_fill_tr(g: &Grid, bg: u8) -> Grid {
     if g.is_empty() { return g.clone(); }
     let rows = g.len();
     let cols = g[0].len();
     let mut result = g.clone();
     for r in 0..rows {
         for c in 0..cols {
-            if g[r][c] != 0 {
+            if g[r][c] != bg {
                 let color = g[r][c];
                 let mut nr = r as i32 + 1;
                 let mut nc = c as i32 - 1;
                 while nr < rows as i32 && nc >= 0 {
-                    if result[nr as usize][nc as usize] == 0 {
+                    if result[nr as usize][nc as usize] == bg {
                         result[nr as usize][nc as usize] = color;
                     }
                     nr += 1; nc -= 1;
@@ -786,7 +1056,7 @@ fn diag_fill_tr(g: &Grid) -> Grid {
                 let mut nr = r as i32 - 1;
                 let mut nc = c as i32 + 1;
                 while nr >= 0 && nc < cols as i32 {
-                    if result[nr as usize][nc as usize] == 0 {
+                    if result[nr as usize][nc as usize] == bg {
                         result[nr as usize][nc as usize] = color;
                     }
                     nr -= 1; nc += 1;
@@ -797,7 +1067,7 @@ fn diag_fill_tr(g: &Grid) -> Grid {
     result
 }
 
-fn fill_enclosed(g: &Grid, wall_color: u8) -> Grid {
+fn fill_enclosed(g: &Grid, wall_color: u8, bg: u8) -> Grid {
     if g.is_empty() { return g.clone(); }
     let rows = g.len();
     let cols = g[0].len();
@@ -827,7 +1097,7 @@ fn fill_enclosed(g: &Grid, wall_color: u8) -> Grid {
     }
     for r in 0..rows {
         for c in 0..cols {
-            if g[r][c] == 0 && !reachable[r][c] {
+            if g[r][c] == bg && !reachable[r][c] {
                 result[r][c] = wall_color;
             }
         }
@@ -835,14 +1105,14 @@ fn fill_enclosed(g: &Grid, wall_color: u8) -> Grid {
     result
 }
 
-fn upscale_objects(g: &Grid, factor: usize) -> Grid {
+fn upscale_objects(g: &Grid, factor: usize, bg: u8) -> Grid {
     if g.is_empty() || factor == 0 { return g.clone(); }
     let rows = g.len();
     let cols = g[0].len();
-    let mut result = vec![vec![0u8; cols * factor]; rows * factor];
+    let mut result = Grid::new(rows * factor, cols * factor, bg);
     for r in 0..rows {
         for c in 0..cols {
-            if g[r][c] != 0 {
+            if g[r][c] != bg {
                 for dr in 0..factor {
                     for dc in 0..factor {
                         result[r * factor + dr][c * factor + dc] = g[r][c];
@@ -854,20 +1124,20 @@ fn upscale_objects(g: &Grid, factor: usize) -> Grid {
     result
 }
 
-fn fill_inside_objects(g: &Grid, fill_color: u8) -> Grid {
+fn fill_inside_objects(g: &Grid, fill_color: u8, bg: u8) -> Grid {
     if g.is_empty() { return g.clone(); }
     let rows = g.len();
     let cols = g[0].len();
     let mut result = g.clone();
 
-    // For each object, find enclosed holes (0s not reachable from border)
+    // For each object, find enclosed holes (background not reachable from border)
     let mut reachable = vec![vec![false; cols]; rows];
     let mut stack: Vec<(usize, usize)> = Vec::new();
 
-    // Start BFS from all border 0s
+    // Start BFS from all border background cells
     for r in 0..rows {
         for c in 0..cols {
-            if (r == 0 || r == rows - 1 || c == 0 || c == cols - 1) && g[r][c] == 0 {
+            if (r == 0 || r == rows - 1 || c == 0 || c == cols - 1) && g[r][c] == bg {
                 reachable[r][c] = true;
                 stack.push((r, c));
             }
@@ -880,7 +1150,7 @@ fn fill_inside_objects(g: &Grid, fill_color: u8) -> Grid {
             let nc = c as i32 + dc;
             if nr >= 0 && nr < rows as i32 && nc >= 0 && nc < cols as i32 {
                 let (nr, nc) = (nr as usize, nc as usize);
-                if !reachable[nr][nc] && g[nr][nc] == 0 {
+                if !reachable[nr][nc] && g[nr][nc] == bg {
                     reachable[nr][nc] = true;
                     stack.push((nr, nc));
                 }
@@ -888,13 +1158,45 @@ fn fill_inside_objects(g: &Grid, fill_color: u8) -> Grid {
         }
     }
 
-    // Fill unreachable 0s
+    // Fill unreachable background
     for r in 0..rows {
         for c in 0..cols {
-            if g[r][c] == 0 && !reachable[r][c] {
+            if g[r][c] == bg && !reachable[r][c] {
                 result[r][c] = fill_color;
             }
         }
     }
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_background_and_threads_it_through_primitives() {
+        let grid = Grid::from(vec![vec![8, 8, 8], vec![8, 2, 8], vec![8, 8, 3]]);
+        assert_eq!(detect_background(&grid), 8);
+        // A tie goes to the lower color
+        assert_eq!(detect_background(&Grid::from(vec![vec![0, 5], vec![5, 0]])), 0);
+
+        assert_eq!(connected_components_bg(&grid, Some(8)).len(), 2);
+        assert_eq!(Prim::CropToBBox.apply_bg(&grid, 8), Grid::from(vec![vec![2, 8], vec![8, 3]]));
+        assert_eq!(Prim::GravityDown.apply_bg(&grid, 8), Grid::from(vec![vec![8, 8, 8], vec![8, 8, 8], vec![8, 2, 3]]));
+        let wrapped = Prim::WithBackground(8, Box::new(Prim::RemoveColor(2)));
+        assert_eq!(wrapped.apply(&grid), Grid::from(vec![vec![8, 8, 8], vec![8, 8, 8], vec![8, 8, 3]]));
+    }
+
+    #[test]
+    fn binary_nodes_combine_two_programs() {
+        // Two 2x2 halves split by a column of 5s
+        let grid = Grid::from(vec![vec![1, 0, 5, 1, 1], vec![0, 0, 5, 0, 0]]);
+        let halves = |op| Prim::Binary(op, Box::new(Prim::SubGrid(0)), Box::new(Prim::SubGrid(1)));
+        assert_eq!(halves(GridOp::Xor).apply(&grid), vec![vec![0, 1], vec![0, 0]]);
+        assert_eq!(halves(GridOp::And).apply(&grid), vec![vec![1, 0], vec![0, 0]]);
+        assert_eq!(halves(GridOp::Diff(4)).apply(&grid), vec![vec![0, 4], vec![0, 0]]);
+        let mirrored = Prim::Binary(GridOp::Overlay, Box::new(Prim::Identity), Box::new(Prim::FlipH));
+        assert_eq!(mirrored.apply(&Grid::from(vec![vec![2, 0, 0]])), vec![vec![2, 0, 2]]);
+        assert_eq!(halves(GridOp::Xor).size(), 3);
+    }
+}
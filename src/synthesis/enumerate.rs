@@ -1,4 +1,18 @@
-use super::dsl::{Prim, Grid};
+// Bottom-up enumeration with observational-equivalence pruning.
+//
+// Programs are bucketed by their outputs on every training input: two
+// programs with the same outputs (FlipH;FlipV and Rotate180) are equal as far
+// as the task can tell, so only the first of each class is extended, and a
+// program that leaves every input unchanged is not extended at all.
+// Compositions are run on the cached outputs of their prefix instead of
+// from the inputs.
+//
+//   let found = synthesize(&examples, 2)?;
+//   println!("{:?} after {} candidates", found.program, found.checked);
+
+use super::dsl::{grid_dimensions, unique_colors, GridOp, Prim, Grid};
+use super::bidir::state_hash;
+use rustc_hash::FxHashMap;
 
 #[derive(Debug, Clone)]
 pub struct SynthesisResult {
@@ -7,24 +21,57 @@ pub struct SynthesisResult {
     pub checked: usize,
 }
 
+// Output signatures seen so far, one per equivalence class
+#[derive(Default)]
+struct Classes {
+    seen: FxHashMap<u64, Vec<Vec<Grid>>>,
+}
+
+impl Classes {
+    // Whether `outputs` start a new class, recording it if so
+    fn insert(&mut self, outputs: &[Grid]) -> bool {
+        let bucket = self.seen.entry(state_hash(outputs)).or_default();
+        if bucket.iter().any(|known| known == outputs) {
+            return false;
+        }
+        bucket.push(outputs.to_vec());
+        true
+    }
+}
+
 pub fn synthesize(examples: &[(Grid, Grid)], max_size: usize) -> Option<SynthesisResult> {
     let mut checked = 0usize;
+    let (inputs, targets): (Vec<Grid>, Vec<Grid>) = examples.iter().cloned().unzip();
+    let found = |program: Prim, checked: usize| Some(SynthesisResult { size: program.size(), program, checked });
 
     let prims = Prim::all_primitives();
+    let mut classes = Classes::default();
+    classes.insert(&inputs);
+
+    // One representative per class of single primitives, with its outputs
+    let mut singles: Vec<(&Prim, Vec<Grid>)> = Vec::new();
     for p in &prims {
         checked += 1;
-        if matches_all(p, examples) {
-            return Some(SynthesisResult { program: p.clone(), size: p.size(), checked });
+        let outputs = apply_all(p, &inputs);
+        if outputs == targets {
+            return found(p.clone(), checked);
+        }
+        if classes.insert(&outputs) {
+            singles.push((p, outputs));
         }
     }
 
+    let mut pairs: Vec<(&Prim, &Prim, Vec<Grid>)> = Vec::new();
     if max_size >= 2 {
-        for a in &prims {
+        for (a, mid) in &singles {
             for b in &prims {
                 checked += 1;
-                let composed = Prim::Compose(Box::new(a.clone()), Box::new(b.clone()));
-                if matches_all(&composed, examples) {
-                    return Some(SynthesisResult { program: composed.clone(), size: composed.size(), checked });
+                let outputs = apply_all(b, mid);
+                if outputs == targets {
+                    return found(Prim::Compose(Box::new((*a).clone()), Box::new(b.clone())), checked);
+                }
+                if classes.insert(&outputs) {
+                    pairs.push((a, b, outputs));
                 }
             }
             if checked > 100_000 {
@@ -34,25 +81,29 @@ pub fn synthesize(examples: &[(Grid, Grid)], max_size: usize) -> Option<Synthesi
     }
 
     if max_size >= 3 {
-        let top_singles: Vec<&Prim> = prims.iter()
-            .filter(|p| partial_match_score(p, examples) > 0.3)
+        if let Some(result) = synthesize_binary(examples, &prims, &mut checked) {
+            return Some(result);
+        }
+
+        let top_singles: Vec<&Prim> = singles.iter()
+            .filter(|(_, outputs)| mean_similarity(outputs, &targets) > 0.3)
+            .map(|(p, _)| *p)
             .take(20)
             .collect();
 
-        for a in &top_singles {
-            for b in &top_singles {
-                for c in &top_singles {
-                    checked += 1;
+        for (a, b, mid) in &pairs {
+            if !top_singles.contains(a) || !top_singles.contains(b) { continue; }
+            for c in &top_singles {
+                checked += 1;
+                if apply_all(c, mid) == targets {
                     let prog = Prim::Compose(
                         Box::new((*a).clone()),
                         Box::new(Prim::Compose(Box::new((*b).clone()), Box::new((*c).clone()))),
                     );
-                    if matches_all(&prog, examples) {
-                        return Some(SynthesisResult { program: prog.clone(), size: prog.size(), checked });
-                    }
-                    if checked > 500_000 {
-                        return None;
-                    }
+                    return found(prog, checked);
+                }
+                if checked > 500_000 {
+                    return None;
                 }
             }
         }
@@ -61,6 +112,46 @@ pub fn synthesize(examples: &[(Grid, Grid)], max_size: usize) -> Option<Synthesi
     None
 }
 
+fn apply_all(program: &Prim, grids: &[Grid]) -> Vec<Grid> {
+    grids.iter().map(|g| program.apply(g)).collect()
+}
+
+fn mean_similarity(outputs: &[Grid], targets: &[Grid]) -> f64 {
+    if outputs.is_empty() { return 0.0; }
+    let total: f64 = outputs.iter().zip(targets).map(|(a, b)| grid_similarity(a, b)).sum();
+    total / outputs.len() as f64
+}
+
+// Binary nodes over operands whose outputs already have the target shapes:
+// the partition regions, and primitives that keep or produce that shape
+fn synthesize_binary(examples: &[(Grid, Grid)], prims: &[Prim], checked: &mut usize) -> Option<SynthesisResult> {
+    let shaped = |p: &Prim| examples.iter().all(|(input, expected)| {
+        grid_dimensions(&p.apply(input)) == grid_dimensions(expected)
+    });
+    let operands: Vec<Prim> = (0..4).map(Prim::SubGrid)
+        .chain(prims.iter().cloned())
+        .filter(|p| shaped(p))
+        .take(16)
+        .collect();
+    let mut colors: Vec<u8> = examples.iter().flat_map(|(_, out)| unique_colors(out)).filter(|&c| c != 0).collect();
+    colors.sort_unstable();
+    colors.dedup();
+
+    for op in GridOp::all(&colors) {
+        for a in &operands {
+            for b in &operands {
+                if a == b { continue; }
+                *checked += 1;
+                let prog = Prim::Binary(op, Box::new(a.clone()), Box::new(b.clone()));
+                if matches_all(&prog, examples) {
+                    return Some(SynthesisResult { size: prog.size(), program: prog, checked: *checked });
+                }
+            }
+        }
+    }
+    None
+}
+
 fn matches_all(program: &Prim, examples: &[(Grid, Grid)]) -> bool {
     examples.iter().all(|(input, expected)| {
         let result = program.apply(input);
@@ -104,3 +195,41 @@ pub fn bottom_up_enumerate(examples: &[(Grid, Grid)], max_programs: usize) -> Ve
     ranked.truncate(max_programs);
     ranked
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_binary_programs_over_partition_halves() {
+        // Output marks the cells where the two halves differ
+        let examples: Vec<(Grid, Grid)> = [
+            (vec![vec![1, 0, 5, 1, 1], vec![0, 1, 5, 0, 0]], vec![vec![0, 3], vec![0, 3]]),
+            (vec![vec![0, 0, 5, 1, 0], vec![1, 1, 5, 0, 1]], vec![vec![3, 0], vec![3, 0]]),
+        ].into_iter().map(|(i, o)| (Grid::from(i), Grid::from(o))).collect();
+        let found = synthesize(&examples, 3).unwrap();
+        assert!(matches!(found.program, Prim::Binary(GridOp::Diff(3), _, _)), "{:?}", found.program);
+        assert!(synthesize(&examples, 2).is_none());
+    }
+
+    #[test]
+    fn extends_one_program_per_equivalence_class() {
+        let input = Grid::from(vec![vec![1, 0, 2], vec![0, 3, 0], vec![4, 0, 0]]);
+        let program = Prim::Compose(Box::new(Prim::FlipH), Box::new(Prim::GravityDown));
+        let examples = vec![(input.clone(), program.apply(&input))];
+        assert_eq!(synthesize(&examples, 2).unwrap().program.apply(&input), examples[0].1);
+
+        // Primitives equal on the input to an earlier one, or to no change
+        // at all, are not extended
+        let prims = Prim::all_primitives();
+        let mut classes = Classes::default();
+        classes.insert(std::slice::from_ref(&input));
+        let extended = prims.iter().filter(|p| classes.insert(&[p.apply(&input)])).count();
+        assert!(extended < prims.len(), "{} of {}", extended, prims.len());
+
+        let mut classes = Classes::default();
+        assert!(classes.insert(&[Prim::Rotate180.apply(&input)]));
+        let flips = Prim::Compose(Box::new(Prim::FlipH), Box::new(Prim::FlipV));
+        assert!(!classes.insert(&[flips.apply(&input)]));
+    }
+}
@@ -180,8 +180,8 @@ mod tests {
 
     #[test]
     fn fingerprint_identical_grids() {
-        let g1 = vec![vec![1, 2], vec![3, 4]];
-        let g2 = vec![vec![1, 2], vec![3, 4]];
+        let g1 = Grid::from(vec![vec![1, 2], vec![3, 4]]);
+        let g2 = Grid::from(vec![vec![1, 2], vec![3, 4]]);
         let fp1 = GridFingerprint::compute(&g1);
         let fp2 = GridFingerprint::compute(&g2);
         assert_eq!(fp1.full, fp2.full);
@@ -190,8 +190,8 @@ mod tests {
 
     #[test]
     fn fingerprint_different_grids() {
-        let g1 = vec![vec![1, 2], vec![3, 4]];
-        let g2 = vec![vec![1, 2], vec![3, 5]];
+        let g1 = Grid::from(vec![vec![1, 2], vec![3, 4]]);
+        let g2 = Grid::from(vec![vec![1, 2], vec![3, 5]]);
         let fp1 = GridFingerprint::compute(&g1);
         let fp2 = GridFingerprint::compute(&g2);
         assert_ne!(fp1.full, fp2.full);
@@ -200,8 +200,8 @@ mod tests {
 
     #[test]
     fn fingerprint_different_shapes() {
-        let g1 = vec![vec![1, 2, 3]];
-        let g2 = vec![vec![1], vec![2], vec![3]];
+        let g1 = Grid::from(vec![vec![1, 2, 3]]);
+        let g2 = Grid::from(vec![vec![1], vec![2], vec![3]]);
         let fp1 = GridFingerprint::compute(&g1);
         let fp2 = GridFingerprint::compute(&g2);
         assert!(!fp1.same_shape(&fp2));
@@ -209,8 +209,8 @@ mod tests {
 
     #[test]
     fn color_signature_same_histogram() {
-        let g1 = vec![vec![1, 2, 1], vec![2, 1, 2]];
-        let g2 = vec![vec![2, 1, 2], vec![1, 2, 1]];
+        let g1 = Grid::from(vec![vec![1, 2, 1], vec![2, 1, 2]]);
+        let g2 = Grid::from(vec![vec![2, 1, 2], vec![1, 2, 1]]);
         let fp1 = GridFingerprint::compute(&g1);
         let fp2 = GridFingerprint::compute(&g2);
         assert_eq!(fp1.color_sig, fp2.color_sig);
@@ -218,9 +218,9 @@ mod tests {
 
     #[test]
     fn fingerprint_set_dedup() {
-        let g1 = vec![vec![1, 2], vec![3, 4]];
-        let g2 = vec![vec![1, 2], vec![3, 4]];
-        let g3 = vec![vec![5, 6], vec![7, 8]];
+        let g1 = Grid::from(vec![vec![1, 2], vec![3, 4]]);
+        let g2 = Grid::from(vec![vec![1, 2], vec![3, 4]]);
+        let g3 = Grid::from(vec![vec![5, 6], vec![7, 8]]);
 
         let mut set = FingerprintSet::new();
         assert!(set.insert(&g1));   // new
@@ -231,15 +231,15 @@ mod tests {
 
     #[test]
     fn multi_res_self_similarity() {
-        let g = vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]];
+        let g = Grid::from(vec![vec![1, 2, 3], vec![4, 5, 6], vec![7, 8, 9]]);
         let mr = MultiResFingerprint::compute(&g);
         assert_eq!(mr.similarity(&mr), 1.0);
     }
 
     #[test]
     fn multi_res_different() {
-        let g1 = vec![vec![1, 2], vec![3, 4]];
-        let g2 = vec![vec![5, 6], vec![7, 8]];
+        let g1 = Grid::from(vec![vec![1, 2], vec![3, 4]]);
+        let g2 = Grid::from(vec![vec![5, 6], vec![7, 8]]);
         let mr1 = MultiResFingerprint::compute(&g1);
         let mr2 = MultiResFingerprint::compute(&g2);
         assert!(mr1.similarity(&mr2) < 1.0);
@@ -247,7 +247,7 @@ mod tests {
 
     #[test]
     fn empty_grid_fingerprint() {
-        let g: Grid = Vec::new();
+        let g = Grid::default();
         let fp = GridFingerprint::compute(&g);
         assert_eq!(fp.shape, 0);
     }
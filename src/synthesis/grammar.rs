@@ -0,0 +1,185 @@
+// Learned probabilistic grammar over primitives.
+//
+// A program is a chain of steps, so the grammar is a PCFG with one
+// production per step given the step before it:
+//
+//   Program -> Step | Program ; Step
+//   P(Program) = P(s1 | start) * P(s2 | s1) * ...
+//
+// Weights are counts over a corpus of solved programs (normalized first),
+// smoothed toward how often each step is used at all, so unseen pairs keep
+// a small probability. Steps are keyed by their skeleton: FillColor(3) and
+// FillColor(5) are the same production.
+//
+//   let grammar = Grammar::from_cache(&cache);
+//   let dag = SearchDag::new(20_000).with_grammar(grammar);
+//
+// SearchDag expands likely programs first and, from each node, tries its
+// likely next steps first. An empty grammar weighs every step the same and
+// leaves the enumeration order as it was.
+
+use super::adaptive::SolutionCache;
+use super::antiunify::skeleton;
+use super::dsl::{GridOp, Prim};
+use super::normalize::normalize;
+use rustc_hash::FxHashMap;
+
+// Pseudo-count pulling each transition toward the step's overall frequency
+const SMOOTHING: f64 = 1.0;
+
+#[derive(Debug, Clone, Default)]
+pub struct Grammar {
+    // (previous step, step) -> count, Identity standing for the start
+    transitions: FxHashMap<(Prim, Prim), f64>,
+    // previous step -> count of transitions out of it
+    contexts: FxHashMap<Prim, f64>,
+    steps: FxHashMap<Prim, f64>,
+    total: f64,
+}
+
+impl Grammar {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn fit<'a>(programs: impl IntoIterator<Item = &'a Prim>) -> Self {
+        let mut grammar = Self::new();
+        for program in programs {
+            grammar.observe(&normalize(program));
+        }
+        grammar
+    }
+
+    pub fn from_cache(cache: &SolutionCache) -> Self {
+        Self::fit(cache.programs())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.total == 0.0
+    }
+
+    // Counts the steps of a program, and of the programs nested in them
+    fn observe(&mut self, program: &Prim) {
+        let mut prev = Prim::Identity;
+        for step in steps(program) {
+            match step {
+                Prim::WithBackground(_, inner) => self.observe(inner),
+                Prim::Binary(_, a, b) => {
+                    self.observe(a);
+                    self.observe(b);
+                }
+                Prim::Conditional(cond, then_p, else_p) => {
+                    self.observe(cond);
+                    self.observe(then_p);
+                    self.observe(else_p);
+                }
+                _ => {}
+            }
+            let next = production(step);
+            *self.transitions.entry((prev.clone(), next.clone())).or_default() += 1.0;
+            *self.contexts.entry(prev).or_default() += 1.0;
+            *self.steps.entry(next.clone()).or_default() += 1.0;
+            self.total += 1.0;
+            prev = next;
+        }
+    }
+
+    // P(step | previous step)
+    pub fn prob(&self, prev: &Prim, step: &Prim) -> f64 {
+        let (prev, step) = (production(prev), production(step));
+        let vocab = self.steps.len() as f64 + 1.0;
+        let overall = (self.steps.get(&step).copied().unwrap_or(0.0) + 1.0) / (self.total + vocab);
+        let seen = self.transitions.get(&(prev.clone(), step)).copied().unwrap_or(0.0);
+        let context = self.contexts.get(&prev).copied().unwrap_or(0.0);
+        (seen + SMOOTHING * overall) / (context + SMOOTHING)
+    }
+
+    pub fn log_prob(&self, program: &Prim) -> f64 {
+        let mut prev = &Prim::Identity;
+        let mut total = 0.0;
+        for step in steps(program) {
+            total += self.prob(prev, step).ln();
+            prev = step;
+        }
+        total
+    }
+
+    // `primitives` most likely first as the step after `program`, ties in
+    // their given order
+    pub fn order(&self, program: &Prim, primitives: &[Prim]) -> Vec<Prim> {
+        let prev = steps(program).last().copied().unwrap_or(&Prim::Identity);
+        let mut scored: Vec<(f64, &Prim)> = primitives.iter().map(|p| (self.prob(prev, p), p)).collect();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored.into_iter().map(|(_, p)| p.clone()).collect()
+    }
+}
+
+// Steps of a compose chain in application order, without Identity
+fn steps(program: &Prim) -> Vec<&Prim> {
+    fn walk<'a>(program: &'a Prim, out: &mut Vec<&'a Prim>) {
+        match program {
+            Prim::Compose(a, b) => {
+                walk(a, out);
+                walk(b, out);
+            }
+            Prim::Identity => {}
+            other => out.push(other),
+        }
+    }
+    let mut out = Vec::new();
+    walk(program, &mut out);
+    out
+}
+
+// The production a step instantiates: its constants zeroed, and nested
+// programs left out
+fn production(step: &Prim) -> Prim {
+    let hole = || Box::new(Prim::Identity);
+    match step {
+        Prim::WithBackground(_, _) => Prim::WithBackground(0, hole()),
+        Prim::Binary(GridOp::Diff(_), _, _) => Prim::Binary(GridOp::Diff(0), hole(), hole()),
+        Prim::Binary(op, _, _) => Prim::Binary(*op, hole(), hole()),
+        Prim::Conditional(_, _, _) => Prim::Conditional(hole(), hole(), hole()),
+        other => skeleton(other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthesis::adaptive::TransformType;
+
+    fn then(a: Prim, b: Prim) -> Prim {
+        Prim::Compose(Box::new(a), Box::new(b))
+    }
+
+    #[test]
+    fn common_compositions_come_first() {
+        let mut cache = SolutionCache::new();
+        for (i, color) in [2, 5, 7].into_iter().enumerate() {
+            let program = then(Prim::FlipH, then(Prim::GravityDown, Prim::FillColor(color)));
+            cache.add(program, format!("task_{}", i), TransformType::Geometric);
+        }
+        cache.add(then(Prim::FlipH, Prim::Transpose), "task_3".into(), TransformType::Geometric);
+        let grammar = Grammar::from_cache(&cache);
+
+        let prims = [Prim::RotateCW, Prim::Transpose, Prim::GravityDown, Prim::FlipH];
+        let after_flip = grammar.order(&Prim::FlipH, &prims);
+        assert_eq!(after_flip[..2], [Prim::GravityDown, Prim::Transpose]);
+        assert_eq!(grammar.order(&Prim::Identity, &prims)[0], Prim::FlipH);
+
+        // Constants are abstracted: an unseen color is as likely as seen ones
+        let seen = then(then(Prim::FlipH, Prim::GravityDown), Prim::FillColor(2));
+        let unseen = then(then(Prim::FlipH, Prim::GravityDown), Prim::FillColor(9));
+        assert_eq!(grammar.log_prob(&seen), grammar.log_prob(&unseen));
+        assert!(grammar.log_prob(&seen) > grammar.log_prob(&then(Prim::GravityDown, Prim::FlipH)));
+    }
+
+    #[test]
+    fn empty_grammar_keeps_the_given_order() {
+        let grammar = Grammar::new();
+        assert!(grammar.is_empty());
+        let prims = Prim::all_primitives();
+        assert_eq!(grammar.order(&Prim::FlipH, &prims), prims);
+    }
+}
@@ -329,27 +329,27 @@ mod tests {
 
     #[test]
     fn dim_same_detected() {
-        let input = vec![vec![1, 2], vec![3, 4]];
-        let output = vec![vec![4, 3], vec![2, 1]];
+        let input = Grid::from(vec![vec![1, 2], vec![3, 4]]);
+        let output = Grid::from(vec![vec![4, 3], vec![2, 1]]);
         let prof = analyze_features(&[(input, output)]);
         assert_eq!(prof.dim_change, DimChange::Same);
     }
 
     #[test]
     fn dim_transposed_detected() {
-        let input = vec![vec![1, 2, 3], vec![4, 5, 6]]; // 2x3
-        let output = vec![vec![1, 4], vec![2, 5], vec![3, 6]]; // 3x2
+        let input = Grid::from(vec![vec![1, 2, 3], vec![4, 5, 6]]); // 2x3
+        let output = Grid::from(vec![vec![1, 4], vec![2, 5], vec![3, 6]]); // 3x2
         let prof = analyze_features(&[(input, output)]);
         assert_eq!(prof.dim_change, DimChange::Transposed);
     }
 
     #[test]
     fn dim_scaled_detected() {
-        let input = vec![vec![1, 2], vec![3, 4]]; // 2x2
-        let output = vec![
+        let input = Grid::from(vec![vec![1, 2], vec![3, 4]]); // 2x2
+        let output = Grid::from(vec![
             vec![1, 1, 2, 2], vec![1, 1, 2, 2],
             vec![3, 3, 4, 4], vec![3, 3, 4, 4],
-        ]; // 4x4 = 2x scale
+        ]); // 4x4 = 2x scale
         let prof = analyze_features(&[(input, output)]);
         assert_eq!(prof.dim_change, DimChange::Scaled(2, 2));
     }
@@ -357,16 +357,16 @@ mod tests {
     #[test]
     fn color_bijection_detected() {
         // Same number of unique colors, different values
-        let input = vec![vec![1, 2], vec![0, 1]];
-        let output = vec![vec![3, 4], vec![0, 3]];
+        let input = Grid::from(vec![vec![1, 2], vec![0, 1]]);
+        let output = Grid::from(vec![vec![3, 4], vec![0, 3]]);
         let prof = analyze_features(&[(input, output)]);
         assert_eq!(prof.color_change, ColorChange::Bijection);
     }
 
     #[test]
     fn heuristic_selects_fewer_prims() {
-        let input = vec![vec![1, 2], vec![3, 4]];
-        let output = vec![vec![4, 3], vec![2, 1]]; // rotation/flip
+        let input = Grid::from(vec![vec![1, 2], vec![3, 4]]);
+        let output = Grid::from(vec![vec![4, 3], vec![2, 1]]); // rotation/flip
         let prof = analyze_features(&[(input, output)]);
         let prims = select_primitives(&prof);
         let all = Prim::all_primitives();
@@ -377,8 +377,8 @@ mod tests {
 
     #[test]
     fn transpose_detected_selects_transpose() {
-        let input = vec![vec![1, 2, 3], vec![4, 5, 6]];
-        let output = vec![vec![1, 4], vec![2, 5], vec![3, 6]];
+        let input = Grid::from(vec![vec![1, 2, 3], vec![4, 5, 6]]);
+        let output = Grid::from(vec![vec![1, 4], vec![2, 5], vec![3, 6]]);
         let prof = analyze_features(&[(input, output)]);
         let prims = select_primitives(&prof);
         assert!(prims.contains(&Prim::Transpose));
@@ -386,8 +386,8 @@ mod tests {
 
     #[test]
     fn symmetry_change_detected() {
-        let input = vec![vec![1, 2, 3], vec![4, 5, 6]];
-        let output = vec![vec![1, 2, 1], vec![4, 5, 4]]; // h-symmetric output
+        let input = Grid::from(vec![vec![1, 2, 3], vec![4, 5, 6]]);
+        let output = Grid::from(vec![vec![1, 2, 1], vec![4, 5, 4]]); // h-symmetric output
         let prof = analyze_features(&[(input, output)]);
         assert!(!prof.input_symmetric_h);
         assert!(prof.output_symmetric_h);
@@ -13,3 +13,8 @@ pub mod cellular;
 pub mod partition;
 pub mod object_ops;
 pub mod connect;
+pub mod solver;
+pub mod parallel;
+pub mod antiunify;
+pub mod normalize;
+pub mod grammar;
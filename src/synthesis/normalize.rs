@@ -0,0 +1,185 @@
+// Program normalizer: rewrites a program into a canonical, equivalent form.
+//
+// Search happily returns programs like Compose(Identity, Compose(FlipH, FlipH))
+// that are longer than what they compute. Normalizing flattens the compose
+// chain into a list of steps, then folds each step into the previous one:
+//
+//   Identity, ReplaceColor(3, 3), RemoveColor(bg)  => dropped
+//   FlipH ; FlipH, Transpose ; Transpose           => cancelled
+//   RotateCW ; Rotate180                           => RotateCCW
+//   GravityDown ; GravityDown                      => GravityDown
+//   FillColor(2) ; FillColor(5)                    => FillColor(5)
+//
+// and rebuilds the chain left-nested, the shape search builds it in.
+//
+//   let program = normalize(&found);
+//   assert_eq!(program.apply(&grid), found.apply(&grid));
+//
+// Every rewrite is exact, so normalizing never changes what a program does.
+// Color rewrites depend on the background, which WithBackground changes for
+// its inner program.
+
+use super::dsl::Prim;
+
+pub fn normalize(program: &Prim) -> Prim {
+    normalize_bg(program, 0)
+}
+
+fn normalize_bg(program: &Prim, bg: u8) -> Prim {
+    let mut steps = Vec::new();
+    flatten(program, bg, &mut steps);
+    steps.into_iter()
+        .reduce(|acc, step| Prim::Compose(Box::new(acc), Box::new(step)))
+        .unwrap_or(Prim::Identity)
+}
+
+// Pushes the steps of `program` in application order, normalizing nested
+// programs on the way. Library references are opaque.
+fn flatten(program: &Prim, bg: u8, steps: &mut Vec<Prim>) {
+    match program {
+        Prim::Compose(a, b) => {
+            flatten(a, bg, steps);
+            flatten(b, bg, steps);
+        }
+        Prim::WithBackground(inner_bg, inner) if *inner_bg == bg => flatten(inner, bg, steps),
+        Prim::WithBackground(inner_bg, inner) => {
+            let inner = normalize_bg(inner, *inner_bg);
+            push(steps, Prim::WithBackground(*inner_bg, Box::new(inner)), bg);
+        }
+        Prim::Conditional(cond, then_p, else_p) => {
+            let then_p = normalize_bg(then_p, bg);
+            let else_p = normalize_bg(else_p, bg);
+            if then_p == else_p {
+                flatten(&then_p, bg, steps);
+            } else {
+                let cond = normalize_bg(cond, bg);
+                push(steps, Prim::Conditional(Box::new(cond), Box::new(then_p), Box::new(else_p)), bg);
+            }
+        }
+        Prim::Binary(op, a, b) => {
+            let step = Prim::Binary(*op, Box::new(normalize_bg(a, bg)), Box::new(normalize_bg(b, bg)));
+            push(steps, step, bg);
+        }
+        other => push(steps, other.clone(), bg),
+    }
+}
+
+// Appends a step, folding it into the last one while they merge
+fn push(steps: &mut Vec<Prim>, step: Prim, bg: u8) {
+    if is_noop(&step, bg) {
+        return;
+    }
+    match steps.last().and_then(|last| merge(last, &step, bg)) {
+        Some(merged) => {
+            steps.pop();
+            push(steps, merged, bg);
+        }
+        None => steps.push(step),
+    }
+}
+
+fn is_noop(step: &Prim, bg: u8) -> bool {
+    match step {
+        Prim::Identity | Prim::Translate(0, 0) | Prim::Scale(1) => true,
+        Prim::ReplaceColor(from, to) => from == to,
+        Prim::RemoveColor(c) => *c == bg,
+        Prim::WithBackground(_, inner) => **inner == Prim::Identity,
+        _ => false,
+    }
+}
+
+// The single step equal to `a` then `b`, Identity when they cancel
+fn merge(a: &Prim, b: &Prim, bg: u8) -> Option<Prim> {
+    if let (Some(x), Some(y)) = (quarter_turns(a), quarter_turns(b)) {
+        return Some(from_quarter_turns(x + y));
+    }
+    match (a, b) {
+        (Prim::FlipH, Prim::FlipH) | (Prim::FlipV, Prim::FlipV) | (Prim::Transpose, Prim::Transpose) => {
+            Some(Prim::Identity)
+        }
+        (Prim::FlipH, Prim::FlipV) | (Prim::FlipV, Prim::FlipH) => Some(Prim::Rotate180),
+        _ if a == b && is_idempotent(a) => Some(a.clone()),
+        // Filling with the background empties the grid for good
+        (Prim::FillColor(x), Prim::FillColor(y)) => Some(Prim::FillColor(if *x == bg { *x } else { *y })),
+        // The first replacement already removed every `from` cell
+        (Prim::ReplaceColor(from, to), Prim::ReplaceColor(again, _)) if from == again => {
+            Some(Prim::ReplaceColor(*from, *to))
+        }
+        // Swapping one foreground color for another is invisible to a fill
+        (Prim::ReplaceColor(from, to), Prim::FillColor(_)) if *from != bg && *to != bg => Some(b.clone()),
+        (Prim::FillColor(x), Prim::ReplaceColor(from, to)) if from == x && *x != bg => Some(Prim::FillColor(*to)),
+        _ => None,
+    }
+}
+
+fn quarter_turns(step: &Prim) -> Option<u8> {
+    match step {
+        Prim::RotateCW => Some(1),
+        Prim::Rotate180 => Some(2),
+        Prim::RotateCCW => Some(3),
+        _ => None,
+    }
+}
+
+fn from_quarter_turns(turns: u8) -> Prim {
+    match turns % 4 {
+        1 => Prim::RotateCW,
+        2 => Prim::Rotate180,
+        3 => Prim::RotateCCW,
+        _ => Prim::Identity,
+    }
+}
+
+// Steps that change nothing when repeated
+fn is_idempotent(step: &Prim) -> bool {
+    matches!(step,
+        Prim::GravityDown | Prim::GravityUp | Prim::GravityLeft | Prim::GravityRight
+        | Prim::CropToBBox | Prim::SortRowsByColor | Prim::SortColsByColor
+        | Prim::FilterColor(_) | Prim::RemoveColor(_))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::synthesis::dsl::Grid;
+
+    fn compose(steps: &[Prim]) -> Prim {
+        steps.iter().rev().cloned()
+            .reduce(|acc, s| Prim::Compose(Box::new(s), Box::new(acc)))
+            .unwrap()
+    }
+
+    #[test]
+    fn removes_identities_and_cancels_inverses() {
+        let program = Prim::Compose(
+            Box::new(Prim::Identity),
+            Box::new(Prim::Compose(Box::new(Prim::FlipH), Box::new(Prim::FlipH))),
+        );
+        assert_eq!(normalize(&program), Prim::Identity);
+
+        // Cancellation cascades through the chain
+        let nested = compose(&[Prim::FlipV, Prim::Transpose, Prim::Transpose, Prim::FlipV, Prim::GravityDown]);
+        assert_eq!(normalize(&nested), Prim::GravityDown);
+
+        let turns = compose(&[Prim::RotateCW, Prim::RotateCW, Prim::Rotate180, Prim::RotateCW]);
+        assert_eq!(normalize(&turns), Prim::RotateCW);
+    }
+
+    #[test]
+    fn merges_color_ops_without_changing_results() {
+        let grid = Grid::from(vec![vec![0, 1, 2], vec![3, 0, 1], vec![2, 2, 0]]);
+        let programs = [
+            compose(&[Prim::FillColor(5), Prim::FillColor(2)]),
+            compose(&[Prim::FillColor(0), Prim::FillColor(2)]),
+            compose(&[Prim::ReplaceColor(1, 4), Prim::ReplaceColor(1, 6), Prim::FillColor(7)]),
+            compose(&[Prim::FillColor(3), Prim::ReplaceColor(3, 8), Prim::GravityDown, Prim::GravityDown]),
+            Prim::WithBackground(2, Box::new(compose(&[Prim::RemoveColor(2), Prim::FillColor(2), Prim::FillColor(4)]))),
+        ];
+        for program in &programs {
+            let normal = normalize(program);
+            assert!(normal.size() < program.size(), "{:?} => {:?}", program, normal);
+            assert_eq!(normal.apply(&grid), program.apply(&grid), "{:?} => {:?}", program, normal);
+        }
+        assert_eq!(normalize(&programs[0]), Prim::FillColor(2));
+    }
+}
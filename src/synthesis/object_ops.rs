@@ -158,7 +158,7 @@ pub fn sort_objects_by_size(grid: &Grid) -> Grid {
     let mut objects = connected_components(grid, true);
     objects.sort_by_key(|o| o.area());
 
-    let mut result = vec![vec![0u8; cols]; rows];
+    let mut result = Grid::new(rows, cols, 0);
     let mut cur_c = 0;
     for obj in &objects {
         let og = obj.to_grid();
@@ -357,11 +357,11 @@ mod tests {
 
     #[test]
     fn extend_markers_h() {
-        let grid = vec![
+        let grid = Grid::from(vec![
             vec![0, 0, 0],
             vec![0, 3, 0],
             vec![0, 0, 0],
-        ];
+        ]);
         let result = extend_markers_to_lines(&grid, LineDir::Horizontal);
         assert_eq!(result[1], vec![3, 3, 3]);
         assert_eq!(result[0], vec![0, 0, 0]);
@@ -369,11 +369,11 @@ mod tests {
 
     #[test]
     fn extend_markers_v() {
-        let grid = vec![
+        let grid = Grid::from(vec![
             vec![0, 0, 0],
             vec![0, 3, 0],
             vec![0, 0, 0],
-        ];
+        ]);
         let result = extend_markers_to_lines(&grid, LineDir::Vertical);
         assert_eq!(result[0][1], 3);
         assert_eq!(result[1][1], 3);
@@ -382,13 +382,13 @@ mod tests {
 
     #[test]
     fn stamp_plus_basic() {
-        let grid = vec![
+        let grid = Grid::from(vec![
             vec![0, 0, 0, 0, 0],
             vec![0, 0, 0, 0, 0],
             vec![0, 0, 2, 0, 0],
             vec![0, 0, 0, 0, 0],
             vec![0, 0, 0, 0, 0],
-        ];
+        ]);
         let result = stamp_plus(&grid, 2, 4, 1);
         assert_eq!(result[1][2], 4); // up
         assert_eq!(result[3][2], 4); // down
@@ -399,13 +399,13 @@ mod tests {
 
     #[test]
     fn stamp_x_basic() {
-        let grid = vec![
+        let grid = Grid::from(vec![
             vec![0, 0, 0, 0, 0],
             vec![0, 0, 0, 0, 0],
             vec![0, 0, 1, 0, 0],
             vec![0, 0, 0, 0, 0],
             vec![0, 0, 0, 0, 0],
-        ];
+        ]);
         let result = stamp_x(&grid, 1, 7, 1);
         assert_eq!(result[1][1], 7);
         assert_eq!(result[1][3], 7);
@@ -415,12 +415,12 @@ mod tests {
 
     #[test]
     fn complete_bbox_basic() {
-        let grid = vec![
+        let grid = Grid::from(vec![
             vec![0, 0, 0, 0],
             vec![0, 1, 0, 0],
             vec![0, 0, 0, 0],
             vec![0, 0, 0, 1],
-        ];
+        ]);
         let result = complete_bbox(&grid);
         // Object 1 at (1,1), object 2 at (3,3) — separate objects, fill each bbox
         assert_eq!(result[1][1], 1);
@@ -430,13 +430,13 @@ mod tests {
     #[test]
     fn draw_bbox_outlines() {
         // Two separate objects with clear bounding boxes
-        let grid = vec![
+        let grid = Grid::from(vec![
             vec![1, 1, 0, 0, 0],
             vec![1, 1, 0, 0, 0],
             vec![0, 0, 0, 0, 0],
             vec![0, 0, 0, 2, 2],
             vec![0, 0, 0, 2, 2],
-        ];
+        ]);
         let result = draw_bboxes(&grid, 5);
         // Object 1 bbox: (0,0)-(1,1), Object 2 bbox: (3,3)-(4,4)
         assert_eq!(result[0][0], 5);
@@ -447,11 +447,11 @@ mod tests {
 
     #[test]
     fn object_solver_finds_bbox() {
-        let input = vec![
+        let input = Grid::from(vec![
             vec![0, 0, 0],
             vec![0, 3, 0],
             vec![0, 0, 3],
-        ];
+        ]);
         let output = complete_bbox(&input);
         let examples = vec![(input, output)];
         let sol = try_object_solve(&examples);
@@ -0,0 +1,78 @@
+// Order-preserving parallel map for the search expansions.
+//
+// Workers owns a rayon pool of `threads` workers with the "parallel"
+// feature, built once when the search is configured; without the feature,
+// or with one thread, `map` is a plain sequential map. Results come back in
+// item order, so a search that merges them in order finds the same program
+// whatever the thread count:
+//
+//   let dag = SearchDag::new(20_000).with_threads(available_threads());
+//
+// Workers only read shared state and return what they would add; the
+// caller deduplicates and inserts at the sync point after each map.
+
+#[cfg(feature = "parallel")]
+use std::sync::Arc;
+
+#[derive(Debug, Clone, Default)]
+pub struct Workers {
+    #[cfg(feature = "parallel")]
+    pool: Option<Arc<rayon::ThreadPool>>,
+}
+
+impl Workers {
+    // A pool of `threads` workers; sequential for one thread, or when the
+    // pool cannot be built
+    pub fn new(threads: usize) -> Self {
+        #[cfg(feature = "parallel")]
+        if threads > 1 {
+            let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().ok().map(Arc::new);
+            return Self { pool };
+        }
+        let _ = threads;
+        Self::default()
+    }
+
+    pub fn threads(&self) -> usize {
+        #[cfg(feature = "parallel")]
+        if let Some(pool) = &self.pool {
+            return pool.current_num_threads();
+        }
+        1
+    }
+
+    pub fn map<T, R, F>(&self, items: &[T], f: F) -> Vec<R>
+    where
+        T: Sync,
+        R: Send,
+        F: Fn(&T) -> R + Sync + Send,
+    {
+        #[cfg(feature = "parallel")]
+        if let (Some(pool), true) = (&self.pool, items.len() > 1) {
+            use rayon::prelude::*;
+            return pool.install(|| items.par_iter().map(&f).collect());
+        }
+        items.iter().map(f).collect()
+    }
+}
+
+// Cores available to this process, at least 1
+pub fn available_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_item_order() {
+        let items: Vec<u32> = (0..1000).collect();
+        let workers = Workers::new(4);
+        assert_eq!(workers.map(&items, |x| x * 2), items.iter().map(|x| x * 2).collect::<Vec<_>>());
+        // The same pool serves every call
+        assert_eq!(workers.map(&items, |x| x + 1)[999], 1000);
+        assert_eq!(Workers::new(1).threads(), 1);
+        assert!(available_threads() >= 1);
+    }
+}
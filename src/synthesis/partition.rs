@@ -62,13 +62,13 @@ pub fn split_at_h_separators(grid: &Grid, seps: &[usize]) -> Vec<Grid> {
     let mut start = 0;
     for &sep in seps {
         if sep > start {
-            let sub: Grid = grid[start..sep].to_vec();
+            let sub = grid.slice_rows(start..sep);
             if !sub.is_empty() { result.push(sub); }
         }
         start = sep + 1;
     }
     if start < grid.len() {
-        result.push(grid[start..].to_vec());
+        result.push(grid.slice_rows(start..grid.len()));
     }
     result
 }
@@ -143,7 +143,7 @@ pub fn partition_grid(grid: &Grid) -> Option<GridPartition> {
 // --- Sub-grid comparison operations ---
 
 pub fn xor_grids(a: &Grid, b: &Grid) -> Grid {
-    if a.is_empty() || b.is_empty() { return Vec::new(); }
+    if a.is_empty() || b.is_empty() { return Grid::default(); }
     let rows = a.len().min(b.len());
     let cols = a[0].len().min(b[0].len());
     (0..rows).map(|r| {
@@ -154,7 +154,7 @@ pub fn xor_grids(a: &Grid, b: &Grid) -> Grid {
 }
 
 pub fn and_grids(a: &Grid, b: &Grid) -> Grid {
-    if a.is_empty() || b.is_empty() { return Vec::new(); }
+    if a.is_empty() || b.is_empty() { return Grid::default(); }
     let rows = a.len().min(b.len());
     let cols = a[0].len().min(b[0].len());
     (0..rows).map(|r| {
@@ -165,7 +165,7 @@ pub fn and_grids(a: &Grid, b: &Grid) -> Grid {
 }
 
 pub fn or_grids(a: &Grid, b: &Grid) -> Grid {
-    if a.is_empty() || b.is_empty() { return Vec::new(); }
+    if a.is_empty() || b.is_empty() { return Grid::default(); }
     let rows = a.len().min(b.len());
     let cols = a[0].len().min(b[0].len());
     (0..rows).map(|r| {
@@ -176,7 +176,7 @@ pub fn or_grids(a: &Grid, b: &Grid) -> Grid {
 }
 
 pub fn diff_grids(a: &Grid, b: &Grid, mark_color: u8) -> Grid {
-    if a.is_empty() || b.is_empty() { return Vec::new(); }
+    if a.is_empty() || b.is_empty() { return Grid::default(); }
     let rows = a.len().min(b.len());
     let cols = a[0].len().min(b[0].len());
     (0..rows).map(|r| {
@@ -416,7 +416,7 @@ fn try_fold_compare(examples: &[(Grid, Grid)]) -> Option<PartitionSolution> {
         if mark == 0 { continue; }
 
         // Mode A: diff(a,b) = mark, same = 0
-        let mut test_ab = vec![vec![0u8; cols]; rows];
+        let mut test_ab = Grid::new(rows, cols, 0);
         for r in 0..rows {
             for c in 0..cols {
                 if a[r][c] != b[r][c] { test_ab[r][c] = mark; }
@@ -430,7 +430,7 @@ fn try_fold_compare(examples: &[(Grid, Grid)]) -> Option<PartitionSolution> {
                         if sa.len() == sb.len() && !sa.is_empty() && sa[0].len() == sb[0].len() {
                             let rr = sa.len();
                             let cc = sa[0].len();
-                            let mut t = vec![vec![0u8; cc]; rr];
+                            let mut t = Grid::new(rr, cc, 0);
                             for r in 0..rr { for c in 0..cc {
                                 if sa[r][c] != sb[r][c] { t[r][c] = mark; }
                             }}
@@ -449,7 +449,7 @@ fn try_fold_compare(examples: &[(Grid, Grid)]) -> Option<PartitionSolution> {
         }
 
         // Mode B: where both non-zero and equal → mark, else 0
-        let mut test_and = vec![vec![0u8; cols]; rows];
+        let mut test_and = Grid::new(rows, cols, 0);
         for r in 0..rows {
             for c in 0..cols {
                 if a[r][c] != 0 && b[r][c] != 0 { test_and[r][c] = mark; }
@@ -463,7 +463,7 @@ fn try_fold_compare(examples: &[(Grid, Grid)]) -> Option<PartitionSolution> {
                         if sa.len() == sb.len() && !sa.is_empty() && sa[0].len() == sb[0].len() {
                             let rr = sa.len();
                             let cc = sa[0].len();
-                            let mut t = vec![vec![0u8; cc]; rr];
+                            let mut t = Grid::new(rr, cc, 0);
                             for r in 0..rr { for c in 0..cc {
                                 if sa[r][c] != 0 && sb[r][c] != 0 { t[r][c] = mark; }
                             }}
@@ -601,7 +601,7 @@ impl PartitionSolution {
                     if a.len() == b.len() && !a.is_empty() && a[0].len() == b[0].len() {
                         let rows = a.len();
                         let cols = a[0].len();
-                        let mut t = vec![vec![0u8; cols]; rows];
+                        let mut t = Grid::new(rows, cols, 0);
                         for r in 0..rows { for c in 0..cols {
                             if a[r][c] != b[r][c] { t[r][c] = *mark; }
                         }}
@@ -616,7 +616,7 @@ impl PartitionSolution {
                     if a.len() == b.len() && !a.is_empty() && a[0].len() == b[0].len() {
                         let rows = a.len();
                         let cols = a[0].len();
-                        let mut t = vec![vec![0u8; cols]; rows];
+                        let mut t = Grid::new(rows, cols, 0);
                         for r in 0..rows { for c in 0..cols {
                             if a[r][c] != 0 && b[r][c] != 0 { t[r][c] = *mark; }
                         }}
@@ -647,88 +647,88 @@ mod tests {
 
     #[test]
     fn detect_h_separator() {
-        let grid = vec![
+        let grid = Grid::from(vec![
             vec![1, 2, 3],
             vec![5, 5, 5], // separator
             vec![4, 6, 7],
-        ];
+        ]);
         let seps = detect_h_separators(&grid);
         assert_eq!(seps, vec![1]);
     }
 
     #[test]
     fn detect_v_separator() {
-        let grid = vec![
+        let grid = Grid::from(vec![
             vec![1, 5, 3],
             vec![2, 5, 4],
             vec![6, 5, 7],
-        ];
+        ]);
         let seps = detect_v_separators(&grid);
         assert_eq!(seps, vec![1]);
     }
 
     #[test]
     fn split_h_basic() {
-        let grid = vec![
+        let grid = Grid::from(vec![
             vec![1, 2],
             vec![5, 5],
             vec![3, 4],
-        ];
+        ]);
         let seps = detect_h_separators(&grid);
         let parts = split_at_h_separators(&grid, &seps);
         assert_eq!(parts.len(), 2);
-        assert_eq!(parts[0], vec![vec![1, 2]]);
-        assert_eq!(parts[1], vec![vec![3, 4]]);
+        assert_eq!(parts[0], Grid::from(vec![vec![1, 2]]));
+        assert_eq!(parts[1], Grid::from(vec![vec![3, 4]]));
     }
 
     #[test]
     fn split_v_basic() {
-        let grid = vec![
+        let grid = Grid::from(vec![
             vec![1, 5, 3],
             vec![2, 5, 4],
-        ];
+        ]);
         let seps = detect_v_separators(&grid);
         let parts = split_at_v_separators(&grid, &seps);
         assert_eq!(parts.len(), 2);
-        assert_eq!(parts[0], vec![vec![1], vec![2]]);
-        assert_eq!(parts[1], vec![vec![3], vec![4]]);
+        assert_eq!(parts[0], Grid::from(vec![vec![1], vec![2]]));
+        assert_eq!(parts[1], Grid::from(vec![vec![3], vec![4]]));
     }
 
     #[test]
     fn xor_grids_basic() {
-        let a = vec![vec![1, 0], vec![0, 1]];
-        let b = vec![vec![0, 1], vec![1, 0]];
+        let a = Grid::from(vec![vec![1, 0], vec![0, 1]]);
+        let b = Grid::from(vec![vec![0, 1], vec![1, 0]]);
         let result = xor_grids(&a, &b);
-        assert_eq!(result, vec![vec![1, 1], vec![1, 1]]);
+        assert_eq!(result, Grid::from(vec![vec![1, 1], vec![1, 1]]));
     }
 
     #[test]
     fn and_grids_basic() {
-        let a = vec![vec![1, 0], vec![3, 1]];
-        let b = vec![vec![2, 1], vec![0, 1]];
+        let a = Grid::from(vec![vec![1, 0], vec![3, 1]]);
+        let b = Grid::from(vec![vec![2, 1], vec![0, 1]]);
         let result = and_grids(&a, &b);
-        assert_eq!(result, vec![vec![1, 0], vec![0, 1]]);
+        assert_eq!(result, Grid::from(vec![vec![1, 0], vec![0, 1]]));
     }
 
     #[test]
     fn diff_grids_basic() {
-        let a = vec![vec![1, 2], vec![3, 4]];
-        let b = vec![vec![1, 5], vec![3, 4]];
+        let a = Grid::from(vec![vec![1, 2], vec![3, 4]]);
+        let b = Grid::from(vec![vec![1, 5], vec![3, 4]]);
         let result = diff_grids(&a, &b, 7);
-        assert_eq!(result, vec![vec![0, 7], vec![0, 0]]);
+        assert_eq!(result, Grid::from(vec![vec![0, 7], vec![0, 0]]));
     }
 
     #[test]
     fn partition_select_subgrid() {
         // Grid split by separator, output = left half
-        let input = vec![
+        let input = Grid::from(vec![
             vec![1, 2, 5, 3, 4],
             vec![6, 7, 5, 8, 9],
-        ];
-        let output = vec![
+        ]);
+        let output = Grid::from(vec![
             vec![1, 2],
             vec![6, 7],
-        ];
+        ]);
         let examples = vec![(input, output)];
         let sol = try_partition_solve(&examples);
         assert!(sol.is_some());
@@ -737,14 +737,14 @@ mod tests {
 
     #[test]
     fn partition_xor() {
-        let input = vec![
+        let input = Grid::from(vec![
             vec![1, 0, 5, 0, 1],
             vec![0, 1, 5, 1, 0],
-        ];
-        let output = vec![
+        ]);
+        let output = Grid::from(vec![
             vec![1, 1],
             vec![1, 1],
-        ];
+        ]);
         let examples = vec![(input, output)];
         let sol = try_partition_solve(&examples);
         assert!(sol.is_some());
@@ -752,18 +752,18 @@ mod tests {
 
     #[test]
     fn partition_2d() {
-        let grid = vec![
+        let grid = Grid::from(vec![
             vec![1, 5, 2],
             vec![5, 5, 5],
             vec![3, 5, 4],
-        ];
+        ]);
         let h = detect_h_separators(&grid);
         let v = detect_v_separators(&grid);
         let subs = split_grid_2d(&grid, &h, &v);
         assert_eq!(subs.len(), 4);
-        assert_eq!(subs[0], vec![vec![1]]);
-        assert_eq!(subs[1], vec![vec![2]]);
-        assert_eq!(subs[2], vec![vec![3]]);
-        assert_eq!(subs[3], vec![vec![4]]);
+        assert_eq!(subs[0], Grid::from(vec![vec![1]]));
+        assert_eq!(subs[1], Grid::from(vec![vec![2]]));
+        assert_eq!(subs[2], Grid::from(vec![vec![3]]));
+        assert_eq!(subs[3], Grid::from(vec![vec![4]]));
     }
 }
@@ -62,7 +62,7 @@ pub fn tile_with_self(grid: &Grid) -> Grid {
     let cols = grid[0].len();
     let out_rows = rows * rows;
     let out_cols = cols * cols;
-    let mut result = vec![vec![0u8; out_cols]; out_rows];
+    let mut result = Grid::new(out_rows, out_cols, 0);
 
     for r in 0..rows {
         for c in 0..cols {
@@ -81,10 +81,10 @@ pub fn tile_with_self(grid: &Grid) -> Grid {
 
 /// Tile a grid n_r × n_c times.
 pub fn tile_grid(grid: &Grid, n_r: usize, n_c: usize) -> Grid {
-    if grid.is_empty() || n_r == 0 || n_c == 0 { return Vec::new(); }
+    if grid.is_empty() || n_r == 0 || n_c == 0 { return Grid::default(); }
     let rows = grid.len();
     let cols = grid[0].len();
-    let mut result = vec![vec![0u8; cols * n_c]; rows * n_r];
+    let mut result = Grid::new(rows * n_r, cols * n_c, 0);
     for tr in 0..n_r {
         for tc in 0..n_c {
             for r in 0..rows {
@@ -133,7 +133,7 @@ pub fn detect_subgrid(input: &Grid, output: &Grid) -> Option<(usize, usize, usiz
     let out_c = output[0].len();
 
     for r in 0..=input.len().saturating_sub(out_r) {
-        for c in 0..=input.get(0).map(|row| row.len()).unwrap_or(0).saturating_sub(out_c) {
+        for c in 0..=input.width().saturating_sub(out_c) {
             let sub = extract_subgrid(input, r, c, out_r, out_c);
             if sub == *output {
                 return Some((r, c, out_r, out_c));
@@ -146,10 +146,10 @@ pub fn detect_subgrid(input: &Grid, output: &Grid) -> Option<(usize, usize, usiz
 /// Deduplicate consecutive identical rows.
 pub fn dedup_rows(grid: &Grid) -> Grid {
     if grid.is_empty() { return grid.clone(); }
-    let mut result = vec![grid[0].clone()];
-    for row in &grid[1..] {
-        if *row != *result.last().unwrap() {
-            result.push(row.clone());
+    let mut result = grid.slice_rows(0..1);
+    for row in grid.iter().skip(1) {
+        if *row != result[result.len() - 1] {
+            result.push_row(row);
         }
     }
     result
@@ -178,12 +178,12 @@ pub fn dedup_cols(grid: &Grid) -> Grid {
 /// Majority vote per cell across multiple grids.
 /// Useful for consensus when multiple strategies produce partial results.
 pub fn majority_vote(grids: &[Grid]) -> Grid {
-    if grids.is_empty() { return Vec::new(); }
+    if grids.is_empty() { return Grid::default(); }
     let rows = grids[0].len();
-    if rows == 0 { return Vec::new(); }
+    if rows == 0 { return Grid::default(); }
     let cols = grids[0][0].len();
 
-    let mut result = vec![vec![0u8; cols]; rows];
+    let mut result = Grid::new(rows, cols, 0);
     for r in 0..rows {
         for c in 0..cols {
             let mut counts = [0u32; 10];
@@ -355,7 +355,7 @@ pub fn repair_period(grid: &Grid, pr: usize, pc: usize) -> Grid {
     let cols = grid[0].len();
 
     // Build tile by majority vote across all period positions
-    let mut tile = vec![vec![0u8; pc]; pr];
+    let mut tile = Grid::new(pr, pc, 0);
     for tr in 0..pr {
         for tc in 0..pc {
             let mut counts = [0u32; 10];
@@ -380,7 +380,7 @@ pub fn repair_period(grid: &Grid, pr: usize, pc: usize) -> Grid {
     }
 
     // Apply tile to fill all cells
-    let mut result = vec![vec![0u8; cols]; rows];
+    let mut result = Grid::new(rows, cols, 0);
     for r in 0..rows {
         for c in 0..cols {
             result[r][c] = tile[r % pr][c % pc];
@@ -395,8 +395,8 @@ mod tests {
 
     #[test]
     fn color_map_simple() {
-        let input = vec![vec![1, 2], vec![3, 0]];
-        let output = vec![vec![4, 5], vec![6, 0]];
+        let input = Grid::from(vec![vec![1, 2], vec![3, 0]]);
+        let output = Grid::from(vec![vec![4, 5], vec![6, 0]]);
         let map = learn_color_map(&input, &output).unwrap();
         assert_eq!(map[&1], 4);
         assert_eq!(map[&2], 5);
@@ -407,14 +407,14 @@ mod tests {
 
     #[test]
     fn color_map_inconsistent() {
-        let input = vec![vec![1, 1]];
-        let output = vec![vec![2, 3]]; // 1→2 and 1→3 conflict
+        let input = Grid::from(vec![vec![1, 1]]);
+        let output = Grid::from(vec![vec![2, 3]]); // 1→2 and 1→3 conflict
         assert!(learn_color_map(&input, &output).is_none());
     }
 
     #[test]
     fn self_tiling() {
-        let input = vec![vec![0, 1], vec![1, 1]];
+        let input = Grid::from(vec![vec![0, 1], vec![1, 1]]);
         let output = tile_with_self(&input);
         assert_eq!(output.len(), 4);
         assert_eq!(output[0].len(), 4);
@@ -432,14 +432,14 @@ mod tests {
 
     #[test]
     fn detect_self_tiling_works() {
-        let input = vec![vec![0, 7, 7], vec![7, 7, 7], vec![0, 7, 7]];
+        let input = Grid::from(vec![vec![0, 7, 7], vec![7, 7, 7], vec![0, 7, 7]]);
         let output = tile_with_self(&input);
         assert!(detect_self_tiling(&input, &output));
     }
 
     #[test]
     fn tiling_2x3() {
-        let input = vec![vec![1, 2], vec![3, 4]];
+        let input = Grid::from(vec![vec![1, 2], vec![3, 4]]);
         let output = tile_grid(&input, 2, 3);
         assert_eq!(output.len(), 4);
         assert_eq!(output[0].len(), 6);
@@ -448,35 +448,35 @@ mod tests {
 
     #[test]
     fn subgrid_detection() {
-        let input = vec![
+        let input = Grid::from(vec![
             vec![1, 2, 3, 4],
             vec![5, 6, 7, 8],
             vec![9, 0, 1, 2],
-        ];
-        let output = vec![vec![6, 7], vec![0, 1]]; // rows 1-2, cols 1-2
+        ]);
+        let output = Grid::from(vec![vec![6, 7], vec![0, 1]]); // rows 1-2, cols 1-2
         let result = detect_subgrid(&input, &output);
         assert_eq!(result, Some((1, 1, 2, 2)));
     }
 
     #[test]
     fn dedup_rows_basic() {
-        let grid = vec![vec![1, 2], vec![1, 2], vec![3, 4], vec![3, 4]];
+        let grid = Grid::from(vec![vec![1, 2], vec![1, 2], vec![3, 4], vec![3, 4]]);
         let result = dedup_rows(&grid);
-        assert_eq!(result, vec![vec![1, 2], vec![3, 4]]);
+        assert_eq!(result, Grid::from(vec![vec![1, 2], vec![3, 4]]));
     }
 
     #[test]
     fn dedup_cols_basic() {
-        let grid = vec![vec![1, 1, 2, 2], vec![3, 3, 4, 4]];
+        let grid = Grid::from(vec![vec![1, 1, 2, 2], vec![3, 3, 4, 4]]);
         let result = dedup_cols(&grid);
-        assert_eq!(result, vec![vec![1, 2], vec![3, 4]]);
+        assert_eq!(result, Grid::from(vec![vec![1, 2], vec![3, 4]]));
     }
 
     #[test]
     fn smart_finds_color_map() {
         let examples = vec![
-            (vec![vec![1, 2]], vec![vec![3, 4]]),
-            (vec![vec![2, 1]], vec![vec![4, 3]]),
+            (Grid::from(vec![vec![1, 2]]), Grid::from(vec![vec![3, 4]])),
+            (Grid::from(vec![vec![2, 1]]), Grid::from(vec![vec![4, 3]])),
         ];
         let result = try_smart_transforms(&examples);
         assert!(result.is_some());
@@ -485,7 +485,7 @@ mod tests {
 
     #[test]
     fn smart_finds_self_tile() {
-        let input = vec![vec![0, 1], vec![1, 1]];
+        let input = Grid::from(vec![vec![0, 1], vec![1, 1]]);
         let output = tile_with_self(&input);
         let examples = vec![(input, output)];
         let result = try_smart_transforms(&examples);
@@ -495,10 +495,10 @@ mod tests {
 
     #[test]
     fn majority_vote_basic() {
-        let g1 = vec![vec![1, 2], vec![3, 4]];
-        let g2 = vec![vec![1, 5], vec![3, 4]];
-        let g3 = vec![vec![1, 2], vec![6, 4]];
+        let g1 = Grid::from(vec![vec![1, 2], vec![3, 4]]);
+        let g2 = Grid::from(vec![vec![1, 5], vec![3, 4]]);
+        let g3 = Grid::from(vec![vec![1, 2], vec![6, 4]]);
         let result = majority_vote(&[g1, g2, g3]);
-        assert_eq!(result, vec![vec![1, 2], vec![3, 4]]); // majority wins
+        assert_eq!(result, Grid::from(vec![vec![1, 2], vec![3, 4]])); // majority wins
     }
 }
@@ -0,0 +1,501 @@
+// Solver facade: runs the synthesis strategies as one cascade.
+//
+// Strategies run in the configured order, cheap learned transforms first
+// and search last, and the first program that reproduces every training
+// pair wins:
+//
+//   let solved = Solver::default().solve_task(&task)?;
+//   println!("{} (size {}, mdl {:.1})", solved.method, solved.program_size, solved.mdl);
+//   let outputs = solved.predict(&task);
+//
+// solve_with also takes an acceptance check, so a caller holding extra
+// pairs (the benchmark, with the test outputs) can reject a program that
+// fits the training pairs and keep cascading. The search strategies are
+// skipped once the time budget is spent, and the bidirectional and DAG
+// searches stop on what is left of it; an unsolved task still reports the
+// closest partial programs the DAG search found.
+//
+// For the two attempts ARC scoring allows, candidates runs every strategy
+// and ranks what they find by MDL plus the strategy's confidence, and
+// predict_top_k keeps the best distinct outputs per test input:
+//
+//   std::fs::write("submission.json", Solver::default().submission(&tasks))?;
+
+use std::time::{Duration, Instant};
+use crate::perception::grid::{submission_json, ArcTask};
+use super::dsl::{detect_background_all, Grid, Prim};
+use super::enumerate::synthesize;
+use super::evolve::evolve;
+use super::heuristics::{analyze_features, select_primitives};
+use super::bidir::BidirSearch;
+use super::abstraction::{Library, SearchDag};
+use super::grammar::Grammar;
+use super::compression::mdl_score;
+use super::normalize::normalize;
+use super::smart_prims::{try_smart_transforms, SmartTransform};
+use super::cellular::{try_ca_solve, CaSolution};
+use super::partition::{try_partition_solve, PartitionSolution};
+use super::object_ops::{try_object_solve, ObjectSolution};
+use super::connect::{try_connect_solve, ConnectSolution};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Strategy {
+    // Learned transforms (color map, tiling, subgrid, dedup)
+    Smart,
+    // Cellular automaton rule learning
+    Cellular,
+    // Grid partition + sub-grid operations
+    Partition,
+    // Connect markers with lines
+    Connect,
+    // Object-centric operations (stamps, bbox, markers)
+    Object,
+    // Heuristic-filtered primitives, single and 2-step compositions
+    Heuristic,
+    // Bidirectional search with inverse primitives
+    Bidir,
+    // DAG search over all pairs jointly
+    Dag,
+    // Brute-force enumeration
+    Enumerate,
+    // Genetic evolution
+    Evolve,
+}
+
+impl Strategy {
+    pub const ALL: [Strategy; 10] = [
+        Strategy::Smart, Strategy::Cellular, Strategy::Partition, Strategy::Connect, Strategy::Object,
+        Strategy::Heuristic, Strategy::Bidir, Strategy::Dag, Strategy::Enumerate, Strategy::Evolve,
+    ];
+
+    // Searches are bounded by the time budget; learned transforms are not
+    pub fn is_search(self) -> bool {
+        matches!(self, Strategy::Bidir | Strategy::Dag | Strategy::Enumerate | Strategy::Evolve)
+    }
+
+    // Prior that a program fitting the training pairs generalizes: learned
+    // transforms are specific, deep search and evolution tend to overfit
+    pub fn confidence(self) -> f64 {
+        match self {
+            Strategy::Smart | Strategy::Heuristic => 0.9,
+            Strategy::Partition | Strategy::Connect | Strategy::Object => 0.8,
+            Strategy::Cellular | Strategy::Bidir => 0.7,
+            Strategy::Dag | Strategy::Enumerate => 0.6,
+            Strategy::Evolve => 0.4,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SolverConfig {
+    pub strategies: Vec<Strategy>,
+    pub timeout_ms: u128,
+    // Program size for brute-force enumeration
+    pub max_size: usize,
+    pub search_depth: usize,
+    pub ca_steps: usize,
+    pub bidir_nodes: usize,
+    pub dag_nodes: usize,
+    // Beam width of the DAG search, breadth-first when None
+    pub dag_beam: Option<usize>,
+    // Workers for the bidirectional and DAG searches
+    pub threads: usize,
+    pub population: usize,
+    pub generations: usize,
+    // Learned entries, tried as one-step primitives ahead of the selected ones
+    pub library: Library,
+    // Step weights fitted on solved programs, ordering the DAG search
+    pub grammar: Option<Grammar>,
+}
+
+impl Default for SolverConfig {
+    fn default() -> Self {
+        Self {
+            strategies: Strategy::ALL.to_vec(),
+            timeout_ms: 3_000,
+            max_size: 2,
+            search_depth: 3,
+            ca_steps: 3,
+            bidir_nodes: 5_000,
+            dag_nodes: 20_000,
+            dag_beam: None,
+            threads: 1,
+            population: 30,
+            generations: 50,
+            library: Library::new(),
+            grammar: None,
+        }
+    }
+}
+
+impl SolverConfig {
+    pub fn with_strategies(mut self, strategies: &[Strategy]) -> Self {
+        self.strategies = strategies.to_vec();
+        self
+    }
+
+    pub fn with_timeout_ms(mut self, timeout_ms: u128) -> Self {
+        self.timeout_ms = timeout_ms;
+        self
+    }
+
+    pub fn with_max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    pub fn with_search_depth(mut self, depth: usize) -> Self {
+        self.search_depth = depth;
+        self
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn with_dag_beam(mut self, width: usize) -> Self {
+        self.dag_beam = Some(width);
+        self
+    }
+
+    pub fn with_library(mut self, library: Library) -> Self {
+        self.library = library;
+        self
+    }
+
+    pub fn with_grammar(mut self, grammar: Grammar) -> Self {
+        self.grammar = Some(grammar);
+        self
+    }
+}
+
+// Winning program of any strategy
+#[derive(Debug)]
+pub enum Program {
+    Smart(SmartTransform),
+    Cellular(CaSolution),
+    Partition(PartitionSolution),
+    Connect(ConnectSolution),
+    Object(ObjectSolution),
+    Prim(Prim),
+}
+
+impl Program {
+    pub fn apply(&self, grid: &Grid) -> Grid {
+        match self {
+            Program::Smart(t) => t.apply(grid),
+            Program::Cellular(ca) => ca.apply(grid),
+            Program::Partition(p) => p.apply(grid),
+            Program::Connect(c) => c.apply(grid),
+            Program::Object(o) => o.apply(grid),
+            Program::Prim(p) => p.apply(grid),
+        }
+    }
+
+    pub fn as_prim(&self) -> Option<&Prim> {
+        match self {
+            Program::Prim(p) => Some(p),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Solved {
+    pub program: Program,
+    pub strategy: Strategy,
+    // Strategy and variant, e.g. smart_tile or bidir_2f_1b
+    pub method: String,
+    pub program_size: usize,
+    // Candidates checked up to and including the winner
+    pub checked: usize,
+    pub mdl: f64,
+    pub elapsed_ms: u64,
+}
+
+impl Solved {
+    // MDL plus the strategy's confidence in bits; lower ranks first
+    pub fn rank(&self) -> f64 {
+        self.mdl - self.strategy.confidence().log2()
+    }
+
+    // Output for each test input of the task
+    pub fn predict(&self, task: &ArcTask) -> Vec<Grid> {
+        task.test.iter().map(|ex| self.program.apply(&ex.input)).collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Unsolved {
+    pub checked: usize,
+    pub elapsed_ms: u64,
+    // Closest programs of the DAG search, with their mean cell similarity
+    // over the pairs, best first
+    pub partial: Vec<(Prim, f64)>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Solver {
+    config: SolverConfig,
+}
+
+impl Solver {
+    pub fn new(config: SolverConfig) -> Self {
+        Self { config }
+    }
+
+    pub fn config(&self) -> &SolverConfig {
+        &self.config
+    }
+
+    pub fn solve_task(&self, task: &ArcTask) -> Option<Solved> {
+        self.solve(&task.train_pairs())
+    }
+
+    pub fn solve(&self, examples: &[(Grid, Grid)]) -> Option<Solved> {
+        self.solve_with(examples, |_| true).ok()
+    }
+
+    // First program that fits every pair and passes `accept`
+    pub fn solve_with<F: Fn(&Program) -> bool>(&self, examples: &[(Grid, Grid)], accept: F) -> Result<Solved, Unsolved> {
+        let mut winner = None;
+        let unsolved = self.run(examples, accept, |solved| {
+            winner = Some(solved);
+            true
+        });
+        winner.ok_or(unsolved)
+    }
+
+    // Every program the strategies find for the pairs, best ranked first.
+    // Each strategy stops at its first program, except the heuristic one,
+    // which reports every fitting primitive.
+    pub fn candidates(&self, examples: &[(Grid, Grid)]) -> Vec<Solved> {
+        let mut found = Vec::new();
+        self.run(examples, |_| true, |solved| {
+            found.push(solved);
+            false
+        });
+        found.sort_by(|a, b| a.rank().total_cmp(&b.rank()));
+        found
+    }
+
+    // Up to k distinct outputs per test input of the task, best first
+    pub fn predict_top_k(&self, task: &ArcTask, k: usize) -> Vec<Vec<Grid>> {
+        let candidates = self.candidates(&task.train_pairs());
+        task.test.iter().map(|ex| {
+            let mut outputs: Vec<Grid> = Vec::with_capacity(k);
+            for candidate in &candidates {
+                if outputs.len() == k {
+                    break;
+                }
+                let output = candidate.program.apply(&ex.input);
+                if !output.is_empty() && !outputs.contains(&output) {
+                    outputs.push(output);
+                }
+            }
+            outputs
+        }).collect()
+    }
+
+    // Top-2 predictions for every task, in submission format
+    pub fn submission(&self, tasks: &[ArcTask]) -> String {
+        let predictions: Vec<(String, Vec<Vec<Grid>>)> = tasks.iter()
+            .map(|task| (task.id.clone(), self.predict_top_k(task, 2)))
+            .collect();
+        submission_json(&predictions)
+    }
+
+    // Runs the strategies in order, handing each accepted program to `emit`
+    // until it returns true
+    fn run<F, E>(&self, examples: &[(Grid, Grid)], accept: F, mut emit: E) -> Unsolved
+    where
+        F: Fn(&Program) -> bool,
+        E: FnMut(Solved) -> bool,
+    {
+        let start = Instant::now();
+        let config = &self.config;
+        let timed_out = || start.elapsed().as_millis() > config.timeout_ms;
+        let elapsed_ms = || start.elapsed().as_millis() as u64;
+        if examples.is_empty() {
+            return Unsolved { checked: 0, elapsed_ms: elapsed_ms(), partial: Vec::new() };
+        }
+        let mut prims: Option<Vec<Prim>> = None;
+        let mut checked = 0;
+        let mut partial = Vec::new();
+        // What is left of the time budget, for the searches to stop on
+        let remaining = || Duration::from_millis(config.timeout_ms.saturating_sub(start.elapsed().as_millis()) as u64);
+
+        for &strategy in &config.strategies {
+            if strategy.is_search() && timed_out() {
+                break;
+            }
+            if matches!(strategy, Strategy::Heuristic | Strategy::Bidir | Strategy::Dag) && prims.is_none() {
+                prims = Some(config.library.with_primitives(&select_primitives(&analyze_features(examples))));
+            }
+            let prims = prims.as_deref().unwrap_or_default();
+            let found = match strategy {
+                Strategy::Smart => try_smart_transforms(examples)
+                    .map(|t| (format!("smart_{}", t.name()), 1, 1, 2.0, Program::Smart(t))),
+                Strategy::Cellular => try_ca_solve(examples, config.ca_steps)
+                    .map(|ca| (format!("cellular_{}steps", ca.steps), 1, 1, 3.0, Program::Cellular(ca))),
+                Strategy::Partition => try_partition_solve(examples)
+                    .map(|p| (format!("partition_{}", p.method), 2, 1, 4.0, Program::Partition(p))),
+                Strategy::Connect => try_connect_solve(examples)
+                    .map(|c| (format!("connect_{}", c.name()), 2, 1, 4.0, Program::Connect(c))),
+                Strategy::Object => try_object_solve(examples)
+                    .map(|o| (format!("object_{}", o.name()), 2, 1, 4.0, Program::Object(o))),
+                Strategy::Heuristic => {
+                    checked += prims.len();
+                    let accepts = |p: &Prim| matches_all(p, examples) && accept(&Program::Prim(p.clone()));
+                    let mut found: Vec<(&str, Prim, usize)> = prims.iter().filter(|p| accepts(p))
+                        .map(|p| ("heuristic_single", p.clone(), checked))
+                        .collect();
+                    // Tasks drawn on a non-zero background: the same
+                    // primitives, treating that color as empty space
+                    let bg = detect_background_all(examples.iter().map(|(input, _)| input));
+                    if found.is_empty() && bg != 0 {
+                        checked += prims.len();
+                        found.extend(prims.iter()
+                            .map(|p| Prim::WithBackground(bg, Box::new(p.clone())))
+                            .filter(|p| accepts(p))
+                            .map(|p| ("heuristic_background", p, checked)));
+                    }
+                    if found.is_empty() {
+                        'compose: for a in prims {
+                            for b in prims {
+                                checked += 1;
+                                let composed = Prim::Compose(Box::new(a.clone()), Box::new(b.clone()));
+                                if accepts(&composed) {
+                                    found.push(("heuristic_compose2", composed, checked));
+                                    break 'compose;
+                                }
+                                if timed_out() { break 'compose; }
+                            }
+                        }
+                    }
+                    // Already checked against `accept`, and counted in `checked`
+                    for (method, program, checked) in found {
+                        let mdl = mdl_score(&program, examples);
+                        let solved = Solved { program_size: program.size(), program: Program::Prim(program), strategy, method: method.to_string(), checked, mdl, elapsed_ms: elapsed_ms() };
+                        if emit(solved) {
+                            return Unsolved { checked, elapsed_ms: elapsed_ms(), partial };
+                        }
+                    }
+                    None
+                }
+                Strategy::Bidir => BidirSearch::new(config.bidir_nodes)
+                    .with_threads(config.threads)
+                    .with_time_budget(remaining())
+                    .search_all(examples, prims, config.search_depth)
+                    .map(|r| (format!("bidir_{}f_{}b", r.forward_depth, r.backward_depth), r.program, r.nodes_explored))
+                    .and_then(|found| prim_found(found, examples)),
+                Strategy::Dag => {
+                    let mut dag = SearchDag::new(config.dag_nodes)
+                        .with_threads(config.threads)
+                        .with_time_budget(remaining());
+                    if let Some(width) = config.dag_beam {
+                        dag = dag.with_beam(width);
+                    }
+                    if let Some(grammar) = &config.grammar {
+                        dag = dag.with_grammar(grammar.clone());
+                    }
+                    let mut scored = dag.search_all_scored(examples, prims, config.search_depth);
+                    let exact = scored.first().is_some_and(|(_, sim)| *sim >= 1.0);
+                    if !exact {
+                        partial = std::mem::take(&mut scored);
+                    }
+                    scored.into_iter().next()
+                        .map(|(p, _)| p)
+                        .filter(|p| matches_all(p, examples))
+                        .map(|p| ("dag_search".to_string(), p, dag.nodes_explored()))
+                        .and_then(|found| prim_found(found, examples))
+                }
+                Strategy::Enumerate => synthesize(examples, config.max_size)
+                    .map(|r| ("enumerate".to_string(), r.program, r.checked))
+                    .and_then(|found| prim_found(found, examples)),
+                Strategy::Evolve => evolve(examples, config.population, config.generations)
+                    .filter(|ind| matches_all(&ind.program, examples))
+                    .map(|ind| ("evolution".to_string(), ind.program, config.population * config.generations))
+                    .and_then(|found| prim_found(found, examples)),
+            };
+            if let Some((method, program_size, extra, mdl, program)) = found {
+                if accept(&program) {
+                    let solved = Solved { program, strategy, method, program_size, checked: checked + extra, mdl, elapsed_ms: elapsed_ms() };
+                    if emit(solved) {
+                        return Unsolved { checked: checked + extra, elapsed_ms: elapsed_ms(), partial };
+                    }
+                }
+            }
+        }
+        Unsolved { checked, elapsed_ms: elapsed_ms(), partial }
+    }
+}
+
+type Found = (String, usize, usize, f64, Program);
+
+// A searched program with its size and MDL score
+fn prim_found((method, program, checked): (String, Prim, usize), examples: &[(Grid, Grid)]) -> Option<Found> {
+    let program = normalize(&program);
+    let mdl = mdl_score(&program, examples);
+    Some((method, program.size(), checked, mdl, Program::Prim(program)))
+}
+
+fn matches_all(program: &Prim, examples: &[(Grid, Grid)]) -> bool {
+    examples.iter().all(|(input, expected)| program.apply(input) == *expected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::perception::grid::ArcExample;
+
+    #[test]
+    fn learned_transforms_win_before_search() {
+        let examples = vec![
+            (Grid::from(vec![vec![1, 2], vec![0, 1]]), Grid::from(vec![vec![3, 2], vec![0, 3]])),
+            (Grid::from(vec![vec![1, 1], vec![0, 2]]), Grid::from(vec![vec![3, 3], vec![0, 2]])),
+        ];
+        let solved = Solver::default().solve(&examples).unwrap();
+        assert_eq!(solved.strategy, Strategy::Smart);
+        assert_eq!(solved.method, "smart_color_map");
+        assert_eq!(solved.program.apply(&Grid::from(vec![vec![1, 0]])), Grid::from(vec![vec![3, 0]]));
+
+        // Every candidate rejected: the search strategies run out
+        let search = Solver::new(SolverConfig::default().with_strategies(&[Strategy::Heuristic, Strategy::Enumerate]));
+        let rejected = search.solve_with(&examples, |_| false).unwrap_err();
+        assert!(rejected.checked > 0);
+    }
+
+    #[test]
+    fn retries_primitives_on_the_task_background() {
+        let examples = vec![
+            (Grid::from(vec![vec![8, 8, 8], vec![8, 2, 3], vec![8, 8, 8]]), Grid::from(vec![vec![2, 3]])),
+            (Grid::from(vec![vec![8, 4, 8], vec![8, 4, 8], vec![8, 8, 8]]), Grid::from(vec![vec![4], vec![4]])),
+        ];
+        let solver = Solver::new(SolverConfig::default().with_strategies(&[Strategy::Heuristic]));
+        let solved = solver.solve(&examples).unwrap();
+        assert_eq!(solved.method, "heuristic_background");
+        assert_eq!(solved.program.apply(&Grid::from(vec![vec![1, 8], vec![8, 8]])), Grid::from(vec![vec![1]]));
+    }
+
+    #[test]
+    fn ranks_distinct_candidates() {
+        // Both a flip and a rotation explain the pair
+        let examples = vec![(Grid::from(vec![vec![1, 2], vec![2, 1]]), Grid::from(vec![vec![2, 1], vec![1, 2]]))];
+        let solver = Solver::new(SolverConfig::default().with_strategies(&[Strategy::Heuristic, Strategy::Evolve]));
+        let candidates = solver.candidates(&examples);
+        assert!(candidates.len() >= 2);
+        assert!(candidates.windows(2).all(|w| w[0].rank() <= w[1].rank()));
+
+        let task = ArcTask {
+            id: "t".into(),
+            train: vec![ArcExample { input: examples[0].0.clone(), output: examples[0].1.clone() }],
+            test: vec![ArcExample { input: Grid::from(vec![vec![1, 2], vec![3, 4]]), output: Grid::default() }],
+        };
+        let top = solver.predict_top_k(&task, 2);
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].len(), 2);
+        assert_ne!(top[0][0], top[0][1]);
+    }
+}